@@ -1,23 +1,57 @@
+use arc_swap::ArcSwap;
 use bytes::Bytes;
-use http_body_util::{combinators::BoxBody, BodyExt, Full};
-use hyper::{body::Incoming, Request, Response, StatusCode};
+use http_body_util::{combinators::BoxBody, BodyExt, Full, Limited};
+use hyper::{body::Incoming, Method, Request, Response, StatusCode};
 use serde::Serialize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::cluster::ClusterManager;
 use crate::config::{Config, MiddlewareConfig};
-use crate::health::HealthChecker;
+use crate::health::{BackendStats, HealthChecker, PassiveHealthChecker};
+use crate::middleware::builtin::BasicAuthMiddleware;
 use crate::router::Router;
+use crate::server::{ConfigReloader, ConnectionTracker};
 use crate::service::ServiceManager;
+use crate::tcp::TcpServiceManager;
+
+type AdminResponse = Response<BoxBody<Bytes, hyper::Error>>;
+
+/// Largest YAML document accepted by `PUT /api/config`
+const MAX_CONFIG_BODY: usize = 4 * 1024 * 1024;
 
 /// Admin API handler for runtime inspection and cluster management
 pub struct AdminApi {
     config: Arc<Config>,
     _router: Arc<Router>,
-    _services: Arc<ServiceManager>,
+    services: Arc<ServiceManager>,
     _health_checker: Option<Arc<HealthChecker>>,
+    passive_health: Option<Arc<PassiveHealthChecker>>,
     cluster_manager: Option<Arc<ClusterManager>>,
+    connections: Option<Arc<ConnectionTracker>>,
+    config_reloader: Option<Arc<ConfigReloader>>,
+    /// TCP services whose backend health is reported
+    tcp_services: Option<Arc<TcpServiceManager>>,
+    /// Guards endpoints that change running state (`api.basicAuth`)
+    auth: ArcSwap<AdminAuth>,
+}
+
+/// `api.basicAuth` as built from a particular config, rebuilt when a reload swaps it
+struct AdminAuth {
+    config: Arc<Config>,
+    middleware: Option<BasicAuthMiddleware>,
+}
+
+impl AdminAuth {
+    fn new(config: Arc<Config>) -> Self {
+        let middleware = config
+            .api
+            .as_ref()
+            .and_then(|api| api.basic_auth.clone())
+            .map(BasicAuthMiddleware::new);
+        Self { config, middleware }
+    }
 }
 
 impl AdminApi {
@@ -27,12 +61,18 @@ impl AdminApi {
         router: Arc<Router>,
         services: Arc<ServiceManager>,
     ) -> Self {
+        let auth = ArcSwap::from_pointee(AdminAuth::new(Arc::clone(&config)));
         Self {
             config,
             _router: router,
-            _services: services,
+            services,
             _health_checker: None,
+            passive_health: None,
             cluster_manager: None,
+            connections: None,
+            config_reloader: None,
+            tcp_services: None,
+            auth,
         }
     }
 
@@ -42,17 +82,116 @@ impl AdminApi {
         self
     }
 
+    /// Attach the passive health checker whose per-backend stats are reported.
+    pub fn with_passive_health(mut self, passive_health: Arc<PassiveHealthChecker>) -> Self {
+        self.passive_health = Some(passive_health);
+        self
+    }
+
     /// Add cluster manager for HA operations
     pub fn with_cluster_manager(mut self, manager: Arc<ClusterManager>) -> Self {
         self.cluster_manager = Some(manager);
         self
     }
 
+    /// Attach the listener's connection tracker so cluster drain and undrain
+    /// also stop and resume accepting connections on this node
+    pub fn with_connection_tracker(mut self, connections: Arc<ConnectionTracker>) -> Self {
+        self.connections = Some(connections);
+        self
+    }
+
+    /// Enable the config reload endpoints; inspection endpoints then report
+    /// the running config instead of the one the API was created with.
+    pub fn with_config_reloader(mut self, reloader: Arc<ConfigReloader>) -> Self {
+        self.config_reloader = Some(reloader);
+        self
+    }
+
+    /// Report the backend health of TCP services, as kept by their active health checks.
+    pub fn with_tcp_services(mut self, tcp_services: Arc<TcpServiceManager>) -> Self {
+        self.tcp_services = Some(tcp_services);
+        self
+    }
+
+    /// The config to report: the running one when a reloader is attached
+    fn config(&self) -> Arc<Config> {
+        match &self.config_reloader {
+            Some(reloader) => reloader.current(),
+            None => Arc::clone(&self.config),
+        }
+    }
+
+    /// The services to report health for, following reloads like [`Self::config`]
+    fn services(&self) -> Arc<ServiceManager> {
+        match &self.config_reloader {
+            Some(reloader) => reloader.state().services.load_full(),
+            None => Arc::clone(&self.services),
+        }
+    }
+
+    /// The passive checker fed by proxied traffic, if any
+    fn passive_health(&self) -> Option<Arc<PassiveHealthChecker>> {
+        self.passive_health.clone().or_else(|| {
+            self.config_reloader
+                .as_ref()
+                .map(|reloader| Arc::clone(&reloader.state().passive_health))
+        })
+    }
+
+    /// The listener's connection tracker, if any
+    fn connections(&self) -> Option<Arc<ConnectionTracker>> {
+        self.connections.clone().or_else(|| {
+            self.config_reloader
+                .as_ref()
+                .map(|reloader| Arc::clone(&reloader.state().connections))
+        })
+    }
+
+    /// Health of each server of a load-balanced service, in config order
+    fn backend_health(&self, name: &str) -> Vec<BackendHealth> {
+        let services = self.services();
+        let passive = self.passive_health();
+        let Some(service) = services.get_service(name) else {
+            return Vec::new();
+        };
+        let Some(lb) = &service.config.load_balancer else {
+            return Vec::new();
+        };
+
+        lb.servers
+            .iter()
+            .enumerate()
+            .map(|(idx, server)| {
+                let active = service.health_statuses.get(idx);
+                let healthy = service.is_server_healthy(idx);
+                BackendHealth {
+                    url: server.url.clone(),
+                    weight: server.weight,
+                    status: if healthy { "healthy" } else { "unhealthy" },
+                    healthy,
+                    consecutive_failures: active
+                        .map_or(0, |status| status.consecutive_failures.load(Ordering::Relaxed)),
+                    last_error: active.and_then(|status| status.last_error.read().clone()),
+                    passive: passive
+                        .as_ref()
+                        .and_then(|passive| passive.get_stats(&server.url))
+                        .map(PassiveStats::from),
+                }
+            })
+            .collect()
+    }
+
     /// Handle admin API request
     pub async fn handle(
         &self,
         req: Request<Incoming>,
     ) -> Response<BoxBody<Bytes, hyper::Error>> {
+        // Replacing the config consumes the request body
+        if req.method() == Method::PUT && req.uri().path() == "/api/config" {
+            return self.replace_config(req).await;
+        }
+
         let path = req.uri().path();
         let method = req.method();
 
@@ -71,16 +210,19 @@ impl AdminApi {
                 self.service_detail(name).await
             }
             ("GET", "/api/health") => self.health_status().await,
+            ("GET", "/api/tcp/services") => self.tcp_services(),
+            // Config endpoints
+            ("POST", "/api/config/reload") => self.reload_config(&req).await,
             // Cluster/HA endpoints
             ("GET", "/api/cluster") => self.cluster_status().await,
             ("GET", "/api/cluster/nodes") => self.cluster_nodes().await,
-            ("POST", "/api/cluster/drain") => self.start_drain().await,
-            ("POST", "/api/cluster/undrain") => self.stop_drain().await,
+            ("POST", "/api/cluster/drain") => self.start_drain(&req).await,
+            ("POST", "/api/cluster/undrain") => self.stop_drain(&req).await,
             ("GET", path) if path.starts_with("/api/cluster/nodes/") => {
                 let node_id = &path["/api/cluster/nodes/".len()..];
                 if node_id.ends_with("/drain") {
                     let node_id = node_id.trim_end_matches("/drain");
-                    self.drain_node(node_id).await
+                    self.drain_node(&req, node_id).await
                 } else {
                     self.node_detail(node_id).await
                 }
@@ -89,9 +231,10 @@ impl AdminApi {
                 let node_id = path
                     .trim_start_matches("/api/cluster/nodes/")
                     .trim_end_matches("/drain");
-                self.drain_node(node_id).await
+                self.drain_node(&req, node_id).await
             }
             ("GET", "/ping") => self.ping(),
+            ("GET", "/metrics") => self.prometheus_metrics(),
             ("GET", "/") | ("GET", "/dashboard") => self.dashboard(),
             _ => self.not_found(),
         }
@@ -108,12 +251,13 @@ impl AdminApi {
             entrypoints: usize,
         }
 
+        let config = self.config();
         let overview = Overview {
             version: env!("CARGO_PKG_VERSION"),
-            routers: self.config.routers().len(),
-            services: self.config.services().len(),
-            middlewares: self.config.middlewares().len(),
-            entrypoints: self.config.entry_points.len(),
+            routers: config.routers().len(),
+            services: config.services().len(),
+            middlewares: config.middlewares().len(),
+            entrypoints: config.entry_points.len(),
         };
 
         self.json_response(&overview)
@@ -128,7 +272,7 @@ impl AdminApi {
         }
 
         let entrypoints: Vec<Entrypoint> = self
-            .config
+            .config()
             .entry_points
             .iter()
             .map(|(name, ep)| Entrypoint {
@@ -153,7 +297,7 @@ impl AdminApi {
         }
 
         let routers: Vec<RouterInfo> = self
-            .config
+            .config()
             .routers()
             .iter()
             .map(|(name, r)| RouterInfo {
@@ -182,7 +326,7 @@ impl AdminApi {
             status: String,
         }
 
-        if let Some(router) = self.config.routers().get(name) {
+        if let Some(router) = self.config().routers().get(name) {
             let detail = RouterDetail {
                 name: name.to_string(),
                 rule: router.rule.clone(),
@@ -214,7 +358,7 @@ impl AdminApi {
         }
 
         let services: Vec<ServiceInfo> = self
-            .config
+            .config()
             .services()
             .iter()
             .map(|(name, s)| {
@@ -246,35 +390,15 @@ impl AdminApi {
         #[derive(Serialize)]
         struct ServiceDetail {
             name: String,
-            servers: Vec<ServerDetail>,
+            servers: Vec<BackendHealth>,
             load_balancer: Option<String>,
             status: String,
         }
 
-        #[derive(Serialize)]
-        struct ServerDetail {
-            url: String,
-            weight: Option<i32>,
-            healthy: bool,
-        }
-
-        if let Some(service) = self.config.services().get(name) {
-            let servers = if let Some(lb) = &service.load_balancer {
-                lb.servers
-                    .iter()
-                    .map(|server| ServerDetail {
-                        url: server.url.clone(),
-                        weight: Some(server.weight as i32),
-                        healthy: true, // TODO: get actual health status
-                    })
-                    .collect()
-            } else {
-                Vec::new()
-            };
-
+        if let Some(service) = self.config().services().get(name) {
             let detail = ServiceDetail {
                 name: name.to_string(),
-                servers,
+                servers: self.backend_health(name),
                 load_balancer: service.load_balancer.as_ref().map(|_| "roundRobin".to_string()),
                 status: "enabled".to_string(),
             };
@@ -294,7 +418,7 @@ impl AdminApi {
         }
 
         let middlewares: Vec<MiddlewareInfo> = self
-            .config
+            .config()
             .middlewares()
             .iter()
             .map(|(name, mw)| MiddlewareInfo {
@@ -364,10 +488,16 @@ impl AdminApi {
     }
 
     /// Start draining this node
-    async fn start_drain(&self) -> Response<BoxBody<Bytes, hyper::Error>> {
+    async fn start_drain(&self, req: &Request<Incoming>) -> Response<BoxBody<Bytes, hyper::Error>> {
+        if let Err(denied) = self.authorize_write(req).await {
+            return *denied;
+        }
         if let Some(cluster) = &self.cluster_manager {
             match cluster.start_drain().await {
                 Ok(()) => {
+                    if let Some(connections) = self.connections() {
+                        connections.start_drain();
+                    }
                     info!("Node drain started via API");
                     #[derive(Serialize)]
                     struct DrainResponse {
@@ -395,21 +525,51 @@ impl AdminApi {
     }
 
     /// Stop draining (re-enable this node)
-    async fn stop_drain(&self) -> Response<BoxBody<Bytes, hyper::Error>> {
-        // Note: In production, you'd want a way to cancel draining
-        // For now, this just returns a message
-        self.error_response(
-            StatusCode::NOT_IMPLEMENTED,
-            "Undrain not yet implemented. Restart the node to re-enable.",
-        )
+    async fn stop_drain(&self, req: &Request<Incoming>) -> Response<BoxBody<Bytes, hyper::Error>> {
+        if let Err(denied) = self.authorize_write(req).await {
+            return *denied;
+        }
+        if let Some(cluster) = &self.cluster_manager {
+            match cluster.stop_drain().await {
+                Ok(()) => {
+                    if let Some(connections) = self.connections() {
+                        connections.stop_drain();
+                    }
+                    info!("Node drain stopped via API");
+                    #[derive(Serialize)]
+                    struct DrainResponse {
+                        success: bool,
+                        message: &'static str,
+                        node_id: String,
+                    }
+                    self.json_response(&DrainResponse {
+                        success: true,
+                        message: "Drain stopped",
+                        node_id: cluster.node_id().to_string(),
+                    })
+                }
+                Err(e) => self.error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &format!("Failed to stop drain: {}", e),
+                ),
+            }
+        } else {
+            self.error_response(
+                StatusCode::BAD_REQUEST,
+                "Cluster mode not enabled",
+            )
+        }
     }
 
     /// Drain a specific node (remote drain)
-    async fn drain_node(&self, node_id: &str) -> Response<BoxBody<Bytes, hyper::Error>> {
+    async fn drain_node(&self, req: &Request<Incoming>, node_id: &str) -> Response<BoxBody<Bytes, hyper::Error>> {
         if let Some(cluster) = &self.cluster_manager {
             // Check if it's this node
             if cluster.node_id() == node_id {
-                return self.start_drain().await;
+                return self.start_drain(req).await;
+            }
+            if let Err(denied) = self.authorize_write(req).await {
+                return *denied;
             }
 
             // For remote nodes, we update their status in the store
@@ -442,6 +602,101 @@ impl AdminApi {
         }
     }
 
+    // =========================================================================
+    // Config Endpoints
+    // =========================================================================
+
+    /// Reload the config from its file on disk
+    async fn reload_config(&self, req: &Request<Incoming>) -> Response<BoxBody<Bytes, hyper::Error>> {
+        let reloader = match self.config_writer(req).await {
+            Ok(reloader) => reloader,
+            Err(denied) => return *denied,
+        };
+        let result = reloader.reload_from_disk();
+        self.reload_response(result)
+    }
+
+    /// Validate a YAML config from the request body and hot-swap it in
+    async fn replace_config(&self, req: Request<Incoming>) -> Response<BoxBody<Bytes, hyper::Error>> {
+        let reloader = match self.config_writer(&req).await {
+            Ok(reloader) => reloader,
+            Err(denied) => return *denied,
+        };
+
+        let body = match Limited::new(req.into_body(), MAX_CONFIG_BODY).collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(_) => {
+                return self.error_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    &format!("Config body must be at most {} bytes", MAX_CONFIG_BODY),
+                );
+            }
+        };
+        let Ok(yaml) = std::str::from_utf8(&body) else {
+            return self.error_response(StatusCode::BAD_REQUEST, "Config body must be UTF-8 YAML");
+        };
+
+        let result = reloader.apply_yaml(yaml);
+        self.reload_response(result)
+    }
+
+    /// Authorize a config change and return the reloader to apply it with
+    async fn config_writer(
+        &self,
+        req: &Request<Incoming>,
+    ) -> Result<&Arc<ConfigReloader>, Box<AdminResponse>> {
+        self.authorize_write(req).await?;
+        self.config_reloader
+            .as_ref()
+            .ok_or_else(|| Box::new(self.error_response(StatusCode::BAD_REQUEST, "Config reload not enabled")))
+    }
+
+    /// The admin credentials of the running config
+    fn auth(&self) -> Arc<AdminAuth> {
+        let config = self.config();
+        let auth = self.auth.load_full();
+        if Arc::ptr_eq(&auth.config, &config) {
+            return auth;
+        }
+        let auth = Arc::new(AdminAuth::new(config));
+        self.auth.store(Arc::clone(&auth));
+        auth
+    }
+
+    /// Require admin credentials for requests that change the node's state
+    async fn authorize_write(&self, req: &Request<Incoming>) -> Result<(), Box<AdminResponse>> {
+        let auth = self.auth();
+        match &auth.middleware {
+            None => Err(Box::new(self.error_response(
+                StatusCode::FORBIDDEN,
+                "Config and drain changes require api.basicAuth to be configured",
+            ))),
+            Some(middleware) if !middleware.authenticate(req).await => Err(Box::new(
+                middleware.unauthorized_response()
+                    .map(|()| Self::full_body(r#"{"error":"Unauthorized"}"#)),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn reload_response(&self, result: anyhow::Result<u64>) -> Response<BoxBody<Bytes, hyper::Error>> {
+        match result {
+            Ok(version) => {
+                info!("Config version {} applied via API", version);
+                #[derive(Serialize)]
+                struct ReloadResponse {
+                    success: bool,
+                    version: u64,
+                }
+                self.json_response(&ReloadResponse { success: true, version })
+            }
+            Err(e) => {
+                warn!("Config change rejected via API: {:#}", e);
+                self.error_response(StatusCode::BAD_REQUEST, &format!("{:#}", e))
+            }
+        }
+    }
+
     // =========================================================================
     // Health Endpoints
     // =========================================================================
@@ -461,45 +716,107 @@ impl AdminApi {
             backends: Vec<BackendHealth>,
         }
 
-        #[derive(Serialize)]
-        struct BackendHealth {
-            url: String,
-            status: String,
-        }
-
         let services: Vec<ServiceHealth> = self
-            .config
+            .config()
             .services()
-            .iter()
-            .map(|(name, s)| {
-                let backends = if let Some(lb) = &s.load_balancer {
-                    lb.servers
-                        .iter()
-                        .map(|server| BackendHealth {
-                            url: server.url.clone(),
-                            status: "healthy".to_string(), // TODO: actual health
-                        })
-                        .collect()
+            .keys()
+            .map(|name| {
+                let backends = self.backend_health(name);
+                let healthy = backends.iter().filter(|b| b.healthy).count();
+                let status = if healthy == backends.len() {
+                    "healthy"
+                } else if healthy == 0 {
+                    "unhealthy"
                 } else {
-                    Vec::new()
+                    "degraded"
                 };
 
                 ServiceHealth {
                     name: name.clone(),
-                    status: "healthy".to_string(),
+                    status: status.to_string(),
                     backends,
                 }
             })
             .collect();
 
+        let status = if services.iter().all(|s| s.status == "healthy") {
+            "healthy"
+        } else {
+            "degraded"
+        };
         let health = HealthStatus {
-            status: "healthy".to_string(),
+            status: status.to_string(),
             services,
         };
 
         self.json_response(&health)
     }
 
+    /// TCP services with the health of each backend
+    fn tcp_services(&self) -> Response<BoxBody<Bytes, hyper::Error>> {
+        #[derive(Serialize)]
+        struct TcpServiceHealth {
+            name: String,
+            status: &'static str,
+            servers: Vec<TcpBackendHealth>,
+        }
+
+        #[derive(Serialize)]
+        struct TcpBackendHealth {
+            address: String,
+            weight: u32,
+            tls: bool,
+            status: &'static str,
+            healthy: bool,
+            consecutive_failures: u32,
+            last_error: Option<String>,
+        }
+
+        let Some(tcp_services) = &self.tcp_services else {
+            return self.json_response(&Vec::<TcpServiceHealth>::new());
+        };
+
+        let services: Vec<TcpServiceHealth> = tcp_services
+            .services()
+            .values()
+            .map(|service| {
+                let servers: Vec<TcpBackendHealth> = service
+                    .servers()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, server)| {
+                        let status = service.health_status(idx);
+                        let healthy = status.is_none_or(|status| status.is_healthy());
+                        TcpBackendHealth {
+                            address: server.address.clone(),
+                            weight: server.weight,
+                            tls: server.use_tls,
+                            status: if healthy { "healthy" } else { "unhealthy" },
+                            healthy,
+                            consecutive_failures: status
+                                .map_or(0, |status| status.consecutive_failures.load(Ordering::Relaxed)),
+                            last_error: status.and_then(|status| status.last_error.read().clone()),
+                        }
+                    })
+                    .collect();
+                let healthy = servers.iter().filter(|s| s.healthy).count();
+                TcpServiceHealth {
+                    name: service.name().to_string(),
+                    status: if healthy == servers.len() {
+                        "healthy"
+                    } else if healthy == 0 {
+                        "unhealthy"
+                    } else {
+                        "degraded"
+                    },
+                    servers,
+                }
+            })
+            .collect();
+
+        self.json_response(&services)
+    }
+
     /// Simple ping endpoint
     fn ping(&self) -> Response<BoxBody<Bytes, hyper::Error>> {
         Response::builder()
@@ -509,6 +826,21 @@ impl AdminApi {
             .unwrap()
     }
 
+    /// Prometheus scrape, for networks where the standalone metrics port is unreachable
+    fn prometheus_metrics(&self) -> Response<BoxBody<Bytes, hyper::Error>> {
+        match crate::metrics::get_prometheus_handle() {
+            Ok(handle) => Response::builder()
+                .status(StatusCode::OK)
+                .header("content-type", "text/plain; version=0.0.4; charset=utf-8")
+                .body(Self::full_body(handle.render()))
+                .unwrap(),
+            Err(e) => self.error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                &format!("Prometheus metrics unavailable: {}", e),
+            ),
+        }
+    }
+
     /// Dashboard HTML
     fn dashboard(&self) -> Response<BoxBody<Bytes, hyper::Error>> {
         let html = r#"<!DOCTYPE html>
@@ -623,9 +955,572 @@ impl AdminApi {
     }
 }
 
+/// Health of a single backend server
+#[derive(Serialize)]
+struct BackendHealth {
+    url: String,
+    weight: u32,
+    status: &'static str,
+    /// Usable by the balancer: passes active checks and is not ejected passively
+    healthy: bool,
+    /// Failed active health checks in a row
+    consecutive_failures: u32,
+    /// Error from the most recent failed active health check
+    last_error: Option<String>,
+    /// Stats from proxied traffic, once the backend has served a request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    passive: Option<PassiveStats>,
+}
+
+/// Passive health stats of a backend
+#[derive(Serialize)]
+struct PassiveStats {
+    healthy: bool,
+    total_requests: u64,
+    total_failures: u64,
+    avg_response_time_us: u64,
+    consecutive_failures: u32,
+    recent_failures: usize,
+}
+
+impl From<BackendStats> for PassiveStats {
+    fn from(stats: BackendStats) -> Self {
+        Self {
+            healthy: stats.healthy,
+            total_requests: stats.total_requests,
+            total_failures: stats.total_failures,
+            avg_response_time_us: stats.avg_response_time_us,
+            consecutive_failures: stats.consecutive_failures,
+            recent_failures: stats.recent_failure_count,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::server::SharedState;
+    use arc_swap::ArcSwap;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use crate::config::{
+        ApiConfig, BasicAuthConfig, ClusterConfig, EntryPoint, HttpConfig, LoadBalancerService, Server,
+        Service, TcpConfig, TcpLoadBalancer, TcpServer, TcpService,
+    };
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use tokio::net::TcpListener;
+
+    fn server(url: &str) -> Server {
+        Server {
+            url: url.to_string(),
+            weight: 1,
+            preserve_path: false,
+            parsed_uri: None,
+            url_arc: None,
+        }
+    }
+
+    fn service(servers: Vec<Server>) -> Service {
+        Service {
+            load_balancer: Some(LoadBalancerService {
+                servers,
+                pass_host_header: true,
+                sticky: None,
+                health_check: None,
+                servers_transport: None,
+                response_forwarding: None,
+                web_socket: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn router(rule: &str) -> crate::config::Router {
+        crate::config::Router {
+            entry_points: vec![],
+            rule: rule.to_string(),
+            rule_syntax: None,
+            service: "api".to_string(),
+            middlewares: vec![],
+            priority: 0,
+            tls: None,
+            observability: None,
+        }
+    }
+
+    /// One router and service, with the API guarded by admin:secret
+    fn test_config() -> Config {
+        let web = EntryPoint {
+            address: ":0".to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        };
+        let api = ApiConfig {
+            basic_auth: Some(BasicAuthConfig {
+                users: vec!["admin:secret".to_string()],
+                users_file: None,
+                realm: None,
+                header_field: None,
+                remove_header: false,
+            }),
+            ..Default::default()
+        };
+        Config {
+            entry_points: HashMap::from([("web".to_string(), web)]),
+            api: Some(api),
+            http: Some(HttpConfig {
+                routers: HashMap::from([("api".to_string(), router("PathPrefix(`/`)"))]),
+                services: HashMap::from([("api".to_string(), service(vec![server("http://127.0.0.1:9")]))]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// `config` with a second router added
+    fn with_extra_router(mut config: Config) -> Config {
+        let http = config.http.as_mut().unwrap();
+        http.routers.insert("extra".to_string(), router("PathPrefix(`/extra`)"));
+        config
+    }
+
+    /// `config` as the YAML document the config endpoints take
+    fn to_yaml(config: &Config) -> String {
+        serde_yml::to_string(config).unwrap()
+    }
+
+    fn admin(config: Config, config_path: PathBuf) -> (AdminApi, Arc<ConfigReloader>, Arc<SharedState>) {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = Arc::new(config);
+        let state = Arc::new(SharedState::new(&config));
+        let reloader = Arc::new(ConfigReloader::new(
+            config_path,
+            Arc::new(ArcSwap::new(Arc::clone(&config))),
+            Arc::clone(&state),
+        ));
+        let api = AdminApi::new(
+            Arc::clone(&config),
+            Arc::new(Router::from_config(&config)),
+            Arc::new(ServiceManager::new(&config)),
+        )
+        .with_config_reloader(Arc::clone(&reloader));
+        (api, reloader, state)
+    }
+
+    /// Serve `api` on an ephemeral port and return its base URL
+    async fn serve(api: AdminApi) -> String {
+        let api = Arc::new(api);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let api = Arc::clone(&api);
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| {
+                        let api = Arc::clone(&api);
+                        async move { Ok::<_, hyper::Error>(api.handle(req).await) }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    async fn router_names(base: &str) -> Vec<String> {
+        let routers: Vec<serde_json::Value> = reqwest::get(format!("{}/api/routers", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let mut names: Vec<String> = routers.iter().map(|r| r["name"].as_str().unwrap().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    fn temp_config_path() -> PathBuf {
+        std::env::temp_dir().join(format!("trafficcop-admin-{}.yaml", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_put_config_applies_valid_config() {
+        let (api, reloader, state) = admin(test_config(), temp_config_path());
+        let base = serve(api).await;
+
+        let response = reqwest::Client::new()
+            .put(format!("{}/api/config", base))
+            .basic_auth("admin", Some("secret"))
+            .body(to_yaml(&with_extra_router(test_config())))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["success"], true);
+        assert_eq!(body["version"], 2);
+
+        assert_eq!(reloader.version(), 2);
+        assert!(reloader.current().routers().contains_key("extra"));
+        assert!(state.services.load().get_service("api").is_some());
+        assert_eq!(router_names(&base).await, ["api", "extra"]);
+    }
+
+    #[tokio::test]
+    async fn test_put_config_rejects_invalid_config() {
+        let (api, reloader, state) = admin(test_config(), temp_config_path());
+        let base = serve(api).await;
+
+        // Valid YAML, but the extra router's service has no servers
+        let mut invalid = with_extra_router(test_config());
+        let http = invalid.http.as_mut().unwrap();
+        http.services.insert("empty".to_string(), service(vec![]));
+        let response = reqwest::Client::new()
+            .put(format!("{}/api/config", base))
+            .basic_auth("admin", Some("secret"))
+            .body(to_yaml(&invalid))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"].as_str().unwrap().contains("must have at least one server"));
+
+        // Unparseable YAML is rejected the same way
+        let response = reqwest::Client::new()
+            .put(format!("{}/api/config", base))
+            .basic_auth("admin", Some("secret"))
+            .body("entryPoints: [")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Nothing about the running config changed
+        assert_eq!(reloader.version(), 1);
+        assert!(!reloader.current().services().contains_key("empty"));
+        assert!(!reloader.current().routers().contains_key("extra"));
+        assert!(state.services.load().get_service("empty").is_none());
+        assert_eq!(router_names(&base).await, ["api"]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_from_disk() {
+        let path = temp_config_path();
+        std::fs::write(&path, to_yaml(&test_config())).unwrap();
+        let (api, reloader, _state) = admin(test_config(), path.clone());
+        let base = serve(api).await;
+        let reload = || {
+            reqwest::Client::new()
+                .post(format!("{}/api/config/reload", base))
+                .basic_auth("admin", Some("secret"))
+                .send()
+        };
+
+        std::fs::write(&path, to_yaml(&with_extra_router(test_config()))).unwrap();
+        let response = reload().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.json::<serde_json::Value>().await.unwrap()["version"], 2);
+        assert_eq!(router_names(&base).await, ["api", "extra"]);
+
+        // A broken file leaves version 2 running
+        std::fs::write(&path, "entryPoints: {}\n").unwrap();
+        let response = reload().await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(reloader.version(), 2);
+        assert_eq!(router_names(&base).await, ["api", "extra"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_config_endpoints_require_admin_auth() {
+        let (api, reloader, _state) = admin(test_config(), temp_config_path());
+        let base = serve(api).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("{}/api/config", base))
+            .body(to_yaml(&test_config()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert!(response.headers().contains_key("www-authenticate"));
+
+        let response = client
+            .post(format!("{}/api/config/reload", base))
+            .basic_auth("admin", Some("wrong"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(reloader.version(), 1);
+
+        // Without api.basicAuth the endpoints are refused outright
+        let unguarded = Config { api: None, ..test_config() };
+        let (api, reloader, _state) = admin(unguarded.clone(), temp_config_path());
+        let base = serve(api).await;
+        let response = client
+            .put(format!("{}/api/config", base))
+            .basic_auth("admin", Some("secret"))
+            .body(to_yaml(&unguarded))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        assert_eq!(reloader.version(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replaced_config_rotates_admin_credentials() {
+        let (api, reloader, _state) = admin(test_config(), temp_config_path());
+        let base = serve(api).await;
+        let client = reqwest::Client::new();
+        let put = |user: &'static str, password: &'static str, config: Config| {
+            client
+                .put(format!("{}/api/config", base))
+                .basic_auth(user, Some(password))
+                .body(to_yaml(&config))
+                .send()
+        };
+
+        let mut rotated = test_config();
+        rotated.api.as_mut().unwrap().basic_auth.as_mut().unwrap().users = vec!["ops:rotated".to_string()];
+        assert_eq!(put("admin", "secret", rotated.clone()).await.unwrap().status(), 200);
+
+        // Only the credentials of the running config are accepted
+        assert_eq!(put("admin", "secret", rotated.clone()).await.unwrap().status(), 401);
+        assert_eq!(put("ops", "rotated", rotated).await.unwrap().status(), 200);
+        assert_eq!(reloader.version(), 3);
+
+        // Removing api.basicAuth closes the write endpoints
+        let unguarded = Config { api: None, ..test_config() };
+        assert_eq!(put("ops", "rotated", unguarded.clone()).await.unwrap().status(), 200);
+        assert_eq!(put("ops", "rotated", unguarded).await.unwrap().status(), 403);
+        assert_eq!(reloader.version(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoints_report_backend_health() {
+        let mut config = test_config();
+        let servers = vec![server("http://127.0.0.1:9"), server("http://127.0.0.1:10")];
+        config.http.as_mut().unwrap().services.insert("api".to_string(), service(servers));
+        let (api, _reloader, state) = admin(config, temp_config_path());
+        {
+            let services = state.services.load();
+            let service = services.get_service("api").unwrap();
+            let status = &service.health_statuses[0];
+            status.record_failure("connection refused".to_string());
+            status.record_failure("connection refused".to_string());
+            status.mark_unhealthy();
+        }
+        state
+            .passive_health
+            .record_response("http://127.0.0.1:10", 200, std::time::Duration::from_millis(3));
+        let base = serve(api).await;
+
+        let health: serde_json::Value = reqwest::get(format!("{}/api/health", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["status"], "degraded");
+        let service = &health["services"][0];
+        assert_eq!(service["name"], "api");
+        assert_eq!(service["status"], "degraded");
+
+        let down = &service["backends"][0];
+        assert_eq!(down["url"], "http://127.0.0.1:9");
+        assert_eq!(down["status"], "unhealthy");
+        assert_eq!(down["healthy"], false);
+        assert_eq!(down["consecutive_failures"], 2);
+        assert_eq!(down["last_error"], "connection refused");
+        assert!(down.get("passive").is_none());
+
+        let up = &service["backends"][1];
+        assert_eq!(up["healthy"], true);
+        assert_eq!(up["consecutive_failures"], 0);
+        assert!(up["last_error"].is_null());
+        assert_eq!(up["passive"]["total_requests"], 1);
+        assert_eq!(up["passive"]["total_failures"], 0);
+
+        let detail: serde_json::Value = reqwest::get(format!("{}/api/services/api", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(detail["servers"][0]["healthy"], false);
+        assert_eq!(detail["servers"][0]["last_error"], "connection refused");
+        assert_eq!(detail["servers"][1]["healthy"], true);
+        assert_eq!(detail["servers"][1]["weight"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_services_report_backend_health() {
+        let server = |address: &str, tls: bool| TcpServer {
+            address: address.to_string(),
+            weight: 1,
+            tls,
+        };
+        let db = TcpLoadBalancer {
+            servers: vec![server("127.0.0.1:9", false), server("127.0.0.1:10", true)],
+            health_check: None,
+            servers_transport: None,
+            proxy_protocol: None,
+            termination_delay: None,
+        };
+        let config = Config {
+            tcp: Some(TcpConfig {
+                services: HashMap::from([(
+                    "db".to_string(),
+                    TcpService { load_balancer: Some(db), ..Default::default() },
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let tcp_services = Arc::new(TcpServiceManager::new(&config));
+        {
+            let service = tcp_services.get_service("db").unwrap();
+            service.health_status(0).unwrap().record_failure("Connect failed".to_string());
+            service.mark_unhealthy(0);
+        }
+        let (api, _reloader, _state) = admin(test_config(), temp_config_path());
+        let base = serve(api.with_tcp_services(tcp_services)).await;
+
+        let services: serde_json::Value = reqwest::get(format!("{}/api/tcp/services", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let service = &services[0];
+        assert_eq!(service["name"], "db");
+        assert_eq!(service["status"], "degraded");
+
+        let down = &service["servers"][0];
+        assert_eq!(down["address"], "127.0.0.1:9");
+        assert_eq!(down["healthy"], false);
+        assert_eq!(down["consecutive_failures"], 1);
+        assert_eq!(down["last_error"], "Connect failed");
+
+        let up = &service["servers"][1];
+        assert_eq!(up["healthy"], true);
+        assert_eq!(up["tls"], true);
+        assert!(up["last_error"].is_null());
+    }
+
+    /// `node_id` with every other setting at its config-file default
+    fn cluster_config(node_id: &str) -> ClusterConfig {
+        ClusterConfig {
+            enabled: false,
+            node_id: Some(node_id.to_string()),
+            advertise_address: None,
+            store: None,
+            heartbeat_interval: crate::config::Duration::from_secs(10),
+            node_timeout: crate::config::Duration::from_secs(30),
+            drain_timeout: crate::config::Duration::from_secs(30),
+            leader_ttl: crate::config::Duration::from_secs(15),
+            config_providers: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cluster_undrain_resumes_accepting_connections() {
+        let (api, _reloader, state) = admin(test_config(), temp_config_path());
+        let store: Arc<dyn crate::store::Store> = Arc::new(crate::store::LocalStore::new());
+        let cluster = ClusterManager::new(cluster_config("node-a"), Arc::clone(&store)).await.unwrap();
+        let base = serve(api.with_cluster_manager(Arc::clone(&cluster))).await;
+        let client = reqwest::Client::new();
+
+        // Draining takes the node out of service, so it needs admin credentials
+        let response = client.post(format!("{}/api/cluster/drain", base)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert!(!cluster.is_draining());
+
+        let response = client
+            .post(format!("{}/api/cluster/drain", base))
+            .basic_auth("admin", Some("secret"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(cluster.is_draining());
+        assert!(state.connections.is_draining());
+        assert!(!state.connections.connection_start());
+
+        let response = client.post(format!("{}/api/cluster/undrain", base)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert!(cluster.is_draining());
+
+        let response = client
+            .post(format!("{}/api/cluster/undrain", base))
+            .basic_auth("admin", Some("secret"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["message"], "Drain stopped");
+        assert_eq!(body["node_id"], "node-a");
+        assert!(!cluster.is_draining());
+        assert!(!state.connections.is_draining());
+        assert!(state.connections.connection_start());
+        let node = store.node_get("node-a").await.unwrap().unwrap();
+        assert_eq!(node.status, crate::store::NodeStatus::Active);
+
+        cluster.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cluster_drain_refused_without_admin_auth() {
+        let unguarded = Config { api: None, ..test_config() };
+        let (api, _reloader, _state) = admin(unguarded, temp_config_path());
+        let store: Arc<dyn crate::store::Store> = Arc::new(crate::store::LocalStore::new());
+        let cluster = ClusterManager::new(cluster_config("node-a"), store).await.unwrap();
+        let base = serve(api.with_cluster_manager(Arc::clone(&cluster))).await;
+        let client = reqwest::Client::new();
+
+        for path in ["drain", "undrain", "nodes/node-a/drain", "nodes/node-b/drain"] {
+            let response = client.post(format!("{}/api/cluster/{}", base, path)).send().await.unwrap();
+            assert_eq!(response.status(), 403, "{path}");
+        }
+        assert!(!cluster.is_draining());
+
+        cluster.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_renders_prometheus_format() {
+        let (api, _reloader, _state) = admin(test_config(), temp_config_path());
+        let base = serve(api).await;
+
+        // Installs the global recorder; later scrapes reuse its handle
+        crate::metrics::get_prometheus_handle().unwrap();
+        metrics::counter!("http_requests_total", "entrypoint" => "admin-scrape-test").increment(1);
+
+        for _ in 0..2 {
+            let response = reqwest::get(format!("{}/metrics", base)).await.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(
+                response.headers()["content-type"],
+                "text/plain; version=0.0.4; charset=utf-8"
+            );
+            let body = response.text().await.unwrap();
+            assert!(body.contains("# TYPE http_requests_total counter"), "{body}");
+            assert!(body.contains(r#"http_requests_total{entrypoint="admin-scrape-test"} 1"#), "{body}");
+        }
+    }
 
     #[test]
     fn test_middleware_type_detection() {
@@ -0,0 +1,225 @@
+use super::{Balancer, KeyedBalancer};
+use crate::config::Server;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Default number of virtual nodes placed on the ring per server
+pub const DEFAULT_REPLICAS: usize = 150;
+
+/// Consistent-hashing load balancer
+/// Maps request keys onto a hash ring of virtual nodes so the same key keeps
+/// landing on the same server, and removing a server only remaps its own keys
+pub struct ConsistentHashBalancer {
+    servers: Vec<Server>,
+    healthy: Vec<AtomicBool>,
+    /// Sorted (hash, server index) virtual nodes
+    ring: Vec<(u64, usize)>,
+    /// Counter used to spread keyless requests around the ring
+    counter: AtomicUsize,
+}
+
+impl ConsistentHashBalancer {
+    /// Create a consistent-hash balancer with [`DEFAULT_REPLICAS`] virtual nodes per server.
+    pub fn new(servers: Vec<Server>) -> Self {
+        Self::with_replicas(servers, DEFAULT_REPLICAS)
+    }
+
+    /// Create a consistent-hash balancer with a custom number of virtual nodes per server.
+    pub fn with_replicas(servers: Vec<Server>, replicas: usize) -> Self {
+        let replicas = replicas.max(1);
+        let mut ring = Vec::with_capacity(servers.len() * replicas);
+
+        for (idx, server) in servers.iter().enumerate() {
+            for replica in 0..replicas {
+                let vnode = format!("{}#{}", server.url, replica);
+                ring.push((hash_key(vnode.as_bytes()), idx));
+            }
+        }
+        ring.sort_unstable();
+
+        let healthy = servers.iter().map(|_| AtomicBool::new(true)).collect();
+
+        Self {
+            servers,
+            healthy,
+            ring,
+            counter: AtomicUsize::new(0),
+        }
+    }
+
+    /// Walk the ring clockwise from `hash`, returning the first healthy server index
+    /// `accept` allows.
+    fn lookup(&self, hash: u64, accept: &dyn Fn(&Server) -> bool) -> Option<usize> {
+        if self.ring.is_empty() {
+            return None;
+        }
+
+        let start = self.ring.partition_point(|&(h, _)| h < hash);
+        let len = self.ring.len();
+
+        (0..len)
+            .map(|i| self.ring[(start + i) % len].1)
+            .find(|&idx| self.healthy[idx].load(Ordering::Relaxed) && accept(&self.servers[idx]))
+    }
+}
+
+impl Balancer for ConsistentHashBalancer {
+    fn next_server(&self) -> Option<&Server> {
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
+        // Without a key there is no affinity to preserve; spread requests around the ring
+        let n = self.counter.fetch_add(1, Ordering::Relaxed) as u64;
+        self.lookup(hash_key(&n.to_le_bytes()), &|_| true)
+            .map(|idx| (idx, &self.servers[idx]))
+    }
+
+    fn mark_healthy(&self, index: usize) {
+        if index < self.healthy.len() {
+            self.healthy[index].store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn mark_unhealthy(&self, index: usize) {
+        if index < self.healthy.len() {
+            self.healthy[index].store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn is_healthy(&self, index: usize) -> bool {
+        self.healthy
+            .get(index)
+            .is_some_and(|h| h.load(Ordering::Relaxed))
+    }
+
+    fn find_server_index(&self, url: &str) -> Option<usize> {
+        self.servers.iter().position(|s| s.url == url)
+    }
+
+    fn as_keyed(&self) -> Option<&dyn KeyedBalancer> {
+        Some(self)
+    }
+}
+
+impl KeyedBalancer for ConsistentHashBalancer {
+    fn next_server_for_key_where(&self, key: &[u8], accept: &dyn Fn(&Server) -> bool) -> Option<&Server> {
+        self.lookup(hash_key(key), accept).map(|idx| &self.servers[idx])
+    }
+}
+
+/// FNV-1a with a murmur3 finalizer so similar keys spread evenly over the ring
+#[inline]
+pub(crate) fn hash_key(key: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in key {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_servers(count: usize) -> Vec<Server> {
+        (0..count)
+            .map(|i| Server {
+                url: format!("http://cache{}:8080", i),
+                weight: 1,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_key_same_server() {
+        let balancer = ConsistentHashBalancer::new(make_servers(4));
+
+        let first = balancer.next_server_for_key(b"user-42").unwrap().url.clone();
+        for _ in 0..100 {
+            assert_eq!(balancer.next_server_for_key(b"user-42").unwrap().url, first);
+        }
+    }
+
+    #[test]
+    fn test_keys_spread_across_servers() {
+        let balancer = ConsistentHashBalancer::new(make_servers(4));
+
+        let mut counts = [0usize; 4];
+        for i in 0..10_000 {
+            let key = format!("key-{}", i);
+            let url = &balancer.next_server_for_key(key.as_bytes()).unwrap().url;
+            counts[balancer.find_server_index(url).unwrap()] += 1;
+        }
+
+        for (i, c) in counts.iter().enumerate() {
+            assert!(*c > 1_500, "server{i} only got {c} of 10000 keys");
+        }
+    }
+
+    #[test]
+    fn test_unhealthy_server_moves_to_next_node() {
+        let balancer = ConsistentHashBalancer::new(make_servers(3));
+
+        let url = balancer.next_server_for_key(b"session-a").unwrap().url.clone();
+        let idx = balancer.find_server_index(&url).unwrap();
+
+        balancer.mark_unhealthy(idx);
+        let failover = balancer.next_server_for_key(b"session-a").unwrap().url.clone();
+        assert_ne!(failover, url);
+
+        balancer.mark_healthy(idx);
+        assert_eq!(balancer.next_server_for_key(b"session-a").unwrap().url, url);
+    }
+
+    #[test]
+    fn test_minimal_remapping_on_removal() {
+        const SERVERS: usize = 5;
+        const KEYS: usize = 20_000;
+
+        let balancer = ConsistentHashBalancer::new(make_servers(SERVERS));
+        let keys: Vec<String> = (0..KEYS).map(|i| format!("object/{}", i)).collect();
+
+        let before: Vec<String> = keys
+            .iter()
+            .map(|k| balancer.next_server_for_key(k.as_bytes()).unwrap().url.clone())
+            .collect();
+
+        balancer.mark_unhealthy(2);
+        let removed = "http://cache2:8080";
+
+        let mut moved = 0;
+        for (key, old) in keys.iter().zip(&before) {
+            let new = &balancer.next_server_for_key(key.as_bytes()).unwrap().url;
+            if new != old {
+                // Only keys owned by the removed server may move
+                assert_eq!(old, removed, "key {key} moved off a healthy server");
+                moved += 1;
+            }
+        }
+
+        assert!(moved > 0);
+        assert!(
+            moved < KEYS / SERVERS,
+            "{moved} of {KEYS} keys moved, expected fewer than 1/{SERVERS}"
+        );
+    }
+
+    #[test]
+    fn test_all_unhealthy_returns_none() {
+        let balancer = ConsistentHashBalancer::with_replicas(make_servers(2), 10);
+        balancer.mark_unhealthy(0);
+        balancer.mark_unhealthy(1);
+
+        assert!(balancer.next_server_for_key(b"k").is_none());
+        assert!(balancer.next_server().is_none());
+    }
+}
@@ -28,24 +28,14 @@ impl LeastConnBalancer {
 
         Self { servers }
     }
-
-    /// Increment connection count for a server (call when starting a request)
-    pub fn acquire(&self, index: usize) {
-        if let Some(server) = self.servers.get(index) {
-            server.active_connections.fetch_add(1, Ordering::Relaxed);
-        }
-    }
-
-    /// Decrement connection count for a server (call when request completes)
-    pub fn release(&self, index: usize) {
-        if let Some(server) = self.servers.get(index) {
-            server.active_connections.fetch_sub(1, Ordering::Relaxed);
-        }
-    }
 }
 
 impl Balancer for LeastConnBalancer {
     fn next_server(&self) -> Option<&Server> {
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
         if self.servers.is_empty() {
             return None;
         }
@@ -73,7 +63,7 @@ impl Balancer for LeastConnBalancer {
             }
         }
 
-        best_idx.map(|idx| &self.servers[idx].config)
+        best_idx.map(|idx| (idx, &self.servers[idx].config))
     }
 
     fn mark_healthy(&self, index: usize) {
@@ -88,9 +78,29 @@ impl Balancer for LeastConnBalancer {
         }
     }
 
+    fn is_healthy(&self, index: usize) -> bool {
+        self.servers
+            .get(index)
+            .is_some_and(|s| s.healthy.load(Ordering::Relaxed))
+    }
+
     fn find_server_index(&self, url: &str) -> Option<usize> {
         self.servers.iter().position(|s| s.config.url == url)
     }
+
+    /// Increment connection count for a server (call when starting a request)
+    fn acquire(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.active_connections.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Decrement connection count for a server (call when request completes)
+    fn release(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.active_connections.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -122,4 +132,24 @@ mod tests {
         let server = balancer.next_server().unwrap();
         assert!(server.url.contains("server2"));
     }
+
+    #[test]
+    fn test_all_unhealthy_returns_none() {
+        let balancer = LeastConnBalancer::new(make_servers(2));
+        balancer.mark_unhealthy(0);
+        balancer.mark_unhealthy(1);
+        assert!(balancer.next_server().is_none());
+    }
+
+    #[test]
+    fn test_all_zero_weights_treated_as_equal() {
+        let mut servers = make_servers(2);
+        for server in &mut servers {
+            server.weight = 0;
+        }
+        let balancer = LeastConnBalancer::new(servers);
+
+        balancer.acquire(0);
+        assert!(balancer.next_server().unwrap().url.contains("server1"));
+    }
 }
@@ -1,34 +1,75 @@
 //! Load balancing strategies for distributing traffic across backend servers.
 
+mod consistent_hash;
 mod least_conn;
+mod p2c;
 mod random;
 mod round_robin;
 mod sticky;
 mod weighted;
+mod weighted_least_conn;
 
+pub use consistent_hash::ConsistentHashBalancer;
+pub(crate) use consistent_hash::hash_key;
 pub use least_conn::LeastConnBalancer;
+pub use p2c::P2CBalancer;
 pub use random::RandomBalancer;
 pub use round_robin::RoundRobinBalancer;
 pub use sticky::StickySessionManager;
+pub(crate) use sticky::{format_set_cookie, request_cookie};
 pub use weighted::WeightedBalancer;
+pub use weighted_least_conn::WeightedLeastConnBalancer;
 
 use crate::config::{LoadBalancerService, Server, Service};
+use crate::health::PassiveHealthChecker;
+use std::sync::Arc;
 
 /// Trait for load balancing strategies with health-aware server selection.
 pub trait Balancer: Send + Sync {
     /// Select the next healthy server, or `None` if no servers exist.
     fn next_server(&self) -> Option<&Server>;
+    /// Select the next server along with its index, so callers can feed
+    /// health or connection tracking back to the exact backend chosen.
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
+        None
+    }
     /// Mark a server at the given index as healthy.
     fn mark_healthy(&self, index: usize);
     /// Mark a server at the given index as unhealthy.
     fn mark_unhealthy(&self, index: usize);
+    /// Returns true if the server at the given index exists and is marked healthy.
+    fn is_healthy(&self, index: usize) -> bool;
     /// Find a server's index by its URL.
     fn find_server_index(&self, url: &str) -> Option<usize>;
+    /// View this balancer as a [`KeyedBalancer`] if it supports key-based selection.
+    fn as_keyed(&self) -> Option<&dyn KeyedBalancer> {
+        None
+    }
+    /// Count a request starting on the server at `index`. Only load-aware
+    /// strategies track this.
+    fn acquire(&self, _index: usize) {}
+    /// Count a request on the server at `index` as finished.
+    fn release(&self, _index: usize) {}
+}
+
+/// Balancers that can pin a request key (e.g. a header value) to a server.
+pub trait KeyedBalancer: Balancer {
+    /// Select the healthy server owning `key`, or `None` if no healthy servers exist.
+    fn next_server_for_key(&self, key: &[u8]) -> Option<&Server> {
+        self.next_server_for_key_where(key, &|_| true)
+    }
+    /// Like [`next_server_for_key`](Self::next_server_for_key), but passing over
+    /// servers `accept` rejects as the key's owner goes to the next one in line.
+    fn next_server_for_key_where(&self, key: &[u8], accept: &dyn Fn(&Server) -> bool) -> Option<&Server>;
 }
 
 /// Wraps a strategy-specific balancer with automatic strategy selection from config.
 pub struct LoadBalancer {
-    strategy: Box<dyn Balancer>,
+    strategy: Arc<dyn Balancer>,
+    /// Server list kept for passive-health fallback selection
+    servers: Vec<Server>,
+    /// Optional passive health checker consulted on every selection
+    passive_health: Option<Arc<PassiveHealthChecker>>,
 }
 
 impl LoadBalancer {
@@ -40,37 +81,144 @@ impl LoadBalancer {
     /// Build a balancer from LB config, choosing weighted or round-robin based on server weights.
     pub fn from_load_balancer(lb: &LoadBalancerService) -> Self {
         // Determine strategy based on server weights
-        // If all weights are equal, use round robin; otherwise use weighted
+        // If all weights are equal (including all zero), use round robin; otherwise use
+        // weighted so zero-weight servers are kept out of rotation
         let all_equal_weights = lb.servers.windows(2).all(|w| w[0].weight == w[1].weight);
-        let has_weights = lb.servers.iter().any(|s| s.weight > 1);
 
-        let strategy: Box<dyn Balancer> = if has_weights && !all_equal_weights {
-            Box::new(WeightedBalancer::new(lb.servers.clone()))
+        let strategy: Arc<dyn Balancer> = if !all_equal_weights {
+            Arc::new(WeightedBalancer::new(lb.servers.clone()))
         } else {
             // Default to round robin
-            Box::new(RoundRobinBalancer::new(lb.servers.clone()))
+            Arc::new(RoundRobinBalancer::new(lb.servers.clone()))
         };
 
-        Self { strategy }
+        Self {
+            strategy,
+            servers: lb.servers.clone(),
+            passive_health: None,
+        }
     }
 
     /// Create a load balancer with a specific strategy
     pub fn with_strategy(servers: Vec<Server>, strategy: &str) -> Self {
-        let strategy: Box<dyn Balancer> = match strategy {
-            "round_robin" | "roundRobin" => Box::new(RoundRobinBalancer::new(servers)),
-            "weighted" => Box::new(WeightedBalancer::new(servers)),
-            "least_conn" | "leastConn" => Box::new(LeastConnBalancer::new(servers)),
-            "random" => Box::new(RandomBalancer::new(servers)),
-            _ => Box::new(RoundRobinBalancer::new(servers)), // Default
+        let all_servers = servers.clone();
+        let strategy: Arc<dyn Balancer> = match strategy {
+            "round_robin" | "roundRobin" => Arc::new(RoundRobinBalancer::new(servers)),
+            "weighted" => Arc::new(WeightedBalancer::new(servers)),
+            "least_conn" | "leastConn" => Arc::new(LeastConnBalancer::new(servers)),
+            "weighted_least_conn" | "weightedLeastConn" => {
+                Arc::new(WeightedLeastConnBalancer::new(servers))
+            }
+            "random" => Arc::new(RandomBalancer::new(servers)),
+            "p2c" | "powerOfTwoChoices" => Arc::new(P2CBalancer::new(servers)),
+            "consistent_hash" | "consistentHash" => Arc::new(ConsistentHashBalancer::new(servers)),
+            _ => Arc::new(RoundRobinBalancer::new(servers)), // Default
         };
 
-        Self { strategy }
+        Self {
+            strategy,
+            servers: all_servers,
+            passive_health: None,
+        }
+    }
+
+    /// Attach a passive health checker so selection skips backends it has declared down.
+    pub fn with_passive_health(mut self, passive_health: Arc<PassiveHealthChecker>) -> Self {
+        self.passive_health = Some(passive_health);
+        self
+    }
+
+    /// Returns true if a passive health checker gates selection for this balancer.
+    pub fn has_passive_health(&self) -> bool {
+        self.passive_health.is_some()
     }
 
     /// Select the next backend server according to the active strategy.
     #[inline]
     pub fn next_server(&self) -> Option<&Server> {
-        self.strategy.next_server()
+        if self.passive_health.is_none() {
+            return self.strategy.next_server();
+        }
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    /// Select the next backend server and its index according to the active strategy.
+    #[inline]
+    pub fn next_server_indexed(&self) -> Option<(usize, &Server)> {
+        let Some(passive) = &self.passive_health else {
+            return self.strategy.next_server_indexed();
+        };
+
+        // Give the strategy one pass over the pool to find a passively-healthy backend
+        for _ in 0..self.servers.len().max(1) {
+            let (idx, server) = self.strategy.next_server_indexed()?;
+            if passive.can_try(&server.url) {
+                return Some((idx, server));
+            }
+        }
+        self.fail_open(passive)
+    }
+
+    /// Select a backend to retry a request on, passing over the servers at `tried`
+    /// while another healthy one is left; once every server has failed, any may be picked.
+    pub fn next_server_excluding(&self, tried: &[usize]) -> Option<(usize, &Server)> {
+        // One pass over the pool keeps retries on the strategy's own choices
+        for _ in 0..self.servers.len().max(1) {
+            let (idx, server) = self.next_server_indexed()?;
+            if !tried.contains(&idx) {
+                return Some((idx, server));
+            }
+        }
+        // A heavily weighted server can take the whole pass; try the rest in order
+        self.servers
+            .iter()
+            .enumerate()
+            .find(|(idx, server)| !tried.contains(idx) && server.weight > 0 && self.is_healthy(*idx))
+            .or_else(|| self.next_server_indexed())
+    }
+
+    /// Select a backend for a hashing key, falling back to `next_server` for unkeyed strategies.
+    #[inline]
+    pub fn next_server_for_key(&self, key: &[u8]) -> Option<&Server> {
+        let Some(keyed) = self.strategy.as_keyed() else {
+            return self.next_server();
+        };
+        let Some(passive) = &self.passive_health else {
+            return keyed.next_server_for_key(key);
+        };
+
+        // Passively-down owners hand the key on to the next server in line
+        keyed
+            .next_server_for_key_where(key, &|server| passive.can_try(&server.url))
+            .or_else(|| self.fail_open(passive).map(|(_, s)| s))
+    }
+
+    /// Every backend is passively down: fail open to the actively-healthy one
+    /// that failed longest ago, never to one the active check has declared dead.
+    fn fail_open(&self, passive: &PassiveHealthChecker) -> Option<(usize, &Server)> {
+        // Zero-weight servers are drained, so they only qualify when no weighted
+        // server is left, the same rule the weighted strategy applies
+        let candidates: Vec<_> = self
+            .servers
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.strategy.is_healthy(*idx))
+            .collect();
+        let any_weighted = candidates.iter().any(|(_, s)| s.weight > 0);
+        candidates
+            .into_iter()
+            .filter(|(_, s)| !any_weighted || s.weight > 0)
+            .min_by_key(|(_, s)| passive.unhealthy_since(&s.url))
+    }
+
+    /// Count a request starting on the server at `index`; it is counted as
+    /// finished when the returned guard is dropped.
+    pub fn acquire(&self, index: usize) -> InFlight {
+        self.strategy.acquire(index);
+        InFlight {
+            strategy: Arc::clone(&self.strategy),
+            index,
+        }
     }
 
     /// Mark a server as healthy by index.
@@ -83,8 +231,277 @@ impl LoadBalancer {
         self.strategy.mark_unhealthy(index);
     }
 
+    /// The configured server at `index`, regardless of health.
+    pub fn server(&self, index: usize) -> Option<&Server> {
+        self.servers.get(index)
+    }
+
+    /// Returns true if the server at `index` is healthy and not held out by passive health.
+    pub fn is_healthy(&self, index: usize) -> bool {
+        if !self.strategy.is_healthy(index) {
+            return false;
+        }
+        match (&self.passive_health, self.servers.get(index)) {
+            (Some(passive), Some(server)) => passive.can_try(&server.url),
+            _ => true,
+        }
+    }
+
     /// Find a server's index by its URL.
     pub fn find_server_index(&self, url: &str) -> Option<usize> {
         self.strategy.find_server_index(url)
     }
 }
+
+/// A request in flight on one server, released back to its balancer on drop
+pub struct InFlight {
+    strategy: Arc<dyn Balancer>,
+    index: usize,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.strategy.release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_servers(count: usize) -> Vec<Server> {
+        (0..count)
+            .map(|i| Server {
+                url: format!("http://server{}:8080", i),
+                weight: (i + 1) as u32,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_indexed_selection_maps_to_server() {
+        let servers = make_servers(3);
+        for strategy in [
+            "roundRobin",
+            "weighted",
+            "leastConn",
+            "random",
+            "p2c",
+            "consistentHash",
+            "weightedLeastConn",
+        ] {
+            let lb = LoadBalancer::with_strategy(servers.clone(), strategy);
+            for _ in 0..20 {
+                let (idx, server) = lb.next_server_indexed().unwrap();
+                assert_eq!(server.url, servers[idx].url, "strategy {strategy}");
+                assert_eq!(lb.find_server_index(&server.url), Some(idx));
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_flight_guard_counts_until_dropped() {
+        let lb = LoadBalancer::with_strategy(make_servers(2), "leastConn");
+
+        let in_flight = lb.acquire(0);
+        assert_eq!(lb.next_server_indexed().unwrap().0, 1);
+
+        drop(in_flight);
+        assert_eq!(lb.next_server_indexed().unwrap().0, 0);
+    }
+
+    #[test]
+    fn test_retry_selection_skips_tried_servers() {
+        let lb = LoadBalancer::with_strategy(make_servers(3), "roundRobin");
+
+        for _ in 0..6 {
+            let (idx, _) = lb.next_server_excluding(&[0, 1]).unwrap();
+            assert_eq!(idx, 2);
+        }
+        // With every server tried, retries go back into rotation
+        assert!(lb.next_server_excluding(&[0, 1, 2]).is_some());
+    }
+
+    #[test]
+    fn test_passive_health_removes_and_restores_backend() {
+        use crate::health::PassiveHealthConfig;
+        use std::time::Duration;
+
+        let passive = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig {
+            failure_threshold: 3,
+            success_threshold: 1,
+            recovery_interval: Duration::from_millis(100),
+            ..Default::default()
+        }));
+        let lb = LoadBalancer::with_strategy(make_servers(2), "roundRobin")
+            .with_passive_health(Arc::clone(&passive));
+        let failing = "http://server0:8080";
+
+        // Backend keeps answering 503 until passive health takes it out of rotation
+        for _ in 0..3 {
+            passive.record_response(failing, 503, Duration::from_millis(5));
+        }
+        for _ in 0..20 {
+            assert_ne!(lb.next_server().unwrap().url, failing);
+        }
+
+        // After the recovery interval it is offered trial traffic again
+        std::thread::sleep(Duration::from_millis(120));
+        assert!((0..4).any(|_| lb.next_server().unwrap().url == failing));
+
+        // A successful trial request fully restores it
+        passive.record_response(failing, 200, Duration::from_millis(5));
+        assert!(passive.is_healthy(failing));
+    }
+
+    #[test]
+    fn test_keyed_selection_applies_passive_health() {
+        use crate::health::PassiveHealthConfig;
+        use std::time::Duration;
+
+        let passive = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig {
+            failure_threshold: 1,
+            recovery_interval: Duration::from_secs(60),
+            ..Default::default()
+        }));
+        let lb = LoadBalancer::with_strategy(make_servers(3), "consistentHash")
+            .with_passive_health(Arc::clone(&passive));
+
+        // The key's owner is passively down, so the key moves to another backend
+        let owner = lb.next_server_for_key(b"user-42").unwrap().url.clone();
+        passive.record_response(&owner, 502, Duration::from_millis(5));
+        let failover = lb.next_server_for_key(b"user-42").unwrap().url.clone();
+        assert_ne!(failover, owner);
+        assert_eq!(lb.next_server_for_key(b"user-42").unwrap().url, failover);
+
+        // With every backend down it fails open to the one that failed longest ago
+        for server in make_servers(3) {
+            if server.url != owner {
+                std::thread::sleep(Duration::from_millis(5));
+                passive.record_response(&server.url, 502, Duration::from_millis(5));
+            }
+        }
+        assert_eq!(lb.next_server_for_key(b"user-42").unwrap().url, owner);
+    }
+
+    #[test]
+    fn test_passive_health_fails_open_to_least_recently_failed() {
+        use crate::health::PassiveHealthConfig;
+        use std::time::Duration;
+
+        let passive = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig {
+            failure_threshold: 1,
+            recovery_interval: Duration::from_secs(60),
+            ..Default::default()
+        }));
+        let lb = LoadBalancer::with_strategy(make_servers(2), "roundRobin")
+            .with_passive_health(Arc::clone(&passive));
+
+        passive.record_response("http://server1:8080", 502, Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(5));
+        passive.record_response("http://server0:8080", 502, Duration::from_millis(5));
+
+        let (idx, server) = lb.next_server_indexed().unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(server.url, "http://server1:8080");
+    }
+
+    #[test]
+    fn test_passive_fail_open_skips_actively_down_backend() {
+        use crate::health::PassiveHealthConfig;
+        use std::time::Duration;
+
+        let passive = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig {
+            failure_threshold: 1,
+            recovery_interval: Duration::from_secs(60),
+            ..Default::default()
+        }));
+        let lb = LoadBalancer::with_strategy(make_servers(2), "roundRobin")
+            .with_passive_health(Arc::clone(&passive));
+
+        // server0 is down on the active check with no passive mark, server1 only passively
+        lb.mark_unhealthy(0);
+        passive.record_response("http://server1:8080", 502, Duration::from_millis(5));
+
+        for _ in 0..5 {
+            let (idx, server) = lb.next_server_indexed().unwrap();
+            assert_eq!(idx, 1);
+            assert_eq!(server.url, "http://server1:8080");
+        }
+
+        // With nothing passing the active check there is nothing to fail open to
+        lb.mark_unhealthy(1);
+        assert!(lb.next_server_indexed().is_none());
+    }
+
+    #[test]
+    fn test_passive_fail_open_skips_zero_weight_backend() {
+        use crate::health::PassiveHealthConfig;
+        use std::time::Duration;
+
+        let passive = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig {
+            failure_threshold: 1,
+            recovery_interval: Duration::from_secs(60),
+            ..Default::default()
+        }));
+        let mut servers = make_servers(2);
+        servers[0].weight = 0;
+        let lb = LoadBalancer::with_strategy(servers, "weighted")
+            .with_passive_health(Arc::clone(&passive));
+
+        // The drained server0 failed longest ago but must not receive traffic
+        passive.record_response("http://server0:8080", 502, Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(5));
+        passive.record_response("http://server1:8080", 502, Duration::from_millis(5));
+
+        for _ in 0..5 {
+            let (idx, server) = lb.next_server_indexed().unwrap();
+            assert_eq!(idx, 1);
+            assert_eq!(server.url, "http://server1:8080");
+        }
+    }
+
+    #[test]
+    fn test_indexed_selection_skips_unhealthy() {
+        let lb = LoadBalancer::with_strategy(make_servers(3), "roundRobin");
+        lb.mark_unhealthy(1);
+
+        for _ in 0..10 {
+            let (idx, server) = lb.next_server_indexed().unwrap();
+            assert_ne!(idx, 1);
+            assert!(!server.url.contains("server1"));
+        }
+    }
+
+    #[test]
+    fn test_from_load_balancer_zero_weights() {
+        let mut servers = make_servers(2);
+        servers[0].weight = 0;
+        servers[1].weight = 1;
+        let lb_config = LoadBalancerService {
+            servers,
+            pass_host_header: true,
+            sticky: None,
+            health_check: None,
+            servers_transport: None,
+            response_forwarding: None,
+            web_socket: None,
+        };
+
+        // A zero-weight server alongside weight-1 servers receives no traffic
+        let lb = LoadBalancer::from_load_balancer(&lb_config);
+        for _ in 0..10 {
+            assert_eq!(lb.next_server().unwrap().url, "http://server1:8080");
+        }
+
+        // Losing every weighted server leaves the zero-weight one serving
+        lb.mark_unhealthy(1);
+        assert_eq!(lb.next_server().unwrap().url, "http://server0:8080");
+
+        lb.mark_unhealthy(0);
+        assert!(lb.next_server().is_none());
+    }
+}
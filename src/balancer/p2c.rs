@@ -0,0 +1,328 @@
+use super::Balancer;
+use crate::config::Server;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Power-of-two-choices load balancer
+/// Samples two distinct healthy servers at random and picks the one with fewer
+/// active connections, giving near least-conn quality without a full scan
+pub struct P2CBalancer {
+    servers: Vec<P2CServer>,
+    healthy_count: AtomicUsize,
+}
+
+struct P2CServer {
+    config: Server,
+    healthy: AtomicBool,
+    active_connections: AtomicUsize,
+}
+
+impl P2CBalancer {
+    /// Create a P2C balancer with all servers initially healthy and zero connections.
+    pub fn new(servers: Vec<Server>) -> Self {
+        let servers: Vec<P2CServer> = servers
+            .into_iter()
+            .map(|config| P2CServer {
+                config,
+                healthy: AtomicBool::new(true),
+                active_connections: AtomicUsize::new(0),
+            })
+            .collect();
+
+        Self {
+            healthy_count: AtomicUsize::new(servers.len()),
+            servers,
+        }
+    }
+
+    /// Current number of active connections for a server.
+    pub fn active_connections(&self, index: usize) -> usize {
+        self.servers
+            .get(index)
+            .map(|s| s.active_connections.load(Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
+    /// Map the n-th healthy server to its index in `servers`.
+    fn nth_healthy(&self, n: usize) -> Option<usize> {
+        self.servers
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.healthy.load(Ordering::Relaxed))
+            .nth(n)
+            .map(|(idx, _)| idx)
+    }
+
+    /// Pick two distinct healthy server indices, or a single one if only one is healthy.
+    fn pick_two(&self) -> Option<(usize, Option<usize>)> {
+        let len = self.servers.len();
+        let healthy = self.healthy_count.load(Ordering::Relaxed).min(len);
+
+        match healthy {
+            0 => None,
+            1 => self.nth_healthy(0).map(|idx| (idx, None)),
+            _ => {
+                let a = fast_random() as usize % healthy;
+                // Draw from the remaining n-1 slots so the second pick never equals the first
+                let mut b = fast_random() as usize % (healthy - 1);
+                if b >= a {
+                    b += 1;
+                }
+
+                if healthy == len {
+                    // Fast path: every server is healthy, indices map directly
+                    Some((a, Some(b)))
+                } else {
+                    let a = self.nth_healthy(a)?;
+                    let b = self.nth_healthy(b);
+                    Some((a, b))
+                }
+            }
+        }
+    }
+}
+
+impl Balancer for P2CBalancer {
+    fn next_server(&self) -> Option<&Server> {
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
+        if self.servers.is_empty() {
+            return None;
+        }
+
+        let (a, b) = self.pick_two()?;
+        let idx = match b {
+            Some(b) => {
+                let conns_a = self.servers[a].active_connections.load(Ordering::Relaxed);
+                let conns_b = self.servers[b].active_connections.load(Ordering::Relaxed);
+                if conns_b < conns_a { b } else { a }
+            }
+            None => a,
+        };
+
+        Some((idx, &self.servers[idx].config))
+    }
+
+    fn mark_healthy(&self, index: usize) {
+        if let Some(server) = self.servers.get(index)
+            && !server.healthy.swap(true, Ordering::Relaxed)
+        {
+            self.healthy_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn mark_unhealthy(&self, index: usize) {
+        if let Some(server) = self.servers.get(index)
+            && server.healthy.swap(false, Ordering::Relaxed)
+        {
+            self.healthy_count.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn is_healthy(&self, index: usize) -> bool {
+        self.servers
+            .get(index)
+            .is_some_and(|s| s.healthy.load(Ordering::Relaxed))
+    }
+
+    fn find_server_index(&self, url: &str) -> Option<usize> {
+        self.servers.iter().position(|s| s.config.url == url)
+    }
+
+    /// Increment connection count for a server (call when starting a request)
+    fn acquire(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.active_connections.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Decrement connection count for a server (call when request completes)
+    fn release(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            let _ = server.active_connections.fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |c| c.checked_sub(1),
+            );
+        }
+    }
+}
+
+/// Fast xorshift random - no allocation, no syscall
+/// Each thread gets a distinct seed so concurrent workers don't make identical
+/// picks in lockstep and herd onto the same pair of servers
+#[inline]
+fn fast_random() -> u32 {
+    use std::cell::Cell;
+    use std::sync::atomic::AtomicU32;
+    static SEED: AtomicU32 = AtomicU32::new(0x2C0FFEE5);
+    thread_local! {
+        static STATE: Cell<u32> = Cell::new(SEED.fetch_add(0x9E3779B9, Ordering::Relaxed) | 1);
+    }
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        state.set(x);
+        x
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balancer::RandomBalancer;
+
+    fn make_servers(count: usize) -> Vec<Server> {
+        (0..count)
+            .map(|i| Server {
+                url: format!("http://server{}:8080", i),
+                weight: 1,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_p2c_prefers_less_loaded() {
+        let balancer = P2CBalancer::new(make_servers(2));
+        balancer.acquire(0);
+        balancer.acquire(0);
+
+        // With two servers both are always sampled, so the idle one wins
+        for _ in 0..20 {
+            let server = balancer.next_server().unwrap();
+            assert!(server.url.contains("server1"));
+        }
+    }
+
+    #[test]
+    fn test_p2c_skips_unhealthy() {
+        let balancer = P2CBalancer::new(make_servers(3));
+        balancer.mark_unhealthy(1);
+        balancer.mark_unhealthy(1); // idempotent
+
+        for _ in 0..100 {
+            let server = balancer.next_server().unwrap();
+            assert!(!server.url.contains("server1"));
+        }
+
+        balancer.mark_unhealthy(0);
+        balancer.mark_unhealthy(2);
+        assert!(balancer.next_server().is_none());
+
+        balancer.mark_healthy(2);
+        assert!(balancer.next_server().unwrap().url.contains("server2"));
+    }
+
+    #[test]
+    fn test_p2c_picks_are_distinct() {
+        let balancer = P2CBalancer::new(make_servers(2));
+        for _ in 0..1000 {
+            let (a, b) = balancer.pick_two().unwrap();
+            assert_ne!(Some(a), b);
+        }
+
+        balancer.mark_unhealthy(0);
+        assert_eq!(balancer.pick_two(), Some((1, None)));
+    }
+
+    #[test]
+    fn test_p2c_distribution_concurrent() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let balancer = Arc::new(P2CBalancer::new(make_servers(4)));
+        let counts: Arc<Vec<AtomicUsize>> = Arc::new((0..4).map(|_| AtomicUsize::new(0)).collect());
+
+        let mut handles = vec![];
+        for _ in 0..8 {
+            let b = Arc::clone(&balancer);
+            let c = Arc::clone(&counts);
+            handles.push(thread::spawn(move || {
+                for _ in 0..500 {
+                    let url = b.next_server().unwrap().url.clone();
+                    let idx = b.find_server_index(&url).unwrap();
+                    b.acquire(idx);
+                    c[idx].fetch_add(1, Ordering::Relaxed);
+                    b.release(idx);
+                }
+            }));
+        }
+
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let total: usize = counts.iter().map(|c| c.load(Ordering::Relaxed)).sum();
+        assert_eq!(total, 4000);
+        for (i, c) in counts.iter().enumerate() {
+            let c = c.load(Ordering::Relaxed);
+            // Expect ~1000 each; allow generous slack for scheduling noise
+            assert!((600..=1400).contains(&c), "server{i} got {c} requests");
+        }
+    }
+
+    /// Discrete-time simulation where server0 is 10x slower than the others.
+    /// Returns the peak number of in-flight requests observed on any server.
+    fn simulate_peak_load<F: Fn(&[usize]) -> usize>(pick: F, on_pick: impl Fn(usize), on_done: impl Fn(usize)) -> usize {
+        let latencies = [10usize, 1, 1, 1];
+        let mut in_flight = [0usize; 4];
+        let mut pending: Vec<(usize, usize)> = Vec::new(); // (finish_tick, server)
+        let mut peak = 0;
+
+        for tick in 0..2000 {
+            pending.retain(|&(finish, idx)| {
+                if finish <= tick {
+                    in_flight[idx] -= 1;
+                    on_done(idx);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            for _ in 0..2 {
+                let idx = pick(&in_flight);
+                in_flight[idx] += 1;
+                on_pick(idx);
+                pending.push((tick + latencies[idx], idx));
+            }
+
+            peak = peak.max(*in_flight.iter().max().unwrap());
+        }
+        peak
+    }
+
+    #[test]
+    fn test_p2c_beats_random_under_uneven_latency() {
+        let p2c = P2CBalancer::new(make_servers(4));
+        let p2c_peak = simulate_peak_load(
+            |_| {
+                let url = &p2c.next_server().unwrap().url;
+                p2c.find_server_index(url).unwrap()
+            },
+            |idx| p2c.acquire(idx),
+            |idx| p2c.release(idx),
+        );
+
+        let random = RandomBalancer::new(make_servers(4));
+        let random_peak = simulate_peak_load(
+            |_| {
+                let url = &random.next_server().unwrap().url;
+                random.find_server_index(url).unwrap()
+            },
+            |_| {},
+            |_| {},
+        );
+
+        assert!(
+            p2c_peak < random_peak,
+            "p2c peak in-flight ({p2c_peak}) should be below random ({random_peak})"
+        );
+    }
+}
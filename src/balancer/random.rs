@@ -51,37 +51,48 @@ impl RandomBalancer {
 
 impl Balancer for RandomBalancer {
     fn next_server(&self) -> Option<&Server> {
-        if self.servers.is_empty() || self.total_weight == 0 {
-            return self.servers.first().map(|s| &s.config);
-        }
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
+        // When every healthy server has zero weight, treat them as equal
+        let weighted = self.total_weight > 0
+            && self
+                .servers
+                .iter()
+                .any(|s| s.config.weight > 0 && s.healthy.load(Ordering::Relaxed));
+        let weight_of = |server: &RandomServer| {
+            if weighted { server.config.weight } else { 1 }
+        };
 
         // Calculate healthy total weight
         let healthy_weight: u32 = self
             .servers
             .iter()
             .filter(|s| s.healthy.load(Ordering::Relaxed))
-            .map(|s| s.config.weight)
+            .map(weight_of)
             .sum();
 
+        // No healthy servers left
         if healthy_weight == 0 {
-            return self.servers.first().map(|s| &s.config);
+            return None;
         }
 
         let rand = Self::fast_random() % healthy_weight;
 
         let mut cumulative = 0u32;
-        for server in &self.servers {
+        for (idx, server) in self.servers.iter().enumerate() {
             if !server.healthy.load(Ordering::Relaxed) {
                 continue;
             }
-            cumulative += server.config.weight;
+            cumulative += weight_of(server);
             if rand < cumulative {
-                return Some(&server.config);
+                return Some((idx, &server.config));
             }
         }
 
-        // Fallback
-        self.servers.first().map(|s| &s.config)
+        // Health changed mid-scan and the draw fell past the remaining servers
+        None
     }
 
     fn mark_healthy(&self, index: usize) {
@@ -96,6 +107,12 @@ impl Balancer for RandomBalancer {
         }
     }
 
+    fn is_healthy(&self, index: usize) -> bool {
+        self.servers
+            .get(index)
+            .is_some_and(|s| s.healthy.load(Ordering::Relaxed))
+    }
+
     fn find_server_index(&self, url: &str) -> Option<usize> {
         self.servers.iter().position(|s| s.config.url == url)
     }
@@ -133,4 +150,62 @@ mod tests {
             assert!(server.is_some());
         }
     }
+
+    fn servers_with_weights(weights: &[u32]) -> Vec<Server> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| Server {
+                url: format!("http://server{}:8080", i),
+                weight,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_all_unhealthy_returns_none() {
+        let balancer = RandomBalancer::new(make_servers());
+        balancer.mark_unhealthy(0);
+        assert_eq!(balancer.next_server().unwrap().url, "http://server1:8080");
+
+        balancer.mark_unhealthy(1);
+        assert!(balancer.next_server().is_none());
+    }
+
+    #[test]
+    fn test_all_zero_weights_treated_as_equal() {
+        let balancer = RandomBalancer::new(servers_with_weights(&[0, 0]));
+
+        let mut seen = [false; 2];
+        for _ in 0..200 {
+            let (idx, _) = balancer.next_server_indexed().unwrap();
+            seen[idx] = true;
+        }
+        assert_eq!(seen, [true, true]);
+    }
+
+    #[test]
+    fn test_zero_weight_server_not_selected() {
+        let balancer = RandomBalancer::new(servers_with_weights(&[0, 3]));
+        for _ in 0..100 {
+            assert_eq!(balancer.next_server().unwrap().url, "http://server1:8080");
+        }
+
+        // Once the weighted server is down the zero-weight one takes the traffic
+        balancer.mark_unhealthy(1);
+        assert_eq!(balancer.next_server().unwrap().url, "http://server0:8080");
+    }
+
+    #[test]
+    fn test_empty_and_single_server() {
+        assert!(RandomBalancer::new(Vec::new()).next_server().is_none());
+
+        let balancer = RandomBalancer::new(servers_with_weights(&[5]));
+        for _ in 0..10 {
+            assert_eq!(balancer.next_server().unwrap().url, "http://server0:8080");
+        }
+    }
 }
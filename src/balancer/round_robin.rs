@@ -23,6 +23,10 @@ impl RoundRobinBalancer {
 
 impl Balancer for RoundRobinBalancer {
     fn next_server(&self) -> Option<&Server> {
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
         if self.servers.is_empty() {
             return None;
         }
@@ -34,13 +38,12 @@ impl Balancer for RoundRobinBalancer {
         for i in 0..len {
             let idx = (start + i) % len;
             if self.healthy[idx].load(Ordering::Relaxed) {
-                return Some(&self.servers[idx]);
+                return Some((idx, &self.servers[idx]));
             }
         }
 
-        // All servers unhealthy, return first one anyway
-        // (the health checker will eventually mark them healthy)
-        Some(&self.servers[start % len])
+        // All servers unhealthy
+        None
     }
 
     fn mark_healthy(&self, index: usize) {
@@ -55,6 +58,12 @@ impl Balancer for RoundRobinBalancer {
         }
     }
 
+    fn is_healthy(&self, index: usize) -> bool {
+        self.healthy
+            .get(index)
+            .is_some_and(|h| h.load(Ordering::Relaxed))
+    }
+
     fn find_server_index(&self, url: &str) -> Option<usize> {
         self.servers.iter().position(|s| s.url == url)
     }
@@ -107,4 +116,25 @@ mod tests {
         assert!(s3.url.contains("server2")); // start=2, returns 2
         assert!(s4.url.contains("server0")); // start=3%3=0, returns 0
     }
+
+    #[test]
+    fn test_all_unhealthy_returns_none() {
+        let balancer = RoundRobinBalancer::new(make_servers(2));
+        balancer.mark_unhealthy(0);
+        balancer.mark_unhealthy(1);
+        assert!(balancer.next_server().is_none());
+
+        balancer.mark_healthy(0);
+        assert!(balancer.next_server().unwrap().url.contains("server0"));
+    }
+
+    #[test]
+    fn test_empty_and_single_server() {
+        assert!(RoundRobinBalancer::new(Vec::new()).next_server().is_none());
+
+        let balancer = RoundRobinBalancer::new(make_servers(1));
+        for _ in 0..3 {
+            assert!(balancer.next_server().unwrap().url.contains("server0"));
+        }
+    }
 }
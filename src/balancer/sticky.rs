@@ -184,63 +184,27 @@ impl StickySessionManager {
                 warn!("Failed to store session in distributed store: {}", e);
             }
 
+        self.maybe_cleanup();
+
         Some(session_id)
     }
 
     /// Get the Set-Cookie header value for a new session
     pub fn set_cookie_header(&self, session_id: &str) -> String {
-        let mut cookie = format!("{}={}", self.cookie_config.name, session_id);
-
-        if let Some(ref path) = self.cookie_config.path {
-            cookie.push_str(&format!("; Path={}", path));
-        } else {
-            cookie.push_str("; Path=/");
-        }
-
-        if let Some(max_age) = self.cookie_config.max_age {
-            cookie.push_str(&format!("; Max-Age={}", max_age));
-        }
-
-        if self.cookie_config.http_only {
-            cookie.push_str("; HttpOnly");
-        }
-
-        if self.cookie_config.secure {
-            cookie.push_str("; Secure");
-        }
-
-        if let Some(ref same_site) = self.cookie_config.same_site {
-            cookie.push_str(&format!("; SameSite={}", same_site));
-        }
-
-        cookie
+        format_set_cookie(&self.cookie_config, session_id)
     }
 
-    /// Add sticky session cookie to response if needed
+    /// Add sticky session cookie to response, keeping any cookies the backend set
     pub fn add_cookie_to_response<B>(&self, response: &mut Response<B>, session_id: &str) {
         let cookie_header = self.set_cookie_header(session_id);
         if let Ok(value) = cookie_header.parse() {
-            response.headers_mut().insert(SET_COOKIE, value);
+            response.headers_mut().append(SET_COOKIE, value);
         }
     }
 
     /// Extract session cookie from request
     fn extract_session_cookie<B>(&self, req: &Request<B>) -> Option<String> {
-        let cookie_header = req.headers().get(COOKIE)?;
-        let cookies = cookie_header.to_str().ok()?;
-
-        for cookie in cookies.split(';') {
-            let cookie = cookie.trim();
-            let (name, value) = cookie.split_once('=')?;
-            
-            
-
-            if name.trim() == self.cookie_config.name {
-                return Some(value.to_string());
-            }
-        }
-
-        None
+        request_cookie(req, &self.cookie_config.name)
     }
 
     /// Find server index by URL
@@ -289,6 +253,47 @@ impl StickySessionManager {
     }
 }
 
+/// Build a Set-Cookie value for a sticky cookie
+pub(crate) fn format_set_cookie(cookie_config: &StickyCookie, value: &str) -> String {
+    let mut cookie = format!("{}={}", cookie_config.name, value);
+
+    if let Some(ref path) = cookie_config.path {
+        cookie.push_str(&format!("; Path={}", path));
+    } else {
+        cookie.push_str("; Path=/");
+    }
+
+    if let Some(max_age) = cookie_config.max_age {
+        cookie.push_str(&format!("; Max-Age={}", max_age));
+    }
+
+    if cookie_config.http_only {
+        cookie.push_str("; HttpOnly");
+    }
+
+    if cookie_config.secure {
+        cookie.push_str("; Secure");
+    }
+
+    if let Some(ref same_site) = cookie_config.same_site {
+        cookie.push_str(&format!("; SameSite={}", same_site));
+    }
+
+    cookie
+}
+
+/// Value of a named cookie in the request's Cookie headers
+pub(crate) fn request_cookie<B>(req: &Request<B>, name: &str) -> Option<String> {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(cookie_name, _)| cookie_name.trim() == name)
+        .map(|(_, value)| value.to_string())
+}
+
 /// Generate a random session ID
 fn generate_session_id() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -339,6 +344,7 @@ mod tests {
                 max_age: Some(3600),
                 path: Some("/".to_string()),
             }),
+            header: None,
         }
     }
 
@@ -390,6 +396,22 @@ mod tests {
         assert_eq!(server_idx, Some(1));
     }
 
+    #[tokio::test]
+    async fn test_create_session_sync_evicts_expired_sessions() {
+        let mut sticky = test_sticky_config();
+        sticky.cookie.as_mut().unwrap().max_age = Some(1);
+        let manager = StickySessionManager::new(&sticky, test_servers(), "test-service").unwrap();
+
+        manager.create_session_sync(0).await.unwrap();
+        assert_eq!(manager.local_session_count(), 1);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        // The expired session is pruned when the next one is created
+        manager.create_session_sync(1).await.unwrap();
+        assert_eq!(manager.local_session_count(), 1);
+    }
+
     #[test]
     fn test_sticky_session_no_cookie() {
         let manager =
@@ -56,18 +56,20 @@ impl WeightedBalancer {
 
 impl Balancer for WeightedBalancer {
     fn next_server(&self) -> Option<&Server> {
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
         if self.servers.is_empty() {
             return None;
         }
 
-        let total = self.cached_total_weight.load(Ordering::Relaxed);
-        if total == 0 {
-            // All servers have zero weight or are unhealthy, return first
-            return self.servers.first().map(|s| &s.config);
-        }
+        // When every healthy server has zero weight, treat them as equal
+        let weighted = self.cached_total_weight.load(Ordering::Relaxed) > 0;
 
         let mut best_idx = None;
         let mut best_weight = i64::MIN;
+        let mut total = 0i64;
 
         // Smooth weighted round-robin
         for (idx, server) in self.servers.iter().enumerate() {
@@ -75,8 +77,18 @@ impl Balancer for WeightedBalancer {
                 continue;
             }
 
-            let ew = server.effective_weight.load(Ordering::Relaxed) as i64;
+            let ew = if weighted {
+                server.effective_weight.load(Ordering::Relaxed) as i64
+            } else {
+                1
+            };
+            if ew == 0 {
+                // Zero-weight servers never receive traffic alongside weighted ones
+                continue;
+            }
+
             let cw = server.current_weight.fetch_add(ew, Ordering::Relaxed) + ew;
+            total += ew;
 
             if best_idx.is_none() || cw > best_weight {
                 best_weight = cw;
@@ -84,16 +96,14 @@ impl Balancer for WeightedBalancer {
             }
         }
 
-        if let Some(idx) = best_idx {
-            // Subtract total weight from selected server
-            self.servers[idx]
-                .current_weight
-                .fetch_sub(total, Ordering::Relaxed);
-            return Some(&self.servers[idx].config);
-        }
+        // No healthy servers left
+        let idx = best_idx?;
 
-        // Fallback
-        self.servers.first().map(|s| &s.config)
+        // Subtract total weight from selected server
+        self.servers[idx]
+            .current_weight
+            .fetch_sub(total, Ordering::Relaxed);
+        Some((idx, &self.servers[idx].config))
     }
 
     fn mark_healthy(&self, index: usize) {
@@ -113,6 +123,12 @@ impl Balancer for WeightedBalancer {
         }
     }
 
+    fn is_healthy(&self, index: usize) -> bool {
+        self.servers
+            .get(index)
+            .is_some_and(|s| s.healthy.load(Ordering::Relaxed))
+    }
+
     fn find_server_index(&self, url: &str) -> Option<usize> {
         self.servers.iter().position(|s| s.config.url == url)
     }
@@ -211,4 +227,75 @@ mod tests {
         assert!(counts[0] > counts[1]);
         assert!(counts[1] > counts[2]);
     }
+
+    fn servers_with_weights(weights: &[u32]) -> Vec<Server> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| Server {
+                url: format!("http://server{}:8080", i),
+                weight,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_all_unhealthy_returns_none() {
+        let balancer = WeightedBalancer::new(make_weighted_servers());
+        for i in 0..3 {
+            balancer.mark_unhealthy(i);
+        }
+        assert!(balancer.next_server().is_none());
+
+        balancer.mark_healthy(1);
+        assert_eq!(balancer.next_server().unwrap().url, "http://server1:8080");
+    }
+
+    #[test]
+    fn test_all_zero_weights_treated_as_equal() {
+        let balancer = WeightedBalancer::new(servers_with_weights(&[0, 0, 0]));
+
+        let mut counts = [0u32; 3];
+        for _ in 0..30 {
+            let (idx, _) = balancer.next_server_indexed().unwrap();
+            counts[idx] += 1;
+        }
+        assert_eq!(counts, [10, 10, 10]);
+    }
+
+    #[test]
+    fn test_zero_weight_server_not_selected() {
+        let balancer = WeightedBalancer::new(servers_with_weights(&[0, 2, 1]));
+        for _ in 0..30 {
+            let (idx, _) = balancer.next_server_indexed().unwrap();
+            assert_ne!(idx, 0);
+        }
+    }
+
+    #[test]
+    fn test_healthy_weights_sum_to_zero() {
+        let balancer = WeightedBalancer::new(servers_with_weights(&[4, 0, 0]));
+        balancer.mark_unhealthy(0);
+
+        // Only zero-weight servers remain: share the load equally instead of failing
+        let mut counts = [0u32; 3];
+        for _ in 0..10 {
+            let (idx, _) = balancer.next_server_indexed().unwrap();
+            counts[idx] += 1;
+        }
+        assert_eq!(counts, [0, 5, 5]);
+    }
+
+    #[test]
+    fn test_single_server() {
+        let balancer = WeightedBalancer::new(servers_with_weights(&[3]));
+        for _ in 0..5 {
+            assert_eq!(balancer.next_server().unwrap().url, "http://server0:8080");
+        }
+        balancer.mark_unhealthy(0);
+        assert!(balancer.next_server().is_none());
+    }
 }
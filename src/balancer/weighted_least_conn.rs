@@ -0,0 +1,196 @@
+use super::Balancer;
+use crate::config::Server;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Weighted least connections load balancer
+/// Selects the server minimizing `active_connections / weight`, so a server
+/// with weight 4 carries roughly 4x the load of a weight-1 server
+pub struct WeightedLeastConnBalancer {
+    servers: Vec<WeightedLeastConnServer>,
+}
+
+struct WeightedLeastConnServer {
+    config: Server,
+    healthy: AtomicBool,
+    active_connections: AtomicUsize,
+}
+
+impl WeightedLeastConnBalancer {
+    /// Create a weighted least-connections balancer with all servers healthy and idle.
+    pub fn new(servers: Vec<Server>) -> Self {
+        let servers = servers
+            .into_iter()
+            .map(|config| WeightedLeastConnServer {
+                config,
+                healthy: AtomicBool::new(true),
+                active_connections: AtomicUsize::new(0),
+            })
+            .collect();
+
+        Self { servers }
+    }
+
+    fn select_index(&self) -> Option<usize> {
+        // When every healthy server has zero weight, treat them as equal
+        let weighted = self
+            .servers
+            .iter()
+            .any(|s| s.config.weight > 0 && s.healthy.load(Ordering::Relaxed));
+
+        let mut best: Option<(usize, u64, u64)> = None; // (index, conns, weight)
+
+        for (idx, server) in self.servers.iter().enumerate() {
+            let weight = if weighted {
+                server.config.weight as u64
+            } else {
+                1
+            };
+            if weight == 0 || !server.healthy.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let conns = server.active_connections.load(Ordering::Relaxed) as u64;
+
+            // Compare conns/weight without floats: a/wa < b/wb  <=>  a*wb < b*wa.
+            // Strict comparison keeps the lowest index on ties.
+            let better = match best {
+                None => true,
+                Some((_, best_conns, best_weight)) => conns * best_weight < best_conns * weight,
+            };
+
+            if better {
+                best = Some((idx, conns, weight));
+            }
+        }
+
+        best.map(|(idx, _, _)| idx)
+    }
+}
+
+impl Balancer for WeightedLeastConnBalancer {
+    fn next_server(&self) -> Option<&Server> {
+        self.next_server_indexed().map(|(_, s)| s)
+    }
+
+    fn next_server_indexed(&self) -> Option<(usize, &Server)> {
+        self.select_index().map(|idx| (idx, &self.servers[idx].config))
+    }
+
+    fn mark_healthy(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.healthy.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn mark_unhealthy(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.healthy.store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn is_healthy(&self, index: usize) -> bool {
+        self.servers
+            .get(index)
+            .is_some_and(|s| s.healthy.load(Ordering::Relaxed))
+    }
+
+    fn find_server_index(&self, url: &str) -> Option<usize> {
+        self.servers.iter().position(|s| s.config.url == url)
+    }
+
+    /// Increment connection count for a server (call when starting a request)
+    fn acquire(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.active_connections.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Decrement connection count for a server (call when request completes)
+    fn release(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            let _ = server.active_connections.fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |c| c.checked_sub(1),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_servers(weights: &[u32]) -> Vec<Server> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| Server {
+                url: format!("http://server{}:8080", i),
+                weight,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ties_break_by_index() {
+        let balancer = WeightedLeastConnBalancer::new(make_servers(&[1, 1, 1]));
+        assert!(balancer.next_server().unwrap().url.contains("server0"));
+
+        balancer.acquire(0);
+        assert!(balancer.next_server().unwrap().url.contains("server1"));
+    }
+
+    #[test]
+    fn test_zero_weight_never_selected() {
+        let balancer = WeightedLeastConnBalancer::new(make_servers(&[0, 1]));
+        for _ in 0..10 {
+            let server = balancer.next_server().unwrap();
+            assert!(server.url.contains("server1"));
+            balancer.acquire(1);
+        }
+
+    }
+
+    #[test]
+    fn test_all_zero_weights_treated_as_equal() {
+        let balancer = WeightedLeastConnBalancer::new(make_servers(&[0, 0]));
+        assert!(balancer.next_server().unwrap().url.contains("server0"));
+        balancer.acquire(0);
+        assert!(balancer.next_server().unwrap().url.contains("server1"));
+
+        // Losing the only weighted server leaves the zero-weight ones sharing load
+        let balancer = WeightedLeastConnBalancer::new(make_servers(&[2, 0]));
+        balancer.mark_unhealthy(0);
+        assert!(balancer.next_server().unwrap().url.contains("server1"));
+    }
+
+    #[test]
+    fn test_skips_unhealthy() {
+        let balancer = WeightedLeastConnBalancer::new(make_servers(&[4, 1]));
+        balancer.mark_unhealthy(0);
+        assert!(balancer.next_server().unwrap().url.contains("server1"));
+
+        balancer.mark_unhealthy(1);
+        assert!(balancer.next_server().is_none());
+    }
+
+    #[test]
+    fn test_steady_load_matches_weights() {
+        let balancer = WeightedLeastConnBalancer::new(make_servers(&[1, 2, 4]));
+
+        // Hold every connection open so the assigned counts reflect steady-state load
+        let mut counts = [0usize; 3];
+        for _ in 0..700 {
+            let url = balancer.next_server().unwrap().url.clone();
+            let idx = balancer.find_server_index(&url).unwrap();
+            balancer.acquire(idx);
+            counts[idx] += 1;
+        }
+
+        assert_eq!(counts, [100, 200, 400]);
+    }
+}
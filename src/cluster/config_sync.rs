@@ -0,0 +1,244 @@
+use crate::config::Config;
+use crate::server::ConfigReloader;
+use crate::store::Store;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+/// Propagates config changes between cluster nodes through the store
+///
+/// Configs applied locally (file reload, admin API) are published with
+/// `config_set`, and every node applies store versions newer than the last one
+/// it published or applied. A config received this way is applied without
+/// being published again, and a node skips the change notification for its own
+/// publish by version, so a change goes round the cluster exactly once.
+pub struct ConfigSync {
+    store: Arc<dyn Store>,
+    reloader: Arc<ConfigReloader>,
+    /// Newest store config version this node has published or applied
+    synced_version: AtomicU64,
+    shutdown_tx: tokio::sync::broadcast::Sender<()>,
+}
+
+impl ConfigSync {
+    /// Create a config sync for the node running `reloader`'s config
+    pub fn new(store: Arc<dyn Store>, reloader: Arc<ConfigReloader>) -> Arc<Self> {
+        let (shutdown_tx, _) = tokio::sync::broadcast::channel(1);
+
+        Arc::new(Self {
+            store,
+            reloader,
+            synced_version: AtomicU64::new(0),
+            shutdown_tx,
+        })
+    }
+
+    /// Catch up with the cluster's config, then publish local changes and
+    /// apply remote ones until shutdown
+    pub async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let mut remote = self
+            .store
+            .subscribe_config_changes()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to subscribe to config changes: {}", e))?;
+        let mut local = self.reloader.subscribe();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+
+        if let Err(e) = self.apply_newer().await {
+            warn!("Failed to apply cluster config: {:#}", e);
+        }
+
+        let sync = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    change = local.recv() => {
+                        let config = match change {
+                            Ok(config) => config,
+                            // Only the newest config matters
+                            Err(RecvError::Lagged(_)) => sync.reloader.current(),
+                            Err(RecvError::Closed) => break,
+                        };
+                        if let Err(e) = sync.publish(&config).await {
+                            warn!("Failed to publish config to the cluster: {:#}", e);
+                        }
+                    }
+                    change = remote.recv() => {
+                        if let Err(RecvError::Closed) = change {
+                            break;
+                        }
+                        if let Err(e) = sync.apply_newer().await {
+                            warn!("Failed to apply cluster config: {:#}", e);
+                        }
+                    }
+                    _ = shutdown_rx.recv() => {
+                        debug!("Config sync stopped");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Publish a locally applied config, returning its store version
+    async fn publish(&self, config: &Config) -> anyhow::Result<u64> {
+        let yaml = serde_yml::to_string(config)?;
+        let version = self
+            .store
+            .config_set(&yaml)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to store config: {}", e))?;
+        self.synced_version.fetch_max(version, Ordering::AcqRel);
+
+        info!("Published config version {} to the cluster", version);
+        Ok(version)
+    }
+
+    /// Apply the store's config if it is newer than the last one this node
+    /// published or applied. Returns whether a config was applied.
+    async fn apply_newer(&self) -> anyhow::Result<bool> {
+        let version = self
+            .store
+            .config_version()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read config version: {}", e))?;
+        if version <= self.synced_version.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+
+        let Some(yaml) = self
+            .store
+            .config_get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read config: {}", e))?
+        else {
+            return Ok(false);
+        };
+
+        // A config this node rejects is not retried on every notification
+        self.synced_version.fetch_max(version, Ordering::AcqRel);
+
+        let mut config = Config::from_yaml(&yaml)?;
+        // Node identity stays with the node
+        config.cluster = self.reloader.current().cluster.clone();
+        self.reloader.apply_remote(config)?;
+
+        info!("Applied cluster config version {}", version);
+        Ok(true)
+    }
+
+    /// Stop publishing and applying config changes
+    pub fn shutdown(&self) {
+        let _ = self.shutdown_tx.send(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::SharedState;
+    use crate::store::LocalStore;
+    use arc_swap::ArcSwap;
+    use std::time::Duration;
+
+    const CONFIG: &str = r#"
+entryPoints:
+  web:
+    address: ":0"
+cluster:
+  nodeId: NODE
+http:
+  routers:
+    api:
+      rule: "PathPrefix(`/`)"
+      service: api
+  services:
+    api:
+      loadBalancer:
+        servers:
+          - url: "http://127.0.0.1:9"
+"#;
+
+    fn config(node_id: &str) -> Config {
+        Config::from_yaml(&CONFIG.replace("NODE", node_id)).unwrap()
+    }
+
+    async fn node(store: &Arc<dyn Store>, node_id: &str) -> (Arc<ConfigSync>, Arc<ConfigReloader>) {
+        let config = config(node_id);
+        let state = Arc::new(SharedState::new(&config));
+        let reloader = Arc::new(ConfigReloader::new(
+            "config.yaml".into(),
+            Arc::new(ArcSwap::from_pointee(config)),
+            state,
+        ));
+        let sync = ConfigSync::new(Arc::clone(store), Arc::clone(&reloader));
+        sync.start().await.unwrap();
+        (sync, reloader)
+    }
+
+    async fn wait_for_router(reloader: &ConfigReloader, name: &str) {
+        for _ in 0..200 {
+            if reloader.current().routers().contains_key(name) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("router '{}' never arrived", name);
+    }
+
+    #[tokio::test]
+    async fn test_config_applied_on_one_node_reaches_the_other() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let (sync_a, node_a) = node(&store, "node-a").await;
+        let (sync_b, node_b) = node(&store, "node-b").await;
+
+        // A local reload (or admin PUT) on node A
+        let mut updated = config("node-a");
+        let router = updated.routers()["api"].clone();
+        updated.http.as_mut().unwrap().routers.insert("extra".to_string(), router);
+        assert_eq!(node_a.apply(updated).unwrap(), 2);
+
+        wait_for_router(&node_b, "extra").await;
+        assert_eq!(node_b.version(), 2);
+        // Node B keeps its own identity
+        let cluster = node_b.current().cluster.clone().unwrap();
+        assert_eq!(cluster.node_id.as_deref(), Some("node-b"));
+
+        // Neither node re-publishes or re-applies the change
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.config_version().await.unwrap(), 1);
+        assert_eq!(node_a.version(), 2);
+        assert_eq!(node_b.version(), 2);
+
+        // A node joining later catches up on start
+        let (sync_c, node_c) = node(&store, "node-c").await;
+        assert!(node_c.current().routers().contains_key("extra"));
+
+        for sync in [sync_a, sync_b, sync_c] {
+            sync.shutdown();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_remote_config_is_not_retried() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let (sync, reloader) = node(&store, "node-a").await;
+
+        store.config_set("http: [not, a, config]").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(sync.apply_newer().await.is_ok_and(|applied| !applied));
+        assert_eq!(reloader.version(), 1);
+
+        // The next good config still gets through
+        let mut updated = config("node-b");
+        let router = updated.routers()["api"].clone();
+        updated.http.as_mut().unwrap().routers.insert("extra".to_string(), router);
+        store.config_set(&serde_yml::to_string(&updated).unwrap()).await.unwrap();
+        wait_for_router(&reloader, "extra").await;
+
+        sync.shutdown();
+    }
+}
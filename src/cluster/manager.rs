@@ -1,7 +1,8 @@
 use crate::config::ClusterConfig;
+use crate::server::ConnectionTracker;
 use crate::store::{NodeInfo, NodeStatus, Store};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
@@ -11,9 +12,11 @@ pub struct ClusterManager {
     advertise_address: String,
     store: Arc<dyn Store>,
     config: ClusterConfig,
-    is_leader: AtomicBool,
+    is_leader: Arc<AtomicBool>,
     is_draining: AtomicBool,
     active_connections: RwLock<u64>,
+    /// Source of the connection count reported in heartbeats, once attached
+    connection_tracker: OnceLock<Arc<ConnectionTracker>>,
     shutdown_tx: tokio::sync::broadcast::Sender<()>,
 }
 
@@ -46,9 +49,10 @@ impl ClusterManager {
             advertise_address,
             store,
             config,
-            is_leader: AtomicBool::new(false),
+            is_leader: Arc::new(AtomicBool::new(false)),
             is_draining: AtomicBool::new(false),
             active_connections: RwLock::new(0),
+            connection_tracker: OnceLock::new(),
             shutdown_tx,
         });
 
@@ -78,6 +82,12 @@ impl ClusterManager {
         self.is_leader.load(Ordering::Relaxed)
     }
 
+    /// Health check leadership flag, kept current by the leader election; hand
+    /// it to a [`DistributedHealthManager`](crate::health::DistributedHealthManager)
+    pub fn health_check_leader_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.is_leader)
+    }
+
     /// Check if this node is draining
     pub fn is_draining(&self) -> bool {
         self.is_draining.load(Ordering::Relaxed)
@@ -93,6 +103,22 @@ impl ClusterManager {
         *self.active_connections.write().await = count;
     }
 
+    /// Report the tracker's active connections in heartbeats, in place of the
+    /// count set with [`update_connections`](Self::update_connections)
+    pub fn track_connections(&self, tracker: Arc<ConnectionTracker>) {
+        if self.connection_tracker.set(tracker).is_err() {
+            warn!("Cluster manager already tracks a connection tracker");
+        }
+    }
+
+    /// Active connections to report for this node
+    async fn current_connections(&self) -> u64 {
+        match self.connection_tracker.get() {
+            Some(tracker) => tracker.active_count() as u64,
+            None => *self.active_connections.read().await,
+        }
+    }
+
     /// Start draining this node
     pub async fn start_drain(&self) -> anyhow::Result<()> {
         info!("Starting node drain: {}", self.node_id);
@@ -104,6 +130,31 @@ impl ClusterManager {
             .await
             .map_err(|e| anyhow::anyhow!("Failed to set drain status: {}", e))?;
 
+        // Hand health checking to a node that will stay up
+        if self.is_leader.swap(false, Ordering::Relaxed) {
+            self.store
+                .leader_release("health_check", &self.node_id)
+                .await
+                .ok();
+            info!("Released health check leadership for drain");
+        }
+
+        Ok(())
+    }
+
+    /// Stop draining this node: mark it active again and rejoin the
+    /// health check leader election
+    pub async fn stop_drain(&self) -> anyhow::Result<()> {
+        info!("Stopping node drain: {}", self.node_id);
+
+        self.store
+            .node_set_status(&self.node_id, NodeStatus::Active)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to clear drain status: {}", e))?;
+        self.is_draining.store(false, Ordering::Release);
+
+        self.contend_for_leadership().await;
+
         Ok(())
     }
 
@@ -133,8 +184,12 @@ impl ClusterManager {
         let info = NodeInfo {
             node_id: self.node_id.clone(),
             address: self.advertise_address.clone(),
-            status: NodeStatus::Active,
-            active_connections: 0,
+            status: if self.is_draining() {
+                NodeStatus::Draining
+            } else {
+                NodeStatus::Active
+            },
+            active_connections: self.current_connections().await,
             last_heartbeat: current_time_millis(),
             started_at: current_time_millis(),
             version: env!("CARGO_PKG_VERSION").to_string(),
@@ -159,11 +214,11 @@ impl ClusterManager {
             loop {
                 tokio::select! {
                     _ = interval_timer.tick() => {
-                        let connections = *self.active_connections.read().await;
-                        if let Err(e) = self.store.node_heartbeat(&self.node_id, connections).await {
-                            warn!("Failed to send heartbeat: {}", e);
-                        } else {
-                            debug!("Heartbeat sent: connections={}", connections);
+                        self.heartbeat().await;
+                        if self.is_health_check_leader()
+                            && let Err(e) = self.prune_stale_nodes().await
+                        {
+                            warn!("Failed to prune stale nodes: {}", e);
                         }
                     }
                     _ = shutdown_rx.recv() => {
@@ -175,10 +230,60 @@ impl ClusterManager {
         });
     }
 
+    /// Refresh this node's heartbeat, re-registering it if it was pruned
+    /// (e.g. after a network partition outlasted the node timeout)
+    async fn heartbeat(&self) {
+        let connections = self.current_connections().await;
+        match self.store.node_get(&self.node_id).await {
+            Ok(Some(_)) => match self.store.node_heartbeat(&self.node_id, connections).await {
+                Ok(()) => debug!("Heartbeat sent: connections={}", connections),
+                Err(e) => warn!("Failed to send heartbeat: {}", e),
+            },
+            Ok(None) => {
+                warn!("Node {} is missing from the cluster registry, re-registering", self.node_id);
+                if let Err(e) = self.register_node().await {
+                    warn!("{}", e);
+                }
+            }
+            Err(e) => warn!("Failed to send heartbeat: {}", e),
+        }
+    }
+
+    /// Remove nodes that have not heartbeated within the node timeout.
+    /// Run by the leader; returns the number of nodes removed.
+    async fn prune_stale_nodes(&self) -> anyhow::Result<usize> {
+        let nodes = self
+            .store
+            .node_list()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to list nodes: {}", e))?;
+
+        let now = current_time_millis();
+        let timeout_ms = self.config.node_timeout.as_std().as_millis() as u64;
+        let mut removed = 0;
+
+        for node in nodes {
+            if node.node_id == self.node_id || now.saturating_sub(node.last_heartbeat) < timeout_ms {
+                continue;
+            }
+            self.store
+                .node_deregister(&node.node_id)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to remove node {}: {}", node.node_id, e))?;
+            info!(
+                "Removed node {} from the cluster: no heartbeat for {}ms",
+                node.node_id,
+                now.saturating_sub(node.last_heartbeat)
+            );
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
     /// Start the leader election task
     fn start_leader_election_task(self: Arc<Self>) {
-        let ttl = self.config.leader_ttl.as_std();
-        let election_interval = ttl / 3; // Try to acquire/renew at 1/3 of TTL
+        let election_interval = self.config.leader_ttl.as_std() / 3; // Try to acquire/renew at 1/3 of TTL
         let mut shutdown_rx = self.shutdown_tx.subscribe();
 
         tokio::spawn(async move {
@@ -187,20 +292,7 @@ impl ClusterManager {
             loop {
                 tokio::select! {
                     _ = interval_timer.tick() => {
-                        match self.store.leader_acquire("health_check", &self.node_id, ttl).await {
-                            Ok(acquired) => {
-                                let was_leader = self.is_leader.swap(acquired, Ordering::Relaxed);
-                                if acquired && !was_leader {
-                                    info!("Acquired health check leadership");
-                                } else if !acquired && was_leader {
-                                    info!("Lost health check leadership");
-                                }
-                            }
-                            Err(e) => {
-                                warn!("Leader election failed: {}", e);
-                                self.is_leader.store(false, Ordering::Relaxed);
-                            }
-                        }
+                        self.contend_for_leadership().await;
                     }
                     _ = shutdown_rx.recv() => {
                         debug!("Leader election task shutting down");
@@ -211,6 +303,29 @@ impl ClusterManager {
         });
     }
 
+    /// Try to acquire or renew health check leadership; draining nodes sit out
+    async fn contend_for_leadership(&self) {
+        if self.is_draining() {
+            return;
+        }
+
+        let ttl = self.config.leader_ttl.as_std();
+        match self.store.leader_acquire("health_check", &self.node_id, ttl).await {
+            Ok(acquired) => {
+                let was_leader = self.is_leader.swap(acquired, Ordering::Relaxed);
+                if acquired && !was_leader {
+                    info!("Acquired health check leadership");
+                } else if !acquired && was_leader {
+                    info!("Lost health check leadership");
+                }
+            }
+            Err(e) => {
+                warn!("Leader election failed: {}", e);
+                self.is_leader.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
     /// Start listening for drain events from other nodes
     fn start_drain_listener(self: Arc<Self>) {
         let mut shutdown_rx = self.shutdown_tx.subscribe();
@@ -269,7 +384,7 @@ impl ClusterManager {
             .into_iter()
             .filter(|n| {
                 n.status != NodeStatus::Unhealthy
-                    && (now - n.last_heartbeat) < timeout_ms
+                    && now.saturating_sub(n.last_heartbeat) < timeout_ms
             })
             .collect())
     }
@@ -313,3 +428,121 @@ fn current_time_millis() -> u64 {
         .unwrap_or_default()
         .as_millis() as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Duration;
+    use crate::store::LocalStore;
+
+    /// `node_id` with every other setting at its config-file default
+    fn cluster_config(node_id: &str) -> ClusterConfig {
+        ClusterConfig {
+            enabled: false,
+            node_id: Some(node_id.to_string()),
+            advertise_address: None,
+            store: None,
+            heartbeat_interval: Duration::from_secs(10),
+            node_timeout: Duration::from_secs(30),
+            drain_timeout: Duration::from_secs(30),
+            leader_ttl: Duration::from_secs(15),
+            config_providers: vec![],
+        }
+    }
+
+    async fn manager(store: Arc<dyn Store>) -> Arc<ClusterManager> {
+        ClusterManager::new(cluster_config("node-a"), store).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_drain_and_undrain_update_node_status_and_leadership() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let cluster = manager(Arc::clone(&store)).await;
+        cluster.contend_for_leadership().await;
+        assert!(cluster.is_health_check_leader());
+
+        cluster.start_drain().await.unwrap();
+        assert!(cluster.is_draining());
+        assert!(!cluster.is_health_check_leader());
+        let node = store.node_get("node-a").await.unwrap().unwrap();
+        assert_eq!(node.status, NodeStatus::Draining);
+
+        // A draining node does not take leadership back
+        cluster.contend_for_leadership().await;
+        assert!(!cluster.is_health_check_leader());
+
+        cluster.stop_drain().await.unwrap();
+        assert!(!cluster.is_draining());
+        assert!(cluster.is_health_check_leader());
+        let node = store.node_get("node-a").await.unwrap().unwrap();
+        assert_eq!(node.status, NodeStatus::Active);
+
+        cluster.shutdown().await.unwrap();
+    }
+
+    async fn node(store: &Arc<dyn Store>, node_id: &str) -> Arc<ClusterManager> {
+        let config = ClusterConfig {
+            heartbeat_interval: Duration::from_millis(50),
+            node_timeout: Duration::from_millis(300),
+            leader_ttl: Duration::from_secs(30),
+            ..cluster_config(node_id)
+        };
+        ClusterManager::new(config, Arc::clone(store)).await.unwrap()
+    }
+
+    async fn node_ids(store: &Arc<dyn Store>) -> Vec<String> {
+        let mut ids: Vec<String> = store
+            .node_list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|n| n.node_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_leader_evicts_node_that_stops_heartbeating() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let leader = node(&store, "node-a").await;
+        leader.contend_for_leadership().await;
+        assert!(leader.is_health_check_leader());
+        let follower = node(&store, "node-b").await;
+
+        let tracker = Arc::new(ConnectionTracker::new());
+        tracker.connection_start();
+        tracker.connection_start();
+        leader.track_connections(tracker);
+
+        // Both nodes keep each other alive well past the timeout
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(node_ids(&store).await, ["node-a", "node-b"]);
+        assert_eq!(leader.get_cluster_stats().await.node_count, 2);
+        let node_a = store.node_get("node-a").await.unwrap().unwrap();
+        assert_eq!(node_a.active_connections, 2);
+
+        // node-b stops heartbeating without deregistering, as if it crashed
+        let _ = follower.shutdown_tx.send(());
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert_eq!(node_ids(&store).await, ["node-a"]);
+
+        leader.shutdown().await.unwrap();
+        assert!(node_ids(&store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_evicted_node_reregisters_on_next_heartbeat() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let cluster = node(&store, "node-a").await;
+        cluster.start_drain().await.unwrap();
+
+        store.node_deregister("node-a").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+
+        let node = store.node_get("node-a").await.unwrap().unwrap();
+        assert_eq!(node.status, NodeStatus::Draining);
+
+        cluster.shutdown().await.unwrap();
+    }
+}
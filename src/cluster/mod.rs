@@ -1,15 +1,19 @@
-//! Cluster coordination: node registration, leader election, health check delegation, and config providers.
+//! Cluster coordination: node registration, leader election, health check delegation, config sync, and config providers.
 
+mod config_sync;
 mod manager;
 mod provider;
+mod sigv4;
 
+/// Publishes local config changes to the store and applies the cluster's.
+pub use config_sync::ConfigSync;
 /// Manages node registration, heartbeats, leader election, and graceful draining.
 pub use manager::ClusterManager;
 /// Trait and implementations for fetching configuration from external sources.
-pub use provider::{ConfigProvider, HttpConfigProvider};
+pub use provider::{ConfigProvider, ConsulConfigProvider, HttpConfigProvider, S3ConfigProvider};
 
 use crate::config::{ClusterConfig, StoreConfig as ConfigStoreConfig};
-use crate::store::{Store, ValkeyConfig, ValkeyStore, LocalStore};
+use crate::store::{ConsulConfig, ConsulStore, Store, ValkeyConfig, ValkeyStore, LocalStore};
 use std::sync::Arc;
 use tracing::info;
 
@@ -47,6 +51,20 @@ pub async fn create_store_from_config(config: &ClusterConfig) -> anyhow::Result<
             info!("Connected to distributed store (Valkey/Redis)");
             Ok(Arc::new(store))
         }
+        Some(ConfigStoreConfig::Consul(consul_config)) => {
+            let store = ConsulStore::new(&ConsulConfig {
+                endpoint: consul_config.endpoint.clone(),
+                token: consul_config.token.clone(),
+                datacenter: consul_config.datacenter.clone(),
+                key_prefix: consul_config.root_key.clone(),
+                timeout: consul_config.timeout,
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to connect to Consul: {}", e))?;
+
+            info!("Connected to distributed store (Consul)");
+            Ok(Arc::new(store))
+        }
         Some(ConfigStoreConfig::Local) | None => {
             info!("Using local in-memory store (single node mode)");
             Ok(Arc::new(LocalStore::new()))
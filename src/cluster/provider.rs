@@ -1,4 +1,8 @@
-use crate::config::{Config, ConfigProviderConfig, HttpProviderConfig};
+use super::sigv4;
+use crate::config::{
+    merge_configs, AwsCredentials, Config, ConfigProviderConfig, ConsulProviderConfig, HttpProviderConfig,
+    HttpProviderTls, S3ProviderConfig,
+};
 use async_trait::async_trait;
 use std::sync::Arc;
 use std::time::Duration;
@@ -28,50 +32,55 @@ pub struct HttpConfigProvider {
 impl HttpConfigProvider {
     /// Create a new HTTP config provider
     pub fn new(config: HttpProviderConfig) -> anyhow::Result<Self> {
-        let mut builder = reqwest::Client::builder()
-            .timeout(config.timeout.as_std())
-            .connect_timeout(Duration::from_secs(10));
-
-        // Configure TLS if needed
-        if let Some(tls) = &config.tls {
-            if tls.insecure_skip_verify {
-                builder = builder.danger_accept_invalid_certs(true);
-            }
+        let client = http_client(config.timeout.as_std(), config.tls.as_ref())?;
 
-            // Add CA cert if provided
-            if let Some(ca_path) = &tls.ca {
-                let ca_cert = std::fs::read(ca_path)
-                    .map_err(|e| anyhow::anyhow!("Failed to read CA cert: {}", e))?;
-                let cert = reqwest::Certificate::from_pem(&ca_cert)
-                    .map_err(|e| anyhow::anyhow!("Failed to parse CA cert: {}", e))?;
-                builder = builder.add_root_certificate(cert);
-            }
+        Ok(Self {
+            config,
+            client,
+            last_etag: RwLock::new(None),
+        })
+    }
+}
 
-            // Add client cert if provided
-            if let (Some(cert_path), Some(key_path)) = (&tls.cert, &tls.key) {
-                let cert = std::fs::read(cert_path)
-                    .map_err(|e| anyhow::anyhow!("Failed to read client cert: {}", e))?;
-                let key = std::fs::read(key_path)
-                    .map_err(|e| anyhow::anyhow!("Failed to read client key: {}", e))?;
+/// HTTP client for a provider, with optional custom CA and client certificate
+fn http_client(timeout: Duration, tls: Option<&HttpProviderTls>) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(Duration::from_secs(10));
 
-                let mut pem = cert;
-                pem.extend_from_slice(&key);
+    // Configure TLS if needed
+    if let Some(tls) = tls {
+        if tls.insecure_skip_verify {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
 
-                let identity = reqwest::Identity::from_pem(&pem)
-                    .map_err(|e| anyhow::anyhow!("Failed to create identity: {}", e))?;
-                builder = builder.identity(identity);
-            }
+        // Add CA cert if provided
+        if let Some(ca_path) = &tls.ca {
+            let ca_cert = std::fs::read(ca_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read CA cert: {}", e))?;
+            let cert = reqwest::Certificate::from_pem(&ca_cert)
+                .map_err(|e| anyhow::anyhow!("Failed to parse CA cert: {}", e))?;
+            builder = builder.add_root_certificate(cert);
         }
 
-        let client = builder.build()
-            .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))?;
+        // Add client cert if provided
+        if let (Some(cert_path), Some(key_path)) = (&tls.cert, &tls.key) {
+            let cert = std::fs::read(cert_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read client cert: {}", e))?;
+            let key = std::fs::read(key_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read client key: {}", e))?;
 
-        Ok(Self {
-            config,
-            client,
-            last_etag: RwLock::new(None),
-        })
+            let mut pem = cert;
+            pem.extend_from_slice(&key);
+
+            let identity = reqwest::Identity::from_pem(&pem)
+                .map_err(|e| anyhow::anyhow!("Failed to create identity: {}", e))?;
+            builder = builder.identity(identity);
+        }
     }
+
+    builder.build()
+        .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))
 }
 
 #[async_trait]
@@ -131,10 +140,269 @@ impl ConfigProvider for HttpConfigProvider {
     }
 }
 
+/// S3 configuration provider, for AWS S3 or an S3-compatible `endpoint` such as MinIO
+pub struct S3ConfigProvider {
+    config: S3ProviderConfig,
+    client: reqwest::Client,
+    url: url::Url,
+    credentials: Option<AwsCredentials>,
+    last_etag: RwLock<Option<String>>,
+    last_modified: RwLock<Option<String>>,
+}
+
+impl S3ConfigProvider {
+    /// Create a new S3 config provider
+    ///
+    /// Requests are signed with the configured `credentials`, falling back to the
+    /// `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`/`AWS_SESSION_TOKEN` environment
+    /// variables; without either the object is fetched anonymously.
+    pub fn new(config: S3ProviderConfig) -> anyhow::Result<Self> {
+        let key = sigv4::encode_key(config.key.trim_start_matches('/'));
+        // Custom endpoints use path-style addressing, which MinIO and most
+        // S3-compatible services expect
+        let url = match &config.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), config.bucket, key),
+            None => format!("https://{}.s3.{}.amazonaws.com/{}", config.bucket, config.region, key),
+        };
+        let url = url::Url::parse(&url)
+            .map_err(|e| anyhow::anyhow!("Invalid S3 object URL '{}': {}", url, e))?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))?;
+
+        let credentials = config.credentials.clone().or_else(Self::credentials_from_env);
+
+        Ok(Self {
+            config,
+            client,
+            url,
+            credentials,
+            last_etag: RwLock::new(None),
+            last_modified: RwLock::new(None),
+        })
+    }
+
+    fn credentials_from_env() -> Option<AwsCredentials> {
+        Some(AwsCredentials {
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// `host[:port]` as sent in the `Host` header
+    fn host(&self) -> String {
+        let host = self.url.host_str().unwrap_or_default();
+        match self.url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigProvider for S3ConfigProvider {
+    async fn fetch(&self) -> anyhow::Result<String> {
+        let mut request = self.client.get(self.url.clone());
+
+        if let Some(credentials) = &self.credentials {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let signed = sigv4::sign_get(&self.host(), self.url.path(), credentials, &self.config.region, now);
+            request = request
+                .header("x-amz-content-sha256", sigv4::EMPTY_PAYLOAD_SHA256)
+                .header("x-amz-date", signed.amz_date)
+                .header("Authorization", signed.authorization);
+            if let Some(token) = &credentials.session_token {
+                request = request.header("x-amz-security-token", token);
+            }
+        }
+
+        // Conditional GET so unchanged objects come back as 304
+        if let Some(etag) = self.last_etag.read().await.as_ref() {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(modified) = self.last_modified.read().await.as_ref() {
+            request = request.header("If-Modified-Since", modified);
+        }
+
+        let response = request.send().await
+            .map_err(|e| anyhow::anyhow!("S3 request failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Err(anyhow::anyhow!("Config not modified"));
+        }
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "S3 request for s3://{}/{} failed with status: {}",
+                self.config.bucket,
+                self.config.key,
+                response.status()
+            ));
+        }
+
+        let header = |name: &str| {
+            response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+        };
+        let etag = header("etag");
+        let modified = header("last-modified");
+
+        let content = response.text().await
+            .map_err(|e| anyhow::anyhow!("Failed to read S3 object: {}", e))?;
+
+        *self.last_etag.write().await = etag;
+        *self.last_modified.write().await = modified;
+
+        Ok(content)
+    }
+
+    fn name(&self) -> &str {
+        "s3"
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.config.poll_interval.as_std()
+    }
+}
+
+/// How long a Consul blocking query waits for a change before answering anyway
+const CONSUL_BLOCKING_WAIT: Duration = Duration::from_secs(300);
+
+/// Timeout for Consul requests that are not blocking queries
+const CONSUL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Poll interval in watch mode. Each poll is a blocking query that returns on
+/// change, so this only paces retries while Consul is unreachable.
+const CONSUL_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Consul KV configuration provider
+pub struct ConsulConfigProvider {
+    config: ConsulProviderConfig,
+    client: reqwest::Client,
+    url: reqwest::Url,
+    /// `X-Consul-Index` of the last value returned by `fetch`
+    last_index: RwLock<Option<u64>>,
+}
+
+impl ConsulConfigProvider {
+    /// Create a new Consul config provider
+    pub fn new(config: ConsulProviderConfig) -> anyhow::Result<Self> {
+        let mut url = reqwest::Url::parse(&config.endpoint)
+            .map_err(|e| anyhow::anyhow!("Invalid Consul endpoint: {}", e))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid Consul endpoint: {}", config.endpoint))?
+            .pop_if_empty()
+            .push("v1")
+            .push("kv")
+            .extend(config.key.trim_matches('/').split('/'));
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_key_only("raw");
+            if let Some(dc) = &config.datacenter {
+                query.append_pair("dc", dc);
+            }
+        }
+
+        let client = http_client(CONSUL_TIMEOUT, config.tls.as_ref())?;
+
+        Ok(Self {
+            config,
+            client,
+            url,
+            last_index: RwLock::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl ConfigProvider for ConsulConfigProvider {
+    async fn fetch(&self) -> anyhow::Result<String> {
+        let last_index = *self.last_index.read().await;
+
+        // Once a value has been seen, watch mode long-polls for the next change
+        let mut url = self.url.clone();
+        let mut timeout = CONSUL_TIMEOUT;
+        if self.config.watch
+            && let Some(index) = last_index
+        {
+            url.query_pairs_mut()
+                .append_pair("index", &index.to_string())
+                .append_pair("wait", &format!("{}s", CONSUL_BLOCKING_WAIT.as_secs()));
+            // Consul adds up to wait/16 of jitter to blocking queries
+            timeout += CONSUL_BLOCKING_WAIT + CONSUL_BLOCKING_WAIT / 16;
+        }
+
+        let mut request = self.client.get(url).timeout(timeout);
+        if let Some(token) = &self.config.token {
+            request = request.header("X-Consul-Token", token);
+        }
+
+        let response = request.send().await
+            .map_err(|e| anyhow::anyhow!("Consul request failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(anyhow::anyhow!("Consul key '{}' not found", self.config.key));
+        }
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Consul request failed with status: {}",
+                response.status()
+            ));
+        }
+
+        let index = response
+            .headers()
+            .get("X-Consul-Index")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+
+        // A blocking query that timed out returns the same index. An index
+        // that went backwards means Consul was reset and counts as a change.
+        if last_index == Some(index) {
+            return Err(anyhow::anyhow!("Config not modified"));
+        }
+
+        let content = response.text().await
+            .map_err(|e| anyhow::anyhow!("Failed to read Consul value: {}", e))?;
+
+        *self.last_index.write().await = Some(index);
+
+        Ok(content)
+    }
+
+    fn name(&self) -> &str {
+        "consul"
+    }
+
+    fn poll_interval(&self) -> Duration {
+        if self.config.watch {
+            CONSUL_WATCH_INTERVAL
+        } else {
+            self.config.poll_interval.as_std()
+        }
+    }
+}
+
 /// Manages multiple config providers, polling for updates and notifying on changes.
+///
+/// The reported config is the local base config with each provider's latest
+/// config merged on top in provider order (see [`merge_configs`]); without a
+/// base config, the first provider to respond supplies the static config.
 #[allow(dead_code, clippy::type_complexity)]
 pub struct ConfigProviderManager {
     providers: Vec<Box<dyn ConfigProvider>>,
+    /// Local static config the providers are merged onto
+    base_config: Option<Config>,
+    /// Latest config fetched from each provider, by provider index
+    provider_configs: RwLock<Vec<Option<Config>>>,
     current_config: RwLock<Option<Config>>,
     on_config_change: RwLock<Option<Box<dyn Fn(Config) + Send + Sync>>>,
     shutdown_tx: tokio::sync::broadcast::Sender<()>,
@@ -152,11 +420,13 @@ impl ConfigProviderManager {
                     let provider = HttpConfigProvider::new(http_config.clone())?;
                     providers.push(Box::new(provider));
                 }
-                ConfigProviderConfig::S3(_) => {
-                    warn!("S3 config provider not yet implemented");
+                ConfigProviderConfig::S3(s3_config) => {
+                    let provider = S3ConfigProvider::new(s3_config.clone())?;
+                    providers.push(Box::new(provider));
                 }
-                ConfigProviderConfig::Consul(_) => {
-                    warn!("Consul config provider not yet implemented");
+                ConfigProviderConfig::Consul(consul_config) => {
+                    let provider = ConsulConfigProvider::new(consul_config.clone())?;
+                    providers.push(Box::new(provider));
                 }
             }
         }
@@ -164,13 +434,21 @@ impl ConfigProviderManager {
         let (shutdown_tx, _) = tokio::sync::broadcast::channel(1);
 
         Ok(Self {
+            provider_configs: RwLock::new(vec![None; providers.len()]),
             providers,
+            base_config: None,
             current_config: RwLock::new(None),
             on_config_change: RwLock::new(None),
             shutdown_tx,
         })
     }
 
+    /// Merge provider configs onto `base`, which supplies entry points and other static config
+    pub fn with_base_config(mut self, base: Config) -> Self {
+        self.base_config = Some(base);
+        self
+    }
+
     /// Set the callback for config changes
     pub async fn set_on_change<F>(&self, callback: F)
     where
@@ -222,15 +500,21 @@ impl ConfigProviderManager {
         let new_config: Config = serde_yml::from_str(&content)
             .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e))?;
 
-        // Validate
-        new_config.validate()
+        // Merge and validate; a rejected config leaves the previous one in place
+        let mut provider_configs = self.provider_configs.write().await;
+        let mut candidate = provider_configs.clone();
+        candidate[provider_idx] = Some(new_config);
+        let merged = self.merge(&candidate);
+        merged.validate()
             .map_err(|e| anyhow::anyhow!("Config validation failed: {}", e))?;
+        *provider_configs = candidate;
+        drop(provider_configs);
 
         // Check if config changed
         let current = self.current_config.read().await;
         let config_changed = current.is_none() || {
             let current_yaml = serde_yml::to_string(current.as_ref().unwrap()).unwrap_or_default();
-            let new_yaml = serde_yml::to_string(&new_config).unwrap_or_default();
+            let new_yaml = serde_yml::to_string(&merged).unwrap_or_default();
             current_yaml != new_yaml
         };
         drop(current);
@@ -239,27 +523,29 @@ impl ConfigProviderManager {
             info!("Configuration updated from {} provider", provider.name());
 
             // Update current config
-            *self.current_config.write().await = Some(new_config.clone());
+            *self.current_config.write().await = Some(merged.clone());
 
             // Call callback
             if let Some(callback) = self.on_config_change.read().await.as_ref() {
-                callback(new_config);
+                callback(merged);
             }
         }
 
         Ok(())
     }
 
-    /// Fetch config from all providers (first success wins)
+    /// Fetch config from all providers and merge the ones that respond
     pub async fn fetch_initial(&self) -> anyhow::Result<Config> {
-        for provider in &self.providers {
-            match provider.fetch().await {
-                Ok(content) => {
-                    let config: Config = serde_yml::from_str(&content)?;
-                    config.validate()?;
-                    *self.current_config.write().await = Some(config.clone());
+        let mut provider_configs = vec![None; self.providers.len()];
+        for (idx, provider) in self.providers.iter().enumerate() {
+            let fetched = provider.fetch().await.and_then(|content| {
+                serde_yml::from_str::<Config>(&content)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e))
+            });
+            match fetched {
+                Ok(config) => {
                     info!("Initial config loaded from {} provider", provider.name());
-                    return Ok(config);
+                    provider_configs[idx] = Some(config);
                 }
                 Err(e) => {
                     warn!("Provider {} failed: {}", provider.name(), e);
@@ -267,7 +553,27 @@ impl ConfigProviderManager {
             }
         }
 
-        Err(anyhow::anyhow!("All config providers failed"))
+        if provider_configs.iter().all(Option::is_none) {
+            return Err(anyhow::anyhow!("All config providers failed"));
+        }
+
+        let config = self.merge(&provider_configs);
+        config.validate()?;
+        *self.provider_configs.write().await = provider_configs;
+        *self.current_config.write().await = Some(config.clone());
+        Ok(config)
+    }
+
+    /// The base config with every provider config merged on in order. Without
+    /// a base config, the first provider's config serves as the base.
+    fn merge(&self, provider_configs: &[Option<Config>]) -> Config {
+        let mut configs = self
+            .base_config
+            .iter()
+            .chain(provider_configs.iter().flatten())
+            .cloned();
+        let base = configs.next().unwrap_or_default();
+        configs.fold(base, merge_configs)
     }
 
     /// Shutdown the provider manager
@@ -279,6 +585,7 @@ impl ConfigProviderManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{EntryPoint, HttpConfig, LoadBalancerService, Server, Service};
     use std::collections::HashMap;
 
     #[tokio::test]
@@ -295,4 +602,380 @@ mod tests {
         let provider = HttpConfigProvider::new(config);
         assert!(provider.is_ok());
     }
+
+    /// Object served by [`mock_s3`] and the requests it received
+    #[derive(Default)]
+    struct MockObject {
+        body: String,
+        etag: String,
+        requests: Vec<hyper::HeaderMap>,
+        paths: Vec<String>,
+    }
+
+    /// Minimal S3 `GetObject` that honours `If-None-Match`
+    async fn mock_s3(object: Arc<parking_lot::Mutex<MockObject>>) -> String {
+        use http_body_util::Full;
+        use hyper::body::Bytes;
+        use hyper::service::service_fn;
+        use hyper_util::rt::TokioIo;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let object = Arc::clone(&object);
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let mut object = object.lock();
+                        object.requests.push(req.headers().clone());
+                        object.paths.push(req.uri().path().to_string());
+                        let not_modified = req
+                            .headers()
+                            .get("if-none-match")
+                            .is_some_and(|etag| etag.as_bytes() == object.etag.as_bytes());
+                        let response = if not_modified {
+                            hyper::Response::builder().status(304).body(Full::new(Bytes::new()))
+                        } else {
+                            hyper::Response::builder()
+                                .header("etag", &object.etag)
+                                .header("last-modified", "Wed, 21 Oct 2026 07:28:00 GMT")
+                                .body(Full::new(Bytes::from(object.body.clone())))
+                        };
+                        async move { response }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn config_yaml(address: &str) -> String {
+        format!("entryPoints:\n  web:\n    address: \"{}\"\n", address)
+    }
+
+    fn s3_config(endpoint: String) -> S3ProviderConfig {
+        S3ProviderConfig {
+            bucket: "configs".to_string(),
+            key: "prod/trafficcop.yaml".to_string(),
+            region: "us-east-1".to_string(),
+            poll_interval: crate::config::Duration::from_secs(30),
+            endpoint: Some(endpoint),
+            credentials: Some(AwsCredentials {
+                access_key_id: "minio".to_string(),
+                secret_access_key: "minio-secret".to_string(),
+                session_token: None,
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_s3_provider_loads_and_polls_for_changes() {
+        let object = Arc::new(parking_lot::Mutex::new(MockObject {
+            body: config_yaml(":8080"),
+            etag: "\"v1\"".to_string(),
+            ..Default::default()
+        }));
+        let endpoint = mock_s3(Arc::clone(&object)).await;
+
+        let manager = ConfigProviderManager::new(&[ConfigProviderConfig::S3(s3_config(endpoint))]).unwrap();
+        let reloads = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&reloads);
+        manager
+            .set_on_change(move |config: Config| seen.lock().push(config.entry_points["web"].address.clone()))
+            .await;
+
+        // Initial load fetches and signs the object with the static credentials
+        let initial = manager.fetch_initial().await.unwrap();
+        assert_eq!(initial.entry_points["web"].address, ":8080");
+        {
+            let object = object.lock();
+            assert_eq!(object.paths[0], "/configs/prod/trafficcop.yaml");
+            let headers = &object.requests[0];
+            let authorization = headers["authorization"].to_str().unwrap();
+            assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=minio/"), "{authorization}");
+            assert!(authorization.contains("/us-east-1/s3/aws4_request"));
+            assert!(headers.contains_key("x-amz-date"));
+            assert!(!headers.contains_key("if-none-match"));
+        }
+
+        // Unchanged object: the ETag matches and nothing is reloaded
+        let err = manager.poll_provider(0).await.unwrap_err();
+        assert!(err.to_string().contains("not modified"));
+        assert_eq!(object.lock().requests[1]["if-none-match"], "\"v1\"");
+        assert!(reloads.lock().is_empty());
+
+        // Changed object triggers a reload with the new config
+        {
+            let mut object = object.lock();
+            object.body = config_yaml(":9090");
+            object.etag = "\"v2\"".to_string();
+        }
+        manager.poll_provider(0).await.unwrap();
+        assert_eq!(*reloads.lock(), vec![":9090".to_string()]);
+
+        manager.poll_provider(0).await.unwrap_err();
+        assert_eq!(object.lock().requests[3]["if-none-match"], "\"v2\"");
+        assert_eq!(reloads.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_s3_provider_rejects_invalid_config() {
+        let object = Arc::new(parking_lot::Mutex::new(MockObject {
+            body: "entryPoints: [not, a, map]".to_string(),
+            etag: "\"bad\"".to_string(),
+            ..Default::default()
+        }));
+        let endpoint = mock_s3(Arc::clone(&object)).await;
+
+        let manager = ConfigProviderManager::new(&[ConfigProviderConfig::S3(s3_config(endpoint))]).unwrap();
+        assert!(manager.fetch_initial().await.is_err());
+    }
+
+    /// KV value served by [`mock_consul`] and the query strings it received
+    #[derive(Default)]
+    struct MockKv {
+        value: String,
+        index: u64,
+        queries: Vec<String>,
+        tokens: Vec<Option<String>>,
+    }
+
+    /// Minimal Consul KV `?raw` endpoint; a blocking query at the current index
+    /// waits for [`set_kv`] or up to 5s
+    async fn mock_consul(kv: Arc<parking_lot::Mutex<MockKv>>, changed: Arc<tokio::sync::Notify>) -> String {
+        use http_body_util::Full;
+        use hyper::body::Bytes;
+        use hyper::service::service_fn;
+        use hyper_util::rt::TokioIo;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let kv = Arc::clone(&kv);
+                let changed = Arc::clone(&changed);
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let kv = Arc::clone(&kv);
+                        let changed = Arc::clone(&changed);
+                        async move {
+                            assert_eq!(req.uri().path(), "/v1/kv/trafficcop/config");
+                            let query = req.uri().query().unwrap_or_default().to_string();
+                            let index: Option<u64> = query
+                                .split('&')
+                                .find_map(|pair| pair.strip_prefix("index="))
+                                .and_then(|index| index.parse().ok());
+                            let notified = changed.notified();
+                            let blocked = {
+                                let mut kv = kv.lock();
+                                kv.queries.push(query);
+                                kv.tokens.push(
+                                    req.headers()
+                                        .get("x-consul-token")
+                                        .map(|token| token.to_str().unwrap().to_string()),
+                                );
+                                index == Some(kv.index)
+                            };
+                            if blocked {
+                                let _ = tokio::time::timeout(Duration::from_secs(5), notified).await;
+                            }
+                            let kv = kv.lock();
+                            Ok::<_, std::convert::Infallible>(
+                                hyper::Response::builder()
+                                    .header("X-Consul-Index", kv.index.to_string())
+                                    .body(Full::new(Bytes::from(kv.value.clone())))
+                                    .unwrap(),
+                            )
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn set_kv(kv: &parking_lot::Mutex<MockKv>, changed: &tokio::sync::Notify, value: &str) {
+        {
+            let mut kv = kv.lock();
+            kv.value = value.to_string();
+            kv.index += 1;
+        }
+        changed.notify_waiters();
+    }
+
+    fn consul_config(endpoint: String) -> ConsulProviderConfig {
+        ConsulProviderConfig {
+            endpoint,
+            key: "trafficcop/config".to_string(),
+            token: Some("secret-token".to_string()),
+            datacenter: Some("dc1".to_string()),
+            watch: true,
+            poll_interval: crate::config::Duration::from_secs(30),
+            tls: None,
+        }
+    }
+
+    /// A manager over one Consul provider, plus the addresses it reloaded
+    async fn consul_manager(endpoint: String) -> (Arc<ConfigProviderManager>, Arc<parking_lot::Mutex<Vec<String>>>) {
+        let manager = ConfigProviderManager::new(&[ConfigProviderConfig::Consul(consul_config(endpoint))]).unwrap();
+        let reloads = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&reloads);
+        manager
+            .set_on_change(move |config: Config| seen.lock().push(config.entry_points["web"].address.clone()))
+            .await;
+        (Arc::new(manager), reloads)
+    }
+
+    #[tokio::test]
+    async fn test_consul_provider_blocking_query_detects_change() {
+        let kv = Arc::new(parking_lot::Mutex::new(MockKv {
+            value: r#"{"entryPoints": {"web": {"address": ":8080"}}}"#.to_string(),
+            index: 7,
+            ..Default::default()
+        }));
+        let changed = Arc::new(tokio::sync::Notify::new());
+        let endpoint = mock_consul(Arc::clone(&kv), Arc::clone(&changed)).await;
+        let (manager, reloads) = consul_manager(endpoint).await;
+
+        // JSON values parse as config too
+        let initial = manager.fetch_initial().await.unwrap();
+        assert_eq!(initial.entry_points["web"].address, ":8080");
+        assert_eq!(kv.lock().queries[0], "raw&dc=dc1");
+        assert_eq!(kv.lock().tokens[0].as_deref(), Some("secret-token"));
+
+        // The next poll long-polls at the last index until the key changes
+        let poll = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            async move { manager.poll_provider(0).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!poll.is_finished());
+        assert_eq!(kv.lock().queries[1], "raw&dc=dc1&index=7&wait=300s");
+
+        set_kv(&kv, &changed, &config_yaml(":9090"));
+        tokio::time::timeout(Duration::from_secs(2), poll)
+            .await
+            .expect("blocking query should return on change")
+            .unwrap()
+            .unwrap();
+        assert_eq!(*reloads.lock(), vec![":9090".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_consul_provider_rejects_invalid_config() {
+        let kv = Arc::new(parking_lot::Mutex::new(MockKv {
+            value: config_yaml(":8080"),
+            index: 1,
+            ..Default::default()
+        }));
+        let changed = Arc::new(tokio::sync::Notify::new());
+        let endpoint = mock_consul(Arc::clone(&kv), Arc::clone(&changed)).await;
+        let (manager, reloads) = consul_manager(endpoint).await;
+        manager.fetch_initial().await.unwrap();
+
+        // Parses, but fails validation: no entry points
+        set_kv(&kv, &changed, "http:\n  routers: {}\n");
+        let err = manager.poll_provider(0).await.unwrap_err();
+        assert!(err.to_string().contains("validation failed"), "{err}");
+        assert!(reloads.lock().is_empty());
+        let current = manager.current_config.read().await;
+        assert_eq!(current.as_ref().unwrap().entry_points["web"].address, ":8080");
+        drop(current);
+
+        // A later valid value is still picked up
+        set_kv(&kv, &changed, &config_yaml(":9090"));
+        manager.poll_provider(0).await.unwrap();
+        assert_eq!(*reloads.lock(), vec![":9090".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_provider_configs_merge_onto_base_in_order() {
+        let first = Arc::new(parking_lot::Mutex::new(MockObject {
+            body: "http:\n  routers:\n    app:\n      rule: \"PathPrefix(`/v1`)\"\n      service: app\n".to_string(),
+            etag: "\"a1\"".to_string(),
+            ..Default::default()
+        }));
+        let second = Arc::new(parking_lot::Mutex::new(MockObject {
+            body: "http:\n  routers:\n    app:\n      rule: \"PathPrefix(`/v2`)\"\n      service: app\n".to_string(),
+            etag: "\"b1\"".to_string(),
+            ..Default::default()
+        }));
+        let providers = [
+            ConfigProviderConfig::S3(s3_config(mock_s3(Arc::clone(&first)).await)),
+            ConfigProviderConfig::S3(s3_config(mock_s3(Arc::clone(&second)).await)),
+        ];
+        let app = Service {
+            load_balancer: Some(LoadBalancerService {
+                servers: vec![Server {
+                    url: "http://10.0.0.1".to_string(),
+                    weight: 1,
+                    preserve_path: false,
+                    parsed_uri: None,
+                    url_arc: None,
+                }],
+                pass_host_header: true,
+                sticky: None,
+                health_check: None,
+                servers_transport: None,
+                response_forwarding: None,
+                web_socket: None,
+            }),
+            ..Default::default()
+        };
+        let web = EntryPoint {
+            address: ":8080".to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        };
+        let base = Config {
+            entry_points: HashMap::from([("web".to_string(), web)]),
+            http: Some(HttpConfig {
+                services: HashMap::from([("app".to_string(), app)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let manager = ConfigProviderManager::new(&providers).unwrap().with_base_config(base);
+
+        // Entry points and services from the base, the router from the last provider
+        let config = manager.fetch_initial().await.unwrap();
+        assert_eq!(config.entry_points["web"].address, ":8080");
+        assert!(config.services().contains_key("app"));
+        assert_eq!(config.routers()["app"].rule, "PathPrefix(`/v2`)");
+
+        // A change from the earlier provider stays shadowed by the later one
+        {
+            let mut first = first.lock();
+            first.body = first.body.replace("/v1", "/v1beta");
+            first.etag = "\"a2\"".to_string();
+        }
+        manager.poll_provider(0).await.unwrap();
+        let current = manager.current_config.read().await;
+        assert_eq!(current.as_ref().unwrap().routers()["app"].rule, "PathPrefix(`/v2`)");
+    }
+
+    #[test]
+    fn test_s3_provider_url() {
+        let mut config = s3_config("http://minio.local:9000/".to_string());
+        let provider = S3ConfigProvider::new(config.clone()).unwrap();
+        assert_eq!(provider.url.as_str(), "http://minio.local:9000/configs/prod/trafficcop.yaml");
+        assert_eq!(provider.host(), "minio.local:9000");
+
+        config.endpoint = None;
+        config.region = "eu-west-1".to_string();
+        let provider = S3ConfigProvider::new(config).unwrap();
+        assert_eq!(provider.url.as_str(), "https://configs.s3.eu-west-1.amazonaws.com/prod/trafficcop.yaml");
+        assert_eq!(provider.host(), "configs.s3.eu-west-1.amazonaws.com");
+    }
 }
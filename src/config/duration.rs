@@ -9,7 +9,7 @@ use std::time::Duration as StdDuration;
 
 /// A duration type that can be deserialized from Go-style duration strings.
 /// Supports: "300ms", "1.5s", "2m", "1h30m", "24h"
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct Duration(StdDuration);
 
 impl Duration {
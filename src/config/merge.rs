@@ -0,0 +1,291 @@
+//! Merging of dynamic configuration from config providers into the local config.
+//!
+//! The local file is authoritative for static configuration (entry points,
+//! certificate resolvers, metrics, API, logging, tracing, cluster). Providers
+//! contribute dynamic configuration (HTTP/TCP/UDP routing and TLS), where a
+//! later source replaces an earlier one's entry of the same name.
+
+use super::types::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Merge `overlay` (a config provider's config) onto `base`.
+///
+/// Routers, services, middlewares, transports and TLS options/stores from
+/// `overlay` are added to `base`, replacing same-named entries with a warning.
+/// Static sections of `overlay` that disagree with `base` are ignored with a
+/// warning. The result is not validated.
+pub fn merge_configs(base: Config, overlay: Config) -> Config {
+    let mut merged = base;
+
+    for (name, entry_point) in overlay.entry_points {
+        match merged.entry_points.get(&name) {
+            Some(existing) if same(existing, &entry_point) => {}
+            Some(_) => warn!(
+                "Config provider redefines entryPoint '{}'; keeping the local definition",
+                name
+            ),
+            None => warn!(
+                "Config provider defines entryPoint '{}'; entryPoints are static and only read from the local config",
+                name
+            ),
+        }
+    }
+    ignore_static("certificatesResolvers", &merged.certificates_resolvers, &overlay.certificates_resolvers);
+    ignore_static("providers", &merged.providers, &overlay.providers);
+    ignore_static("metrics", &merged.metrics, &overlay.metrics);
+    ignore_static("api", &merged.api, &overlay.api);
+    ignore_static("log", &merged.log, &overlay.log);
+    ignore_static("accessLog", &merged.access_log, &overlay.access_log);
+    ignore_static("tracing", &merged.tracing, &overlay.tracing);
+    ignore_static("cluster", &merged.cluster, &overlay.cluster);
+
+    if let Some(http) = overlay.http {
+        let merged_http = merged.http.get_or_insert_default();
+        merge_map("http router", &mut merged_http.routers, http.routers);
+        merge_map("http service", &mut merged_http.services, http.services);
+        merge_map("http middleware", &mut merged_http.middlewares, http.middlewares);
+        merge_map("http serversTransport", &mut merged_http.servers_transports, http.servers_transports);
+    }
+
+    if let Some(tcp) = overlay.tcp {
+        let merged_tcp = merged.tcp.get_or_insert_default();
+        merge_map("tcp router", &mut merged_tcp.routers, tcp.routers);
+        merge_map("tcp service", &mut merged_tcp.services, tcp.services);
+        merge_map("tcp middleware", &mut merged_tcp.middlewares, tcp.middlewares);
+        merge_map("tcp serversTransport", &mut merged_tcp.servers_transports, tcp.servers_transports);
+    }
+
+    if let Some(udp) = overlay.udp {
+        let merged_udp = merged.udp.get_or_insert_default();
+        merge_map("udp router", &mut merged_udp.routers, udp.routers);
+        merge_map("udp service", &mut merged_udp.services, udp.services);
+        merge_map("udp middleware", &mut merged_udp.middlewares, udp.middlewares);
+    }
+
+    if let Some(tls) = overlay.tls {
+        let merged_tls = merged.tls.get_or_insert_default();
+        for certificate in tls.certificates {
+            if !merged_tls
+                .certificates
+                .iter()
+                .any(|existing| existing.cert_file == certificate.cert_file)
+            {
+                merged_tls.certificates.push(certificate);
+            }
+        }
+        merge_map("tls options", &mut merged_tls.options, tls.options);
+        merge_map("tls store", &mut merged_tls.stores, tls.stores);
+    }
+
+    merged
+}
+
+/// Insert every overlay entry, warning when it replaces a different one
+fn merge_map<V: Serialize>(kind: &str, base: &mut HashMap<String, V>, overlay: HashMap<String, V>) {
+    for (name, value) in overlay {
+        if let Some(previous) = base.insert(name.clone(), value)
+            && !same(&previous, &base[&name])
+        {
+            warn!("Config provider overrides {} '{}'", kind, name);
+        }
+    }
+}
+
+/// Warn when a provider sets a static section differently from the local config
+fn ignore_static<T: Serialize>(section: &str, base: &T, overlay: &T) {
+    let overlay = serde_json::to_value(overlay).unwrap_or_default();
+    let unset = match &overlay {
+        serde_json::Value::Null => true,
+        serde_json::Value::Object(map) => map.is_empty(),
+        _ => false,
+    };
+    if !unset && serde_json::to_value(base).unwrap_or_default() != overlay {
+        warn!(
+            "Ignoring '{}' from config provider; static config is only read from the local config",
+            section
+        );
+    }
+}
+
+/// Whether two config values serialize identically
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_point(address: &str) -> EntryPoint {
+        EntryPoint {
+            address: address.to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        }
+    }
+
+    fn router(rule: &str, service: &str) -> Router {
+        Router {
+            entry_points: vec![],
+            rule: rule.to_string(),
+            rule_syntax: None,
+            service: service.to_string(),
+            middlewares: vec![],
+            priority: 0,
+            tls: None,
+            observability: None,
+        }
+    }
+
+    fn service(url: &str) -> Service {
+        Service {
+            load_balancer: Some(LoadBalancerService {
+                servers: vec![Server {
+                    url: url.to_string(),
+                    weight: 1,
+                    preserve_path: false,
+                    parsed_uri: None,
+                    url_arc: None,
+                }],
+                pass_host_header: true,
+                sticky: None,
+                health_check: None,
+                servers_transport: None,
+                response_forwarding: None,
+                web_socket: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn http(routers: Vec<(&str, Router)>, services: Vec<(&str, Service)>) -> Option<HttpConfig> {
+        Some(HttpConfig {
+            routers: routers.into_iter().map(|(name, r)| (name.to_string(), r)).collect(),
+            services: services.into_iter().map(|(name, s)| (name.to_string(), s)).collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Entry point `web` routing `/api` to one backend
+    fn local() -> Config {
+        Config {
+            entry_points: HashMap::from([("web".to_string(), entry_point(":8080"))]),
+            http: http(
+                vec![("api", router("PathPrefix(`/api`)", "api"))],
+                vec![("api", service("http://10.0.0.1:8080"))],
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_later_provider_overrides_router() {
+        let first = Config {
+            http: http(vec![("api", router("PathPrefix(`/v1`)", "api"))], vec![]),
+            ..Default::default()
+        };
+        let second = Config {
+            http: http(vec![("api", router("PathPrefix(`/v2`)", "api"))], vec![]),
+            ..Default::default()
+        };
+
+        let merged = merge_configs(merge_configs(local(), first), second);
+        assert_eq!(merged.routers().len(), 1);
+        assert_eq!(merged.routers()["api"].rule, "PathPrefix(`/v2`)");
+        merged.validate().unwrap();
+    }
+
+    #[test]
+    fn test_provider_services_are_additive() {
+        let mut overlay = Config {
+            http: http(
+                vec![("web", router("PathPrefix(`/`)", "web"))],
+                vec![("web", service("http://10.0.0.2:8080"))],
+            ),
+            ..Default::default()
+        };
+        let strip = MiddlewareConfig {
+            strip_prefix: Some(StripPrefixConfig {
+                prefixes: vec!["/web".to_string()],
+                force_slash: true,
+            }),
+            ..Default::default()
+        };
+        overlay.http.as_mut().unwrap().middlewares.insert("strip".to_string(), strip);
+
+        let merged = merge_configs(local(), overlay);
+        let mut services: Vec<&String> = merged.services().keys().collect();
+        services.sort();
+        assert_eq!(services, ["api", "web"]);
+        assert_eq!(merged.routers().len(), 2);
+        assert!(merged.middlewares().contains_key("strip"));
+        merged.validate().unwrap();
+    }
+
+    #[test]
+    fn test_provider_entry_points_do_not_override_local() {
+        let overlay = Config {
+            entry_points: HashMap::from([
+                ("web".to_string(), entry_point(":9999")),
+                ("admin".to_string(), entry_point(":9000")),
+            ]),
+            api: Some(ApiConfig { dashboard: true, ..Default::default() }),
+            ..Default::default()
+        };
+
+        let merged = merge_configs(local(), overlay);
+        assert_eq!(merged.entry_points.len(), 1);
+        assert_eq!(merged.entry_points["web"].address, ":8080");
+        assert!(merged.api.is_none());
+        merged.validate().unwrap();
+    }
+
+    #[test]
+    fn test_merge_onto_config_without_dynamic_sections() {
+        let base = Config {
+            entry_points: HashMap::from([("web".to_string(), entry_point(":8080"))]),
+            ..Default::default()
+        };
+        let db_router = TcpRouter {
+            entry_points: vec!["web".to_string()],
+            rule: "HostSNI(`*`)".to_string(),
+            rule_syntax: None,
+            service: "db".to_string(),
+            middlewares: vec![],
+            priority: 0,
+            tls: None,
+        };
+        let db_service = TcpService {
+            load_balancer: Some(TcpLoadBalancer {
+                servers: vec![TcpServer {
+                    address: "10.0.0.3:5432".to_string(),
+                    weight: 1,
+                    tls: false,
+                }],
+                health_check: None,
+                servers_transport: None,
+                proxy_protocol: None,
+                termination_delay: None,
+            }),
+            ..Default::default()
+        };
+        let overlay = Config {
+            tcp: Some(TcpConfig {
+                routers: HashMap::from([("db".to_string(), db_router)]),
+                services: HashMap::from([("db".to_string(), db_service)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let merged = merge_configs(base, overlay);
+        assert!(merged.http.is_none());
+        assert!(merged.tcp_routers().contains_key("db"));
+        merged.validate().unwrap();
+    }
+}
@@ -5,12 +5,15 @@
 
 /// Go-style duration parsing (e.g., "30s", "1m30s", "100ms").
 pub mod duration;
+mod merge;
 mod types;
 /// File-system watcher for automatic config reloading on changes.
 pub mod watcher;
 
 /// Re-exported duration type for config fields.
 pub use duration::Duration;
+/// Merging of config provider output into the local config.
+pub use merge::merge_configs;
 pub use types::*;
 /// Re-exported config watcher types.
 pub use watcher::{watch_config_async, ConfigWatcher};
@@ -25,7 +28,12 @@ impl Config {
         let content = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {:?}", path))?;
 
-        let mut config: Config = serde_yml::from_str(&content)
+        Self::from_yaml(&content)
+    }
+
+    /// Parse and validate a YAML config document.
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let mut config: Config = serde_yml::from_str(content)
             .with_context(|| "Failed to parse config file")?;
 
         config.validate()?;
@@ -35,7 +43,7 @@ impl Config {
     }
 
     /// Pre-parse backend URIs at config load time for performance
-    fn pre_parse_uris(&mut self) {
+    pub(crate) fn pre_parse_uris(&mut self) {
         if let Some(http) = &mut self.http {
             for service in http.services.values_mut() {
                 if let Some(lb) = &mut service.load_balancer {
@@ -43,7 +51,12 @@ impl Config {
                         server.url_arc = Some(Arc::from(server.url.as_str()));
                         if let Ok(uri) = server.url.parse::<hyper::Uri>()
                             && let (Some(scheme), Some(authority)) = (uri.scheme().cloned(), uri.authority().cloned()) {
-                                server.parsed_uri = Some(ParsedBackendUri { scheme, authority });
+                                let path = uri.path().trim_end_matches('/');
+                                server.parsed_uri = Some(ParsedBackendUri {
+                                    scheme,
+                                    authority,
+                                    path: (!path.is_empty()).then(|| Arc::from(path)),
+                                });
                             }
                     }
                 }
@@ -177,13 +190,54 @@ impl Config {
                 if m.service.is_empty() {
                     anyhow::bail!("Mirroring service '{}' must have a main service", name);
                 }
+            } else if let Some(f) = &service.failover {
+                for target in [&f.service, &f.fallback] {
+                    if !self.services().contains_key(target) {
+                        anyhow::bail!(
+                            "Failover service '{}' references non-existent service '{}'",
+                            name,
+                            target
+                        );
+                    }
+                }
             } else {
-                anyhow::bail!("Service '{}' must have loadBalancer, weighted, or mirroring configured", name);
+                anyhow::bail!(
+                    "Service '{}' must have loadBalancer, weighted, mirroring, or failover configured",
+                    name
+                );
+            }
+        }
+
+        // Validate middleware settings
+        for (name, middleware) in self.middlewares() {
+            if let Some(compress) = &middleware.compress {
+                let levels = &compress.levels;
+                for (encoding, level, range) in [
+                    ("gzip", levels.gzip, CompressionLevels::GZIP_RANGE),
+                    ("brotli", levels.brotli, CompressionLevels::BROTLI_RANGE),
+                    ("zstd", levels.zstd, CompressionLevels::ZSTD_RANGE),
+                ] {
+                    if !range.contains(&level) {
+                        anyhow::bail!(
+                            "Middleware '{}' has {} compression level {}, expected {} to {}",
+                            name,
+                            encoding,
+                            level,
+                            range.start(),
+                            range.end()
+                        );
+                    }
+                }
             }
         }
 
         // Validate routers reference valid services
         for (name, router) in self.routers() {
+            // A rule the router can't parse would otherwise drop the route silently
+            if let Err(e) = crate::router::RouteMatcher::from_rule(&router.rule) {
+                anyhow::bail!("Router '{}' has an invalid rule '{}': {}", name, router.rule, e);
+            }
+
             if !self.services().contains_key(&router.service) {
                 anyhow::bail!(
                     "Router '{}' references non-existent service '{}'",
@@ -266,6 +320,36 @@ impl Config {
             }
         }
 
+        if let Some(prometheus) = self.metrics.as_ref().and_then(|m| m.prometheus.as_ref()) {
+            let bucket_sets = [
+                ("buckets", Some(&prometheus.buckets)),
+                ("requestDurationBuckets", prometheus.request_duration_buckets.as_ref()),
+                ("backendDurationBuckets", prometheus.backend_duration_buckets.as_ref()),
+            ];
+            for (field, buckets) in bucket_sets {
+                let Some(buckets) = buckets else { continue };
+                if buckets.iter().any(|b| !b.is_finite()) {
+                    anyhow::bail!("Prometheus {} must be finite numbers", field);
+                }
+                if buckets.windows(2).any(|w| w[0] >= w[1]) {
+                    anyhow::bail!("Prometheus {} must be strictly increasing: {:?}", field, buckets);
+                }
+            }
+        }
+
+        if let Some(tracing) = &self.tracing {
+            if let Some(endpoint) = &tracing.endpoint {
+                url::Url::parse(endpoint)
+                    .with_context(|| format!("Invalid tracing endpoint: {}", endpoint))?;
+            }
+            if !(0.0..=1.0).contains(&tracing.sampling_ratio) {
+                anyhow::bail!(
+                    "Tracing samplingRatio must be between 0.0 and 1.0, got {}",
+                    tracing.sampling_ratio
+                );
+            }
+        }
+
         // Validate UDP services
         for (name, service) in self.udp_services() {
             if let Some(lb) = &service.load_balancer {
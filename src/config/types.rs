@@ -55,9 +55,18 @@ pub struct Config {
     #[serde(default)]
     pub access_log: Option<AccessLogConfig>,
 
+    /// Distributed tracing (OTLP span export)
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+
     /// Cluster/HA configuration
     #[serde(default)]
     pub cluster: Option<ClusterConfig>,
+
+    /// Cap on concurrent HTTP connections across all entrypoints (static config).
+    /// Connections beyond it wait in the listen backlog until one closes.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
 }
 
 /// HTTP routing configuration: routers, services, middlewares, and transports.
@@ -203,7 +212,8 @@ pub struct TcpLoadBalancer {
     #[serde(default)]
     pub proxy_protocol: Option<u8>,
 
-    /// Termination delay for graceful shutdown
+    /// How long to keep relaying one direction after the other side half-closes
+    /// (default 100ms)
     #[serde(default)]
     pub termination_delay: Option<Duration>,
 }
@@ -440,6 +450,10 @@ pub struct UdpLoadBalancer {
     /// Health check configuration
     #[serde(default)]
     pub health_check: Option<UdpHealthCheck>,
+
+    /// Idle time after which a client is no longer pinned to its backend (default 60s)
+    #[serde(default)]
+    pub session_timeout: Option<Duration>,
 }
 
 /// UDP backend server
@@ -574,6 +588,39 @@ pub struct MetricsConfig {
     /// Prometheus metrics configuration.
     #[serde(default)]
     pub prometheus: Option<PrometheusConfig>,
+
+    /// StatsD/DogStatsD push configuration.
+    #[serde(default)]
+    pub statsd: Option<StatsdConfig>,
+}
+
+/// StatsD/DogStatsD push exporter configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsdConfig {
+    /// Agent address (host:port).
+    #[serde(default = "default_statsd_address")]
+    pub address: String,
+
+    /// Prefix prepended to every metric name (e.g., "trafficcop").
+    #[serde(default)]
+    pub prefix: Option<String>,
+
+    /// Tags added to every metric, sent in DogStatsD `|#key:value` form.
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+
+    /// How often aggregated metrics are sent to the agent.
+    #[serde(default = "default_statsd_flush_interval")]
+    pub flush_interval: Duration,
+}
+
+fn default_statsd_address() -> String {
+    "127.0.0.1:8125".to_string()
+}
+
+fn default_statsd_flush_interval() -> Duration {
+    Duration::from_secs(10)
 }
 
 /// Prometheus metrics endpoint configuration.
@@ -585,24 +632,40 @@ pub struct PrometheusConfig {
     pub address: String,
 
     /// Add entry point labels to metrics.
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub add_entry_points_labels: bool,
 
     /// Add service labels to metrics.
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub add_services_labels: bool,
 
     /// Add router labels to metrics
     #[serde(default)]
     pub add_routers_labels: bool,
 
+    /// Only emit labels with these names (empty = no restriction)
+    #[serde(default)]
+    pub allowed_labels: Vec<String>,
+
+    /// Never emit labels with these names
+    #[serde(default)]
+    pub denied_labels: Vec<String>,
+
     /// Serve metrics on specific entry point (alternative to address)
     #[serde(default)]
     pub entry_point: Option<String>,
 
-    /// Custom histogram buckets
+    /// Custom histogram buckets (upper bounds, strictly increasing) for all histograms
     #[serde(default)]
     pub buckets: Vec<f64>,
+
+    /// Buckets for `http_request_duration_seconds`, overriding `buckets`
+    #[serde(default)]
+    pub request_duration_buckets: Option<Vec<f64>>,
+
+    /// Buckets for `backend_request_duration_seconds`, overriding `buckets`
+    #[serde(default)]
+    pub backend_duration_buckets: Option<Vec<f64>>,
 }
 
 fn default_metrics_address() -> String {
@@ -632,6 +695,12 @@ pub struct ApiConfig {
     /// Hide dashboard advertisement
     #[serde(default, rename = "disabledashboardad")]
     pub disable_dashboard_ad: bool,
+
+    /// Credentials for endpoints that change running state (`/api/config`
+    /// and the cluster drain/undrain endpoints); those endpoints are refused
+    /// while this is unset.
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuthConfig>,
 }
 
 /// Application logging configuration (level, format, output file).
@@ -664,8 +733,130 @@ pub struct AccessLogConfig {
     pub format: Option<String>,
 
     /// Number of access log lines to buffer before flushing.
-    #[serde(default)]
+    #[serde(default, alias = "bufferingSize")]
     pub bufferingsize: Option<u64>,
+
+    /// Which fields and request headers are logged, dropped, or redacted.
+    #[serde(default)]
+    pub fields: Option<AccessLogFieldsConfig>,
+}
+
+/// Access log field filtering, after Traefik's `accessLog.fields`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessLogFieldsConfig {
+    /// Mode for fields not listed in `names` (default: keep).
+    #[serde(default)]
+    pub default_mode: AccessLogFieldMode,
+
+    /// Per-field modes, keyed by field name (e.g., `user_agent`).
+    #[serde(default)]
+    pub names: HashMap<String, AccessLogFieldMode>,
+
+    /// Request headers to log. Headers are dropped unless configured here.
+    #[serde(default)]
+    pub headers: Option<AccessLogHeadersConfig>,
+}
+
+/// Request header logging; kept headers appear as `request_<Header-Name>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessLogHeadersConfig {
+    /// Mode for headers not listed in `names` (default: drop).
+    #[serde(default = "default_access_log_header_mode")]
+    pub default_mode: AccessLogFieldMode,
+
+    /// Per-header modes, keyed by header name (case-insensitive).
+    #[serde(default)]
+    pub names: HashMap<String, AccessLogFieldMode>,
+}
+
+impl Default for AccessLogHeadersConfig {
+    fn default() -> Self {
+        Self {
+            default_mode: default_access_log_header_mode(),
+            names: HashMap::new(),
+        }
+    }
+}
+
+fn default_access_log_header_mode() -> AccessLogFieldMode {
+    AccessLogFieldMode::Drop
+}
+
+/// What to do with an access log field or header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessLogFieldMode {
+    /// Log the value.
+    #[default]
+    Keep,
+    /// Leave the field out.
+    Drop,
+    /// Log `REDACTED` in place of the value.
+    Redact,
+}
+
+/// Distributed tracing configuration: context propagation and OTLP span export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TracingConfig {
+    /// OTLP/HTTP collector URL (e.g., "http://collector:4318"). `/v1/traces`
+    /// is appended unless the path already ends with it. Spans are not
+    /// exported when unset.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+
+    /// Header formats used to pass trace context to backends (default: W3C).
+    #[serde(default = "default_trace_propagation")]
+    pub propagation: Vec<TracePropagation>,
+
+    /// Extra headers sent with every export request (e.g., auth tokens).
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// Fraction of new traces to sample, 0.0 to 1.0. Requests that arrive
+    /// with a trace context follow the caller's sampling decision.
+    #[serde(default = "default_sampling_ratio")]
+    pub sampling_ratio: f64,
+
+    /// `service.name` resource attribute reported to the collector.
+    #[serde(default = "default_tracing_service_name")]
+    pub service_name: String,
+
+    /// How often buffered spans are sent to the collector.
+    #[serde(default = "default_tracing_flush_interval")]
+    pub flush_interval: Duration,
+}
+
+/// Trace context header format sent to backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TracePropagation {
+    /// W3C Trace Context (`traceparent`/`tracestate`)
+    #[serde(rename = "tracecontext")]
+    TraceContext,
+    /// Zipkin B3 single header (`b3`)
+    #[serde(rename = "b3")]
+    B3,
+    /// Zipkin B3 multi-header (`X-B3-TraceId`, `X-B3-SpanId`, `X-B3-Sampled`)
+    #[serde(rename = "b3multi")]
+    B3Multi,
+}
+
+fn default_trace_propagation() -> Vec<TracePropagation> {
+    vec![TracePropagation::TraceContext]
+}
+
+fn default_sampling_ratio() -> f64 {
+    1.0
+}
+
+fn default_tracing_service_name() -> String {
+    "trafficcop".to_string()
+}
+
+fn default_tracing_flush_interval() -> Duration {
+    Duration::from_secs(5)
 }
 
 /// Dynamic configuration providers (file, etc.).
@@ -767,11 +958,12 @@ pub struct RedirectEntryPoint {
     #[serde(default = "default_https_scheme")]
     pub scheme: String,
 
-    /// Use permanent (301) redirect.
+    /// Use a permanent (301/308) rather than temporary (302/307) redirect.
     #[serde(default = "default_true")]
     pub permanent: bool,
 
-    /// Priority for the generated redirect router.
+    /// Routers on the entrypoint with a higher priority are served instead of
+    /// redirected (default: the redirect always applies).
     #[serde(default)]
     pub priority: Option<i32>,
 }
@@ -837,21 +1029,45 @@ pub struct EntryPointTransport {
     /// Maximum time a keep-alive connection can be used
     #[serde(default)]
     pub keep_alive_max_time: Option<Duration>,
+
+    /// Maximum concurrent connections on this entrypoint (unset or 0 = unlimited).
+    /// Connections beyond it wait in the listen backlog until one closes.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+
+    /// Answer requests with a 503 while the node drains instead of refusing
+    /// new connections. Unset keeps refusing them.
+    #[serde(default)]
+    pub drain_response: Option<DrainResponse>,
+}
+
+/// Response sent to new requests on a draining node, telling clients to retry
+/// elsewhere. HTTP/1 connections are closed after it.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DrainResponse {
+    /// Value for the Retry-After header, sent in whole seconds.
+    #[serde(default)]
+    pub retry_after: Option<Duration>,
+
+    /// Value for the Location header, e.g. another node or a load balancer.
+    #[serde(default)]
+    pub location: Option<String>,
 }
 
 /// Timeouts for reading requests, writing responses, and idle connections.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RespondingTimeouts {
-    /// Maximum duration for reading the entire request.
+    /// Maximum duration for receiving a request, headers and body, from its first byte (0 = no limit).
     #[serde(default = "default_read_timeout")]
     pub read_timeout: Duration,
 
-    /// Maximum duration for writing the response.
+    /// Maximum duration from receiving a request to finishing its response (0 = no limit).
     #[serde(default)]
     pub write_timeout: Duration,
 
-    /// Maximum duration an idle connection is kept open.
+    /// Maximum duration an idle keep-alive connection is kept open (0 = no limit).
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout: Duration,
 }
@@ -981,6 +1197,10 @@ pub struct LoadBalancerService {
     /// Response forwarding settings.
     #[serde(default)]
     pub response_forwarding: Option<ResponseForwarding>,
+
+    /// WebSocket proxying settings.
+    #[serde(default)]
+    pub web_socket: Option<WebSocketConfig>,
 }
 
 /// HTTP backend server (URL, weight, and pre-parsed URI for hot-path performance).
@@ -994,7 +1214,8 @@ pub struct Server {
     #[serde(default = "default_weight")]
     pub weight: u32,
 
-    /// Preserve the original request path when forwarding.
+    /// Keep the path of the server URL, prefixing it to the request path
+    /// (after middlewares rewrote it). When false the URL's path is ignored.
     #[serde(default)]
     pub preserve_path: bool,
 
@@ -1015,6 +1236,8 @@ pub struct ParsedBackendUri {
     pub scheme: hyper::http::uri::Scheme,
     /// URI authority (host and optional port).
     pub authority: hyper::http::uri::Authority,
+    /// URI path without its trailing slash, when it is more than `/`.
+    pub path: Option<std::sync::Arc<str>>,
 }
 
 fn default_weight() -> u32 {
@@ -1028,6 +1251,10 @@ pub struct Sticky {
     /// Cookie-based sticky session configuration.
     #[serde(default)]
     pub cookie: Option<StickyCookie>,
+
+    /// Request header whose value keys the bucket (weighted services only).
+    #[serde(default)]
+    pub header: Option<String>,
 }
 
 /// Sticky session cookie parameters.
@@ -1074,7 +1301,7 @@ pub struct HealthCheck {
     #[serde(default = "default_health_timeout")]
     pub timeout: Duration,
 
-    /// Scheme to use for health checks (e.g., "http", "https").
+    /// Scheme to use for health checks (e.g., "http", "https"; "grpc" selects gRPC mode).
     #[serde(default)]
     pub scheme: Option<String>,
 
@@ -1128,6 +1355,40 @@ pub struct ResponseForwarding {
     pub flush_interval: Option<Duration>,
 }
 
+/// WebSocket proxying settings.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketConfig {
+    /// How the `permessage-deflate` extension is negotiated.
+    #[serde(default)]
+    pub per_message_deflate: PerMessageDeflate,
+
+    /// Close the connection after this long without a frame in either
+    /// direction; pings count as activity. Unset or zero disables it.
+    #[serde(default)]
+    pub idle_timeout: Option<Duration>,
+
+    /// Close the connection once it has been open this long, regardless of
+    /// activity. Unset or zero disables it.
+    #[serde(default)]
+    pub max_lifetime: Option<Duration>,
+}
+
+/// `permessage-deflate` (RFC 7692) negotiation mode for proxied WebSockets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PerMessageDeflate {
+    /// Offer the client's extensions to the backend and relay its answer;
+    /// the extension is dropped when the backend does not accept it.
+    #[default]
+    Passthrough,
+    /// Never negotiate compression on either side of the proxy.
+    Strip,
+    /// Like passthrough, but when the backend declines the proxy accepts the
+    /// extension itself and compresses/decompresses messages in between.
+    Terminate,
+}
+
 /// Weighted service for traffic splitting across multiple backend services.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -1319,7 +1580,8 @@ pub struct Router {
     #[serde(default)]
     pub middlewares: Vec<String>,
 
-    /// Priority for rule matching.
+    /// Priority for rule matching. When unset (0), it is derived from the
+    /// rule's specificity so more specific rules are tried first.
     #[serde(default)]
     pub priority: i32,
 
@@ -1467,7 +1729,7 @@ pub struct MiddlewareConfig {
     pub in_flight_req: Option<InFlightReqConfig>,
 
     /// Pass TLS client certificate middleware.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(default, alias = "passTLSClientCert", skip_serializing_if = "Option::is_none")]
     pub pass_tls_client_cert: Option<PassTlsClientCertConfig>,
 
     /// Content-Type auto-detection middleware.
@@ -1482,6 +1744,34 @@ pub struct MiddlewareConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub jwt: Option<JwtConfig>,
 
+    /// OAuth2 token introspection middleware (RFC 7662).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth2_introspection: Option<OAuth2IntrospectionConfig>,
+
+    /// GeoIP country blocking and location headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geo_ip: Option<GeoIpConfig>,
+
+    /// Request ID generation and propagation middleware.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<RequestIdConfig>,
+
+    /// Maintenance mode middleware (serve a fixed page instead of the backend).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<MaintenanceConfig>,
+
+    /// Regex substitutions on response bodies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace_response_body: Option<ReplaceResponseBodyConfig>,
+
+    /// Request body decompression middleware.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decompress_request: Option<DecompressRequestConfig>,
+
+    /// Tarpit middleware (slow down flagged clients).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tarpit: Option<TarpitConfig>,
+
     /// Errors middleware - custom error pages
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub errors: Option<ErrorsConfig>,
@@ -1519,6 +1809,13 @@ impl MiddlewareConfig {
         else if self.content_type.is_some() { "contentType" }
         else if self.grpc_web.is_some() { "grpcWeb" }
         else if self.jwt.is_some() { "jwt" }
+        else if self.oauth2_introspection.is_some() { "oauth2Introspection" }
+        else if self.geo_ip.is_some() { "geoIp" }
+        else if self.request_id.is_some() { "requestId" }
+        else if self.maintenance.is_some() { "maintenance" }
+        else if self.replace_response_body.is_some() { "replaceResponseBody" }
+        else if self.decompress_request.is_some() { "decompressRequest" }
+        else if self.tarpit.is_some() { "tarpit" }
         else if self.errors.is_some() { "errors" }
         else { "unknown" }
     }
@@ -1547,6 +1844,12 @@ pub struct RateLimitConfig {
     /// Criterion for identifying the rate-limit source.
     #[serde(default)]
     pub source_criterion: Option<SourceCriterion>,
+
+    /// Enforce the limit across all nodes by counting in the cluster store.
+    /// Without a cluster store each node counts on its own. `burst` still
+    /// applies per node.
+    #[serde(default)]
+    pub distributed: bool,
 }
 
 fn default_rate_period() -> Duration {
@@ -1574,11 +1877,12 @@ pub struct SourceCriterion {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct IpStrategy {
-    /// Depth in X-Forwarded-For to extract the client IP.
+    /// Position in X-Forwarded-For of the client IP, counted from the right
+    /// (1 is the rightmost entry). 0 leaves it unset.
     #[serde(default)]
     pub depth: u32,
 
-    /// IPs to exclude when extracting client IP.
+    /// IPs to exclude when extracting client IP. Ignored when `depth` is set.
     #[serde(default)]
     pub excluded_ips: Vec<String>,
 
@@ -1603,6 +1907,137 @@ pub struct IpAllowListConfig {
     pub reject_status_code: Option<u16>,
 }
 
+/// GeoIP middleware: country allow/deny lists and location headers from a
+/// MaxMind GeoLite2/GeoIP2 Country or City database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoIpConfig {
+    /// Path to the `.mmdb` database, reopened on every config reload.
+    pub mmdb_path: String,
+
+    /// ISO country codes to permit; when set, every other origin is rejected.
+    #[serde(default)]
+    pub allow_countries: Vec<String>,
+
+    /// ISO country codes to reject.
+    #[serde(default)]
+    pub deny_countries: Vec<String>,
+
+    /// Add X-GeoIP-Country and X-GeoIP-City headers to the request (default: true).
+    #[serde(default = "default_true")]
+    pub add_headers: bool,
+
+    /// Strategy for extracting client IP.
+    #[serde(default)]
+    pub ip_strategy: Option<IpStrategy>,
+}
+
+/// Request ID middleware: tags each request with an ID sent to the backend,
+/// echoed to the client, and recorded in access logs and traces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestIdConfig {
+    /// Header carrying the ID on the request and response (default: X-Request-Id)
+    #[serde(default = "default_request_id_header")]
+    pub header_name: String,
+
+    /// Reuse an ID supplied by the client instead of always generating one.
+    #[serde(default)]
+    pub trust_incoming: bool,
+}
+
+fn default_request_id_header() -> String {
+    "X-Request-Id".to_string()
+}
+
+/// Maintenance mode middleware: answers every request with a fixed page
+/// instead of calling the backend, except for allowlisted source IPs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceConfig {
+    /// Whether maintenance mode is on (default: true)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Status code of the maintenance response (default: 503)
+    #[serde(default = "default_maintenance_status")]
+    pub status_code: u16,
+
+    /// Value for the Retry-After header, sent in whole seconds.
+    #[serde(default)]
+    pub retry_after: Option<Duration>,
+
+    /// Inline response body.
+    #[serde(default)]
+    pub body: Option<String>,
+
+    /// File to serve as the response body, read on every config reload (takes precedence over body).
+    #[serde(default)]
+    pub file: Option<String>,
+
+    /// Content-Type of the response (default: text/html; charset=utf-8)
+    #[serde(default = "default_maintenance_content_type")]
+    pub content_type: String,
+
+    /// Source IP ranges (CIDR notation) that bypass maintenance.
+    #[serde(default)]
+    pub allow_source_range: Vec<String>,
+
+    /// Strategy for extracting client IP.
+    #[serde(default)]
+    pub ip_strategy: Option<IpStrategy>,
+}
+
+fn default_maintenance_status() -> u16 {
+    503
+}
+
+fn default_maintenance_content_type() -> String {
+    "text/html; charset=utf-8".to_string()
+}
+
+/// Tarpit middleware: delays requests from flagged clients to slow down
+/// scrapers without blocking them outright.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TarpitConfig {
+    /// Source IP ranges (CIDR notation) to tarpit.
+    #[serde(default)]
+    pub source_range: Vec<String>,
+
+    /// Request header that flags a client for the tarpit.
+    #[serde(default)]
+    pub header_name: Option<String>,
+
+    /// Required value of `headerName`; any value matches when unset.
+    #[serde(default)]
+    pub header_value: Option<String>,
+
+    /// Delay applied to flagged requests (default: 10s)
+    #[serde(default = "default_tarpit_delay")]
+    pub delay: Duration,
+
+    /// Random extra delay of up to this much (default: none)
+    #[serde(default)]
+    pub jitter: Option<Duration>,
+
+    /// Most requests held in the tarpit at once; further flagged requests get a 429 (default: 100)
+    #[serde(default = "default_tarpit_max_concurrent")]
+    pub max_concurrent: usize,
+
+    /// Strategy for extracting client IP.
+    #[serde(default)]
+    pub ip_strategy: Option<IpStrategy>,
+}
+
+fn default_tarpit_delay() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_tarpit_max_concurrent() -> usize {
+    100
+}
+
 /// IP denylist middleware (block listed CIDR ranges).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -1712,7 +2147,7 @@ pub struct HeadersConfig {
     pub sts_preload: bool,
 
     /// Force STS header even on HTTP.
-    #[serde(default)]
+    #[serde(default, alias = "forceSTSHeader")]
     pub force_sts_header: bool,
 
     /// Allowed hosts for host checking.
@@ -1781,7 +2216,8 @@ pub struct DigestAuthConfig {
     #[serde(default)]
     pub users: Vec<String>,
 
-    /// Path to users file.
+    /// Path to an htdigest users file (`user:realm:ha1` lines); entries for
+    /// other realms are skipped.
     #[serde(default)]
     pub users_file: Option<String>,
 
@@ -1863,6 +2299,18 @@ pub struct JwtConfig {
     #[serde(default)]
     pub public_key: Option<String>,
 
+    /// JWKS endpoint to fetch RSA/EC keys from, selected by the token's `kid` (alternative to publicKey)
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+
+    /// How often to refresh the JWKS key set (default: 1h)
+    #[serde(default = "default_jwks_refresh_interval")]
+    pub jwks_refresh_interval: Duration,
+
+    /// Minimum time between JWKS fetches, including refetches for an unknown `kid` (default: 30s)
+    #[serde(default = "default_jwks_min_refresh_interval")]
+    pub jwks_min_refresh_interval: Duration,
+
     /// Algorithm to use for validation (default: HS256)
     #[serde(default = "default_jwt_algorithm")]
     pub algorithm: String,
@@ -1875,6 +2323,10 @@ pub struct JwtConfig {
     #[serde(default)]
     pub audience: Option<String>,
 
+    /// Clock-skew tolerance in seconds applied to exp, nbf and iat (default: 0)
+    #[serde(default)]
+    pub leeway_secs: u64,
+
     /// Header name to extract JWT from (default: Authorization)
     #[serde(default = "default_jwt_header")]
     pub header_name: String,
@@ -1904,6 +2356,14 @@ fn default_jwt_algorithm() -> String {
     "HS256".to_string()
 }
 
+fn default_jwks_refresh_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_jwks_min_refresh_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
 fn default_jwt_header() -> String {
     "Authorization".to_string()
 }
@@ -1912,6 +2372,37 @@ fn default_jwt_prefix() -> String {
     "Bearer ".to_string()
 }
 
+/// OAuth2 token introspection middleware (RFC 7662): validates opaque bearer
+/// tokens against the authorization server's introspection endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuth2IntrospectionConfig {
+    /// URL of the introspection endpoint.
+    pub endpoint: String,
+
+    /// Client ID used to authenticate to the endpoint (HTTP Basic).
+    pub client_id: String,
+
+    /// Client secret used to authenticate to the endpoint (HTTP Basic).
+    pub client_secret: String,
+
+    /// How long an active token is cached, capped by its `exp` (default: 60s, 0 disables)
+    #[serde(default = "default_introspection_cache_ttl")]
+    pub cache_ttl: Duration,
+
+    /// Header name to extract the token from (default: Authorization)
+    #[serde(default = "default_jwt_header")]
+    pub header_name: String,
+
+    /// Prefix before token in header (default: Bearer)
+    #[serde(default = "default_jwt_prefix")]
+    pub header_prefix: String,
+}
+
+fn default_introspection_cache_ttl() -> Duration {
+    Duration::from_secs(60)
+}
+
 /// Response compression middleware (gzip, brotli, zstd).
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -1935,12 +2426,73 @@ pub struct CompressConfig {
     /// Supported compression encodings in priority order.
     #[serde(default = "default_encodings")]
     pub encodings: Vec<String>,
+
+    /// Largest body buffered for compression; bigger bodies stream through uncompressed (default: 1MiB)
+    #[serde(default = "default_compress_max_bytes")]
+    pub max_body_bytes: u64,
+
+    /// Compression level per encoding, trading CPU for ratio.
+    #[serde(default)]
+    pub levels: CompressionLevels,
+}
+
+/// Compression levels for the compress middleware (default: gzip 6, brotli 4, zstd 3).
+/// Higher levels compress smaller at more CPU cost.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionLevels {
+    /// Gzip level, 0 (stored) to 9.
+    #[serde(default = "default_gzip_level")]
+    pub gzip: u32,
+
+    /// Brotli quality, 0 to 11.
+    #[serde(default = "default_brotli_level")]
+    pub brotli: u32,
+
+    /// Zstd level, 1 to 22.
+    #[serde(default = "default_zstd_level")]
+    pub zstd: u32,
+}
+
+impl CompressionLevels {
+    /// Valid gzip levels.
+    pub const GZIP_RANGE: std::ops::RangeInclusive<u32> = 0..=9;
+    /// Valid brotli qualities.
+    pub const BROTLI_RANGE: std::ops::RangeInclusive<u32> = 0..=11;
+    /// Valid zstd levels.
+    pub const ZSTD_RANGE: std::ops::RangeInclusive<u32> = 1..=22;
+}
+
+impl Default for CompressionLevels {
+    fn default() -> Self {
+        Self {
+            gzip: default_gzip_level(),
+            brotli: default_brotli_level(),
+            zstd: default_zstd_level(),
+        }
+    }
 }
 
 fn default_compress_min_size() -> u64 {
     1024
 }
 
+fn default_compress_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_gzip_level() -> u32 {
+    6
+}
+
+fn default_brotli_level() -> u32 {
+    4
+}
+
+fn default_zstd_level() -> u32 {
+    3
+}
+
 fn default_encodings() -> Vec<String> {
     vec!["zstd".to_string(), "br".to_string(), "gzip".to_string()]
 }
@@ -1956,6 +2508,26 @@ pub struct RetryConfig {
     /// Initial interval before the first retry.
     #[serde(default = "default_retry_initial_interval")]
     pub initial_interval: Duration,
+
+    /// Upper bound on the interval between retries.
+    #[serde(default = "default_retry_max_interval")]
+    pub max_interval: Duration,
+
+    /// Factor the interval grows by after each retry.
+    #[serde(default = "default_retry_multiplier")]
+    pub multiplier: f64,
+
+    /// Randomize each interval (between half and all of it).
+    #[serde(default = "default_true")]
+    pub jitter: bool,
+
+    /// Response status codes that are retried; connection errors always are.
+    #[serde(default = "default_retry_status_codes")]
+    pub status_codes: Vec<u16>,
+
+    /// Also retry non-idempotent methods (POST, PATCH).
+    #[serde(default)]
+    pub retry_non_idempotent: bool,
 }
 
 fn default_retry_attempts() -> u32 {
@@ -1966,6 +2538,18 @@ fn default_retry_initial_interval() -> Duration {
     Duration::from_millis(100)
 }
 
+fn default_retry_max_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_retry_multiplier() -> f64 {
+    2.0
+}
+
+fn default_retry_status_codes() -> Vec<u16> {
+    vec![408, 429, 502, 503, 504]
+}
+
 /// Circuit breaker middleware (trips on error threshold, auto-recovers).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -2045,8 +2629,9 @@ pub struct StripPrefixConfig {
     /// Prefixes to strip from the request path.
     pub prefixes: Vec<String>,
 
-    /// Ensure resulting path starts with a slash.
-    #[serde(default)]
+    /// Leave `/` when the whole path is stripped (default: true). Without it the
+    /// path is left empty, so a preserved server path gets no trailing slash.
+    #[serde(default = "default_true")]
     pub force_slash: bool,
 }
 
@@ -2118,6 +2703,56 @@ pub struct BufferingConfig {
     pub retry_expression: Option<String>,
 }
 
+/// Response body rewrite middleware: regex substitutions on text bodies,
+/// e.g. rewriting absolute backend URLs to the public hostname.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceResponseBodyConfig {
+    /// Substitutions, applied in order.
+    pub replacements: Vec<BodyReplacement>,
+
+    /// Media types to rewrite (default: text/html, application/json)
+    #[serde(default = "default_replace_body_content_types")]
+    pub content_types: Vec<String>,
+
+    /// Largest body buffered for rewriting; bigger bodies stream through unchanged (default: 1MiB)
+    #[serde(default = "default_replace_body_max_bytes")]
+    pub max_body_bytes: u64,
+}
+
+/// A single regex substitution on a response body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyReplacement {
+    /// Regex to match.
+    pub regex: String,
+
+    /// Replacement text; `$1` / `${name}` refer to capture groups.
+    pub replacement: String,
+}
+
+fn default_replace_body_content_types() -> Vec<String> {
+    vec!["text/html".to_string(), "application/json".to_string()]
+}
+
+fn default_replace_body_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+/// Request decompression middleware: decodes gzip, deflate, br and zstd
+/// request bodies before they reach the backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecompressRequestConfig {
+    /// Largest body accepted, compressed or decompressed; larger ones get a 413 (default: 10MiB)
+    #[serde(default = "default_decompress_max_bytes")]
+    pub max_decompressed_bytes: u64,
+}
+
+fn default_decompress_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
 /// In-flight request limiter middleware (concurrent request cap).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -2425,6 +3060,10 @@ pub struct AcmeConfig {
     #[serde(default)]
     pub certificate_duration: Option<Duration>,
 
+    /// Renew certificates this long before they expire (default 720h, 30 days).
+    #[serde(default = "default_acme_renew_before")]
+    pub renew_before: Duration,
+
     /// Preferred certificate chain.
     #[serde(default)]
     pub preferred_chain: Option<String>,
@@ -2446,6 +3085,10 @@ fn default_acme_storage() -> String {
     "acme.json".to_string()
 }
 
+fn default_acme_renew_before() -> Duration {
+    Duration::from_secs(30 * 24 * 60 * 60)
+}
+
 /// ACME External Account Binding (EAB) credentials.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -2564,6 +3207,10 @@ pub enum StoreConfig {
     /// Redis/Valkey distributed store
     #[serde(rename = "redis")]
     Redis(Box<RedisStoreConfig>),
+
+    /// Consul KV distributed store
+    #[serde(rename = "consul")]
+    Consul(Box<ConsulStoreConfig>),
 }
 
 
@@ -2613,6 +3260,39 @@ fn default_redis_timeout() -> Duration {
     Duration::from_secs(5)
 }
 
+/// Consul KV store configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsulStoreConfig {
+    /// Consul HTTP API address
+    #[serde(default = "default_consul_endpoint")]
+    pub endpoint: String,
+
+    /// ACL token
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// Datacenter (defaults to the agent's own)
+    #[serde(default)]
+    pub datacenter: Option<String>,
+
+    /// Key prefix for all keys
+    #[serde(default = "default_key_prefix")]
+    pub root_key: String,
+
+    /// Request timeout
+    #[serde(default = "default_consul_timeout")]
+    pub timeout: Duration,
+}
+
+fn default_consul_endpoint() -> String {
+    "http://127.0.0.1:8500".to_string()
+}
+
+fn default_consul_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
 /// Redis TLS configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -2791,6 +3471,10 @@ pub struct ConsulProviderConfig {
     #[serde(default = "default_true")]
     pub watch: bool,
 
+    /// Poll interval when `watch` is disabled
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: Duration,
+
     /// TLS configuration
     #[serde(default)]
     pub tls: Option<HttpProviderTls>,
@@ -10,7 +10,7 @@ use std::path::Path;
 use std::sync::mpsc;
 use std::time::Duration;
 use tokio::sync::broadcast;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info};
 
 /// Config watcher that monitors config file for changes and notifies subscribers
 pub struct ConfigWatcher {
@@ -98,13 +98,10 @@ impl ConfigWatcher {
                     // Small delay to ensure file write is complete
                     std::thread::sleep(Duration::from_millis(100));
 
+                    // Only a fully valid config is sent on; anything else keeps
+                    // the running config, so a typo can't drop routes
                     match Config::load(&config_path) {
                         Ok(config) => {
-                            if let Err(e) = config.validate() {
-                                warn!("Invalid config after change: {}", e);
-                                continue;
-                            }
-
                             info!("Config reloaded successfully");
                             if self.tx.send(config).is_err() {
                                 debug!("No config subscribers, stopping watcher");
@@ -112,7 +109,7 @@ impl ConfigWatcher {
                             }
                         }
                         Err(e) => {
-                            warn!("Failed to reload config: {}", e);
+                            error!("Invalid config after change, keeping the running config: {:#}", e);
                         }
                     }
                 }
@@ -1,3 +1,4 @@
+use super::grpc::GrpcHealthCheck;
 use super::HealthStatus;
 use crate::config::HealthCheck;
 use hyper::body::Bytes;
@@ -11,7 +12,8 @@ use std::time::Duration;
 use tokio::time::{interval, timeout};
 use tracing::{debug, warn};
 
-/// Active HTTP health checker that periodically polls a backend server.
+/// Active health checker that periodically polls a backend server over HTTP,
+/// or with the gRPC health-checking protocol when `mode` (or `scheme`) is `grpc`.
 pub struct HealthChecker {
     config: HealthCheck,
     server_url: String,
@@ -21,6 +23,8 @@ pub struct HealthChecker {
     check_uri: hyper::Uri,
     /// Pre-parsed HTTP method to avoid parsing per tick
     check_method: Method,
+    /// Set when checking via grpc.health.v1 instead of an HTTP path
+    grpc: Option<GrpcHealthCheck>,
 }
 
 impl HealthChecker {
@@ -49,6 +53,12 @@ impl HealthChecker {
             .map(|m| m.parse().unwrap_or(Method::GET))
             .unwrap_or(Method::GET);
 
+        let is_grpc = [config.mode.as_deref(), config.scheme.as_deref()]
+            .into_iter()
+            .flatten()
+            .any(|v| v.eq_ignore_ascii_case("grpc"));
+        let grpc = is_grpc.then(|| GrpcHealthCheck::new(&server_url, config.port, &config.path));
+
         Self {
             config,
             server_url,
@@ -56,59 +66,70 @@ impl HealthChecker {
             client,
             check_uri,
             check_method,
+            grpc,
         }
     }
 
     /// Run the health check loop until the task is cancelled.
     pub async fn start(self) {
-        let interval_duration = self.config.interval.as_std();
-        let check_timeout = self.config.timeout.as_std();
-        // Traefik doesn't have threshold concepts, so we use sensible defaults
-        let healthy_threshold = 2u32;
-        let unhealthy_threshold = 3u32;
-
-        let mut ticker = interval(interval_duration);
+        let mut ticker = interval(self.config.interval.as_std());
 
         loop {
             ticker.tick().await;
+            self.check().await;
+        }
+    }
+
+    /// Probe the server once and update its status: it turns unhealthy after
+    /// three failures in a row and healthy again after two successes.
+    pub(crate) async fn check(&self) {
+        // Traefik doesn't have threshold concepts, so we use sensible defaults
+        let healthy_threshold = 2u32;
+        let unhealthy_threshold = 3u32;
 
-            let result = timeout(check_timeout, self.perform_http_check()).await;
+        let result = timeout(self.config.timeout.as_std(), self.perform_check()).await;
 
-            match result {
-                Ok(Ok(())) => {
-                    self.status.record_success();
-                    let successes = self
-                        .status
-                        .consecutive_successes
-                        .load(Ordering::Relaxed);
+        match result {
+            Ok(Ok(())) => {
+                self.status.record_success();
+                let successes = self
+                    .status
+                    .consecutive_successes
+                    .load(Ordering::Relaxed);
 
-                    if !self.status.is_healthy() && successes >= healthy_threshold {
-                        self.status.mark_healthy();
-                        debug!("Server {} is now healthy", self.server_url);
-                    }
+                if !self.status.is_healthy() && successes >= healthy_threshold {
+                    self.status.mark_healthy();
+                    debug!("Server {} is now healthy", self.server_url);
                 }
-                Ok(Err(e)) => {
-                    self.status.record_failure(e.clone());
-                    let failures = self.status.consecutive_failures.load(Ordering::Relaxed);
-
-                    if self.status.is_healthy() && failures >= unhealthy_threshold {
-                        self.status.mark_unhealthy();
-                        warn!("Server {} is now unhealthy: {}", self.server_url, e);
-                    }
+            }
+            Ok(Err(e)) => {
+                self.status.record_failure(e.clone());
+                let failures = self.status.consecutive_failures.load(Ordering::Relaxed);
+
+                if self.status.is_healthy() && failures >= unhealthy_threshold {
+                    self.status.mark_unhealthy();
+                    warn!("Server {} is now unhealthy: {}", self.server_url, e);
                 }
-                Err(_) => {
-                    self.status.record_failure("Timeout".to_string());
-                    let failures = self.status.consecutive_failures.load(Ordering::Relaxed);
-
-                    if self.status.is_healthy() && failures >= unhealthy_threshold {
-                        self.status.mark_unhealthy();
-                        warn!("Server {} is now unhealthy: timeout", self.server_url);
-                    }
+            }
+            Err(_) => {
+                self.status.record_failure("Timeout".to_string());
+                let failures = self.status.consecutive_failures.load(Ordering::Relaxed);
+
+                if self.status.is_healthy() && failures >= unhealthy_threshold {
+                    self.status.mark_unhealthy();
+                    warn!("Server {} is now unhealthy: timeout", self.server_url);
                 }
             }
         }
     }
 
+    async fn perform_check(&self) -> Result<(), String> {
+        match self.grpc {
+            Some(ref grpc) => grpc.check().await,
+            None => self.perform_http_check().await,
+        }
+    }
+
     async fn perform_http_check(&self) -> Result<(), String> {
         let req = Request::builder()
             .method(self.check_method.clone())
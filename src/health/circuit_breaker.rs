@@ -1,5 +1,9 @@
+use crate::config::CircuitBreakerConfig;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
+use thiserror::Error;
 
 /// Circuit breaker states
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,45 +17,93 @@ pub enum CircuitState {
 }
 
 /// High-performance circuit breaker using atomics
+/// After the recovery timeout the circuit goes half-open and admits a limited
+/// number of probe requests: one success closes it, one failure re-opens it.
+/// A probe that ends without a recorded outcome gives its slot back.
 pub struct CircuitBreaker {
     failure_threshold: u32,
     recovery_timeout_ms: u64,
+    max_half_open_probes: u32,
 
     // Atomic state
     failures: AtomicU32,
-    successes: AtomicU32,
+    /// Probe slots currently held since the circuit last opened
+    half_open_probes: AtomicU32,
+    /// Bumped whenever probe slots are cleared, so older probes don't free new ones
+    probe_generation: AtomicU64,
     state: AtomicU32, // 0=Closed, 1=Open, 2=HalfOpen
     opened_at: AtomicU64,
     epoch: Instant,
+
+    /// Expression trigger evaluated over recent responses, if configured
+    trigger: Option<ExpressionTrigger>,
+}
+
+/// Trip expression plus the rolling window of responses it is evaluated over
+struct ExpressionTrigger {
+    expression: TripExpression,
+    window: Duration,
+    samples: Mutex<VecDeque<(Instant, ResponseSample)>>,
 }
 
 impl CircuitBreaker {
     /// Create a circuit breaker with the given failure threshold and recovery timeout.
+    /// Half-open admits a single probe request; see [`Self::with_half_open_probes`].
     pub fn new(failure_threshold: u32, recovery_timeout: Duration) -> Self {
         Self {
             failure_threshold,
             recovery_timeout_ms: recovery_timeout.as_millis() as u64,
+            max_half_open_probes: 1,
             failures: AtomicU32::new(0),
-            successes: AtomicU32::new(0),
+            half_open_probes: AtomicU32::new(0),
+            probe_generation: AtomicU64::new(0),
             state: AtomicU32::new(0), // Closed
             opened_at: AtomicU64::new(0),
             epoch: Instant::now(),
+            trigger: None,
         }
     }
 
+    /// Build a breaker from middleware config: it trips when `expression` holds over
+    /// the responses of the last `check_period` and stays open for `fallback_duration`.
+    pub fn from_config(config: &CircuitBreakerConfig) -> Result<Self, ExpressionParseError> {
+        let expression = TripExpression::parse(&config.expression)?;
+        Ok(Self::new(u32::MAX, config.fallback_duration.as_std())
+            .with_expression(expression, config.check_period.as_std()))
+    }
+
+    /// Trip on `expression` evaluated over responses seen within `window`
+    /// instead of on consecutive failures.
+    pub fn with_expression(mut self, expression: TripExpression, window: Duration) -> Self {
+        self.trigger = Some(ExpressionTrigger {
+            expression,
+            window,
+            samples: Mutex::new(VecDeque::new()),
+        });
+        self
+    }
+
+    /// Set how many concurrent probe requests the half-open state admits (minimum 1).
+    pub fn with_half_open_probes(mut self, max_probes: u32) -> Self {
+        self.max_half_open_probes = max_probes.max(1);
+        self
+    }
+
     /// Get the current circuit state, auto-transitioning from open to half-open on timeout.
     #[inline]
     pub fn state(&self) -> CircuitState {
-        match self.state.load(Ordering::Relaxed) {
+        match self.state.load(Ordering::Acquire) {
             0 => CircuitState::Closed,
             1 => {
                 // Check if recovery timeout has passed
                 let opened = self.opened_at.load(Ordering::Relaxed);
                 let now = self.epoch.elapsed().as_millis() as u64;
-                if now - opened >= self.recovery_timeout_ms {
-                    // Transition to half-open
-                    self.state.store(2, Ordering::Relaxed);
-                    self.successes.store(0, Ordering::Relaxed);
+                if now.saturating_sub(opened) >= self.recovery_timeout_ms {
+                    // Transition to half-open. Probe slots were cleared when the
+                    // circuit opened, so racing callers can't hand out extra probes.
+                    let _ = self
+                        .state
+                        .compare_exchange(1, 2, Ordering::AcqRel, Ordering::Relaxed);
                     CircuitState::HalfOpen
                 } else {
                     CircuitState::Open
@@ -61,15 +113,26 @@ impl CircuitBreaker {
         }
     }
 
-    /// Check if request should be allowed
+    /// Check if request should be allowed, returning `None` when it should get
+    /// the fallback response instead.
+    /// In half-open state this claims a probe slot, held until the returned
+    /// permit is dropped; requests beyond the probe limit are rejected.
     #[inline]
-    pub fn allow_request(&self) -> bool {
+    pub fn allow_request(&self) -> Option<RequestPermit<'_>> {
         match self.state() {
-            CircuitState::Closed => true,
-            CircuitState::Open => false,
+            CircuitState::Closed => Some(RequestPermit { breaker: self, probe: None }),
+            CircuitState::Open => None,
             CircuitState::HalfOpen => {
-                // Allow limited requests in half-open state
-                true
+                let generation = self.probe_generation.load(Ordering::Acquire);
+                self.half_open_probes
+                    .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |taken| {
+                        (taken < self.max_half_open_probes).then_some(taken + 1)
+                    })
+                    .ok()
+                    .map(|_| RequestPermit {
+                        breaker: self,
+                        probe: Some(generation),
+                    })
             }
         }
     }
@@ -83,12 +146,9 @@ impl CircuitBreaker {
                 self.failures.store(0, Ordering::Relaxed);
             }
             CircuitState::HalfOpen => {
-                let successes = self.successes.fetch_add(1, Ordering::Relaxed) + 1;
-                // After a few successes in half-open, close the circuit
-                if successes >= 3 {
-                    self.state.store(0, Ordering::Relaxed);
-                    self.failures.store(0, Ordering::Relaxed);
-                }
+                // A successful probe means the backend has recovered
+                self.failures.store(0, Ordering::Relaxed);
+                self.state.store(0, Ordering::Release);
             }
             CircuitState::Open => {
                 // Shouldn't happen, but handle gracefully
@@ -103,17 +163,12 @@ impl CircuitBreaker {
             CircuitState::Closed => {
                 let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
                 if failures >= self.failure_threshold {
-                    // Open the circuit
-                    self.state.store(1, Ordering::Relaxed);
-                    self.opened_at
-                        .store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+                    self.open();
                 }
             }
             CircuitState::HalfOpen => {
-                // Any failure in half-open goes back to open
-                self.state.store(1, Ordering::Relaxed);
-                self.opened_at
-                    .store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+                // A failed probe goes back to open for another recovery timeout
+                self.open();
             }
             CircuitState::Open => {
                 // Already open
@@ -121,14 +176,356 @@ impl CircuitBreaker {
         }
     }
 
+    /// Record a completed request for expression evaluation. `status` is None for
+    /// network errors. Without an expression this maps to success/failure, where
+    /// network errors and 5xx count as failures.
+    pub fn record_response(&self, status: Option<u16>, latency: Duration) {
+        let failed = status.is_none_or(|code| code >= 500);
+        let Some(ref trigger) = self.trigger else {
+            if failed {
+                self.record_failure();
+            } else {
+                self.record_success();
+            }
+            return;
+        };
+
+        match self.state() {
+            CircuitState::Closed => {
+                if trigger.record(ResponseSample { status, latency }) {
+                    self.open();
+                }
+            }
+            CircuitState::HalfOpen if failed => self.record_failure(),
+            CircuitState::HalfOpen => self.record_success(),
+            CircuitState::Open => {}
+        }
+    }
+
+    fn open(&self) {
+        if let Some(ref trigger) = self.trigger {
+            // Start the next closed period from a clean window
+            trigger.samples.lock().clear();
+        }
+        self.opened_at
+            .store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+        self.clear_probes();
+        self.state.store(1, Ordering::Release);
+    }
+
     /// Reset the circuit breaker
     pub fn reset(&self) {
-        self.state.store(0, Ordering::Relaxed);
+        self.state.store(0, Ordering::Release);
         self.failures.store(0, Ordering::Relaxed);
-        self.successes.store(0, Ordering::Relaxed);
+        self.clear_probes();
+    }
+
+    fn clear_probes(&self) {
+        self.probe_generation.fetch_add(1, Ordering::AcqRel);
+        self.half_open_probes.store(0, Ordering::Release);
+    }
+}
+
+/// Admission returned by [`CircuitBreaker::allow_request`]. A half-open probe
+/// frees its slot when the permit is dropped, so a probe whose outcome is never
+/// recorded (cancelled or timed out) doesn't keep the circuit from recovering.
+#[must_use = "a half-open probe slot is released when the permit is dropped"]
+pub struct RequestPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    /// Probe generation the slot was taken in, or None outside half-open
+    probe: Option<u64>,
+}
+
+impl Drop for RequestPermit<'_> {
+    fn drop(&mut self) {
+        let Some(generation) = self.probe else {
+            return;
+        };
+        // Slots were already cleared if the circuit re-opened or reset meanwhile
+        if self.breaker.probe_generation.load(Ordering::Acquire) == generation {
+            let _ = self.breaker.half_open_probes.fetch_update(
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+                |taken| taken.checked_sub(1),
+            );
+        }
+    }
+}
+
+impl ExpressionTrigger {
+    /// Add a sample, drop the ones older than the window, and evaluate the expression
+    fn record(&self, sample: ResponseSample) -> bool {
+        let now = Instant::now();
+        let mut samples = self.samples.lock();
+        samples.push_back((now, sample));
+        while samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > self.window)
+        {
+            samples.pop_front();
+        }
+
+        self.expression.evaluate(samples.iter().map(|(_, s)| s))
+    }
+}
+
+/// Outcome of a single proxied request, used to evaluate trip expressions.
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseSample {
+    /// Response status code, or None when the request failed with a network error
+    pub status: Option<u16>,
+    /// Time taken to receive the response
+    pub latency: Duration,
+}
+
+/// Errors produced when parsing a circuit breaker expression.
+#[derive(Debug, Error)]
+pub enum ExpressionParseError {
+    /// The expression has invalid syntax.
+    #[error("Invalid circuit breaker expression: {0}")]
+    InvalidSyntax(String),
+
+    /// The expression references an unknown metric function.
+    #[error("Unknown circuit breaker function: {0}")]
+    UnknownFunction(String),
+}
+
+/// Metric functions available in trip expressions
+#[derive(Debug, Clone, PartialEq)]
+pub enum Metric {
+    /// Share of requests that failed with a network error
+    NetworkErrorRatio,
+    /// Responses with status in `[from, to)` divided by those in `[divided_by_from, divided_by_to)`
+    ResponseCodeRatio {
+        /// Numerator range start (inclusive)
+        from: u16,
+        /// Numerator range end (exclusive)
+        to: u16,
+        /// Denominator range start (inclusive)
+        divided_by_from: u16,
+        /// Denominator range end (exclusive)
+        divided_by_to: u16,
+    },
+    /// Latency in milliseconds at the given quantile (0-100)
+    LatencyAtQuantileMs(f64),
+}
+
+/// Comparison operators in trip expressions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+}
+
+/// Parsed circuit breaker trip expression, e.g. `NetworkErrorRatio() > 0.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TripExpression {
+    /// Compare a metric against a constant
+    Compare(Metric, Comparison, f64),
+    /// Both sub-expressions hold
+    And(Box<TripExpression>, Box<TripExpression>),
+    /// Either sub-expression holds
+    Or(Box<TripExpression>, Box<TripExpression>),
+}
+
+impl TripExpression {
+    /// Parse an expression supporting `&&`, `||`, parentheses and the metric functions
+    /// `NetworkErrorRatio()`, `ResponseCodeRatio(from, to, dividedByFrom, dividedByTo)`
+    /// and `LatencyAtQuantileMS(quantile)`.
+    pub fn parse(input: &str) -> Result<Self, ExpressionParseError> {
+        Self::parse_or(input.trim())
+    }
+
+    fn parse_or(input: &str) -> Result<Self, ExpressionParseError> {
+        if let Some(pos) = find_operator(input, "||") {
+            let left = Self::parse_or(&input[..pos])?;
+            let right = Self::parse_or(&input[pos + 2..])?;
+            return Ok(Self::Or(Box::new(left), Box::new(right)));
+        }
+        Self::parse_and(input)
+    }
+
+    fn parse_and(input: &str) -> Result<Self, ExpressionParseError> {
+        if let Some(pos) = find_operator(input, "&&") {
+            let left = Self::parse_and(&input[..pos])?;
+            let right = Self::parse_and(&input[pos + 2..])?;
+            return Ok(Self::And(Box::new(left), Box::new(right)));
+        }
+        Self::parse_primary(input)
+    }
+
+    fn parse_primary(input: &str) -> Result<Self, ExpressionParseError> {
+        let input = input.trim();
+
+        // Handle parentheses
+        if input.starts_with('(') && input.ends_with(')') {
+            return Self::parse_or(&input[1..input.len() - 1]);
+        }
+
+        Self::parse_comparison(input)
+    }
+
+    fn parse_comparison(input: &str) -> Result<Self, ExpressionParseError> {
+        // Two-character operators first so `>=` isn't read as `>`
+        const OPERATORS: [(&str, Comparison); 6] = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+        ];
+
+        let (pos, op, comparison) = OPERATORS
+            .iter()
+            .find_map(|(op, cmp)| find_operator(input, op).map(|pos| (pos, *op, *cmp)))
+            .ok_or_else(|| ExpressionParseError::InvalidSyntax(input.to_string()))?;
+
+        let metric = Self::parse_metric(input[..pos].trim())?;
+        let value = input[pos + op.len()..]
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| ExpressionParseError::InvalidSyntax(input.to_string()))?;
+
+        Ok(Self::Compare(metric, comparison, value))
+    }
+
+    fn parse_metric(input: &str) -> Result<Metric, ExpressionParseError> {
+        let invalid = || ExpressionParseError::InvalidSyntax(input.to_string());
+
+        let paren = input.find('(').ok_or_else(invalid)?;
+        if !input.ends_with(')') {
+            return Err(invalid());
+        }
+        let name = input[..paren].trim();
+        let args: Vec<&str> = input[paren + 1..input.len() - 1]
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect();
+
+        match (name, args.as_slice()) {
+            ("NetworkErrorRatio", []) => Ok(Metric::NetworkErrorRatio),
+            ("ResponseCodeRatio", [from, to, divided_by_from, divided_by_to]) => {
+                let code = |s: &str| s.parse::<u16>().map_err(|_| invalid());
+                Ok(Metric::ResponseCodeRatio {
+                    from: code(from)?,
+                    to: code(to)?,
+                    divided_by_from: code(divided_by_from)?,
+                    divided_by_to: code(divided_by_to)?,
+                })
+            }
+            ("LatencyAtQuantileMS", [quantile]) => {
+                let quantile = quantile.parse::<f64>().map_err(|_| invalid())?;
+                if !(quantile > 0.0 && quantile <= 100.0) {
+                    return Err(invalid());
+                }
+                Ok(Metric::LatencyAtQuantileMs(quantile))
+            }
+            ("NetworkErrorRatio" | "ResponseCodeRatio" | "LatencyAtQuantileMS", _) => Err(invalid()),
+            _ => Err(ExpressionParseError::UnknownFunction(name.to_string())),
+        }
+    }
+
+    /// Evaluate against a window of samples. A metric with nothing to measure
+    /// (empty window, zero denominator) makes its comparison false.
+    pub fn evaluate<'a, I>(&self, samples: I) -> bool
+    where
+        I: IntoIterator<Item = &'a ResponseSample>,
+        I::IntoIter: Clone,
+    {
+        let samples = samples.into_iter();
+        match self {
+            Self::Compare(metric, comparison, value) => metric
+                .value(samples)
+                .is_some_and(|actual| comparison.holds(actual, *value)),
+            Self::And(a, b) => a.evaluate(samples.clone()) && b.evaluate(samples),
+            Self::Or(a, b) => a.evaluate(samples.clone()) || b.evaluate(samples),
+        }
+    }
+}
+
+impl Metric {
+    fn value<'a>(&self, samples: impl Iterator<Item = &'a ResponseSample>) -> Option<f64> {
+        match *self {
+            Metric::NetworkErrorRatio => {
+                let (mut errors, mut total) = (0u64, 0u64);
+                for sample in samples {
+                    total += 1;
+                    errors += u64::from(sample.status.is_none());
+                }
+                ratio(errors, total)
+            }
+            Metric::ResponseCodeRatio {
+                from,
+                to,
+                divided_by_from,
+                divided_by_to,
+            } => {
+                let (mut matched, mut total) = (0u64, 0u64);
+                for code in samples.filter_map(|s| s.status) {
+                    matched += u64::from((from..to).contains(&code));
+                    total += u64::from((divided_by_from..divided_by_to).contains(&code));
+                }
+                ratio(matched, total)
+            }
+            Metric::LatencyAtQuantileMs(quantile) => {
+                let mut latencies: Vec<f64> = samples
+                    .map(|s| s.latency.as_secs_f64() * 1000.0)
+                    .collect();
+                if latencies.is_empty() {
+                    return None;
+                }
+                latencies.sort_by(f64::total_cmp);
+                // Nearest-rank quantile
+                let rank = ((quantile / 100.0) * latencies.len() as f64).ceil() as usize;
+                Some(latencies[rank.clamp(1, latencies.len()) - 1])
+            }
+        }
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+impl Comparison {
+    pub(crate) fn holds(self, actual: f64, expected: f64) -> bool {
+        match self {
+            Comparison::Greater => actual > expected,
+            Comparison::GreaterOrEqual => actual >= expected,
+            Comparison::Less => actual < expected,
+            Comparison::LessOrEqual => actual <= expected,
+            Comparison::Equal => actual == expected,
+            Comparison::NotEqual => actual != expected,
+        }
     }
 }
 
+/// Find `op` outside of parentheses
+pub(crate) fn find_operator(input: &str, op: &str) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if depth == 0 && input[i..].starts_with(op) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,7 +535,7 @@ mod tests {
         let cb = CircuitBreaker::new(3, Duration::from_secs(30));
 
         assert_eq!(cb.state(), CircuitState::Closed);
-        assert!(cb.allow_request());
+        assert!(cb.allow_request().is_some());
 
         // Record failures
         cb.record_failure();
@@ -147,7 +544,7 @@ mod tests {
 
         cb.record_failure();
         assert_eq!(cb.state(), CircuitState::Open);
-        assert!(!cb.allow_request());
+        assert!(cb.allow_request().is_none());
     }
 
     #[test]
@@ -163,4 +560,208 @@ mod tests {
         cb.record_failure();
         assert_eq!(cb.state(), CircuitState::Closed);
     }
+
+    fn tripped(max_probes: u32) -> CircuitBreaker {
+        let cb = CircuitBreaker::new(1, Duration::from_millis(50)).with_half_open_probes(max_probes);
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Open);
+        assert!(cb.allow_request().is_none());
+        cb
+    }
+
+    #[test]
+    fn test_half_open_probe_success_closes() {
+        let cb = tripped(1);
+        std::thread::sleep(Duration::from_millis(60));
+
+        assert_eq!(cb.state(), CircuitState::HalfOpen);
+        let probe = cb.allow_request();
+        assert!(probe.is_some(), "first request is the probe");
+        assert!(cb.allow_request().is_none(), "excess requests get the fallback");
+
+        cb.record_success();
+        drop(probe);
+        assert_eq!(cb.state(), CircuitState::Closed);
+        assert!(cb.allow_request().is_some());
+        assert!(cb.allow_request().is_some());
+    }
+
+    #[test]
+    fn test_half_open_probe_failure_reopens() {
+        let cb = tripped(1);
+        std::thread::sleep(Duration::from_millis(60));
+
+        let probe = cb.allow_request();
+        assert!(probe.is_some());
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Open);
+        assert!(cb.allow_request().is_none());
+
+        // Re-opened for a full recovery timeout, then a fresh probe slot
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cb.state(), CircuitState::HalfOpen);
+        let fresh = cb.allow_request();
+        assert!(fresh.is_some());
+        // The probe from before the re-open can't free the new one's slot
+        drop(probe);
+        assert!(cb.allow_request().is_none());
+        drop(fresh);
+    }
+
+    #[test]
+    fn test_unrecorded_probe_frees_its_slot() {
+        let cb = tripped(1);
+        std::thread::sleep(Duration::from_millis(60));
+
+        // The probe is cancelled before its outcome is recorded
+        let probe = cb.allow_request();
+        assert!(probe.is_some());
+        assert!(cb.allow_request().is_none());
+        drop(probe);
+
+        assert_eq!(cb.state(), CircuitState::HalfOpen);
+        let retry = cb.allow_request();
+        assert!(retry.is_some(), "the next request probes instead");
+        cb.record_success();
+        assert_eq!(cb.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_probe_limit_concurrent() {
+        let cb = tripped(3);
+        std::thread::sleep(Duration::from_millis(60));
+
+        // Every thread holds its permit until all of them have asked
+        let barrier = std::sync::Barrier::new(16);
+        let admitted = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..16)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let permit = cb.allow_request();
+                        barrier.wait();
+                        permit.is_some()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .filter(|&allowed| allowed)
+                .count()
+        });
+        assert_eq!(admitted, 3);
+    }
+
+    fn sample(status: Option<u16>, latency_ms: u64) -> ResponseSample {
+        ResponseSample {
+            status,
+            latency: Duration::from_millis(latency_ms),
+        }
+    }
+
+    fn eval(expression: &str, samples: &[ResponseSample]) -> bool {
+        TripExpression::parse(expression).unwrap().evaluate(samples)
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        assert_eq!(
+            TripExpression::parse("NetworkErrorRatio() > 0.5").unwrap(),
+            TripExpression::Compare(Metric::NetworkErrorRatio, Comparison::Greater, 0.5)
+        );
+        assert_eq!(
+            TripExpression::parse("ResponseCodeRatio(500, 600, 0, 600) >= 0.3").unwrap(),
+            TripExpression::Compare(
+                Metric::ResponseCodeRatio {
+                    from: 500,
+                    to: 600,
+                    divided_by_from: 0,
+                    divided_by_to: 600
+                },
+                Comparison::GreaterOrEqual,
+                0.3
+            )
+        );
+        assert!(matches!(
+            TripExpression::parse("(LatencyAtQuantileMS(50.0) > 100) && NetworkErrorRatio() > 0.1 || NetworkErrorRatio() > 0.5"),
+            Ok(TripExpression::Or(..))
+        ));
+
+        assert!(matches!(
+            TripExpression::parse("Unknown() > 1"),
+            Err(ExpressionParseError::UnknownFunction(_))
+        ));
+        assert!(TripExpression::parse("NetworkErrorRatio()").is_err());
+        assert!(TripExpression::parse("NetworkErrorRatio(1) > 0.5").is_err());
+        assert!(TripExpression::parse("ResponseCodeRatio(500, 600) > 0.5").is_err());
+        assert!(TripExpression::parse("LatencyAtQuantileMS(150) > 10").is_err());
+        assert!(TripExpression::parse("NetworkErrorRatio() > high").is_err());
+    }
+
+    #[test]
+    fn test_network_error_ratio() {
+        let samples = [sample(None, 1), sample(None, 1), sample(Some(200), 1), sample(Some(502), 1)];
+        assert!(eval("NetworkErrorRatio() > 0.4", &samples));
+        assert!(!eval("NetworkErrorRatio() > 0.5", &samples));
+        assert!(eval("NetworkErrorRatio() == 0.5", &samples));
+    }
+
+    #[test]
+    fn test_response_code_ratio() {
+        let samples = [
+            sample(Some(200), 1),
+            sample(Some(200), 1),
+            sample(Some(500), 1),
+            sample(Some(503), 1),
+            sample(None, 1), // network errors have no status and are ignored
+        ];
+        assert!(eval("ResponseCodeRatio(500, 600, 0, 600) > 0.3", &samples));
+        assert!(!eval("ResponseCodeRatio(500, 600, 0, 600) > 0.5", &samples));
+        // 5xx relative to 2xx only
+        assert!(eval("ResponseCodeRatio(500, 600, 200, 300) == 1", &samples));
+    }
+
+    #[test]
+    fn test_latency_at_quantile() {
+        let samples: Vec<_> = (1..=100).map(|ms| sample(Some(200), ms)).collect();
+        assert!(eval("LatencyAtQuantileMS(50.0) == 50", &samples));
+        assert!(eval("LatencyAtQuantileMS(99) > 98", &samples));
+        assert!(!eval("LatencyAtQuantileMS(99) > 99", &samples));
+        assert!(eval("LatencyAtQuantileMS(100) == 100", &samples));
+    }
+
+    #[test]
+    fn test_empty_window_is_false() {
+        assert!(!eval("NetworkErrorRatio() >= 0", &[]));
+        assert!(!eval("LatencyAtQuantileMS(50) >= 0", &[]));
+        // Zero denominator: no responses in the divided-by range
+        let samples = [sample(Some(500), 1)];
+        assert!(!eval("ResponseCodeRatio(500, 600, 200, 300) >= 0", &samples));
+        assert!(!eval("ResponseCodeRatio(500, 600, 200, 300) < 1", &samples));
+    }
+
+    #[test]
+    fn test_breaker_trips_on_expression() {
+        let expression = TripExpression::parse("ResponseCodeRatio(500, 600, 0, 600) > 0.5").unwrap();
+        let cb = CircuitBreaker::new(u32::MAX, Duration::from_millis(50))
+            .with_expression(expression, Duration::from_secs(10));
+
+        cb.record_response(Some(200), Duration::from_millis(5));
+        cb.record_response(Some(500), Duration::from_millis(5));
+        assert_eq!(cb.state(), CircuitState::Closed);
+
+        cb.record_response(Some(502), Duration::from_millis(5));
+        assert_eq!(cb.state(), CircuitState::Open);
+        assert!(cb.allow_request().is_none());
+
+        // A healthy probe closes the breaker with a fresh window
+        std::thread::sleep(Duration::from_millis(60));
+        let probe = cb.allow_request();
+        assert!(probe.is_some());
+        cb.record_response(Some(200), Duration::from_millis(5));
+        assert_eq!(cb.state(), CircuitState::Closed);
+        cb.record_response(Some(200), Duration::from_millis(5));
+        cb.record_response(Some(500), Duration::from_millis(5));
+        assert_eq!(cb.state(), CircuitState::Closed);
+    }
 }
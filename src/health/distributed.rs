@@ -1,200 +1,93 @@
+use super::{HealthChecker, HealthStatus};
 use crate::config::HealthCheck;
 use crate::store::{HealthStatus as StoreHealthStatus, Store};
-use hyper::body::Bytes;
-use hyper::{Method, Request, StatusCode};
-use hyper_util::client::legacy::connect::HttpConnector;
-use hyper_util::client::legacy::Client;
-use hyper_util::rt::TokioExecutor;
+use dashmap::DashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::{interval, timeout};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::interval;
 use tracing::{debug, info, warn};
 
 /// Distributed health checker that coordinates health checks across the cluster
 ///
 /// When in HA mode:
 /// - Only the leader node performs active health checks
-/// - Health status is stored in the distributed store
-/// - All nodes read health status from the store
-/// - Leader election ensures only one node is checking at a time
+/// - The leader publishes each result to the distributed store
+/// - Followers apply the published results to their local status (see
+///   [`DistributedHealthManager`]), so every node's balancer sees the same health
+/// - When leadership moves, the new leader starts probing on its next tick
 pub struct DistributedHealthChecker {
     service_name: String,
     server_url: String,
-    config: HealthCheck,
+    checker: HealthChecker,
+    check_interval: Duration,
+    status: Arc<HealthStatus>,
     store: Arc<dyn Store>,
     is_leader: Arc<AtomicBool>,
-    #[allow(dead_code)]
-    node_id: String,
-    client: Client<HttpConnector, http_body_util::Empty<Bytes>>,
 }
 
 impl DistributedHealthChecker {
-    /// Create a new distributed health checker
+    /// Create a new distributed health checker that keeps `status` (the
+    /// balancer's view of the server) up to date
     pub fn new(
         service_name: String,
         server_url: String,
         config: HealthCheck,
+        status: Arc<HealthStatus>,
         store: Arc<dyn Store>,
         is_leader: Arc<AtomicBool>,
-        node_id: String,
     ) -> Self {
-        let connector = HttpConnector::new();
-        let client = Client::builder(TokioExecutor::new())
-            .pool_idle_timeout(Duration::from_secs(30))
-            .pool_max_idle_per_host(2)
-            .build(connector);
+        let check_interval = config.interval.as_std();
+        let checker = HealthChecker::new(config, server_url.clone(), Arc::clone(&status));
 
         Self {
             service_name,
             server_url,
-            config,
+            checker,
+            check_interval,
+            status,
             store,
             is_leader,
-            node_id,
-            client,
         }
     }
 
     /// Start the health check loop
     pub async fn start(self) {
-        let interval_duration = self.config.interval.as_std();
-        let check_timeout = self.config.timeout.as_std();
-        let healthy_threshold = 2u32;
-        let unhealthy_threshold = 3u32;
+        // Pick up what the leader has published so far; later results arrive
+        // through the manager's subscription
+        match self.store.health_get(&self.service_name, &self.server_url).await {
+            Ok(Some(status)) => apply_published(&self.status, &status),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read health status from store: {}", e),
+        }
 
-        let mut ticker = interval(interval_duration);
-        let mut local_consecutive_successes = 0u32;
-        let mut local_consecutive_failures = 0u32;
-        let mut current_health = true;
+        let mut ticker = interval(self.check_interval);
 
         loop {
             ticker.tick().await;
 
             // Only perform health check if we're the leader
             if !self.is_leader.load(Ordering::Relaxed) {
-                // Not leader, just read health status from store
-                match self.store.health_get(&self.service_name, &self.server_url).await {
-                    Ok(Some(status)) => {
-                        current_health = status.healthy;
-                        debug!(
-                            "Read health status for {} from store: healthy={}",
-                            self.server_url, status.healthy
-                        );
-                    }
-                    Ok(None) => {
-                        // No status in store, assume healthy
-                        current_health = true;
-                    }
-                    Err(e) => {
-                        warn!("Failed to read health status from store: {}", e);
-                    }
-                }
                 continue;
             }
 
-            // We're the leader, perform the health check
-            let result = timeout(check_timeout, self.perform_http_check()).await;
-
-            match result {
-                Ok(Ok(())) => {
-                    local_consecutive_successes += 1;
-                    local_consecutive_failures = 0;
-
-                    if !current_health && local_consecutive_successes >= healthy_threshold {
-                        current_health = true;
-                        info!("Server {} is now healthy", self.server_url);
-                    }
-                }
-                Ok(Err(e)) => {
-                    local_consecutive_failures += 1;
-                    local_consecutive_successes = 0;
-
-                    if current_health && local_consecutive_failures >= unhealthy_threshold {
-                        current_health = false;
-                        warn!("Server {} is now unhealthy: {}", self.server_url, e);
-                    }
-                }
-                Err(_) => {
-                    local_consecutive_failures += 1;
-                    local_consecutive_successes = 0;
-
-                    if current_health && local_consecutive_failures >= unhealthy_threshold {
-                        current_health = false;
-                        warn!("Server {} is now unhealthy: timeout", self.server_url);
-                    }
-                }
-            }
-
-            // Update distributed store
-            let status = StoreHealthStatus {
-                healthy: current_health,
-                last_check: current_time_millis(),
-                consecutive_failures: local_consecutive_failures,
-                last_error: if current_health {
-                    None
-                } else {
-                    Some("Health check failed".to_string())
-                },
-            };
-
-            if let Err(e) = self.store.health_set(&self.service_name, &self.server_url, &status).await {
-                warn!("Failed to update health status in store: {}", e);
-            }
+            self.checker.check().await;
+            self.publish().await;
         }
     }
 
-    async fn perform_http_check(&self) -> Result<(), String> {
-        let scheme = self.config.scheme.as_deref().unwrap_or("http");
-        let check_url = format!(
-            "{}://{}{}",
-            scheme,
-            self.server_url
-                .trim_start_matches("http://")
-                .trim_start_matches("https://")
-                .trim_end_matches('/'),
-            self.config.path
-        );
-
-        let uri: hyper::Uri = check_url
-            .parse()
-            .map_err(|e| format!("Invalid URL: {}", e))?;
-
-        let method = self
-            .config
-            .method
-            .as_ref()
-            .map(|m| m.parse().unwrap_or(Method::GET))
-            .unwrap_or(Method::GET);
-
-        let req = Request::builder()
-            .method(method)
-            .uri(uri)
-            .header("user-agent", "traffic-management-health-checker/1.0")
-            .body(http_body_util::Empty::<Bytes>::new())
-            .map_err(|e| format!("Failed to build request: {}", e))?;
-
-        let response = self
-            .client
-            .request(req)
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
-
-        let status = response.status();
-
-        if let Some(expected) = self.config.status {
-            if status.as_u16() != expected {
-                return Err(format!("Expected status {}, got {}", expected, status));
-            }
-            return Ok(());
-        }
+    /// Write this server's current status to the store for the followers
+    async fn publish(&self) {
+        let status = StoreHealthStatus {
+            healthy: self.status.is_healthy(),
+            last_check: current_time_millis(),
+            consecutive_failures: self.status.consecutive_failures.load(Ordering::Relaxed),
+            last_error: self.status.last_error.read().clone(),
+        };
 
-        if status.is_success() || status == StatusCode::NOT_FOUND {
-            Ok(())
-        } else if status.is_server_error() {
-            Err(format!("Server error: {}", status))
-        } else {
-            Ok(())
+        if let Err(e) = self.store.health_set(&self.service_name, &self.server_url, &status).await {
+            warn!("Failed to update health status in store: {}", e);
         }
     }
 }
@@ -204,39 +97,60 @@ pub struct DistributedHealthManager {
     store: Arc<dyn Store>,
     is_leader: Arc<AtomicBool>,
     node_id: String,
+    /// Local status of every checked server, keyed by (service, server URL)
+    statuses: Arc<DashMap<(String, String), Arc<HealthStatus>>>,
+    /// Whether the health change subscription has been started
+    following: AtomicBool,
 }
 
 impl DistributedHealthManager {
-    /// Create a new distributed health manager.
+    /// Create a new distributed health manager. `is_leader` is the health
+    /// check leadership flag kept by the cluster's leader election.
     pub fn new(store: Arc<dyn Store>, is_leader: Arc<AtomicBool>, node_id: String) -> Self {
         Self {
             store,
             is_leader,
             node_id,
+            statuses: Arc::new(DashMap::new()),
+            following: AtomicBool::new(false),
         }
     }
 
-    /// Start health checks for a service
+    /// Start health checks for a service. Each server is paired with the
+    /// status its balancer reads: the leader probes and updates it, followers
+    /// update it from the leader's published results.
     pub fn start_health_checks(
         &self,
         service_name: &str,
-        servers: &[String],
+        servers: &[(String, Arc<HealthStatus>)],
         config: &HealthCheck,
     ) {
-        for server_url in servers {
+        for (server_url, status) in servers {
+            self.statuses
+                .insert((service_name.to_string(), server_url.clone()), Arc::clone(status));
+
             let checker = DistributedHealthChecker::new(
                 service_name.to_string(),
                 server_url.clone(),
                 config.clone(),
+                Arc::clone(status),
                 Arc::clone(&self.store),
                 Arc::clone(&self.is_leader),
-                self.node_id.clone(),
             );
 
             tokio::spawn(async move {
                 checker.start().await;
             });
         }
+
+        if !self.following.swap(true, Ordering::Relaxed) {
+            tokio::spawn(follow_health_changes(
+                Arc::clone(&self.store),
+                Arc::clone(&self.is_leader),
+                Arc::clone(&self.statuses),
+                self.node_id.clone(),
+            ));
+        }
     }
 
     /// Get health status for all servers in a service
@@ -263,6 +177,61 @@ impl DistributedHealthManager {
     }
 }
 
+/// Apply the leader's published health results to the local statuses while
+/// this node is a follower
+async fn follow_health_changes(
+    store: Arc<dyn Store>,
+    is_leader: Arc<AtomicBool>,
+    statuses: Arc<DashMap<(String, String), Arc<HealthStatus>>>,
+    node_id: String,
+) {
+    let mut changes = match store.subscribe_health_changes().await {
+        Ok(rx) => rx,
+        Err(e) => {
+            warn!("Node {} failed to subscribe to health changes: {}", node_id, e);
+            return;
+        }
+    };
+
+    loop {
+        match changes.recv().await {
+            Ok((service, server_url, status)) => {
+                // The leader's own results are already in its local status
+                if is_leader.load(Ordering::Relaxed) {
+                    continue;
+                }
+                if let Some(local) = statuses.get(&(service, server_url.clone())) {
+                    if local.is_healthy() != status.healthy {
+                        info!(
+                            "Server {} is now {} (reported by health check leader)",
+                            server_url,
+                            if status.healthy { "healthy" } else { "unhealthy" }
+                        );
+                    }
+                    apply_published(&local, &status);
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                debug!("Node {} skipped {} health changes", node_id, skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Copy a published health result into a local status
+fn apply_published(local: &HealthStatus, published: &StoreHealthStatus) {
+    if published.healthy {
+        local.mark_healthy();
+    } else {
+        local.mark_unhealthy();
+    }
+    local
+        .consecutive_failures
+        .store(published.consecutive_failures, Ordering::Relaxed);
+    *local.last_error.write() = published.last_error.clone();
+}
+
 fn current_time_millis() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -273,7 +242,11 @@ fn current_time_millis() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cluster::ClusterManager;
+    use crate::config::ClusterConfig;
     use crate::store::LocalStore;
+    use std::sync::atomic::AtomicU16;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn test_health_manager_creation() {
@@ -314,4 +287,103 @@ mod tests {
             .await;
         assert!(is_healthy);
     }
+
+    /// Backend answering every request with the status code in `code`
+    async fn backend(code: Arc<AtomicU16>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let code = code.load(Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = conn.read(&mut buf).await;
+                    let response =
+                        format!("HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", code);
+                    let _ = conn.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    /// A cluster node health checking `server_url`; returns the node and the
+    /// status its balancer would read
+    async fn node(store: &Arc<dyn Store>, node_id: &str, server_url: &str) -> (Arc<ClusterManager>, Arc<HealthStatus>) {
+        let config = ClusterConfig {
+            enabled: false,
+            node_id: Some(node_id.to_string()),
+            advertise_address: None,
+            store: None,
+            heartbeat_interval: crate::config::Duration::from_secs(10),
+            node_timeout: crate::config::Duration::from_secs(30),
+            drain_timeout: crate::config::Duration::from_secs(30),
+            leader_ttl: crate::config::Duration::from_millis(300),
+            config_providers: vec![],
+        };
+        let cluster = ClusterManager::new(config, Arc::clone(store)).await.unwrap();
+        let health_check = HealthCheck {
+            path: "/health".to_string(),
+            interval: crate::config::Duration::from_millis(20),
+            timeout: crate::config::Duration::from_secs(1),
+            scheme: None,
+            mode: None,
+            method: None,
+            status: None,
+            port: None,
+            hostname: None,
+            headers: Default::default(),
+            follow_redirects: false,
+        };
+
+        let status = Arc::new(HealthStatus::new());
+        let manager = DistributedHealthManager::new(
+            Arc::clone(store),
+            cluster.health_check_leader_flag(),
+            node_id.to_string(),
+        );
+        manager.start_health_checks("api", &[(server_url.to_string(), Arc::clone(&status))], &health_check);
+        (cluster, status)
+    }
+
+    async fn wait_until(what: &str, condition: impl Fn() -> bool) {
+        for _ in 0..300 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out waiting until {}", what);
+    }
+
+    #[tokio::test]
+    async fn test_only_leader_probes_and_follower_takes_over() {
+        let code = Arc::new(AtomicU16::new(200));
+        let server_url = backend(Arc::clone(&code)).await;
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+
+        let (leader, leader_status) = node(&store, "node-a", &server_url).await;
+        wait_until("node-a leads", || leader.is_health_check_leader()).await;
+        let (follower, follower_status) = node(&store, "node-b", &server_url).await;
+
+        // The leader sees the failures; the follower learns of them from the store
+        code.store(500, Ordering::Relaxed);
+        wait_until("the follower marks the server down", || !follower_status.is_healthy()).await;
+        assert!(!leader_status.is_healthy());
+        assert!(leader_status.last_check.read().is_some());
+        assert!(follower_status.last_check.read().is_none(), "follower probed the server");
+        assert!(!follower.is_health_check_leader());
+        assert!(!store.health_get("api", &server_url).await.unwrap().unwrap().healthy);
+
+        // With the leader gone, the follower wins the election and probes itself
+        leader.shutdown().await.unwrap();
+        wait_until("node-b leads", || follower.is_health_check_leader()).await;
+        code.store(200, Ordering::Relaxed);
+        wait_until("node-b marks the server up", || follower_status.is_healthy()).await;
+        assert!(follower_status.last_check.read().is_some());
+        assert!(store.health_get("api", &server_url).await.unwrap().unwrap().healthy);
+
+        follower.shutdown().await.unwrap();
+    }
 }
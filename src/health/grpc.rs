@@ -0,0 +1,312 @@
+//! gRPC health checking via the standard `grpc.health.v1.Health/Check` RPC.
+
+use crate::proxy::{GrpcStatus, Http2ConnectionPool};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, StatusCode};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+const HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// `HealthCheckResponse.ServingStatus` values from grpc.health.v1
+const SERVING: u64 = 1;
+const NOT_SERVING: u64 = 2;
+const SERVICE_UNKNOWN: u64 = 3;
+
+/// Probe for a single gRPC backend over a pooled h2c connection.
+pub(super) struct GrpcHealthCheck {
+    pool: Http2ConnectionPool,
+    host: String,
+    port: u16,
+    /// Service name sent in the request; empty asks about the server as a whole
+    service: String,
+}
+
+impl GrpcHealthCheck {
+    /// Build a probe for `server_url`. The service name is taken from the
+    /// health check path without its leading slash, so the default `/`
+    /// checks overall server health.
+    pub(super) fn new(server_url: &str, port: Option<u16>, path: &str) -> Self {
+        let uri: Option<hyper::Uri> = server_url.parse().ok();
+        let host = uri
+            .as_ref()
+            .and_then(|uri| uri.host())
+            .unwrap_or("localhost")
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let url_port = uri.as_ref().and_then(|uri| uri.port_u16());
+
+        Self {
+            pool: Http2ConnectionPool::new(),
+            host: host.to_string(),
+            port: port.or(url_port).unwrap_or(80),
+            service: path.trim_start_matches('/').to_string(),
+        }
+    }
+
+    /// Call Health/Check. SERVING is healthy; NOT_SERVING, UNKNOWN and
+    /// SERVICE_UNKNOWN are not. Backends that don't implement the health
+    /// service fall back to a plain TCP connect check.
+    pub(super) async fn check(&self) -> Result<(), String> {
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{}:{}{}", self.host, self.port, HEALTH_CHECK_PATH))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .header("user-agent", "traffic-management-health-checker/1.0")
+            .body(
+                Full::new(encode_request(&self.service))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .map_err(|e| format!("Failed to build request: {}", e))?;
+
+        let response = self
+            .pool
+            .send_request(&self.host, self.port, req)
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if response.status() != StatusCode::OK {
+            return Err(format!("Unexpected HTTP status {}", response.status()));
+        }
+
+        // Trailers-Only responses carry grpc-status in the headers
+        let header_status = grpc_status(response.headers());
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        let status = body.trailers().and_then(grpc_status).or(header_status);
+
+        match status {
+            Some(0) => {}
+            Some(code) if code == GrpcStatus::Unimplemented as i32 => {
+                debug!(
+                    "{}:{} does not implement grpc.health.v1, falling back to TCP check",
+                    self.host, self.port
+                );
+                return self.tcp_check().await;
+            }
+            Some(code) => return Err(format!("Health check RPC failed with grpc-status {}", code)),
+            None => return Err("Missing grpc-status in response".to_string()),
+        }
+
+        match decode_response(&body.to_bytes())? {
+            SERVING => Ok(()),
+            NOT_SERVING => Err("Serving status NOT_SERVING".to_string()),
+            SERVICE_UNKNOWN => Err(format!("Service '{}' unknown to backend", self.service)),
+            _ => Err("Serving status UNKNOWN".to_string()),
+        }
+    }
+
+    async fn tcp_check(&self) -> Result<(), String> {
+        TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("TCP connect failed: {}", e))
+    }
+}
+
+fn grpc_status(headers: &hyper::HeaderMap) -> Option<i32> {
+    headers.get("grpc-status")?.to_str().ok()?.parse().ok()
+}
+
+/// Length-prefixed gRPC message holding `HealthCheckRequest { string service = 1; }`
+fn encode_request(service: &str) -> Bytes {
+    let mut message = Vec::new();
+    if !service.is_empty() {
+        message.push(0x0a);
+        push_varint(&mut message, service.len() as u64);
+        message.extend_from_slice(service.as_bytes());
+    }
+
+    let mut frame = Vec::with_capacity(5 + message.len());
+    frame.push(0); // uncompressed
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend(message);
+    Bytes::from(frame)
+}
+
+/// Extract `status` (field 1) from a length-prefixed `HealthCheckResponse`.
+/// A missing field decodes as the proto3 default, UNKNOWN (0).
+fn decode_response(body: &[u8]) -> Result<u64, String> {
+    if body.len() < 5 {
+        return Err("Truncated gRPC response".to_string());
+    }
+    if body[0] != 0 {
+        return Err("Compressed gRPC responses are not supported".to_string());
+    }
+    let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+    let mut message = body
+        .get(5..5 + len)
+        .ok_or_else(|| "Truncated gRPC response".to_string())?;
+
+    let mut status = 0;
+    while !message.is_empty() {
+        let key = read_varint(&mut message)?;
+        match key & 0x7 {
+            0 => {
+                let value = read_varint(&mut message)?;
+                if key >> 3 == 1 {
+                    status = value;
+                }
+            }
+            // Skip unknown length-delimited and fixed-width fields
+            2 => {
+                let len = read_varint(&mut message)? as usize;
+                message = message.get(len..).ok_or("Truncated protobuf field")?;
+            }
+            1 => message = message.get(8..).ok_or("Truncated protobuf field")?,
+            5 => message = message.get(4..).ok_or("Truncated protobuf field")?,
+            wire_type => return Err(format!("Unsupported protobuf wire type {}", wire_type)),
+        }
+    }
+
+    Ok(status)
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or("Truncated protobuf varint")?;
+        *input = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Protobuf varint too long".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::combinators::BoxBody;
+    use hyper::body::{Frame, Incoming};
+    use hyper::service::service_fn;
+    use hyper::{HeaderMap, Response};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use std::convert::Infallible;
+    use tokio::net::TcpListener;
+
+    /// How the mock backend answers Health/Check
+    #[derive(Clone, Copy)]
+    enum Reply {
+        Status(u64),
+        Unimplemented,
+    }
+
+    /// Spawn an h2c server that answers every Health/Check call with `reply`
+    async fn spawn_health_server(reply: Reply) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| async move {
+                        assert_eq!(req.uri().path(), HEALTH_CHECK_PATH);
+                        let request = req.into_body().collect().await.unwrap().to_bytes();
+                        assert_eq!(&request[..], &encode_request("")[..]);
+
+                        let response = match reply {
+                            Reply::Status(status) => {
+                                let mut trailers = HeaderMap::new();
+                                trailers.insert("grpc-status", "0".parse().unwrap());
+                                let frames = vec![
+                                    Ok::<_, Infallible>(Frame::data(Bytes::from(vec![0, 0, 0, 0, 2, 0x08, status as u8]))),
+                                    Ok(Frame::trailers(trailers)),
+                                ];
+                                let body: BoxBody<Bytes, Infallible> =
+                                    http_body_util::StreamBody::new(futures::stream::iter(frames)).boxed();
+                                Response::builder()
+                                    .header("content-type", "application/grpc")
+                                    .body(body)
+                                    .unwrap()
+                            }
+                            Reply::Unimplemented => Response::builder()
+                                .header("content-type", "application/grpc")
+                                .header("grpc-status", "12")
+                                .body(Full::new(Bytes::new()).boxed())
+                                .unwrap(),
+                        };
+                        Ok::<_, Infallible>(response)
+                    });
+                    let _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        port
+    }
+
+    fn probe(port: u16) -> GrpcHealthCheck {
+        GrpcHealthCheck::new(&format!("h2c://127.0.0.1:{}", port), None, "/")
+    }
+
+    #[tokio::test]
+    async fn test_serving_is_healthy() {
+        let port = spawn_health_server(Reply::Status(SERVING)).await;
+        assert_eq!(probe(port).check().await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_not_serving_is_unhealthy() {
+        let port = spawn_health_server(Reply::Status(NOT_SERVING)).await;
+        let err = probe(port).check().await.unwrap_err();
+        assert!(err.contains("NOT_SERVING"), "{err}");
+
+        let port = spawn_health_server(Reply::Status(0)).await;
+        assert!(probe(port).check().await.unwrap_err().contains("UNKNOWN"));
+    }
+
+    #[tokio::test]
+    async fn test_unimplemented_falls_back_to_tcp() {
+        let port = spawn_health_server(Reply::Unimplemented).await;
+        assert_eq!(probe(port).check().await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_backend_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        assert!(probe(port).check().await.is_err());
+    }
+
+    #[test]
+    fn test_new_parses_target() {
+        let check = GrpcHealthCheck::new("http://backend:50051", None, "/my.pkg.Service");
+        assert_eq!((check.host.as_str(), check.port), ("backend", 50051));
+        assert_eq!(check.service, "my.pkg.Service");
+
+        let check = GrpcHealthCheck::new("h2c://[::1]:9000/", Some(9001), "/");
+        assert_eq!((check.host.as_str(), check.port), ("::1", 9001));
+        assert_eq!(check.service, "");
+    }
+
+    #[test]
+    fn test_request_encoding_round_trip() {
+        assert_eq!(&encode_request("")[..], &[0, 0, 0, 0, 0]);
+        assert_eq!(&encode_request("svc")[..], &[0, 0, 0, 0, 5, 0x0a, 3, b's', b'v', b'c']);
+
+        // Unknown fields before the status are skipped
+        let body = [0, 0, 0, 0, 6, 0x12, 0x02, b'h', b'i', 0x08, 0x01];
+        assert_eq!(decode_response(&body), Ok(SERVING));
+        assert!(decode_response(&[0, 0, 0, 0, 4, 0x08]).is_err());
+    }
+}
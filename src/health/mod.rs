@@ -3,12 +3,17 @@
 mod checker;
 mod circuit_breaker;
 mod distributed;
+mod grpc;
 mod passive;
 
 /// Active HTTP health checker that polls backends on a schedule.
 pub use checker::HealthChecker;
 /// Circuit breaker with closed/open/half-open state transitions.
-pub use circuit_breaker::{CircuitBreaker, CircuitState};
+pub use circuit_breaker::{
+    CircuitBreaker, CircuitState, Comparison, ExpressionParseError, Metric, RequestPermit,
+    ResponseSample, TripExpression,
+};
+pub(crate) use circuit_breaker::find_operator;
 /// Cluster-aware health checker coordinated via distributed store.
 pub use distributed::{DistributedHealthChecker, DistributedHealthManager};
 /// Passive health monitoring based on response codes and latencies.
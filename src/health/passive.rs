@@ -156,6 +156,12 @@ impl PassiveHealthChecker {
             .unwrap_or(true)
     }
 
+    /// Returns when the backend was last marked unhealthy, or `None` if it is healthy or unknown.
+    pub fn unhealthy_since(&self, backend_url: &str) -> Option<Instant> {
+        let backends = self.backends.read();
+        backends.get(backend_url).and_then(|s| s.unhealthy_since)
+    }
+
     /// Record a response from a backend
     pub fn record_response(
         &self,
@@ -212,6 +218,9 @@ impl PassiveHealthChecker {
                 state.consecutive_successes = 0;
                 return HealthChange::BecameUnhealthy;
             }
+        } else if is_failure {
+            // A failed trial request re-arms the recovery interval
+            state.unhealthy_since = Some(Instant::now());
         } else {
             // Check if should mark healthy again
             if state.consecutive_successes >= self.config.success_threshold {
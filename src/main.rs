@@ -1,14 +1,17 @@
 use anyhow::Result;
 use clap::Parser;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use trafficcop::{
+    cluster::create_store_from_config,
     config::Config,
     metrics,
     server::Server,
     tls::AcmeManagerBuilder,
+    Store,
 };
 
 #[cfg(unix)]
@@ -81,20 +84,52 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    // Start metrics server if configured
-    if let Some(ref metrics_config) = config.metrics
-        && let Some(ref prometheus) = metrics_config.prometheus {
+    // Start metrics exporters if configured
+    if let Some(ref metrics_config) = config.metrics {
+        if let Some(ref prometheus) = metrics_config.prometheus {
             info!(
                 "Starting Prometheus metrics server on {}",
                 prometheus.address
             );
-            if let Err(e) = metrics::start_metrics_server(&prometheus.address) {
-                warn!(
-                    "Failed to start metrics server: {}. Continuing without metrics.",
+        }
+        if let Some(ref statsd) = metrics_config.statsd {
+            info!("Pushing StatsD metrics to {}", statsd.address);
+        }
+        if let Err(e) = metrics::start_exporters(metrics_config) {
+            warn!(
+                "Failed to start metrics exporters: {}. Continuing without metrics.",
+                e
+            );
+        }
+    }
+
+    // In cluster mode sticky sessions, rate limits, digest nonce counts and
+    // ACME challenges are kept in the shared store so every node sees them
+    let cluster_store: Option<Arc<dyn Store>> = match config.cluster.as_ref().filter(|c| c.enabled) {
+        Some(cluster) => match create_store_from_config(cluster).await {
+            Ok(store) => Some(store),
+            Err(e) => {
+                error!(
+                    "Failed to connect to cluster store: {}. Continuing with node-local state.",
                     e
                 );
+                None
             }
-        }
+        },
+        None => None,
+    };
+    if cluster_store.is_none()
+        && config
+            .middlewares()
+            .values()
+            .any(|m| m.rate_limit.as_ref().is_some_and(|r| r.distributed))
+    {
+        warn!("rateLimit middlewares with distributed: true count per node without a cluster store");
+    }
+    let local_server = |config: Config, config_path: PathBuf| match &cluster_store {
+        Some(store) => Server::with_store(config, config_path, Arc::clone(store)),
+        None => Server::with_path(config, config_path),
+    };
 
     // Initialize ACME if configured via certificatesResolvers
     let server = if let Some((resolver_name, resolver)) = config
@@ -110,12 +145,21 @@ async fn main() -> Result<()> {
 
         let ca_server = acme_config.ca_server.as_deref();
 
-        let mut builder = AcmeManagerBuilder::new(&acme_config.email, &acme_config.storage);
+        let mut builder = AcmeManagerBuilder::new(&acme_config.email, &acme_config.storage)
+            .renew_before(acme_config.renew_before.as_std());
 
         if let Some(ca) = ca_server {
             builder = builder.ca_server(ca);
         }
 
+        if let Some(dns) = &acme_config.dns_challenge {
+            builder = builder.dns_challenge(dns.clone());
+        }
+
+        if let Some(store) = &cluster_store {
+            builder = builder.store(Arc::clone(store));
+        }
+
         // Domains are typically configured per-router via tls.domains in Traefik
         // For now, we'll collect domains from routers that use this resolver
         for router in config.routers().values() {
@@ -136,11 +180,11 @@ async fn main() -> Result<()> {
             }
             Err(e) => {
                 error!("Failed to initialize ACME: {}. Starting without ACME.", e);
-                Server::with_path(config, args.config)
+                local_server(config, args.config)
             }
         }
     } else {
-        Server::with_path(config, args.config)
+        local_server(config, args.config)
     };
 
     info!("Starting TrafficCop server");
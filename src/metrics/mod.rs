@@ -1,7 +1,18 @@
-//! Prometheus metrics collection for HTTP requests, backend health, and connection tracking.
+//! Metrics collection for HTTP requests, backend health, and connection tracking,
+//! exported via Prometheus scrape and/or StatsD push.
 
+mod statsd;
+
+/// StatsD/DogStatsD push recorder.
+pub use statsd::StatsdRecorder;
+
+use crate::config::{MetricsConfig, PrometheusConfig};
 use metrics::{counter, gauge, histogram, describe_counter, describe_gauge, describe_histogram};
-use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use arc_swap::ArcSwap;
+use metrics_util::layers::FanoutBuilder;
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock};
 use std::time::Duration;
 
 /// Register all metric descriptions with the global recorder.
@@ -25,35 +36,212 @@ pub fn init_metrics() {
     describe_gauge!("backend_health", "Backend health status (1=healthy, 0=unhealthy)");
     describe_gauge!("active_connections", "Number of active connections");
     describe_gauge!("connection_pool_size", "Size of connection pool");
+    describe_gauge!("udp_active_sessions", "Number of UDP clients pinned to a backend");
 }
 
-/// Start a Prometheus HTTP scrape endpoint on the given address.
-pub fn start_metrics_server(addr: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let addr: std::net::SocketAddr = addr.parse()?;
+/// Prometheus builder with the configured histogram buckets applied.
+fn prometheus_builder(config: &PrometheusConfig) -> Result<PrometheusBuilder, BuildError> {
+    let mut builder = PrometheusBuilder::new();
+    if !config.buckets.is_empty() {
+        builder = builder.set_buckets(&config.buckets)?;
+    }
+    if let Some(buckets) = &config.request_duration_buckets {
+        builder = builder.set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            buckets,
+        )?;
+    }
+    if let Some(buckets) = &config.backend_duration_buckets {
+        builder = builder.set_buckets_for_metric(
+            Matcher::Full("backend_request_duration_seconds".to_string()),
+            buckets,
+        )?;
+    }
+    Ok(builder)
+}
 
-    PrometheusBuilder::new()
-        .with_http_listener(addr)
-        .install()?;
+/// Handle to the installed Prometheus recorder, shared by every scrape endpoint
+static PROMETHEUS_HANDLE: parking_lot::Mutex<Option<PrometheusHandle>> = parking_lot::Mutex::new(None);
+
+/// Start a Prometheus HTTP scrape endpoint on the configured address.
+/// Must be called from within a Tokio runtime.
+pub fn start_metrics_server(config: &PrometheusConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let addr: std::net::SocketAddr = config.address.parse()?;
+
+    let (recorder, exporter) = prometheus_builder(config)?.with_http_listener(addr).build()?;
+    let handle = recorder.handle();
+    metrics::set_global_recorder(recorder)
+        .map_err(|_| "a global metrics recorder is already installed")?;
+    *PROMETHEUS_HANDLE.lock() = Some(handle);
+    tokio::spawn(async move {
+        if let Err(e) = exporter.await {
+            tracing::error!("Prometheus metrics endpoint failed: {:?}", e);
+        }
+    });
+
+    Metrics::set_global(Metrics::new(LabelPolicy::from_config(config)));
 
     init_metrics();
 
     Ok(())
 }
 
-/// Install the Prometheus recorder and return a handle for manual metric rendering.
+/// Start every exporter in `config` (Prometheus scrape endpoint, StatsD push)
+/// behind a single global recorder, so each metric reaches all of them.
+/// Must be called from within a Tokio runtime.
+pub fn start_exporters(config: &MetricsConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut fanout = FanoutBuilder::default();
+    let mut exporters = 0;
+    let mut prometheus_handle = None;
+
+    if let Some(prometheus) = &config.prometheus {
+        let addr: std::net::SocketAddr = prometheus.address.parse()?;
+        let (recorder, exporter) = prometheus_builder(prometheus)?.with_http_listener(addr).build()?;
+        prometheus_handle = Some(recorder.handle());
+        tokio::spawn(async move {
+            if let Err(e) = exporter.await {
+                tracing::error!("Prometheus metrics endpoint failed: {:?}", e);
+            }
+        });
+        fanout = fanout.add_recorder(recorder);
+        exporters += 1;
+    }
+
+    if let Some(statsd) = &config.statsd {
+        let recorder = StatsdRecorder::new(statsd);
+        tokio::spawn(recorder.exporter());
+        fanout = fanout.add_recorder(recorder);
+        exporters += 1;
+    }
+
+    if exporters == 0 {
+        return Ok(());
+    }
+
+    Metrics::set_global(Metrics::from_config(config));
+
+    metrics::set_global_recorder(fanout.build())
+        .map_err(|_| "a global metrics recorder is already installed")?;
+    if prometheus_handle.is_some() {
+        *PROMETHEUS_HANDLE.lock() = prometheus_handle;
+    }
+    init_metrics();
+
+    Ok(())
+}
+
+/// Return a handle for manual metric rendering, reusing the recorder installed by
+/// [`start_exporters`] or [`start_metrics_server`], or installing one on first use.
 pub fn get_prometheus_handle() -> Result<PrometheusHandle, Box<dyn std::error::Error + Send + Sync>> {
+    let mut installed = PROMETHEUS_HANDLE.lock();
+    if let Some(handle) = installed.as_ref() {
+        return Ok(handle.clone());
+    }
     let handle = PrometheusBuilder::new().install_recorder()?;
+    *installed = Some(handle.clone());
     init_metrics();
     Ok(handle)
 }
 
-/// Static helper for recording proxy metrics (requests, backends, connections).
-pub struct Metrics;
+/// Which labels are attached to emitted metrics.
+///
+/// Built from `PrometheusConfig`: the `addEntryPointsLabels`,
+/// `addServicesLabels` and `addRoutersLabels` flags gate the `entrypoint`,
+/// `service` and `router` labels, then `allowedLabels`/`deniedLabels` filter
+/// whatever is left. The default keeps every label.
+#[derive(Debug, Clone)]
+pub struct LabelPolicy {
+    entrypoint: bool,
+    service: bool,
+    router: bool,
+    allowed: Option<HashSet<String>>,
+    denied: HashSet<String>,
+}
+
+impl Default for LabelPolicy {
+    fn default() -> Self {
+        Self {
+            entrypoint: true,
+            service: true,
+            router: true,
+            allowed: None,
+            denied: HashSet::new(),
+        }
+    }
+}
+
+impl LabelPolicy {
+    /// Build the policy from Prometheus config.
+    pub fn from_config(config: &PrometheusConfig) -> Self {
+        Self {
+            entrypoint: config.add_entry_points_labels,
+            service: config.add_services_labels,
+            router: config.add_routers_labels,
+            allowed: (!config.allowed_labels.is_empty())
+                .then(|| config.allowed_labels.iter().cloned().collect()),
+            denied: config.denied_labels.iter().cloned().collect(),
+        }
+    }
+
+    /// Whether a label with this name should be emitted
+    pub fn allows(&self, label: &str) -> bool {
+        let enabled = match label {
+            "entrypoint" => self.entrypoint,
+            "service" => self.service,
+            "router" => self.router,
+            _ => true,
+        };
+        enabled
+            && !self.denied.contains(label)
+            && self.allowed.as_ref().is_none_or(|allowed| allowed.contains(label))
+    }
+
+    fn apply<const N: usize>(&self, labels: [(&'static str, String); N]) -> Vec<(&'static str, String)> {
+        labels.into_iter().filter(|(name, _)| self.allows(name)).collect()
+    }
+}
+
+/// Process-wide metrics helper; replaced when metrics config is (re)loaded
+static GLOBAL: LazyLock<ArcSwap<Metrics>> = LazyLock::new(|| ArcSwap::from_pointee(Metrics::default()));
+
+/// Helper for recording proxy metrics (requests, backends, connections) with
+/// the configured label policy applied.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    labels: LabelPolicy,
+}
 
 impl Metrics {
+    /// Create a helper applying `labels` to everything it records
+    pub fn new(labels: LabelPolicy) -> Self {
+        Self { labels }
+    }
+
+    /// Build from metrics config; label settings come from the Prometheus block
+    pub fn from_config(config: &MetricsConfig) -> Self {
+        Self::new(
+            config
+                .prometheus
+                .as_ref()
+                .map(LabelPolicy::from_config)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The helper used by the proxy
+    pub fn global() -> Arc<Metrics> {
+        GLOBAL.load_full()
+    }
+
+    /// Replace the helper used by the proxy
+    pub fn set_global(metrics: Metrics) {
+        GLOBAL.store(Arc::new(metrics));
+    }
+
     /// Record an incoming HTTP request
     #[inline]
     pub fn record_request(
+        &self,
         entrypoint: &str,
         router: &str,
         service: &str,
@@ -61,13 +249,13 @@ impl Metrics {
         status: u16,
         duration: Duration,
     ) {
-        let labels = [
+        let labels = self.labels.apply([
             ("entrypoint", entrypoint.to_string()),
             ("router", router.to_string()),
             ("service", service.to_string()),
             ("method", method.to_string()),
             ("status", status.to_string()),
-        ];
+        ]);
 
         counter!("http_requests_total", &labels).increment(1);
         histogram!("http_request_duration_seconds", &labels).record(duration.as_secs_f64());
@@ -75,12 +263,12 @@ impl Metrics {
 
     /// Record a backend request
     #[inline]
-    pub fn record_backend_request(service: &str, server: &str, status: u16, duration: Duration) {
-        let labels = [
+    pub fn record_backend_request(&self, service: &str, server: &str, status: u16, duration: Duration) {
+        let labels = self.labels.apply([
             ("service", service.to_string()),
             ("server", server.to_string()),
             ("status", status.to_string()),
-        ];
+        ]);
 
         counter!("backend_requests_total", &labels).increment(1);
         histogram!("backend_request_duration_seconds", &labels).record(duration.as_secs_f64());
@@ -88,26 +276,33 @@ impl Metrics {
 
     /// Set backend health status
     #[inline]
-    pub fn set_backend_health(service: &str, server: &str, healthy: bool) {
-        let labels = [
+    pub fn set_backend_health(&self, service: &str, server: &str, healthy: bool) {
+        let labels = self.labels.apply([
             ("service", service.to_string()),
             ("server", server.to_string()),
-        ];
+        ]);
 
         gauge!("backend_health", &labels).set(if healthy { 1.0 } else { 0.0 });
     }
 
     /// Record connection pool size
     #[inline]
-    pub fn record_connection_pool_size(service: &str, size: usize) {
-        let labels = [("service", service.to_string())];
+    pub fn record_connection_pool_size(&self, service: &str, size: usize) {
+        let labels = self.labels.apply([("service", service.to_string())]);
         gauge!("connection_pool_size", &labels).set(size as f64);
     }
 
+    /// Record active UDP sessions
+    #[inline]
+    pub fn record_udp_sessions(&self, service: &str, count: usize) {
+        let labels = self.labels.apply([("service", service.to_string())]);
+        gauge!("udp_active_sessions", &labels).set(count as f64);
+    }
+
     /// Record active connections
     #[inline]
-    pub fn record_active_connections(entrypoint: &str, count: usize) {
-        let labels = [("entrypoint", entrypoint.to_string())];
+    pub fn record_active_connections(&self, entrypoint: &str, count: usize) {
+        let labels = self.labels.apply([("entrypoint", entrypoint.to_string())]);
         gauge!("active_connections", &labels).set(count as f64);
     }
 }
@@ -136,7 +331,7 @@ impl RequestTimer {
     /// Stop the timer and record the request duration with the given HTTP status.
     pub fn finish(self, status: u16) {
         let duration = self.start.elapsed();
-        Metrics::record_request(
+        Metrics::global().record_request(
             &self.entrypoint,
             &self.router,
             &self.service,
@@ -146,3 +341,156 @@ impl RequestTimer {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> PrometheusConfig {
+        PrometheusConfig {
+            address: ":9090".to_string(),
+            add_entry_points_labels: true,
+            add_services_labels: true,
+            add_routers_labels: false,
+            allowed_labels: vec![],
+            denied_labels: vec![],
+            entry_point: None,
+            buckets: vec![],
+            request_duration_buckets: None,
+            backend_duration_buckets: None,
+        }
+    }
+
+    #[test]
+    fn test_custom_buckets_in_exposition() {
+        let config = PrometheusConfig {
+            buckets: vec![0.5, 1.0],
+            request_duration_buckets: Some(vec![0.001, 0.0025, 0.005]),
+            ..test_config()
+        };
+        let recorder = prometheus_builder(&config).unwrap().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            let metrics = Metrics::default();
+            metrics.record_request("web", "api", "api-svc", "GET", 200, Duration::from_millis(2));
+            metrics.record_backend_request("api-svc", "http://10.0.0.1:80", 200, Duration::from_millis(2));
+        });
+        let output = handle.render();
+
+        let request_buckets: Vec<&str> = output
+            .lines()
+            .filter(|l| l.starts_with("http_request_duration_seconds_bucket"))
+            .collect();
+        assert_eq!(request_buckets.len(), 4);
+        assert!(request_buckets[0].contains("le=\"0.001\"") && request_buckets[0].ends_with(" 0"));
+        assert!(request_buckets[1].contains("le=\"0.0025\"") && request_buckets[1].ends_with(" 1"));
+        assert!(request_buckets[2].contains("le=\"0.005\""));
+        assert!(request_buckets[3].contains("le=\"+Inf\""));
+
+        // Backend durations fall back to the shared buckets
+        let backend_buckets: Vec<&str> = output
+            .lines()
+            .filter(|l| l.starts_with("backend_request_duration_seconds_bucket"))
+            .collect();
+        assert_eq!(backend_buckets.len(), 3);
+        assert!(backend_buckets[0].contains("le=\"0.5\""));
+        assert!(backend_buckets[1].contains("le=\"1\""));
+    }
+
+    /// Label names on the `http_requests_total` series after recording one request
+    fn request_label_names(metrics: &Metrics) -> Vec<String> {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            metrics.record_request("web", "api", "api-svc", "GET", 200, Duration::from_millis(1));
+        });
+
+        let output = handle.render();
+        let line = output
+            .lines()
+            .find(|l| l.starts_with("http_requests_total{"))
+            .unwrap();
+        let labels = &line[line.find('{').unwrap() + 1..line.find('}').unwrap()];
+        let mut names: Vec<String> = labels
+            .split(',')
+            .map(|pair| pair.split('=').next().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_label_flags_control_emitted_labels() {
+        // Unconfigured: every label
+        assert_eq!(
+            request_label_names(&Metrics::default()),
+            ["entrypoint", "method", "router", "service", "status"]
+        );
+
+        // Config defaults follow Traefik: entry point and service labels, no router
+        let config = test_config();
+        assert_eq!(
+            request_label_names(&Metrics::new(LabelPolicy::from_config(&config))),
+            ["entrypoint", "method", "service", "status"]
+        );
+
+        let config = PrometheusConfig {
+            add_entry_points_labels: false,
+            add_services_labels: false,
+            add_routers_labels: true,
+            ..test_config()
+        };
+        assert_eq!(
+            request_label_names(&Metrics::new(LabelPolicy::from_config(&config))),
+            ["method", "router", "status"]
+        );
+    }
+
+    #[test]
+    fn test_label_allow_and_deny_lists() {
+        let config = PrometheusConfig {
+            denied_labels: vec!["method".to_string()],
+            ..test_config()
+        };
+        assert_eq!(
+            request_label_names(&Metrics::new(LabelPolicy::from_config(&config))),
+            ["entrypoint", "service", "status"]
+        );
+
+        // The allow list can't re-enable a label its flag turned off
+        let config = PrometheusConfig {
+            allowed_labels: vec!["status".to_string(), "router".to_string(), "service".to_string()],
+            ..test_config()
+        };
+        assert_eq!(
+            request_label_names(&Metrics::new(LabelPolicy::from_config(&config))),
+            ["service", "status"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsorted_buckets() {
+        let entry_point = crate::config::EntryPoint {
+            address: ":80".to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        };
+        let config = crate::config::Config {
+            entry_points: [("web".to_string(), entry_point)].into(),
+            metrics: Some(crate::config::MetricsConfig {
+                prometheus: Some(PrometheusConfig {
+                    backend_duration_buckets: Some(vec![0.01, 0.005]),
+                    ..test_config()
+                }),
+                statsd: None,
+            }),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("backendDurationBuckets"), "{}", err);
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use parking_lot::Mutex;
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+
+use crate::config::StatsdConfig;
+
+/// Largest payload packed into one datagram; stays under a typical 1500-byte MTU
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// Name and rendered tag suffix identifying one metric series
+type SeriesKey = (Arc<str>, Arc<str>);
+
+/// Metrics recorder that aggregates in memory and pushes to a StatsD or
+/// DogStatsD agent over UDP on a fixed interval.
+///
+/// Counters are sent as the delta since the last flush, gauges when they
+/// change, and histograms as every sample recorded. Labels and the configured
+/// tags are sent as DogStatsD tags.
+#[derive(Clone)]
+pub struct StatsdRecorder {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    address: String,
+    prefix: String,
+    /// Configured tags, rendered once as `key:value` pairs
+    global_tags: Vec<String>,
+    flush_interval: Duration,
+    aggregates: Mutex<Aggregates>,
+}
+
+#[derive(Default)]
+struct Aggregates {
+    counters: HashMap<SeriesKey, u64>,
+    gauges: HashMap<SeriesKey, f64>,
+    changed_gauges: HashSet<SeriesKey>,
+    histograms: HashMap<SeriesKey, Vec<f64>>,
+}
+
+impl StatsdRecorder {
+    /// Build a recorder from config. Nothing is sent until [`exporter`](Self::exporter) runs.
+    pub fn new(config: &StatsdConfig) -> Self {
+        let mut global_tags: Vec<String> = config
+            .tags
+            .iter()
+            .map(|(k, v)| format!("{}:{}", sanitize_tag(k), sanitize_tag(v)))
+            .collect();
+        global_tags.sort();
+
+        let prefix = match config.prefix.as_deref() {
+            Some(p) if !p.is_empty() => format!("{}.", p.trim_end_matches('.')),
+            _ => String::new(),
+        };
+
+        Self {
+            inner: Arc::new(Inner {
+                address: config.address.clone(),
+                prefix,
+                global_tags,
+                flush_interval: config.flush_interval.as_std(),
+                aggregates: Mutex::new(Aggregates::default()),
+            }),
+        }
+    }
+
+    /// Flush loop sending aggregated metrics to the agent every flush interval.
+    /// Spawn it on the runtime; it runs until dropped.
+    pub fn exporter(&self) -> impl Future<Output = ()> + Send + 'static {
+        let inner = Arc::clone(&self.inner);
+        async move {
+            let socket = match UdpSocket::bind("0.0.0.0:0").await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    warn!("StatsD exporter disabled: failed to bind UDP socket: {}", e);
+                    return;
+                }
+            };
+
+            let mut ticker = tokio::time::interval(inner.flush_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                for payload in inner.drain() {
+                    if let Err(e) = socket.send_to(payload.as_bytes(), &inner.address).await {
+                        debug!("Failed to send StatsD metrics to {}: {}", inner.address, e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn series(&self, key: &Key) -> SeriesKey {
+        let name = format!("{}{}", self.inner.prefix, sanitize_name(key.name()));
+
+        let mut tags = self.inner.global_tags.clone();
+        tags.extend(
+            key.labels()
+                .map(|l| format!("{}:{}", sanitize_tag(l.key()), sanitize_tag(l.value()))),
+        );
+        let tags = if tags.is_empty() {
+            String::new()
+        } else {
+            format!("|#{}", tags.join(","))
+        };
+
+        (name.into(), tags.into())
+    }
+
+    fn handle(&self, key: &Key) -> Arc<SeriesHandle> {
+        Arc::new(SeriesHandle {
+            series: self.series(key),
+            inner: Arc::clone(&self.inner),
+        })
+    }
+}
+
+impl Inner {
+    /// Take everything recorded since the last flush, packed into datagram payloads
+    fn drain(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        {
+            let mut aggregates = self.aggregates.lock();
+            for ((name, tags), value) in aggregates.counters.drain() {
+                if value > 0 {
+                    lines.push(format!("{}:{}|c{}", name, value, tags));
+                }
+            }
+            let changed: Vec<SeriesKey> = aggregates.changed_gauges.drain().collect();
+            for series in changed {
+                if let Some(value) = aggregates.gauges.get(&series) {
+                    lines.push(format!("{}:{}|g{}", series.0, value, series.1));
+                }
+            }
+            for ((name, tags), samples) in aggregates.histograms.drain() {
+                for sample in samples {
+                    lines.push(format!("{}:{}|h{}", name, sample, tags));
+                }
+            }
+        }
+
+        let mut payloads = Vec::new();
+        let mut current = String::new();
+        for line in lines {
+            if !current.is_empty() && current.len() + 1 + line.len() > MAX_DATAGRAM_SIZE {
+                payloads.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(&line);
+        }
+        if !current.is_empty() {
+            payloads.push(current);
+        }
+        payloads
+    }
+}
+
+/// Handle for one registered series; updates land in the shared aggregates
+struct SeriesHandle {
+    series: SeriesKey,
+    inner: Arc<Inner>,
+}
+
+impl SeriesHandle {
+    fn update_gauge(&self, update: impl FnOnce(f64) -> f64) {
+        let mut aggregates = self.inner.aggregates.lock();
+        let value = aggregates.gauges.entry(self.series.clone()).or_insert(0.0);
+        *value = update(*value);
+        aggregates.changed_gauges.insert(self.series.clone());
+    }
+}
+
+impl CounterFn for SeriesHandle {
+    fn increment(&self, value: u64) {
+        *self
+            .inner
+            .aggregates
+            .lock()
+            .counters
+            .entry(self.series.clone())
+            .or_insert(0) += value;
+    }
+
+    fn absolute(&self, _value: u64) {
+        // StatsD counters are deltas; there is no way to send an absolute total
+    }
+}
+
+impl GaugeFn for SeriesHandle {
+    fn increment(&self, value: f64) {
+        self.update_gauge(|current| current + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update_gauge(|current| current - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update_gauge(|_| value);
+    }
+}
+
+impl HistogramFn for SeriesHandle {
+    fn record(&self, value: f64) {
+        self.inner
+            .aggregates
+            .lock()
+            .histograms
+            .entry(self.series.clone())
+            .or_default()
+            .push(value);
+    }
+}
+
+impl Recorder for StatsdRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.handle(key))
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(self.handle(key))
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(self.handle(key))
+    }
+}
+
+/// Replace characters that delimit fields in the StatsD line protocol
+fn sanitize_name(name: &str) -> String {
+    name.replace([':', '|', '@', '\n'], "_")
+}
+
+/// Replace characters that delimit DogStatsD tags
+fn sanitize_tag(tag: &str) -> String {
+    tag.replace([',', '|', '#', '\n'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn config(address: &str) -> StatsdConfig {
+        StatsdConfig {
+            address: address.to_string(),
+            prefix: Some("trafficcop".to_string()),
+            tags: HashMap::from([("env".to_string(), "prod".to_string())]),
+            flush_interval: crate::config::Duration::from_millis(20),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sends_recorded_request_to_agent() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let recorder = StatsdRecorder::new(&config(&agent.local_addr().unwrap().to_string()));
+        tokio::spawn(recorder.exporter());
+
+        metrics::with_local_recorder(&recorder, || {
+            let metrics = Metrics::default();
+            metrics.record_request("web", "api", "api-svc", "GET", 200, Duration::from_millis(250));
+            metrics.record_request("web", "api", "api-svc", "GET", 200, Duration::from_millis(250));
+            metrics.set_backend_health("api-svc", "http://10.0.0.1:80", true);
+        });
+
+        let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+        let len = tokio::time::timeout(Duration::from_secs(5), agent.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let payload = std::str::from_utf8(&buf[..len]).unwrap();
+        let mut lines: Vec<&str> = payload.lines().collect();
+        lines.sort();
+
+        let tags = "|#env:prod,entrypoint:web,router:api,service:api-svc,method:GET,status:200";
+        assert_eq!(
+            lines,
+            vec![
+                "trafficcop.backend_health:1|g|#env:prod,service:api-svc,server:http://10.0.0.1:80"
+                    .to_string(),
+                format!("trafficcop.http_request_duration_seconds:0.25|h{}", tags),
+                format!("trafficcop.http_request_duration_seconds:0.25|h{}", tags),
+                format!("trafficcop.http_requests_total:2|c{}", tags),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drain_resets_counters_and_packs_datagrams() {
+        let recorder = StatsdRecorder::new(&config("127.0.0.1:8125"));
+        metrics::with_local_recorder(&recorder, || {
+            for i in 0..200 {
+                metrics::counter!("requests", "shard" => i.to_string()).increment(1);
+            }
+            metrics::gauge!("connections").set(3.0);
+        });
+
+        let payloads = recorder.inner.drain();
+        assert!(payloads.len() > 1);
+        assert!(payloads.iter().all(|p| p.len() <= MAX_DATAGRAM_SIZE));
+        assert_eq!(payloads.iter().map(|p| p.lines().count()).sum::<usize>(), 201);
+
+        // Nothing new recorded: counters were reset and the gauge is unchanged
+        assert!(recorder.inner.drain().is_empty());
+    }
+}
@@ -1,16 +1,65 @@
+use crate::config::{AccessLogConfig, AccessLogFieldMode, AccessLogFieldsConfig};
+use hyper::HeaderMap;
 use serde::Serialize;
-use std::fs::OpenOptions;
-use std::io::{BufWriter, Write};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use tracing::info;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+/// How often buffered access log lines are written out
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Logged in place of redacted fields and headers
+const REDACTED: &str = "REDACTED";
+
+/// Access log line format, from `format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// Traefik's Common Log Format variant (the default)
+    Common,
+    /// One JSON object per line
+    Json,
+}
+
+impl AccessLogFormat {
+    fn from_config(format: Option<&str>) -> Self {
+        match format.map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => Self::Json,
+            None | Some("common") | Some("clf") => Self::Common,
+            Some(other) => {
+                warn!("Unknown access log format '{}', using common", other);
+                Self::Common
+            }
+        }
+    }
+}
+
+/// Backend that served a request and how many times it was retried. The
+/// forwarding endpoint attaches it to the response for the access log.
+#[derive(Debug, Clone)]
+pub struct BackendInfo {
+    /// Backend server URL
+    pub url: Arc<str>,
+    /// Attempts after the first one
+    pub retry_attempts: u32,
+    /// The backend never answered (connection error or timeout), so the
+    /// response was generated by the proxy
+    pub network_error: bool,
+}
 
 /// Structured access log entry
 #[derive(Debug, Serialize)]
 pub struct AccessLogEntry {
-    /// Timestamp (RFC3339)
+    /// Request start time (RFC3339)
     pub timestamp: String,
+    /// Request start time, for the Common Log Format timestamp
+    #[serde(skip)]
+    pub start_time: SystemTime,
     /// Remote client address
     pub remote_addr: String,
     /// HTTP method
@@ -51,11 +100,16 @@ pub struct AccessLogEntry {
     /// Backend server URL
     #[serde(skip_serializing_if = "Option::is_none")]
     pub backend: Option<String>,
+    /// Number of times the request was retried
+    pub retry_attempts: u32,
     /// TLS protocol version
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tls_version: Option<String>,
     /// HTTP protocol version
     pub protocol: String,
+    /// Request headers, captured only when the log keeps some of them
+    #[serde(skip)]
+    pub request_headers: Vec<(String, String)>,
 }
 
 impl AccessLogEntry {
@@ -80,11 +134,151 @@ impl AccessLogEntry {
             info!(target: "access_log", "{}", json);
         }
     }
+
+    /// Render as a JSON line (without the trailing newline)
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Render in Traefik's Common Log Format variant:
+    /// `<client> - - [<time>] "<method> <path> <proto>" <status> <size> "<referer>" "<ua>" <count> "<router>" "<backend>" <duration>ms`
+    pub fn to_common(&self, request_count: u64) -> String {
+        common_line(self, &self.fields(), request_count)
+    }
+
+    /// Logged fields by their JSON name
+    fn fields(&self) -> Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        }
+    }
+}
+
+/// Common Log Format line from (possibly filtered) `fields`. Dropped fields
+/// show as `-`.
+fn common_line(entry: &AccessLogEntry, fields: &Map<String, Value>, request_count: u64) -> String {
+    let field = |name: &str| match fields.get(name) {
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+        None => "-".to_string(),
+    };
+    let remote_addr = field("remote_addr");
+    let client = remote_addr
+        .parse::<SocketAddr>()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or(remote_addr);
+    let target = match fields.get("query") {
+        Some(_) => format!("{}?{}", field("path"), field("query")),
+        None => field("path"),
+    };
+    let duration = match fields.get("duration_ms").and_then(Value::as_f64) {
+        Some(ms) => (ms.round() as u64).to_string(),
+        None => field("duration_ms"),
+    };
+
+    format!(
+        "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\" {} \"{}\" \"{}\" {}ms",
+        client,
+        clf_time(entry.start_time),
+        field("method"),
+        target,
+        field("protocol"),
+        field("status"),
+        field("body_bytes"),
+        field("referer"),
+        field("user_agent"),
+        request_count,
+        field("route"),
+        field("backend"),
+        duration,
+    )
+}
+
+/// Field and header filtering from `accessLog.fields`
+#[derive(Debug, Clone)]
+pub struct AccessLogFields {
+    default_mode: AccessLogFieldMode,
+    names: HashMap<String, AccessLogFieldMode>,
+    header_default_mode: AccessLogFieldMode,
+    /// Keyed by lowercase header name
+    headers: HashMap<String, AccessLogFieldMode>,
+}
+
+impl Default for AccessLogFields {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl AccessLogFields {
+    /// Build from config. Without one, every field is kept and every header dropped.
+    pub fn new(config: Option<&AccessLogFieldsConfig>) -> Self {
+        let headers = config.and_then(|c| c.headers.clone()).unwrap_or_default();
+        Self {
+            default_mode: config.map(|c| c.default_mode).unwrap_or_default(),
+            names: config.map(|c| c.names.clone()).unwrap_or_default(),
+            header_default_mode: headers.default_mode,
+            headers: headers
+                .names
+                .into_iter()
+                .map(|(name, mode)| (name.to_ascii_lowercase(), mode))
+                .collect(),
+        }
+    }
+
+    /// Whether any request header can end up in the log
+    pub fn logs_headers(&self) -> bool {
+        self.header_default_mode != AccessLogFieldMode::Drop
+            || self.headers.values().any(|mode| *mode != AccessLogFieldMode::Drop)
+    }
+
+    /// The entry's fields with modes applied, plus kept or redacted request
+    /// headers as `request_<Header-Name>`
+    pub fn apply(&self, entry: &AccessLogEntry) -> Map<String, Value> {
+        let mut fields = Map::new();
+        for (name, value) in entry.fields() {
+            let mode = self.names.get(&name).copied().unwrap_or(self.default_mode);
+            if let Some(value) = filtered(mode, value) {
+                fields.insert(name, value);
+            }
+        }
+        for (name, value) in &entry.request_headers {
+            let mode = self.headers.get(name).copied().unwrap_or(self.header_default_mode);
+            if let Some(value) = filtered(mode, Value::String(value.clone())) {
+                fields.insert(format!("request_{}", canonical_header_name(name)), value);
+            }
+        }
+        fields
+    }
+}
+
+fn filtered(mode: AccessLogFieldMode, value: Value) -> Option<Value> {
+    match mode {
+        AccessLogFieldMode::Keep => Some(value),
+        AccessLogFieldMode::Drop => None,
+        AccessLogFieldMode::Redact => Some(Value::String(REDACTED.to_string())),
+    }
+}
+
+/// `x-forwarded-for` -> `X-Forwarded-For`
+fn canonical_header_name(name: &str) -> String {
+    name.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
 }
 
 /// Builder for creating access log entries
 pub struct AccessLogBuilder {
     start: Instant,
+    start_time: SystemTime,
     remote_addr: SocketAddr,
     method: String,
     path: String,
@@ -96,6 +290,8 @@ pub struct AccessLogBuilder {
     request_id: Option<String>,
     protocol: String,
     is_tls: bool,
+    retry_attempts: u32,
+    request_headers: Vec<(String, String)>,
 }
 
 impl AccessLogBuilder {
@@ -103,6 +299,7 @@ impl AccessLogBuilder {
     pub fn new(remote_addr: SocketAddr, method: &str, path: &str, protocol: &str) -> Self {
         Self {
             start: Instant::now(),
+            start_time: SystemTime::now(),
             remote_addr,
             method: method.to_string(),
             path: path.to_string(),
@@ -114,6 +311,8 @@ impl AccessLogBuilder {
             request_id: None,
             protocol: protocol.to_string(),
             is_tls: false,
+            retry_attempts: 0,
+            request_headers: Vec::new(),
         }
     }
 
@@ -159,6 +358,31 @@ impl AccessLogBuilder {
         self
     }
 
+    /// Capture request headers (lowercase names; repeated headers are joined
+    /// with `, `).
+    pub fn request_headers(mut self, headers: Option<&HeaderMap>) -> Self {
+        if let Some(headers) = headers {
+            self.request_headers = headers
+                .keys()
+                .map(|name| {
+                    let values: Vec<_> = headers
+                        .get_all(name)
+                        .iter()
+                        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                        .collect();
+                    (name.as_str().to_string(), values.join(", "))
+                })
+                .collect();
+        }
+        self
+    }
+
+    /// Set the number of times the request was retried.
+    pub fn retry_attempts(mut self, retry_attempts: u32) -> Self {
+        self.retry_attempts = retry_attempts;
+        self
+    }
+
     /// Finish building and create the log entry
     pub fn finish(
         self,
@@ -171,7 +395,8 @@ impl AccessLogBuilder {
         let duration = self.start.elapsed();
 
         AccessLogEntry {
-            timestamp: chrono_lite_now(),
+            timestamp: rfc3339(self.start_time),
+            start_time: self.start_time,
             remote_addr: self.remote_addr.to_string(),
             method: self.method,
             path: self.path,
@@ -187,6 +412,8 @@ impl AccessLogBuilder {
             route: route.map(|s| s.to_string()),
             service: service.map(|s| s.to_string()),
             backend: backend.map(|s| s.to_string()),
+            retry_attempts: self.retry_attempts,
+            request_headers: self.request_headers,
             tls_version: if self.is_tls {
                 Some("TLSv1.3".to_string())
             } else {
@@ -197,84 +424,241 @@ impl AccessLogBuilder {
     }
 }
 
-/// File-backed access log writer that writes JSON lines to a configured file path.
+/// File-backed access log writer, rendering entries in the configured format.
+///
+/// When the config has no `file_path`, the writer is a no-op. Lines are held
+/// in memory until `bufferingsize` of them are pending, then written out; a
+/// background task also writes them out every second, and [`flush`] does on
+/// shutdown. [`reopen`] switches to a fresh file at the same path, so the log
+/// can be rotated by renaming it.
 ///
-/// When the config has no `file_path`, the writer is a no-op.  The inner file
-/// handle is wrapped in `Arc<Mutex<BufWriter<File>>>` so it can be cheaply
-/// cloned and shared across tasks.
-#[derive(Clone)]
+/// [`flush`]: AccessLogWriter::flush
+/// [`reopen`]: AccessLogWriter::reopen
+#[derive(Clone, Default)]
 pub struct AccessLogWriter {
-    writer: Option<Arc<Mutex<BufWriter<std::fs::File>>>>,
+    inner: Option<Arc<WriterInner>>,
+}
+
+struct WriterInner {
+    path: String,
+    format: AccessLogFormat,
+    fields: AccessLogFields,
+    /// Pending lines that trigger a write; 1 writes every line immediately
+    buffer_lines: usize,
+    /// Requests logged so far, reported in the Common Log Format
+    requests: AtomicU64,
+    state: Mutex<WriterState>,
+}
+
+struct WriterState {
+    file: File,
+    buffer: String,
+    pending: usize,
 }
 
 impl AccessLogWriter {
     /// Open the access log file from config.  Returns a no-op writer when
-    /// access logging is disabled or no file path is set.
-    pub fn new(config: &Option<crate::config::AccessLogConfig>) -> Self {
-        let writer = config.as_ref().and_then(|c| {
-            c.file_path.as_ref().and_then(|path| {
-                OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(path)
-                    .ok()
-                    .map(|f| Arc::new(Mutex::new(BufWriter::new(f))))
-            })
+    /// access logging is disabled, no file path is set, or the file can't
+    /// be opened.
+    pub fn new(config: &Option<AccessLogConfig>) -> Self {
+        let Some((config, path)) = config
+            .as_ref()
+            .and_then(|c| c.file_path.as_ref().map(|path| (c, path)))
+        else {
+            return Self::default();
+        };
+        let file = match open_append(path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open access log {}: {}", path, e);
+                return Self::default();
+            }
+        };
+
+        let buffer_lines = config.bufferingsize.unwrap_or(0).max(1) as usize;
+        let inner = Arc::new(WriterInner {
+            path: path.clone(),
+            format: AccessLogFormat::from_config(config.format.as_deref()),
+            fields: AccessLogFields::new(config.fields.as_ref()),
+            buffer_lines,
+            requests: AtomicU64::new(0),
+            state: Mutex::new(WriterState {
+                file,
+                buffer: String::new(),
+                pending: 0,
+            }),
         });
-        if writer.is_some() {
-            info!("Access log writer initialized");
+
+        if buffer_lines > 1
+            && let Ok(runtime) = tokio::runtime::Handle::try_current()
+        {
+            runtime.spawn(flush_periodically(Arc::downgrade(&inner)));
         }
-        Self { writer }
+
+        info!("Access log writer initialized ({:?} format, {})", inner.format, path);
+        Self { inner: Some(inner) }
     }
 
-    /// Serialize `entry` as a single JSON line and flush it to the log file.
+    /// Render `entry` as a single line, writing out pending lines once
+    /// `bufferingsize` of them have accumulated.
     pub fn log(&self, entry: &AccessLogEntry) {
-        if let Some(ref writer) = self.writer {
-            if let Ok(json) = serde_json::to_string(entry) {
-                if let Ok(mut w) = writer.lock() {
-                    let _ = writeln!(w, "{}", json);
-                    let _ = w.flush();
-                }
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let count = inner.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        let fields = inner.fields.apply(entry);
+        let line = match inner.format {
+            AccessLogFormat::Common => common_line(entry, &fields, count),
+            AccessLogFormat::Json => Value::Object(fields).to_string(),
+        };
+
+        if let Ok(mut state) = inner.state.lock() {
+            state.buffer.push_str(&line);
+            state.buffer.push('\n');
+            state.pending += 1;
+            if state.pending >= inner.buffer_lines {
+                state.write_out();
+            }
+        }
+    }
+
+    /// Whether entries should carry request headers, i.e. `fields.headers`
+    /// keeps or redacts some of them
+    pub fn logs_headers(&self) -> bool {
+        self.inner.as_ref().is_some_and(|inner| inner.fields.logs_headers())
+    }
+
+    /// Write out all pending lines
+    pub fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+
+    /// Write out pending lines and reopen the log file by path, e.g. after
+    /// logrotate renamed it. The current file stays in use if reopening fails.
+    pub fn reopen(&self) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let Ok(mut state) = inner.state.lock() else {
+            return;
+        };
+        state.write_out();
+        match open_append(&inner.path) {
+            Ok(file) => {
+                state.file = file;
+                info!("Reopened access log {}", inner.path);
             }
+            Err(e) => error!("Failed to reopen access log {}: {}", inner.path, e),
         }
     }
 }
 
-/// Simple timestamp function (avoiding chrono dependency)
-fn chrono_lite_now() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
+impl WriterInner {
+    fn flush(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.write_out();
+        }
+    }
+}
+
+impl Drop for WriterInner {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl WriterState {
+    fn write_out(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        if let Err(e) = self.file.write_all(self.buffer.as_bytes()) {
+            error!("Failed to write access log: {}", e);
+        }
+        self.buffer.clear();
+        self.pending = 0;
+    }
+}
 
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
+fn open_append(path: &str) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
 
-    let secs = now.as_secs();
-    let millis = now.subsec_millis();
+/// Write out buffered lines every [`FLUSH_INTERVAL`] until the writer is dropped
+async fn flush_periodically(inner: Weak<WriterInner>) {
+    let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match inner.upgrade() {
+            Some(inner) => inner.flush(),
+            None => return,
+        }
+    }
+}
 
-    // Convert to rough ISO 8601 format
-    // This is simplified - for production, use chrono
-    let days_since_epoch = secs / 86400;
-    let secs_today = secs % 86400;
-    let hours = secs_today / 3600;
-    let mins = (secs_today % 3600) / 60;
-    let secs_remaining = secs_today % 60;
+/// `(year, month, day)` for days since 1970-01-01 (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
 
-    // Approximate year/month/day calculation (not accounting for leap years perfectly)
-    let years_approx = days_since_epoch / 365;
-    let year = 1970 + years_approx;
-    let day_of_year = days_since_epoch % 365;
-    let month = (day_of_year / 30).min(11) + 1;
-    let day = (day_of_year % 30) + 1;
+/// UTC date and time-of-day fields of `time`
+fn utc_fields(time: SystemTime) -> (i64, u32, u32, u64, u64, u64, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_today = secs % 86_400;
+    (
+        year,
+        month,
+        day,
+        secs_today / 3_600,
+        secs_today % 3_600 / 60,
+        secs_today % 60,
+        since_epoch.subsec_millis(),
+    )
+}
 
+/// RFC3339 UTC timestamp with milliseconds, e.g. `2026-10-17T08:05:09.123Z`
+fn rfc3339(time: SystemTime) -> String {
+    let (year, month, day, hours, mins, secs, millis) = utc_fields(time);
     format!(
         "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
-        year, month, day, hours, mins, secs_remaining, millis
+        year, month, day, hours, mins, secs, millis
+    )
+}
+
+/// Common Log Format timestamp, e.g. `17/Oct/2026:08:05:09 +0000`
+fn clf_time(time: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (year, month, day, hours, mins, secs, _) = utc_fields(time);
+    format!(
+        "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        hours,
+        mins,
+        secs
     )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::AccessLogHeadersConfig;
 
     #[test]
     fn test_access_log_builder() {
@@ -293,9 +677,206 @@ mod tests {
 
     #[test]
     fn test_timestamp_format() {
-        let ts = chrono_lite_now();
-        // Should be roughly ISO 8601 format
-        assert!(ts.contains("T"));
-        assert!(ts.ends_with("Z"));
+        // 2024-02-29T13:55:36.250Z, a leap day
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_214_936_250);
+        assert_eq!(rfc3339(time), "2024-02-29T13:55:36.250Z");
+        assert_eq!(clf_time(time), "29/Feb/2024:13:55:36 +0000");
+    }
+
+    fn sample_entry() -> AccessLogEntry {
+        let addr: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+        let mut entry = AccessLogBuilder::new(addr, "POST", "/api/orders", "HTTP/1.1")
+            .query(Some("page=2"))
+            .host(Some("shop.example.com"))
+            .user_agent(Some("curl/8.5"))
+            .retry_attempts(2)
+            .finish(201, Some(512), Some("api"), Some("orders"), Some("http://10.0.0.5:8080"));
+        entry.start_time = UNIX_EPOCH + Duration::from_millis(1_709_214_936_250);
+        entry.timestamp = rfc3339(entry.start_time);
+        entry.duration_ms = 42.4;
+        entry
+    }
+
+    #[test]
+    fn test_common_log_format() {
+        assert_eq!(
+            sample_entry().to_common(7),
+            "203.0.113.7 - - [29/Feb/2024:13:55:36 +0000] \"POST /api/orders?page=2 HTTP/1.1\" 201 512 \"-\" \"curl/8.5\" 7 \"api\" \"http://10.0.0.5:8080\" 42ms"
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let json: serde_json::Value = serde_json::from_str(&sample_entry().to_json()).unwrap();
+
+        assert_eq!(json["timestamp"], "2024-02-29T13:55:36.250Z");
+        assert_eq!(json["remote_addr"], "203.0.113.7:51234");
+        assert_eq!(json["method"], "POST");
+        assert_eq!(json["path"], "/api/orders");
+        assert_eq!(json["status"], 201);
+        assert_eq!(json["duration_ms"], 42.4);
+        assert_eq!(json["route"], "api");
+        assert_eq!(json["service"], "orders");
+        assert_eq!(json["backend"], "http://10.0.0.5:8080");
+        assert_eq!(json["retry_attempts"], 2);
+        assert!(json.get("start_time").is_none());
+    }
+
+    fn modes(entries: &[(&str, AccessLogFieldMode)]) -> HashMap<String, AccessLogFieldMode> {
+        entries.iter().map(|(name, mode)| (name.to_string(), *mode)).collect()
+    }
+
+    fn entry_with_headers() -> AccessLogEntry {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer secret-token".parse().unwrap());
+        headers.insert("cookie", "session=abc".parse().unwrap());
+        headers.insert("x-tenant", "acme".parse().unwrap());
+        headers.insert("user-agent", "curl/8.5".parse().unwrap());
+
+        let addr: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+        AccessLogBuilder::new(addr, "GET", "/account", "HTTP/1.1")
+            .user_agent(Some("curl/8.5"))
+            .request_headers(Some(&headers))
+            .finish(200, None, Some("web"), Some("app"), None)
+    }
+
+    #[test]
+    fn test_fields_redact_drop_and_keep() {
+        let fields = AccessLogFields::new(Some(&AccessLogFieldsConfig {
+            default_mode: AccessLogFieldMode::Keep,
+            names: modes(&[
+                ("user_agent", AccessLogFieldMode::Drop),
+                ("remote_addr", AccessLogFieldMode::Redact),
+            ]),
+            headers: Some(AccessLogHeadersConfig {
+                default_mode: AccessLogFieldMode::Drop,
+                names: modes(&[
+                    ("Authorization", AccessLogFieldMode::Redact),
+                    ("Cookie", AccessLogFieldMode::Drop),
+                    ("X-Tenant", AccessLogFieldMode::Keep),
+                ]),
+            }),
+        }));
+        assert!(fields.logs_headers());
+
+        let logged = fields.apply(&entry_with_headers());
+        assert_eq!(logged["request_Authorization"], "REDACTED");
+        assert_eq!(logged["remote_addr"], "REDACTED");
+        assert!(logged.get("user_agent").is_none());
+        assert!(logged.get("request_Cookie").is_none());
+        // Headers not listed follow headers.defaultMode, which is drop
+        assert!(logged.get("request_User-Agent").is_none());
+        assert_eq!(logged["request_X-Tenant"], "acme");
+        assert_eq!(logged["route"], "web");
+        assert_eq!(logged["status"], 200);
+
+        // Common format shows dropped fields as `-`
+        let line = common_line(&entry_with_headers(), &logged, 1);
+        assert!(line.starts_with("REDACTED - - ["));
+        assert!(line.contains("\"-\" \"-\" 1 \"web\""));
+    }
+
+    #[test]
+    fn test_fields_default_modes() {
+        // Without config every field is kept and no header is logged
+        let default = AccessLogFields::default();
+        assert!(!default.logs_headers());
+        let logged = default.apply(&entry_with_headers());
+        assert_eq!(logged["user_agent"], "curl/8.5");
+        assert!(!logged.keys().any(|key| key.starts_with("request_")));
+
+        let fields = AccessLogFields::new(Some(&AccessLogFieldsConfig {
+            default_mode: AccessLogFieldMode::Drop,
+            names: modes(&[("status", AccessLogFieldMode::Keep)]),
+            headers: Some(AccessLogHeadersConfig {
+                default_mode: AccessLogFieldMode::Keep,
+                names: modes(&[("authorization", AccessLogFieldMode::Redact)]),
+            }),
+        }));
+        let logged = fields.apply(&entry_with_headers());
+        assert_eq!(logged["status"], 200);
+        assert!(logged.get("method").is_none());
+        assert_eq!(logged["request_Authorization"], "REDACTED");
+        assert_eq!(logged["request_Cookie"], "session=abc");
+        assert_eq!(logged["request_User-Agent"], "curl/8.5");
+    }
+
+    fn temp_log_path() -> String {
+        std::env::temp_dir()
+            .join(format!("trafficcop-access-{}.log", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn writer(path: &str, format: &str, bufferingsize: Option<u64>) -> AccessLogWriter {
+        AccessLogWriter::new(&Some(AccessLogConfig {
+            file_path: Some(path.to_string()),
+            format: Some(format.to_string()),
+            bufferingsize,
+            fields: None,
+        }))
+    }
+
+    fn lines(path: &str) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_buffer_flushes_on_size_threshold() {
+        let path = temp_log_path();
+        let log = writer(&path, "json", Some(3));
+
+        log.log(&sample_entry());
+        log.log(&sample_entry());
+        assert!(lines(&path).is_empty());
+
+        log.log(&sample_entry());
+        let written = lines(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(written.len(), 3);
+        assert!(written
+            .iter()
+            .all(|line| serde_json::from_str::<Value>(line).unwrap()["status"] == 201));
+    }
+
+    #[test]
+    fn test_buffer_flushes_on_shutdown() {
+        let path = temp_log_path();
+        let log = writer(&path, "common", Some(100));
+
+        log.log(&sample_entry());
+        assert!(lines(&path).is_empty());
+        log.flush();
+        assert_eq!(lines(&path).len(), 1);
+
+        // Dropping the last handle writes out whatever is still pending
+        log.log(&sample_entry());
+        drop(log);
+        let written = lines(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(written.len(), 2);
+        assert!(written[1].contains("\" 2 \"api\""));
+    }
+
+    #[test]
+    fn test_reopen_after_rotation() {
+        let path = temp_log_path();
+        let rotated = format!("{}.1", path);
+        let log = writer(&path, "common", None);
+
+        log.log(&sample_entry());
+        std::fs::rename(&path, &rotated).unwrap();
+        log.reopen();
+        log.log(&sample_entry());
+
+        let (old, new) = (lines(&rotated), lines(&path));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&rotated);
+        assert_eq!(old.len(), 1);
+        assert_eq!(new.len(), 1);
     }
 }
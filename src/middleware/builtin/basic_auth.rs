@@ -1,30 +1,44 @@
+use super::htpasswd;
 use crate::config::BasicAuthConfig;
+use dashmap::DashSet;
 use hyper::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
 use hyper::{Request, Response, StatusCode};
+use ring::digest::{Context, SHA256};
 use std::collections::HashMap;
+use tracing::error;
+
+/// Most successful checks remembered. Each user normally has one matching
+/// password, but bcrypt ignores bytes past the 72nd, so the cache is capped.
+const MAX_VERIFIED: usize = 4096;
 
 /// Basic authentication middleware
-/// Supports htpasswd-style password format: user:password (plaintext) or user:$apr1$... (hashed)
+/// Users are htpasswd-style `user:hash` entries, inline and/or from `usersFile`.
+/// Hashes may be bcrypt (`$2y$`), Apache MD5 (`$apr1$`), SHA-1 (`{SHA}`), or
+/// plaintext.
 pub struct BasicAuthMiddleware {
-    /// Map of username -> password (plaintext or hash)
+    /// Map of username -> password hash (or plaintext password)
     users: HashMap<String, String>,
     realm: String,
     www_authenticate: HeaderValue,
+    /// Digests of (user, hash, password) checks that succeeded, so valid
+    /// clients don't pay for bcrypt on every request
+    verified: DashSet<[u8; 32]>,
 }
 
 impl BasicAuthMiddleware {
-    /// Create from config, parsing user:password entries.
+    /// Create from config, parsing user:hash entries. Entries in `usersFile`
+    /// take precedence over inline ones; if the file can't be read its users
+    /// are rejected rather than the middleware being skipped.
     pub fn new(config: BasicAuthConfig) -> Self {
-        let users: HashMap<String, String> = config
-            .users
-            .iter()
-            .filter_map(|entry| {
-                let mut parts = entry.splitn(2, ':');
-                let user = parts.next()?.to_string();
-                let pass = parts.next()?.to_string();
-                Some((user, pass))
-            })
-            .collect();
+        let mut users: HashMap<String, String> =
+            htpasswd::parse(&config.users.join("\n")).into_iter().collect();
+
+        if let Some(path) = &config.users_file {
+            match htpasswd::load_file(path) {
+                Ok(entries) => users.extend(entries),
+                Err(e) => error!("basicAuth: {:#}", e),
+            }
+        }
 
         let realm = config.realm.unwrap_or_else(|| "Restricted".to_string());
         let www_authenticate =
@@ -34,69 +48,33 @@ impl BasicAuthMiddleware {
             users,
             realm,
             www_authenticate,
+            verified: DashSet::new(),
         }
     }
 
-    /// Check if request is authenticated
-    pub fn is_authenticated<B>(&self, req: &Request<B>) -> bool {
-        let auth_header = match req.headers().get(AUTHORIZATION) {
-            Some(h) => h,
-            None => return false,
-        };
-
-        let auth_str = match auth_header.to_str() {
-            Ok(s) => s,
-            Err(_) => return false,
-        };
-
-        // Check for "Basic " prefix
-        if !auth_str.starts_with("Basic ") {
+    /// Check if request is authenticated. Hashes are checked on the blocking
+    /// pool, as bcrypt takes milliseconds; successful checks are cached.
+    pub async fn authenticate<B>(&self, req: &Request<B>) -> bool {
+        let Some((username, password)) = credentials(req) else {
             return false;
-        }
-
-        let encoded = &auth_str[6..];
-
-        // Decode base64
-        let decoded = match base64_decode(encoded) {
-            Some(d) => d,
-            None => return false,
         };
-
-        // Parse user:password
-        let mut parts = decoded.splitn(2, ':');
-        let username = match parts.next() {
-            Some(u) => u,
-            None => return false,
-        };
-        let password = match parts.next() {
-            Some(p) => p,
-            None => return false,
+        let Some(stored) = self.users.get(&username) else {
+            return false;
         };
 
-        // Check credentials
-        self.verify(username, password)
-    }
+        let key = verified_key(&username, stored, &password);
+        if self.verified.contains(&key) {
+            return true;
+        }
 
-    /// Verify username and password
-    fn verify(&self, username: &str, password: &str) -> bool {
-        match self.users.get(username) {
-            Some(stored) => {
-                // Check if it's a hash or plaintext
-                if stored.starts_with("$apr1$") || stored.starts_with("$2") {
-                    // Apache MD5 or bcrypt hash - for now just compare directly
-                    // In production, you'd want proper hash verification
-                    // This is a placeholder for the hash comparison
-                    tracing::warn!(
-                        "Hash-based password verification not fully implemented, using plaintext comparison"
-                    );
-                    stored == password
-                } else {
-                    // Plaintext comparison (constant-time would be better for production)
-                    constant_time_compare(stored, password)
-                }
-            }
-            None => false,
+        let stored = stored.clone();
+        let valid = tokio::task::spawn_blocking(move || htpasswd::verify_password(&password, &stored))
+            .await
+            .unwrap_or(false);
+        if valid && self.verified.len() < MAX_VERIFIED {
+            self.verified.insert(key);
         }
+        valid
     }
 
     /// Build 401 Unauthorized response
@@ -114,6 +92,27 @@ impl BasicAuthMiddleware {
     }
 }
 
+/// Username and password from a Basic Authorization header
+fn credentials<B>(req: &Request<B>) -> Option<(String, String)> {
+    let auth_str = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let decoded = base64_decode(auth_str.strip_prefix("Basic ")?)?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Cache key: SHA-256 of the user, stored hash and password, so passwords are
+/// not kept in memory
+fn verified_key(username: &str, stored: &str, password: &str) -> [u8; 32] {
+    let mut context = Context::new(&SHA256);
+    for part in [username, stored, password] {
+        context.update(&(part.len() as u64).to_be_bytes());
+        context.update(part.as_bytes());
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(context.finish().as_ref());
+    key
+}
+
 /// Simple base64 decode (no external dependency)
 fn base64_decode(input: &str) -> Option<String> {
     const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
@@ -148,19 +147,6 @@ fn base64_decode(input: &str) -> Option<String> {
     String::from_utf8(result).ok()
 }
 
-/// Constant-time string comparison to prevent timing attacks
-fn constant_time_compare(a: &str, b: &str) -> bool {
-    if a.len() != b.len() {
-        return false;
-    }
-
-    let mut result = 0u8;
-    for (x, y) in a.bytes().zip(b.bytes()) {
-        result |= x ^ y;
-    }
-    result == 0
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,8 +164,8 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_valid_credentials() {
+    #[tokio::test]
+    async fn test_valid_credentials() {
         let middleware = BasicAuthMiddleware::new(test_config());
 
         // admin:secret123 in base64 = YWRtaW46c2VjcmV0MTIz
@@ -188,11 +174,11 @@ mod tests {
             .body(())
             .unwrap();
 
-        assert!(middleware.is_authenticated(&req));
+        assert!(middleware.authenticate(&req).await);
     }
 
-    #[test]
-    fn test_invalid_password() {
+    #[tokio::test]
+    async fn test_invalid_password() {
         let middleware = BasicAuthMiddleware::new(test_config());
 
         // admin:wrongpass in base64 = YWRtaW46d3JvbmdwYXNz
@@ -201,11 +187,11 @@ mod tests {
             .body(())
             .unwrap();
 
-        assert!(!middleware.is_authenticated(&req));
+        assert!(!middleware.authenticate(&req).await);
     }
 
-    #[test]
-    fn test_unknown_user() {
+    #[tokio::test]
+    async fn test_unknown_user() {
         let middleware = BasicAuthMiddleware::new(test_config());
 
         // unknown:password in base64 = dW5rbm93bjpwYXNzd29yZA==
@@ -214,20 +200,20 @@ mod tests {
             .body(())
             .unwrap();
 
-        assert!(!middleware.is_authenticated(&req));
+        assert!(!middleware.authenticate(&req).await);
     }
 
-    #[test]
-    fn test_no_auth_header() {
+    #[tokio::test]
+    async fn test_no_auth_header() {
         let middleware = BasicAuthMiddleware::new(test_config());
 
         let req = Request::builder().body(()).unwrap();
 
-        assert!(!middleware.is_authenticated(&req));
+        assert!(!middleware.authenticate(&req).await);
     }
 
-    #[test]
-    fn test_wrong_auth_type() {
+    #[tokio::test]
+    async fn test_wrong_auth_type() {
         let middleware = BasicAuthMiddleware::new(test_config());
 
         let req = Request::builder()
@@ -235,7 +221,7 @@ mod tests {
             .body(())
             .unwrap();
 
-        assert!(!middleware.is_authenticated(&req));
+        assert!(!middleware.authenticate(&req).await);
     }
 
     #[test]
@@ -261,10 +247,75 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_constant_time_compare() {
-        assert!(constant_time_compare("test", "test"));
-        assert!(!constant_time_compare("test", "Test"));
-        assert!(!constant_time_compare("test", "test1"));
+    /// `user:password` as a Basic Authorization header
+    fn basic(user: &str, password: &str) -> String {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        format!("Basic {}", STANDARD.encode(format!("{}:{}", user, password)))
+    }
+
+    async fn authenticates(middleware: &BasicAuthMiddleware, user: &str, password: &str) -> bool {
+        let req = Request::builder()
+            .header(AUTHORIZATION, basic(user, password))
+            .body(())
+            .unwrap();
+        middleware.authenticate(&req).await
+    }
+
+    #[tokio::test]
+    async fn test_users_file_with_bcrypt_and_apr1() {
+        let path = std::env::temp_dir().join(format!("trafficcop-htpasswd-{}", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "# htpasswd -B / htpasswd -m\n\
+             carol:$2y$05$abcdefghijklmnopqrstuuS0TNuUKeHEI4dIzi2OnnJXK77gW5q4q\n\
+             dave:$apr1$s4ltS4lt$m5g2HzJDITG6YV6xbxl1u1\n\
+             admin:{SHA}xO2etOilyqtV8o1RvvnmkeBx7QI=\n",
+        )
+        .unwrap();
+
+        let middleware = BasicAuthMiddleware::new(BasicAuthConfig {
+            users_file: Some(path.to_string_lossy().into_owned()),
+            ..test_config()
+        });
+        let _ = std::fs::remove_file(&path);
+
+        assert!(authenticates(&middleware, "carol", "bcrypt-pass").await);
+        assert!(!authenticates(&middleware, "carol", "bcrypt-Pass").await);
+        assert!(authenticates(&middleware, "dave", "apr1-pass").await);
+        assert!(!authenticates(&middleware, "dave", "apr1-pas").await);
+        // The file's entry replaces the inline one
+        assert!(authenticates(&middleware, "admin", "sha-pass").await);
+        assert!(!authenticates(&middleware, "admin", "secret123").await);
+        // Inline users not in the file still work
+        assert!(authenticates(&middleware, "user", "password").await);
+
+        let www_auth = middleware.unauthorized_response();
+        assert_eq!(www_auth.headers()[WWW_AUTHENTICATE], "Basic realm=\"Test Realm\"");
+    }
+
+    #[tokio::test]
+    async fn test_missing_users_file_rejects_its_users() {
+        let middleware = BasicAuthMiddleware::new(BasicAuthConfig {
+            users: Vec::new(),
+            users_file: Some("/nonexistent/htpasswd".to_string()),
+            ..test_config()
+        });
+        assert!(!authenticates(&middleware, "admin", "secret123").await);
+    }
+
+    #[tokio::test]
+    async fn test_successful_checks_are_cached() {
+        let middleware = BasicAuthMiddleware::new(BasicAuthConfig {
+            users: vec!["carol:$2y$05$abcdefghijklmnopqrstuuS0TNuUKeHEI4dIzi2OnnJXK77gW5q4q".to_string()],
+            ..test_config()
+        });
+
+        assert!(!authenticates(&middleware, "carol", "bcrypt-Pass").await);
+        assert!(middleware.verified.is_empty());
+        assert!(authenticates(&middleware, "carol", "bcrypt-pass").await);
+        assert!(authenticates(&middleware, "carol", "bcrypt-pass").await);
+        assert_eq!(middleware.verified.len(), 1);
+        // A cached check doesn't let other passwords through
+        assert!(!authenticates(&middleware, "carol", "bcrypt-Pass").await);
     }
 }
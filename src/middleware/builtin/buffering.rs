@@ -1,4 +1,252 @@
 use crate::config::BufferingConfig;
+use crate::health::{find_operator, Comparison};
+use crate::middleware::BufferedRequestBody;
+use bytes::{Bytes, BytesMut};
+use futures::stream;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Body, Frame};
+use hyper::header::{HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING};
+use hyper::http::Extensions;
+use hyper::{Request, StatusCode};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Upper bound on attempts, whatever the retry expression says
+const MAX_RETRY_ATTEMPTS: u32 = 10;
+
+/// Chunk size when streaming a spooled body back out
+const SPOOL_READ_CHUNK: usize = 64 * 1024;
+
+/// Why a request body could not be buffered
+#[derive(Debug, Error)]
+pub enum BufferingError {
+    /// The body exceeds `maxRequestBodyBytes`
+    #[error("request body exceeds {0} bytes")]
+    TooLarge(i64),
+    /// The body could not be read from the client
+    #[error("failed to read request body: {0}")]
+    Body(String),
+    /// The body could not be spooled to disk
+    #[error("failed to spool request body: {0}")]
+    Spool(#[from] std::io::Error),
+}
+
+impl BufferingError {
+    /// Status code to reject the request with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Body(_) => StatusCode::BAD_REQUEST,
+            Self::Spool(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// A request body spooled to a temporary file. The file is removed on drop.
+#[derive(Debug)]
+pub struct SpoolFile {
+    path: PathBuf,
+    len: u64,
+}
+
+impl SpoolFile {
+    fn new() -> Self {
+        Self {
+            path: std::env::temp_dir().join(format!("trafficcop-body-{}", Uuid::new_v4().simple())),
+            len: 0,
+        }
+    }
+
+    /// Location of the spooled body
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size of the spooled body in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the spooled body is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Stream the body from the start of the file. A read error ends the body
+    /// early, which fails the request on its Content-Length.
+    pub fn body(self: &Arc<Self>) -> BoxBody<Bytes, hyper::Error> {
+        let chunks = stream::unfold((Arc::clone(self), None::<File>), |(spool, file)| async move {
+            let mut file = match file {
+                Some(file) => file,
+                None => match File::open(&spool.path).await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        warn!("Failed to open spooled body {}: {}", spool.path.display(), e);
+                        return None;
+                    }
+                },
+            };
+            let mut chunk = BytesMut::zeroed(SPOOL_READ_CHUNK);
+            match file.read(&mut chunk).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    Some((Ok(Frame::data(chunk.freeze())), (spool, Some(file))))
+                }
+                Err(e) => {
+                    warn!("Failed to read spooled body {}: {}", spool.path.display(), e);
+                    None
+                }
+            }
+        });
+        BodyExt::boxed(StreamBody::new(chunks))
+    }
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Request body spooled to disk by the buffering middleware. Like
+/// [`BufferedRequestBody`], the forwarding endpoint sends it in place of the
+/// drained incoming body.
+#[derive(Debug, Clone)]
+pub struct SpooledRequestBody(pub Arc<SpoolFile>);
+
+/// A buffered request body that can be sent more than once
+#[derive(Debug, Clone)]
+pub enum ReplayableBody {
+    /// Held in memory
+    Memory(Bytes),
+    /// Spooled to a temporary file
+    Spooled(Arc<SpoolFile>),
+}
+
+impl ReplayableBody {
+    /// The buffered body recorded in a request's extensions, if any
+    pub fn from_extensions(extensions: &Extensions) -> Option<Self> {
+        if let Some(buffered) = extensions.get::<BufferedRequestBody>() {
+            return Some(Self::Memory(buffered.0.clone()));
+        }
+        extensions
+            .get::<SpooledRequestBody>()
+            .map(|spooled| Self::Spooled(Arc::clone(&spooled.0)))
+    }
+
+    /// A fresh body to send
+    pub fn body(&self) -> BoxBody<Bytes, hyper::Error> {
+        match self {
+            Self::Memory(bytes) => Full::new(bytes.clone()).map_err(|never| match never {}).boxed(),
+            Self::Spooled(spool) => spool.body(),
+        }
+    }
+}
+
+/// Functions available in retry expressions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetryMetric {
+    /// Attempts made so far, counting the first
+    Attempts,
+    /// Status code of the last response (no value after a network error)
+    ResponseCode,
+}
+
+/// Parsed buffering retry expression, e.g. `IsNetworkError() && Attempts() < 2`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryExpression {
+    /// The last attempt failed without a response (connection error or timeout)
+    IsNetworkError,
+    /// Compare a metric against a constant
+    Compare(RetryMetric, Comparison, f64),
+    /// Both sub-expressions hold
+    And(Box<RetryExpression>, Box<RetryExpression>),
+    /// Either sub-expression holds
+    Or(Box<RetryExpression>, Box<RetryExpression>),
+}
+
+impl RetryExpression {
+    /// Parse an expression supporting `&&`, `||`, parentheses, `IsNetworkError()`,
+    /// and comparisons on `Attempts()` and `ResponseCode()`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if let Some(pos) = find_operator(input, "||") {
+            let left = Self::parse(&input[..pos])?;
+            let right = Self::parse(&input[pos + 2..])?;
+            return Ok(Self::Or(Box::new(left), Box::new(right)));
+        }
+        if let Some(pos) = find_operator(input, "&&") {
+            let left = Self::parse(&input[..pos])?;
+            let right = Self::parse(&input[pos + 2..])?;
+            return Ok(Self::And(Box::new(left), Box::new(right)));
+        }
+        if input.starts_with('(') && input.ends_with(')') {
+            return Self::parse(&input[1..input.len() - 1]);
+        }
+        if input.replace(' ', "") == "IsNetworkError()" {
+            return Ok(Self::IsNetworkError);
+        }
+
+        // Two-character operators first so `>=` isn't read as `>`
+        const OPERATORS: [(&str, Comparison); 6] = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+        ];
+        let (pos, op, comparison) = OPERATORS
+            .iter()
+            .find_map(|(op, cmp)| find_operator(input, op).map(|pos| (pos, *op, *cmp)))
+            .ok_or_else(|| format!("invalid retry expression '{}'", input))?;
+
+        let metric = match input[..pos].replace(' ', "").as_str() {
+            "Attempts()" => RetryMetric::Attempts,
+            "ResponseCode()" => RetryMetric::ResponseCode,
+            other => return Err(format!("unknown retry function '{}'", other)),
+        };
+        let value = input[pos + op.len()..]
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid retry expression '{}'", input))?;
+        Ok(Self::Compare(metric, comparison, value))
+    }
+
+    /// Evaluate after `attempts` attempts, the last ending with `status`
+    /// (`None` for a network error)
+    pub fn evaluate(&self, attempts: u32, status: Option<u16>) -> bool {
+        match self {
+            Self::IsNetworkError => status.is_none(),
+            Self::Compare(metric, comparison, value) => {
+                let actual = match metric {
+                    RetryMetric::Attempts => Some(attempts as f64),
+                    RetryMetric::ResponseCode => status.map(f64::from),
+                };
+                actual.is_some_and(|actual| comparison.holds(actual, *value))
+            }
+            Self::And(a, b) => a.evaluate(attempts, status) && b.evaluate(attempts, status),
+            Self::Or(a, b) => a.evaluate(attempts, status) || b.evaluate(attempts, status),
+        }
+    }
+
+    /// Whether to send the request again, capped at a fixed number of attempts
+    pub fn should_retry(&self, attempts: u32, status: Option<u16>) -> bool {
+        attempts < MAX_RETRY_ATTEMPTS && self.evaluate(attempts, status)
+    }
+}
+
+/// Retry policy attached to a buffered request; the forwarding endpoint replays
+/// the body while the expression holds
+#[derive(Debug, Clone)]
+pub struct BufferingRetry(pub Arc<RetryExpression>);
 
 /// Buffering middleware configuration for request/response body buffering
 /// This allows retrying requests by buffering the body in memory
@@ -13,17 +261,27 @@ pub struct BufferingMiddleware {
     pub mem_response_body_bytes: i64,
     /// Expression to determine when to retry (e.g., "IsNetworkError() && Attempts() < 2")
     pub retry_expression: Option<String>,
+    /// Parsed `retry_expression`
+    retry: Option<Arc<RetryExpression>>,
 }
 
 impl BufferingMiddleware {
     /// Create from config with the specified buffer size limits.
     pub fn new(config: BufferingConfig) -> Self {
+        let retry = config.retry_expression.as_deref().and_then(|expression| {
+            RetryExpression::parse(expression)
+                .map_err(|e| warn!("Ignoring buffering retryExpression: {}", e))
+                .ok()
+                .map(Arc::new)
+        });
+
         Self {
             max_request_body_bytes: config.max_request_body_bytes,
             mem_request_body_bytes: config.mem_request_body_bytes,
             max_response_body_bytes: config.max_response_body_bytes,
             mem_response_body_bytes: config.mem_response_body_bytes,
             retry_expression: config.retry_expression,
+            retry,
         }
     }
 
@@ -56,12 +314,114 @@ impl BufferingMiddleware {
     pub fn response_fits_in_memory(&self, size: i64) -> bool {
         self.mem_response_body_bytes == 0 || size <= self.mem_response_body_bytes
     }
+
+    /// Read the whole request body, rejecting it once it passes
+    /// `maxRequestBodyBytes`. Bodies up to `memRequestBodyBytes` stay in memory
+    /// ([`BufferedRequestBody`]); larger ones are spooled to a temporary file
+    /// ([`SpooledRequestBody`]). With a retry expression, a [`BufferingRetry`]
+    /// is attached so the body can be replayed.
+    pub async fn read_request_body<B>(&self, req: &mut Request<B>) -> Result<(), BufferingError>
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: std::fmt::Display,
+    {
+        if !self.buffer_request() && self.retry.is_none() {
+            return Ok(());
+        }
+
+        let declared_len = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok());
+        if declared_len.is_some_and(|len| !self.request_within_limit(len)) {
+            return Err(BufferingError::TooLarge(self.max_request_body_bytes));
+        }
+
+        // An earlier middleware may already have replaced the body
+        let mut replaced = req.extensions_mut().remove::<BufferedRequestBody>().map(|b| b.0);
+        let mut memory = BytesMut::new();
+        let mut spool: Option<(File, SpoolFile)> = None;
+        let mut total = 0i64;
+        loop {
+            let data = match replaced.take() {
+                Some(data) => data,
+                None => match req.body_mut().frame().await {
+                    Some(frame) => match frame.map_err(|e| BufferingError::Body(e.to_string()))?.into_data() {
+                        Ok(data) => data,
+                        Err(_) => continue,
+                    },
+                    None => break,
+                },
+            };
+            total += data.len() as i64;
+            if !self.request_within_limit(total) {
+                return Err(BufferingError::TooLarge(self.max_request_body_bytes));
+            }
+
+            match &mut spool {
+                Some((file, _)) => file.write_all(&data).await?,
+                None if self.request_fits_in_memory(total) => memory.extend_from_slice(&data),
+                None => {
+                    // Created first so the file is removed if writing fails
+                    let spool_file = SpoolFile::new();
+                    let mut file = File::create(&spool_file.path).await?;
+                    file.write_all(&memory).await?;
+                    file.write_all(&data).await?;
+                    memory = BytesMut::new();
+                    spool = Some((file, spool_file));
+                }
+            }
+        }
+
+        let headers = req.headers_mut();
+        headers.remove(TRANSFER_ENCODING);
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(total));
+
+        match spool {
+            Some((mut file, mut spool_file)) => {
+                file.flush().await?;
+                spool_file.len = total as u64;
+                req.extensions_mut().insert(SpooledRequestBody(Arc::new(spool_file)));
+            }
+            None => {
+                req.extensions_mut().insert(BufferedRequestBody(memory.freeze()));
+            }
+        }
+        if let Some(retry) = &self.retry {
+            req.extensions_mut().insert(BufferingRetry(Arc::clone(retry)));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn middleware(max: i64, mem: i64, retry_expression: Option<&str>) -> BufferingMiddleware {
+        BufferingMiddleware::new(BufferingConfig {
+            max_request_body_bytes: max,
+            mem_request_body_bytes: mem,
+            max_response_body_bytes: 0,
+            mem_response_body_bytes: 0,
+            retry_expression: retry_expression.map(String::from),
+        })
+    }
+
+    /// Request whose body arrives in `chunks`, without a Content-Length
+    fn chunked_request(chunks: &[&'static [u8]]) -> Request<BoxBody<Bytes, hyper::Error>> {
+        let frames: Vec<Result<Frame<Bytes>, hyper::Error>> = chunks
+            .iter()
+            .map(|c| Ok(Frame::data(Bytes::from_static(c))))
+            .collect();
+        Request::new(BodyExt::boxed(StreamBody::new(stream::iter(frames))))
+    }
+
+    async fn collect(body: BoxBody<Bytes, hyper::Error>) -> Bytes {
+        body.collect().await.unwrap().to_bytes()
+    }
+
     #[test]
     fn test_buffering_defaults() {
         let config = BufferingConfig {
@@ -121,4 +481,74 @@ mod tests {
         assert!(!middleware.buffer_request());
         assert!(!middleware.buffer_response());
     }
+
+    #[tokio::test]
+    async fn test_under_limit_body_stays_in_memory() {
+        let mw = middleware(64, 32, None);
+        let mut req = chunked_request(&[b"hello ", b"buffer"]);
+        mw.read_request_body(&mut req).await.unwrap();
+
+        assert_eq!(req.headers().get(CONTENT_LENGTH).unwrap(), "12");
+        assert!(req.extensions().get::<SpooledRequestBody>().is_none());
+        assert!(req.extensions().get::<BufferingRetry>().is_none());
+        let replay = ReplayableBody::from_extensions(req.extensions()).unwrap();
+        assert_eq!(collect(replay.body()).await.as_ref(), b"hello buffer");
+    }
+
+    #[tokio::test]
+    async fn test_over_limit_body_is_rejected() {
+        let mw = middleware(8, 4, None);
+
+        let err = mw.read_request_body(&mut chunked_request(&[b"12345", b"67890"])).await.unwrap_err();
+        assert!(matches!(err, BufferingError::TooLarge(8)));
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // A declared length over the limit is rejected without reading
+        let mut req = chunked_request(&[]);
+        req.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(9u64));
+        assert_eq!(mw.read_request_body(&mut req).await.unwrap_err().status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_large_body_spools_to_disk_and_replays() {
+        let mw = middleware(64, 8, Some("IsNetworkError() && Attempts() < 3"));
+        let mut req = chunked_request(&[b"0123456789", b"abcdefghij", b"KLMNOP"]);
+        mw.read_request_body(&mut req).await.unwrap();
+
+        assert!(req.extensions().get::<BufferedRequestBody>().is_none());
+        let spool = Arc::clone(&req.extensions().get::<SpooledRequestBody>().unwrap().0);
+        assert_eq!(spool.len(), 26);
+        assert_eq!(std::fs::read(spool.path()).unwrap(), b"0123456789abcdefghijKLMNOP");
+        assert!(req.extensions().get::<BufferingRetry>().is_some());
+
+        // Each replay streams the whole file again
+        let replay = ReplayableBody::from_extensions(req.extensions()).unwrap();
+        for _ in 0..2 {
+            assert_eq!(collect(replay.body()).await.as_ref(), b"0123456789abcdefghijKLMNOP");
+        }
+
+        let path = spool.path().to_path_buf();
+        drop((req, replay, spool));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_retry_expression() {
+        let expr = RetryExpression::parse("IsNetworkError() && Attempts() < 2").unwrap();
+        assert!(expr.should_retry(1, None));
+        assert!(!expr.should_retry(2, None));
+        assert!(!expr.should_retry(1, Some(502)));
+
+        let expr = RetryExpression::parse("(ResponseCode() >= 502 && ResponseCode() <= 504) || IsNetworkError()").unwrap();
+        assert!(expr.should_retry(1, Some(503)));
+        assert!(expr.should_retry(1, None));
+        assert!(!expr.should_retry(1, Some(500)));
+
+        // Unbounded expressions still stop
+        let expr = RetryExpression::parse("IsNetworkError()").unwrap();
+        assert!(!expr.should_retry(MAX_RETRY_ATTEMPTS, None));
+
+        assert!(RetryExpression::parse("Latency() > 5").is_err());
+        assert!(RetryExpression::parse("Attempts() < x").is_err());
+    }
 }
@@ -1,13 +1,35 @@
-use crate::config::CompressConfig;
+use crate::config::{CompressConfig, CompressionLevels};
+use bytes::Bytes;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
-use hyper::HeaderMap;
+use http_body_util::combinators::BoxBody;
+use futures::stream::{self, StreamExt};
+use http_body_util::{BodyExt, BodyStream, Full, StreamBody};
+use hyper::body::{Body, Frame};
+use hyper::header::{
+    HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, VARY,
+};
+use hyper::{HeaderMap, Response, StatusCode};
 use std::io::Write;
+use tracing::{debug, warn};
 
-/// Compression middleware for response body compression
+/// Compression middleware for response body compression.
+///
+/// The encoding is negotiated from the client's Accept-Encoding: the configured
+/// encoding with the highest q-value wins, ties going to the one listed first in
+/// `encodings`. Responses below `minResponseBodyBytes`, of excluded content types,
+/// or already encoded by the backend pass through unchanged, as do bodies larger
+/// than `maxBodyBytes`, which would otherwise have to be buffered whole.
 pub struct CompressMiddleware {
     min_size: u64,
+    max_body_bytes: usize,
+    /// Configured encodings, most preferred first
+    encodings: Vec<CompressionAlgorithm>,
+    /// Used when the client sends no Accept-Encoding (or only `*`)
+    default_encoding: Option<CompressionAlgorithm>,
+    included_content_types: Vec<String>,
+    excluded_content_types: Vec<String>,
+    levels: CompressionLevels,
 }
 
 /// Supported response compression algorithms.
@@ -17,52 +39,117 @@ pub enum CompressionAlgorithm {
     Gzip,
     /// Brotli compression (RFC 7932).
     Brotli,
+    /// Zstandard compression (RFC 8878).
+    Zstd,
     /// No compression applied.
     None,
 }
 
+impl CompressionAlgorithm {
+    fn parse(token: &str) -> Option<Self> {
+        match token.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "br" => Some(Self::Brotli),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
 impl CompressMiddleware {
-    /// Create from config with the minimum response size threshold.
+    /// Create from config. Unknown encodings are skipped with a warning.
     pub fn new(config: CompressConfig) -> Self {
+        let encodings: Vec<CompressionAlgorithm> = config
+            .encodings
+            .iter()
+            .filter_map(|name| {
+                let algorithm = CompressionAlgorithm::parse(name);
+                if algorithm.is_none() {
+                    warn!("Ignoring unsupported compress encoding '{}'", name);
+                }
+                algorithm
+            })
+            .collect();
+        let default_encoding = config
+            .default_encoding
+            .as_deref()
+            .and_then(CompressionAlgorithm::parse)
+            .filter(|algorithm| encodings.contains(algorithm));
+
         Self {
             min_size: config.min_response_body_bytes,
+            max_body_bytes: usize::try_from(config.max_body_bytes).unwrap_or(usize::MAX),
+            encodings,
+            default_encoding,
+            included_content_types: normalize_media_types(&config.included_content_types),
+            excluded_content_types: normalize_media_types(&config.excluded_content_types),
+            levels: config.levels,
         }
     }
 
-    /// Determine best compression algorithm from Accept-Encoding header
-    #[inline]
-    pub fn select_algorithm(headers: &HeaderMap) -> CompressionAlgorithm {
+    /// Determine the best compression algorithm from the Accept-Encoding header
+    pub fn select_algorithm(&self, headers: &HeaderMap) -> CompressionAlgorithm {
         let accept = match headers.get(ACCEPT_ENCODING) {
             Some(v) => match v.to_str() {
                 Ok(s) => s,
                 Err(_) => return CompressionAlgorithm::None,
             },
-            None => return CompressionAlgorithm::None,
+            None => return self.default_encoding.unwrap_or(CompressionAlgorithm::None),
         };
 
-        // Case-insensitive check without allocation
-        // Prefer brotli over gzip
-        if accept.as_bytes().windows(2).any(|w| w.eq_ignore_ascii_case(b"br")) {
-            return CompressionAlgorithm::Brotli;
+        let mut wildcard = None;
+        let mut accepted: Vec<(CompressionAlgorithm, f32)> = Vec::new();
+        for (coding, q) in accept.split(',').filter_map(parse_coding) {
+            if coding == "*" {
+                wildcard = Some(q);
+            } else if let Some(algorithm) = CompressionAlgorithm::parse(coding) {
+                accepted.push((algorithm, q));
+            }
         }
-        if accept.as_bytes().windows(4).any(|w| w.eq_ignore_ascii_case(b"gzip")) {
-            return CompressionAlgorithm::Gzip;
+
+        // A bare `*` leaves the choice to the server
+        if accepted.is_empty() && wildcard.is_some_and(|q| q > 0.0) && let Some(default) = self.default_encoding {
+            return default;
         }
 
-        CompressionAlgorithm::None
+        // Highest q-value wins; on a tie the earlier configured encoding is kept
+        let mut best = (CompressionAlgorithm::None, 0.0);
+        for &algorithm in &self.encodings {
+            let q = accepted
+                .iter()
+                .find(|(a, _)| *a == algorithm)
+                .map(|&(_, q)| q)
+                .or(wildcard)
+                .unwrap_or(0.0);
+            if q > best.1 {
+                best = (algorithm, q);
+            }
+        }
+        best.0
     }
 
-    /// Check if content type should be compressed
-    #[inline]
-    pub fn should_compress_content_type(headers: &HeaderMap) -> bool {
+    /// Check if the response's content type should be compressed. Excluded types
+    /// never are; with `includedContentTypes` only those are, otherwise text-like types.
+    pub fn should_compress_content_type(&self, headers: &HeaderMap) -> bool {
         let content_type = match headers.get(CONTENT_TYPE) {
             Some(v) => match v.to_str() {
-                Ok(s) => s.to_lowercase(),
+                Ok(s) => media_type(s),
                 Err(_) => return false,
             },
-            None => return true, // Assume compressible if no content type
+            None => return self.included_content_types.is_empty(), // Assume compressible if no content type
         };
 
+        // Event streams must reach the client as they are written
+        if content_type == "text/event-stream" {
+            return false;
+        }
+        if matches_media_type(&self.excluded_content_types, &content_type) {
+            return false;
+        }
+        if !self.included_content_types.is_empty() {
+            return matches_media_type(&self.included_content_types, &content_type);
+        }
+
         // Compress text-based content types
         content_type.contains("text/")
             || content_type.contains("application/json")
@@ -75,7 +162,9 @@ impl CompressMiddleware {
     /// Check if response is already compressed
     #[inline]
     pub fn is_already_compressed(headers: &HeaderMap) -> bool {
-        headers.contains_key(CONTENT_ENCODING)
+        headers
+            .get(CONTENT_ENCODING)
+            .is_some_and(|encoding| !encoding.as_bytes().eq_ignore_ascii_case(b"identity"))
     }
 
     /// Check if body size meets minimum threshold
@@ -87,41 +176,136 @@ impl CompressMiddleware {
         }
     }
 
+    /// Compress the response with `algorithm` (from [`select_algorithm`](Self::select_algorithm)
+    /// on the request) when it qualifies. The body is buffered to be compressed; a body
+    /// that turns out smaller than the minimum size or larger than `maxBodyBytes` is
+    /// sent as is.
+    pub async fn compress_response(
+        &self,
+        response: Response<BoxBody<Bytes, hyper::Error>>,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+        let status = response.status();
+        if status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT
+            || Self::is_already_compressed(response.headers())
+            || !self.should_compress_content_type(response.headers())
+        {
+            return Ok(response);
+        }
+        let size_hint = response.body().size_hint();
+        if !self.meets_size_threshold(size_hint.exact())
+            || size_hint.lower() > self.max_body_bytes as u64
+        {
+            return Ok(response);
+        }
+
+        // The representation now depends on the client's Accept-Encoding
+        let (mut parts, mut body) = response.into_parts();
+        parts.headers.append(VARY, HeaderValue::from_static("Accept-Encoding"));
+        let Some(encoding) = Self::encoding_header(algorithm) else {
+            return Ok(Response::from_parts(parts, body));
+        };
+
+        let mut buffered: Vec<Bytes> = Vec::new();
+        let mut size = 0usize;
+        while let Some(frame) = body.frame().await {
+            let frame = frame?;
+            let within_cap = frame
+                .data_ref()
+                .is_some_and(|data| size + data.len() <= self.max_body_bytes);
+            if !within_cap {
+                debug!("Response body exceeds compress limit, passing through uncompressed");
+                let replay = stream::iter(
+                    buffered
+                        .into_iter()
+                        .map(Frame::data)
+                        .chain(std::iter::once(frame))
+                        .map(Ok),
+                );
+                let passthrough = StreamBody::new(replay.chain(BodyStream::new(body)));
+                return Ok(Response::from_parts(parts, BodyExt::boxed(passthrough)));
+            }
+            if let Ok(data) = frame.into_data() {
+                size += data.len();
+                buffered.push(data);
+            }
+        }
+        let data = Bytes::from(buffered.concat());
+        if (data.len() as u64) < self.min_size {
+            return Ok(Response::from_parts(parts, full_body(data)));
+        }
+        let compressed = match self.compress(data.to_vec(), algorithm).await {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                debug!("Failed to {} compress response: {}", encoding, e);
+                return Ok(Response::from_parts(parts, full_body(data)));
+            }
+        };
+
+        parts.headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        parts.headers.insert(CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+        parts.headers.remove(ACCEPT_RANGES);
+        // The encoded body is a different representation: a strong ETag becomes weak
+        if let Some(etag) = parts.headers.get(ETAG)
+            && !etag.as_bytes().starts_with(b"W/")
+            && let Ok(weak) = HeaderValue::from_bytes(&[b"W/", etag.as_bytes()].concat())
+        {
+            parts.headers.insert(ETAG, weak);
+        }
+        Ok(Response::from_parts(parts, full_body(Bytes::from(compressed))))
+    }
+
     /// Compress bytes with gzip (synchronous, for use inside spawn_blocking)
-    fn compress_gzip_sync(data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    fn compress_gzip_sync(data: &[u8], level: u32) -> Result<Vec<u8>, std::io::Error> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
         encoder.write_all(data)?;
         encoder.finish()
     }
 
     /// Compress bytes with brotli (synchronous, for use inside spawn_blocking)
-    fn compress_brotli_sync(data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    fn compress_brotli_sync(data: &[u8], quality: u32) -> Result<Vec<u8>, std::io::Error> {
         let mut output = Vec::new();
-        let mut writer = brotli::CompressorWriter::new(&mut output, 4096, 4, 22);
+        let mut writer = brotli::CompressorWriter::new(&mut output, 4096, quality, 22);
         writer.write_all(data)?;
         drop(writer);
         Ok(output)
     }
 
-    /// Compress bytes with gzip (non-blocking)
-    pub async fn compress_gzip(data: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
-        tokio::task::spawn_blocking(move || Self::compress_gzip_sync(&data))
+    /// Compress bytes with zstd (synchronous, for use inside spawn_blocking)
+    fn compress_zstd_sync(data: &[u8], level: u32) -> Result<Vec<u8>, std::io::Error> {
+        zstd::encode_all(data, level as i32)
+    }
+
+    /// Compress bytes with gzip at `level` (non-blocking)
+    pub async fn compress_gzip(data: Vec<u8>, level: u32) -> Result<Vec<u8>, std::io::Error> {
+        tokio::task::spawn_blocking(move || Self::compress_gzip_sync(&data, level))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// Compress bytes with brotli at `quality` (non-blocking)
+    pub async fn compress_brotli(data: Vec<u8>, quality: u32) -> Result<Vec<u8>, std::io::Error> {
+        tokio::task::spawn_blocking(move || Self::compress_brotli_sync(&data, quality))
             .await
             .map_err(std::io::Error::other)?
     }
 
-    /// Compress bytes with brotli (non-blocking)
-    pub async fn compress_brotli(data: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
-        tokio::task::spawn_blocking(move || Self::compress_brotli_sync(&data))
+    /// Compress bytes with zstd at `level` (non-blocking)
+    pub async fn compress_zstd(data: Vec<u8>, level: u32) -> Result<Vec<u8>, std::io::Error> {
+        tokio::task::spawn_blocking(move || Self::compress_zstd_sync(&data, level))
             .await
             .map_err(std::io::Error::other)?
     }
 
-    /// Compress data with the specified algorithm (non-blocking)
-    pub async fn compress(data: Vec<u8>, algorithm: CompressionAlgorithm) -> Result<Vec<u8>, std::io::Error> {
+    /// Compress data with the specified algorithm at its configured level (non-blocking)
+    pub async fn compress(&self, data: Vec<u8>, algorithm: CompressionAlgorithm) -> Result<Vec<u8>, std::io::Error> {
         match algorithm {
-            CompressionAlgorithm::Gzip => Self::compress_gzip(data).await,
-            CompressionAlgorithm::Brotli => Self::compress_brotli(data).await,
+            CompressionAlgorithm::Gzip => Self::compress_gzip(data, self.levels.gzip).await,
+            CompressionAlgorithm::Brotli => Self::compress_brotli(data, self.levels.brotli).await,
+            CompressionAlgorithm::Zstd => Self::compress_zstd(data, self.levels.zstd).await,
             CompressionAlgorithm::None => Ok(data),
         }
     }
@@ -132,69 +316,398 @@ impl CompressMiddleware {
         match algorithm {
             CompressionAlgorithm::Gzip => Some("gzip"),
             CompressionAlgorithm::Brotli => Some("br"),
+            CompressionAlgorithm::Zstd => Some("zstd"),
             CompressionAlgorithm::None => None,
         }
     }
 }
 
+/// One Accept-Encoding entry as (coding, q). Entries with an unparsable q are dropped.
+fn parse_coding(entry: &str) -> Option<(&str, f32)> {
+    let mut params = entry.split(';');
+    let coding = params.next()?.trim();
+    if coding.is_empty() {
+        return None;
+    }
+    let mut q = 1.0;
+    for param in params {
+        if let Some((name, value)) = param.split_once('=')
+            && name.trim().eq_ignore_ascii_case("q")
+        {
+            q = value.trim().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?;
+        }
+    }
+    Some((coding, q))
+}
+
+/// Media type of a Content-Type value, lowercased and without parameters
+fn media_type(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+fn normalize_media_types(types: &[String]) -> Vec<String> {
+    types.iter().map(|t| media_type(t)).collect()
+}
+
+/// Whether `content_type` is in `types`, which may hold `type/*` wildcards
+fn matches_media_type(types: &[String], content_type: &str) -> bool {
+    types.iter().any(|t| match t.strip_suffix("/*") {
+        Some(main) => content_type.split('/').next() == Some(main),
+        None => t == content_type,
+    })
+}
+
+fn full_body(data: Bytes) -> BoxBody<Bytes, hyper::Error> {
+    Full::new(data).map_err(|never| match never {}).boxed()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hyper::header::HeaderValue;
+    use crate::config::Config;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn test_config() -> CompressConfig {
+        CompressConfig {
+            excluded_content_types: vec![],
+            included_content_types: vec![],
+            min_response_body_bytes: 1024,
+            default_encoding: None,
+            encodings: vec!["zstd".to_string(), "br".to_string(), "gzip".to_string()],
+            max_body_bytes: 1024 * 1024,
+            levels: CompressionLevels::default(),
+        }
+    }
+
+    fn with_min_size(min_response_body_bytes: u64) -> CompressConfig {
+        CompressConfig { min_response_body_bytes, ..test_config() }
+    }
+
+    fn middleware(config: CompressConfig) -> CompressMiddleware {
+        CompressMiddleware::new(config)
+    }
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    fn response(content_type: &'static str, body: &str) -> Response<BoxBody<Bytes, hyper::Error>> {
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .body(full_body(Bytes::from(body.to_string())))
+            .unwrap()
+    }
+
+    async fn body(response: Response<BoxBody<Bytes, hyper::Error>>) -> Bytes {
+        response.into_body().collect().await.unwrap().to_bytes()
+    }
 
     #[test]
     fn test_select_algorithm_gzip() {
-        let mut headers = HeaderMap::new();
-        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip, deflate"));
         assert_eq!(
-            CompressMiddleware::select_algorithm(&headers),
+            middleware(test_config()).select_algorithm(&accept("gzip, deflate")),
             CompressionAlgorithm::Gzip
         );
     }
 
     #[test]
     fn test_select_algorithm_brotli() {
-        let mut headers = HeaderMap::new();
-        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip, br"));
         assert_eq!(
-            CompressMiddleware::select_algorithm(&headers),
+            middleware(test_config()).select_algorithm(&accept("gzip, br")),
             CompressionAlgorithm::Brotli
         );
     }
 
     #[test]
     fn test_select_algorithm_none() {
-        let headers = HeaderMap::new();
         assert_eq!(
-            CompressMiddleware::select_algorithm(&headers),
+            middleware(test_config()).select_algorithm(&HeaderMap::new()),
+            CompressionAlgorithm::None
+        );
+    }
+
+    #[test]
+    fn test_select_algorithm_by_q_value() {
+        let compress = middleware(test_config());
+        // The client's preference beats the configured order
+        assert_eq!(
+            compress.select_algorithm(&accept("zstd;q=0.5, br;q=0.8, gzip;q=1.0")),
+            CompressionAlgorithm::Gzip
+        );
+        assert_eq!(
+            compress.select_algorithm(&accept("gzip;q=0.2, br;q=0.9")),
+            CompressionAlgorithm::Brotli
+        );
+        // Equal q-values follow the configured order (zstd, br, gzip)
+        assert_eq!(
+            compress.select_algorithm(&accept("gzip, br, zstd")),
+            CompressionAlgorithm::Zstd
+        );
+        // q=0 refuses an encoding, including through the wildcard
+        assert_eq!(
+            compress.select_algorithm(&accept("br;q=0, gzip")),
+            CompressionAlgorithm::Gzip
+        );
+        assert_eq!(
+            compress.select_algorithm(&accept("*;q=0.5, zstd;q=0, br;q=0")),
+            CompressionAlgorithm::Gzip
+        );
+        assert_eq!(
+            compress.select_algorithm(&accept("identity, deflate")),
             CompressionAlgorithm::None
         );
     }
 
+    #[test]
+    fn test_select_algorithm_limited_to_configured_encodings() {
+        let compress = middleware(CompressConfig {
+            encodings: vec!["gzip".to_string(), "snappy".to_string()],
+            default_encoding: Some("gzip".to_string()),
+            ..test_config()
+        });
+        assert_eq!(
+            compress.select_algorithm(&accept("zstd, br;q=0.9, gzip;q=0.1")),
+            CompressionAlgorithm::Gzip
+        );
+        assert_eq!(compress.select_algorithm(&accept("zstd, br")), CompressionAlgorithm::None);
+        // No preference from the client: the default encoding
+        assert_eq!(compress.select_algorithm(&HeaderMap::new()), CompressionAlgorithm::Gzip);
+        assert_eq!(compress.select_algorithm(&accept("*")), CompressionAlgorithm::Gzip);
+    }
+
     #[test]
     fn test_compress_gzip() {
         let data = "Hello, World! This is some test data that should compress well. ".repeat(100);
-        let compressed = CompressMiddleware::compress_gzip_sync(data.as_bytes()).unwrap();
+        let compressed = CompressMiddleware::compress_gzip_sync(data.as_bytes(), 6).unwrap();
         assert!(compressed.len() < data.len());
     }
 
     #[test]
     fn test_compress_brotli() {
         let data = "Hello, World! This is some test data that should compress well. ".repeat(100);
-        let compressed = CompressMiddleware::compress_brotli_sync(data.as_bytes()).unwrap();
+        let compressed = CompressMiddleware::compress_brotli_sync(data.as_bytes(), 4).unwrap();
         assert!(compressed.len() < data.len());
     }
 
+    #[test]
+    fn test_compress_zstd() {
+        let data = "Hello, World! This is some test data that should compress well. ".repeat(100);
+        let compressed = CompressMiddleware::compress_zstd_sync(data.as_bytes(), 3).unwrap();
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), data.as_bytes());
+    }
+
+    /// Compressible but not trivially repetitive text
+    fn prose(words: usize) -> String {
+        const WORDS: [&str; 12] = [
+            "proxy", "backend", "router", "service", "request", "header",
+            "stream", "cluster", "health", "config", "tls", "middleware",
+        ];
+        let mut state = 0x2545_f491_u32;
+        (0..words)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                WORDS[(state >> 16) as usize % WORDS.len()]
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[tokio::test]
+    async fn test_higher_brotli_level_compresses_smaller() {
+        let data = prose(5000).into_bytes();
+        let levels = |brotli| CompressConfig {
+            levels: CompressionLevels { brotli, ..Default::default() },
+            ..test_config()
+        };
+        let fast = middleware(levels(1));
+        let best = middleware(levels(11));
+
+        let fast = fast.compress(data.clone(), CompressionAlgorithm::Brotli).await.unwrap();
+        let best = best.compress(data.clone(), CompressionAlgorithm::Brotli).await.unwrap();
+        assert!(best.len() < fast.len(), "level 11: {} bytes, level 1: {} bytes", best.len(), fast.len());
+        assert!(fast.len() < data.len());
+    }
+
+    #[test]
+    fn test_levels_default_balanced() {
+        let compress = middleware(CompressConfig {
+            levels: CompressionLevels { zstd: 19, ..Default::default() },
+            ..test_config()
+        });
+        assert_eq!(
+            (compress.levels.gzip, compress.levels.brotli, compress.levels.zstd),
+            (6, 4, 19)
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_levels_rejected() {
+        let config = |levels: &str| {
+            format!(
+                "entryPoints:\n  web:\n    address: \":80\"\nhttp:\n  middlewares:\n    compress:\n      compress:\n        levels: {}\n",
+                levels
+            )
+        };
+        assert!(Config::from_yaml(&config("{gzip: 9, brotli: 11, zstd: 22}")).is_ok());
+        for levels in ["{gzip: 10}", "{brotli: 12}", "{zstd: 0}", "{zstd: 23}"] {
+            let err = Config::from_yaml(&config(levels)).unwrap_err();
+            assert!(
+                format!("{:#}", err).contains("compression level"),
+                "{}: {:#}",
+                levels,
+                err
+            );
+        }
+    }
+
     #[test]
     fn test_should_compress_content_type() {
+        let compress = middleware(test_config());
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
-        assert!(CompressMiddleware::should_compress_content_type(&headers));
+        assert!(compress.should_compress_content_type(&headers));
 
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        assert!(CompressMiddleware::should_compress_content_type(&headers));
+        assert!(compress.should_compress_content_type(&headers));
 
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
-        assert!(!CompressMiddleware::should_compress_content_type(&headers));
+        assert!(!compress.should_compress_content_type(&headers));
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        assert!(!compress.should_compress_content_type(&headers));
+    }
+
+    #[test]
+    fn test_included_and_excluded_content_types() {
+        let compress = middleware(CompressConfig {
+            excluded_content_types: vec!["text/csv".to_string()],
+            ..test_config()
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/csv; charset=utf-8"));
+        assert!(!compress.should_compress_content_type(&headers));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(compress.should_compress_content_type(&headers));
+
+        let compress = middleware(CompressConfig {
+            included_content_types: vec!["application/wasm".to_string(), "font/*".to_string()],
+            ..test_config()
+        });
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/wasm"));
+        assert!(compress.should_compress_content_type(&headers));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("font/woff"));
+        assert!(compress.should_compress_content_type(&headers));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(!compress.should_compress_content_type(&headers));
+    }
+
+    #[tokio::test]
+    async fn test_compress_response_with_selected_encoding() {
+        let compress = middleware(with_min_size(10));
+        let text = "compress me please ".repeat(20);
+        let resp = response("text/plain", &text);
+        let resp = compress.compress_response(resp, CompressionAlgorithm::Gzip).await.unwrap();
+
+        assert_eq!(resp.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(resp.headers()[VARY], "Accept-Encoding");
+        let compressed = body(resp).await;
+        let mut decoded = String::new();
+        GzDecoder::new(compressed.as_ref()).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, text);
+    }
+
+    #[tokio::test]
+    async fn test_small_response_not_compressed() {
+        let compress = middleware(with_min_size(1024));
+        let resp = compress
+            .compress_response(response("text/plain", "tiny"), CompressionAlgorithm::Gzip)
+            .await
+            .unwrap();
+
+        assert!(resp.headers().get(CONTENT_ENCODING).is_none());
+        assert!(resp.headers().get(VARY).is_none());
+        assert_eq!(body(resp).await, "tiny");
+    }
+
+    #[tokio::test]
+    async fn test_excluded_content_type_not_compressed() {
+        let compress = middleware(CompressConfig {
+            excluded_content_types: vec!["application/json".to_string()],
+            ..with_min_size(10)
+        });
+        let json = format!("[{}]", "1,".repeat(100));
+        let resp = compress
+            .compress_response(response("application/json", &json), CompressionAlgorithm::Gzip)
+            .await
+            .unwrap();
+
+        assert!(resp.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(body(resp).await, json);
+    }
+
+    #[tokio::test]
+    async fn test_already_encoded_response_passes_through() {
+        let compress = middleware(with_min_size(10));
+        let mut resp = response("text/plain", "already brotli encoded bytes, honest");
+        resp.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+        let resp = compress.compress_response(resp, CompressionAlgorithm::Gzip).await.unwrap();
+
+        assert_eq!(resp.headers()[CONTENT_ENCODING], "br");
+        assert!(resp.headers().get(VARY).is_none());
+        assert_eq!(body(resp).await, "already brotli encoded bytes, honest");
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_response_still_varies() {
+        let compress = middleware(with_min_size(10));
+        let mut resp = response("text/plain", &"x".repeat(100));
+        resp.headers_mut().insert(VARY, HeaderValue::from_static("Origin"));
+        resp.headers_mut().insert(ETAG, HeaderValue::from_static("\"v1\""));
+
+        // The client accepted no configured encoding
+        let plain = compress.compress_response(resp, CompressionAlgorithm::None).await.unwrap();
+        let vary: Vec<_> = plain.headers().get_all(VARY).iter().collect();
+        assert_eq!(vary, ["Origin", "Accept-Encoding"]);
+        assert!(plain.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(plain.headers()[ETAG], "\"v1\"");
+
+        // The compressed representation gets a weak ETag
+        let mut resp = response("text/plain", &"x".repeat(100));
+        resp.headers_mut().insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let compressed = compress.compress_response(resp, CompressionAlgorithm::Zstd).await.unwrap();
+        assert_eq!(compressed.headers()[CONTENT_ENCODING], "zstd");
+        assert_eq!(compressed.headers()[ETAG], "W/\"v1\"");
+    }
+
+    #[tokio::test]
+    async fn test_chunked_response_over_cap_streams_uncompressed() {
+        let compress = middleware(CompressConfig { max_body_bytes: 1024, ..with_min_size(10) });
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<Frame<Bytes>, hyper::Error>>();
+        let chunk = Bytes::from("a".repeat(600));
+        tx.unbounded_send(Ok(Frame::data(chunk.clone()))).unwrap();
+        tx.unbounded_send(Ok(Frame::data(chunk.clone()))).unwrap();
+        let resp = Response::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .body(BodyExt::boxed(StreamBody::new(rx)))
+            .unwrap();
+
+        // The backend is still sending: the response comes back without waiting for the end
+        let resp = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            compress.compress_response(resp, CompressionAlgorithm::Gzip),
+        )
+        .await
+        .expect("compress_response waited for the whole body")
+        .unwrap();
+        assert!(resp.headers().get(CONTENT_ENCODING).is_none());
+        assert!(resp.headers().get(CONTENT_LENGTH).is_none());
+
+        tx.unbounded_send(Ok(Frame::data(Bytes::from_static(b"tail")))).unwrap();
+        drop(tx);
+        let expected = format!("{}{}tail", "a".repeat(600), "a".repeat(600));
+        assert_eq!(body(resp).await, expected);
     }
 }
@@ -6,11 +6,24 @@ use hyper::header::{
     VARY,
 };
 use hyper::{HeaderMap, Method, Request, Response, StatusCode};
+use parking_lot::Mutex;
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Most preflight decisions kept; the least recently used is evicted first
+const PREFLIGHT_CACHE_CAPACITY: usize = 1024;
+
+/// Vary value for preflight responses
+const PREFLIGHT_VARY: &str = "Origin, Access-Control-Request-Method, Access-Control-Request-Headers";
 
 /// CORS middleware for handling Cross-Origin Resource Sharing
 /// In Traefik, CORS is handled through the headers middleware
 pub struct CorsMiddleware {
     allowed_origins: Vec<String>,
+    /// Compiled once from accessControlAllowOriginListRegex
+    allowed_origin_regexes: Vec<Regex>,
     allow_all_origins: bool,
     allowed_methods: String,
     allowed_headers: String,
@@ -19,6 +32,7 @@ pub struct CorsMiddleware {
     allow_credentials: bool,
     max_age: Option<String>,
     add_vary_header: bool,
+    preflight_cache: PreflightCache,
 }
 
 impl CorsMiddleware {
@@ -60,10 +74,29 @@ impl CorsMiddleware {
             Some(config.access_control_expose_headers.join(", "))
         };
 
+        let allowed_origin_regexes = config
+            .access_control_allow_origin_list_regex
+            .iter()
+            .filter_map(|r| match Regex::new(r) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("Invalid CORS origin regex '{}': {}", r, e);
+                    None
+                }
+            })
+            .collect();
+
         let max_age = config.access_control_max_age.map(|v| v.to_string());
 
+        // Browsers cache a preflight for max-age seconds, so the decision can be reused as long
+        let preflight_ttl = config
+            .access_control_max_age
+            .filter(|&secs| secs > 0)
+            .map(|secs| Duration::from_secs(secs as u64));
+
         Some(Self {
             allowed_origins: config.access_control_allow_origin_list.clone(),
+            allowed_origin_regexes,
             allow_all_origins,
             allowed_methods,
             allowed_headers,
@@ -72,6 +105,7 @@ impl CorsMiddleware {
             allow_credentials: config.access_control_allow_credentials,
             max_age,
             add_vary_header: config.add_vary_header,
+            preflight_cache: PreflightCache::new(preflight_ttl),
         })
     }
 
@@ -90,6 +124,7 @@ impl CorsMiddleware {
             return true;
         }
         self.allowed_origins.iter().any(|o| o == origin)
+            || self.allowed_origin_regexes.iter().any(|r| r.is_match(origin))
     }
 
     /// Validate preflight request headers
@@ -118,26 +153,38 @@ impl CorsMiddleware {
         true
     }
 
-    /// Handle preflight request, returning a response
+    /// Handle preflight request, returning a response.
+    /// Identical preflights within max-age are answered from cache.
     pub fn handle_preflight<B>(&self, req: &Request<B>) -> Option<Response<()>> {
         let origin = req.headers().get(ORIGIN)?.to_str().ok()?;
+        let header_str = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let key = PreflightKey {
+            origin: origin.to_string(),
+            method: header_str(ACCESS_CONTROL_REQUEST_METHOD),
+            headers: header_str(ACCESS_CONTROL_REQUEST_HEADERS),
+        };
 
-        if !self.is_origin_allowed(origin) {
-            return Some(
-                Response::builder()
-                    .status(StatusCode::FORBIDDEN)
-                    .body(())
-                    .unwrap(),
-            );
+        if let Some(cached) = self.preflight_cache.get(&key) {
+            return Some(cached);
         }
 
-        if !self.validate_preflight(req) {
-            return Some(
-                Response::builder()
-                    .status(StatusCode::FORBIDDEN)
-                    .body(())
-                    .unwrap(),
-            );
+        let response = self.build_preflight(req, origin);
+        self.preflight_cache.insert(key, &response);
+        Some(response)
+    }
+
+    fn build_preflight<B>(&self, req: &Request<B>, origin: &str) -> Response<()> {
+        if !self.is_origin_allowed(origin) || !self.validate_preflight(req) {
+            return Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(())
+                .unwrap();
         }
 
         let mut builder = Response::builder().status(StatusCode::NO_CONTENT);
@@ -169,17 +216,19 @@ impl CorsMiddleware {
 
         // Add Vary header for caching
         if self.add_vary_header {
-            builder = builder.header(
-                VARY,
-                "Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
-            );
+            builder = builder.header(VARY, PREFLIGHT_VARY);
         }
 
-        Some(builder.body(()).unwrap())
+        builder.body(()).unwrap()
     }
 
     /// Apply CORS headers to a response
     pub fn apply_headers(&self, origin: Option<&str>, headers: &mut HeaderMap) {
+        // The response depends on Origin whether or not this one is allowed
+        if self.add_vary_header {
+            add_vary_origin(headers);
+        }
+
         let origin = match origin {
             Some(o) if self.is_origin_allowed(o) => o,
             _ => return,
@@ -209,11 +258,6 @@ impl CorsMiddleware {
             && let Ok(val) = HeaderValue::from_str(exposed) {
                 headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, val);
             }
-
-        // Add Vary header
-        if self.add_vary_header {
-            headers.insert(VARY, HeaderValue::from_static("Origin"));
-        }
     }
 
     /// Get origin from request headers
@@ -225,6 +269,109 @@ impl CorsMiddleware {
     }
 }
 
+/// Add `Origin` to Vary, keeping whatever the backend already varies on
+fn add_vary_origin(headers: &mut HeaderMap) {
+    let covered = headers.get_all(VARY).iter().any(|v| {
+        v.to_str().is_ok_and(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|token| token == "*" || token.eq_ignore_ascii_case("origin"))
+        })
+    });
+    if !covered {
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PreflightKey {
+    origin: String,
+    method: String,
+    headers: String,
+}
+
+struct CachedPreflight {
+    status: StatusCode,
+    headers: HeaderMap,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct PreflightEntries {
+    map: HashMap<PreflightKey, CachedPreflight>,
+    /// Incremented on every access to order entries by recency
+    clock: u64,
+}
+
+/// Preflight responses by (origin, method, requested headers), kept for max-age
+struct PreflightCache {
+    /// None disables caching (no max-age configured)
+    ttl: Option<Duration>,
+    entries: Mutex<PreflightEntries>,
+}
+
+impl PreflightCache {
+    fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(PreflightEntries::default()),
+        }
+    }
+
+    fn get(&self, key: &PreflightKey) -> Option<Response<()>> {
+        self.ttl?;
+        let mut entries = self.entries.lock();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        let entry = entries.map.get_mut(key)?;
+        if entry.expires_at <= Instant::now() {
+            entries.map.remove(key);
+            return None;
+        }
+        entry.last_used = clock;
+
+        let mut response = Response::new(());
+        *response.status_mut() = entry.status;
+        *response.headers_mut() = entry.headers.clone();
+        Some(response)
+    }
+
+    fn insert(&self, key: PreflightKey, response: &Response<()>) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let mut entries = self.entries.lock();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        if entries.map.len() >= PREFLIGHT_CACHE_CAPACITY && !entries.map.contains_key(&key) {
+            let now = Instant::now();
+            entries.map.retain(|_, e| e.expires_at > now);
+            if entries.map.len() >= PREFLIGHT_CACHE_CAPACITY
+                && let Some(oldest) = entries
+                    .map
+                    .iter()
+                    .min_by_key(|(_, e)| e.last_used)
+                    .map(|(k, _)| k.clone())
+            {
+                entries.map.remove(&oldest);
+            }
+        }
+
+        entries.map.insert(
+            key,
+            CachedPreflight {
+                status: response.status(),
+                headers: response.headers().clone(),
+                expires_at: Instant::now() + ttl,
+                last_used: clock,
+            },
+        );
+    }
+}
+
 /// Check if a header is a CORS-safelisted header
 fn is_simple_header(header: &str) -> bool {
     matches!(
@@ -338,4 +485,91 @@ mod tests {
             .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
             .is_some());
     }
+
+    fn preflight(origin: &str, method: &str) -> Request<()> {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_regex_origin_match() {
+        let mut config = test_config();
+        config.access_control_allow_origin_list_regex =
+            vec![r"^https://[a-z0-9-]+\.example\.org$".to_string(), "(".to_string()];
+        let cors = CorsMiddleware::from_headers_config(&config).unwrap();
+
+        assert!(cors.is_origin_allowed("https://app.example.org"));
+        assert!(cors.is_origin_allowed("https://example.com"));
+        assert!(!cors.is_origin_allowed("https://app.example.org.evil.com"));
+        assert!(!cors.is_origin_allowed("http://app.example.org"));
+
+        let response = cors.handle_preflight(&preflight("https://app.example.org", "POST")).unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://app.example.org"
+        );
+    }
+
+    #[test]
+    fn test_identical_preflight_served_from_cache() {
+        let cors = CorsMiddleware::from_headers_config(&test_config()).unwrap();
+        let first = cors.handle_preflight(&preflight("https://example.com", "POST")).unwrap();
+        assert_eq!(first.status(), StatusCode::NO_CONTENT);
+        assert_eq!(cors.preflight_cache.entries.lock().map.len(), 1);
+
+        // Mark the cached entry: only a cache hit can return the marker
+        for entry in cors.preflight_cache.entries.lock().map.values_mut() {
+            entry.headers.insert("x-cached", HeaderValue::from_static("1"));
+        }
+        let second = cors.handle_preflight(&preflight("https://example.com", "POST")).unwrap();
+        assert_eq!(second.headers().get("x-cached").unwrap(), "1");
+        assert_eq!(second.headers().get(ACCESS_CONTROL_MAX_AGE).unwrap(), "86400");
+
+        // A different method is a different key, and denials are cached too
+        let denied = cors.handle_preflight(&preflight("https://example.com", "DELETE")).unwrap();
+        assert_eq!(denied.status(), StatusCode::FORBIDDEN);
+        assert_eq!(cors.preflight_cache.entries.lock().map.len(), 2);
+
+        // Without max-age nothing is cached
+        let mut config = test_config();
+        config.access_control_max_age = None;
+        let uncached = CorsMiddleware::from_headers_config(&config).unwrap();
+        uncached.handle_preflight(&preflight("https://example.com", "POST")).unwrap();
+        assert!(uncached.preflight_cache.entries.lock().map.is_empty());
+    }
+
+    #[test]
+    fn test_vary_header_is_merged() {
+        let cors = CorsMiddleware::from_headers_config(&test_config()).unwrap();
+
+        let response = cors.handle_preflight(&preflight("https://example.com", "POST")).unwrap();
+        assert_eq!(response.headers().get(VARY).unwrap(), PREFLIGHT_VARY);
+
+        // The backend's Vary is kept and Origin added once
+        let mut headers = HeaderMap::new();
+        headers.insert(VARY, HeaderValue::from_static("Accept-Encoding"));
+        cors.apply_headers(Some("https://example.com"), &mut headers);
+        cors.apply_headers(Some("https://example.com"), &mut headers);
+        let vary: Vec<_> = headers.get_all(VARY).iter().collect();
+        assert_eq!(vary, ["Accept-Encoding", "Origin"]);
+
+        // Disallowed origins still vary, so caches never serve them an allowed response
+        let mut headers = HeaderMap::new();
+        cors.apply_headers(Some("https://evil.com"), &mut headers);
+        assert_eq!(headers.get(VARY).unwrap(), "Origin");
+        assert!(headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+
+        let mut config = test_config();
+        config.add_vary_header = false;
+        let no_vary = CorsMiddleware::from_headers_config(&config).unwrap();
+        let mut headers = HeaderMap::new();
+        no_vary.apply_headers(Some("https://example.com"), &mut headers);
+        assert!(headers.get(VARY).is_none());
+    }
 }
@@ -0,0 +1,287 @@
+//! Decompression of gzip, deflate, brotli and zstd request bodies.
+
+use crate::config::DecompressRequestConfig;
+use crate::middleware::BufferedRequestBody;
+use bytes::{Bytes, BytesMut};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
+use hyper::{Request, StatusCode};
+use std::io::Read;
+use thiserror::Error;
+
+/// A content coding the middleware can decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestEncoding {
+    /// `gzip` / `x-gzip`
+    Gzip,
+    /// `deflate` (zlib-wrapped, or raw as some clients send it)
+    Deflate,
+    /// `br`
+    Brotli,
+    /// `zstd`
+    Zstd,
+}
+
+impl RequestEncoding {
+    fn parse(token: &str) -> Option<Self> {
+        match token.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            "br" => Some(Self::Brotli),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Why a request body could not be decompressed
+#[derive(Debug, Error)]
+pub enum DecompressError {
+    /// The compressed or decompressed body exceeds the configured limit
+    #[error("request body exceeds {0} bytes")]
+    TooLarge(usize),
+    /// A content coding the middleware does not support
+    #[error("unsupported content encoding '{0}'")]
+    Unsupported(String),
+    /// The body could not be read or is not validly encoded
+    #[error("invalid request body: {0}")]
+    Invalid(String),
+}
+
+impl DecompressError {
+    /// Status code to reject the request with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Unsupported(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// Decompresses request bodies so backends receive them unencoded
+pub struct DecompressRequestMiddleware {
+    max_bytes: usize,
+}
+
+impl DecompressRequestMiddleware {
+    /// Create from config.
+    pub fn new(config: &DecompressRequestConfig) -> Self {
+        Self {
+            max_bytes: usize::try_from(config.max_decompressed_bytes).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Codings applied to the body, in the order they were applied.
+    /// `None` when the body is not encoded.
+    pub fn encodings(&self, headers: &HeaderMap) -> Result<Option<Vec<RequestEncoding>>, DecompressError> {
+        let mut encodings = Vec::new();
+        for value in headers.get_all(CONTENT_ENCODING) {
+            let value = value
+                .to_str()
+                .map_err(|_| DecompressError::Unsupported(String::from_utf8_lossy(value.as_bytes()).into_owned()))?;
+            for token in value.split(',').map(str::trim) {
+                if token.is_empty() || token.eq_ignore_ascii_case("identity") {
+                    continue;
+                }
+                let encoding = RequestEncoding::parse(token)
+                    .ok_or_else(|| DecompressError::Unsupported(token.to_string()))?;
+                encodings.push(encoding);
+            }
+        }
+        Ok((!encodings.is_empty()).then_some(encodings))
+    }
+
+    /// Decode a body, undoing `encodings` last to first
+    pub fn decompress(&self, data: &[u8], encodings: &[RequestEncoding]) -> Result<Vec<u8>, DecompressError> {
+        let mut current = data.to_vec();
+        for &encoding in encodings.iter().rev() {
+            current = self.decode(&current, encoding)?;
+        }
+        Ok(current)
+    }
+
+    fn decode(&self, data: &[u8], encoding: RequestEncoding) -> Result<Vec<u8>, DecompressError> {
+        match encoding {
+            RequestEncoding::Gzip => self.read_limited(GzDecoder::new(data)),
+            RequestEncoding::Deflate => self
+                .read_limited(ZlibDecoder::new(data))
+                .or_else(|e| match e {
+                    DecompressError::Invalid(_) => self.read_limited(DeflateDecoder::new(data)),
+                    other => Err(other),
+                }),
+            RequestEncoding::Brotli => self.read_limited(brotli::Decompressor::new(data, 4096)),
+            RequestEncoding::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(data)
+                    .map_err(|e| DecompressError::Invalid(e.to_string()))?;
+                self.read_limited(decoder)
+            }
+        }
+    }
+
+    /// Read a decoder to the end, stopping one byte past the limit so a
+    /// decompression bomb is never inflated in full
+    fn read_limited(&self, reader: impl Read) -> Result<Vec<u8>, DecompressError> {
+        let mut out = Vec::new();
+        reader
+            .take(self.max_bytes as u64 + 1)
+            .read_to_end(&mut out)
+            .map_err(|e| DecompressError::Invalid(e.to_string()))?;
+        if out.len() > self.max_bytes {
+            return Err(DecompressError::TooLarge(self.max_bytes));
+        }
+        Ok(out)
+    }
+
+    /// Drain and decompress an encoded request body. On success the decoded body
+    /// is attached as a [`BufferedRequestBody`] and the headers describe it;
+    /// requests without a content coding are left untouched.
+    pub async fn decompress_request<B>(&self, req: &mut Request<B>) -> Result<(), DecompressError>
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: std::fmt::Display,
+    {
+        let Some(encodings) = self.encodings(req.headers())? else {
+            return Ok(());
+        };
+
+        let mut compressed = BytesMut::new();
+        while let Some(frame) = req.body_mut().frame().await {
+            let frame = frame.map_err(|e| DecompressError::Invalid(e.to_string()))?;
+            if let Ok(data) = frame.into_data() {
+                if compressed.len() + data.len() > self.max_bytes {
+                    return Err(DecompressError::TooLarge(self.max_bytes));
+                }
+                compressed.extend_from_slice(&data);
+            }
+        }
+
+        let limits = Self { max_bytes: self.max_bytes };
+        let decoded = tokio::task::spawn_blocking(move || limits.decompress(&compressed, &encodings))
+            .await
+            .map_err(|e| DecompressError::Invalid(e.to_string()))??;
+
+        let headers = req.headers_mut();
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(TRANSFER_ENCODING);
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(decoded.len()));
+        req.extensions_mut().insert(BufferedRequestBody(Bytes::from(decoded)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use http_body_util::Full;
+    use std::io::Write;
+
+    const PAYLOAD: &[u8] = b"{\"items\":[1,2,3],\"note\":\"compressed request body\"}";
+
+    fn middleware(max_bytes: u64) -> DecompressRequestMiddleware {
+        DecompressRequestMiddleware::new(&DecompressRequestConfig {
+            max_decompressed_bytes: max_bytes,
+        })
+    }
+
+    fn encode(encoding: RequestEncoding, data: &[u8]) -> Vec<u8> {
+        match encoding {
+            RequestEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            RequestEncoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            RequestEncoding::Brotli => {
+                let mut out = Vec::new();
+                let mut writer = brotli::CompressorWriter::new(&mut out, 4096, 4, 22);
+                writer.write_all(data).unwrap();
+                drop(writer);
+                out
+            }
+            RequestEncoding::Zstd => zstd::encode_all(data, 3).unwrap(),
+        }
+    }
+
+    fn request(content_encoding: Option<&str>, body: Vec<u8>) -> Request<Full<Bytes>> {
+        let mut builder = Request::builder().method("POST").header(CONTENT_LENGTH, body.len());
+        if let Some(encoding) = content_encoding {
+            builder = builder.header(CONTENT_ENCODING, encoding);
+        }
+        builder.body(Full::new(Bytes::from(body))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_decompresses_each_encoding() {
+        let mw = middleware(1024);
+        for (name, encoding) in [
+            ("gzip", RequestEncoding::Gzip),
+            ("deflate", RequestEncoding::Deflate),
+            ("br", RequestEncoding::Brotli),
+            ("zstd", RequestEncoding::Zstd),
+        ] {
+            let mut req = request(Some(name), encode(encoding, PAYLOAD));
+            mw.decompress_request(&mut req).await.unwrap();
+
+            assert!(req.headers().get(CONTENT_ENCODING).is_none(), "{}", name);
+            assert_eq!(req.headers().get(CONTENT_LENGTH).unwrap(), &PAYLOAD.len().to_string());
+            let body = req.extensions().get::<BufferedRequestBody>().unwrap();
+            assert_eq!(body.0.as_ref(), PAYLOAD, "{}", name);
+        }
+
+        // Stacked codings are undone in reverse order
+        let stacked = encode(RequestEncoding::Brotli, &encode(RequestEncoding::Gzip, PAYLOAD));
+        let mut req = request(Some("gzip, br"), stacked);
+        mw.decompress_request(&mut req).await.unwrap();
+        assert_eq!(req.extensions().get::<BufferedRequestBody>().unwrap().0.as_ref(), PAYLOAD);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bodies_over_the_limit() {
+        let mw = middleware(1024);
+        let bomb = encode(RequestEncoding::Gzip, &vec![b'a'; 256 * 1024]);
+        assert!(bomb.len() < 1024);
+
+        let err = mw.decompress_request(&mut request(Some("gzip"), bomb)).await.unwrap_err();
+        assert!(matches!(err, DecompressError::TooLarge(1024)));
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let err = middleware(8)
+            .decompress_request(&mut request(Some("gzip"), encode(RequestEncoding::Gzip, PAYLOAD)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_unencoded_body_passes_through() {
+        let mw = middleware(1024);
+        for encoding in [None, Some("identity")] {
+            let mut req = request(encoding, PAYLOAD.to_vec());
+            mw.decompress_request(&mut req).await.unwrap();
+            assert!(req.extensions().get::<BufferedRequestBody>().is_none());
+            let body = req.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body.as_ref(), PAYLOAD);
+        }
+
+        let err = mw
+            .decompress_request(&mut request(Some("compress"), PAYLOAD.to_vec()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let err = mw
+            .decompress_request(&mut request(Some("gzip"), PAYLOAD.to_vec()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,5 +1,11 @@
 use crate::config::ErrorsConfig;
+use crate::middleware::Endpoint;
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{HeaderMap, Response};
 use std::ops::RangeInclusive;
+use tracing::debug;
 
 /// Errors middleware - intercepts error responses and serves custom error pages
 pub struct ErrorsMiddleware {
@@ -86,6 +92,46 @@ impl ErrorsMiddleware {
     pub fn query_template(&self) -> &str {
         &self.query
     }
+
+    /// Replace an intercepted response with the error page from the error service,
+    /// keeping its status. The response passes through unchanged when its status
+    /// isn't intercepted or the error service doesn't answer with a 2xx page.
+    pub async fn serve_error_page(
+        &self,
+        response: Response<BoxBody<Bytes, hyper::Error>>,
+        endpoint: &dyn Endpoint,
+        headers: &HeaderMap,
+    ) -> Response<BoxBody<Bytes, hyper::Error>> {
+        let status = response.status();
+        if !self.should_intercept(status.as_u16()) {
+            return response;
+        }
+
+        let query = self.build_query(status.as_u16());
+        let page = match endpoint.fetch(&self.service, &query, headers).await {
+            Some(page) if page.status().is_success() => page,
+            Some(page) => {
+                debug!(
+                    "Error page {} from service '{}' answered {}, keeping original response",
+                    query, self.service, page.status()
+                );
+                return response;
+            }
+            None => return response,
+        };
+
+        // Keep the original status, headers (cookies, request ID) and extensions;
+        // only the headers describing the body come from the page
+        let (mut parts, _) = response.into_parts();
+        let (mut page_parts, body) = page.into_parts();
+        for name in [CONTENT_TYPE, CONTENT_LENGTH, CONTENT_ENCODING] {
+            match page_parts.headers.remove(&name) {
+                Some(value) => parts.headers.insert(name, value),
+                None => parts.headers.remove(name),
+            };
+        }
+        Response::from_parts(parts, body)
+    }
 }
 
 #[cfg(test)]
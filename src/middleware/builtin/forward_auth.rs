@@ -1,11 +1,26 @@
 use crate::config::ForwardAuthConfig;
-use hyper::header::{HeaderMap, HeaderName, AUTHORIZATION, COOKIE};
+use crate::middleware::RequestContext;
+use bytes::Bytes;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, SET_COOKIE};
 use hyper::{Method, Request, StatusCode};
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use reqwest::Client;
 use std::time::Duration;
 use tracing::{debug, warn};
 
+/// Auth response headers never copied back to the client on a denial
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-length",
+];
+
 /// ForwardAuth middleware delegates authentication to an external service
 pub struct ForwardAuthMiddleware {
     client: Client,
@@ -31,10 +46,11 @@ impl ForwardAuthMiddleware {
             .filter_map(|h| HeaderName::try_from(h.as_str()).ok())
             .collect();
 
+        // Header names are compared in their lowercase wire form, so match case-insensitively
         let auth_response_headers_regex = config
             .auth_response_headers_regex
             .as_ref()
-            .and_then(|r| Regex::new(r).ok());
+            .and_then(|r| RegexBuilder::new(r).case_insensitive(true).build().ok());
 
         let auth_request_headers: Vec<HeaderName> = config
             .auth_request_headers
@@ -54,64 +70,53 @@ impl ForwardAuthMiddleware {
     }
 
     /// Check authentication by calling the external auth service
-    /// Returns Ok with headers to forward, or Err with status code and body
-    pub async fn authenticate<B>(
-        &self,
-        req: &Request<B>,
-    ) -> Result<AuthResult, (StatusCode, String)> {
-        // Build the auth request
-        let mut auth_req = self
-            .client
-            .request(Method::GET, &self.address);
-
-        // Forward specific headers
+    /// Returns Ok with headers to forward, or Err with the response to send the client
+    pub async fn authenticate<B>(&self, req: &Request<B>) -> Result<AuthResult, AuthDenied> {
         let original_headers = req.headers();
+        let mut auth_headers = HeaderMap::new();
 
         // Always forward these headers if present
-        if let Some(auth) = original_headers.get(AUTHORIZATION)
-            && let Ok(v) = auth.to_str() {
-                auth_req = auth_req.header(AUTHORIZATION.as_str(), v);
-            }
-
-        if let Some(cookie) = original_headers.get(COOKIE)
-            && let Ok(v) = cookie.to_str() {
-                auth_req = auth_req.header(COOKIE.as_str(), v);
+        for name in [AUTHORIZATION, COOKIE] {
+            if let Some(value) = original_headers.get(&name) {
+                auth_headers.insert(name, value.clone());
             }
+        }
 
         // Forward configured request headers
         for header_name in &self.auth_request_headers {
-            if let Some(value) = original_headers.get(header_name)
-                && let Ok(v) = value.to_str() {
-                    auth_req = auth_req.header(header_name.as_str(), v);
-                }
-        }
-
-        // Forward X-Forwarded-* headers if trusted
-        if self.trust_forward_header {
-            for (name, value) in original_headers.iter() {
-                if name.as_str().starts_with("x-forwarded-")
-                    && let Ok(v) = value.to_str() {
-                        auth_req = auth_req.header(name.as_str(), v);
-                    }
+            if let Some(value) = original_headers.get(header_name) {
+                auth_headers.insert(header_name.clone(), value.clone());
             }
         }
 
-        // Add request info headers
-        auth_req = auth_req
-            .header("X-Forwarded-Method", req.method().as_str())
-            .header("X-Forwarded-Uri", req.uri().to_string());
-
-        if let Some(host) = original_headers.get("host")
-            && let Ok(v) = host.to_str() {
-                auth_req = auth_req.header("X-Forwarded-Host", v);
-            }
+        // Describe the original request; trusted X-Forwarded-* values from upstream win
+        for (name, value) in self.forwarded_headers(req) {
+            let trusted = self
+                .trust_forward_header
+                .then(|| original_headers.get(&name))
+                .flatten();
+            match trusted {
+                Some(existing) => auth_headers.insert(name, existing.clone()),
+                None => auth_headers.insert(name, value),
+            };
+        }
 
         // Send the request
-        let response = match auth_req.send().await {
+        let response = match self
+            .client
+            .request(Method::GET, &self.address)
+            .headers(auth_headers)
+            .send()
+            .await
+        {
             Ok(r) => r,
             Err(e) => {
                 warn!("Forward auth request failed: {}", e);
-                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Auth service unavailable".to_string()));
+                return Err(AuthDenied {
+                    status: StatusCode::INTERNAL_SERVER_ERROR,
+                    headers: HeaderMap::new(),
+                    body: Bytes::from_static(b"Auth service unavailable"),
+                });
             }
         };
 
@@ -121,32 +126,24 @@ impl ForwardAuthMiddleware {
             // Auth succeeded - extract headers to forward
             let response_headers = response.headers();
             let mut forward_headers = HeaderMap::new();
-            let mut cookies_to_add = Vec::new();
 
-            // Copy explicitly configured headers
-            for header_name in &self.auth_response_headers {
-                if let Some(value) = response_headers.get(header_name) {
-                    forward_headers.insert(header_name.clone(), value.clone());
-                }
-            }
-
-            // Copy headers matching regex
-            if let Some(ref regex) = self.auth_response_headers_regex {
-                for (name, value) in response_headers.iter() {
-                    if regex.is_match(name.as_str()) {
-                        forward_headers.insert(name.clone(), value.clone());
-                    }
+            for (name, value) in response_headers.iter() {
+                if self.is_auth_response_header(name) {
+                    forward_headers.append(name.clone(), value.clone());
                 }
             }
 
             // Extract cookies to add to response
-            for cookie_name in &self.add_auth_cookies_to_response {
-                if let Some(set_cookie) = response_headers.get("set-cookie")
-                    && let Ok(v) = set_cookie.to_str()
-                        && v.starts_with(cookie_name) {
-                            cookies_to_add.push(v.to_string());
-                        }
-            }
+            let cookies_to_add: Vec<String> = response_headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .filter(|v| {
+                    let name = v.split_once('=').map_or("", |(name, _)| name.trim());
+                    self.add_auth_cookies_to_response.iter().any(|c| c == name)
+                })
+                .map(str::to_string)
+                .collect();
 
             debug!("Forward auth succeeded, forwarding {} headers", forward_headers.len());
 
@@ -155,15 +152,97 @@ impl ForwardAuthMiddleware {
                 cookies_to_response: cookies_to_add,
             })
         } else {
-            // Auth failed - return the error response
-            let body = response.text().await.unwrap_or_default();
+            // Auth failed - hand the auth server's response back to the client
+            let mut headers = HeaderMap::new();
+            for (name, value) in response.headers().iter() {
+                if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+                    headers.append(name.clone(), value.clone());
+                }
+            }
+            let body = response.bytes().await.unwrap_or_default();
             debug!("Forward auth failed with status {}", status);
-            Err((status, body))
+            Err(AuthDenied {
+                status,
+                headers,
+                body,
+            })
+        }
+    }
+
+    /// Replace the copied auth response headers on the upstream request.
+    ///
+    /// Every header the auth response could have supplied is removed first, so a
+    /// client can't smuggle e.g. `X-User-Id` past an auth server that didn't set it.
+    pub fn apply_to_request(&self, result: &AuthResult, headers: &mut HeaderMap) {
+        let spoofable: Vec<HeaderName> = headers
+            .keys()
+            .filter(|name| self.is_auth_response_header(name))
+            .cloned()
+            .collect();
+        for name in spoofable {
+            headers.remove(name);
+        }
+
+        for (name, value) in result.headers_to_request.iter() {
+            headers.append(name.clone(), value.clone());
         }
     }
+
+    fn is_auth_response_header(&self, name: &HeaderName) -> bool {
+        self.auth_response_headers.contains(name)
+            || self
+                .auth_response_headers_regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(name.as_str()))
+    }
+
+    /// X-Forwarded-* headers describing the original request
+    fn forwarded_headers<B>(&self, req: &Request<B>) -> Vec<(HeaderName, HeaderValue)> {
+        let context = req.extensions().get::<RequestContext>();
+        let proto = match context {
+            Some(ctx) if ctx.is_tls => "https",
+            Some(_) => "http",
+            None => req.uri().scheme_str().unwrap_or("http"),
+        };
+        let uri = req.uri().path_and_query().map_or("/", |pq| pq.as_str());
+
+        let mut headers = vec![
+            (
+                HeaderName::from_static("x-forwarded-method"),
+                HeaderValue::from_str(req.method().as_str()).ok(),
+            ),
+            (
+                HeaderName::from_static("x-forwarded-proto"),
+                HeaderValue::from_str(proto).ok(),
+            ),
+            (
+                HeaderName::from_static("x-forwarded-uri"),
+                HeaderValue::from_str(uri).ok(),
+            ),
+            (
+                HeaderName::from_static("x-forwarded-host"),
+                req.headers()
+                    .get(hyper::header::HOST)
+                    .cloned()
+                    .or_else(|| req.uri().authority().and_then(|a| HeaderValue::from_str(a.as_str()).ok())),
+            ),
+        ];
+        if let Some(ctx) = context {
+            headers.push((
+                HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(&ctx.remote_addr.ip().to_string()).ok(),
+            ));
+        }
+
+        headers
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value?)))
+            .collect()
+    }
 }
 
 /// Result of successful authentication
+#[derive(Debug)]
 pub struct AuthResult {
     /// Headers to add to the request before forwarding to backend
     pub headers_to_request: HeaderMap,
@@ -171,9 +250,164 @@ pub struct AuthResult {
     pub cookies_to_response: Vec<String>,
 }
 
+/// Auth server rejection (or failure), relayed to the client as-is
+#[derive(Debug)]
+pub struct AuthDenied {
+    /// Status returned by the auth server
+    pub status: StatusCode,
+    /// Auth server response headers, minus hop-by-hop headers (includes Set-Cookie)
+    pub headers: HeaderMap,
+    /// Auth server response body
+    pub body: Bytes,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use http_body_util::Full;
+    use hyper::body::Incoming;
+    use hyper::service::service_fn;
+    use hyper::Response;
+    use hyper_util::rt::TokioIo;
+    use parking_lot::Mutex;
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Auth server: `/allow` answers 200 with identity headers and cookies, anything
+    /// else 401 with a login challenge. Records the headers of the last request.
+    async fn auth_server() -> (String, Arc<Mutex<HeaderMap>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let seen = Arc::new(Mutex::new(HeaderMap::new()));
+        let recorded = Arc::clone(&seen);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let seen = Arc::clone(&seen);
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| {
+                        *seen.lock() = req.headers().clone();
+                        let response = if req.uri().path() == "/allow" {
+                            Response::builder()
+                                .header("X-User-Id", "42")
+                                .header("X-Auth-Role", "admin")
+                                .header("X-Auth-Tenant", "acme")
+                                .header("X-Internal", "secret")
+                                .header("Set-Cookie", "session=abc; Path=/")
+                                .header("Set-Cookie", "sessionid=nope")
+                                .body(Full::new(Bytes::new()))
+                        } else {
+                            Response::builder()
+                                .status(StatusCode::UNAUTHORIZED)
+                                .header("WWW-Authenticate", "Bearer realm=\"app\"")
+                                .header("Set-Cookie", "csrf=xyz; HttpOnly")
+                                .body(Full::new(Bytes::from("login required")))
+                        };
+                        async move { Ok::<_, Infallible>(response.unwrap()) }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (format!("http://{}", addr), recorded)
+    }
+
+    fn test_config(address: String) -> ForwardAuthConfig {
+        ForwardAuthConfig {
+            address,
+            trust_forward_header: false,
+            auth_response_headers: vec![],
+            auth_response_headers_regex: None,
+            auth_request_headers: vec![],
+            tls: None,
+            add_auth_cookies_to_response: vec![],
+        }
+    }
+
+    fn client_request() -> Request<()> {
+        Request::builder()
+            .method("POST")
+            .uri("/orders?id=7")
+            .header("host", "app.example.com")
+            .header("authorization", "Bearer token")
+            .header("x-forwarded-host", "upstream.example.com")
+            .header("x-user-id", "spoofed")
+            .header("x-auth-role", "spoofed")
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_allow_copies_listed_headers_and_cookies() {
+        let (address, seen) = auth_server().await;
+        let auth = ForwardAuthMiddleware::new(ForwardAuthConfig {
+            auth_response_headers: vec!["X-User-Id".to_string()],
+            add_auth_cookies_to_response: vec!["session".to_string()],
+            ..test_config(format!("{}/allow", address))
+        })
+        .unwrap();
+        let mut req = client_request();
+
+        let result = auth.authenticate(&req).await.unwrap();
+        assert_eq!(result.cookies_to_response, vec!["session=abc; Path=/".to_string()]);
+
+        auth.apply_to_request(&result, req.headers_mut());
+        assert_eq!(req.headers()["x-user-id"], "42");
+        assert!(req.headers().get("x-internal").is_none());
+        // Not selected for copyback, so the client's value is left alone
+        assert_eq!(req.headers()["x-auth-role"], "spoofed");
+
+        // Untrusted X-Forwarded-* values are replaced with the real request's
+        let seen = seen.lock();
+        assert_eq!(seen["authorization"], "Bearer token");
+        assert_eq!(seen["x-forwarded-method"], "POST");
+        assert_eq!(seen["x-forwarded-uri"], "/orders?id=7");
+        assert_eq!(seen["x-forwarded-host"], "app.example.com");
+        assert_eq!(seen["x-forwarded-proto"], "http");
+    }
+
+    #[tokio::test]
+    async fn test_deny_relays_status_body_and_cookies() {
+        let (address, _) = auth_server().await;
+        let auth = ForwardAuthMiddleware::new(ForwardAuthConfig {
+            auth_response_headers: vec!["X-User-Id".to_string()],
+            ..test_config(format!("{}/deny", address))
+        })
+        .unwrap();
+
+        let denied = auth.authenticate(&client_request()).await.unwrap_err();
+        assert_eq!(denied.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(denied.body, Bytes::from("login required"));
+        assert_eq!(denied.headers[SET_COOKIE], "csrf=xyz; HttpOnly");
+        assert_eq!(denied.headers["www-authenticate"], "Bearer realm=\"app\"");
+        assert!(denied.headers.get("content-length").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_regex_selects_headers_case_insensitively() {
+        let (address, seen) = auth_server().await;
+        let auth = ForwardAuthMiddleware::new(ForwardAuthConfig {
+            auth_response_headers_regex: Some("^X-Auth-".to_string()),
+            trust_forward_header: true,
+            ..test_config(format!("{}/allow", address))
+        })
+        .unwrap();
+        let mut req = client_request();
+
+        let result = auth.authenticate(&req).await.unwrap();
+        auth.apply_to_request(&result, req.headers_mut());
+        assert_eq!(req.headers()["x-auth-role"], "admin");
+        assert_eq!(req.headers()["x-auth-tenant"], "acme");
+        assert_eq!(req.headers()["x-user-id"], "spoofed");
+        assert!(req.headers().get("x-internal").is_none());
+        assert!(result.cookies_to_response.is_empty());
+
+        // Trusted upstream X-Forwarded-* values are passed through
+        assert_eq!(seen.lock()["x-forwarded-host"], "upstream.example.com");
+    }
 
     #[test]
     fn test_forward_auth_creation() {
@@ -0,0 +1,255 @@
+//! GeoIP country filtering and location headers backed by a MaxMind `.mmdb` database.
+
+use crate::config::GeoIpConfig;
+use crate::middleware::ip_strategy::ClientIpResolver;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+/// Request header carrying the client's ISO country code
+pub const COUNTRY_HEADER: HeaderName = HeaderName::from_static("x-geoip-country");
+/// Request header carrying the client's city name (English)
+pub const CITY_HEADER: HeaderName = HeaderName::from_static("x-geoip-city");
+
+/// Location resolved for a client IP
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoLocation {
+    /// ISO 3166-1 alpha-2 country code
+    pub country: Option<String>,
+    /// English city name (City databases only)
+    pub city: Option<String>,
+}
+
+/// GeoIP middleware: rejects requests by origin country and annotates the rest
+pub struct GeoIpMiddleware {
+    reader: Reader<Vec<u8>>,
+    /// Uppercased ISO codes; empty means every country is allowed
+    allow_countries: HashSet<String>,
+    deny_countries: HashSet<String>,
+    add_headers: bool,
+    client_ip: ClientIpResolver,
+}
+
+impl GeoIpMiddleware {
+    /// Create from config, reading the whole database into memory.
+    pub fn new(config: &GeoIpConfig) -> Result<Self, MaxMindDBError> {
+        let reader = Reader::open_readfile(&config.mmdb_path)?;
+        Ok(Self::with_reader(reader, config))
+    }
+
+    fn with_reader(reader: Reader<Vec<u8>>, config: &GeoIpConfig) -> Self {
+        let normalize = |codes: &[String]| codes.iter().map(|c| c.to_ascii_uppercase()).collect();
+
+        Self {
+            reader,
+            allow_countries: normalize(&config.allow_countries),
+            deny_countries: normalize(&config.deny_countries),
+            add_headers: config.add_headers,
+            client_ip: ClientIpResolver::new(config.ip_strategy.as_ref()),
+        }
+    }
+
+    /// Client IP to look up, per the configured `ipStrategy`
+    pub fn get_client_ip(&self, forwarded_for: Option<&str>, remote_addr: IpAddr) -> IpAddr {
+        self.client_ip.resolve(forwarded_for, remote_addr)
+    }
+
+    /// Look up an IP; addresses missing from the database resolve to an empty location
+    pub fn lookup(&self, ip: IpAddr) -> GeoLocation {
+        // City records are a superset of Country records, so this reads either database
+        let Ok(record) = self.reader.lookup::<geoip2::City>(ip) else {
+            return GeoLocation::default();
+        };
+
+        GeoLocation {
+            country: record
+                .country
+                .and_then(|c| c.iso_code)
+                .map(str::to_string),
+            city: record
+                .city
+                .and_then(|c| c.names)
+                .and_then(|names| names.get("en").map(|n| n.to_string())),
+        }
+    }
+
+    /// Check if a country may pass. With an allow list, unknown origins are rejected.
+    pub fn is_allowed(&self, country: Option<&str>) -> bool {
+        let country = country.map(str::to_ascii_uppercase);
+        if let Some(ref code) = country
+            && self.deny_countries.contains(code)
+        {
+            return false;
+        }
+
+        self.allow_countries.is_empty()
+            || country.is_some_and(|code| self.allow_countries.contains(&code))
+    }
+
+    /// Replace any client-supplied GeoIP headers with the resolved location
+    pub fn apply_headers(&self, location: &GeoLocation, headers: &mut HeaderMap) {
+        if !self.add_headers {
+            return;
+        }
+
+        for (name, value) in [(COUNTRY_HEADER, &location.country), (CITY_HEADER, &location.city)] {
+            headers.remove(&name);
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IpStrategy;
+
+    /// Encode an MMDB data-section value: `kind` is the MMDB type number
+    fn value(kind: u8, payload: &[u8], size: usize) -> Vec<u8> {
+        assert!(size < 29);
+        let mut out = if kind <= 7 {
+            vec![(kind << 5) | size as u8]
+        } else {
+            vec![size as u8, kind - 7]
+        };
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        value(2, s.as_bytes(), s.len())
+    }
+
+    fn uint(kind: u8, v: u64, bytes: usize) -> Vec<u8> {
+        value(kind, &v.to_be_bytes()[8 - bytes..], bytes)
+    }
+
+    fn map(entries: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+        let mut out = value(7, &[], entries.len());
+        for (key, v) in entries {
+            out.extend(string(key));
+            out.extend(v);
+        }
+        out
+    }
+
+    fn record(country: &str, city: Option<&str>) -> Vec<u8> {
+        let mut fields = vec![("country", map(vec![("iso_code", string(country))]))];
+        if let Some(city) = city {
+            fields.push(("city", map(vec![("names", map(vec![("en", string(city))]))])));
+        }
+        map(fields)
+    }
+
+    /// IPv4 database with three nodes:
+    /// 64.0.0.0/2 is Berlin, DE; 128.0.0.0/2 is US with no city; the rest is unknown
+    fn test_database() -> Reader<Vec<u8>> {
+        const NODE_COUNT: u32 = 3;
+        let germany = record("DE", Some("Berlin"));
+        let us = record("US", None);
+        let data_pointer = |offset: usize| NODE_COUNT + 16 + offset as u32;
+        let (de_ptr, us_ptr) = (data_pointer(0), data_pointer(germany.len()));
+
+        let mut db = Vec::new();
+        for (left, right) in [(1, 2), (NODE_COUNT, de_ptr), (us_ptr, NODE_COUNT)] {
+            db.extend_from_slice(&u32::to_be_bytes(left)[1..]);
+            db.extend_from_slice(&u32::to_be_bytes(right)[1..]);
+        }
+        db.extend_from_slice(&[0; 16]);
+        db.extend(germany);
+        db.extend(us);
+
+        db.extend_from_slice(b"\xAB\xCD\xEFMaxMind.com");
+        db.extend(map(vec![
+            ("binary_format_major_version", uint(5, 2, 2)),
+            ("binary_format_minor_version", uint(5, 0, 2)),
+            ("build_epoch", uint(9, 0, 8)),
+            ("database_type", string("GeoLite2-City")),
+            ("description", map(vec![])),
+            ("ip_version", uint(5, 4, 2)),
+            ("languages", value(11, &[], 0)),
+            ("node_count", uint(6, NODE_COUNT as u64, 4)),
+            ("record_size", uint(5, 24, 2)),
+        ]));
+
+        Reader::from_source(db).unwrap()
+    }
+
+    fn test_config() -> GeoIpConfig {
+        GeoIpConfig {
+            mmdb_path: "unused.mmdb".to_string(),
+            allow_countries: vec![],
+            deny_countries: vec![],
+            add_headers: true,
+            ip_strategy: None,
+        }
+    }
+
+    fn middleware(config: GeoIpConfig) -> GeoIpMiddleware {
+        GeoIpMiddleware::with_reader(test_database(), &config)
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_lookup_and_header_injection() {
+        let geoip = middleware(test_config());
+
+        let berlin = geoip.lookup(ip("81.2.69.142"));
+        assert_eq!(berlin.country.as_deref(), Some("DE"));
+        assert_eq!(berlin.city.as_deref(), Some("Berlin"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CITY_HEADER, HeaderValue::from_static("Spoofed"));
+        geoip.apply_headers(&geoip.lookup(ip("128.101.101.101")), &mut headers);
+        assert_eq!(headers.get(COUNTRY_HEADER).unwrap(), "US");
+        assert!(headers.get(CITY_HEADER).is_none());
+
+        assert_eq!(geoip.lookup(ip("10.0.0.1")), GeoLocation::default());
+
+        let mut headers = HeaderMap::new();
+        middleware(GeoIpConfig { add_headers: false, ..test_config() }).apply_headers(&berlin, &mut headers);
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn test_country_allow_and_deny_lists() {
+        let deny = middleware(GeoIpConfig { deny_countries: vec!["de".to_string()], ..test_config() });
+        assert!(!deny.is_allowed(deny.lookup(ip("81.2.69.142")).country.as_deref()));
+        assert!(deny.is_allowed(deny.lookup(ip("128.101.101.101")).country.as_deref()));
+        assert!(deny.is_allowed(None));
+
+        let allow = middleware(GeoIpConfig { allow_countries: vec!["US".to_string()], ..test_config() });
+        assert!(allow.is_allowed(Some("us")));
+        assert!(!allow.is_allowed(Some("DE")));
+        assert!(!allow.is_allowed(None));
+    }
+
+    #[test]
+    fn test_client_ip_follows_ip_strategy() {
+        let geoip = middleware(GeoIpConfig {
+            ip_strategy: Some(IpStrategy { depth: 1, ..Default::default() }),
+            ..test_config()
+        });
+        let client = geoip.get_client_ip(Some("81.2.69.142"), ip("10.0.0.2"));
+        assert_eq!(geoip.lookup(client).country.as_deref(), Some("DE"));
+
+        let direct = middleware(test_config());
+        let client = direct.get_client_ip(Some("81.2.69.142"), ip("10.0.0.2"));
+        assert_eq!(client, ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn test_missing_database_is_an_error() {
+        let config = GeoIpConfig {
+            mmdb_path: "/nonexistent/geo.mmdb".to_string(),
+            ..test_config()
+        };
+        assert!(GeoIpMiddleware::new(&config).is_err());
+    }
+}
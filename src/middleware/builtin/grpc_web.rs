@@ -1,15 +1,54 @@
 use crate::config::GrpcWebConfig;
+use crate::middleware::BufferedRequestBody;
+use crate::proxy::{decode_grpc_web_text, grpc_web_to_grpc_headers, GrpcStatus, GrpcWebEncoding};
 use bytes::{BufMut, Bytes, BytesMut};
-use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE};
-use hyper::{HeaderMap, Request, Response};
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use hyper::header::{
+    HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, CONTENT_LENGTH, CONTENT_TYPE, ORIGIN, VARY,
+};
+use hyper::{HeaderMap, Method, Request, Response, StatusCode};
 use regex::Regex;
 use std::sync::OnceLock;
+use thiserror::Error;
 use tracing::debug;
 
 const GRPC_WEB_CONTENT_TYPE: &str = "application/grpc-web";
 const GRPC_WEB_TEXT_CONTENT_TYPE: &str = "application/grpc-web-text";
 const GRPC_CONTENT_TYPE: &str = "application/grpc";
 
+/// Upper bound on a grpc-web-text request body, which is buffered to decode it
+const MAX_TEXT_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// Request headers allowed in preflight when the browser doesn't list any
+const DEFAULT_ALLOW_HEADERS: &str = "content-type,x-grpc-web,x-user-agent,grpc-timeout,authorization";
+
+/// Why a gRPC-Web request could not be translated
+#[derive(Debug, Error)]
+pub enum GrpcWebError {
+    /// The grpc-web-text body is over the buffering limit
+    #[error("grpc-web-text request body exceeds {0} bytes")]
+    TooLarge(usize),
+    /// The grpc-web-text body isn't valid base64
+    #[error("invalid grpc-web-text request body")]
+    InvalidBase64,
+    /// The body could not be read from the client
+    #[error("failed to read request body: {0}")]
+    Body(String),
+}
+
+impl GrpcWebError {
+    /// gRPC status to fail the call with
+    pub fn grpc_status(&self) -> GrpcStatus {
+        match self {
+            Self::TooLarge(_) => GrpcStatus::ResourceExhausted,
+            Self::InvalidBase64 | Self::Body(_) => GrpcStatus::InvalidArgument,
+        }
+    }
+}
+
 /// gRPC-Web middleware that translates between gRPC-Web and gRPC protocols
 ///
 /// This middleware allows browser-based clients to communicate with gRPC services
@@ -50,6 +89,79 @@ impl GrpcWebMiddleware {
         self.allow_origins.iter().any(|re| re.is_match(origin))
     }
 
+    /// Check if this is a CORS preflight request
+    pub fn is_preflight<B>(req: &Request<B>) -> bool {
+        req.method() == Method::OPTIONS
+            && req.headers().contains_key(ORIGIN)
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+    }
+
+    /// Answer a CORS preflight, or None when the origin is not allowed
+    pub fn handle_preflight<B>(&self, req: &Request<B>) -> Option<Response<()>> {
+        let origin = req.headers().get(ORIGIN)?;
+        if !self.is_origin_allowed(origin.to_str().ok()?) {
+            return None;
+        }
+        let allow_headers = req
+            .headers()
+            .get(ACCESS_CONTROL_REQUEST_HEADERS)
+            .cloned()
+            .unwrap_or(HeaderValue::from_static(DEFAULT_ALLOW_HEADERS));
+
+        Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+            .header(ACCESS_CONTROL_ALLOW_METHODS, "POST, OPTIONS")
+            .header(ACCESS_CONTROL_ALLOW_HEADERS, allow_headers)
+            .header(ACCESS_CONTROL_MAX_AGE, "86400")
+            .header(VARY, "Origin")
+            .body(())
+            .ok()
+    }
+
+    /// Add CORS headers to a gRPC-Web response for an allowed origin, exposing
+    /// the status headers of Trailers-Only responses
+    pub fn apply_cors(&self, origin: Option<&str>, headers: &mut HeaderMap) {
+        let Some(origin) = origin.filter(|origin| self.is_origin_allowed(origin)) else {
+            return;
+        };
+        if let Ok(val) = HeaderValue::from_str(origin) {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, val);
+            headers.insert(VARY, HeaderValue::from_static("Origin"));
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static("grpc-status,grpc-message"));
+        }
+    }
+
+    /// Translate a gRPC-Web request into native gRPC in place. Binary bodies
+    /// stream through unchanged; text bodies are buffered and base64-decoded
+    /// into a [`BufferedRequestBody`].
+    pub async fn translate_request<B>(&self, req: &mut Request<B>, encoding: GrpcWebEncoding) -> Result<(), GrpcWebError>
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: std::fmt::Display,
+    {
+        debug!("gRPC-Web request detected, translating to gRPC");
+        grpc_web_to_grpc_headers(req.headers_mut(), encoding);
+        if encoding == GrpcWebEncoding::Binary {
+            return Ok(());
+        }
+
+        let mut text = BytesMut::new();
+        while let Some(frame) = req.body_mut().frame().await {
+            let frame = frame.map_err(|e| GrpcWebError::Body(e.to_string()))?;
+            if let Ok(data) = frame.into_data() {
+                if text.len() + data.len() > MAX_TEXT_BODY_BYTES {
+                    return Err(GrpcWebError::TooLarge(MAX_TEXT_BODY_BYTES));
+                }
+                text.extend_from_slice(&data);
+            }
+        }
+        let decoded = decode_grpc_web_text(&text).ok_or(GrpcWebError::InvalidBase64)?;
+        req.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(decoded.len()));
+        req.extensions_mut().insert(BufferedRequestBody(decoded));
+        Ok(())
+    }
+
     /// Check if this is a gRPC-Web request
     pub fn is_grpc_web_request<B>(req: &Request<B>) -> bool {
         req.headers()
@@ -303,4 +415,32 @@ mod tests {
 
         assert!(middleware.is_origin_allowed("https://any.domain.com"));
     }
+
+    #[test]
+    fn test_preflight_honors_allowed_origins() {
+        let middleware = GrpcWebMiddleware::new(GrpcWebConfig {
+            allow_origins: vec!["https://app.example.com".to_string()],
+        });
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .header(ORIGIN, origin)
+                .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .body(())
+                .unwrap()
+        };
+
+        let req = preflight("https://app.example.com");
+        assert!(GrpcWebMiddleware::is_preflight(&req));
+        let resp = middleware.handle_preflight(&req).unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_HEADERS], DEFAULT_ALLOW_HEADERS);
+
+        assert!(middleware.handle_preflight(&preflight("https://evil.example")).is_none());
+
+        let mut headers = HeaderMap::new();
+        middleware.apply_cors(Some("https://evil.example"), &mut headers);
+        assert!(headers.is_empty());
+    }
 }
@@ -1,17 +1,23 @@
 use crate::config::HeadersConfig;
-use hyper::header::{HeaderName, HeaderValue};
-use hyper::HeaderMap;
+use hyper::header::{HeaderName, HeaderValue, HOST, LOCATION, STRICT_TRANSPORT_SECURITY};
+use hyper::{HeaderMap, Request, Response, StatusCode};
 
 /// Headers middleware for adding/removing request and response headers
 /// In Traefik, empty header values mean "remove this header"
+///
+/// Also enforces `allowedHosts`, redirects plain HTTP to HTTPS
+/// (`sslRedirect`), and emits HSTS. `isDevelopment` turns all three off.
 pub struct HeadersMiddleware {
-    #[allow(dead_code)]
     config: HeadersConfig,
     // Pre-parsed headers for performance
     request_headers: Vec<(HeaderName, HeaderValue)>,
     response_headers: Vec<(HeaderName, HeaderValue)>,
     remove_request: Vec<HeaderName>,
     remove_response: Vec<HeaderName>,
+    hosts_proxy_headers: Vec<HeaderName>,
+    ssl_proxy_headers: Vec<(HeaderName, String)>,
+    /// Strict-Transport-Security value, when `stsSeconds` is set
+    sts_value: Option<HeaderValue>,
 }
 
 impl HeadersMiddleware {
@@ -45,12 +51,120 @@ impl HeadersMiddleware {
             }
         }
 
+        let hosts_proxy_headers = config
+            .hosts_proxy_headers
+            .iter()
+            .filter_map(|h| HeaderName::try_from(h.as_str()).ok())
+            .collect();
+        let ssl_proxy_headers = config
+            .ssl_proxy_headers
+            .iter()
+            .filter_map(|(k, v)| Some((HeaderName::try_from(k.as_str()).ok()?, v.clone())))
+            .collect();
+
+        let sts_value = (config.sts_seconds > 0).then(|| {
+            let mut value = format!("max-age={}", config.sts_seconds);
+            if config.sts_include_subdomains {
+                value.push_str("; includeSubDomains");
+            }
+            if config.sts_preload {
+                value.push_str("; preload");
+            }
+            HeaderValue::from_str(&value).unwrap()
+        });
+
         Self {
             config,
             request_headers,
             response_headers,
             remove_request,
             remove_response,
+            hosts_proxy_headers,
+            ssl_proxy_headers,
+            sts_value,
+        }
+    }
+
+    /// Whether the request arrived over HTTPS, either directly or through a
+    /// TLS-terminating proxy that set one of `sslProxyHeaders`
+    pub fn is_secure<B>(&self, req: &Request<B>, is_tls: bool) -> bool {
+        is_tls
+            || self.ssl_proxy_headers.iter().any(|(name, expected)| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .is_some_and(|v| v.eq_ignore_ascii_case(expected))
+            })
+    }
+
+    /// Host the client asked for: the first `hostsProxyHeaders` header
+    /// present, else `Host`, else the URI authority
+    fn request_host<'r, B>(&self, req: &'r Request<B>) -> Option<&'r str> {
+        self.hosts_proxy_headers
+            .iter()
+            .chain(std::iter::once(&HOST))
+            .find_map(|name| req.headers().get(name).and_then(|v| v.to_str().ok()))
+            .or(req.uri().authority().map(|a| a.as_str()))
+    }
+
+    /// Whether the request's host is in `allowedHosts`. An empty list allows
+    /// every host. Entries match with or without the request's port.
+    pub fn host_allowed<B>(&self, req: &Request<B>) -> bool {
+        if self.config.allowed_hosts.is_empty() || self.config.is_development {
+            return true;
+        }
+        let Some(host) = self.request_host(req) else {
+            return false;
+        };
+        let hostname = strip_port(host);
+        self.config
+            .allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host) || allowed.eq_ignore_ascii_case(hostname))
+    }
+
+    /// HTTPS redirect for an insecure request when `sslRedirect` (301) or
+    /// `sslTemporaryRedirect` (302) is set. With `sslForceHost`, secure
+    /// requests for a host other than `sslHost` are redirected too.
+    pub fn ssl_redirect<B>(&self, req: &Request<B>, secure: bool) -> Option<Response<()>> {
+        let config = &self.config;
+        if !(config.ssl_redirect || config.ssl_temporary_redirect) || config.is_development {
+            return None;
+        }
+        let host = self.request_host(req).unwrap_or("localhost");
+        let target_host = config.ssl_host.as_deref().unwrap_or_else(|| strip_port(host));
+        if secure {
+            let wrong_host = config.ssl_host.as_deref().is_some_and(|ssl_host| {
+                !ssl_host.eq_ignore_ascii_case(host) && !ssl_host.eq_ignore_ascii_case(strip_port(host))
+            });
+            if !(config.ssl_force_host && wrong_host) {
+                return None;
+            }
+        }
+
+        let path_and_query = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+        let location = format!("https://{}{}", target_host, path_and_query);
+        let status = if config.ssl_temporary_redirect {
+            StatusCode::FOUND
+        } else {
+            StatusCode::MOVED_PERMANENTLY
+        };
+
+        let mut response = Response::builder().status(status);
+        if let Ok(location_value) = HeaderValue::from_str(&location) {
+            response = response.header(LOCATION, location_value);
+        }
+        Some(response.body(()).unwrap())
+    }
+
+    /// Add Strict-Transport-Security to a response for a secure request, or
+    /// to every response with `forceSTSHeader`
+    pub fn apply_sts(&self, headers: &mut HeaderMap, secure: bool) {
+        if let Some(value) = &self.sts_value
+            && (secure || self.config.force_sts_header)
+            && !self.config.is_development
+        {
+            headers.insert(STRICT_TRANSPORT_SECURITY, value.clone());
         }
     }
 
@@ -83,10 +197,22 @@ impl HeadersMiddleware {
     }
 }
 
+/// `host` without a trailing `:port` (IPv6 literals keep their brackets)
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn request(host: &str, path: &str) -> Request<()> {
+        Request::builder().uri(path).header(HOST, host).body(()).unwrap()
+    }
+
     #[test]
     fn test_add_headers() {
         let mut config = HeadersConfig::default();
@@ -133,4 +259,91 @@ mod tests {
 
         assert_eq!(headers.get("x-frame-options").unwrap(), "DENY");
     }
+
+    #[test]
+    fn test_disallowed_host_rejected() {
+        let config = HeadersConfig {
+            allowed_hosts: vec!["example.com".to_string()],
+            hosts_proxy_headers: vec!["X-Forwarded-Host".to_string()],
+            ..Default::default()
+        };
+        let middleware = HeadersMiddleware::new(config);
+
+        assert!(middleware.host_allowed(&request("example.com", "/")));
+        assert!(middleware.host_allowed(&request("Example.com:8080", "/")));
+        assert!(!middleware.host_allowed(&request("evil.com", "/")));
+
+        // The proxy header names the host the client actually asked for
+        let mut proxied = request("backend.internal", "/");
+        proxied
+            .headers_mut()
+            .insert("x-forwarded-host", HeaderValue::from_static("evil.com"));
+        assert!(!middleware.host_allowed(&proxied));
+    }
+
+    #[test]
+    fn test_ssl_redirect_behind_proxy() {
+        let mut config = HeadersConfig {
+            ssl_redirect: true,
+            ..Default::default()
+        };
+        config
+            .ssl_proxy_headers
+            .insert("X-Forwarded-Proto".to_string(), "https".to_string());
+        let middleware = HeadersMiddleware::new(config);
+
+        let plain = request("example.com:80", "/login?next=/");
+        let secure = middleware.is_secure(&plain, false);
+        assert!(!secure);
+        let redirect = middleware.ssl_redirect(&plain, secure).unwrap();
+        assert_eq!(redirect.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(redirect.headers()[LOCATION], "https://example.com/login?next=/");
+
+        let mut proxied = request("example.com", "/login");
+        proxied
+            .headers_mut()
+            .insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        assert!(middleware.is_secure(&proxied, false));
+        assert!(middleware.ssl_redirect(&proxied, true).is_none());
+
+        let temporary = HeadersMiddleware::new(HeadersConfig {
+            ssl_temporary_redirect: true,
+            ssl_host: Some("secure.example.com".to_string()),
+            ..Default::default()
+        });
+        let redirect = temporary.ssl_redirect(&request("example.com", "/"), false).unwrap();
+        assert_eq!(redirect.status(), StatusCode::FOUND);
+        assert_eq!(redirect.headers()[LOCATION], "https://secure.example.com/");
+    }
+
+    #[test]
+    fn test_hsts_only_over_https() {
+        let config = HeadersConfig {
+            sts_seconds: 31536000,
+            sts_include_subdomains: true,
+            sts_preload: true,
+            ..Default::default()
+        };
+        let middleware = HeadersMiddleware::new(config.clone());
+
+        let mut headers = HeaderMap::new();
+        middleware.apply_sts(&mut headers, false);
+        assert!(headers.get(STRICT_TRANSPORT_SECURITY).is_none());
+
+        middleware.apply_sts(&mut headers, true);
+        assert_eq!(
+            headers[STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000; includeSubDomains; preload"
+        );
+
+        let forced = HeadersMiddleware::new(HeadersConfig {
+            force_sts_header: true,
+            sts_include_subdomains: false,
+            sts_preload: false,
+            ..config
+        });
+        let mut headers = HeaderMap::new();
+        forced.apply_sts(&mut headers, false);
+        assert_eq!(headers[STRICT_TRANSPORT_SECURITY], "max-age=31536000");
+    }
 }
@@ -0,0 +1,193 @@
+//! htpasswd users files and password hash verification for basic auth.
+//!
+//! Supported hash schemes are those `htpasswd` writes: bcrypt (`$2y$`, `-B`),
+//! Apache MD5 (`$apr1$`, the default), and SHA-1 (`{SHA}`, `-s`), plus
+//! MD5-crypt (`$1$`) and plaintext.
+
+use super::digest_auth::md5_compute;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Alphabet of the crypt(3) base64 variant used by MD5-crypt
+const CRYPT_ALPHABET: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Read `user:hash` entries from an htpasswd file. Blank lines and `#`
+/// comments are skipped.
+pub(super) fn load_file(path: &str) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read users file {}", path))?;
+    Ok(parse(&content))
+}
+
+/// Parse `user:hash` lines
+pub(super) fn parse(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (user, hash) = line.split_once(':')?;
+            Some((user.to_string(), hash.to_string()))
+        })
+        .collect()
+}
+
+/// Whether `password` matches a stored htpasswd hash (or plaintext password)
+pub(super) fn verify_password(password: &str, stored: &str) -> bool {
+    if stored.starts_with("$2a$") || stored.starts_with("$2b$") || stored.starts_with("$2y$") {
+        // Malformed hashes never match
+        bcrypt::verify(password, stored).unwrap_or(false)
+    } else if let Some(rest) = stored.strip_prefix("$apr1$") {
+        constant_time_eq(md5_crypt(password, rest, "$apr1$").as_bytes(), stored.as_bytes())
+    } else if let Some(rest) = stored.strip_prefix("$1$") {
+        constant_time_eq(md5_crypt(password, rest, "$1$").as_bytes(), stored.as_bytes())
+    } else if let Some(digest) = stored.strip_prefix("{SHA}") {
+        let computed = STANDARD.encode(sha1_smol::Sha1::from(password).digest().bytes());
+        constant_time_eq(computed.as_bytes(), digest.as_bytes())
+    } else {
+        constant_time_eq(password.as_bytes(), stored.as_bytes())
+    }
+}
+
+/// MD5-crypt (FreeBSD `$1$`, Apache `$apr1$`): `magic salt $ hash`.
+/// `rest` is the stored hash after the magic; only its salt is used.
+fn md5_crypt(password: &str, rest: &str, magic: &str) -> String {
+    let salt = rest.split('$').next().unwrap_or("");
+    // At most 8 characters; cutting at a byte count could split a multi-byte one
+    let salt = salt.char_indices().nth(8).map_or(salt, |(end, _)| &salt[..end]);
+    let pw = password.as_bytes();
+
+    let alternate = md5_compute(&[pw, salt.as_bytes(), pw].concat());
+
+    let mut ctx = [pw, magic.as_bytes(), salt.as_bytes()].concat();
+    let mut remaining = pw.len();
+    while remaining > 0 {
+        let n = remaining.min(16);
+        ctx.extend_from_slice(&alternate[..n]);
+        remaining -= n;
+    }
+    let mut i = pw.len();
+    while i > 0 {
+        ctx.push(if i & 1 == 1 { 0 } else { pw.first().copied().unwrap_or(0) });
+        i >>= 1;
+    }
+    let mut digest = md5_compute(&ctx);
+
+    // Deliberately slow: 1000 rounds mixing password, salt, and digest
+    for round in 0..1000 {
+        let mut block = Vec::with_capacity(64);
+        if round & 1 == 1 {
+            block.extend_from_slice(pw);
+        } else {
+            block.extend_from_slice(&digest);
+        }
+        if round % 3 != 0 {
+            block.extend_from_slice(salt.as_bytes());
+        }
+        if round % 7 != 0 {
+            block.extend_from_slice(pw);
+        }
+        if round & 1 == 1 {
+            block.extend_from_slice(&digest);
+        } else {
+            block.extend_from_slice(pw);
+        }
+        digest = md5_compute(&block);
+    }
+
+    let mut out = format!("{}{}$", magic, salt);
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        let value = (digest[a] as u32) << 16 | (digest[b] as u32) << 8 | digest[c] as u32;
+        push_crypt64(&mut out, value, 4);
+    }
+    push_crypt64(&mut out, digest[11] as u32, 2);
+    out
+}
+
+/// Append the low `chars * 6` bits of `value`, least significant first
+fn push_crypt64(out: &mut String, mut value: u32, chars: usize) {
+    for _ in 0..chars {
+        out.push(CRYPT_ALPHABET[(value & 0x3f) as usize] as char);
+        value >>= 6;
+    }
+}
+
+/// Constant-time byte comparison (length differences return early)
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_md5_crypt_reference_hashes() {
+        // openssl passwd -apr1 / -1
+        assert!(verify_password("apr1-pass", "$apr1$s4ltS4lt$m5g2HzJDITG6YV6xbxl1u1"));
+        assert!(!verify_password("apr1-Pass", "$apr1$s4ltS4lt$m5g2HzJDITG6YV6xbxl1u1"));
+        assert!(verify_password("md5-pass", "$1$abc$V5TKgwOods.IJKD73Gy0.0"));
+    }
+
+    #[test]
+    fn test_md5_crypt_non_ascii_salt() {
+        // The 8th byte falls inside a multi-byte character; it must not panic
+        assert!(!verify_password("apr1-pass", "$apr1$saltsaläx$m5g2HzJDITG6YV6xbxl1u1"));
+        let hash = md5_crypt("apr1-pass", "saltsaläx$", "$apr1$");
+        assert!(hash.starts_with("$apr1$saltsalä$"));
+        assert!(verify_password("apr1-pass", &hash));
+    }
+
+    #[test]
+    fn test_bcrypt_reference_hashes() {
+        // Produced by the system crypt(3)
+        assert!(verify_password("U*U", "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"));
+        assert!(verify_password("U*U", "$2b$04$CCCCCCCCCCCCCCCCCCCCC.K7Qr0se1MxuggH4aP4YgB.U2Em1pGSK"));
+        assert!(verify_password("", "$2a$04$abcdefghijklmnopqrstuubyCG3zY1GIXMyxfivm.ClDiInHzxjiq"));
+        assert!(!verify_password("U*V", "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"));
+    }
+
+    #[test]
+    fn test_bcrypt_long_passwords_truncated() {
+        let hash = "$2y$04$abcdefghijklmnopqrstuubzadhGtS2zEF.gu0yd0opP6cVzb.e0i";
+        assert!(verify_password(&"x".repeat(72), hash));
+        assert!(verify_password(&"x".repeat(100), hash));
+        assert!(!verify_password(&"x".repeat(71), hash));
+    }
+
+    #[test]
+    fn test_malformed_bcrypt_hashes_rejected() {
+        assert!(!verify_password("U*U", "$2a$05$CCCC"));
+        assert!(!verify_password("U*U", "$2a$03$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"));
+    }
+
+    #[test]
+    fn test_sha_and_plaintext() {
+        assert!(verify_password("sha-pass", "{SHA}xO2etOilyqtV8o1RvvnmkeBx7QI="));
+        assert!(!verify_password("sha-pas", "{SHA}xO2etOilyqtV8o1RvvnmkeBx7QI="));
+        assert!(verify_password("plain", "plain"));
+        assert!(!verify_password("plain", "plain2"));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"test", b"test"));
+        assert!(!constant_time_eq(b"test", b"Test"));
+        assert!(!constant_time_eq(b"test", b"test1"));
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_blank_lines() {
+        let users = parse("# admins\nalice:$apr1$x$y\n\n  bob:secret:with:colons  \nnocolon\n");
+        assert_eq!(
+            users,
+            vec![
+                ("alice".to_string(), "$apr1$x$y".to_string()),
+                ("bob".to_string(), "secret:with:colons".to_string()),
+            ]
+        );
+    }
+}
@@ -1,13 +1,12 @@
-use crate::config::{IpAllowListConfig, IpDenyListConfig, IpStrategy};
+use crate::config::{IpAllowListConfig, IpDenyListConfig};
+use crate::middleware::ip_strategy::{parse_network, ClientIpResolver};
 use ipnetwork::IpNetwork;
 use std::net::IpAddr;
 
 /// IP allowlist middleware (Traefik ipAllowList)
 pub struct IpAllowListMiddleware {
     source_range: Vec<IpNetwork>,
-    ip_strategy: Option<IpStrategy>,
-    /// Pre-parsed excluded IP networks (avoids parse_network() per request)
-    excluded_networks: Vec<IpNetwork>,
+    client_ip: ClientIpResolver,
     reject_status_code: u16,
 }
 
@@ -20,16 +19,9 @@ impl IpAllowListMiddleware {
             .filter_map(|s| parse_network(s))
             .collect();
 
-        let excluded_networks = config
-            .ip_strategy
-            .as_ref()
-            .map(|s| s.excluded_ips.iter().filter_map(|e| parse_network(e)).collect())
-            .unwrap_or_default();
-
         Self {
             source_range,
-            ip_strategy: config.ip_strategy.clone(),
-            excluded_networks,
+            client_ip: ClientIpResolver::new(config.ip_strategy.as_ref()),
             reject_status_code: config.reject_status_code.unwrap_or(403),
         }
     }
@@ -57,23 +49,7 @@ impl IpAllowListMiddleware {
 
     /// Get IP from X-Forwarded-For based on strategy
     pub fn get_client_ip(&self, forwarded_for: Option<&str>, remote_addr: IpAddr) -> IpAddr {
-        if let Some(strategy) = &self.ip_strategy
-            && let Some(xff) = forwarded_for {
-                let ips: Vec<&str> = xff.split(',').map(|s| s.trim()).collect();
-                let depth = strategy.depth as usize;
-
-                if depth < ips.len() {
-                    let idx = ips.len() - 1 - depth;
-                    if let Ok(ip) = ips[idx].parse::<IpAddr>() {
-                        let should_exclude = self.excluded_networks.iter().any(|net| net.contains(ip));
-                        if !should_exclude {
-                            return ip;
-                        }
-                    }
-                }
-            }
-
-        remote_addr
+        self.client_ip.resolve(forwarded_for, remote_addr)
     }
 
     /// Check if filter has any rules configured
@@ -85,9 +61,7 @@ impl IpAllowListMiddleware {
 /// IP denylist middleware (Traefik ipDenyList)
 pub struct IpDenyListMiddleware {
     source_range: Vec<IpNetwork>,
-    ip_strategy: Option<IpStrategy>,
-    /// Pre-parsed excluded IP networks (avoids parse_network() per request)
-    excluded_networks: Vec<IpNetwork>,
+    client_ip: ClientIpResolver,
 }
 
 impl IpDenyListMiddleware {
@@ -99,16 +73,9 @@ impl IpDenyListMiddleware {
             .filter_map(|s| parse_network(s))
             .collect();
 
-        let excluded_networks = config
-            .ip_strategy
-            .as_ref()
-            .map(|s| s.excluded_ips.iter().filter_map(|e| parse_network(e)).collect())
-            .unwrap_or_default();
-
         Self {
             source_range,
-            ip_strategy: config.ip_strategy.clone(),
-            excluded_networks,
+            client_ip: ClientIpResolver::new(config.ip_strategy.as_ref()),
         }
     }
 
@@ -126,23 +93,7 @@ impl IpDenyListMiddleware {
 
     /// Get IP from X-Forwarded-For based on strategy
     pub fn get_client_ip(&self, forwarded_for: Option<&str>, remote_addr: IpAddr) -> IpAddr {
-        if let Some(strategy) = &self.ip_strategy
-            && let Some(xff) = forwarded_for {
-                let ips: Vec<&str> = xff.split(',').map(|s| s.trim()).collect();
-                let depth = strategy.depth as usize;
-
-                if depth < ips.len() {
-                    let idx = ips.len() - 1 - depth;
-                    if let Ok(ip) = ips[idx].parse::<IpAddr>() {
-                        let should_exclude = self.excluded_networks.iter().any(|net| net.contains(ip));
-                        if !should_exclude {
-                            return ip;
-                        }
-                    }
-                }
-            }
-
-        remote_addr
+        self.client_ip.resolve(forwarded_for, remote_addr)
     }
 
     /// Check if filter has any rules configured
@@ -151,28 +102,10 @@ impl IpDenyListMiddleware {
     }
 }
 
-/// Parse an IP address or CIDR notation into IpNetwork
-fn parse_network(s: &str) -> Option<IpNetwork> {
-    // Try parsing as CIDR first
-    if let Ok(network) = s.parse::<IpNetwork>() {
-        return Some(network);
-    }
-
-    // Try parsing as single IP address
-    if let Ok(ip) = s.parse::<IpAddr>() {
-        return match ip {
-            IpAddr::V4(v4) => Some(IpNetwork::V4(ipnetwork::Ipv4Network::new(v4, 32).ok()?)),
-            IpAddr::V6(v6) => Some(IpNetwork::V6(ipnetwork::Ipv6Network::new(v6, 128).ok()?)),
-        };
-    }
-
-    tracing::warn!("Failed to parse IP filter rule: {}", s);
-    None
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::IpStrategy;
 
     #[test]
     fn test_allow_single_ip() {
@@ -244,7 +177,7 @@ mod tests {
         let config = IpAllowListConfig {
             source_range: vec!["10.0.0.0/8".to_string()],
             ip_strategy: Some(IpStrategy {
-                depth: 1,
+                depth: 2,
                 excluded_ips: vec![],
                 ipv6_subnet: None,
             }),
@@ -253,7 +186,7 @@ mod tests {
         let filter = IpAllowListMiddleware::new(&config);
 
         // X-Forwarded-For: client, proxy1, proxy2
-        // depth=1 means skip proxy2, use proxy1
+        // depth=2 means skip proxy2, use proxy1
         let client_ip =
             filter.get_client_ip(Some("10.0.0.1, 192.168.1.1, 172.16.0.1"), "127.0.0.1".parse().unwrap());
 
@@ -0,0 +1,225 @@
+//! JSON Web Key Set (JWKS) fetching and caching for JWT signature verification.
+
+use super::jwt::JwtAlgorithm;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Caches verification keys from a JWKS endpoint, keyed by `kid`
+/// Keys are stored in the same form as a static public key: PKCS#1 RSAPublicKey DER
+/// for RSA, an uncompressed point for EC
+pub(super) struct JwksCache {
+    url: String,
+    algorithm: JwtAlgorithm,
+    client: reqwest::Client,
+    refresh_interval: Duration,
+    min_refresh_interval: Duration,
+    state: RwLock<JwksState>,
+    /// Serializes fetches so a burst of unknown `kid`s triggers a single request
+    fetch_lock: tokio::sync::Mutex<()>,
+}
+
+#[derive(Default)]
+struct JwksState {
+    keys: HashMap<String, Arc<[u8]>>,
+    fetched_at: Option<Instant>,
+    last_attempt: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    alg: Option<String>,
+    #[serde(default, rename = "use")]
+    key_use: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+impl JwksCache {
+    pub(super) fn new(
+        url: String,
+        algorithm: JwtAlgorithm,
+        refresh_interval: Duration,
+        min_refresh_interval: Duration,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            url,
+            algorithm,
+            client,
+            refresh_interval,
+            min_refresh_interval,
+            state: RwLock::new(JwksState::default()),
+            fetch_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Look up a cached key without fetching.
+    /// A token without `kid` is accepted only when the set holds exactly one key.
+    pub(super) fn cached_key(&self, kid: Option<&str>) -> Option<Arc<[u8]>> {
+        let state = self.state.read();
+        match kid {
+            Some(kid) => state.keys.get(kid).cloned(),
+            None if state.keys.len() == 1 => state.keys.values().next().cloned(),
+            None => None,
+        }
+    }
+
+    /// Make sure the cache is fresh and holds `kid`, fetching when the refresh
+    /// interval has elapsed or the key is unknown. Fetches are rate limited by
+    /// `min_refresh_interval` so unknown `kid`s can't hammer the provider.
+    pub(super) async fn ensure_key(&self, kid: Option<&str>) {
+        let stale = {
+            let state = self.state.read();
+            state
+                .fetched_at
+                .is_none_or(|t| t.elapsed() >= self.refresh_interval)
+        };
+
+        if (stale || self.cached_key(kid).is_none()) && self.can_fetch() {
+            let _guard = self.fetch_lock.lock().await;
+            // Another task may have refreshed while we waited for the lock
+            if self.can_fetch() {
+                self.refresh().await;
+            }
+        }
+    }
+
+    fn can_fetch(&self) -> bool {
+        self.state
+            .read()
+            .last_attempt
+            .is_none_or(|t| t.elapsed() >= self.min_refresh_interval)
+    }
+
+    /// Fetch the key set and replace the cache. On failure the previous keys are kept.
+    async fn refresh(&self) {
+        self.state.write().last_attempt = Some(Instant::now());
+
+        let result = async {
+            let response = self.client.get(&self.url).send().await?.error_for_status()?;
+            response.json::<JwkSet>().await
+        }
+        .await;
+
+        match result {
+            Ok(set) => {
+                let keys = self.usable_keys(set);
+                tracing::debug!("Fetched {} usable JWKS keys from {}", keys.len(), self.url);
+                let mut state = self.state.write();
+                state.keys = keys;
+                state.fetched_at = Some(Instant::now());
+            }
+            Err(e) => {
+                tracing::warn!("Failed to fetch JWKS from {}: {}", self.url, e);
+            }
+        }
+    }
+
+    /// Keep only signing keys compatible with the configured algorithm.
+    fn usable_keys(&self, set: JwkSet) -> HashMap<String, Arc<[u8]>> {
+        set.keys
+            .into_iter()
+            .filter(|jwk| jwk.key_use.as_deref().is_none_or(|u| u == "sig"))
+            .filter(|jwk| {
+                jwk.alg
+                    .as_deref()
+                    .is_none_or(|a| JwtAlgorithm::from_name(a) == Some(self.algorithm))
+            })
+            .filter_map(|jwk| {
+                let key = jwk_to_key(&jwk, self.algorithm)?;
+                Some((jwk.kid.unwrap_or_default(), Arc::from(key)))
+            })
+            .collect()
+    }
+}
+
+/// Convert a JWK into ring-compatible public key bytes for `algorithm`.
+fn jwk_to_key(jwk: &Jwk, algorithm: JwtAlgorithm) -> Option<Vec<u8>> {
+    match (jwk.kty.as_str(), algorithm) {
+        ("RSA", JwtAlgorithm::RS256 | JwtAlgorithm::RS384 | JwtAlgorithm::RS512) => {
+            let n = URL_SAFE_NO_PAD.decode(jwk.n.as_deref()?).ok()?;
+            let e = URL_SAFE_NO_PAD.decode(jwk.e.as_deref()?).ok()?;
+            Some(rsa_public_key_der(&n, &e))
+        }
+        ("EC", JwtAlgorithm::ES256 | JwtAlgorithm::ES384) => {
+            let (crv, coord_len) = match algorithm {
+                JwtAlgorithm::ES256 => ("P-256", 32),
+                _ => ("P-384", 48),
+            };
+            if jwk.crv.as_deref() != Some(crv) {
+                return None;
+            }
+            let x = URL_SAFE_NO_PAD.decode(jwk.x.as_deref()?).ok()?;
+            let y = URL_SAFE_NO_PAD.decode(jwk.y.as_deref()?).ok()?;
+            if x.len() != coord_len || y.len() != coord_len {
+                return None;
+            }
+            let mut point = Vec::with_capacity(1 + 2 * coord_len);
+            point.push(0x04);
+            point.extend_from_slice(&x);
+            point.extend_from_slice(&y);
+            Some(point)
+        }
+        _ => None,
+    }
+}
+
+/// DER-encode a PKCS#1 RSAPublicKey: SEQUENCE { INTEGER n, INTEGER e }
+fn rsa_public_key_der(n: &[u8], e: &[u8]) -> Vec<u8> {
+    let mut body = der_unsigned_integer(n);
+    body.extend(der_unsigned_integer(e));
+    let mut out = vec![0x30];
+    der_push_len(&mut out, body.len());
+    out.extend(body);
+    out
+}
+
+fn der_unsigned_integer(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len().saturating_sub(1));
+    let bytes = &bytes[start..];
+    // A leading zero keeps the value positive when the high bit is set
+    let pad = bytes.first().is_some_and(|&b| b & 0x80 != 0);
+    let mut out = vec![0x02];
+    der_push_len(&mut out, bytes.len() + pad as usize);
+    if pad {
+        out.push(0);
+    }
+    out.extend_from_slice(bytes);
+    out
+}
+
+fn der_push_len(out: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len() - 1);
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Maintenance mode: serve a fixed page instead of the backend.
+
+use crate::config::MaintenanceConfig;
+use crate::middleware::ip_strategy::{parse_network, ClientIpResolver};
+use bytes::Bytes;
+use hyper::header::{HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use hyper::{Response, StatusCode};
+use ipnetwork::IpNetwork;
+use std::net::IpAddr;
+use tracing::warn;
+
+/// Body served when neither `body` nor a readable `file` is configured
+const DEFAULT_BODY: &str = "Service temporarily unavailable for maintenance";
+
+/// Maintenance middleware
+pub struct MaintenanceMiddleware {
+    enabled: bool,
+    status: StatusCode,
+    retry_after: Option<HeaderValue>,
+    content_type: HeaderValue,
+    body: Bytes,
+    allow_source_range: Vec<IpNetwork>,
+    client_ip: ClientIpResolver,
+}
+
+impl MaintenanceMiddleware {
+    /// Create from config, reading the body file if one is set.
+    pub fn new(config: &MaintenanceConfig) -> Self {
+        let status = StatusCode::from_u16(config.status_code).unwrap_or_else(|_| {
+            warn!("Invalid maintenance statusCode {}, using 503", config.status_code);
+            StatusCode::SERVICE_UNAVAILABLE
+        });
+
+        let body = match (&config.file, &config.body) {
+            (Some(path), _) => match std::fs::read(path) {
+                Ok(contents) => Bytes::from(contents),
+                Err(e) => {
+                    warn!("Failed to read maintenance page '{}': {}", path, e);
+                    config
+                        .body
+                        .clone()
+                        .map(Bytes::from)
+                        .unwrap_or_else(|| Bytes::from_static(DEFAULT_BODY.as_bytes()))
+                }
+            },
+            (None, Some(body)) => Bytes::from(body.clone()),
+            (None, None) => Bytes::from_static(DEFAULT_BODY.as_bytes()),
+        };
+
+        let content_type = HeaderValue::from_str(&config.content_type)
+            .unwrap_or_else(|_| HeaderValue::from_static("text/html; charset=utf-8"));
+
+        Self {
+            enabled: config.enabled,
+            status,
+            retry_after: config
+                .retry_after
+                .map(|d| HeaderValue::from(d.as_std().as_secs())),
+            content_type,
+            body,
+            allow_source_range: config
+                .allow_source_range
+                .iter()
+                .filter_map(|s| parse_network(s))
+                .collect(),
+            client_ip: ClientIpResolver::new(config.ip_strategy.as_ref()),
+        }
+    }
+
+    /// Client IP checked against `allowSourceRange`, per the configured `ipStrategy`
+    pub fn get_client_ip(&self, forwarded_for: Option<&str>, remote_addr: IpAddr) -> IpAddr {
+        self.client_ip.resolve(forwarded_for, remote_addr)
+    }
+
+    /// Whether a client gets the maintenance page. Unknown clients (`None`) are blocked.
+    pub fn should_block(&self, client_ip: Option<IpAddr>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        !client_ip.is_some_and(|ip| self.allow_source_range.iter().any(|net| net.contains(ip)))
+    }
+
+    /// Build the maintenance response
+    pub fn response(&self) -> Response<Bytes> {
+        let mut resp = Response::new(self.body.clone());
+        *resp.status_mut() = self.status;
+        resp.headers_mut().insert(CONTENT_TYPE, self.content_type.clone());
+        if let Some(ref retry_after) = self.retry_after {
+            resp.headers_mut().insert(RETRY_AFTER, retry_after.clone());
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Duration, IpStrategy};
+
+    fn test_config() -> MaintenanceConfig {
+        MaintenanceConfig {
+            enabled: true,
+            status_code: 503,
+            retry_after: None,
+            body: None,
+            file: None,
+            content_type: "text/html; charset=utf-8".to_string(),
+            allow_source_range: vec![],
+            ip_strategy: None,
+        }
+    }
+
+    fn middleware(config: MaintenanceConfig) -> MaintenanceMiddleware {
+        MaintenanceMiddleware::new(&config)
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_blocked_client_gets_custom_page() {
+        let mw = middleware(MaintenanceConfig {
+            retry_after: Some(Duration::from_secs(600)),
+            body: Some("<h1>Back soon</h1>".to_string()),
+            allow_source_range: vec!["10.0.0.0/8".to_string()],
+            ..test_config()
+        });
+        assert!(mw.should_block(Some(ip("203.0.113.5"))));
+        assert!(mw.should_block(None));
+
+        let resp = mw.response();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "600");
+        assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "text/html; charset=utf-8");
+        assert_eq!(resp.body().as_ref(), b"<h1>Back soon</h1>");
+    }
+
+    #[test]
+    fn test_allowlisted_client_passes_through() {
+        let mw = middleware(MaintenanceConfig {
+            allow_source_range: vec!["10.0.0.0/8".to_string(), "192.0.2.7".to_string()],
+            ..test_config()
+        });
+        assert!(!mw.should_block(Some(ip("10.20.30.40"))));
+        assert!(!mw.should_block(Some(ip("192.0.2.7"))));
+        assert!(mw.should_block(Some(ip("192.0.2.8"))));
+
+        // Behind a proxy, the allowlist applies to the resolved client
+        let proxied = middleware(MaintenanceConfig {
+            allow_source_range: vec!["10.0.0.0/8".to_string()],
+            ip_strategy: Some(IpStrategy { depth: 1, ..Default::default() }),
+            ..test_config()
+        });
+        let client = proxied.get_client_ip(Some("198.51.100.1"), ip("10.0.0.2"));
+        assert!(proxied.should_block(Some(client)));
+
+        let disabled = middleware(MaintenanceConfig { enabled: false, ..test_config() });
+        assert!(!disabled.should_block(None));
+    }
+
+    #[test]
+    fn test_body_from_file_and_custom_status() {
+        let path = std::env::temp_dir().join(format!("maintenance-{}.html", std::process::id()));
+        std::fs::write(&path, "<p>Upgrading</p>").unwrap();
+
+        let mw = middleware(MaintenanceConfig {
+            status_code: 502,
+            body: Some("ignored".to_string()),
+            file: Some(path.display().to_string()),
+            content_type: "text/plain".to_string(),
+            ..test_config()
+        });
+        let resp = mw.response();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(resp.body().as_ref(), b"<p>Upgrading</p>");
+        assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+        assert!(resp.headers().get(RETRY_AFTER).is_none());
+        std::fs::remove_file(&path).unwrap();
+
+        // An unreadable file falls back to the inline body
+        let missing = middleware(MaintenanceConfig {
+            body: Some("fallback".to_string()),
+            file: Some("/nonexistent/maintenance.html".to_string()),
+            ..test_config()
+        });
+        assert_eq!(missing.response().body().as_ref(), b"fallback");
+    }
+}
@@ -6,26 +6,42 @@ mod buffering;
 mod chain;
 mod compress;
 mod cors;
+mod decompress_request;
 mod digest_auth;
 mod errors;
 mod forward_auth;
+mod geoip;
 mod grpc_web;
 mod headers;
+mod htpasswd;
+mod jwks;
 mod jwt;
 mod ip_filter;
+mod maintenance;
+mod oauth2_introspect;
+mod pass_tls_client_cert;
 mod path;
 mod rate_limit;
+mod redirect_regex;
 mod redirect_scheme;
+mod replace_body;
+mod request_id;
 mod retry;
+mod tarpit;
 
 /// Structured access log entry builder, output, and file writer.
-pub use access_log::{AccessLogBuilder, AccessLogEntry, AccessLogWriter};
+pub use access_log::{AccessLogBuilder, AccessLogEntry, AccessLogFields, AccessLogFormat, AccessLogWriter, BackendInfo};
 /// HTTP Basic authentication middleware.
 pub use basic_auth::BasicAuthMiddleware;
 /// Request/response body buffering for retry support.
-pub use buffering::BufferingMiddleware;
+pub use buffering::{
+    BufferingError, BufferingMiddleware, BufferingRetry, ReplayableBody, RetryExpression, RetryMetric,
+    SpoolFile, SpooledRequestBody,
+};
 /// Custom error page middleware.
 pub use errors::ErrorsMiddleware;
+/// Request body decompression (gzip, deflate, br, zstd) with a size limit.
+pub use decompress_request::{DecompressError, DecompressRequestMiddleware, RequestEncoding};
 /// HTTP Digest authentication (RFC 7616).
 pub use digest_auth::{AuthResult as DigestAuthResult, DigestAuthMiddleware};
 /// Compose multiple named middleware into a single reference.
@@ -35,23 +51,39 @@ pub use compress::{CompressMiddleware, CompressionAlgorithm};
 /// Cross-Origin Resource Sharing (CORS) middleware.
 pub use cors::CorsMiddleware;
 /// Delegate authentication to an external HTTP service.
-pub use forward_auth::{AuthResult, ForwardAuthMiddleware};
+pub use forward_auth::{AuthDenied, AuthResult, ForwardAuthMiddleware};
+/// Country allow/deny lists and location headers from a MaxMind database.
+pub use geoip::{GeoIpMiddleware, GeoLocation};
 /// gRPC-Web to native gRPC protocol translation.
-pub use grpc_web::GrpcWebMiddleware;
+pub use grpc_web::{GrpcWebError, GrpcWebMiddleware};
 /// Add, remove, or override request/response headers.
 pub use headers::HeadersMiddleware;
 /// JWT token validation and claim forwarding.
 pub use jwt::{ClaimValue, JwtAlgorithm, JwtMiddleware, JwtValidationResult};
 /// IP-based allow/deny list filtering.
 pub use ip_filter::{IpAllowListMiddleware, IpDenyListMiddleware};
+/// Maintenance mode page with an allowlist bypass.
+pub use maintenance::MaintenanceMiddleware;
+/// OAuth2 token introspection (RFC 7662) with result caching.
+pub use oauth2_introspect::{IntrospectionResponse, OAuth2IntrospectionMiddleware};
+/// Forward the mTLS client certificate and selected fields to backends.
+pub use pass_tls_client_cert::PassTlsClientCertMiddleware;
 /// URL path manipulation (strip, add, replace with literal or regex).
 pub use path::{
     AddPrefixMiddleware, ReplacePathMiddleware, ReplacePathRegexMiddleware,
     StripPrefixMiddleware, StripPrefixRegexMiddleware,
 };
 /// Token-bucket rate limiting with optional distributed backing store.
-pub use rate_limit::RateLimitMiddleware;
+pub use rate_limit::{RateLimitMiddleware, RateLimitResult};
+/// Redirect requests whose URL matches a regex, with capture group expansion.
+pub use redirect_regex::RedirectRegexMiddleware;
 /// HTTP-to-HTTPS (or reverse) scheme redirect.
 pub use redirect_scheme::RedirectSchemeMiddleware;
+/// Regex substitutions on buffered text response bodies.
+pub use replace_body::ReplaceResponseBodyMiddleware;
+/// Request ID generation, echoed to clients and propagated to backends.
+pub use request_id::{RequestId, RequestIdMiddleware};
 /// Retry failed requests with exponential backoff.
-pub use retry::{RetryIterator, RetryMiddleware};
+pub use retry::{RequestRetry, RetryIterator, RetryMiddleware};
+/// Delay flagged clients, with a cap on requests held at once.
+pub use tarpit::{TarpitGuard, TarpitMiddleware};
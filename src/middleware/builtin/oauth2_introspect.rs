@@ -0,0 +1,348 @@
+//! OAuth2 token introspection (RFC 7662) with a cache of active tokens.
+
+use crate::config::OAuth2IntrospectionConfig;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use dashmap::DashMap;
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Request, StatusCode};
+use reqwest::Client;
+use ring::digest::{digest, SHA256};
+use serde::Deserialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Cached entries beyond which expired ones are swept on insert
+const CACHE_SWEEP_THRESHOLD: usize = 10_000;
+
+/// Introspection endpoint response. Only the fields the proxy uses are parsed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IntrospectionResponse {
+    /// Whether the token is currently active
+    pub active: bool,
+    /// Expiry as seconds since the Unix epoch
+    #[serde(default)]
+    pub exp: Option<u64>,
+    /// Space-separated scopes granted to the token
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Client the token was issued to
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Subject of the token
+    #[serde(default)]
+    pub sub: Option<String>,
+}
+
+struct CachedToken {
+    response: IntrospectionResponse,
+    expires_at: Instant,
+}
+
+/// Validates bearer tokens by POSTing them to an introspection endpoint.
+/// Active results are cached by token hash until the token's `exp` or the
+/// configured TTL, whichever comes first; inactive results are never cached.
+pub struct OAuth2IntrospectionMiddleware {
+    client: Client,
+    endpoint: String,
+    /// Precomputed HTTP Basic credentials for the endpoint
+    credentials: HeaderValue,
+    cache_ttl: Duration,
+    header_name: String,
+    header_prefix: String,
+    cache: DashMap<[u8; 32], CachedToken>,
+}
+
+impl OAuth2IntrospectionMiddleware {
+    /// Create from config, building an HTTP client for the introspection endpoint.
+    pub fn new(config: OAuth2IntrospectionConfig) -> Option<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .ok()?;
+
+        let encoded = STANDARD.encode(format!("{}:{}", config.client_id, config.client_secret));
+        let credentials = HeaderValue::from_str(&format!("Basic {}", encoded)).ok()?;
+
+        Some(Self {
+            client,
+            endpoint: config.endpoint,
+            credentials,
+            cache_ttl: config.cache_ttl.as_std(),
+            header_name: config.header_name,
+            header_prefix: config.header_prefix,
+            cache: DashMap::new(),
+        })
+    }
+
+    /// Introspect the request's bearer token.
+    /// Returns the endpoint's response for an active token, or the status and
+    /// message to reject the request with.
+    pub async fn authenticate<B>(
+        &self,
+        req: &Request<B>,
+    ) -> Result<IntrospectionResponse, (StatusCode, String)> {
+        let token = self
+            .extract_token(req)
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing bearer token".to_string()))?;
+
+        let key = token_key(token);
+        if let Some(cached) = self.cache.get(&key) {
+            if cached.expires_at > Instant::now() {
+                return Ok(cached.response.clone());
+            }
+            drop(cached);
+            self.cache.remove(&key);
+        }
+
+        let response = self.introspect(token).await?;
+        if !response.active {
+            return Err((StatusCode::UNAUTHORIZED, "Token is not active".to_string()));
+        }
+
+        if let Some(ttl) = self.cache_duration(&response) {
+            if self.cache.len() >= CACHE_SWEEP_THRESHOLD {
+                let now = Instant::now();
+                self.cache.retain(|_, entry| entry.expires_at > now);
+            }
+            self.cache.insert(
+                key,
+                CachedToken {
+                    response: response.clone(),
+                    expires_at: Instant::now() + ttl,
+                },
+            );
+        }
+
+        Ok(response)
+    }
+
+    async fn introspect(&self, token: &str) -> Result<IntrospectionResponse, (StatusCode, String)> {
+        let body = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("token", token)
+            .append_pair("token_type_hint", "access_token")
+            .finish();
+
+        let unavailable = || {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Introspection service unavailable".to_string(),
+            )
+        };
+
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header(AUTHORIZATION, self.credentials.clone())
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| {
+                warn!("Token introspection request failed: {}", e);
+                unavailable()
+            })?;
+
+        if !response.status().is_success() {
+            warn!("Token introspection endpoint returned {}", response.status());
+            return Err(unavailable());
+        }
+
+        response.json::<IntrospectionResponse>().await.map_err(|e| {
+            warn!("Invalid token introspection response: {}", e);
+            unavailable()
+        })
+    }
+
+    /// How long an active response may be cached, or None if it must not be
+    fn cache_duration(&self, response: &IntrospectionResponse) -> Option<Duration> {
+        let ttl = match response.exp {
+            Some(exp) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                self.cache_ttl.min(Duration::from_secs(exp.saturating_sub(now)))
+            }
+            None => self.cache_ttl,
+        };
+
+        if ttl.is_zero() {
+            debug!("Not caching introspection result");
+            return None;
+        }
+        Some(ttl)
+    }
+
+    /// Extract the token from the configured header
+    fn extract_token<'r, B>(&self, req: &'r Request<B>) -> Option<&'r str> {
+        req.headers()
+            .get(&self.header_name)?
+            .to_str()
+            .ok()?
+            .strip_prefix(&self.header_prefix)
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+    }
+}
+
+/// Cache key: SHA-256 of the token, so raw tokens are not kept in memory
+fn token_key(token: &str) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key.copy_from_slice(digest(&SHA256, token.as_bytes()).as_ref());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Incoming;
+    use hyper::service::service_fn;
+    use hyper::Response;
+    use hyper_util::rt::TokioIo;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Introspection server: `good` is active for an hour, `expiring` is active
+    /// but already at its `exp`, anything else is inactive. Requests without
+    /// the expected client credentials get a 401. Counts requests served.
+    async fn introspection_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let hits = Arc::clone(&hits);
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| {
+                        let hits = Arc::clone(&hits);
+                        async move {
+                            hits.fetch_add(1, Ordering::SeqCst);
+                            let authorized = req.headers().get(AUTHORIZATION)
+                                == Some(&HeaderValue::from_static("Basic cHJveHk6czNjcmV0"));
+                            let body = req.into_body().collect().await.unwrap().to_bytes();
+                            let form: Vec<(String, String)> =
+                                url::form_urlencoded::parse(&body).into_owned().collect();
+                            let token = form
+                                .iter()
+                                .find(|(k, _)| k == "token")
+                                .map(|(_, v)| v.as_str())
+                                .unwrap_or_default();
+
+                            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                            let json = match token {
+                                "good" => format!(
+                                    r#"{{"active":true,"exp":{},"scope":"read","sub":"alice"}}"#,
+                                    now + 3600
+                                ),
+                                "expiring" => format!(r#"{{"active":true,"exp":{}}}"#, now),
+                                _ => r#"{"active":false}"#.to_string(),
+                            };
+                            let response = if authorized {
+                                Response::new(Full::new(Bytes::from(json)))
+                            } else {
+                                let mut resp = Response::new(Full::new(Bytes::new()));
+                                *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                                resp
+                            };
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (format!("http://{}/introspect", addr), counter)
+    }
+
+    fn test_config(endpoint: &str) -> OAuth2IntrospectionConfig {
+        OAuth2IntrospectionConfig {
+            endpoint: endpoint.to_string(),
+            client_id: "proxy".to_string(),
+            client_secret: "s3cret".to_string(),
+            cache_ttl: crate::config::Duration::from_secs(60),
+            header_name: "Authorization".to_string(),
+            header_prefix: "Bearer ".to_string(),
+        }
+    }
+
+    fn middleware(config: OAuth2IntrospectionConfig) -> OAuth2IntrospectionMiddleware {
+        OAuth2IntrospectionMiddleware::new(config).unwrap()
+    }
+
+    fn request(token: &str) -> Request<()> {
+        Request::builder()
+            .header("authorization", format!("Bearer {}", token))
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_active_token_is_cached() {
+        let (endpoint, hits) = introspection_server().await;
+        let auth = middleware(test_config(&endpoint));
+
+        let response = auth.authenticate(&request("good")).await.unwrap();
+        assert_eq!(response.sub.as_deref(), Some("alice"));
+        assert_eq!(response.scope.as_deref(), Some("read"));
+
+        let cached = auth.authenticate(&request("good")).await.unwrap();
+        assert_eq!(cached.sub.as_deref(), Some("alice"));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_inactive_token_is_rejected_and_not_cached() {
+        let (endpoint, hits) = introspection_server().await;
+        let auth = middleware(test_config(&endpoint));
+
+        for _ in 0..2 {
+            let (status, _) = auth.authenticate(&request("revoked")).await.unwrap_err();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        let (status, _) = auth.authenticate(&Request::new(())).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_lifetime_is_bounded_by_exp_and_ttl() {
+        let (endpoint, hits) = introspection_server().await;
+
+        // Active but at its exp: nothing left to cache
+        let auth = middleware(test_config(&endpoint));
+        auth.authenticate(&request("expiring")).await.unwrap();
+        auth.authenticate(&request("expiring")).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // A zero TTL disables caching altogether
+        let uncached = middleware(OAuth2IntrospectionConfig {
+            cache_ttl: crate::config::Duration::from_secs(0),
+            ..test_config(&endpoint)
+        });
+        uncached.authenticate(&request("good")).await.unwrap();
+        uncached.authenticate(&request("good")).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_failure_is_server_error() {
+        let (endpoint, _) = introspection_server().await;
+        let auth = middleware(OAuth2IntrospectionConfig {
+            client_secret: "wrong".to_string(),
+            ..test_config(&endpoint)
+        });
+
+        let (status, _) = auth.authenticate(&request("good")).await.unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,393 @@
+//! Forward the TLS client certificate (mTLS) to backends as request headers.
+//!
+//! Header formats follow Traefik so existing backends keep working:
+//! `X-Forwarded-Tls-Client-Cert` carries each certificate of the chain as
+//! base64 DER (the PEM body without markers or line breaks), comma-separated;
+//! `X-Forwarded-Tls-Client-Cert-Info` carries the selected fields, e.g.
+//! `Subject="C=US,CN=client";NB="1767225600";SAN="a.example.com"`, one entry
+//! per certificate. Both values are URL query escaped.
+
+use crate::config::{PassTlsClientCertConfig, TlsClientCertInfo};
+use crate::tls::ClientCertInfo;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use x509_parser::oid_registry::{
+    OID_DOMAIN_COMPONENT, OID_X509_COMMON_NAME, OID_X509_COUNTRY_NAME, OID_X509_LOCALITY_NAME,
+    OID_X509_ORGANIZATIONAL_UNIT, OID_X509_ORGANIZATION_NAME, OID_X509_SERIALNUMBER,
+    OID_X509_STATE_OR_PROVINCE_NAME,
+};
+use x509_parser::prelude::*;
+
+/// Header carrying the certificate chain
+pub const CLIENT_CERT_HEADER: HeaderName = HeaderName::from_static("x-forwarded-tls-client-cert");
+
+/// Header carrying the selected certificate fields
+pub const CLIENT_CERT_INFO_HEADER: HeaderName =
+    HeaderName::from_static("x-forwarded-tls-client-cert-info");
+
+/// Distinguished name attributes to include, in output order
+#[derive(Debug, Clone, Copy, Default)]
+struct DnFields {
+    country: bool,
+    province: bool,
+    locality: bool,
+    organization: bool,
+    organizational_unit: bool,
+    common_name: bool,
+    serial_number: bool,
+    domain_component: bool,
+}
+
+impl DnFields {
+    fn any(&self) -> bool {
+        self.country
+            || self.province
+            || self.locality
+            || self.organization
+            || self.organizational_unit
+            || self.common_name
+            || self.serial_number
+            || self.domain_component
+    }
+
+    /// `K=v` pairs of the selected attributes, comma-separated
+    fn format(&self, name: &X509Name<'_>) -> String {
+        let selected = [
+            (self.country, "C", OID_X509_COUNTRY_NAME),
+            (self.province, "ST", OID_X509_STATE_OR_PROVINCE_NAME),
+            (self.locality, "L", OID_X509_LOCALITY_NAME),
+            (self.organization, "O", OID_X509_ORGANIZATION_NAME),
+            (self.organizational_unit, "OU", OID_X509_ORGANIZATIONAL_UNIT),
+            (self.common_name, "CN", OID_X509_COMMON_NAME),
+            (self.serial_number, "SerialNumber", OID_X509_SERIALNUMBER),
+            (self.domain_component, "DC", OID_DOMAIN_COMPONENT),
+        ];
+
+        selected
+            .iter()
+            .filter(|(enabled, _, _)| *enabled)
+            .flat_map(|(_, key, oid)| {
+                name.iter_by_oid(oid)
+                    .filter_map(|attr| attr.as_str().ok())
+                    .map(move |value| format!("{}={}", key, value))
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Pass TLS client certificate middleware
+pub struct PassTlsClientCertMiddleware {
+    pem: bool,
+    info: Option<TlsClientCertInfo>,
+    subject: DnFields,
+    issuer: DnFields,
+}
+
+impl PassTlsClientCertMiddleware {
+    pub fn new(config: &PassTlsClientCertConfig) -> Self {
+        let info = config.info.as_ref();
+        let subject = info
+            .and_then(|info| info.subject.as_ref())
+            .map(|s| DnFields {
+                country: s.country,
+                province: s.province,
+                locality: s.locality,
+                organization: s.organization,
+                organizational_unit: s.organizational_unit,
+                common_name: s.common_name,
+                serial_number: s.serial_number,
+                domain_component: s.domain_component,
+            })
+            .unwrap_or_default();
+        let issuer = info
+            .and_then(|info| info.issuer.as_ref())
+            .map(|i| DnFields {
+                country: i.country,
+                province: i.province,
+                locality: i.locality,
+                organization: i.organization,
+                organizational_unit: false,
+                common_name: i.common_name,
+                serial_number: i.serial_number,
+                domain_component: i.domain_component,
+            })
+            .unwrap_or_default();
+
+        Self {
+            pem: config.pem,
+            info: config.info.clone(),
+            subject,
+            issuer,
+        }
+    }
+
+    /// Set the certificate headers for the backend. Copies sent by the client
+    /// are always removed so they can't be spoofed on plain connections.
+    pub fn apply(&self, headers: &mut HeaderMap, cert: Option<&ClientCertInfo>) {
+        headers.remove(CLIENT_CERT_HEADER);
+        headers.remove(CLIENT_CERT_INFO_HEADER);
+
+        let Some(cert) = cert.filter(|cert| cert.has_cert()) else {
+            return;
+        };
+
+        if self.pem {
+            let value = Self::pem_value(&cert.chain);
+            if let Ok(value) = HeaderValue::from_str(&query_escape(&value)) {
+                headers.insert(CLIENT_CERT_HEADER, value);
+            }
+        }
+
+        if let Some(info) = &self.info
+            && let Some(value) = self.info_value(info, &cert.chain)
+            && let Ok(value) = HeaderValue::from_str(&query_escape(&value))
+        {
+            headers.insert(CLIENT_CERT_INFO_HEADER, value);
+        }
+    }
+
+    /// Each certificate as single-line base64 DER, comma-separated
+    fn pem_value(chain: &[Vec<u8>]) -> String {
+        chain
+            .iter()
+            .map(|der| STANDARD.encode(der))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Selected fields of each certificate, comma-separated. `None` when no
+    /// field is selected or no certificate could be parsed.
+    fn info_value(&self, info: &TlsClientCertInfo, chain: &[Vec<u8>]) -> Option<String> {
+        let entries: Vec<String> = chain
+            .iter()
+            .filter_map(|der| x509_parser::parse_x509_certificate(der).ok())
+            .map(|(_, cert)| self.cert_info(info, &cert))
+            .filter(|entry| !entry.is_empty())
+            .collect();
+
+        (!entries.is_empty()).then(|| entries.join(","))
+    }
+
+    fn cert_info(&self, info: &TlsClientCertInfo, cert: &X509Certificate<'_>) -> String {
+        let mut fields = Vec::new();
+
+        if self.subject.any() {
+            fields.push(format!("Subject=\"{}\"", self.subject.format(cert.subject())));
+        }
+        if self.issuer.any() {
+            fields.push(format!("Issuer=\"{}\"", self.issuer.format(cert.issuer())));
+        }
+        if info.serial_number {
+            fields.push(format!("SerialNumber=\"{}\"", cert.serial));
+        }
+        if info.not_before {
+            fields.push(format!("NB=\"{}\"", cert.validity().not_before.timestamp()));
+        }
+        if info.not_after {
+            fields.push(format!("NA=\"{}\"", cert.validity().not_after.timestamp()));
+        }
+        if info.sans {
+            fields.push(format!("SAN=\"{}\"", sans(cert).join(",")));
+        }
+
+        fields.join(";")
+    }
+}
+
+/// DNS names, then email addresses, IP addresses, and URIs
+fn sans(cert: &X509Certificate<'_>) -> Vec<String> {
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Vec::new();
+    };
+    let names = &san.value.general_names;
+
+    let dns = names.iter().filter_map(|name| match name {
+        GeneralName::DNSName(dns) => Some(dns.to_string()),
+        _ => None,
+    });
+    let emails = names.iter().filter_map(|name| match name {
+        GeneralName::RFC822Name(email) => Some(email.to_string()),
+        _ => None,
+    });
+    let ips = names.iter().filter_map(|name| match name {
+        GeneralName::IPAddress(bytes) => ip_to_string(bytes),
+        _ => None,
+    });
+    let uris = names.iter().filter_map(|name| match name {
+        GeneralName::URI(uri) => Some(uri.to_string()),
+        _ => None,
+    });
+
+    dns.chain(emails).chain(ips).chain(uris).collect()
+}
+
+fn ip_to_string(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes).ok().map(|b| std::net::Ipv4Addr::from(b).to_string()),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(|b| std::net::Ipv6Addr::from(b).to_string()),
+        _ => None,
+    }
+}
+
+/// URL query escaping (spaces become `+`)
+fn query_escape(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{TlsClientCertIssuer, TlsClientCertSubject};
+
+    /// CN=client.example.com with every subject attribute and DNS, email,
+    /// and IP SANs; serial 4660, valid 2026-01-01 to 2027-01-01
+    const CLIENT_CERT: &str = "-----BEGIN CERTIFICATE-----\n\
+MIICZDCCAgugAwIBAgICEjQwCgYIKoZIzj0EAwIwPDELMAkGA1UEBhMCVVMxEzAR\n\
+BgNVBAoMCkV4YW1wbGUgQ0ExGDAWBgNVBAMMD0V4YW1wbGUgUm9vdCBDQTAeFw0y\n\
+NjAxMDEwMDAwMDBaFw0yNzAxMDEwMDAwMDBaMIGtMQswCQYDVQQGEwJVUzETMBEG\n\
+A1UECAwKQ2FsaWZvcm5pYTEWMBQGA1UEBwwNU2FuIEZyYW5jaXNjbzEUMBIGA1UE\n\
+CgwLRXhhbXBsZSBPcmcxFDASBgNVBAsMC0VuZ2luZWVyaW5nMRswGQYDVQQDDBJj\n\
+bGllbnQuZXhhbXBsZS5jb20xDzANBgNVBAUTBkEtMTIzNDEXMBUGCgmSJomT8ixk\n\
+ARkWB2V4YW1wbGUwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAAQhOn02PVvE1ywu\n\
+ZdeU96jAl0b8ycTtLVJiLr+2nO5vO7CGqb6ZscMSkXHBc+nxtKlHtSN4nqAjt9K+\n\
+MxYpcKEHo4GKMIGHMEUGA1UdEQQ+MDyCEmNsaWVudC5leGFtcGxlLmNvbYIPYXBp\n\
+LmV4YW1wbGUuY29tgQ9vcHNAZXhhbXBsZS5jb22HBAoAAAcwHQYDVR0OBBYEFIgG\n\
+WghA64VbOYzjVGxOJHHLoA3EMB8GA1UdIwQYMBaAFOTjdKQmzumOavtfoUhE47Ho\n\
+sePeMAoGCCqGSM49BAMCA0cAMEQCIGh78GaSjN88lqcfKmhteSYqw7Rys5zNDqG+\n\
+MMk4ew0pAiBnCYA6aDLhDSL8c5ZnTCfikoBma31zTFHj0FjN1DCt9w==\n\
+-----END CERTIFICATE-----\n";
+
+    /// Self-signed C=US, O=Example CA, CN=Example Root CA, issuer of CLIENT_CERT
+    const CA_CERT: &str = "-----BEGIN CERTIFICATE-----\n\
+MIIBzTCCAXOgAwIBAgIUEl7WJM5EgNbnoybE9PNnI2eV3bowCgYIKoZIzj0EAwIw\n\
+PDELMAkGA1UEBhMCVVMxEzARBgNVBAoMCkV4YW1wbGUgQ0ExGDAWBgNVBAMMD0V4\n\
+YW1wbGUgUm9vdCBDQTAeFw0yNjAxMDEwMDAwMDBaFw0zNjAxMDEwMDAwMDBaMDwx\n\
+CzAJBgNVBAYTAlVTMRMwEQYDVQQKDApFeGFtcGxlIENBMRgwFgYDVQQDDA9FeGFt\n\
+cGxlIFJvb3QgQ0EwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATv0ldav2QQNTaY\n\
+iwCSw66LFgdioT8idsr6r8hvn6HFaHSuXLJfLGBNEa5QS+N8LKL7JS8FY5p75KSq\n\
+qkWTQuS7o1MwUTAdBgNVHQ4EFgQU5ON0pCbO6Y5q+1+hSETjseix494wHwYDVR0j\n\
+BBgwFoAU5ON0pCbO6Y5q+1+hSETjseix494wDwYDVR0TAQH/BAUwAwEB/zAKBggq\n\
+hkjOPQQDAgNIADBFAiAITzsRrzd/rFIWaJ1+ZCLdNp0YJFwSDG7r0H43PPTq3gIh\n\
+AO4VbfDy2cf3YwzZlasRV4QCv3a5eVbvKZqhRjPudI5p\n\
+-----END CERTIFICATE-----\n";
+
+    fn middleware(config: PassTlsClientCertConfig) -> PassTlsClientCertMiddleware {
+        PassTlsClientCertMiddleware::new(&config)
+    }
+
+    fn client_cert(pems: &[&str]) -> ClientCertInfo {
+        let chain: Vec<_> = pems
+            .iter()
+            .map(|pem| rustls_pemfile::certs(&mut pem.as_bytes()).next().unwrap().unwrap())
+            .collect();
+        ClientCertInfo::from_chain(&chain)
+    }
+
+    /// Header value with the URL escaping undone
+    fn header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+        let value = headers.get(name)?.to_str().unwrap();
+        Some(
+            url::form_urlencoded::parse(format!("v={}", value).as_bytes())
+                .next()
+                .unwrap()
+                .1
+                .into_owned(),
+        )
+    }
+
+    /// PEM body on one line, as carried in the header
+    fn pem_body(pem: &str) -> String {
+        pem.lines().filter(|line| !line.starts_with("-----")).collect()
+    }
+
+    #[test]
+    fn test_pem_chain_forwarded_and_spoofed_headers_removed() {
+        let mw = middleware(PassTlsClientCertConfig { pem: true, info: None });
+        let cert = client_cert(&[CLIENT_CERT, CA_CERT]);
+        let mut headers = HeaderMap::new();
+        headers.insert(CLIENT_CERT_INFO_HEADER, HeaderValue::from_static("spoofed"));
+        mw.apply(&mut headers, Some(&cert));
+
+        assert_eq!(
+            header(&headers, CLIENT_CERT_HEADER).unwrap(),
+            format!("{},{}", pem_body(CLIENT_CERT), pem_body(CA_CERT))
+        );
+        // Base64 '+' and '/' are escaped
+        assert!(!headers.get(CLIENT_CERT_HEADER).unwrap().to_str().unwrap().contains('+'));
+        assert!(headers.get(CLIENT_CERT_INFO_HEADER).is_none());
+
+        // Without a client certificate, client-sent copies are still dropped
+        let mut headers = HeaderMap::new();
+        headers.insert(CLIENT_CERT_HEADER, HeaderValue::from_static("spoofed"));
+        mw.apply(&mut headers, None);
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn test_info_with_all_fields() {
+        let mw = middleware(PassTlsClientCertConfig {
+            pem: false,
+            info: Some(TlsClientCertInfo {
+                not_after: true,
+                not_before: true,
+                sans: true,
+                serial_number: true,
+                subject: Some(TlsClientCertSubject {
+                    country: true,
+                    province: true,
+                    locality: true,
+                    organization: true,
+                    organizational_unit: true,
+                    common_name: true,
+                    serial_number: true,
+                    domain_component: true,
+                }),
+                issuer: Some(TlsClientCertIssuer {
+                    country: true,
+                    organization: true,
+                    common_name: true,
+                    ..Default::default()
+                }),
+            }),
+        });
+        let mut headers = HeaderMap::new();
+        mw.apply(&mut headers, Some(&client_cert(&[CLIENT_CERT])));
+
+        assert_eq!(
+            header(&headers, CLIENT_CERT_INFO_HEADER).unwrap(),
+            "Subject=\"C=US,ST=California,L=San Francisco,O=Example Org,OU=Engineering,\
+CN=client.example.com,SerialNumber=A-1234,DC=example\";\
+Issuer=\"C=US,O=Example CA,CN=Example Root CA\";\
+SerialNumber=\"4660\";NB=\"1767225600\";NA=\"1798761600\";\
+SAN=\"client.example.com,api.example.com,ops@example.com,10.0.0.7\""
+        );
+        assert!(headers.get(CLIENT_CERT_HEADER).is_none());
+    }
+
+    #[test]
+    fn test_info_with_selected_fields_per_certificate() {
+        let mw = middleware(PassTlsClientCertConfig {
+            pem: true,
+            info: Some(TlsClientCertInfo {
+                not_after: true,
+                subject: Some(TlsClientCertSubject { common_name: true, ..Default::default() }),
+                ..Default::default()
+            }),
+        });
+        let mut headers = HeaderMap::new();
+        mw.apply(&mut headers, Some(&client_cert(&[CLIENT_CERT, CA_CERT])));
+
+        assert_eq!(
+            header(&headers, CLIENT_CERT_INFO_HEADER).unwrap(),
+            "Subject=\"CN=client.example.com\";NA=\"1798761600\",\
+Subject=\"CN=Example Root CA\";NA=\"2082758400\""
+        );
+        assert_eq!(
+            headers.get(CLIENT_CERT_INFO_HEADER).unwrap(),
+            "Subject%3D%22CN%3Dclient.example.com%22%3BNA%3D%221798761600%22%2C\
+Subject%3D%22CN%3DExample+Root+CA%22%3BNA%3D%222082758400%22"
+        );
+        assert!(headers.get(CLIENT_CERT_HEADER).is_some());
+    }
+}
@@ -1,8 +1,10 @@
 use crate::config::{AddPrefixConfig, ReplacePathConfig, ReplacePathRegexConfig, StripPrefixConfig, StripPrefixRegexConfig};
 use regex::Regex;
-use hyper::Uri;
+use hyper::header::HeaderValue;
+use hyper::{Request, Uri};
 
-/// StripPrefix middleware removes the specified prefixes from the request URL path
+/// StripPrefix middleware removes the specified prefixes from the request URL path.
+/// The longest matching prefix wins.
 pub struct StripPrefixMiddleware {
     prefixes: Vec<String>,
     force_slash: bool,
@@ -11,42 +13,57 @@ pub struct StripPrefixMiddleware {
 impl StripPrefixMiddleware {
     /// Create from config with the list of prefixes to strip.
     pub fn new(config: StripPrefixConfig) -> Self {
+        let mut prefixes = config.prefixes;
+        // Longest first, so `/api/v1` is stripped whole rather than as `/api`
+        prefixes.sort_by_key(|prefix| std::cmp::Reverse(prefix.len()));
         Self {
-            prefixes: config.prefixes,
+            prefixes,
             force_slash: config.force_slash,
         }
     }
 
     /// Transform the URI by stripping the prefix
-    /// Returns the new URI and optionally the original path for X-Forwarded-Prefix header
+    /// Returns the new URI and the stripped prefix for the X-Forwarded-Prefix header
     pub fn transform_uri(&self, uri: &Uri) -> Option<(Uri, String)> {
         let path = uri.path();
 
         for prefix in &self.prefixes {
-            if path.starts_with(prefix) {
-                let original_prefix = prefix.clone();
-                let mut new_path = path.strip_prefix(prefix).unwrap_or(path).to_string();
-
-                // Ensure path starts with /
-                if new_path.is_empty() || (!new_path.starts_with('/') && self.force_slash) {
-                    new_path = format!("/{}", new_path);
-                }
-                if new_path.is_empty() {
-                    new_path = "/".to_string();
-                }
-
-                // Rebuild URI with new path
-                if let Some(new_uri) = rebuild_uri_with_path(uri, &new_path) {
-                    return Some((new_uri, original_prefix));
-                }
+            let Some(rest) = path.strip_prefix(prefix.as_str()) else {
+                continue;
+            };
+
+            // Whatever remains keeps a leading slash. Stripping the whole path
+            // leaves `/`, or an empty path without forceSlash.
+            let new_path = if rest.is_empty() {
+                if self.force_slash { "/" } else { "" }.to_string()
+            } else if rest.starts_with('/') {
+                rest.to_string()
+            } else {
+                format!("/{}", rest)
+            };
+
+            if let Some(new_uri) = rebuild_uri_with_path(uri, &new_path) {
+                return Some((new_uri, prefix.clone()));
             }
         }
 
         None
     }
+
+    /// Strip the prefix from the request and record it in X-Forwarded-Prefix
+    pub fn apply<B>(&self, req: &mut Request<B>) {
+        if let Some((new_uri, prefix)) = self.transform_uri(req.uri()) {
+            *req.uri_mut() = new_uri;
+            if let Ok(val) = HeaderValue::from_str(&prefix) {
+                req.headers_mut().insert("X-Forwarded-Prefix", val);
+            }
+        }
+    }
 }
 
-/// StripPrefixRegex middleware removes prefixes matching regex patterns
+/// StripPrefixRegex middleware removes prefixes matching regex patterns.
+/// Patterns are tried in order; the first one matching at the start of the
+/// path wins.
 pub struct StripPrefixRegexMiddleware {
     patterns: Vec<Regex>,
 }
@@ -69,8 +86,8 @@ impl StripPrefixRegexMiddleware {
 
         for pattern in &self.patterns {
             if let Some(mat) = pattern.find(path) {
-                // Only match at the start of the path
-                if mat.start() == 0 {
+                // Only a non-empty match at the start of the path is a prefix
+                if mat.start() == 0 && !mat.is_empty() {
                     let matched = mat.as_str().to_string();
                     let mut new_path = path[mat.end()..].to_string();
 
@@ -88,9 +105,21 @@ impl StripPrefixRegexMiddleware {
 
         None
     }
+
+    /// Strip the prefix from the request and record it in X-Forwarded-Prefix
+    pub fn apply<B>(&self, req: &mut Request<B>) {
+        if let Some((new_uri, prefix)) = self.transform_uri(req.uri()) {
+            *req.uri_mut() = new_uri;
+            if let Ok(val) = HeaderValue::from_str(&prefix) {
+                req.headers_mut().insert("X-Forwarded-Prefix", val);
+            }
+        }
+    }
 }
 
-/// AddPrefix middleware adds a prefix to the request URL path
+/// AddPrefix middleware adds a prefix to the request URL path. It leaves
+/// X-Forwarded-Prefix alone: the added prefix is internal to the backend, not
+/// part of the URL the client sees.
 pub struct AddPrefixMiddleware {
     prefix: String,
 }
@@ -109,6 +138,13 @@ impl AddPrefixMiddleware {
         let new_path = format!("{}{}", self.prefix, path);
         rebuild_uri_with_path(uri, &new_path)
     }
+
+    /// Prepend the prefix to the request path
+    pub fn apply<B>(&self, req: &mut Request<B>) {
+        if let Some(new_uri) = self.transform_uri(req.uri()) {
+            *req.uri_mut() = new_uri;
+        }
+    }
 }
 
 /// ReplacePath middleware replaces the entire request URL path
@@ -147,17 +183,29 @@ impl ReplacePathRegexMiddleware {
         })
     }
 
-    /// Transform the URI using regex replacement
-    /// Returns the new URI and the original path
+    /// Transform the URI using regex replacement. Only the path is matched
+    /// and rewritten; `$1`/`${name}` expand to capture groups and the query
+    /// string is kept. Returns the new URI and the original path.
     pub fn transform_uri(&self, uri: &Uri) -> Option<(Uri, String)> {
         let path = uri.path();
-        let original_path = path.to_string();
-        let new_path = self.pattern.replace(path, &self.replacement).to_string();
+        if !self.pattern.is_match(path) {
+            return None;
+        }
 
-        if new_path != original_path {
-            rebuild_uri_with_path(uri, &new_path).map(|u| (u, original_path))
-        } else {
-            None
+        let mut new_path = self.pattern.replace_all(path, &self.replacement).into_owned();
+        if !new_path.starts_with('/') {
+            new_path.insert(0, '/');
+        }
+        rebuild_uri_with_path(uri, &new_path).map(|u| (u, path.to_string()))
+    }
+
+    /// Rewrite the request path and record the original in X-Replaced-Path
+    pub fn apply<B>(&self, req: &mut Request<B>) {
+        if let Some((new_uri, original)) = self.transform_uri(req.uri()) {
+            *req.uri_mut() = new_uri;
+            if let Ok(val) = HeaderValue::from_str(&original) {
+                req.headers_mut().insert("X-Replaced-Path", val);
+            }
         }
     }
 }
@@ -236,6 +284,72 @@ mod tests {
         assert_eq!(new_uri.path(), "/");
     }
 
+    #[test]
+    fn test_strip_prefix_force_slash_off_leaves_empty_path() {
+        let middleware = StripPrefixMiddleware::new(StripPrefixConfig {
+            prefixes: vec!["/api".to_string()],
+            force_slash: false,
+        });
+
+        let (new_uri, _) = middleware.transform_uri(&"/api".parse().unwrap()).unwrap();
+        assert_eq!(new_uri.path(), "");
+        let (new_uri, _) = middleware.transform_uri(&"/api?x=1".parse().unwrap()).unwrap();
+        assert_eq!(new_uri.path_and_query().unwrap().as_str(), "?x=1");
+
+        // Only a fully stripped path is left empty
+        let (new_uri, _) = middleware.transform_uri(&"/api/users".parse().unwrap()).unwrap();
+        assert_eq!(new_uri.path(), "/users");
+        let (new_uri, _) = middleware.transform_uri(&"/apiusers".parse().unwrap()).unwrap();
+        assert_eq!(new_uri.path(), "/users");
+
+        // With forceSlash, the default, the path becomes `/`
+        let config = StripPrefixConfig {
+            prefixes: vec!["/api".to_string()],
+            force_slash: true,
+        };
+        let (new_uri, _) = StripPrefixMiddleware::new(config)
+            .transform_uri(&"/api".parse().unwrap())
+            .unwrap();
+        assert_eq!(new_uri.path(), "/");
+    }
+
+    #[test]
+    fn test_strip_prefix_sets_forwarded_prefix() {
+        let config = StripPrefixConfig {
+            prefixes: vec!["/app".to_string()],
+            force_slash: true,
+        };
+        let middleware = StripPrefixMiddleware::new(config);
+
+        let mut req = Request::builder().uri("/app/assets/main.css").body(()).unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.uri().path(), "/assets/main.css");
+        assert_eq!(req.headers()["X-Forwarded-Prefix"], "/app");
+
+        let mut req = Request::builder().uri("/other").body(()).unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.uri().path(), "/other");
+        assert!(req.headers().get("X-Forwarded-Prefix").is_none());
+    }
+
+    #[test]
+    fn test_strip_prefix_longest_match_first() {
+        let config = StripPrefixConfig {
+            prefixes: vec!["/api".to_string(), "/api/v1".to_string(), "/api/v1/admin".to_string()],
+            force_slash: true,
+        };
+        let middleware = StripPrefixMiddleware::new(config);
+
+        let (new_uri, prefix) = middleware.transform_uri(&"/api/v1/users".parse().unwrap()).unwrap();
+        assert_eq!((new_uri.path(), prefix.as_str()), ("/users", "/api/v1"));
+
+        let (new_uri, prefix) = middleware.transform_uri(&"/api/v1/admin/keys".parse().unwrap()).unwrap();
+        assert_eq!((new_uri.path(), prefix.as_str()), ("/keys", "/api/v1/admin"));
+
+        let (new_uri, prefix) = middleware.transform_uri(&"/api/v2/users".parse().unwrap()).unwrap();
+        assert_eq!((new_uri.path(), prefix.as_str()), ("/v2/users", "/api"));
+    }
+
     #[test]
     fn test_strip_prefix_regex() {
         let config = StripPrefixRegexConfig {
@@ -253,6 +367,56 @@ mod tests {
         assert_eq!(new_uri.path(), "/items");
     }
 
+    #[test]
+    fn test_strip_prefix_regex_sets_forwarded_prefix() {
+        let config = StripPrefixRegexConfig {
+            regex: vec![r"^/tenants/[a-z]+".to_string()],
+        };
+        let middleware = StripPrefixRegexMiddleware::new(config).unwrap();
+
+        let mut req = Request::builder()
+            .uri("/tenants/acme/orders?page=2")
+            .body(())
+            .unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.uri().path(), "/orders");
+        assert_eq!(req.uri().query(), Some("page=2"));
+        assert_eq!(req.headers()["X-Forwarded-Prefix"], "/tenants/acme");
+
+        // Unmatched requests are left alone
+        let mut req = Request::builder().uri("/orders").body(()).unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.uri().path(), "/orders");
+        assert!(req.headers().get("X-Forwarded-Prefix").is_none());
+    }
+
+    #[test]
+    fn test_strip_prefix_regex_tried_in_order() {
+        let config = StripPrefixRegexConfig {
+            regex: vec![
+                r"^/api/v\d+".to_string(),
+                r"^/api".to_string(),
+                // Matches, but not at the start of the path
+                r"/internal".to_string(),
+            ],
+        };
+        let middleware = StripPrefixRegexMiddleware::new(config).unwrap();
+
+        let (new_uri, matched) = middleware.transform_uri(&"/api/v3/users".parse().unwrap()).unwrap();
+        assert_eq!((new_uri.path(), matched.as_str()), ("/users", "/api/v3"));
+
+        let (new_uri, matched) = middleware.transform_uri(&"/api/users".parse().unwrap()).unwrap();
+        assert_eq!((new_uri.path(), matched.as_str()), ("/users", "/api"));
+
+        assert!(middleware.transform_uri(&"/svc/internal/x".parse().unwrap()).is_none());
+
+        // Invalid patterns are rejected up front
+        assert!(StripPrefixRegexMiddleware::new(StripPrefixRegexConfig {
+            regex: vec![r"^/ok".to_string(), "(".to_string()],
+        })
+        .is_none());
+    }
+
     #[test]
     fn test_add_prefix() {
         let config = AddPrefixConfig {
@@ -265,6 +429,27 @@ mod tests {
         assert_eq!(new_uri.path(), "/api/v1/users/123");
     }
 
+    #[test]
+    fn test_add_prefix_leaves_forwarded_prefix_alone() {
+        let middleware = AddPrefixMiddleware::new(AddPrefixConfig {
+            prefix: "/internal".to_string(),
+        });
+
+        let mut req = Request::builder().uri("/users").body(()).unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.uri().path(), "/internal/users");
+        assert!(req.headers().get("X-Forwarded-Prefix").is_none());
+
+        // A prefix stripped earlier in the chain is kept
+        let mut req = Request::builder()
+            .uri("/users")
+            .header("X-Forwarded-Prefix", "/app")
+            .body(())
+            .unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.headers()["X-Forwarded-Prefix"], "/app");
+    }
+
     #[test]
     fn test_add_prefix_preserves_query() {
         let config = AddPrefixConfig {
@@ -305,6 +490,25 @@ mod tests {
         assert_eq!(original, "/api/users/123");
     }
 
+    #[test]
+    fn test_replace_path_regex_preserves_query() {
+        let config = ReplacePathRegexConfig {
+            regex: r"^/users/(?P<id>\d+)/profile$".to_string(),
+            replacement: "/profiles/${id}".to_string(),
+        };
+        let middleware = ReplacePathRegexMiddleware::new(config).unwrap();
+
+        let mut req = Request::builder()
+            .uri("/users/42/profile?fields=name&users=7")
+            .body(())
+            .unwrap();
+        middleware.apply(&mut req);
+        assert_eq!(req.uri().path(), "/profiles/42");
+        // The query isn't matched or rewritten
+        assert_eq!(req.uri().query(), Some("fields=name&users=7"));
+        assert_eq!(req.headers()["X-Replaced-Path"], "/users/42/profile");
+    }
+
     #[test]
     fn test_replace_path_regex_no_match() {
         let config = ReplacePathRegexConfig {
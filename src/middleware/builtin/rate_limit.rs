@@ -1,10 +1,13 @@
 use crate::config::RateLimitConfig;
+use crate::middleware::ip_strategy::ClientIpResolver;
 use crate::store::Store;
 use dashmap::DashMap;
+use hyper::header::{HeaderMap, HeaderValue, HOST, RETRY_AFTER};
+use hyper::Request;
 use std::net::IpAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::{debug, warn};
 
 /// High-performance token bucket rate limiter with optional distributed backing
@@ -16,8 +19,13 @@ use tracing::{debug, warn};
 /// - Local cache handles most requests (sub-microsecond)
 /// - Background sync to distributed store every ~100ms
 /// - ~1-5% variance in rate limits across cluster (acceptable tradeoff)
+///
+/// [`check`](Self::check) is the strict alternative: with a store attached it
+/// also counts every request in the store, so the limit holds cluster-wide.
 pub struct RateLimitMiddleware {
     config: RateLimitConfig,
+    /// Client IP resolution per `sourceCriterion.ipStrategy`
+    client_ip: ClientIpResolver,
     /// Local token buckets for fast path
     buckets: DashMap<String, TokenBucket>,
     /// Distributed store (optional)
@@ -48,8 +56,9 @@ impl TokenBucket {
         }
     }
 
+    /// Take one token, refilling `average` tokens per `period` first.
     #[inline]
-    fn try_acquire(&self, tokens_per_sec: u64, burst: u64) -> bool {
+    fn try_acquire(&self, average: u64, period: Duration, burst: u64) -> bool {
         let now_nanos = self.epoch.elapsed().as_nanos() as u64;
         let max_tokens = burst * 1000;
 
@@ -57,12 +66,14 @@ impl TokenBucket {
         for _ in 0..8 {
             let last = self.last_update.load(Ordering::Relaxed);
 
-            // Calculate tokens to add based on time elapsed
-            let elapsed_millis = (now_nanos.saturating_sub(last)) / 1_000_000;
-            let tokens_to_add = (elapsed_millis * tokens_per_sec) / 1000;
+            // Tokens (in thousandths) earned since the last update, computed in
+            // nanoseconds so high rates and short gaps don't round down to zero
+            let elapsed_nanos = now_nanos.saturating_sub(last) as u128;
+            let tokens_to_add = elapsed_nanos * average as u128 * 1000 / period.as_nanos().max(1);
+            let tokens_to_add = u64::try_from(tokens_to_add).unwrap_or(u64::MAX);
 
             let current = self.tokens.load(Ordering::Relaxed);
-            let new_tokens = (current + tokens_to_add).min(max_tokens);
+            let new_tokens = current.saturating_add(tokens_to_add).min(max_tokens);
 
             // Not enough tokens
             if new_tokens < 1000 {
@@ -111,6 +122,7 @@ impl RateLimitMiddleware {
     /// Create a local-only rate limiter from config.
     pub fn new(config: RateLimitConfig) -> Self {
         Self {
+            client_ip: Self::client_ip_resolver(&config),
             config,
             buckets: DashMap::with_capacity(10000),
             store: None,
@@ -123,6 +135,7 @@ impl RateLimitMiddleware {
     /// Create with distributed store backing
     pub fn with_store(config: RateLimitConfig, store: Arc<dyn Store>) -> Self {
         Self {
+            client_ip: Self::client_ip_resolver(&config),
             config,
             buckets: DashMap::with_capacity(10000),
             store: Some(store),
@@ -132,6 +145,113 @@ impl RateLimitMiddleware {
         }
     }
 
+    fn client_ip_resolver(config: &RateLimitConfig) -> ClientIpResolver {
+        ClientIpResolver::new(
+            config
+                .source_criterion
+                .as_ref()
+                .and_then(|c| c.ip_strategy.as_ref()),
+        )
+    }
+
+    /// Returns true if checks go through a shared store.
+    pub fn is_distributed(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Resolve the rate limit key for a request from the source criterion.
+    ///
+    /// Defaults to the client IP, resolved from `forwarded_for` by `ipStrategy`
+    /// (pass `None` for untrusted peers); the header and host criteria fall
+    /// back to it when absent.
+    pub fn source_key<B>(
+        &self,
+        req: &Request<B>,
+        remote_ip: IpAddr,
+        forwarded_for: Option<&str>,
+    ) -> String {
+        let client_ip = || self.client_ip.resolve(forwarded_for, remote_ip).to_string();
+        let Some(criterion) = &self.config.source_criterion else {
+            return client_ip();
+        };
+
+        let value = if criterion.ip_strategy.is_some() {
+            None
+        } else if let Some(name) = &criterion.request_header_name {
+            req.headers().get(name.as_str()).and_then(|v| v.to_str().ok())
+        } else if criterion.request_host {
+            req.headers()
+                .get(HOST)
+                .and_then(|v| v.to_str().ok())
+                .or_else(|| req.uri().host())
+        } else {
+            None
+        };
+
+        match value {
+            Some(value) if !value.is_empty() => value.to_string(),
+            _ => client_ip(),
+        }
+    }
+
+    /// Count a request against `key` and report the figures for response headers.
+    ///
+    /// The local bucket applies `burst`. With a store attached, a request it lets
+    /// through must also fit the store's count of `average` per `period`, which
+    /// holds across every node sharing the store; if the store is unreachable the
+    /// local bucket decides alone.
+    pub async fn check(&self, key: &str) -> RateLimitResult {
+        let local = self.check_local(key);
+        let Some(store) = &self.store else {
+            return local;
+        };
+        if !local.allowed {
+            return local;
+        }
+
+        let window_secs = self.config.period.as_std().as_secs().max(1);
+        match store.rate_limit_check(key, self.config.average, window_secs).await {
+            Ok((allowed, remaining, reset_time)) => {
+                if !allowed {
+                    debug!("Distributed rate limit exceeded for key: {}", key);
+                }
+                RateLimitResult {
+                    allowed,
+                    remaining: remaining.min(local.remaining),
+                    reset_time: if allowed { reset_time.max(local.reset_time) } else { reset_time },
+                    limit: self.config.average,
+                }
+            }
+            Err(e) => {
+                warn!("Distributed rate limit check failed: {}, using local bucket", e);
+                local
+            }
+        }
+    }
+
+    /// Take a token from the local bucket for `key`.
+    fn check_local(&self, key: &str) -> RateLimitResult {
+        let burst = self.config.burst.max(1);
+        let bucket = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| TokenBucket::new(self.config.burst.max(self.config.average)));
+        let period = self.config.period.as_std();
+        let allowed = bucket.try_acquire(self.config.average, period, burst);
+        let remaining = bucket.remaining();
+
+        // The bucket refills `average` tokens per period; reset is when it is full again
+        let missing = burst.saturating_sub(remaining).max(1) as u128;
+        let refill_nanos = missing * period.as_nanos() / self.config.average.max(1) as u128;
+        let refill_millis = u64::try_from(refill_nanos.div_ceil(1_000_000)).unwrap_or(u64::MAX);
+        RateLimitResult {
+            allowed,
+            remaining,
+            reset_time: epoch_millis().saturating_add(refill_millis),
+            limit: self.config.average,
+        }
+    }
+
     /// Check if request is allowed (fast path - local only)
     #[inline]
     pub fn is_allowed(&self, ip: IpAddr) -> bool {
@@ -146,7 +266,11 @@ impl RateLimitMiddleware {
             TokenBucket::new(self.config.burst.max(self.config.average))
         });
 
-        let allowed = bucket.try_acquire(self.config.average, self.config.burst.max(1));
+        let allowed = bucket.try_acquire(
+            self.config.average,
+            self.config.period.as_std(),
+            self.config.burst.max(1),
+        );
 
         // Schedule async sync if we have a distributed store
         if self.store.is_some() {
@@ -320,18 +444,334 @@ impl DistributedRateLimiter {
 
 /// Result of a rate limit check
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct RateLimitResult {
+    /// Whether the request fits within the limit.
     pub allowed: bool,
+    /// Requests left in the current window.
     pub remaining: u64,
+    /// When the window resets, in milliseconds since the Unix epoch.
     pub reset_time: u64,
+    /// Requests allowed per window.
     pub limit: u64,
 }
 
+impl RateLimitResult {
+    /// Set `X-RateLimit-Remaining` and `X-RateLimit-Reset` (Unix seconds), plus
+    /// `Retry-After` (seconds) when the request was rejected.
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        let reset_secs = self.reset_time.div_ceil(1000);
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(reset_secs));
+
+        if !self.allowed {
+            let retry_after = self.reset_time.saturating_sub(epoch_millis()).div_ceil(1000).max(1);
+            headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+    }
+}
+
+fn epoch_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Duration as ConfigDuration;
+    use crate::config::{Duration as ConfigDuration, IpStrategy, SourceCriterion};
+    use crate::store::{HealthStatus, LocalStore, NodeInfo, NodeStatus, StoreError, StoreResult};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicBool;
+
+    fn test_config(average: u64) -> RateLimitConfig {
+        RateLimitConfig {
+            average,
+            burst: 0,
+            period: ConfigDuration::from_secs(1),
+            source_criterion: None,
+            distributed: false,
+        }
+    }
+
+    fn by_source(source_criterion: SourceCriterion) -> RateLimitMiddleware {
+        RateLimitMiddleware::new(RateLimitConfig {
+            source_criterion: Some(source_criterion),
+            ..test_config(1)
+        })
+    }
+
+    /// `LocalStore` whose rate limit calls fail while `failing` is set
+    #[derive(Default)]
+    struct FlakyStore {
+        inner: LocalStore,
+        failing: AtomicBool,
+    }
+
+    impl FlakyStore {
+        fn check_available(&self) -> StoreResult<()> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err(StoreError::Connection("refused".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Store for FlakyStore {
+        async fn rate_limit_check(&self, key: &str, limit: u64, window_secs: u64) -> StoreResult<(bool, u64, u64)> {
+            self.check_available()?;
+            self.inner.rate_limit_check(key, limit, window_secs).await
+        }
+        async fn rate_limit_remaining(&self, key: &str, limit: u64) -> StoreResult<u64> {
+            self.check_available()?;
+            self.inner.rate_limit_remaining(key, limit).await
+        }
+        async fn sticky_session_get(&self, service: &str, session_id: &str) -> StoreResult<Option<String>> {
+            self.inner.sticky_session_get(service, session_id).await
+        }
+        async fn sticky_session_set(&self, service: &str, session_id: &str, server_url: &str, ttl: Duration) -> StoreResult<()> {
+            self.inner.sticky_session_set(service, session_id, server_url, ttl).await
+        }
+        async fn sticky_session_delete(&self, service: &str, session_id: &str) -> StoreResult<()> {
+            self.inner.sticky_session_delete(service, session_id).await
+        }
+        async fn health_get(&self, service: &str, server_url: &str) -> StoreResult<Option<HealthStatus>> {
+            self.inner.health_get(service, server_url).await
+        }
+        async fn health_set(&self, service: &str, server_url: &str, status: &HealthStatus) -> StoreResult<()> {
+            self.inner.health_set(service, server_url, status).await
+        }
+        async fn health_get_all(&self, service: &str) -> StoreResult<HashMap<String, HealthStatus>> {
+            self.inner.health_get_all(service).await
+        }
+        async fn circuit_breaker_fail(&self, service: &str, window_secs: u64) -> StoreResult<u64> {
+            self.inner.circuit_breaker_fail(service, window_secs).await
+        }
+        async fn circuit_breaker_success(&self, service: &str) -> StoreResult<()> {
+            self.inner.circuit_breaker_success(service).await
+        }
+        async fn circuit_breaker_failures(&self, service: &str) -> StoreResult<u64> {
+            self.inner.circuit_breaker_failures(service).await
+        }
+        async fn node_register(&self, info: &NodeInfo) -> StoreResult<()> {
+            self.inner.node_register(info).await
+        }
+        async fn node_heartbeat(&self, node_id: &str, connections: u64) -> StoreResult<()> {
+            self.inner.node_heartbeat(node_id, connections).await
+        }
+        async fn node_set_status(&self, node_id: &str, status: NodeStatus) -> StoreResult<()> {
+            self.inner.node_set_status(node_id, status).await
+        }
+        async fn node_get(&self, node_id: &str) -> StoreResult<Option<NodeInfo>> {
+            self.inner.node_get(node_id).await
+        }
+        async fn node_list(&self) -> StoreResult<Vec<NodeInfo>> {
+            self.inner.node_list().await
+        }
+        async fn node_deregister(&self, node_id: &str) -> StoreResult<()> {
+            self.inner.node_deregister(node_id).await
+        }
+        async fn config_version(&self) -> StoreResult<u64> {
+            self.inner.config_version().await
+        }
+        async fn config_get(&self) -> StoreResult<Option<String>> {
+            self.inner.config_get().await
+        }
+        async fn config_set(&self, content: &str) -> StoreResult<u64> {
+            self.inner.config_set(content).await
+        }
+        async fn subscribe_config_changes(&self) -> StoreResult<tokio::sync::broadcast::Receiver<()>> {
+            self.inner.subscribe_config_changes().await
+        }
+        async fn subscribe_health_changes(&self) -> StoreResult<tokio::sync::broadcast::Receiver<(String, String, HealthStatus)>> {
+            self.inner.subscribe_health_changes().await
+        }
+        async fn subscribe_drain_events(&self) -> StoreResult<tokio::sync::broadcast::Receiver<String>> {
+            self.inner.subscribe_drain_events().await
+        }
+        async fn acme_challenge_set(&self, token: &str, auth: &str, ttl: Duration) -> StoreResult<()> {
+            self.inner.acme_challenge_set(token, auth, ttl).await
+        }
+        async fn acme_challenge_get(&self, token: &str) -> StoreResult<Option<String>> {
+            self.inner.acme_challenge_get(token).await
+        }
+        async fn acme_challenge_delete(&self, token: &str) -> StoreResult<()> {
+            self.inner.acme_challenge_delete(token).await
+        }
+        async fn digest_nonce_use(&self, nonce: &str, nc: u64, ttl: Duration) -> StoreResult<bool> {
+            self.inner.digest_nonce_use(nonce, nc, ttl).await
+        }
+        async fn leader_acquire(&self, task: &str, node_id: &str, ttl: Duration) -> StoreResult<bool> {
+            self.inner.leader_acquire(task, node_id, ttl).await
+        }
+        async fn leader_release(&self, task: &str, node_id: &str) -> StoreResult<()> {
+            self.inner.leader_release(task, node_id).await
+        }
+        async fn leader_get(&self, task: &str) -> StoreResult<Option<String>> {
+            self.inner.leader_get(task).await
+        }
+        async fn health_check(&self) -> StoreResult<()> {
+            self.inner.health_check().await
+        }
+        fn store_type(&self) -> &'static str {
+            "flaky"
+        }
+    }
+
+    #[test]
+    fn test_source_key_from_criterion() {
+        let remote: IpAddr = "192.0.2.1".parse().unwrap();
+        let xff = Some("203.0.113.9, 10.0.0.2");
+        let req = Request::builder()
+            .uri("/")
+            .header("host", "api.example.com")
+            .header("x-api-key", "key-123")
+            .body(())
+            .unwrap();
+
+        let limiter = RateLimitMiddleware::new(test_config(1));
+        assert_eq!(limiter.source_key(&req, remote, xff), "192.0.2.1");
+
+        let limiter = by_source(SourceCriterion {
+            ip_strategy: Some(IpStrategy { depth: 2, ..Default::default() }),
+            ..Default::default()
+        });
+        assert_eq!(limiter.source_key(&req, remote, xff), "203.0.113.9");
+        // Untrusted peers' X-Forwarded-For is never passed in
+        assert_eq!(limiter.source_key(&req, remote, None), "192.0.2.1");
+
+        let limiter = by_source(SourceCriterion {
+            ip_strategy: Some(IpStrategy {
+                depth: 0,
+                excluded_ips: vec!["10.0.0.0/8".to_string()],
+                ipv6_subnet: None,
+            }),
+            ..Default::default()
+        });
+        assert_eq!(limiter.source_key(&req, remote, xff), "203.0.113.9");
+
+        let limiter = by_source(SourceCriterion {
+            request_header_name: Some("X-Api-Key".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(limiter.source_key(&req, remote, xff), "key-123");
+        let bare = Request::builder().uri("/").body(()).unwrap();
+        assert_eq!(limiter.source_key(&bare, remote, xff), "192.0.2.1");
+
+        let limiter = by_source(SourceCriterion {
+            request_host: true,
+            ..Default::default()
+        });
+        assert_eq!(limiter.source_key(&req, remote, xff), "api.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_store_enforces_limit_per_key_across_instances() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let rate = RateLimitConfig {
+            burst: 2,
+            period: ConfigDuration::from_secs(60),
+            distributed: true,
+            ..test_config(2)
+        };
+        let node_a = RateLimitMiddleware::with_store(rate.clone(), Arc::clone(&store));
+        let node_b = RateLimitMiddleware::with_store(rate, Arc::clone(&store));
+        assert!(node_a.is_distributed());
+
+        let first = node_a.check("api:10.0.0.1").await;
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+
+        // The second node sees the first node's request
+        assert!(node_b.check("api:10.0.0.1").await.allowed);
+        let rejected = node_a.check("api:10.0.0.1").await;
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.remaining, 0);
+        assert!(rejected.reset_time > epoch_millis());
+
+        // Other keys have their own budget
+        assert!(node_b.check("api:10.0.0.2").await.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_rejection_sets_rate_limit_headers() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        // The burst leaves room, so the store's window decides
+        let config = RateLimitConfig {
+            burst: 6,
+            period: ConfigDuration::from_secs(3),
+            ..test_config(5)
+        };
+        let limiter = RateLimitMiddleware::with_store(config, store);
+
+        // Allowed requests report the budget but no Retry-After
+        let mut headers = HeaderMap::new();
+        limiter.check("api:10.0.0.1").await.apply_headers(&mut headers);
+        assert_eq!(headers["x-ratelimit-remaining"], "4");
+        assert!(headers.get(RETRY_AFTER).is_none());
+
+        for _ in 0..4 {
+            assert!(limiter.check("api:10.0.0.1").await.allowed);
+        }
+        let result = limiter.check("api:10.0.0.1").await;
+        assert!(!result.allowed);
+
+        let mut headers = HeaderMap::new();
+        result.apply_headers(&mut headers);
+        assert_eq!(headers["x-ratelimit-remaining"], "0");
+        assert_eq!(
+            headers["x-ratelimit-reset"].to_str().unwrap().parse::<u64>().unwrap(),
+            result.reset_time.div_ceil(1000)
+        );
+        // The window resets 3s after it opened
+        assert_eq!(headers[RETRY_AFTER], "3");
+    }
+
+    #[tokio::test]
+    async fn test_store_failure_falls_back_to_local_bucket() {
+        let store = Arc::new(FlakyStore::default());
+        let limiter = RateLimitMiddleware::with_store(
+            RateLimitConfig { burst: 2, ..test_config(20) },
+            Arc::clone(&store) as Arc<dyn Store>,
+        );
+        store.failing.store(true, Ordering::Relaxed);
+
+        assert!(limiter.check("api:10.0.0.1").await.allowed);
+        assert!(limiter.check("api:10.0.0.1").await.allowed);
+        let rejected = limiter.check("api:10.0.0.1").await;
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.remaining, 0);
+
+        // Once the store recovers it counts requests again
+        store.failing.store(false, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(limiter.check("api:10.0.0.1").await.allowed);
+        assert_eq!(store.inner.rate_limit_remaining("api:10.0.0.1", 20).await.unwrap(), 19);
+    }
+
+    #[tokio::test]
+    async fn test_store_backed_check_applies_burst() {
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let limiter = RateLimitMiddleware::with_store(
+            RateLimitConfig {
+                burst: 2,
+                period: ConfigDuration::from_secs(60),
+                distributed: true,
+                ..test_config(10)
+            },
+            store,
+        );
+
+        // The store's window still has room, but the burst is spent
+        assert!(limiter.check("api:10.0.0.1").await.allowed);
+        assert!(limiter.check("api:10.0.0.1").await.allowed);
+        let rejected = limiter.check("api:10.0.0.1").await;
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.remaining, 0);
+    }
 
     #[test]
     fn test_rate_limit_allows_within_limit() {
@@ -340,6 +780,7 @@ mod tests {
             burst: 10,
             period: ConfigDuration::from_secs(1),
             source_criterion: None,
+            distributed: false,
         };
         let limiter = RateLimitMiddleware::new(config);
         let ip: IpAddr = "127.0.0.1".parse().unwrap();
@@ -357,6 +798,7 @@ mod tests {
             burst: 2,
             period: ConfigDuration::from_secs(1),
             source_criterion: None,
+            distributed: false,
         };
         let limiter = RateLimitMiddleware::new(config);
         let ip: IpAddr = "127.0.0.1".parse().unwrap();
@@ -369,6 +811,34 @@ mod tests {
         assert!(!limiter.is_allowed(ip));
     }
 
+    #[test]
+    fn test_high_rate_refills_within_milliseconds() {
+        // Over 1000 per second a token is due in well under a millisecond
+        let config = RateLimitConfig {
+            burst: 1,
+            ..test_config(2000)
+        };
+        let limiter = RateLimitMiddleware::new(config);
+
+        assert!(limiter.is_allowed_by_key("api"));
+        assert!(!limiter.is_allowed_by_key("api"));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(limiter.is_allowed_by_key("api"));
+    }
+
+    #[tokio::test]
+    async fn test_reset_time_follows_rate_and_period() {
+        // Two tokens per minute: the spent one is back in 30s
+        let limiter = RateLimitMiddleware::new(RateLimitConfig {
+            burst: 1,
+            period: ConfigDuration::from_secs(60),
+            ..test_config(2)
+        });
+        let result = limiter.check("api").await;
+        let wait = result.reset_time - epoch_millis();
+        assert!((29_000..=30_000).contains(&wait), "{}", wait);
+    }
+
     #[test]
     fn test_rate_limit_by_key() {
         let config = RateLimitConfig {
@@ -376,6 +846,7 @@ mod tests {
             burst: 5,
             period: ConfigDuration::from_secs(1),
             source_criterion: None,
+            distributed: false,
         };
         let limiter = RateLimitMiddleware::new(config);
 
@@ -400,6 +871,7 @@ mod tests {
             burst: 100,
             period: ConfigDuration::from_secs(1),
             source_criterion: None,
+            distributed: false,
         };
         let limiter = Arc::new(RateLimitMiddleware::new(config));
 
@@ -438,6 +910,7 @@ mod tests {
             burst: 10,
             period: ConfigDuration::from_secs(1),
             source_criterion: None,
+            distributed: false,
         };
         let limiter = RateLimitMiddleware::new(config);
         let ip: IpAddr = "127.0.0.1".parse().unwrap();
@@ -0,0 +1,143 @@
+use crate::config::RedirectRegexConfig;
+use hyper::header::{HeaderValue, HOST, LOCATION};
+use hyper::{Method, Request, Response, StatusCode};
+use regex::Regex;
+
+/// Middleware redirecting requests whose full URL matches a regex
+pub struct RedirectRegexMiddleware {
+    regex: Regex,
+    replacement: String,
+    permanent: bool,
+}
+
+impl RedirectRegexMiddleware {
+    /// Create from config. Returns `None` if the regex pattern is invalid.
+    pub fn new(config: RedirectRegexConfig) -> Option<Self> {
+        Regex::new(&config.regex).ok().map(|regex| Self {
+            regex,
+            replacement: config.replacement,
+            permanent: config.permanent,
+        })
+    }
+
+    /// Full request URL the regex is matched against, e.g. `https://example.com/path?q=1`
+    pub fn request_url<B>(req: &Request<B>, is_tls: bool) -> String {
+        let scheme = if is_tls { "https" } else { "http" };
+        let host = req
+            .headers()
+            .get(HOST)
+            .and_then(|h| h.to_str().ok())
+            .or(req.uri().authority().map(|a| a.as_str()))
+            .unwrap_or("localhost");
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+
+        format!("{}://{}{}", scheme, host, path_and_query)
+    }
+
+    /// Build the redirect response, or `None` when the URL doesn't match.
+    /// `$1` and `${name}` in the replacement expand to capture groups.
+    pub fn build_redirect<B>(&self, req: &Request<B>, is_tls: bool) -> Option<Response<()>> {
+        let url = Self::request_url(req, is_tls);
+        if !self.regex.is_match(&url) {
+            return None;
+        }
+        let location = self.regex.replace_all(&url, self.replacement.as_str());
+
+        let mut response = Response::builder().status(self.status_code(req.method()));
+        if let Ok(location_value) = HeaderValue::from_str(&location) {
+            response = response.header(LOCATION, location_value);
+        }
+        Some(response.body(()).unwrap())
+    }
+
+    /// 301/302 for GET and HEAD; 308/307 otherwise, so clients repeat the
+    /// method (and body) at the new location
+    pub fn status_code(&self, method: &Method) -> StatusCode {
+        let keeps_method = !matches!(*method, Method::GET | Method::HEAD);
+        match (self.permanent, keeps_method) {
+            (true, false) => StatusCode::MOVED_PERMANENTLY,
+            (true, true) => StatusCode::PERMANENT_REDIRECT,
+            (false, false) => StatusCode::FOUND,
+            (false, true) => StatusCode::TEMPORARY_REDIRECT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn middleware(regex: &str, replacement: &str, permanent: bool) -> RedirectRegexMiddleware {
+        RedirectRegexMiddleware::new(RedirectRegexConfig {
+            regex: regex.to_string(),
+            replacement: replacement.to_string(),
+            permanent,
+        })
+        .unwrap()
+    }
+
+    fn request(method: Method, host: &str, path: &str) -> Request<()> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(HOST, host)
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_capture_groups_expanded() {
+        let mw = middleware(
+            r"^https?://(?P<host>[^/]+)/old/(.*)$",
+            "https://${host}/new/$2",
+            true,
+        );
+        let req = request(Method::GET, "example.com", "/old/page?id=7");
+
+        let response = mw.build_redirect(&req, false).unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[LOCATION], "https://example.com/new/page?id=7");
+    }
+
+    #[test]
+    fn test_non_matching_request_passes_through() {
+        let mw = middleware(r"^http://example\.com/old/(.*)", "http://example.com/new/$1", true);
+
+        assert!(mw.build_redirect(&request(Method::GET, "example.com", "/other"), false).is_none());
+        // The scheme is part of the matched URL
+        assert!(mw.build_redirect(&request(Method::GET, "example.com", "/old/x"), true).is_none());
+    }
+
+    #[test]
+    fn test_temporary_and_permanent_status() {
+        let temporary = middleware(r"^http://(.*)", "https://$1", false);
+        let permanent = middleware(r"^http://(.*)", "https://$1", true);
+        let get = request(Method::GET, "example.com", "/");
+        let post = request(Method::POST, "example.com", "/form");
+
+        assert_eq!(temporary.build_redirect(&get, false).unwrap().status(), StatusCode::FOUND);
+        assert_eq!(
+            temporary.build_redirect(&post, false).unwrap().status(),
+            StatusCode::TEMPORARY_REDIRECT
+        );
+        assert_eq!(permanent.build_redirect(&get, false).unwrap().status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            permanent.build_redirect(&post, false).unwrap().status(),
+            StatusCode::PERMANENT_REDIRECT
+        );
+    }
+
+    #[test]
+    fn test_invalid_regex_rejected() {
+        assert!(RedirectRegexMiddleware::new(RedirectRegexConfig {
+            regex: "(unclosed".to_string(),
+            replacement: String::new(),
+            permanent: false,
+        })
+        .is_none());
+    }
+}
@@ -0,0 +1,248 @@
+//! Regex substitutions on response bodies.
+
+use crate::config::ReplaceResponseBodyConfig;
+use crate::proxy::is_event_stream;
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, StreamExt};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, BodyStream, Full, StreamBody};
+use hyper::body::Frame;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, TRANSFER_ENCODING};
+use hyper::Response;
+use regex::bytes::Regex;
+use tracing::{debug, warn};
+
+/// Rewrites buffered response bodies with regex substitutions
+pub struct ReplaceResponseBodyMiddleware {
+    replacements: Vec<(Regex, Vec<u8>)>,
+    /// Lowercased media types, without parameters
+    content_types: Vec<String>,
+    max_body_bytes: usize,
+}
+
+impl ReplaceResponseBodyMiddleware {
+    /// Create from config. Invalid regexes are logged and skipped.
+    pub fn new(config: &ReplaceResponseBodyConfig) -> Self {
+        let replacements = config
+            .replacements
+            .iter()
+            .filter_map(|r| match Regex::new(&r.regex) {
+                Ok(regex) => Some((regex, r.replacement.clone().into_bytes())),
+                Err(e) => {
+                    warn!("Invalid replaceResponseBody regex '{}': {}", r.regex, e);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            replacements,
+            content_types: config
+                .content_types
+                .iter()
+                .map(|t| t.trim().to_ascii_lowercase())
+                .collect(),
+            max_body_bytes: usize::try_from(config.max_body_bytes).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Whether a response is a candidate: an allowed, uncompressed media type
+    /// whose declared length (if any) fits under the cap. Event streams never are.
+    pub fn should_rewrite(&self, headers: &HeaderMap) -> bool {
+        if self.replacements.is_empty() || is_event_stream(headers) {
+            return false;
+        }
+
+        let encoded = headers
+            .get(CONTENT_ENCODING)
+            .is_some_and(|v| !v.as_bytes().eq_ignore_ascii_case(b"identity"));
+        if encoded {
+            return false;
+        }
+
+        let media_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase());
+        if !media_type.is_some_and(|t| self.content_types.contains(&t)) {
+            return false;
+        }
+
+        let declared_len = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+        declared_len.is_none_or(|len| len <= self.max_body_bytes)
+    }
+
+    /// Apply the substitutions to a body
+    pub fn replace(&self, body: &[u8]) -> Vec<u8> {
+        let mut out = body.to_vec();
+        for (regex, replacement) in &self.replacements {
+            if let std::borrow::Cow::Owned(replaced) = regex.replace_all(&out, replacement.as_slice()) {
+                out = replaced;
+            }
+        }
+        out
+    }
+
+    /// Rewrite a response. The body is buffered up to the cap; if it grows past
+    /// the cap (or carries trailers) what was read is replayed and the rest
+    /// streams through unchanged.
+    pub async fn rewrite(
+        &self,
+        resp: Response<BoxBody<Bytes, hyper::Error>>,
+    ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+        if !self.should_rewrite(resp.headers()) {
+            return Ok(resp);
+        }
+
+        let (mut parts, mut body) = resp.into_parts();
+        let mut buffered: Vec<Bytes> = Vec::new();
+        let mut size = 0usize;
+
+        while let Some(frame) = body.frame().await {
+            let frame = frame?;
+            let within_cap = frame
+                .data_ref()
+                .is_some_and(|data| size + data.len() <= self.max_body_bytes);
+            if !within_cap {
+                debug!("Response body exceeds replaceResponseBody limit, passing through");
+                let replay = stream::iter(
+                    buffered
+                        .into_iter()
+                        .map(Frame::data)
+                        .chain(std::iter::once(frame))
+                        .map(Ok),
+                );
+                let passthrough = StreamBody::new(replay.chain(BodyStream::new(body)));
+                return Ok(Response::from_parts(parts, BodyExt::boxed(passthrough)));
+            }
+            if let Ok(data) = frame.into_data() {
+                size += data.len();
+                buffered.push(data);
+            }
+        }
+
+        let mut original = BytesMut::with_capacity(size);
+        for chunk in buffered {
+            original.extend_from_slice(&chunk);
+        }
+        let replaced = Bytes::from(self.replace(&original));
+
+        parts.headers.remove(TRANSFER_ENCODING);
+        if replaced.as_ref() != original.as_ref() {
+            // A strong validator for the original body no longer describes this one
+            parts.headers.remove(ETAG);
+        }
+        parts.headers.insert(CONTENT_LENGTH, HeaderValue::from(replaced.len()));
+
+        Ok(Response::from_parts(
+            parts,
+            Full::new(replaced).map_err(|never| match never {}).boxed(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BodyReplacement;
+
+    /// Rewrites `http://backend:8080` to `https://example.com`
+    fn test_config() -> ReplaceResponseBodyConfig {
+        ReplaceResponseBodyConfig {
+            replacements: vec![BodyReplacement {
+                regex: "http://backend:8080".to_string(),
+                replacement: "https://example.com".to_string(),
+            }],
+            content_types: vec!["text/html".to_string(), "application/json".to_string()],
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+
+    fn response(content_type: &str, chunks: &[&'static str]) -> Response<BoxBody<Bytes, hyper::Error>> {
+        let frames: Vec<Result<Frame<Bytes>, hyper::Error>> = chunks
+            .iter()
+            .map(|c| Ok(Frame::data(Bytes::from_static(c.as_bytes()))))
+            .collect();
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .header(ETAG, "\"v1\"")
+            .body(BodyExt::boxed(StreamBody::new(stream::iter(frames))))
+            .unwrap()
+    }
+
+    async fn body_text(resp: Response<BoxBody<Bytes, hyper::Error>>) -> String {
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_substitutes_and_fixes_content_length() {
+        let mw = ReplaceResponseBodyMiddleware::new(&test_config());
+        let resp = response(
+            "text/html; charset=utf-8",
+            &["<a href=\"http://backend:8080/a\">", "<img src=\"http://backend:8080/b.png\">"],
+        );
+
+        let rewritten = mw.rewrite(resp).await.unwrap();
+        let expected = "<a href=\"https://example.com/a\"><img src=\"https://example.com/b.png\">";
+        assert_eq!(
+            rewritten.headers().get(CONTENT_LENGTH).unwrap(),
+            &expected.len().to_string()
+        );
+        assert!(rewritten.headers().get(ETAG).is_none());
+        assert_eq!(body_text(rewritten).await, expected);
+
+        let groups = ReplaceResponseBodyMiddleware::new(&ReplaceResponseBodyConfig {
+            replacements: vec![BodyReplacement {
+                regex: r#""id":(\d+)"#.to_string(),
+                replacement: r#""id":"$1""#.to_string(),
+            }],
+            ..test_config()
+        });
+        assert_eq!(groups.replace(br#"{"id":42}"#), br#"{"id":"42"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_other_content_types_pass_through() {
+        let mw = ReplaceResponseBodyMiddleware::new(&test_config());
+        let resp = response("image/svg+xml", &["http://backend:8080/logo"]);
+        let untouched = mw.rewrite(resp).await.unwrap();
+        assert!(untouched.headers().get(ETAG).is_some());
+        assert_eq!(body_text(untouched).await, "http://backend:8080/logo");
+
+        let mut gzipped = response("text/html", &["http://backend:8080/"]);
+        gzipped.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        assert_eq!(body_text(mw.rewrite(gzipped).await.unwrap()).await, "http://backend:8080/");
+
+        // Event streams are never buffered, even when listed
+        let sse = ReplaceResponseBodyMiddleware::new(&ReplaceResponseBodyConfig {
+            content_types: vec!["text/event-stream".to_string()],
+            ..test_config()
+        });
+        let events = response("text/event-stream", &["data: http://backend:8080/\n\n"]);
+        assert!(!sse.should_rewrite(events.headers()));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_streams_through_unchanged() {
+        let mw = ReplaceResponseBodyMiddleware::new(&ReplaceResponseBodyConfig {
+            max_body_bytes: 32,
+            ..test_config()
+        });
+
+        // Discovered while reading: the chunks already buffered are replayed in order
+        let chunks = ["http://backend:8080/1 ", "http://backend:8080/2 ", "http://backend:8080/3"];
+        let streamed = mw.rewrite(response("text/html", &chunks)).await.unwrap();
+        assert!(streamed.headers().get(CONTENT_LENGTH).is_none());
+        assert_eq!(body_text(streamed).await, chunks.concat());
+
+        // Declared up front: not read at all
+        let mut declared = response("text/html", &["http://backend:8080/"]);
+        declared.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(64u64));
+        assert!(!mw.should_rewrite(declared.headers()));
+    }
+}
@@ -0,0 +1,149 @@
+//! Request ID generation and propagation.
+
+use crate::config::RequestIdConfig;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Request, Response};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Longest client-supplied ID that is reused when incoming IDs are trusted
+const MAX_INCOMING_ID_LEN: usize = 128;
+
+/// The request's ID, stored in request and response extensions for access logs and traces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Generate a new random (UUID v4) ID
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    /// The ID as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Request ID middleware
+pub struct RequestIdMiddleware {
+    header_name: HeaderName,
+    trust_incoming: bool,
+}
+
+impl RequestIdMiddleware {
+    /// Create from config. An invalid header name falls back to X-Request-Id.
+    pub fn new(config: &RequestIdConfig) -> Self {
+        let header_name = HeaderName::try_from(config.header_name.as_str()).unwrap_or_else(|_| {
+            warn!("Invalid requestId headerName '{}', using X-Request-Id", config.header_name);
+            HeaderName::from_static("x-request-id")
+        });
+
+        Self {
+            header_name,
+            trust_incoming: config.trust_incoming,
+        }
+    }
+
+    /// Pick the ID for a request: the client's when trusted and well-formed, otherwise a new one
+    pub fn resolve(&self, headers: &HeaderMap) -> RequestId {
+        self.trust_incoming
+            .then(|| headers.get(&self.header_name))
+            .flatten()
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.len() <= MAX_INCOMING_ID_LEN)
+            .map(|id| RequestId(id.to_string()))
+            .unwrap_or_else(RequestId::generate)
+    }
+
+    /// Assign the request its ID: set the header sent to the backend and the extension
+    pub fn apply_to_request<B>(&self, req: &mut Request<B>) -> RequestId {
+        let id = self.resolve(req.headers());
+        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+            req.headers_mut().insert(self.header_name.clone(), value);
+        }
+        req.extensions_mut().insert(id.clone());
+        id
+    }
+
+    /// Echo the ID to the client and record it on the response for logging
+    pub fn apply_to_response<B>(&self, id: RequestId, resp: &mut Response<B>) {
+        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+            resp.headers_mut().insert(self.header_name.clone(), value);
+        }
+        resp.extensions_mut().insert(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> RequestIdConfig {
+        RequestIdConfig {
+            header_name: "X-Request-Id".to_string(),
+            trust_incoming: false,
+        }
+    }
+
+    fn request(id: Option<&str>) -> Request<()> {
+        let mut builder = Request::builder();
+        if let Some(id) = id {
+            builder = builder.header("x-request-id", id);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_generates_unique_ids() {
+        let mw = RequestIdMiddleware::new(&test_config());
+        let mut first = request(None);
+        let mut second = request(None);
+        let a = mw.apply_to_request(&mut first);
+        let b = mw.apply_to_request(&mut second);
+
+        assert_ne!(a, b);
+        assert!(Uuid::parse_str(a.as_str()).is_ok());
+        assert_eq!(first.headers().get("x-request-id").unwrap(), a.as_str());
+        assert_eq!(first.extensions().get::<RequestId>(), Some(&a));
+    }
+
+    #[test]
+    fn test_incoming_id_reused_only_when_trusted() {
+        let trusted = RequestIdMiddleware::new(&RequestIdConfig {
+            trust_incoming: true,
+            ..test_config()
+        });
+        assert_eq!(trusted.apply_to_request(&mut request(Some("abc-123"))).as_str(), "abc-123");
+
+        let untrusted = RequestIdMiddleware::new(&test_config());
+        let mut req = request(Some("abc-123"));
+        let id = untrusted.apply_to_request(&mut req);
+        assert_ne!(id.as_str(), "abc-123");
+        assert_eq!(req.headers().get("x-request-id").unwrap(), id.as_str());
+
+        // Empty or oversized IDs are replaced even when trusted
+        assert_ne!(trusted.resolve(request(Some(" ")).headers()).as_str(), " ");
+        let long = "x".repeat(MAX_INCOMING_ID_LEN + 1);
+        assert_ne!(trusted.resolve(request(Some(&long)).headers()).as_str(), long);
+    }
+
+    #[test]
+    fn test_id_echoed_in_response() {
+        let mw = RequestIdMiddleware::new(&RequestIdConfig {
+            header_name: "X-Correlation-Id".to_string(),
+            trust_incoming: true,
+        });
+        let mut req = Request::builder()
+            .header("x-correlation-id", "corr-7")
+            .body(())
+            .unwrap();
+        let id = mw.apply_to_request(&mut req);
+
+        let mut resp = Response::new(());
+        mw.apply_to_response(id, &mut resp);
+        assert_eq!(resp.headers().get("x-correlation-id").unwrap(), "corr-7");
+        assert_eq!(resp.extensions().get::<RequestId>().map(RequestId::as_str), Some("corr-7"));
+    }
+}
@@ -1,22 +1,46 @@
 use crate::config::RetryConfig;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::Arc;
 use std::time::Duration;
+use tracing::warn;
+
+/// Retry policy attached to a request by the retry middleware; the forwarding
+/// endpoint retries failed attempts with backoff
+#[derive(Clone)]
+pub struct RequestRetry(pub Arc<RetryMiddleware>);
 
 /// Retry middleware with exponential backoff
+#[derive(Clone)]
 pub struct RetryMiddleware {
     max_attempts: u32,
     initial_interval: Duration,
     max_interval: Duration,
     multiplier: f64,
+    jitter: bool,
+    status_codes: Vec<u16>,
+    retry_non_idempotent: bool,
+    rng: SystemRandom,
 }
 
 impl RetryMiddleware {
-    /// Create from config with max attempts and initial backoff interval.
+    /// Create from config with max attempts and backoff settings.
     pub fn new(config: RetryConfig) -> Self {
+        let multiplier = if config.multiplier.is_finite() && config.multiplier >= 1.0 {
+            config.multiplier
+        } else {
+            warn!("Invalid retry multiplier {}, using 1.0", config.multiplier);
+            1.0
+        };
+
         Self {
             max_attempts: config.attempts.max(1),
             initial_interval: config.initial_interval.as_std(),
-            max_interval: Duration::from_secs(30), // Cap at 30 seconds
-            multiplier: 2.0,
+            max_interval: config.max_interval.as_std(),
+            multiplier,
+            jitter: config.jitter,
+            status_codes: config.status_codes,
+            retry_non_idempotent: config.retry_non_idempotent,
+            rng: SystemRandom::new(),
         }
     }
 
@@ -26,7 +50,7 @@ impl RetryMiddleware {
         self.max_attempts
     }
 
-    /// Calculate delay for a given attempt (0-indexed)
+    /// Calculate delay for a given attempt (0-indexed), before jitter
     #[inline]
     pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
         if attempt == 0 {
@@ -34,13 +58,48 @@ impl RetryMiddleware {
         }
 
         let delay_ms = self.initial_interval.as_millis() as f64
-            * self.multiplier.powi(attempt.saturating_sub(1) as i32);
+            * self.multiplier.powf(f64::from(attempt - 1));
 
+        // Saturates on overflow; the cap applies either way
         let delay = Duration::from_millis(delay_ms as u64);
         delay.min(self.max_interval)
     }
 
-    /// Check if a status code is retryable
+    /// Delay before the given attempt with jitter applied: a random point
+    /// between half and all of [`delay_for_attempt`](Self::delay_for_attempt)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.delay_for_attempt(attempt);
+        if !self.jitter || delay.is_zero() {
+            return delay;
+        }
+        let mut bytes = [0u8; 8];
+        if self.rng.fill(&mut bytes).is_err() {
+            return delay;
+        }
+        let fraction = u64::from_le_bytes(bytes) as f64 / u64::MAX as f64;
+        delay / 2 + (delay / 2).mul_f64(fraction)
+    }
+
+    /// Whether requests with this method may be retried
+    #[inline]
+    pub fn retries_method(&self, method: &str) -> bool {
+        self.retry_non_idempotent || Self::is_idempotent_method(method)
+    }
+
+    /// Whether a response with this status is retried
+    #[inline]
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.status_codes.contains(&status)
+    }
+
+    /// Delay before the next attempt after `attempts` attempts, the last ending
+    /// with `status` (`None` for a connection error), or `None` to stop
+    pub fn next_delay(&self, attempts: u32, status: Option<u16>) -> Option<Duration> {
+        (attempts < self.max_attempts && status.is_none_or(|status| self.retries_status(status)))
+            .then(|| self.backoff(attempts))
+    }
+
+    /// Check if a status code is retryable by default
     #[inline]
     pub fn is_retryable_status(status: u16) -> bool {
         matches!(status, 502 | 503 | 504 | 408 | 429)
@@ -70,8 +129,8 @@ impl RetryMiddleware {
     #[inline]
     pub fn should_retry(&self, attempt: u32, status: u16, method: &str) -> bool {
         attempt < self.max_attempts
-            && Self::is_retryable_status(status)
-            && Self::is_idempotent_method(method)
+            && self.retries_status(status)
+            && self.retries_method(method)
     }
 
     /// Should retry based on error and method
@@ -79,7 +138,7 @@ impl RetryMiddleware {
     pub fn should_retry_error(&self, attempt: u32, error: &str, method: &str) -> bool {
         attempt < self.max_attempts
             && Self::is_retryable_error(error)
-            && Self::is_idempotent_method(method)
+            && self.retries_method(method)
     }
 }
 
@@ -107,7 +166,7 @@ impl Iterator for RetryIterator {
             return None;
         }
 
-        let delay = self.middleware.delay_for_attempt(self.current_attempt);
+        let delay = self.middleware.backoff(self.current_attempt);
         self.current_attempt += 1;
         Some(delay)
     }
@@ -122,6 +181,11 @@ mod tests {
         RetryConfig {
             attempts: 3,
             initial_interval: ConfigDuration::from_millis(100),
+            max_interval: ConfigDuration::from_secs(30),
+            multiplier: 2.0,
+            jitter: false,
+            status_codes: vec![408, 429, 502, 503, 504],
+            retry_non_idempotent: false,
         }
     }
 
@@ -147,6 +211,7 @@ mod tests {
         let config = RetryConfig {
             attempts: 20,
             initial_interval: ConfigDuration::from_millis(1000),
+            ..make_config()
         };
         let middleware = RetryMiddleware::new(config);
 
@@ -209,4 +274,69 @@ mod tests {
         assert_eq!(delays[1], Duration::from_millis(100));
         assert_eq!(delays[2], Duration::from_millis(200));
     }
+
+    #[test]
+    fn test_intervals_grow_geometrically_up_to_max() {
+        let middleware = RetryMiddleware::new(RetryConfig {
+            initial_interval: ConfigDuration::from_millis(50),
+            max_interval: ConfigDuration::from_secs(1),
+            multiplier: 3.0,
+            ..make_config()
+        });
+
+        let delays: Vec<Duration> = (1..=6).map(|attempt| middleware.delay_for_attempt(attempt)).collect();
+        assert_eq!(
+            delays,
+            [50, 150, 450, 1000, 1000, 1000].map(Duration::from_millis).to_vec()
+        );
+        // Huge attempt counts saturate rather than overflow
+        assert_eq!(middleware.delay_for_attempt(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_half_interval() {
+        let middleware = RetryMiddleware::new(RetryConfig {
+            max_interval: ConfigDuration::from_secs(1),
+            jitter: true,
+            ..make_config()
+        });
+
+        for attempt in 1..=5 {
+            let base = middleware.delay_for_attempt(attempt);
+            for _ in 0..20 {
+                let delay = middleware.backoff(attempt);
+                assert!(delay >= base / 2 && delay <= base, "{:?} vs {:?}", delay, base);
+            }
+        }
+        assert_eq!(middleware.backoff(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_non_idempotent_methods_retried_only_when_opted_in() {
+        let default = RetryMiddleware::new(make_config());
+        assert!(!default.retries_method("POST"));
+        assert!(!default.should_retry(1, 502, "PATCH"));
+        assert!(default.should_retry(1, 502, "GET"));
+
+        let opted_in = RetryMiddleware::new(RetryConfig {
+            retry_non_idempotent: true,
+            ..make_config()
+        });
+        assert!(opted_in.retries_method("POST"));
+        assert!(opted_in.should_retry(1, 502, "POST"));
+    }
+
+    #[test]
+    fn test_next_delay_follows_status_codes() {
+        let middleware = RetryMiddleware::new(RetryConfig {
+            initial_interval: ConfigDuration::from_millis(10),
+            status_codes: vec![503],
+            ..make_config()
+        });
+
+        assert_eq!(middleware.next_delay(1, Some(503)), Some(Duration::from_millis(10)));
+        assert_eq!(middleware.next_delay(2, None), Some(Duration::from_millis(20)));
+        assert_eq!(middleware.next_delay(1, Some(502)), None);
+        assert_eq!(middleware.next_delay(3, Some(503)), None);
+    }
 }
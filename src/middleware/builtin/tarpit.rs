@@ -0,0 +1,226 @@
+//! Tarpit: hold flagged requests for a while before letting them through.
+
+use crate::config::TarpitConfig;
+use crate::middleware::ip_strategy::{parse_network, ClientIpResolver};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use ipnetwork::IpNetwork;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Tarpit middleware
+pub struct TarpitMiddleware {
+    source_range: Vec<IpNetwork>,
+    header: Option<(HeaderName, Option<HeaderValue>)>,
+    delay: Duration,
+    jitter: Duration,
+    max_concurrent: usize,
+    /// Requests currently held, shared with outstanding guards
+    active: Arc<AtomicUsize>,
+    client_ip: ClientIpResolver,
+    rng: SystemRandom,
+}
+
+/// A slot in the tarpit; releases it on drop, including when the request is cancelled
+pub struct TarpitGuard {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for TarpitGuard {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl TarpitMiddleware {
+    /// Create from config, parsing CIDR ranges and the flag header.
+    pub fn new(config: &TarpitConfig) -> Self {
+        let header = config.header_name.as_deref().and_then(|name| {
+            let Ok(name) = HeaderName::try_from(name) else {
+                warn!("Invalid tarpit headerName '{}'", name);
+                return None;
+            };
+            let value = config
+                .header_value
+                .as_deref()
+                .and_then(|v| HeaderValue::from_str(v).ok());
+            Some((name, value))
+        });
+
+        Self {
+            source_range: config
+                .source_range
+                .iter()
+                .filter_map(|s| parse_network(s))
+                .collect(),
+            header,
+            delay: config.delay.as_std(),
+            jitter: config.jitter.map(|j| j.as_std()).unwrap_or_default(),
+            max_concurrent: config.max_concurrent,
+            active: Arc::new(AtomicUsize::new(0)),
+            client_ip: ClientIpResolver::new(config.ip_strategy.as_ref()),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Client IP matched against `sourceRange`, per the configured `ipStrategy`
+    pub fn get_client_ip(&self, forwarded_for: Option<&str>, remote_addr: IpAddr) -> IpAddr {
+        self.client_ip.resolve(forwarded_for, remote_addr)
+    }
+
+    /// Whether a request is flagged by source range or header
+    pub fn is_flagged(&self, client_ip: Option<IpAddr>, headers: &HeaderMap) -> bool {
+        let by_ip = client_ip
+            .is_some_and(|ip| self.source_range.iter().any(|network| network.contains(ip)));
+        let by_header = self.header.as_ref().is_some_and(|(name, expected)| {
+            headers
+                .get_all(name)
+                .iter()
+                .any(|value| expected.as_ref().is_none_or(|expected| value == expected))
+        });
+        by_ip || by_header
+    }
+
+    /// Take a tarpit slot, or None when `maxConcurrent` requests are already held
+    pub fn try_acquire(&self) -> Option<TarpitGuard> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < self.max_concurrent).then_some(active + 1)
+            })
+            .ok()?;
+        Some(TarpitGuard {
+            active: Arc::clone(&self.active),
+        })
+    }
+
+    /// Requests currently held in the tarpit
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// How long to hold the next request: the delay plus random jitter
+    pub fn delay(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.delay;
+        }
+        let mut bytes = [0u8; 8];
+        if self.rng.fill(&mut bytes).is_err() {
+            return self.delay;
+        }
+        let fraction = u64::from_le_bytes(bytes) as f64 / u64::MAX as f64;
+        self.delay + self.jitter.mul_f64(fraction)
+    }
+
+    /// Hold a flagged request for [`delay`](Self::delay). The guard is dropped
+    /// when the wait ends or the future is cancelled.
+    pub async fn hold(&self, guard: TarpitGuard) {
+        tokio::time::sleep(self.delay()).await;
+        drop(guard);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Duration as ConfigDuration;
+    use std::time::Instant;
+
+    fn test_config() -> TarpitConfig {
+        TarpitConfig {
+            source_range: vec![],
+            header_name: None,
+            header_value: None,
+            delay: ConfigDuration::from_secs(10),
+            jitter: None,
+            max_concurrent: 100,
+            ip_strategy: None,
+        }
+    }
+
+    fn middleware(config: TarpitConfig) -> TarpitMiddleware {
+        TarpitMiddleware::new(&config)
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_flagged_by_source_range_or_header() {
+        let tarpit = middleware(TarpitConfig {
+            source_range: vec!["203.0.113.0/24".to_string()],
+            header_name: Some("X-Bot-Score".to_string()),
+            header_value: Some("high".to_string()),
+            ..test_config()
+        });
+        let mut headers = HeaderMap::new();
+        assert!(tarpit.is_flagged(Some(ip("203.0.113.9")), &headers));
+        assert!(!tarpit.is_flagged(Some(ip("198.51.100.1")), &headers));
+        assert!(!tarpit.is_flagged(None, &headers));
+
+        headers.insert("x-bot-score", HeaderValue::from_static("low"));
+        assert!(!tarpit.is_flagged(Some(ip("198.51.100.1")), &headers));
+        headers.append("x-bot-score", HeaderValue::from_static("high"));
+        assert!(tarpit.is_flagged(Some(ip("198.51.100.1")), &headers));
+
+        let any_value = middleware(TarpitConfig {
+            header_name: Some("X-Scraper".to_string()),
+            ..test_config()
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("x-scraper", HeaderValue::from_static("yes"));
+        assert!(any_value.is_flagged(None, &headers));
+    }
+
+    #[tokio::test]
+    async fn test_delay_is_applied_with_jitter() {
+        let tarpit = middleware(TarpitConfig {
+            delay: ConfigDuration::from_secs(2),
+            jitter: Some(ConfigDuration::from_secs(1)),
+            max_concurrent: 1,
+            ..test_config()
+        });
+        for _ in 0..20 {
+            let delay = tarpit.delay();
+            assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(3));
+        }
+
+        let exact = middleware(TarpitConfig {
+            delay: ConfigDuration::from_millis(50),
+            ..test_config()
+        });
+        assert_eq!(exact.delay(), Duration::from_millis(50));
+        let start = Instant::now();
+        exact.hold(exact.try_acquire().unwrap()).await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(exact.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_cap_rejects_excess() {
+        let tarpit = Arc::new(middleware(TarpitConfig {
+            delay: ConfigDuration::from_secs(30),
+            max_concurrent: 2,
+            ..test_config()
+        }));
+        let mut held: Vec<_> = (0..2)
+            .map(|_| {
+                let tarpit = Arc::clone(&tarpit);
+                let guard = tarpit.try_acquire().unwrap();
+                tokio::spawn(async move { tarpit.hold(guard).await })
+            })
+            .collect();
+
+        assert_eq!(tarpit.active(), 2);
+        assert!(tarpit.try_acquire().is_none());
+
+        // A cancelled request (client went away) frees its slot
+        held[0].abort();
+        let _ = held.remove(0).await;
+        assert_eq!(tarpit.active(), 1);
+        assert!(tarpit.try_acquire().is_some());
+    }
+}
@@ -0,0 +1,242 @@
+//! Client IP resolution from X-Forwarded-For, shared by the IP filter and rate limit middleware.
+
+use crate::config::{ForwardedHeaders, IpStrategy};
+use ipnetwork::{IpNetwork, Ipv6Network};
+use std::net::IpAddr;
+use tracing::warn;
+
+/// Decides whose X-Forwarded-For headers to believe, per entrypoint
+/// (`forwardedHeaders.trustedIps` / `insecure`)
+#[derive(Debug, Clone, Default)]
+pub struct ForwardedHeadersPolicy {
+    trusted_ips: Vec<IpNetwork>,
+    insecure: bool,
+}
+
+impl ForwardedHeadersPolicy {
+    /// Build a policy from entrypoint configuration. Without one, no peer is trusted.
+    pub fn from_config(config: Option<&ForwardedHeaders>) -> Self {
+        let Some(config) = config else {
+            return Self::default();
+        };
+
+        Self {
+            trusted_ips: config.trusted_ips.iter().filter_map(|ip| parse_network(ip)).collect(),
+            insecure: config.insecure,
+        }
+    }
+
+    /// Whether forwarded headers sent by `peer` are honored
+    pub fn is_trusted(&self, peer: IpAddr) -> bool {
+        self.insecure || self.trusted_ips.iter().any(|network| network.contains(peer))
+    }
+}
+
+/// Resolves the client IP for a request according to an [`IpStrategy`]
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpResolver {
+    /// False when neither `depth` nor `excludedIps` is set: X-Forwarded-For is ignored
+    use_forwarded: bool,
+    /// 1-based position from the right of X-Forwarded-For; 0 when not set
+    depth: usize,
+    /// Pre-parsed excluded IP networks (avoids parse_network() per request)
+    excluded_networks: Vec<IpNetwork>,
+    ipv6_subnet: Option<u8>,
+}
+
+impl ClientIpResolver {
+    /// Build a resolver; without a strategy the peer address is always used.
+    pub fn new(strategy: Option<&IpStrategy>) -> Self {
+        let Some(strategy) = strategy else {
+            return Self::default();
+        };
+
+        let ipv6_subnet = strategy.ipv6_subnet.and_then(|bits| match u8::try_from(bits) {
+            Ok(bits) if bits <= 128 => Some(bits),
+            _ => {
+                warn!("Ignoring invalid ipStrategy ipv6Subnet: {}", bits);
+                None
+            }
+        });
+
+        // As in Traefik, depth takes precedence and excludedIps is ignored
+        if strategy.depth > 0 && !strategy.excluded_ips.is_empty() {
+            warn!("ipStrategy sets both depth and excludedIps; excludedIps is ignored");
+        }
+        let excluded_networks: Vec<IpNetwork> = if strategy.depth > 0 {
+            Vec::new()
+        } else {
+            strategy
+                .excluded_ips
+                .iter()
+                .filter_map(|e| parse_network(e))
+                .collect()
+        };
+
+        Self {
+            use_forwarded: strategy.depth > 0 || !excluded_networks.is_empty(),
+            depth: strategy.depth as usize,
+            excluded_networks,
+            ipv6_subnet,
+        }
+    }
+
+    /// Resolve the client IP.
+    ///
+    /// With `depth` set, takes the `depth`th X-Forwarded-For entry from the
+    /// right (1 is the rightmost) and ignores `excludedIps`. With `depth` unset
+    /// (0) it takes the rightmost entry that isn't excluded, and with neither `depth` nor
+    /// `excludedIps` set it uses the peer address, as Traefik does. Falls back
+    /// to the peer address when `forwarded_for` is absent (pass `None` for
+    /// untrusted peers), too short, or malformed. IPv6 results are masked to
+    /// `ipv6Subnet`.
+    pub fn resolve(&self, forwarded_for: Option<&str>, peer: IpAddr) -> IpAddr {
+        let ip = forwarded_for
+            .filter(|_| self.use_forwarded)
+            .and_then(|xff| self.select(xff))
+            .unwrap_or(peer);
+        self.mask(ip)
+    }
+
+    fn select(&self, forwarded_for: &str) -> Option<IpAddr> {
+        let mut remaining = self.depth.max(1);
+        for hop in forwarded_for.rsplit(',').map(str::trim) {
+            let ip = hop.parse::<IpAddr>().ok();
+            if let Some(ip) = ip
+                && self.excluded_networks.iter().any(|net| net.contains(ip))
+            {
+                continue;
+            }
+            remaining -= 1;
+            if remaining == 0 {
+                return ip;
+            }
+        }
+        None
+    }
+
+    fn mask(&self, ip: IpAddr) -> IpAddr {
+        match (ip, self.ipv6_subnet) {
+            (IpAddr::V6(v6), Some(bits)) => Ipv6Network::new(v6, bits)
+                .map(|network| IpAddr::V6(network.network()))
+                .unwrap_or(ip),
+            _ => ip,
+        }
+    }
+}
+
+/// Parse an IP address or CIDR notation into IpNetwork
+pub(crate) fn parse_network(s: &str) -> Option<IpNetwork> {
+    // Try parsing as CIDR first
+    if let Ok(network) = s.parse::<IpNetwork>() {
+        return Some(network);
+    }
+
+    // Try parsing as single IP address
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return match ip {
+            IpAddr::V4(v4) => Some(IpNetwork::V4(ipnetwork::Ipv4Network::new(v4, 32).ok()?)),
+            IpAddr::V6(v6) => Some(IpNetwork::V6(Ipv6Network::new(v6, 128).ok()?)),
+        };
+    }
+
+    warn!("Failed to parse IP filter rule: {}", s);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XFF: &str = "203.0.113.7, 198.51.100.2, 10.0.0.5";
+
+    fn resolver(depth: u32, excluded_ips: &[&str]) -> ClientIpResolver {
+        ClientIpResolver::new(Some(&IpStrategy {
+            depth,
+            excluded_ips: excluded_ips.iter().map(|s| s.to_string()).collect(),
+            ipv6_subnet: None,
+        }))
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_depth_counts_from_the_right() {
+        let peer = ip("192.0.2.1");
+        assert_eq!(resolver(1, &[]).resolve(Some(XFF), peer), ip("10.0.0.5"));
+        assert_eq!(resolver(2, &[]).resolve(Some(XFF), peer), ip("198.51.100.2"));
+        assert_eq!(resolver(3, &[]).resolve(Some(XFF), peer), ip("203.0.113.7"));
+
+        // Past the end of the chain, or no chain at all, the peer is the client
+        assert_eq!(resolver(4, &[]).resolve(Some(XFF), peer), peer);
+        assert_eq!(resolver(1, &[]).resolve(None, peer), peer);
+        assert_eq!(ClientIpResolver::new(None).resolve(Some(XFF), peer), peer);
+
+        // depth 0 means not set: without excludedIps the peer is the client
+        assert_eq!(resolver(0, &[]).resolve(Some(XFF), peer), peer);
+        let subnet_only = IpStrategy { ipv6_subnet: Some(64), ..Default::default() };
+        assert_eq!(ClientIpResolver::new(Some(&subnet_only)).resolve(Some(XFF), peer), peer);
+    }
+
+    #[test]
+    fn test_excluded_hops_are_skipped() {
+        let peer = ip("192.0.2.1");
+        // Without depth, the rightmost hop that isn't excluded is the client
+        let internal = resolver(0, &["10.0.0.0/8"]);
+        assert_eq!(internal.resolve(Some(XFF), peer), ip("198.51.100.2"));
+
+        // depth wins over excludedIps, as in Traefik
+        let cdn = resolver(2, &["10.0.0.0/8", "198.51.100.2"]);
+        assert_eq!(cdn.resolve(Some(XFF), peer), ip("198.51.100.2"));
+
+        let all = resolver(0, &["0.0.0.0/0"]);
+        assert_eq!(all.resolve(Some(XFF), peer), peer);
+
+        // A malformed hop is never trusted as the client address
+        assert_eq!(resolver(1, &[]).resolve(Some("203.0.113.7, bogus"), peer), peer);
+    }
+
+    #[test]
+    fn test_untrusted_peer_cannot_spoof_forwarded_for() {
+        let config = ForwardedHeaders {
+            trusted_ips: vec!["10.0.0.0/8".to_string(), "192.0.2.10".to_string()],
+            ..Default::default()
+        };
+        let policy = ForwardedHeadersPolicy::from_config(Some(&config));
+        let resolver = resolver(1, &[]);
+
+        let resolve_from = |peer: IpAddr| {
+            let forwarded_for = policy.is_trusted(peer).then_some("203.0.113.7");
+            resolver.resolve(forwarded_for, peer)
+        };
+        assert_eq!(resolve_from(ip("10.1.2.3")), ip("203.0.113.7"));
+        assert_eq!(resolve_from(ip("192.0.2.10")), ip("203.0.113.7"));
+        assert_eq!(resolve_from(ip("198.51.100.9")), ip("198.51.100.9"));
+
+        assert!(!ForwardedHeadersPolicy::from_config(None).is_trusted(ip("10.1.2.3")));
+        let insecure = ForwardedHeaders { insecure: true, ..Default::default() };
+        assert!(ForwardedHeadersPolicy::from_config(Some(&insecure)).is_trusted(ip("198.51.100.9")));
+    }
+
+    #[test]
+    fn test_ipv6_subnet_groups_clients() {
+        let resolver = ClientIpResolver::new(Some(&IpStrategy {
+            depth: 1,
+            ipv6_subnet: Some(64),
+            ..Default::default()
+        }));
+        let peer = ip("2001:db8:1:2:aaaa::1");
+
+        assert_eq!(resolver.resolve(None, peer), ip("2001:db8:1:2::"));
+        assert_eq!(
+            resolver.resolve(Some("2001:db8:1:2:bbbb::9"), peer),
+            resolver.resolve(None, peer)
+        );
+        assert_eq!(resolver.resolve(Some("2001:db8:1:3::9"), peer), ip("2001:db8:1:3::"));
+
+        // IPv4 addresses are left alone
+        assert_eq!(resolver.resolve(None, ip("192.0.2.1")), ip("192.0.2.1"));
+    }
+}
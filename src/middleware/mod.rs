@@ -2,6 +2,7 @@
 
 pub mod builtin;
 mod chain;
+pub mod ip_strategy;
 pub mod registry;
 
 /// Core built-in middleware types re-exported for convenience.
@@ -12,15 +13,23 @@ pub use builtin::{
 };
 /// Ordered chain of middleware to execute per request.
 pub use chain::MiddlewareChain;
+/// Client IP resolution honoring `ipStrategy` and trusted forwarded headers.
+pub use ip_strategy::{ClientIpResolver, ForwardedHeadersPolicy};
 /// Registry for resolving middleware by name, and per-request context.
 pub use registry::{MiddlewareRegistry, RequestContext};
 
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
-use hyper::{body::Incoming, Request, Response};
+use hyper::{body::Incoming, HeaderMap, Request, Response};
 use std::future::Future;
 use std::pin::Pin;
 
+/// Request body buffered and transformed by a middleware. When present in the
+/// request extensions, the forwarding endpoint sends it instead of the
+/// (already drained) incoming body.
+#[derive(Debug, Clone)]
+pub struct BufferedRequestBody(pub Bytes);
+
 /// A pinned, boxed, sendable future used throughout the middleware pipeline.
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
@@ -43,6 +52,16 @@ pub trait Middleware: Send + Sync {
 pub trait Endpoint: Send + Sync {
     /// Handle the request at the end of the middleware chain (e.g., proxy to backend).
     fn call(&self, req: Request<Incoming>) -> BoxFuture<'_, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>>;
+
+    /// GET `path` from another service with the given request headers, for middleware
+    /// that answer from a second service (e.g. custom error pages). `None` when the
+    /// service has no reachable backend.
+    fn fetch<'a>(
+        &'a self,
+        service: &'a str,
+        path: &'a str,
+        headers: &'a HeaderMap,
+    ) -> BoxFuture<'a, Option<Response<BoxBody<Bytes, hyper::Error>>>>;
 }
 
 /// Continuation handle passed to middleware; calling `run` invokes the next middleware or endpoint.
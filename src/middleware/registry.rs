@@ -1,20 +1,26 @@
 use super::builtin::{
-    BasicAuthMiddleware, CorsMiddleware, HeadersMiddleware, IpAllowListMiddleware,
-    IpDenyListMiddleware, RateLimitMiddleware, RedirectSchemeMiddleware,
+    BackendInfo, BasicAuthMiddleware, BufferingMiddleware, CompressMiddleware, CompressionAlgorithm, CorsMiddleware, DecompressRequestMiddleware, DigestAuthMiddleware, DigestAuthResult, ErrorsMiddleware, ForwardAuthMiddleware, GeoIpMiddleware, GrpcWebMiddleware, HeadersMiddleware, IpAllowListMiddleware,
+    IpDenyListMiddleware, MaintenanceMiddleware, OAuth2IntrospectionMiddleware, PassTlsClientCertMiddleware, RateLimitMiddleware, RedirectRegexMiddleware, RedirectSchemeMiddleware,
+    ReplaceResponseBodyMiddleware, RequestIdMiddleware, RequestRetry, RetryMiddleware, TarpitMiddleware,
     AddPrefixMiddleware, StripPrefixMiddleware, ReplacePathMiddleware,
     StripPrefixRegexMiddleware, ReplacePathRegexMiddleware,
 };
 use super::{BoxFuture, Middleware, Next};
 use crate::config::MiddlewareConfig;
+use crate::health::CircuitBreaker;
+use crate::proxy::{grpc_error_response, grpc_to_grpc_web_response, GrpcWebEncoding};
+use crate::store::Store;
+use crate::tls::ClientCertInfo;
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
 use http_body_util::{BodyExt, Full};
-use hyper::header::{HeaderValue, CONTENT_TYPE};
-use hyper::{body::Incoming, Request, Response, StatusCode};
+use hyper::header::{HeaderValue, CONTENT_TYPE, SET_COOKIE, WWW_AUTHENTICATE};
+use hyper::{body::Incoming, Method, Request, Response, StatusCode};
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tracing::{debug, warn};
+use tracing::{debug, error, warn};
 
 /// Request context injected via request extensions before middleware chain runs
 #[derive(Clone)]
@@ -23,28 +29,112 @@ pub struct RequestContext {
     pub remote_addr: SocketAddr,
     /// Whether the connection arrived over TLS.
     pub is_tls: bool,
+    /// Whether the peer is trusted to send X-Forwarded-For (entrypoint `forwardedHeaders`).
+    pub trust_forwarded: bool,
+}
+
+/// Stable fingerprint of a middleware config. Going through `serde_json::Value`
+/// sorts map keys, so equal configs match regardless of `HashMap` order.
+/// A basicAuth or digestAuth `usersFile` and a maintenance body `file`
+/// contribute their contents and a GeoIP database its size and modification
+/// time, so a reload picks up edited files even when the config itself is
+/// unchanged.
+fn config_fingerprint(config: &MiddlewareConfig) -> Option<u64> {
+    let canonical = serde_json::to_value(config).ok()?.to_string();
+    let mut hasher = DefaultHasher::new();
+    canonical.hash(&mut hasher);
+    if let Some(path) = config.basic_auth.as_ref().and_then(|c| c.users_file.as_ref()) {
+        std::fs::read(path).ok().hash(&mut hasher);
+    }
+    if let Some(path) = config.digest_auth.as_ref().and_then(|c| c.users_file.as_ref()) {
+        std::fs::read(path).ok().hash(&mut hasher);
+    }
+    if let Some(path) = config.maintenance.as_ref().and_then(|c| c.file.as_ref()) {
+        std::fs::read(path).ok().hash(&mut hasher);
+    }
+    if let Some(geo_config) = &config.geo_ip {
+        // Databases run to tens of megabytes, so skip hashing their contents
+        std::fs::metadata(&geo_config.mmdb_path)
+            .ok()
+            .map(|m| (m.len(), m.modified().ok()))
+            .hash(&mut hasher);
+    }
+    Some(hasher.finish())
 }
 
 /// Registry of instantiated middleware, keyed by name
 pub struct MiddlewareRegistry {
     middlewares: HashMap<String, Arc<dyn Middleware>>,
+    /// Fingerprint of the config each instance was built from
+    fingerprints: HashMap<String, u64>,
+    store: Option<Arc<dyn Store>>,
 }
 
 impl MiddlewareRegistry {
     /// Build registry from config middleware definitions
     pub fn from_config(configs: &HashMap<String, MiddlewareConfig>) -> Self {
+        Self::build(configs, None)
+    }
+
+    /// Build registry with `distributed` rate limits counted in the shared store
+    pub fn with_store(configs: &HashMap<String, MiddlewareConfig>, store: Arc<dyn Store>) -> Self {
+        Self::build(configs, Some(store))
+    }
+
+    /// Build a registry for updated config definitions. Middlewares whose name
+    /// and config are unchanged keep their current instance, so rate-limit
+    /// buckets and other per-instance state survive reloads that don't touch
+    /// them; only new or changed middlewares are rebuilt.
+    pub fn reload(&self, configs: &HashMap<String, MiddlewareConfig>) -> Self {
+        Self::build_reusing(configs, self.store.clone(), Some(self))
+    }
+
+    fn build(configs: &HashMap<String, MiddlewareConfig>, store: Option<Arc<dyn Store>>) -> Self {
+        Self::build_reusing(configs, store, None)
+    }
+
+    fn build_reusing(
+        configs: &HashMap<String, MiddlewareConfig>,
+        store: Option<Arc<dyn Store>>,
+        previous: Option<&Self>,
+    ) -> Self {
         let mut middlewares: HashMap<String, Arc<dyn Middleware>> = HashMap::new();
+        let mut fingerprints = HashMap::new();
+        let mut reused = 0usize;
 
         for (name, config) in configs {
-            if let Some(mw) = Self::create_middleware(name, config) {
+            let fingerprint = config_fingerprint(config);
+            if let Some(previous) = previous
+                && let Some(fingerprint) = fingerprint
+                && previous.fingerprints.get(name) == Some(&fingerprint)
+                && let Some(mw) = previous.middlewares.get(name)
+            {
+                middlewares.insert(name.clone(), Arc::clone(mw));
+                fingerprints.insert(name.clone(), fingerprint);
+                reused += 1;
+                continue;
+            }
+
+            if let Some(mw) = Self::create_middleware(name, config, store.as_ref()) {
                 debug!("Registered middleware '{}'", name);
                 middlewares.insert(name.clone(), mw);
+                if let Some(fingerprint) = fingerprint {
+                    fingerprints.insert(name.clone(), fingerprint);
+                }
             } else {
                 warn!("Unsupported middleware type for '{}': {}", name, config.middleware_type());
             }
         }
 
-        Self { middlewares }
+        if previous.is_some() {
+            debug!(
+                "Middleware reload kept {} unchanged instance(s), rebuilt {}",
+                reused,
+                middlewares.len() - reused
+            );
+        }
+
+        Self { middlewares, fingerprints, store }
     }
 
     /// Look up middleware by name, returns ordered list of middleware instances
@@ -61,7 +151,20 @@ impl MiddlewareRegistry {
             .collect()
     }
 
-    fn create_middleware(name: &str, config: &MiddlewareConfig) -> Option<Arc<dyn Middleware>> {
+    /// Whether `name` resolves to exactly this instance (used to check reloads).
+    #[cfg(test)]
+    fn is_same_instance(&self, name: &str, other: &Self) -> bool {
+        match (self.middlewares.get(name), other.middlewares.get(name)) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    fn create_middleware(
+        name: &str,
+        config: &MiddlewareConfig,
+        store: Option<&Arc<dyn Store>>,
+    ) -> Option<Arc<dyn Middleware>> {
         // Headers middleware
         if let Some(headers_config) = &config.headers {
             let headers = HeadersMiddleware::new(headers_config.clone());
@@ -82,7 +185,12 @@ impl MiddlewareRegistry {
 
         // Rate limit middleware
         if let Some(rl_config) = &config.rate_limit {
-            let limiter = RateLimitMiddleware::new(rl_config.clone());
+            let limiter = match store {
+                Some(store) if rl_config.distributed => {
+                    RateLimitMiddleware::with_store(rl_config.clone(), Arc::clone(store))
+                }
+                _ => RateLimitMiddleware::new(rl_config.clone()),
+            };
             return Some(Arc::new(RateLimitWrapper {
                 name: name.to_string(),
                 inner: limiter,
@@ -107,6 +215,121 @@ impl MiddlewareRegistry {
             }));
         }
 
+        // Tarpit
+        if let Some(tarpit_config) = &config.tarpit {
+            return Some(Arc::new(TarpitWrapper {
+                name: name.to_string(),
+                inner: TarpitMiddleware::new(tarpit_config),
+            }));
+        }
+
+        // gRPC-Web translation
+        if let Some(grpc_web_config) = &config.grpc_web {
+            return Some(Arc::new(GrpcWebWrapper {
+                name: name.to_string(),
+                inner: GrpcWebMiddleware::new(grpc_web_config.clone()),
+            }));
+        }
+
+        // Retry with backoff
+        if let Some(retry_config) = &config.retry {
+            return Some(Arc::new(RetryWrapper {
+                name: name.to_string(),
+                inner: Arc::new(RetryMiddleware::new(retry_config.clone())),
+            }));
+        }
+
+        // Circuit breaker; its state lives in the wrapper, shared by every request
+        if let Some(breaker_config) = &config.circuit_breaker {
+            let breaker = match CircuitBreaker::from_config(breaker_config) {
+                Ok(breaker) => breaker,
+                Err(e) => {
+                    error!("Invalid circuitBreaker expression for '{}': {}", name, e);
+                    return None;
+                }
+            };
+            return Some(Arc::new(CircuitBreakerWrapper {
+                name: name.to_string(),
+                inner: breaker,
+                response_code: StatusCode::from_u16(breaker_config.response_code)
+                    .unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+            }));
+        }
+
+        // Request body buffering
+        if let Some(buffering_config) = &config.buffering {
+            return Some(Arc::new(BufferingWrapper {
+                name: name.to_string(),
+                inner: BufferingMiddleware::new(buffering_config.clone()),
+            }));
+        }
+
+        // Request decompression
+        if let Some(decompress_config) = &config.decompress_request {
+            return Some(Arc::new(DecompressRequestWrapper {
+                name: name.to_string(),
+                inner: DecompressRequestMiddleware::new(decompress_config),
+            }));
+        }
+
+        // Response body rewrite
+        if let Some(replace_config) = &config.replace_response_body {
+            return Some(Arc::new(ReplaceResponseBodyWrapper {
+                name: name.to_string(),
+                inner: ReplaceResponseBodyMiddleware::new(replace_config),
+            }));
+        }
+
+        // Maintenance
+        if let Some(maintenance_config) = &config.maintenance {
+            return Some(Arc::new(MaintenanceWrapper {
+                name: name.to_string(),
+                inner: MaintenanceMiddleware::new(maintenance_config),
+            }));
+        }
+
+        // Custom error pages
+        if let Some(errors_config) = &config.errors {
+            return Some(Arc::new(ErrorsWrapper {
+                name: name.to_string(),
+                inner: ErrorsMiddleware::new(errors_config.clone()),
+            }));
+        }
+
+        // Request ID
+        if let Some(id_config) = &config.request_id {
+            return Some(Arc::new(RequestIdWrapper {
+                name: name.to_string(),
+                inner: RequestIdMiddleware::new(id_config),
+            }));
+        }
+
+        // Pass TLS client certificate
+        if let Some(cert_config) = &config.pass_tls_client_cert {
+            return Some(Arc::new(PassTlsClientCertWrapper {
+                name: name.to_string(),
+                inner: PassTlsClientCertMiddleware::new(cert_config),
+            }));
+        }
+
+        // GeoIP
+        if let Some(geo_config) = &config.geo_ip {
+            // Without its database the country lists can't be enforced, so the
+            // middleware stays in the chain and rejects requests until a reload loads it
+            let inner = GeoIpMiddleware::new(geo_config)
+                .inspect_err(|e| {
+                    error!(
+                        "Failed to load GeoIP database '{}' for '{}', rejecting requests: {}",
+                        geo_config.mmdb_path, name, e
+                    );
+                })
+                .ok();
+            return Some(Arc::new(GeoIpWrapper {
+                name: name.to_string(),
+                inner,
+            }));
+        }
+
         // Basic auth
         if let Some(auth_config) = &config.basic_auth {
             let auth = BasicAuthMiddleware::new(auth_config.clone());
@@ -116,6 +339,52 @@ impl MiddlewareRegistry {
             }));
         }
 
+        // Digest auth; nonce counts go to the store, shared across nodes when it is the cluster store
+        if let Some(auth_config) = &config.digest_auth {
+            let auth = match store {
+                Some(store) => DigestAuthMiddleware::with_store(auth_config.clone(), Arc::clone(store)),
+                None => DigestAuthMiddleware::new(auth_config.clone()),
+            };
+            return Some(Arc::new(DigestAuthWrapper {
+                name: name.to_string(),
+                inner: auth,
+            }));
+        }
+
+        // Forward auth
+        if let Some(auth_config) = &config.forward_auth
+            && let Some(auth) = ForwardAuthMiddleware::new(auth_config.clone()) {
+                return Some(Arc::new(ForwardAuthWrapper {
+                    name: name.to_string(),
+                    inner: auth,
+                }));
+            }
+
+        // OAuth2 token introspection
+        if let Some(introspection_config) = &config.oauth2_introspection
+            && let Some(auth) = OAuth2IntrospectionMiddleware::new(introspection_config.clone()) {
+                return Some(Arc::new(OAuth2IntrospectionWrapper {
+                    name: name.to_string(),
+                    inner: auth,
+                }));
+            }
+
+        // Redirect regex
+        if let Some(redirect_config) = &config.redirect_regex {
+            match RedirectRegexMiddleware::new(redirect_config.clone()) {
+                Some(redirect) => {
+                    return Some(Arc::new(RedirectRegexWrapper {
+                        name: name.to_string(),
+                        inner: redirect,
+                    }));
+                }
+                None => {
+                    error!("Invalid redirectRegex pattern '{}' for '{}'", redirect_config.regex, name);
+                    return None;
+                }
+            }
+        }
+
         // Redirect scheme
         if let Some(redirect_config) = &config.redirect_scheme {
             let redirect = RedirectSchemeMiddleware::new(redirect_config.clone());
@@ -153,28 +422,34 @@ impl MiddlewareRegistry {
         }
 
         // Strip prefix regex
-        if let Some(strip_regex_config) = &config.strip_prefix_regex
-            && let Some(strip) = StripPrefixRegexMiddleware::new(strip_regex_config.clone()) {
-                return Some(Arc::new(StripPrefixRegexWrapper {
-                    name: name.to_string(),
-                    inner: strip,
-                }));
-            }
+        if let Some(strip_regex_config) = &config.strip_prefix_regex {
+            let Some(strip) = StripPrefixRegexMiddleware::new(strip_regex_config.clone()) else {
+                error!("Invalid stripPrefixRegex pattern in {:?} for '{}'", strip_regex_config.regex, name);
+                return None;
+            };
+            return Some(Arc::new(StripPrefixRegexWrapper {
+                name: name.to_string(),
+                inner: strip,
+            }));
+        }
 
         // Replace path regex
-        if let Some(replace_regex_config) = &config.replace_path_regex
-            && let Some(replace) = ReplacePathRegexMiddleware::new(replace_regex_config.clone()) {
-                return Some(Arc::new(ReplacePathRegexWrapper {
-                    name: name.to_string(),
-                    inner: replace,
-                }));
-            }
+        if let Some(replace_regex_config) = &config.replace_path_regex {
+            let Some(replace) = ReplacePathRegexMiddleware::new(replace_regex_config.clone()) else {
+                error!("Invalid replacePathRegex pattern '{}' for '{}'", replace_regex_config.regex, name);
+                return None;
+            };
+            return Some(Arc::new(ReplacePathRegexWrapper {
+                name: name.to_string(),
+                inner: replace,
+            }));
+        }
 
         // Compress middleware
         if let Some(compress_config) = &config.compress {
             return Some(Arc::new(CompressWrapper {
                 name: name.to_string(),
-                min_size: compress_config.min_response_body_bytes,
+                inner: CompressMiddleware::new(compress_config.clone()),
             }));
         }
 
@@ -204,6 +479,18 @@ fn get_client_ip(req: &Request<Incoming>) -> Option<IpAddr> {
         .map(|ctx| ctx.remote_addr.ip())
 }
 
+/// X-Forwarded-For, only when the entrypoint trusts the peer that sent it.
+fn trusted_forwarded_for(req: &Request<Incoming>) -> Option<&str> {
+    let trusted = req
+        .extensions()
+        .get::<RequestContext>()
+        .is_some_and(|ctx| ctx.trust_forwarded);
+    if !trusted {
+        return None;
+    }
+    req.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok())
+}
+
 // --- Headers ---
 struct HeadersWrapper {
     name: String,
@@ -215,14 +502,45 @@ impl Middleware for HeadersWrapper {
 
     fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
+            let secure = headers_request_secure(&self.inner, &req);
+            if let Some(resp) = headers_rejection(&self.inner, &req, secure) {
+                return Ok(resp);
+            }
             self.inner.apply_request(req.headers_mut());
             let mut resp = next.run(req).await?;
             self.inner.apply_response(resp.headers_mut());
+            self.inner.apply_sts(resp.headers_mut(), secure);
             Ok(resp)
         })
     }
 }
 
+/// Whether the request is HTTPS, directly or per the headers' `sslProxyHeaders`
+fn headers_request_secure(headers: &HeadersMiddleware, req: &Request<Incoming>) -> bool {
+    let is_tls = req.extensions()
+        .get::<RequestContext>()
+        .map(|ctx| ctx.is_tls)
+        .unwrap_or(false);
+    headers.is_secure(req, is_tls)
+}
+
+/// Host and HTTPS checks shared by both headers wrappers: the rejection or
+/// redirect to send instead of forwarding the request, if any
+fn headers_rejection(
+    headers: &HeadersMiddleware,
+    req: &Request<Incoming>,
+    secure: bool,
+) -> Option<Response<BoxBody<Bytes, hyper::Error>>> {
+    if !headers.host_allowed(req) {
+        return Some(error_response(StatusCode::MISDIRECTED_REQUEST, "Host not allowed"));
+    }
+    let (parts, _) = headers.ssl_redirect(req, secure)?.into_parts();
+    Some(Response::from_parts(
+        parts,
+        Full::new(Bytes::new()).map_err(|never| match never {}).boxed(),
+    ))
+}
+
 // --- Headers + CORS ---
 struct HeadersAndCorsWrapper {
     name: String,
@@ -235,6 +553,11 @@ impl Middleware for HeadersAndCorsWrapper {
 
     fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
+            let secure = headers_request_secure(&self.headers, &req);
+            if let Some(resp) = headers_rejection(&self.headers, &req, secure) {
+                return Ok(resp);
+            }
+
             // Handle CORS preflight
             if self.cors.is_preflight(&req) {
                 if let Some(resp) = self.cors.handle_preflight(&req) {
@@ -251,6 +574,7 @@ impl Middleware for HeadersAndCorsWrapper {
             self.headers.apply_request(req.headers_mut());
             let mut resp = next.run(req).await?;
             self.headers.apply_response(resp.headers_mut());
+            self.headers.apply_sts(resp.headers_mut(), secure);
             self.cors.apply_headers(origin.as_deref(), resp.headers_mut());
             Ok(resp)
         })
@@ -268,11 +592,23 @@ impl Middleware for RateLimitWrapper {
 
     fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
-            if let Some(ip) = get_client_ip(&req)
-                && !self.inner.is_allowed(ip) {
-                    return Ok(error_response(StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded"));
-                }
-            next.run(req).await
+            let Some(ip) = get_client_ip(&req) else {
+                return next.run(req).await;
+            };
+
+            // Namespace keys by middleware so limiters sharing a store don't collide
+            let source = self.inner.source_key(&req, ip, trusted_forwarded_for(&req));
+            let key = format!("{}:{}", self.name, source);
+            let result = self.inner.check(&key).await;
+            if !result.allowed {
+                let mut resp = error_response(StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded");
+                result.apply_headers(resp.headers_mut());
+                return Ok(resp);
+            }
+
+            let mut resp = next.run(req).await?;
+            result.apply_headers(resp.headers_mut());
+            Ok(resp)
         })
     }
 }
@@ -290,7 +626,7 @@ impl Middleware for IpAllowWrapper {
         Box::pin(async move {
             if self.inner.has_rules()
                 && let Some(ip) = get_client_ip(&req)
-                    && !self.inner.is_allowed(ip) {
+                    && !self.inner.is_allowed(self.inner.get_client_ip(trusted_forwarded_for(&req), ip)) {
                         let status = StatusCode::from_u16(self.inner.reject_status_code())
                             .unwrap_or(StatusCode::FORBIDDEN);
                         return Ok(error_response(status, "Forbidden"));
@@ -313,7 +649,7 @@ impl Middleware for IpDenyWrapper {
         Box::pin(async move {
             if self.inner.has_rules()
                 && let Some(ip) = get_client_ip(&req)
-                    && self.inner.is_denied(ip) {
+                    && self.inner.is_denied(self.inner.get_client_ip(trusted_forwarded_for(&req), ip)) {
                         return Ok(error_response(StatusCode::FORBIDDEN, "Forbidden"));
                     }
             next.run(req).await
@@ -332,7 +668,7 @@ impl Middleware for BasicAuthWrapper {
 
     fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
-            if !self.inner.is_authenticated(&req) {
+            if !self.inner.authenticate(&req).await {
                 let resp = self.inner.unauthorized_response();
                 let (parts, _) = resp.into_parts();
                 return Ok(Response::from_parts(
@@ -345,6 +681,376 @@ impl Middleware for BasicAuthWrapper {
     }
 }
 
+// --- Digest Auth ---
+struct DigestAuthWrapper {
+    name: String,
+    inner: DigestAuthMiddleware,
+}
+
+impl Middleware for DigestAuthWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let resp = match self.inner.authenticate(&req).await {
+                DigestAuthResult::Authenticated(username) => {
+                    self.inner.apply_authenticated(req.headers_mut(), &username);
+                    return next.run(req).await;
+                }
+                DigestAuthResult::Stale => self.inner.stale_response(),
+                DigestAuthResult::NeedsAuth | DigestAuthResult::Invalid => self.inner.unauthorized_response(),
+            };
+            let (parts, body) = resp.into_parts();
+            Ok(Response::from_parts(
+                parts,
+                Full::new(Bytes::from(body)).map_err(|never| match never {}).boxed(),
+            ))
+        })
+    }
+}
+
+// --- Tarpit ---
+struct TarpitWrapper {
+    name: String,
+    inner: TarpitMiddleware,
+}
+
+impl Middleware for TarpitWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let client_ip = get_client_ip(&req)
+                .map(|ip| self.inner.get_client_ip(trusted_forwarded_for(&req), ip));
+            if self.inner.is_flagged(client_ip, req.headers()) {
+                let Some(guard) = self.inner.try_acquire() else {
+                    return Ok(error_response(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests"));
+                };
+                self.inner.hold(guard).await;
+            }
+            next.run(req).await
+        })
+    }
+}
+
+// --- Decompress Request ---
+struct DecompressRequestWrapper {
+    name: String,
+    inner: DecompressRequestMiddleware,
+}
+
+impl Middleware for DecompressRequestWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            if let Err(e) = self.inner.decompress_request(&mut req).await {
+                debug!("Rejecting request body: {}", e);
+                return Ok(error_response(e.status(), &e.to_string()));
+            }
+            next.run(req).await
+        })
+    }
+}
+
+// --- gRPC-Web ---
+struct GrpcWebWrapper {
+    name: String,
+    inner: GrpcWebMiddleware,
+}
+
+impl Middleware for GrpcWebWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            if GrpcWebMiddleware::is_preflight(&req) {
+                if let Some(resp) = self.inner.handle_preflight(&req) {
+                    let (parts, _) = resp.into_parts();
+                    return Ok(Response::from_parts(
+                        parts,
+                        Full::new(Bytes::new()).map_err(|never| match never {}).boxed(),
+                    ));
+                }
+                return Ok(error_response(StatusCode::FORBIDDEN, "CORS origin not allowed"));
+            }
+
+            let Some(encoding) = GrpcWebEncoding::from_headers(req.headers()) else {
+                return next.run(req).await;
+            };
+            let origin = CorsMiddleware::get_origin(&req);
+            let resp = match self.inner.translate_request(&mut req, encoding).await {
+                Ok(()) => next.run(req).await?,
+                Err(e) => {
+                    debug!("Rejecting gRPC-Web request: {}", e);
+                    grpc_error_response(e.grpc_status(), &e.to_string())
+                }
+            };
+            let mut resp = grpc_to_grpc_web_response(resp, encoding);
+            self.inner.apply_cors(origin.as_deref(), resp.headers_mut());
+            Ok(resp)
+        })
+    }
+}
+
+// --- Retry ---
+struct RetryWrapper {
+    name: String,
+    inner: Arc<RetryMiddleware>,
+}
+
+impl Middleware for RetryWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            // The forwarding endpoint performs the retries
+            if self.inner.retries_method(req.method().as_str()) {
+                req.extensions_mut().insert(RequestRetry(Arc::clone(&self.inner)));
+            }
+            next.run(req).await
+        })
+    }
+}
+
+// --- Circuit Breaker ---
+struct CircuitBreakerWrapper {
+    name: String,
+    inner: CircuitBreaker,
+    /// Returned while the circuit is open
+    response_code: StatusCode,
+}
+
+impl Middleware for CircuitBreakerWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            // Held until the outcome is recorded; a dropped half-open probe frees its slot
+            let Some(_permit) = self.inner.allow_request() else {
+                debug!("Circuit breaker '{}' is open, rejecting request", self.name);
+                let reason = self.response_code.canonical_reason().unwrap_or("Service Unavailable");
+                return Ok(error_response(self.response_code, reason));
+            };
+            let start = std::time::Instant::now();
+            let result = next.run(req).await;
+            // Responses the proxy generated because the backend never answered
+            // count as network errors
+            let status = result.as_ref().ok().and_then(|resp| {
+                let network_error = resp.extensions().get::<BackendInfo>().is_some_and(|b| b.network_error);
+                (!network_error).then(|| resp.status().as_u16())
+            });
+            self.inner.record_response(status, start.elapsed());
+            result
+        })
+    }
+}
+
+// --- Buffering ---
+struct BufferingWrapper {
+    name: String,
+    inner: BufferingMiddleware,
+}
+
+impl Middleware for BufferingWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            if let Err(e) = self.inner.read_request_body(&mut req).await {
+                debug!("Rejecting request body: {}", e);
+                return Ok(error_response(e.status(), &e.to_string()));
+            }
+            next.run(req).await
+        })
+    }
+}
+
+// --- Replace Response Body ---
+struct ReplaceResponseBodyWrapper {
+    name: String,
+    inner: ReplaceResponseBodyMiddleware,
+}
+
+impl Middleware for ReplaceResponseBodyWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let resp = next.run(req).await?;
+            self.inner.rewrite(resp).await
+        })
+    }
+}
+
+// --- Maintenance ---
+struct MaintenanceWrapper {
+    name: String,
+    inner: MaintenanceMiddleware,
+}
+
+impl Middleware for MaintenanceWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let client_ip = get_client_ip(&req)
+                .map(|ip| self.inner.get_client_ip(trusted_forwarded_for(&req), ip));
+            if self.inner.should_block(client_ip) {
+                let (parts, body) = self.inner.response().into_parts();
+                return Ok(Response::from_parts(
+                    parts,
+                    Full::new(body).map_err(|never| match never {}).boxed(),
+                ));
+            }
+            next.run(req).await
+        })
+    }
+}
+
+// --- Errors ---
+struct ErrorsWrapper {
+    name: String,
+    inner: ErrorsMiddleware,
+}
+
+impl Middleware for ErrorsWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            // The error page request carries the client's headers (Accept, language)
+            let headers = req.headers().clone();
+            let endpoint = next.endpoint;
+            let resp = next.run(req).await?;
+            Ok(self.inner.serve_error_page(resp, endpoint, &headers).await)
+        })
+    }
+}
+
+// --- Request ID ---
+struct RequestIdWrapper {
+    name: String,
+    inner: RequestIdMiddleware,
+}
+
+impl Middleware for RequestIdWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let id = self.inner.apply_to_request(&mut req);
+            let mut resp = next.run(req).await?;
+            self.inner.apply_to_response(id, &mut resp);
+            Ok(resp)
+        })
+    }
+}
+
+// --- Pass TLS Client Cert ---
+struct PassTlsClientCertWrapper {
+    name: String,
+    inner: PassTlsClientCertMiddleware,
+}
+
+impl Middleware for PassTlsClientCertWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let cert = req.extensions().get::<Arc<ClientCertInfo>>().cloned();
+            self.inner.apply(req.headers_mut(), cert.as_deref());
+            next.run(req).await
+        })
+    }
+}
+
+// --- GeoIP ---
+struct GeoIpWrapper {
+    name: String,
+    /// `None` when the database failed to load
+    inner: Option<GeoIpMiddleware>,
+}
+
+impl Middleware for GeoIpWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let Some(geoip) = &self.inner else {
+                return Ok(error_response(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable"));
+            };
+            if let Some(ip) = get_client_ip(&req) {
+                let client_ip = geoip.get_client_ip(trusted_forwarded_for(&req), ip);
+                let location = geoip.lookup(client_ip);
+                if !geoip.is_allowed(location.country.as_deref()) {
+                    return Ok(error_response(StatusCode::FORBIDDEN, "Forbidden"));
+                }
+                geoip.apply_headers(&location, req.headers_mut());
+            }
+            next.run(req).await
+        })
+    }
+}
+
+// --- Forward Auth ---
+struct ForwardAuthWrapper {
+    name: String,
+    inner: ForwardAuthMiddleware,
+}
+
+impl Middleware for ForwardAuthWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let result = match self.inner.authenticate(&req).await {
+                Ok(result) => result,
+                Err(denied) => {
+                    let mut resp = Response::new(
+                        Full::new(denied.body).map_err(|never| match never {}).boxed(),
+                    );
+                    *resp.status_mut() = denied.status;
+                    *resp.headers_mut() = denied.headers;
+                    return Ok(resp);
+                }
+            };
+
+            self.inner.apply_to_request(&result, req.headers_mut());
+            let mut resp = next.run(req).await?;
+            for cookie in &result.cookies_to_response {
+                if let Ok(value) = HeaderValue::from_str(cookie) {
+                    resp.headers_mut().append(SET_COOKIE, value);
+                }
+            }
+            Ok(resp)
+        })
+    }
+}
+
+// --- OAuth2 Introspection ---
+struct OAuth2IntrospectionWrapper {
+    name: String,
+    inner: OAuth2IntrospectionMiddleware,
+}
+
+impl Middleware for OAuth2IntrospectionWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            if let Err((status, msg)) = self.inner.authenticate(&req).await {
+                let mut resp = error_response(status, &msg);
+                if status == StatusCode::UNAUTHORIZED {
+                    resp.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                }
+                return Ok(resp);
+            }
+            next.run(req).await
+        })
+    }
+}
+
 // --- Redirect Scheme ---
 struct RedirectSchemeWrapper {
     name: String,
@@ -374,6 +1080,34 @@ impl Middleware for RedirectSchemeWrapper {
     }
 }
 
+// --- Redirect Regex ---
+struct RedirectRegexWrapper {
+    name: String,
+    inner: RedirectRegexMiddleware,
+}
+
+impl Middleware for RedirectRegexWrapper {
+    fn name(&self) -> &str { &self.name }
+
+    fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
+        Box::pin(async move {
+            let is_tls = req.extensions()
+                .get::<RequestContext>()
+                .map(|ctx| ctx.is_tls)
+                .unwrap_or(false);
+
+            if let Some(resp) = self.inner.build_redirect(&req, is_tls) {
+                let (parts, _) = resp.into_parts();
+                return Ok(Response::from_parts(
+                    parts,
+                    Full::new(Bytes::new()).map_err(|never| match never {}).boxed(),
+                ));
+            }
+            next.run(req).await
+        })
+    }
+}
+
 // --- Strip Prefix ---
 struct StripPrefixWrapper {
     name: String,
@@ -385,12 +1119,7 @@ impl Middleware for StripPrefixWrapper {
 
     fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
-            if let Some((new_uri, prefix)) = self.inner.transform_uri(req.uri()) {
-                *req.uri_mut() = new_uri;
-                if let Ok(val) = HeaderValue::from_str(&prefix) {
-                    req.headers_mut().insert("X-Forwarded-Prefix", val);
-                }
-            }
+            self.inner.apply(&mut req);
             next.run(req).await
         })
     }
@@ -407,9 +1136,7 @@ impl Middleware for AddPrefixWrapper {
 
     fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
-            if let Some(new_uri) = self.inner.transform_uri(req.uri()) {
-                *req.uri_mut() = new_uri;
-            }
+            self.inner.apply(&mut req);
             next.run(req).await
         })
     }
@@ -448,12 +1175,7 @@ impl Middleware for StripPrefixRegexWrapper {
 
     fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
-            if let Some((new_uri, prefix)) = self.inner.transform_uri(req.uri()) {
-                *req.uri_mut() = new_uri;
-                if let Ok(val) = HeaderValue::from_str(&prefix) {
-                    req.headers_mut().insert("X-Forwarded-Prefix", val);
-                }
-            }
+            self.inner.apply(&mut req);
             next.run(req).await
         })
     }
@@ -470,12 +1192,7 @@ impl Middleware for ReplacePathRegexWrapper {
 
     fn handle<'a>(&'a self, mut req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
         Box::pin(async move {
-            if let Some((new_uri, original)) = self.inner.transform_uri(req.uri()) {
-                *req.uri_mut() = new_uri;
-                if let Ok(val) = HeaderValue::from_str(&original) {
-                    req.headers_mut().insert("X-Replaced-Path", val);
-                }
-            }
+            self.inner.apply(&mut req);
             next.run(req).await
         })
     }
@@ -484,18 +1201,226 @@ impl Middleware for ReplacePathRegexWrapper {
 // --- Compress (placeholder — actual compression requires body collection) ---
 struct CompressWrapper {
     name: String,
-    #[allow(dead_code)]
-    min_size: u64,
+    inner: CompressMiddleware,
 }
 
 impl Middleware for CompressWrapper {
     fn name(&self) -> &str { &self.name }
 
     fn handle<'a>(&'a self, req: Request<Incoming>, next: Next<'a>) -> BoxFuture<'a, Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error>> {
-        // Compression needs to collect the response body and re-encode it,
-        // which requires significant changes to the response pipeline.
-        // For now, pass through — compression will be done at the response level
-        // when the full middleware pipeline supports body transformation.
-        Box::pin(async move { next.run(req).await })
+        Box::pin(async move {
+            // HEAD responses have no body to compress
+            let algorithm = if req.method() == Method::HEAD {
+                CompressionAlgorithm::None
+            } else {
+                self.inner.select_algorithm(req.headers())
+            };
+            let resp = next.run(req).await?;
+            self.inner.compress_response(resp, algorithm).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        BasicAuthConfig, DigestAuthConfig, Duration, GeoIpConfig, HeadersConfig, MaintenanceConfig,
+        RateLimitConfig, RetryConfig,
+    };
+
+    fn tagger(headers: &[(&str, &str)]) -> MiddlewareConfig {
+        MiddlewareConfig {
+            headers: Some(HeadersConfig {
+                custom_request_headers: headers
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn retrier() -> MiddlewareConfig {
+        MiddlewareConfig {
+            retry: Some(RetryConfig {
+                attempts: 3,
+                initial_interval: Duration::from_millis(100),
+                max_interval: Duration::from_secs(30),
+                multiplier: 2.0,
+                jitter: true,
+                status_codes: vec![408, 429, 502, 503, 504],
+                retry_non_idempotent: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A rate limiter, a header tagger and a retrier
+    fn base() -> HashMap<String, MiddlewareConfig> {
+        let limited = MiddlewareConfig {
+            rate_limit: Some(RateLimitConfig {
+                average: 10,
+                burst: 5,
+                period: Duration::from_secs(1),
+                source_criterion: None,
+                distributed: false,
+            }),
+            ..Default::default()
+        };
+        HashMap::from([
+            ("limited".to_string(), limited),
+            ("tagger".to_string(), tagger(&[("X-Tag", "one"), ("X-Env", "prod")])),
+            ("retrier".to_string(), retrier()),
+        ])
+    }
+
+    #[test]
+    fn test_reload_keeps_unchanged_instances() {
+        let registry = MiddlewareRegistry::from_config(&base());
+        let mut updated = base();
+        updated.insert("tagger".to_string(), tagger(&[("X-Tag", "two"), ("X-Env", "prod")]));
+        let reloaded = registry.reload(&updated);
+
+        assert!(reloaded.is_same_instance("limited", &registry));
+        assert!(reloaded.is_same_instance("retrier", &registry));
+        assert!(!reloaded.is_same_instance("tagger", &registry));
+        assert_eq!(reloaded.resolve(&["tagger".to_string()]).len(), 1);
+    }
+
+    #[test]
+    fn test_reload_adds_and_removes_middlewares() {
+        let registry = MiddlewareRegistry::from_config(&base());
+        let mut updated = base();
+        updated.remove("retrier");
+        updated.insert("fresh".to_string(), retrier());
+        let reloaded = registry.reload(&updated);
+
+        assert!(reloaded.is_same_instance("limited", &registry));
+        assert!(reloaded.resolve(&["retrier".to_string()]).is_empty());
+        assert_eq!(reloaded.resolve(&["fresh".to_string()]).len(), 1);
+
+        // A middleware that comes back with its old config is a new instance
+        let restored = reloaded.reload(&base());
+        assert!(!restored.is_same_instance("retrier", &registry));
+        assert!(restored.is_same_instance("limited", &registry));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_map_order() {
+        let mut reordered = base();
+        reordered.insert("tagger".to_string(), tagger(&[("X-Env", "prod"), ("X-Tag", "one")]));
+
+        let registry = MiddlewareRegistry::from_config(&base());
+        let reloaded = registry.reload(&reordered);
+        assert!(reloaded.is_same_instance("tagger", &registry));
+    }
+
+    #[test]
+    fn test_reload_rebuilds_basic_auth_when_users_file_changes() {
+        let path = std::env::temp_dir().join(format!("trafficcop-htpasswd-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "admin:one\n").unwrap();
+        let auth = MiddlewareConfig {
+            basic_auth: Some(BasicAuthConfig {
+                users: vec![],
+                users_file: Some(path.display().to_string()),
+                realm: None,
+                header_field: None,
+                remove_header: false,
+            }),
+            ..Default::default()
+        };
+        let configs = HashMap::from([("auth".to_string(), auth)]);
+
+        let registry = MiddlewareRegistry::from_config(&configs);
+        let unchanged = registry.reload(&configs);
+        std::fs::write(&path, "admin:two\n").unwrap();
+        let edited = registry.reload(&configs);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(unchanged.is_same_instance("auth", &registry));
+        assert!(!edited.is_same_instance("auth", &registry));
+    }
+
+    #[test]
+    fn test_reload_rebuilds_digest_auth_when_users_file_changes() {
+        let path = std::env::temp_dir().join(format!("trafficcop-htdigest-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "admin:Restricted:0cc175b9c0f1b6a831c399e269772661\n").unwrap();
+        let auth = MiddlewareConfig {
+            digest_auth: Some(DigestAuthConfig {
+                users: vec![],
+                users_file: Some(path.display().to_string()),
+                realm: None,
+                header_field: None,
+                remove_header: false,
+            }),
+            ..Default::default()
+        };
+        let configs = HashMap::from([("auth".to_string(), auth)]);
+
+        let registry = MiddlewareRegistry::from_config(&configs);
+        let unchanged = registry.reload(&configs);
+        std::fs::write(&path, "admin:Restricted:900150983cd24fb0d6963f7d28e17f72\n").unwrap();
+        let edited = registry.reload(&configs);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(unchanged.is_same_instance("auth", &registry));
+        assert!(!edited.is_same_instance("auth", &registry));
+    }
+
+    #[test]
+    fn test_reload_rebuilds_maintenance_when_body_file_changes() {
+        let path = std::env::temp_dir().join(format!("trafficcop-maintenance-{}.html", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "<h1>Back soon</h1>").unwrap();
+        let maintenance = MiddlewareConfig {
+            maintenance: Some(MaintenanceConfig {
+                enabled: true,
+                status_code: 503,
+                retry_after: None,
+                body: None,
+                file: Some(path.display().to_string()),
+                content_type: "text/html; charset=utf-8".to_string(),
+                allow_source_range: vec![],
+                ip_strategy: None,
+            }),
+            ..Default::default()
+        };
+        let configs = HashMap::from([("maintenance".to_string(), maintenance)]);
+
+        let registry = MiddlewareRegistry::from_config(&configs);
+        let unchanged = registry.reload(&configs);
+        std::fs::write(&path, "<h1>Back at 14:00</h1>").unwrap();
+        let edited = registry.reload(&configs);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(unchanged.is_same_instance("maintenance", &registry));
+        assert!(!edited.is_same_instance("maintenance", &registry));
+    }
+
+    #[test]
+    fn test_reload_rebuilds_geoip_when_database_changes() {
+        let path = std::env::temp_dir().join(format!("trafficcop-geoip-{}.mmdb", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "old database").unwrap();
+        let geo = MiddlewareConfig {
+            geo_ip: Some(GeoIpConfig {
+                mmdb_path: path.display().to_string(),
+                allow_countries: vec![],
+                deny_countries: vec![],
+                add_headers: true,
+                ip_strategy: None,
+            }),
+            ..Default::default()
+        };
+        let configs = HashMap::from([("geo".to_string(), geo)]);
+
+        let registry = MiddlewareRegistry::from_config(&configs);
+        let unchanged = registry.reload(&configs);
+        std::fs::write(&path, "updated database").unwrap();
+        let replaced = registry.reload(&configs);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(unchanged.is_same_instance("geo", &registry));
+        assert!(!replaced.is_same_instance("geo", &registry));
     }
 }
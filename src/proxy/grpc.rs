@@ -1,9 +1,15 @@
 //! gRPC and gRPC-Web request handling utilities.
 
-use bytes::Bytes;
+use crate::middleware::builtin::GrpcWebMiddleware;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
 use http_body_util::{combinators::BoxBody, BodyExt, Full};
-use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE};
-use hyper::{body::Incoming, Request, Response, StatusCode};
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Request, Response, StatusCode};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use tracing::debug;
 
 /// gRPC content type prefix
@@ -203,6 +209,269 @@ pub fn copy_grpc_trailers(
         }
 }
 
+/// Build the `grpc-status`/`grpc-message` trailers reporting an error
+pub fn grpc_error_trailers(status: GrpcStatus, message: &str) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    trailers.insert(
+        HeaderName::from_static("grpc-status"),
+        HeaderValue::from(status as i32),
+    );
+    if let Ok(val) = HeaderValue::from_str(&percent_encode(message)) {
+        trailers.insert(HeaderName::from_static("grpc-message"), val);
+    }
+    trailers
+}
+
+/// Response body for proxied gRPC calls. Frames and trailers pass through as
+/// they arrive; if the backend fails mid-stream, the call ends with UNAVAILABLE
+/// trailers instead of a reset stream, so the client sees a gRPC status.
+pub struct GrpcResponseBody<B> {
+    inner: B,
+    done: bool,
+}
+
+impl<B> GrpcResponseBody<B> {
+    /// Wrap a backend response body.
+    pub fn new(inner: B) -> Self {
+        Self { inner, done: false }
+    }
+}
+
+impl<B> Body for GrpcResponseBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: std::fmt::Display,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, B::Error>>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                this.done = frame.is_trailers();
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                debug!("gRPC backend stream failed: {}", e);
+                this.done = true;
+                Poll::Ready(Some(Ok(Frame::trailers(grpc_error_trailers(
+                    GrpcStatus::Unavailable,
+                    "backend stream closed",
+                )))))
+            }
+            Poll::Ready(None) => {
+                this.done = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Error trailers may follow, but they carry no data
+        self.inner.size_hint()
+    }
+}
+
+/// Wire encoding of a gRPC-Web call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcWebEncoding {
+    /// `application/grpc-web`: the same length-prefixed messages as gRPC
+    Binary,
+    /// `application/grpc-web-text`: the binary stream, base64-encoded
+    Text,
+}
+
+impl GrpcWebEncoding {
+    /// The encoding of a gRPC-Web request or response, from its Content-Type
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        if content_type.starts_with(GRPC_WEB_TEXT_CONTENT_TYPE) {
+            Some(Self::Text)
+        } else if content_type.starts_with(GRPC_WEB_CONTENT_TYPE) {
+            Some(Self::Binary)
+        } else {
+            None
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Binary => GRPC_WEB_CONTENT_TYPE,
+            Self::Text => GRPC_WEB_TEXT_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Replace a Content-Type's `from` prefix with `to`, keeping any message
+/// format suffix such as `+proto`
+fn swap_content_type(headers: &mut HeaderMap, from: &str, to: &str) {
+    let suffix = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix(from))
+        .unwrap_or("")
+        .to_string();
+    if let Ok(val) = HeaderValue::from_str(&format!("{}{}", to, suffix)) {
+        headers.insert(CONTENT_TYPE, val);
+    }
+}
+
+/// Rewrite gRPC-Web request headers for a native gRPC backend: the Content-Type
+/// becomes `application/grpc` (keeping any `+proto`/`+json` suffix) and
+/// `TE: trailers` is set. The body of a text request must be decoded with
+/// [`decode_grpc_web_text`].
+pub fn grpc_web_to_grpc_headers(headers: &mut HeaderMap, encoding: GrpcWebEncoding) {
+    swap_content_type(headers, encoding.content_type(), GRPC_CONTENT_TYPE);
+    headers.insert(HeaderName::from_static("te"), HeaderValue::from_static("trailers"));
+}
+
+/// Decode a `grpc-web-text` body. Each base64 chunk may carry its own padding,
+/// so the body is decoded four characters at a time.
+pub fn decode_grpc_web_text(body: &[u8]) -> Option<Bytes> {
+    let chars: Vec<u8> = body.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    let mut decoded = BytesMut::with_capacity(chars.len() / 4 * 3);
+    for group in chars.chunks(4) {
+        let bytes = if group.len() == 4 {
+            STANDARD.decode(group)
+        } else {
+            STANDARD_NO_PAD.decode(group)
+        };
+        decoded.extend_from_slice(&bytes.ok()?);
+    }
+    Some(decoded.freeze())
+}
+
+/// Turn a native gRPC response into gRPC-Web for the given encoding: the
+/// Content-Type goes back to gRPC-Web and the body is re-encoded by
+/// [`GrpcWebResponseBody`]. Trailers-Only responses keep their status headers.
+pub fn grpc_to_grpc_web_response(
+    response: Response<BoxBody<Bytes, hyper::Error>>,
+    encoding: GrpcWebEncoding,
+) -> Response<BoxBody<Bytes, hyper::Error>> {
+    // Leave non-gRPC responses (e.g. from other middleware) alone
+    let is_grpc = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(GRPC_CONTENT_TYPE));
+    if !is_grpc || GrpcWebEncoding::from_headers(response.headers()).is_some() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    swap_content_type(&mut parts.headers, GRPC_CONTENT_TYPE, encoding.content_type());
+    // The trailer frame (and base64) change the length
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, GrpcWebResponseBody::new(body, encoding).boxed())
+}
+
+/// Response body translating native gRPC to gRPC-Web. Messages pass through
+/// (base64-encoded for the text encoding) and HTTP/2 trailers are appended to
+/// the body as a trailer frame, which is how gRPC-Web clients receive
+/// `grpc-status` and `grpc-message`.
+pub struct GrpcWebResponseBody<B> {
+    inner: B,
+    encoding: GrpcWebEncoding,
+    /// Bytes not yet base64-encoded, so chunks only carry padding at the very end
+    pending: BytesMut,
+    done: bool,
+}
+
+impl<B> GrpcWebResponseBody<B> {
+    /// Wrap a gRPC response body.
+    pub fn new(inner: B, encoding: GrpcWebEncoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            pending: BytesMut::new(),
+            done: false,
+        }
+    }
+
+    /// Encode outgoing bytes. Text keeps up to two bytes back until `last`.
+    fn encode(&mut self, data: &[u8], last: bool) -> Bytes {
+        match self.encoding {
+            GrpcWebEncoding::Binary => Bytes::copy_from_slice(data),
+            GrpcWebEncoding::Text => {
+                self.pending.extend_from_slice(data);
+                let ready = if last {
+                    self.pending.len()
+                } else {
+                    self.pending.len() / 3 * 3
+                };
+                Bytes::from(STANDARD.encode(self.pending.split_to(ready)))
+            }
+        }
+    }
+}
+
+impl<B> Body for GrpcWebResponseBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, B::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            let frame = match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => {
+                    this.done = true;
+                    let rest = this.encode(&[], true);
+                    return Poll::Ready((!rest.is_empty()).then(|| Ok(Frame::data(rest))));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let out = match frame.into_data() {
+                Ok(data) => this.encode(&data, false),
+                Err(frame) => {
+                    let Ok(trailers) = frame.into_trailers() else {
+                        continue;
+                    };
+                    this.done = true;
+                    let fields: Vec<(String, String)> = trailers
+                        .iter()
+                        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                        .collect();
+                    this.encode(&GrpcWebMiddleware::encode_trailers(&fields), true)
+                }
+            };
+            if !out.is_empty() {
+                return Poll::Ready(Some(Ok(Frame::data(out))));
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
 #[inline]
 fn empty_body() -> BoxBody<Bytes, hyper::Error> {
     Full::new(Bytes::new())
@@ -227,6 +496,94 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_mid_stream_failure_becomes_unavailable_trailers() {
+        use futures::stream;
+        use http_body_util::StreamBody;
+
+        let frames = vec![
+            Ok(Frame::data(Bytes::from_static(b"\0\0\0\0\x01a"))),
+            Err(std::io::Error::other("connection reset")),
+            Ok(Frame::data(Bytes::from_static(b"never sent"))),
+        ];
+        let mut body = GrpcResponseBody::new(StreamBody::new(stream::iter(frames)));
+
+        let data = body.frame().await.unwrap().unwrap();
+        assert_eq!(data.into_data().unwrap().as_ref(), b"\0\0\0\0\x01a");
+        let trailers = body.frame().await.unwrap().unwrap().into_trailers().unwrap();
+        assert_eq!(trailers["grpc-status"], "14");
+        assert_eq!(trailers["grpc-message"], "backend%20stream%20closed");
+        assert!(body.frame().await.is_none());
+        assert!(body.is_end_stream());
+    }
+
+    /// Body yielding `frames`, then `trailers`
+    fn grpc_body(frames: &[&'static [u8]], trailers: HeaderMap) -> BoxBody<Bytes, hyper::Error> {
+        use futures::stream;
+        use http_body_util::StreamBody;
+
+        let mut items: Vec<Result<Frame<Bytes>, hyper::Error>> = frames
+            .iter()
+            .map(|f| Ok(Frame::data(Bytes::from_static(f))))
+            .collect();
+        items.push(Ok(Frame::trailers(trailers)));
+        StreamBody::new(stream::iter(items)).boxed()
+    }
+
+    fn ok_trailers() -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        trailers
+    }
+
+    async fn web_response(encoding: GrpcWebEncoding) -> (HeaderMap, Bytes) {
+        let response = Response::builder()
+            .header(CONTENT_TYPE, "application/grpc+proto")
+            .body(grpc_body(&[b"\0\0\0\0\x02hi", b"\0\0\0\0\x01!"], ok_trailers()))
+            .unwrap();
+        let (parts, body) = grpc_to_grpc_web_response(response, encoding).into_parts();
+        (parts.headers, body.collect().await.unwrap().to_bytes())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_binary_response_appends_trailer_frame() {
+        let (headers, body) = web_response(GrpcWebEncoding::Binary).await;
+        assert_eq!(headers[CONTENT_TYPE], "application/grpc-web+proto");
+        assert_eq!(
+            body.as_ref(),
+            b"\0\0\0\0\x02hi\0\0\0\0\x01!\x80\0\0\0\x10grpc-status: 0\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_text_response_is_one_base64_stream() {
+        let (headers, body) = web_response(GrpcWebEncoding::Text).await;
+        assert_eq!(headers[CONTENT_TYPE], "application/grpc-web-text+proto");
+        // Padding only at the very end, so it decodes in one go
+        assert!(!body[..body.len() - 2].contains(&b'='));
+        assert_eq!(
+            STANDARD.decode(&body).unwrap(),
+            b"\0\0\0\0\x02hi\0\0\0\0\x01!\x80\0\0\0\x10grpc-status: 0\r\n"
+        );
+    }
+
+    #[test]
+    fn test_grpc_web_request_translation() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc-web-text+proto"));
+        assert_eq!(GrpcWebEncoding::from_headers(&headers), Some(GrpcWebEncoding::Text));
+        grpc_web_to_grpc_headers(&mut headers, GrpcWebEncoding::Text);
+        assert_eq!(headers[CONTENT_TYPE], "application/grpc+proto");
+        assert_eq!(headers["te"], "trailers");
+        assert_eq!(GrpcWebEncoding::from_headers(&headers), None);
+
+        // Separately padded chunks decode back to back
+        let text = format!("{}{}", STANDARD.encode(b"\0\0\0\0\x01a"), STANDARD.encode(b"xy"));
+        assert_eq!(decode_grpc_web_text(text.as_bytes()).unwrap().as_ref(), b"\0\0\0\0\x01axy");
+        assert_eq!(decode_grpc_web_text(b"AAAA\r\nAQ").unwrap().as_ref(), b"\0\0\0\x01");
+        assert!(decode_grpc_web_text(b"not base64!").is_none());
+    }
+
     #[test]
     fn test_percent_encode() {
         assert_eq!(percent_encode("hello"), "hello");
@@ -1,32 +1,119 @@
 //! HTTP/2 connection pool for multiplexed upstream connections (h2c and gRPC).
 
+use crate::config::ServersTransport;
+use crate::metrics::Metrics;
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
-use hyper::client::conn::http2::SendRequest;
+use hyper::client::conn::http2::{Builder, SendRequest};
 use hyper::{Request, Response};
-use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::Notify;
 use tracing::debug;
 
+/// Sizing and keepalive settings for an [`Http2ConnectionPool`]
+#[derive(Debug, Clone)]
+pub struct Http2PoolConfig {
+    /// Requests in flight on one connection before another is opened
+    pub max_concurrent_streams: usize,
+    /// Connections per authority; once reached, requests share the least loaded one
+    pub max_connections_per_host: usize,
+    /// Close connections unused for this long (zero keeps them open)
+    pub idle_timeout: Duration,
+    /// Send a PING after this long without reading a frame (`None` disables keepalives)
+    pub keep_alive_interval: Option<Duration>,
+    /// Close the connection when a PING isn't acknowledged within this time
+    pub keep_alive_timeout: Duration,
+}
+
+impl Default for Http2PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_streams: 100,
+            max_connections_per_host: 200,
+            idle_timeout: Duration::from_secs(90),
+            keep_alive_interval: None,
+            keep_alive_timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+impl Http2PoolConfig {
+    /// Settings from a servers transport: `maxIdleConnsPerHost` bounds the
+    /// connections per backend, and `forwardingTimeouts` sets the idle timeout
+    /// (`idleConnTimeout`) and PING keepalives (`readIdleTimeout`, `pingTimeout`).
+    pub fn from_transport(transport: &ServersTransport) -> Self {
+        let mut config = Self::default();
+        if transport.max_idle_conns_per_host > 0 {
+            config.max_connections_per_host = transport.max_idle_conns_per_host as usize;
+        }
+        if let Some(timeouts) = &transport.forwarding_timeouts {
+            config.idle_timeout = timeouts.idle_conn_timeout.as_std();
+            config.keep_alive_interval =
+                (!timeouts.read_idle_timeout.is_zero()).then(|| timeouts.read_idle_timeout.as_std());
+            if !timeouts.ping_timeout.is_zero() {
+                config.keep_alive_timeout = timeouts.ping_timeout.as_std();
+            }
+        }
+        config
+    }
+}
+
 /// HTTP/2 connection pool for upstream connections
-/// Maintains persistent HTTP/2 connections with multiplexing support
+/// Maintains persistent HTTP/2 connections per backend authority, multiplexing
+/// requests over each and opening more as streams fill up
 pub struct Http2ConnectionPool {
-    /// Map of host -> connection
-    connections: RwLock<HashMap<String, Arc<Http2Connection>>>,
+    config: Http2PoolConfig,
+    /// Map of authority (`scheme://host:port`) -> its connections
+    authorities: RwLock<HashMap<String, Arc<AuthorityPool>>>,
+}
+
+#[derive(Default)]
+struct AuthorityPool {
+    state: Mutex<AuthorityState>,
+    /// Woken when a connect attempt ends, for requests waiting on a slot
+    connected: Notify,
+}
+
+#[derive(Default)]
+struct AuthorityState {
+    connections: Vec<Arc<Http2Connection>>,
+    /// Connections being established, counted against the per-host limit
+    connecting: usize,
 }
 
 struct Http2Connection {
-    sender: Mutex<Option<SendRequest<BoxBody<Bytes, hyper::Error>>>>,
+    sender: SendRequest<BoxBody<Bytes, hyper::Error>>,
+    /// Requests still waiting for their response headers
+    in_flight: AtomicUsize,
+    /// When the connection last finished a request
+    idle_since: Mutex<Instant>,
+}
+
+/// Outcome of picking a connection for a request
+enum Checkout {
+    Reuse(Arc<Http2Connection>),
+    Connect,
+    /// Every slot is taken by a connection still being established
+    Wait,
 }
 
 impl Http2ConnectionPool {
-    /// Create an empty connection pool.
+    /// Create an empty connection pool with default settings.
     pub fn new() -> Self {
+        Self::with_config(Http2PoolConfig::default())
+    }
+
+    /// Create an empty connection pool with the given settings.
+    pub fn with_config(config: Http2PoolConfig) -> Self {
         Self {
-            connections: RwLock::new(HashMap::new()),
+            config,
+            authorities: RwLock::new(HashMap::new()),
         }
     }
 
@@ -37,43 +124,72 @@ impl Http2ConnectionPool {
         port: u16,
         req: Request<BoxBody<Bytes, hyper::Error>>,
     ) -> Result<Response<hyper::body::Incoming>, Http2Error> {
-        // Get or create connection first, before consuming the request
-        let conn = self.get_or_create_connection(host, port).await?;
-
-        match conn.send(req).await {
-            Ok(response) => Ok(response),
-            Err(Http2Error::ConnectionClosed) => {
-                // Connection closed, remove it so next request creates a new one
-                self.remove_connection(host, port).await;
-                Err(Http2Error::ConnectionClosed)
+        let authority = authority_key(req.uri().scheme_str().unwrap_or("http"), host, port);
+        let pool = self.authority_pool(&authority);
+
+        let conn = loop {
+            // Registered before checking out so a connect finishing in between isn't missed
+            let connected = pool.connected.notified();
+            tokio::pin!(connected);
+            connected.as_mut().enable();
+
+            match self.checkout(&pool) {
+                Checkout::Reuse(conn) => break conn,
+                Checkout::Connect => {
+                    let _connecting = ConnectingGuard(&pool);
+                    let conn = self.create_connection(host, port).await?;
+                    pool.state.lock().connections.push(Arc::clone(&conn));
+                    break conn;
+                }
+                Checkout::Wait => connected.await,
             }
-            Err(e) => Err(e),
+        };
+
+        let _in_flight = InFlightGuard::new(&conn);
+        let mut sender = conn.sender.clone();
+        if let Err(e) = sender.ready().await {
+            // Connection closed, remove it so the next request creates a new one
+            pool.state.lock().connections.retain(|c| !Arc::ptr_eq(c, &conn));
+            return Err(if sender.is_closed() {
+                Http2Error::ConnectionClosed
+            } else {
+                Http2Error::Ready(e)
+            });
         }
+        sender.send_request(req).await.map_err(Http2Error::Request)
     }
 
-    /// Get existing connection or create a new one
-    async fn get_or_create_connection(&self, host: &str, port: u16) -> Result<Arc<Http2Connection>, Http2Error> {
-        let key = format!("{}:{}", host, port);
-
-        // Check for existing connection
-        {
-            let connections = self.connections.read().await;
-            if let Some(conn) = connections.get(&key)
-                && conn.is_ready().await {
-                    return Ok(Arc::clone(conn));
-                }
+    /// The pool for an authority, created on first use
+    fn authority_pool(&self, authority: &str) -> Arc<AuthorityPool> {
+        if let Some(pool) = self.authorities.read().get(authority) {
+            return Arc::clone(pool);
         }
+        Arc::clone(self.authorities.write().entry(authority.to_string()).or_default())
+    }
 
-        // Create new connection
-        let conn = self.create_connection(host, port).await?;
-
-        // Store connection
+    /// Pick the least loaded live connection with a free stream, or decide to
+    /// open another while under the per-host limit
+    fn checkout(&self, pool: &AuthorityPool) -> Checkout {
+        let mut state = pool.state.lock();
+        let now = Instant::now();
+        state
+            .connections
+            .retain(|conn| !conn.sender.is_closed() && !conn.is_idle_for(self.config.idle_timeout, now));
+
+        let least_loaded = state.connections.iter().min_by_key(|conn| conn.in_flight()).cloned();
+        if let Some(conn) = &least_loaded
+            && conn.in_flight() < self.config.max_concurrent_streams
         {
-            let mut connections = self.connections.write().await;
-            connections.insert(key, Arc::clone(&conn));
+            return Checkout::Reuse(Arc::clone(conn));
+        }
+        if state.connections.len() + state.connecting < self.config.max_connections_per_host.max(1) {
+            state.connecting += 1;
+            return Checkout::Connect;
+        }
+        match least_loaded {
+            Some(conn) => Checkout::Reuse(conn),
+            None => Checkout::Wait,
         }
-
-        Ok(conn)
     }
 
     /// Create a new HTTP/2 connection
@@ -89,12 +205,18 @@ impl Http2ConnectionPool {
 
         let io = TokioIo::new(stream);
 
-        // Perform HTTP/2 handshake
-        let (sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor::new(), io)
-            .await
-            .map_err(Http2Error::Handshake)?;
+        // Perform HTTP/2 handshake, with PING keepalives when configured
+        let mut builder = Builder::new(TokioExecutor::new());
+        builder.timer(TokioTimer::new());
+        if let Some(interval) = self.config.keep_alive_interval {
+            builder
+                .keep_alive_interval(interval)
+                .keep_alive_timeout(self.config.keep_alive_timeout)
+                .keep_alive_while_idle(true);
+        }
+        let (sender, conn) = builder.handshake(io).await.map_err(Http2Error::Handshake)?;
 
-        // Spawn connection driver
+        // Spawn connection driver; it ends once the pool drops the sender
         let addr_clone = addr.clone();
         tokio::spawn(async move {
             if let Err(e) = conn.await {
@@ -103,23 +225,65 @@ impl Http2ConnectionPool {
         });
 
         Ok(Arc::new(Http2Connection {
-            sender: Mutex::new(Some(sender)),
+            sender,
+            in_flight: AtomicUsize::new(0),
+            idle_since: Mutex::new(Instant::now()),
         }))
     }
 
-    /// Remove a cached connection for the given host and port.
+    /// Remove the cached connections for the given host and port.
     pub async fn remove_connection(&self, host: &str, port: u16) {
-        let key = format!("{}:{}", host, port);
-        let mut connections = self.connections.write().await;
-        connections.remove(&key);
+        let suffix = format!("://{}:{}", host, port);
+        self.authorities.write().retain(|authority, _| !authority.ends_with(&suffix));
     }
 
-    /// Return current pool statistics.
-    pub async fn stats(&self) -> Http2PoolStats {
-        let connections = self.connections.read().await;
-        Http2PoolStats {
-            connection_count: connections.len(),
+    /// Close connections idle for longer than the idle timeout, drop authorities
+    /// left without connections, and record the pool size of each authority in
+    /// the `connection_pool_size` gauge. Returns the number of connections closed.
+    pub fn evict_idle(&self) -> usize {
+        let now = Instant::now();
+        let mut evicted = 0;
+        self.authorities.write().retain(|authority, pool| {
+            let mut state = pool.state.lock();
+            let before = state.connections.len();
+            state
+                .connections
+                .retain(|conn| !conn.sender.is_closed() && !conn.is_idle_for(self.config.idle_timeout, now));
+            evicted += before - state.connections.len();
+            Metrics::global().record_connection_pool_size(authority, state.connections.len());
+            !state.connections.is_empty() || state.connecting > 0
+        });
+        if evicted > 0 {
+            debug!("Closed {} idle HTTP/2 connections", evicted);
         }
+        evicted
+    }
+
+    /// Return current pool statistics across all authorities.
+    pub async fn stats(&self) -> Http2PoolStats {
+        self.stats_by_authority()
+            .await
+            .into_values()
+            .fold(Http2PoolStats::default(), |total, stats| Http2PoolStats {
+                connection_count: total.connection_count + stats.connection_count,
+                in_flight: total.in_flight + stats.in_flight,
+            })
+    }
+
+    /// Return pool statistics per authority (`scheme://host:port`).
+    pub async fn stats_by_authority(&self) -> HashMap<String, Http2PoolStats> {
+        self.authorities
+            .read()
+            .iter()
+            .map(|(authority, pool)| {
+                let state = pool.state.lock();
+                let stats = Http2PoolStats {
+                    connection_count: state.connections.len(),
+                    in_flight: state.connections.iter().map(|conn| conn.in_flight()).sum(),
+                };
+                (authority.clone(), stats)
+            })
+            .collect()
     }
 }
 
@@ -130,36 +294,59 @@ impl Default for Http2ConnectionPool {
 }
 
 impl Http2Connection {
-    /// Check if the connection is ready to send requests
-    async fn is_ready(&self) -> bool {
-        let sender_guard = self.sender.lock().await;
-        if let Some(ref sender) = *sender_guard {
-            sender.is_ready()
-        } else {
-            false
-        }
+    #[inline]
+    fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
     }
 
-    /// Send request (must have valid sender)
-    async fn send(
-        &self,
-        req: Request<BoxBody<Bytes, hyper::Error>>,
-    ) -> Result<Response<hyper::body::Incoming>, Http2Error> {
-        let mut sender_guard = self.sender.lock().await;
+    /// Whether the connection has had nothing in flight for `timeout` (never when zero)
+    fn is_idle_for(&self, timeout: Duration, now: Instant) -> bool {
+        !timeout.is_zero()
+            && self.in_flight() == 0
+            && now.saturating_duration_since(*self.idle_since.lock()) >= timeout
+    }
+}
 
-        if let Some(ref mut sender) = *sender_guard {
-            sender.ready().await.map_err(Http2Error::Ready)?;
-            sender.send_request(req).await.map_err(Http2Error::Request)
-        } else {
-            Err(Http2Error::ConnectionClosed)
-        }
+/// Counts a request against its connection until the response headers arrive
+struct InFlightGuard<'a>(&'a Http2Connection);
+
+impl<'a> InFlightGuard<'a> {
+    fn new(conn: &'a Http2Connection) -> Self {
+        conn.in_flight.fetch_add(1, Ordering::AcqRel);
+        Self(conn)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.idle_since.lock() = Instant::now();
+        self.0.in_flight.fetch_sub(1, Ordering::AcqRel);
     }
 }
 
+/// Releases a reserved connection slot once the connect attempt ends, including
+/// when it fails or is cancelled
+struct ConnectingGuard<'a>(&'a AuthorityPool);
+
+impl Drop for ConnectingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().connecting -= 1;
+        self.0.connected.notify_waiters();
+    }
+}
+
+#[inline]
+fn authority_key(scheme: &str, host: &str, port: u16) -> String {
+    format!("{}://{}:{}", scheme, host, port)
+}
+
 /// Snapshot of HTTP/2 pool metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Http2PoolStats {
     /// Number of active connections in the pool.
     pub connection_count: usize,
+    /// Requests waiting for response headers.
+    pub in_flight: usize,
 }
 
 /// Errors that can occur during HTTP/2 connection lifecycle.
@@ -194,11 +381,47 @@ impl std::error::Error for Http2Error {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ForwardingTimeouts;
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Incoming;
+    use hyper::service::service_fn;
+    use std::convert::Infallible;
+    use tokio::net::TcpListener;
+
+    /// Spawn an h2c server answering every request after `delay`
+    async fn spawn_h2c_server(delay: Duration) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let service = service_fn(move |_req: Request<Incoming>| async move {
+                        tokio::time::sleep(delay).await;
+                        Ok::<_, Infallible>(Response::new(Empty::<Bytes>::new()))
+                    });
+                    let _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        port
+    }
+
+    async fn get(pool: &Http2ConnectionPool, port: u16) {
+        let req = Request::builder()
+            .uri(format!("http://127.0.0.1:{}/", port))
+            .body(Empty::new().map_err(|never| match never {}).boxed())
+            .unwrap();
+        let response = pool.send_request("127.0.0.1", port, req).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
 
     #[test]
     fn test_pool_creation() {
         let pool = Http2ConnectionPool::new();
-        assert!(pool.connections.try_read().is_ok());
+        assert!(pool.authorities.try_read().is_some());
     }
 
     #[tokio::test]
@@ -207,4 +430,94 @@ mod tests {
         let stats = pool.stats().await;
         assert_eq!(stats.connection_count, 0);
     }
+
+    fn transport(max_idle_conns_per_host: i32, forwarding_timeouts: ForwardingTimeouts) -> ServersTransport {
+        ServersTransport {
+            server_name: None,
+            insecure_skip_verify: false,
+            root_cas: vec![],
+            certificates: vec![],
+            max_idle_conns_per_host,
+            forwarding_timeouts: Some(forwarding_timeouts),
+            disable_http2: false,
+            peer_cert_uri: None,
+        }
+    }
+
+    #[test]
+    fn test_config_from_transport() {
+        let timeouts = ForwardingTimeouts {
+            idle_conn_timeout: crate::config::Duration::from_secs(5),
+            read_idle_timeout: crate::config::Duration::from_secs(10),
+            ping_timeout: crate::config::Duration::from_secs(3),
+            ..Default::default()
+        };
+        let config = Http2PoolConfig::from_transport(&transport(4, timeouts));
+        assert_eq!(config.max_connections_per_host, 4);
+        assert_eq!(config.idle_timeout, Duration::from_secs(5));
+        assert_eq!(config.keep_alive_interval, Some(Duration::from_secs(10)));
+        assert_eq!(config.keep_alive_timeout, Duration::from_secs(3));
+
+        // Without readIdleTimeout no PINGs are sent
+        let config = Http2PoolConfig::from_transport(&transport(200, ForwardingTimeouts::default()));
+        assert_eq!(config.keep_alive_interval, None);
+    }
+
+    #[tokio::test]
+    async fn test_backends_get_separate_pools() {
+        let first = spawn_h2c_server(Duration::ZERO).await;
+        let second = spawn_h2c_server(Duration::ZERO).await;
+        let pool = Http2ConnectionPool::new();
+
+        for _ in 0..3 {
+            get(&pool, first).await;
+        }
+        get(&pool, second).await;
+
+        let stats = pool.stats_by_authority().await;
+        assert_eq!(stats.len(), 2);
+        // Sequential requests reuse one connection per backend
+        let one = Http2PoolStats { connection_count: 1, in_flight: 0 };
+        assert_eq!(stats[&format!("http://127.0.0.1:{}", first)], one);
+        assert_eq!(stats[&format!("http://127.0.0.1:{}", second)], one);
+
+        pool.remove_connection("127.0.0.1", first).await;
+        assert_eq!(pool.stats().await.connection_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_opens_connections_up_to_per_host_limit() {
+        let port = spawn_h2c_server(Duration::from_millis(100)).await;
+
+        let pool = Http2ConnectionPool::with_config(Http2PoolConfig {
+            max_concurrent_streams: 1,
+            max_connections_per_host: 2,
+            ..Http2PoolConfig::default()
+        });
+        tokio::join!(get(&pool, port), get(&pool, port), get(&pool, port));
+
+        // Full connections spill onto new ones until the limit, then share
+        assert_eq!(pool.stats().await.connection_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_idle_connections_are_evicted() {
+        let port = spawn_h2c_server(Duration::ZERO).await;
+        let pool = Http2ConnectionPool::with_config(Http2PoolConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..Http2PoolConfig::default()
+        });
+
+        get(&pool, port).await;
+        assert_eq!(pool.evict_idle(), 0);
+        assert_eq!(pool.stats().await.connection_count, 1);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(pool.evict_idle(), 1);
+        assert!(pool.stats_by_authority().await.is_empty());
+
+        // The next request reconnects
+        get(&pool, port).await;
+        assert_eq!(pool.stats().await.connection_count, 1);
+    }
 }
@@ -5,12 +5,20 @@ pub mod grpc;
 mod handler;
 /// HTTP/2 connection pooling for upstream backends.
 pub mod http2_client;
+/// Response streaming: Server-Sent Events detection and flush-interval pacing.
+pub mod streaming;
 /// Shared TLS client configuration for upstream backend connections.
 pub(crate) mod tls_client;
+mod transport;
 /// WebSocket upgrade detection and bidirectional proxying.
 pub mod websocket;
 
-pub use grpc::{is_grpc_request, is_grpc_web_request, grpc_error_response, grpc_gateway_error, GrpcStatus};
+pub use grpc::{
+    decode_grpc_web_text, grpc_error_response, grpc_error_trailers, grpc_gateway_error, grpc_to_grpc_web_response,
+    grpc_web_to_grpc_headers, is_grpc_request, is_grpc_web_request, GrpcResponseBody, GrpcStatus, GrpcWebEncoding,
+    GrpcWebResponseBody,
+};
 pub use handler::ProxyHandler;
-pub use http2_client::{Http2ConnectionPool, Http2Error, Http2PoolStats};
+pub use http2_client::{Http2ConnectionPool, Http2Error, Http2PoolConfig, Http2PoolStats};
+pub use streaming::{is_event_stream, FlushIntervalBody, InFlightBody};
 pub use websocket::{handle_websocket_upgrade, is_websocket_upgrade};
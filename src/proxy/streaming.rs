@@ -0,0 +1,252 @@
+use crate::balancer::InFlight;
+use bytes::{Bytes, BytesMut};
+use http_body_util::combinators::BoxBody;
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::header::CONTENT_TYPE;
+use hyper::HeaderMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{sleep, Sleep};
+
+/// Most data held back between flushes; reaching it flushes early
+const MAX_PENDING_BYTES: usize = 32 * 1024;
+
+/// Returns true for Server-Sent Events responses (`Content-Type: text/event-stream`),
+/// which must reach the client event by event.
+#[inline]
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
+/// Response body that coalesces backend chunks and flushes them to the client at
+/// most once per interval (or when enough data is pending), like Traefik's
+/// `responseForwarding.flushInterval`.
+pub struct FlushIntervalBody {
+    inner: BoxBody<Bytes, hyper::Error>,
+    interval: Duration,
+    pending: BytesMut,
+    /// When the pending data must go out; armed by the first chunk after a flush
+    deadline: Option<Pin<Box<Sleep>>>,
+    /// Trailers held until the pending data ahead of them is sent
+    trailers: Option<Frame<Bytes>>,
+    inner_done: bool,
+}
+
+impl FlushIntervalBody {
+    /// Wrap a response body, flushing every `interval`.
+    pub fn new(inner: BoxBody<Bytes, hyper::Error>, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            pending: BytesMut::new(),
+            deadline: None,
+            trailers: None,
+            inner_done: false,
+        }
+    }
+
+    fn flush(&mut self) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+        self.deadline = None;
+        Poll::Ready(Some(Ok(Frame::data(self.pending.split().freeze()))))
+    }
+}
+
+impl Body for FlushIntervalBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.inner_done {
+                if !this.pending.is_empty() {
+                    return this.flush();
+                }
+                return Poll::Ready(this.trailers.take().map(Ok));
+            }
+
+            match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => match frame.into_data() {
+                    Ok(data) => {
+                        if this.pending.is_empty() {
+                            this.deadline = Some(Box::pin(sleep(this.interval)));
+                        }
+                        this.pending.extend_from_slice(&data);
+                        if this.pending.len() >= MAX_PENDING_BYTES {
+                            return this.flush();
+                        }
+                    }
+                    Err(trailers) => {
+                        this.trailers = Some(trailers);
+                        this.inner_done = true;
+                    }
+                },
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => this.inner_done = true,
+                Poll::Pending => {
+                    let due = this
+                        .deadline
+                        .as_mut()
+                        .is_some_and(|deadline| deadline.as_mut().poll(cx).is_ready());
+                    if due && !this.pending.is_empty() {
+                        return this.flush();
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner_done && self.pending.is_empty() && self.trailers.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = self.inner.size_hint();
+        let pending = self.pending.len() as u64;
+        hint.set_lower(hint.lower() + pending);
+        if let Some(upper) = hint.upper() {
+            hint.set_upper(upper + pending);
+        }
+        hint
+    }
+}
+
+/// Response body that keeps its backend counted as busy until the last frame
+/// is sent (or the client goes away), so load-aware balancers see streaming
+/// responses as load.
+pub struct InFlightBody {
+    inner: BoxBody<Bytes, hyper::Error>,
+    in_flight: Option<InFlight>,
+}
+
+impl InFlightBody {
+    /// Wrap a response body, releasing `in_flight` when it ends or is dropped.
+    pub fn new(inner: BoxBody<Bytes, hyper::Error>, in_flight: InFlight) -> Self {
+        Self {
+            inner,
+            in_flight: Some(in_flight),
+        }
+    }
+}
+
+impl Body for InFlightBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(None) = frame {
+            this.in_flight = None;
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::{self, StreamExt};
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::header::HeaderValue;
+    use std::time::Instant;
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+        headers
+    }
+
+    /// Body yielding one chunk every `gap`
+    fn paced_body(chunks: &'static [&'static str], gap: Duration) -> BoxBody<Bytes, hyper::Error> {
+        let frames = stream::iter(chunks).then(move |chunk| async move {
+            tokio::time::sleep(gap).await;
+            Ok::<_, hyper::Error>(Frame::data(Bytes::from_static(chunk.as_bytes())))
+        });
+        BodyExt::boxed(StreamBody::new(frames))
+    }
+
+    #[test]
+    fn test_detects_event_stream() {
+        assert!(is_event_stream(&headers("text/event-stream")));
+        assert!(is_event_stream(&headers("Text/Event-Stream; charset=utf-8")));
+        assert!(!is_event_stream(&headers("text/html")));
+        assert!(!is_event_stream(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_coalesces_chunks_within_interval() {
+        let chunks: &[&str] = &["a", "b", "c", "d", "e", "f"];
+        let mut body = FlushIntervalBody::new(paced_body(chunks, Duration::from_millis(10)), Duration::from_millis(200));
+
+        let mut flushes = Vec::new();
+        while let Some(frame) = body.frame().await {
+            flushes.push(frame.unwrap().into_data().unwrap());
+        }
+        // Everything arrived inside one interval, so it went out together
+        assert_eq!(flushes.concat(), b"abcdef");
+        assert!(flushes.len() < chunks.len(), "{:?}", flushes);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_on_interval() {
+        let chunks: &[&str] = &["first", "second"];
+        let started = Instant::now();
+        let mut body = FlushIntervalBody::new(paced_body(chunks, Duration::from_millis(150)), Duration::from_millis(20));
+
+        // The first chunk is flushed on the interval, not held until the second arrives
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(first.as_ref(), b"first");
+        assert!(started.elapsed() < Duration::from_millis(290));
+
+        let second = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(second.as_ref(), b"second");
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_released_when_body_ends() {
+        use crate::balancer::LoadBalancer;
+        use crate::config::Server;
+
+        let servers = ["http://a", "http://b"]
+            .map(|url| Server {
+                url: url.to_string(),
+                weight: 1,
+                preserve_path: false,
+                parsed_uri: None,
+                url_arc: None,
+            })
+            .to_vec();
+        let lb = LoadBalancer::with_strategy(servers, "leastConn");
+
+        let mut body = InFlightBody::new(paced_body(&["a"], Duration::ZERO), lb.acquire(0));
+        assert!(body.frame().await.is_some());
+        // Still streaming, so least-connections avoids the busy server
+        assert_eq!(lb.next_server().unwrap().url, "http://b");
+
+        assert!(body.frame().await.is_none());
+        assert_eq!(lb.next_server().unwrap().url, "http://a");
+    }
+}
@@ -1,16 +1,17 @@
 //! Shared rustls ClientConfig / TlsConnector for outbound upstream connections.
 //!
-//! Mirrors `proxy::handler`'s `NoVerifier` approach: backend certificates are
-//! not validated (insecureSkipVerify), matching the permissive mode used for
-//! the HTTP client pool. Separate so the WebSocket path can dial TLS upstreams.
+//! Backend certificates are not validated (insecureSkipVerify), matching the
+//! permissive mode of the default HTTP client pool. Separate so the WebSocket
+//! path can dial TLS upstreams.
 
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::ClientConfig;
 use std::sync::Arc;
 use tokio_rustls::TlsConnector;
 
+/// TLS certificate verifier that accepts all certificates (insecureSkipVerify).
 #[derive(Debug)]
-struct NoVerifier;
+pub(crate) struct NoVerifier;
 
 impl ServerCertVerifier for NoVerifier {
     fn verify_server_cert(
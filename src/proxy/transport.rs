@@ -0,0 +1,214 @@
+//! Backend HTTP clients per servers transport.
+//!
+//! Services without a `serversTransport` share the default clients, which don't
+//! verify backend certificates. A configured transport verifies backends
+//! against its `rootCAs` (or the system roots) unless `insecureSkipVerify` is
+//! set, presents its client `certificates` for backend mTLS, overrides SNI and
+//! the verified name with `serverName`, and can turn off HTTP/2 negotiation.
+
+use super::tls_client::NoVerifier;
+use crate::config::{ForwardingTimeouts, ServersTransport};
+use crate::service::BackendTransport;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use hyper_rustls::{FixedServerNameResolver, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use parking_lot::RwLock;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Type alias for the HTTPS-capable connector used by proxy clients.
+type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+
+/// HTTP/1.1 and HTTP/2 client pools sharing one transport's settings.
+pub(super) struct BackendClients {
+    /// Negotiates HTTP/2 with TLS backends that offer it, unless disabled
+    pub http: Client<HttpsConnector, BoxBody<Bytes, hyper::Error>>,
+    /// HTTP/2 only, for gRPC and h2c backends
+    pub h2: Client<HttpsConnector, BoxBody<Bytes, hyper::Error>>,
+}
+
+impl BackendClients {
+    /// Clients that don't verify backend certificates, with default timeouts
+    fn insecure() -> Self {
+        let tls = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerifier))
+            .with_no_client_auth();
+        Self::build(tls, None, false, &ForwardingTimeouts::default())
+    }
+
+    /// Clients for a servers transport
+    fn for_transport(transport: &ServersTransport) -> Result<Self> {
+        let server_name = transport
+            .server_name
+            .as_deref()
+            .map(|name| {
+                ServerName::try_from(name.to_string())
+                    .with_context(|| format!("Invalid serverName '{}'", name))
+            })
+            .transpose()?;
+        let timeouts = transport.forwarding_timeouts.clone().unwrap_or_default();
+        Ok(Self::build(
+            tls_config(transport)?,
+            server_name,
+            transport.disable_http2,
+            &timeouts,
+        ))
+    }
+
+    /// Clients that give up dialing after `dialTimeout` and close pooled
+    /// connections idle for `idleConnTimeout` (zero disables either).
+    fn build(
+        tls: ClientConfig,
+        server_name: Option<ServerName<'static>>,
+        disable_http2: bool,
+        timeouts: &ForwardingTimeouts,
+    ) -> Self {
+        let dial_timeout = (!timeouts.dial_timeout.is_zero()).then(|| timeouts.dial_timeout.as_std());
+        let idle_timeout =
+            (!timeouts.idle_conn_timeout.is_zero()).then(|| timeouts.idle_conn_timeout.as_std());
+
+        let connector = |http1_only: bool| {
+            let mut http = HttpConnector::new();
+            http.set_nodelay(true);
+            http.set_reuse_address(true);
+            http.enforce_http(false);
+            http.set_connect_timeout(dial_timeout);
+
+            let builder = HttpsConnectorBuilder::new()
+                .with_tls_config(tls.clone())
+                .https_or_http();
+            let builder = match &server_name {
+                Some(name) => builder.with_server_name_resolver(FixedServerNameResolver::new(name.clone())),
+                None => builder,
+            };
+            if http1_only {
+                builder.enable_http1().wrap_connector(http)
+            } else {
+                builder.enable_all_versions().wrap_connector(http)
+            }
+        };
+
+        let http = Client::builder(TokioExecutor::new())
+            .pool_idle_timeout(idle_timeout)
+            .pool_max_idle_per_host(256)
+            .retry_canceled_requests(true)
+            .set_host(true)
+            .build(connector(disable_http2));
+
+        let h2 = Client::builder(TokioExecutor::new())
+            .pool_idle_timeout(idle_timeout)
+            .pool_max_idle_per_host(256)
+            .retry_canceled_requests(true)
+            .set_host(true)
+            .http2_only(true)
+            .build(connector(false));
+
+        Self { http, h2 }
+    }
+}
+
+/// Backend clients for the default transport and each distinct servers
+/// transport, so services sharing transport settings share connection pools.
+pub(super) struct ClientPools {
+    default: Arc<BackendClients>,
+    by_transport: RwLock<HashMap<String, Arc<BackendClients>>>,
+}
+
+impl ClientPools {
+    pub fn new() -> Self {
+        Self {
+            default: Arc::new(BackendClients::insecure()),
+            by_transport: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The clients for a service's transport, built on first use. Fails when
+    /// the transport's certificates can't be loaded.
+    pub fn for_transport(&self, transport: Option<&BackendTransport>) -> Result<Arc<BackendClients>> {
+        let Some(transport) = transport else {
+            return Ok(Arc::clone(&self.default));
+        };
+        if let Some(clients) = self.by_transport.read().get(&transport.key) {
+            return Ok(Arc::clone(clients));
+        }
+
+        let clients = BackendClients::for_transport(&transport.config)
+            .with_context(|| format!("Invalid serversTransport '{}'", transport.name))?;
+        Ok(Arc::clone(
+            self.by_transport
+                .write()
+                .entry(transport.key.clone())
+                .or_insert_with(|| Arc::new(clients)),
+        ))
+    }
+}
+
+/// TLS settings for a servers transport
+fn tls_config(transport: &ServersTransport) -> Result<ClientConfig> {
+    let builder = ClientConfig::builder();
+    let builder = if transport.insecure_skip_verify {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerifier))
+    } else {
+        let mut roots = RootCertStore::empty();
+        if transport.root_cas.is_empty() {
+            let native = rustls_native_certs::load_native_certs();
+            for error in &native.errors {
+                warn!("Failed to load a system root certificate: {}", error);
+            }
+            roots.add_parsable_certificates(native.certs);
+        }
+        for path in &transport.root_cas {
+            for cert in load_certs(path)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("Invalid root CA in {}", path))?;
+            }
+        }
+        builder.with_root_certificates(roots)
+    };
+
+    let Some(certificate) = transport.certificates.first() else {
+        return Ok(builder.with_no_client_auth());
+    };
+    if transport.certificates.len() > 1 {
+        warn!("Only the first of a serversTransport's certificates is presented to backends");
+    }
+    builder
+        .with_client_auth_cert(
+            load_certs(&certificate.cert_file)?,
+            load_private_key(&certificate.key_file)?,
+        )
+        .with_context(|| format!("Invalid client certificate {}", certificate.cert_file))
+}
+
+/// Load certificates from a PEM file
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open certificate file: {}", path))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificates from: {}", path))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in file: {}", path);
+    }
+    Ok(certs)
+}
+
+/// Load a private key from a PEM file
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("Failed to open key file: {}", path))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse private key from: {}", path))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in file: {}", path))
+}
@@ -1,16 +1,63 @@
 //! WebSocket upgrade detection and transparent bidirectional proxying.
+//!
+//! The `permessage-deflate` extension (RFC 7692) is negotiated end to end by
+//! default: the client's offer is forwarded and the backend's answer relayed,
+//! after which frames are copied byte for byte. When the proxy terminates the
+//! extension instead, messages are inflated towards the backend and deflated
+//! towards the client; control frames are forwarded as soon as they arrive,
+//! even between the fragments of a data message.
+//!
+//! The relay tracks frame boundaries in both modes so the proxy can close a
+//! tunnel cleanly (idle timeout, max lifetime, server drain) by sending each
+//! side a close frame between frames.
 
+use crate::config::{Duration, PerMessageDeflate, WebSocketConfig};
+use crate::server::{ConnectionSlot, ConnectionTracker};
 use bytes::Bytes;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use http_body_util::{combinators::BoxBody, BodyExt, Empty};
-use hyper::header::{HeaderValue, CONNECTION, SEC_WEBSOCKET_KEY, UPGRADE};
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS,
+    SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, UPGRADE,
+};
 use hyper::{body::Incoming, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
+use ring::rand::{SecureRandom, SystemRandom};
 use rustls::pki_types::ServerName;
+use std::borrow::Cow;
+use std::io::{self, Write};
 use std::net::SocketAddr;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time::Instant;
 use tracing::{debug, error};
 
+/// Extension token for RFC 7692 compression.
+const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+
+/// Extension response sent to the client when the proxy terminates compression.
+/// Both directions reset their context per message, so no window state is kept.
+const TERMINATED_DEFLATE_RESPONSE: &str =
+    "permessage-deflate; server_no_context_takeover; client_no_context_takeover";
+
+/// Largest message the proxy reassembles while terminating compression.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_CLOSE: u8 = 0x8;
+
+/// Tail removed from every compressed message by the sender (RFC 7692 §7.2.1).
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Empty final block appended after the restored tail so the inflater sees a
+/// complete stream.
+const DEFLATE_FINAL_BLOCK: [u8; 2] = [0x03, 0x00];
+
 /// Check if request is a WebSocket upgrade request
 #[inline]
 pub fn is_websocket_upgrade(req: &Request<Incoming>) -> bool {
@@ -37,6 +84,7 @@ pub async fn handle_websocket_upgrade(
     req: Request<Incoming>,
     backend_addr: &str,
     _remote_addr: SocketAddr,
+    config: &WebSocketConfig,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     // Parse backend address
     let backend_url: url::Url = match backend_addr.parse() {
@@ -102,9 +150,12 @@ pub async fn handle_websocket_upgrade(
         .cloned()
         .unwrap_or_else(|| HeaderValue::from_static(""));
 
-    let path = req.uri().path_and_query()
-        .map(|pq| pq.as_str())
-        .unwrap_or("/");
+    let path = match req.uri().path_and_query().map(|pq| pq.as_str()) {
+        Some(path) if path.starts_with('/') => Cow::Borrowed(path),
+        // Path left empty by stripPrefix without forceSlash
+        Some(query) => Cow::Owned(format!("/{}", query)),
+        None => Cow::Borrowed("/"),
+    };
 
     let host_header = req
         .headers()
@@ -112,19 +163,33 @@ pub async fn handle_websocket_upgrade(
         .and_then(|v| v.to_str().ok())
         .unwrap_or(&addr);
 
+    // Extensions offered to the backend; strip mode never offers compression
+    let client_extensions = joined_header(req.headers(), &SEC_WEBSOCKET_EXTENSIONS);
+    let backend_offer = match config.per_message_deflate {
+        PerMessageDeflate::Strip => client_extensions.as_deref().and_then(without_deflate),
+        PerMessageDeflate::Passthrough | PerMessageDeflate::Terminate => client_extensions.clone(),
+    };
+    let protocols = joined_header(req.headers(), &SEC_WEBSOCKET_PROTOCOL);
+
     // Send HTTP upgrade request manually
-    let upgrade_request = format!(
+    let mut upgrade_request = format!(
         "GET {} HTTP/1.1\r\n\
          Host: {}\r\n\
          Upgrade: websocket\r\n\
          Connection: Upgrade\r\n\
          Sec-WebSocket-Key: {}\r\n\
-         Sec-WebSocket-Version: 13\r\n\
-         \r\n",
+         Sec-WebSocket-Version: 13\r\n",
         path,
         host_header,
         ws_key.to_str().unwrap_or("")
     );
+    if let Some(offer) = &backend_offer {
+        upgrade_request.push_str(&format!("Sec-WebSocket-Extensions: {}\r\n", offer));
+    }
+    if let Some(protocols) = &protocols {
+        upgrade_request.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", protocols));
+    }
+    upgrade_request.push_str("\r\n");
 
     // Write upgrade request to backend
     if let Err(e) = backend_stream.write_all(upgrade_request.as_bytes()).await {
@@ -137,8 +202,7 @@ pub async fn handle_websocket_upgrade(
     let mut total_read = 0usize;
 
     // Read until we find \r\n\r\n (end of headers)
-    loop {
-        use tokio::io::AsyncReadExt;
+    let (handshake, header_end) = loop {
         let n = match backend_stream.read(&mut buf[total_read..]).await {
             Ok(0) => {
                 error!("Backend closed connection during WebSocket handshake");
@@ -156,38 +220,71 @@ pub async fn handle_websocket_upgrade(
         if let Some(pos) = find_header_end(&buf[..total_read]) {
             // Parse the response
             let response_text = String::from_utf8_lossy(&buf[..pos]);
-            if !response_text.contains("101") {
-                error!("Backend rejected WebSocket upgrade: {}", response_text.lines().next().unwrap_or(""));
-                return Ok(error_response(StatusCode::BAD_GATEWAY));
+            match HandshakeResponse::parse(&response_text) {
+                Some(handshake) if handshake.status == 101 => break (handshake, pos),
+                _ => {
+                    error!("Backend rejected WebSocket upgrade: {}", response_text.lines().next().unwrap_or(""));
+                    return Ok(error_response(StatusCode::BAD_GATEWAY));
+                }
             }
-            break;
         }
 
         if total_read >= buf.len() {
             error!("WebSocket handshake response too large");
             return Ok(error_response(StatusCode::BAD_GATEWAY));
         }
-    }
+    };
 
     debug!("WebSocket: Backend accepted upgrade");
 
+    // Frames the backend sent right behind its handshake belong to the client
+    let leftover = buf[header_end..total_read].to_vec();
+
+    // The proxy only takes over compression when the backend accepted no
+    // extensions at all, so no other extension can claim the RSV bits
+    let terminate = config.per_message_deflate == PerMessageDeflate::Terminate
+        && handshake.extensions.is_none()
+        && client_extensions.as_deref().is_some_and(can_terminate_deflate);
+    let extensions = if terminate {
+        debug!("WebSocket: terminating permessage-deflate at the proxy");
+        Some(TERMINATED_DEFLATE_RESPONSE.to_string())
+    } else {
+        handshake.extensions
+    };
+
     // Build 101 response for client
-    let response = Response::builder()
+    let mut builder = Response::builder()
         .status(StatusCode::SWITCHING_PROTOCOLS)
         .header(UPGRADE, "websocket")
         .header(CONNECTION, "Upgrade")
-        .header("Sec-WebSocket-Accept", compute_accept_key(ws_key.to_str().unwrap_or("")))
-        .body(empty_body())
-        .unwrap();
+        .header(SEC_WEBSOCKET_ACCEPT, compute_accept_key(ws_key.to_str().unwrap_or("")));
+    if let Some(extensions) = extensions.and_then(|e| HeaderValue::from_str(&e).ok()) {
+        builder = builder.header(SEC_WEBSOCKET_EXTENSIONS, extensions);
+    }
+    if let Some(protocol) = handshake.protocol.and_then(|p| HeaderValue::from_str(&p).ok()) {
+        builder = builder.header(SEC_WEBSOCKET_PROTOCOL, protocol);
+    }
+    let response = builder.body(empty_body()).unwrap();
+
+    // The tunnel outlives the client's HTTP connection, so it holds its own
+    // slot in the connection tracker and closes when the server drains. It also
+    // keeps the connection's place under the entrypoint's connection cap.
+    let tracker = req.extensions().get::<Arc<ConnectionTracker>>().cloned();
+    let guard = tracker.as_ref().map(ConnectionTracker::hold);
+    let slot = req.extensions().get::<Arc<ConnectionSlot>>().cloned();
+    let limits = TunnelLimits::new(config, tracker.map(|tracker| tracker.drain_signal()));
 
     // Schedule the upgrade handler - this runs after we return the 101 response
     let req_upgrade = hyper::upgrade::on(req);
 
     tokio::spawn(async move {
+        let _guard = guard;
+        let _slot = slot;
         match req_upgrade.await {
             Ok(upgraded) => {
                 let client_stream = TokioIo::new(upgraded);
-                if let Err(e) = proxy_streams(client_stream, backend_stream).await {
+                let result = relay(client_stream, backend_stream, &leftover, terminate, limits).await;
+                if let Err(e) = result {
                     debug!("WebSocket proxy ended: {}", e);
                 }
             }
@@ -200,6 +297,86 @@ pub async fn handle_websocket_upgrade(
     Ok(response)
 }
 
+/// Status line and WebSocket headers of the backend's handshake response.
+#[derive(Debug)]
+struct HandshakeResponse {
+    status: u16,
+    extensions: Option<String>,
+    protocol: Option<String>,
+}
+
+impl HandshakeResponse {
+    /// Parse a raw response head (status line plus headers).
+    fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.split("\r\n");
+        let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+
+        let mut extensions: Vec<&str> = Vec::new();
+        let mut protocol = None;
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if name.trim().eq_ignore_ascii_case("sec-websocket-extensions") {
+                extensions.push(value);
+            } else if name.trim().eq_ignore_ascii_case("sec-websocket-protocol") {
+                protocol = Some(value.to_string());
+            }
+        }
+
+        Some(Self {
+            status,
+            extensions: (!extensions.is_empty()).then(|| extensions.join(", ")),
+            protocol,
+        })
+    }
+}
+
+/// All values of a header joined into a single comma-separated list.
+fn joined_header(headers: &HeaderMap, name: &HeaderName) -> Option<String> {
+    let values: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// Extension name of a single offer such as `permessage-deflate; client_max_window_bits`.
+fn extension_name(offer: &str) -> &str {
+    offer.split(';').next().unwrap_or("").trim()
+}
+
+/// Remove `permessage-deflate` offers, keeping any other extensions.
+fn without_deflate(extensions: &str) -> Option<String> {
+    let rest: Vec<&str> = extensions
+        .split(',')
+        .map(str::trim)
+        .filter(|offer| !offer.is_empty() && !extension_name(offer).eq_ignore_ascii_case(PERMESSAGE_DEFLATE))
+        .collect();
+    (!rest.is_empty()).then(|| rest.join(", "))
+}
+
+/// Whether the client offered `permessage-deflate` with parameters the proxy can
+/// honour. The proxy always compresses with a 15-bit window, so offers limiting
+/// `server_max_window_bits` below that are declined.
+fn can_terminate_deflate(extensions: &str) -> bool {
+    extensions
+        .split(',')
+        .filter(|offer| extension_name(offer).eq_ignore_ascii_case(PERMESSAGE_DEFLATE))
+        .any(|offer| {
+            offer.split(';').skip(1).all(|param| match param.split_once('=') {
+                Some((name, bits)) if name.trim().eq_ignore_ascii_case("server_max_window_bits") => {
+                    bits.trim().trim_matches('"') == "15"
+                }
+                _ => true,
+            })
+        })
+}
+
 /// Find the end of HTTP headers (\r\n\r\n)
 fn find_header_end(buf: &[u8]) -> Option<usize> {
     for i in 0..buf.len().saturating_sub(3) {
@@ -257,28 +434,421 @@ fn base64_encode(data: &[u8]) -> String {
 trait BackendIo: AsyncRead + AsyncWrite + Unpin + Send {}
 impl<T: AsyncRead + AsyncWrite + Unpin + Send> BackendIo for T {}
 
-/// Proxy data bidirectionally between two streams
-async fn proxy_streams<C, B>(client: C, backend: B) -> std::io::Result<()>
+/// How long relays get to finish the frame in flight once the proxy decides
+/// to close a tunnel; after that the connection is dropped without close frames.
+const CLOSE_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Why the proxy closed a tunnel on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CloseReason {
+    Idle,
+    MaxLifetime,
+    Draining,
+}
+
+impl CloseReason {
+    /// Close status code: normal closure for idleness, going away otherwise.
+    fn code(self) -> u16 {
+        match self {
+            CloseReason::Idle => 1000,
+            CloseReason::MaxLifetime | CloseReason::Draining => 1001,
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            CloseReason::Idle => "idle timeout",
+            CloseReason::MaxLifetime => "max lifetime reached",
+            CloseReason::Draining => "server shutting down",
+        }
+    }
+
+    fn frame(self) -> WsFrame {
+        let mut payload = self.code().to_be_bytes().to_vec();
+        payload.extend_from_slice(self.text().as_bytes());
+        WsFrame { fin: true, rsv1: false, opcode: OP_CLOSE, payload }
+    }
+}
+
+/// Time of the last frame seen in either direction of a tunnel.
+struct Activity {
+    start: Instant,
+    last_ms: AtomicU64,
+}
+
+impl Activity {
+    fn new() -> Self {
+        Self { start: Instant::now(), last_ms: AtomicU64::new(0) }
+    }
+
+    fn touch(&self) {
+        self.last_ms.store(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.start + std::time::Duration::from_millis(self.last_ms.load(Ordering::Relaxed))
+    }
+}
+
+/// Idle and lifetime limits of a tunnel, plus the server's drain signal.
+struct TunnelLimits {
+    idle_timeout: Option<std::time::Duration>,
+    max_lifetime: Option<std::time::Duration>,
+    drain: Option<watch::Receiver<bool>>,
+}
+
+impl TunnelLimits {
+    fn new(config: &WebSocketConfig, drain: Option<watch::Receiver<bool>>) -> Self {
+        let enabled = |limit: Option<Duration>| limit.filter(|l| !l.is_zero()).map(|l| l.as_std());
+        Self {
+            idle_timeout: enabled(config.idle_timeout),
+            max_lifetime: enabled(config.max_lifetime),
+            drain,
+        }
+    }
+
+    /// Resolve once the proxy should close the tunnel.
+    async fn expired(self, activity: &Activity) -> CloseReason {
+        let Self { idle_timeout, max_lifetime, drain } = self;
+
+        let lifetime = async {
+            match max_lifetime {
+                Some(lifetime) => tokio::time::sleep(lifetime).await,
+                None => std::future::pending().await,
+            }
+        };
+        let idle = async {
+            let Some(timeout) = idle_timeout else {
+                return std::future::pending().await;
+            };
+            // Activity moves the deadline forward; sleep until it stops moving
+            loop {
+                let deadline = activity.last() + timeout;
+                if Instant::now() >= deadline {
+                    break;
+                }
+                tokio::time::sleep_until(deadline).await;
+            }
+        };
+        let draining = async {
+            let Some(mut drain) = drain else {
+                return std::future::pending().await;
+            };
+            if drain.wait_for(|draining| *draining).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        };
+
+        tokio::select! {
+            _ = lifetime => CloseReason::MaxLifetime,
+            _ = idle => CloseReason::Idle,
+            _ = draining => CloseReason::Draining,
+        }
+    }
+}
+
+/// Proxy a tunnel until either side disconnects or a limit expires.
+/// `leftover` holds backend bytes read past the end of the handshake. When the
+/// proxy ends the tunnel itself, both relays stop at a frame boundary and each
+/// side gets a close frame.
+async fn relay<C, B>(client: C, backend: B, leftover: &[u8], terminate: bool, limits: TunnelLimits) -> io::Result<()>
 where
     C: AsyncRead + AsyncWrite + Unpin,
     B: AsyncRead + AsyncWrite + Unpin,
 {
     let (mut client_read, mut client_write) = tokio::io::split(client);
-    let (mut backend_read, mut backend_write) = tokio::io::split(backend);
+    let (backend_read, mut backend_write) = tokio::io::split(backend);
+    let mut backend_read = leftover.chain(backend_read);
+    let activity = Activity::new();
+    let (stop_tx, stop) = watch::channel(false);
 
-    let client_to_backend = tokio::io::copy(&mut client_read, &mut backend_write);
-    let backend_to_client = tokio::io::copy(&mut backend_read, &mut client_write);
+    let reason = {
+        let client_to_backend =
+            relay_frames(&mut client_read, &mut backend_write, Leg::ToBackend, terminate, &activity, stop.clone());
+        let backend_to_client =
+            relay_frames(&mut backend_read, &mut client_write, Leg::ToClient, terminate, &activity, stop);
+        tokio::pin!(client_to_backend, backend_to_client);
 
-    tokio::select! {
-        result = client_to_backend => {
-            debug!("WebSocket client->backend closed: {:?}", result);
+        let reason = tokio::select! {
+            result = &mut client_to_backend => {
+                debug!("WebSocket client->backend closed: {:?}", result);
+                return result;
+            }
+            result = &mut backend_to_client => {
+                debug!("WebSocket backend->client closed: {:?}", result);
+                return result;
+            }
+            reason = limits.expired(&activity) => reason,
+        };
+
+        stop_tx.send_replace(true);
+        let stopped = tokio::time::timeout(CLOSE_GRACE, futures::future::join(client_to_backend, backend_to_client)).await;
+        if stopped.is_err() {
+            debug!("WebSocket frame still in flight after {:?}, dropping tunnel", CLOSE_GRACE);
+            return Ok(());
         }
-        result = backend_to_client => {
-            debug!("WebSocket backend->client closed: {:?}", result);
+        reason
+    };
+
+    debug!("Closing WebSocket tunnel: {}", reason.text());
+    let rng = SystemRandom::new();
+    let close = reason.frame();
+    let _ = client_write.write_all(&close.encode(Leg::ToClient, &rng)?).await;
+    let _ = backend_write.write_all(&close.encode(Leg::ToBackend, &rng)?).await;
+    let _ = client_write.shutdown().await;
+    let _ = backend_write.shutdown().await;
+    Ok(())
+}
+
+/// Direction of a relayed frame, which decides masking and compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Leg {
+    /// Client to backend: frames are masked and messages inflated.
+    ToBackend,
+    /// Backend to client: frames are unmasked and messages deflated.
+    ToClient,
+}
+
+/// Relay frames from `reader` to `writer` until the stream ends or `stop` is
+/// raised between frames. Frames are copied verbatim unless `terminate` is set,
+/// in which case fragmented data messages are reassembled and converted while
+/// control frames are forwarded immediately.
+async fn relay_frames<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    leg: Leg,
+    terminate: bool,
+    activity: &Activity,
+    mut stop: watch::Receiver<bool>,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let rng = SystemRandom::new();
+    let mut pending: Option<WsFrame> = None;
+    let mut buf = vec![0u8; 8192];
+
+    loop {
+        let header = tokio::select! {
+            biased;
+            _ = stop.wait_for(|stop| *stop) => return Ok(()),
+            header = read_frame_header(reader) => match header? {
+                Some(header) => header,
+                None => return Ok(()),
+            },
+        };
+        activity.touch();
+
+        if !terminate {
+            // Forward the frame as received, payload still masked
+            writer.write_all(&header.raw).await?;
+            let mut remaining = header.len;
+            while remaining > 0 {
+                let chunk = remaining.min(buf.len() as u64) as usize;
+                let n = reader.read(&mut buf[..chunk]).await?;
+                if n == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                writer.write_all(&buf[..n]).await?;
+                remaining -= n as u64;
+                activity.touch();
+            }
+            continue;
+        }
+
+        let frame = header.read_payload(reader).await?;
+        if frame.is_control() {
+            writer.write_all(&frame.encode(leg, &rng)?).await?;
+            if frame.opcode == OP_CLOSE {
+                debug!("WebSocket close frame relayed ({:?})", leg);
+            }
+            continue;
+        }
+
+        let mut message = match (pending.take(), frame.opcode) {
+            (Some(mut message), OP_CONTINUATION) => {
+                if message.payload.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                    return Err(invalid_data("WebSocket message too large"));
+                }
+                message.payload.extend_from_slice(&frame.payload);
+                message.fin = frame.fin;
+                message
+            }
+            (None, opcode) if opcode != OP_CONTINUATION => frame,
+            _ => return Err(invalid_data("unexpected WebSocket continuation frame")),
+        };
+        if !message.fin {
+            pending = Some(message);
+            continue;
+        }
+
+        match leg {
+            Leg::ToBackend if message.rsv1 => {
+                message.payload = inflate_message(&message.payload)?;
+                message.rsv1 = false;
+            }
+            Leg::ToBackend => {}
+            Leg::ToClient => {
+                message.payload = deflate_message(&message.payload)?;
+                message.rsv1 = true;
+            }
         }
+        writer.write_all(&message.encode(leg, &rng)?).await?;
     }
+}
 
-    Ok(())
+/// A single WebSocket frame with its payload unmasked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WsFrame {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+impl WsFrame {
+    fn is_control(&self) -> bool {
+        self.opcode & 0x8 != 0
+    }
+
+    /// Serialize the frame for `leg`; frames towards the backend are masked.
+    fn encode(&self, leg: Leg, rng: &SystemRandom) -> io::Result<Vec<u8>> {
+        let mask = match leg {
+            Leg::ToBackend => {
+                let mut key = [0u8; 4];
+                rng.fill(&mut key).map_err(|_| io::Error::other("failed to generate masking key"))?;
+                Some(key)
+            }
+            Leg::ToClient => None,
+        };
+        Ok(encode_frame(self, mask))
+    }
+}
+
+/// Serialize a frame, masking the payload when a key is given.
+fn encode_frame(frame: &WsFrame, mask: Option<[u8; 4]>) -> Vec<u8> {
+    let len = frame.payload.len();
+    let mut out = Vec::with_capacity(len + 14);
+    out.push((u8::from(frame.fin) << 7) | (u8::from(frame.rsv1) << 6) | frame.opcode);
+
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    if len < 126 {
+        out.push(mask_bit | len as u8);
+    } else if let Ok(len) = u16::try_from(len) {
+        out.push(mask_bit | 126);
+        out.extend_from_slice(&len.to_be_bytes());
+    } else {
+        out.push(mask_bit | 127);
+        out.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+
+    match mask {
+        Some(key) => {
+            out.extend_from_slice(&key);
+            out.extend(frame.payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        }
+        None => out.extend_from_slice(&frame.payload),
+    }
+    out
+}
+
+/// Header of a frame whose payload has not been read yet.
+struct FrameHeader {
+    /// Header bytes exactly as received.
+    raw: Vec<u8>,
+    len: u64,
+    mask: Option<[u8; 4]>,
+}
+
+impl FrameHeader {
+    /// Read and unmask the payload.
+    async fn read_payload<R: AsyncRead + Unpin>(self, reader: &mut R) -> io::Result<WsFrame> {
+        if self.len > MAX_MESSAGE_SIZE as u64 {
+            return Err(invalid_data("WebSocket frame too large"));
+        }
+        let mut payload = vec![0u8; self.len as usize];
+        reader.read_exact(&mut payload).await?;
+        if let Some(key) = self.mask {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= key[i % 4];
+            }
+        }
+
+        Ok(WsFrame {
+            fin: self.raw[0] & 0x80 != 0,
+            rsv1: self.raw[0] & 0x40 != 0,
+            opcode: self.raw[0] & 0x0f,
+            payload,
+        })
+    }
+}
+
+/// Read a frame header, or `None` when the stream ends cleanly between frames.
+async fn read_frame_header<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<FrameHeader>> {
+    let mut head = [0u8; 2];
+    match reader.read_exact(&mut head).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut raw = head.to_vec();
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let len = reader.read_u16().await?;
+            raw.extend_from_slice(&len.to_be_bytes());
+            u64::from(len)
+        }
+        127 => {
+            let len = reader.read_u64().await?;
+            raw.extend_from_slice(&len.to_be_bytes());
+            len
+        }
+        len => u64::from(len),
+    };
+
+    let mask = if head[1] & 0x80 != 0 {
+        let mut key = [0u8; 4];
+        reader.read_exact(&mut key).await?;
+        raw.extend_from_slice(&key);
+        Some(key)
+    } else {
+        None
+    };
+
+    Ok(Some(FrameHeader { raw, len, mask }))
+}
+
+/// Compress a whole message with a fresh context, dropping the sync-flush tail.
+fn deflate_message(payload: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(payload.len() / 2 + 16), Compression::default());
+    encoder.write_all(payload)?;
+    encoder.flush()?;
+    let mut compressed = std::mem::take(encoder.get_mut());
+    if compressed.ends_with(&DEFLATE_TAIL) {
+        compressed.truncate(compressed.len() - DEFLATE_TAIL.len());
+    }
+    Ok(compressed)
+}
+
+/// Decompress a whole message compressed with a fresh context.
+fn inflate_message(payload: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let input = Read::chain(Read::chain(payload, &DEFLATE_TAIL[..]), &DEFLATE_FINAL_BLOCK[..]);
+    let mut message = Vec::with_capacity(payload.len() * 2);
+    DeflateDecoder::new(input)
+        .take(MAX_MESSAGE_SIZE as u64 + 1)
+        .read_to_end(&mut message)?;
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(invalid_data("WebSocket message too large"));
+    }
+    Ok(message)
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
 }
 
 fn error_response(status: StatusCode) -> Response<BoxBody<Bytes, hyper::Error>> {
@@ -293,3 +863,350 @@ fn empty_body() -> BoxBody<Bytes, hyper::Error> {
         .map_err(|never| match never {})
         .boxed()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::service_fn;
+    use tokio::net::TcpListener;
+
+    /// "Hello" compressed without context takeover (RFC 7692 §7.2.3.1).
+    const HELLO_DEFLATED: [u8; 7] = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+
+    /// Read one whole frame, or `None` when the stream ends cleanly between frames.
+    async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<WsFrame>> {
+        match read_frame_header(reader).await? {
+            Some(header) => header.read_payload(reader).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn text(fin: bool, rsv1: bool, opcode: u8, payload: &[u8]) -> WsFrame {
+        WsFrame { fin, rsv1, opcode, payload: payload.to_vec() }
+    }
+
+    fn deflate_config(mode: PerMessageDeflate) -> WebSocketConfig {
+        WebSocketConfig { per_message_deflate: mode, ..Default::default() }
+    }
+
+    async fn ws_proxy(backend: SocketAddr, mode: PerMessageDeflate) -> SocketAddr {
+        ws_proxy_with(backend, deflate_config(mode), None).await
+    }
+
+    /// Serve `handle_websocket_upgrade` for every request, like the entrypoint
+    /// listeners, which hand upgrade requests their connection tracker.
+    async fn ws_proxy_with(
+        backend: SocketAddr,
+        config: WebSocketConfig,
+        tracker: Option<Arc<ConnectionTracker>>,
+    ) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote_addr) = listener.accept().await.unwrap();
+                let config = config.clone();
+                let tracker = tracker.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |mut req: Request<Incoming>| {
+                        let config = config.clone();
+                        if let Some(tracker) = &tracker {
+                            req.extensions_mut().insert(Arc::clone(tracker));
+                        }
+                        async move { handle_websocket_upgrade(req, &format!("http://{}", backend), remote_addr, &config).await }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .with_upgrades()
+                        .await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// Raw WebSocket backend that echoes every frame unmasked and reports the
+    /// upgrade request it received. It accepts `permessage-deflate` only when
+    /// `supports_deflate` is set.
+    async fn echo_backend(supports_deflate: bool) -> (SocketAddr, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let head = read_head(&mut stream).await;
+                    let offered = head.to_ascii_lowercase().contains(PERMESSAGE_DEFLATE);
+                    let _ = tx.send(head);
+                    let mut response = String::from(
+                        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: x\r\n",
+                    );
+                    if supports_deflate && offered {
+                        response.push_str("Sec-WebSocket-Extensions: permessage-deflate\r\n");
+                    }
+                    response.push_str("\r\n");
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                    while let Ok(Some(frame)) = read_frame(&mut stream).await {
+                        if stream.write_all(&encode_frame(&frame, None)).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (addr, rx)
+    }
+
+    async fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    /// Open a WebSocket through the proxy, returning the stream and the 101 head.
+    async fn connect(proxy: SocketAddr, extensions: Option<&str>) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(proxy).await.unwrap();
+        let mut request = format!(
+            "GET /chat HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n",
+            proxy
+        );
+        if let Some(extensions) = extensions {
+            request.push_str(&format!("Sec-WebSocket-Extensions: {}\r\n", extensions));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let head = read_head(&mut stream).await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{}", head);
+        (stream, head)
+    }
+
+    fn extensions_header(head: &str) -> Option<String> {
+        HandshakeResponse::parse(head.trim_end()).unwrap().extensions
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_relays_negotiated_deflate() {
+        let (backend, mut requests) = echo_backend(true).await;
+        let proxy = ws_proxy(backend, PerMessageDeflate::Passthrough).await;
+
+        let (mut stream, head) = connect(proxy, Some("permessage-deflate; client_max_window_bits")).await;
+        assert_eq!(extensions_header(&head).as_deref(), Some("permessage-deflate"));
+        assert!(requests.recv().await.unwrap().contains("Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits"));
+
+        // Compressed frames pass through untouched
+        let frame = text(true, true, 0x1, &HELLO_DEFLATED);
+        stream.write_all(&encode_frame(&frame, Some([1, 2, 3, 4]))).await.unwrap();
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), frame);
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_drops_deflate_when_backend_unsupported() {
+        let (backend, mut requests) = echo_backend(false).await;
+        let proxy = ws_proxy(backend, PerMessageDeflate::Passthrough).await;
+
+        let (mut stream, head) = connect(proxy, Some("permessage-deflate")).await;
+        assert_eq!(extensions_header(&head), None);
+        assert!(requests.recv().await.unwrap().contains("permessage-deflate"));
+
+        let frame = text(true, false, 0x1, b"plain");
+        stream.write_all(&encode_frame(&frame, Some([9, 9, 9, 9]))).await.unwrap();
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), frame);
+    }
+
+    #[tokio::test]
+    async fn test_strip_never_offers_deflate() {
+        let (backend, mut requests) = echo_backend(true).await;
+        let proxy = ws_proxy(backend, PerMessageDeflate::Strip).await;
+
+        let (_stream, head) = connect(proxy, Some("permessage-deflate; client_max_window_bits")).await;
+        assert_eq!(extensions_header(&head), None);
+        assert!(!requests.recv().await.unwrap().contains("permessage-deflate"));
+    }
+
+    #[tokio::test]
+    async fn test_terminate_compresses_at_proxy() {
+        let (backend, mut requests) = echo_backend(false).await;
+        let proxy = ws_proxy(backend, PerMessageDeflate::Terminate).await;
+
+        let (mut stream, head) = connect(proxy, Some("permessage-deflate")).await;
+        assert_eq!(extensions_header(&head).as_deref(), Some(TERMINATED_DEFLATE_RESPONSE));
+        assert!(requests.recv().await.unwrap().contains("permessage-deflate"));
+
+        // A compressed message split over two fragments with a ping in between
+        let first = text(false, true, 0x1, &HELLO_DEFLATED[..3]);
+        let ping = text(true, false, 0x9, b"ping");
+        let last = text(true, false, OP_CONTINUATION, &HELLO_DEFLATED[3..]);
+        for frame in [&first, &ping, &last] {
+            stream.write_all(&encode_frame(frame, Some([5, 6, 7, 8]))).await.unwrap();
+        }
+
+        // The control frame is relayed on its own, ahead of the reassembled message
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), ping);
+        let echoed = read_frame(&mut stream).await.unwrap().unwrap();
+        assert!(echoed.fin && echoed.rsv1);
+        assert_eq!(echoed.opcode, 0x1);
+        assert_eq!(inflate_message(&echoed.payload).unwrap(), b"Hello");
+    }
+
+    #[tokio::test]
+    async fn test_terminate_defers_to_backend_deflate() {
+        let (backend, _requests) = echo_backend(true).await;
+        let proxy = ws_proxy(backend, PerMessageDeflate::Terminate).await;
+
+        let (mut stream, head) = connect(proxy, Some("permessage-deflate")).await;
+        assert_eq!(extensions_header(&head).as_deref(), Some("permessage-deflate"));
+
+        let frame = text(true, true, 0x2, &HELLO_DEFLATED);
+        stream.write_all(&encode_frame(&frame, Some([1, 1, 1, 1]))).await.unwrap();
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), frame);
+    }
+
+    /// Read frames until a close frame arrives, returning its status code and
+    /// how many data frames came before it.
+    async fn read_until_close(stream: &mut TcpStream) -> (u16, usize) {
+        let mut data_frames = 0;
+        loop {
+            let frame = read_frame(stream).await.unwrap().expect("connection closed without a close frame");
+            if frame.opcode == OP_CLOSE {
+                return (u16::from_be_bytes([frame.payload[0], frame.payload[1]]), data_frames);
+            }
+            data_frames += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_closes_after_inactivity() {
+        let (backend, _requests) = echo_backend(false).await;
+        let config = WebSocketConfig { idle_timeout: Some(Duration::from_millis(300)), ..Default::default() };
+        let proxy = ws_proxy_with(backend, config, None).await;
+        let (mut stream, _) = connect(proxy, None).await;
+
+        // Pings keep the tunnel open well past the idle timeout
+        let ping = text(true, false, 0x9, b"keepalive");
+        for _ in 0..6 {
+            stream.write_all(&encode_frame(&ping, Some([1, 2, 3, 4]))).await.unwrap();
+            assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), ping);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        let silent = std::time::Instant::now();
+        assert_eq!(read_until_close(&mut stream).await, (1000, 0));
+        assert!(silent.elapsed() >= std::time::Duration::from_millis(150));
+        assert!(read_frame(&mut stream).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_max_lifetime_closes_mid_stream() {
+        let (backend, _requests) = echo_backend(false).await;
+        let config = WebSocketConfig {
+            idle_timeout: Some(Duration::from_secs(10)),
+            max_lifetime: Some(Duration::from_millis(400)),
+            ..Default::default()
+        };
+        let proxy = ws_proxy_with(backend, config, None).await;
+        let (stream, _) = connect(proxy, None).await;
+        let (mut read, mut write) = stream.into_split();
+
+        // Keep traffic flowing so only the lifetime limit can end the tunnel
+        let sender = tokio::spawn(async move {
+            let frame = text(true, false, 0x2, &[0xab; 1024]);
+            while write.write_all(&encode_frame(&frame, Some([4, 3, 2, 1]))).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        });
+
+        let opened = std::time::Instant::now();
+        let mut data_frames = 0;
+        let code = loop {
+            let frame = read_frame(&mut read).await.unwrap().unwrap();
+            if frame.opcode == OP_CLOSE {
+                break u16::from_be_bytes([frame.payload[0], frame.payload[1]]);
+            }
+            assert_eq!(frame.payload.len(), 1024);
+            data_frames += 1;
+        };
+        assert_eq!(code, 1001);
+        assert!(data_frames > 5);
+        assert!(opened.elapsed() >= std::time::Duration::from_millis(350));
+        sender.abort();
+    }
+
+    #[tokio::test]
+    async fn test_drain_closes_tunnel_and_releases_tracker() {
+        let (backend, _requests) = echo_backend(false).await;
+        let tracker = Arc::new(ConnectionTracker::new());
+        let proxy = ws_proxy_with(backend, WebSocketConfig::default(), Some(Arc::clone(&tracker))).await;
+        let (mut stream, _) = connect(proxy, None).await;
+
+        let frame = text(true, false, 0x1, b"hi");
+        stream.write_all(&encode_frame(&frame, Some([7, 7, 7, 7]))).await.unwrap();
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), frame);
+        assert_eq!(tracker.active_count(), 1);
+
+        tracker.start_drain();
+        assert_eq!(read_until_close(&mut stream).await, (1001, 0));
+        tracker.wait_for_drain(std::time::Duration::from_secs(2)).await;
+        assert_eq!(tracker.active_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let frame = text(true, false, 0x2, &[7u8; 300]);
+        for mask in [None, Some([0xde, 0xad, 0xbe, 0xef])] {
+            let encoded = encode_frame(&frame, mask);
+            assert_eq!(encoded[1] & 0x7f, 126);
+            let decoded = read_frame(&mut encoded.as_slice()).await.unwrap().unwrap();
+            assert_eq!(decoded, frame);
+        }
+        assert!(read_frame(&mut &[][..]).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_deflate_roundtrip() {
+        assert_eq!(inflate_message(&HELLO_DEFLATED).unwrap(), b"Hello");
+
+        let message = b"compressible ".repeat(100);
+        let compressed = deflate_message(&message).unwrap();
+        assert!(compressed.len() < message.len());
+        assert!(!compressed.ends_with(&DEFLATE_TAIL));
+        assert_eq!(inflate_message(&compressed).unwrap(), message);
+        assert_eq!(inflate_message(&deflate_message(b"").unwrap()).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_extension_offers() {
+        assert_eq!(
+            without_deflate("permessage-deflate; client_max_window_bits, x-webkit-foo").as_deref(),
+            Some("x-webkit-foo")
+        );
+        assert_eq!(without_deflate("permessage-deflate"), None);
+
+        assert!(can_terminate_deflate("permessage-deflate; client_max_window_bits"));
+        assert!(can_terminate_deflate("permessage-deflate; server_max_window_bits=15"));
+        assert!(!can_terminate_deflate("permessage-deflate; server_max_window_bits=10"));
+        assert!(can_terminate_deflate(
+            "permessage-deflate; server_max_window_bits=10, permessage-deflate"
+        ));
+        assert!(!can_terminate_deflate("x-webkit-foo"));
+    }
+
+    #[test]
+    fn test_parse_handshake_response() {
+        let handshake = HandshakeResponse::parse(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             sec-websocket-extensions: permessage-deflate\r\nSec-WebSocket-Protocol: chat",
+        )
+        .unwrap();
+        assert_eq!(handshake.status, 101);
+        assert_eq!(handshake.extensions.as_deref(), Some("permessage-deflate"));
+        assert_eq!(handshake.protocol.as_deref(), Some("chat"));
+
+        assert_eq!(HandshakeResponse::parse("HTTP/1.1 400 Bad Request").unwrap().status, 400);
+        assert!(HandshakeResponse::parse("garbage").is_none());
+    }
+}
@@ -2,6 +2,7 @@
 
 use super::rule::{Rule, RuleParseError, RuleParser};
 use hyper::HeaderMap;
+use std::net::IpAddr;
 
 /// Compiled matcher that evaluates a parsed routing rule against request attributes.
 #[derive(Debug)]
@@ -24,12 +25,20 @@ impl RouteMatcher {
         query: Option<&str>,
         method: Option<&str>,
         headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
     ) -> bool {
-        self.rule.matches(host, path, query, method, headers)
+        self.rule.matches(host, path, query, method, headers, client_ip)
     }
 
     /// Extract host names from the rule for indexing
     pub fn extract_hosts(&self) -> Vec<&str> {
         self.rule.extract_hosts()
     }
+
+    /// Priority for routers that don't set one: each matcher counts 16 plus the
+    /// length of its literals, so `Host(..) && Path(..)` outranks a bare `Host(..)`.
+    pub fn default_priority(&self) -> i32 {
+        let (matchers, literals) = self.rule.specificity();
+        i32::try_from(matchers * 16 + literals).unwrap_or(i32::MAX)
+    }
 }
@@ -8,6 +8,7 @@ pub use rule::{Rule, RuleParser};
 
 use crate::config::Config;
 use std::collections::HashMap;
+use std::net::IpAddr;
 
 /// Routes incoming requests to services using rule-based matching with host and entrypoint indexing.
 pub struct Router {
@@ -21,6 +22,20 @@ pub struct Router {
     host_index: HashMap<String, Vec<usize>>,
 }
 
+/// Router and service a request was routed to, added to the request's
+/// extensions once it matches.
+#[derive(Debug, Clone)]
+pub struct MatchedRoute {
+    /// Name of the matched router.
+    pub router: String,
+    /// Service the router forwards to.
+    pub service: String,
+    /// Entrypoint the request arrived on.
+    pub entrypoint: String,
+    /// Whether the router's requests are access logged.
+    pub access_logs: bool,
+}
+
 /// A single routing rule that maps matched requests to a service.
 pub struct Route {
     /// Unique name identifying this route.
@@ -35,10 +50,26 @@ pub struct Route {
     pub middlewares: Vec<String>,
     /// Priority for route ordering (higher wins).
     pub priority: i32,
+    /// Whether requests on this route produce trace spans.
+    pub tracing: bool,
+    /// Whether requests on this route are access logged.
+    pub access_logs: bool,
     /// Whether this route has been indexed by host (skip in non-host scan)
     host_indexed: bool,
 }
 
+impl Route {
+    /// Request extension describing this route, as matched on `entrypoint`.
+    pub fn matched(&self, entrypoint: &str) -> MatchedRoute {
+        MatchedRoute {
+            router: self.name.clone(),
+            service: self.service.clone(),
+            entrypoint: entrypoint.to_string(),
+            access_logs: self.access_logs,
+        }
+    }
+}
+
 impl Router {
     /// Build a router from config, pre-computing entrypoint and host indexes.
     pub fn from_config(config: &Config) -> Self {
@@ -50,10 +81,22 @@ impl Router {
                     Ok(matcher) => Some(Route {
                         name: name.clone(),
                         entrypoints: router_config.entry_points.clone(),
+                        // An unset priority falls back to the rule's specificity
+                        priority: match router_config.priority {
+                            0 => matcher.default_priority(),
+                            priority => priority,
+                        },
                         matcher,
                         service: router_config.service.clone(),
                         middlewares: router_config.middlewares.clone(),
-                        priority: router_config.priority,
+                        tracing: router_config
+                            .observability
+                            .as_ref()
+                            .is_none_or(|o| o.tracing),
+                        access_logs: router_config
+                            .observability
+                            .as_ref()
+                            .is_none_or(|o| o.access_logs),
                         host_indexed: false,
                     }),
                     Err(e) => {
@@ -65,7 +108,7 @@ impl Router {
             .collect();
 
         // Sort by priority (higher first)
-        routes.sort_by(|a, b| b.priority.cmp(&a.priority));
+        routes.sort_by_key(|r| std::cmp::Reverse(r.priority));
 
         // Build host index
         let mut host_index: HashMap<String, Vec<usize>> = HashMap::new();
@@ -127,6 +170,7 @@ impl Router {
     }
 
     /// Find the highest-priority route matching the given request attributes.
+    #[allow(clippy::too_many_arguments)]
     pub fn match_request(
         &self,
         entrypoint: &str,
@@ -135,6 +179,7 @@ impl Router {
         query: Option<&str>,
         method: Option<&str>,
         headers: &hyper::HeaderMap,
+        client_ip: Option<IpAddr>,
     ) -> Option<&Route> {
         // Get candidate indices for this entrypoint
         let candidates = self
@@ -152,7 +197,7 @@ impl Router {
                     let route = &self.routes[idx];
                     let ep_match = route.entrypoints.is_empty()
                         || route.entrypoints.iter().any(|ep| ep == entrypoint);
-                    if ep_match && route.matcher.matches(Some(h), path, query, method, headers) {
+                    if ep_match && route.matcher.matches(Some(h), path, query, method, headers, client_ip) {
                         return Some(route);
                     }
                 }
@@ -165,7 +210,7 @@ impl Router {
             if route.host_indexed {
                 continue; // Already checked via host index
             }
-            if route.matcher.matches(host, path, query, method, headers) {
+            if route.matcher.matches(host, path, query, method, headers, client_ip) {
                 return Some(route);
             }
         }
@@ -173,3 +218,105 @@ impl Router {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HttpConfig, RouterObservability};
+
+    /// Router config sending `rule` to the `app` service; priority 0 is unset
+    fn rule(rule: &str, priority: i32) -> crate::config::Router {
+        crate::config::Router {
+            entry_points: vec![],
+            rule: rule.to_string(),
+            rule_syntax: None,
+            service: "app".to_string(),
+            middlewares: vec![],
+            priority,
+            tls: None,
+            observability: None,
+        }
+    }
+
+    fn router(routers: Vec<(&str, crate::config::Router)>) -> Router {
+        let config = Config {
+            http: Some(HttpConfig {
+                routers: routers.into_iter().map(|(name, r)| (name.to_string(), r)).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        Router::from_config(&config)
+    }
+
+    fn route<'a>(router: &'a Router, host: &str, path: &str) -> Option<&'a str> {
+        router
+            .match_request("web", Some(host), path, None, Some("GET"), &hyper::HeaderMap::new(), None)
+            .map(|r| r.name.as_str())
+    }
+
+    #[test]
+    fn test_default_priority_prefers_specific_rule() {
+        let router = router(vec![
+            ("site", rule("Host(`example.com`)", 0)),
+            ("api", rule("Host(`example.com`) && PathPrefix(`/api`)", 0)),
+            ("prefix", rule("PathPrefix(`/`)", 0)),
+            ("prefix-v1", rule("PathPrefix(`/v1`)", 0)),
+        ]);
+
+        assert_eq!(route(&router, "example.com", "/api/users"), Some("api"));
+        assert_eq!(route(&router, "example.com", "/about"), Some("site"));
+        assert_eq!(route(&router, "other.com", "/v1/items"), Some("prefix-v1"));
+        assert_eq!(route(&router, "other.com", "/v2/items"), Some("prefix"));
+    }
+
+    #[test]
+    fn test_explicit_priority_is_authoritative() {
+        let router = router(vec![
+            ("site", rule("Host(`example.com`)", 1000)),
+            ("api", rule("Host(`example.com`) && PathPrefix(`/api`)", 0)),
+            ("fallback", rule("PathPrefix(`/`)", 1)),
+            ("v1", rule("PathPrefix(`/v1`)", 0)),
+        ]);
+
+        assert_eq!(route(&router, "example.com", "/api/users"), Some("site"));
+        assert_eq!(route(&router, "other.com", "/v1/items"), Some("v1"));
+        assert_eq!(router.routes.iter().find(|r| r.name == "fallback").unwrap().priority, 1);
+    }
+
+    #[test]
+    fn test_or_rule_is_as_specific_as_its_weakest_branch() {
+        let narrow = RouteMatcher::from_rule("Path(`/a/b/c`)").unwrap();
+        let either = RouteMatcher::from_rule("Path(`/a/b/c`) || Path(`/a`)").unwrap();
+        let both = RouteMatcher::from_rule("Path(`/a/b/c`) && Method(`GET`)").unwrap();
+
+        assert!(either.default_priority() < narrow.default_priority());
+        assert!(both.default_priority() > narrow.default_priority());
+    }
+
+    #[test]
+    fn test_matched_route_describes_route_and_entrypoint() {
+        let api = crate::config::Router {
+            service: "backend".to_string(),
+            observability: Some(RouterObservability {
+                access_logs: false,
+                tracing: true,
+                metrics: true,
+            }),
+            ..rule("PathPrefix(`/api`)", 0)
+        };
+        let router = router(vec![("api", api)]);
+        let route = router
+            .match_request("websecure", None, "/api/users", None, None, &hyper::HeaderMap::new(), None)
+            .unwrap();
+
+        let mut req = hyper::Request::new(());
+        req.extensions_mut().insert(route.matched("websecure"));
+
+        let matched = req.extensions().get::<MatchedRoute>().unwrap();
+        assert_eq!(matched.router, "api");
+        assert_eq!(matched.service, "backend");
+        assert_eq!(matched.entrypoint, "websecure");
+        assert!(!matched.access_logs);
+    }
+}
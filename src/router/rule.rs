@@ -1,6 +1,8 @@
 //! Rule AST and recursive-descent parser for Traefik-style routing rules.
 
+use ipnetwork::IpNetwork;
 use regex::Regex;
+use std::net::IpAddr;
 use thiserror::Error;
 
 /// Errors produced when parsing a routing rule string.
@@ -17,6 +19,10 @@ pub enum RuleParseError {
     /// The rule references an unknown matcher function.
     #[error("Unknown function: {0}")]
     UnknownFunction(String),
+
+    /// A ClientIP range is not a valid IP address or CIDR.
+    #[error("Invalid ClientIP range '{0}': {1}")]
+    InvalidClientIp(String, ipnetwork::IpNetworkError),
 }
 
 /// AST node representing a routing rule (host, path, header, query, method, or boolean combinator).
@@ -38,8 +44,14 @@ pub enum Rule {
     HeaderRegex(String, Regex),
     /// Match requests by query parameter key and value.
     Query(String, String),
+    /// Match requests carrying a query parameter, regardless of its value.
+    QueryPresent(String),
+    /// Match requests by query parameter key with a regex value.
+    QueryRegex(String, Regex),
     /// Match requests by HTTP method.
     Method(String),
+    /// Match requests whose client IP falls in any of the given ranges.
+    ClientIp(Vec<IpNetwork>),
     /// Both sub-rules must match (logical AND).
     And(Box<Rule>, Box<Rule>),
     /// Either sub-rule must match (logical OR).
@@ -57,6 +69,7 @@ impl Rule {
         query: Option<&str>,
         method: Option<&str>,
         headers: &hyper::HeaderMap,
+        client_ip: Option<IpAddr>,
     ) -> bool {
         match self {
             Rule::Host(expected) => {
@@ -68,85 +81,90 @@ impl Rule {
             Rule::Path(expected) => path == expected,
             Rule::PathPrefix(prefix) => path.starts_with(prefix),
             Rule::PathRegex(re) => re.is_match(path),
-            Rule::Header(name, value) => {
-                headers
-                    .get(name)
-                    .and_then(|v| v.to_str().ok())
-                    .map(|v| v == value)
-                    .unwrap_or(false)
+            Rule::Header(name, value) => Self::header_matches(headers, name, |v| v == value),
+            Rule::HeaderRegex(name, re) => Self::header_matches(headers, name, |v| re.is_match(v)),
+            Rule::Query(key, expected_value) => {
+                query.map(|q| Self::query_param_matches(q, key, |v| v == expected_value)).unwrap_or(false)
             }
-            Rule::HeaderRegex(name, re) => {
-                headers
-                    .get(name)
-                    .and_then(|v| v.to_str().ok())
-                    .map(|v| re.is_match(v))
-                    .unwrap_or(false)
+            Rule::QueryPresent(key) => {
+                query.map(|q| Self::query_param_matches(q, key, |_| true)).unwrap_or(false)
             }
-            Rule::Query(key, expected_value) => {
-                query.map(|q| Self::query_param_matches(q, key, expected_value)).unwrap_or(false)
+            Rule::QueryRegex(key, re) => {
+                query.map(|q| Self::query_param_matches(q, key, |v| re.is_match(v))).unwrap_or(false)
             }
             Rule::Method(expected_method) => {
                 method.map(|m| m.eq_ignore_ascii_case(expected_method)).unwrap_or(false)
             }
+            Rule::ClientIp(networks) => {
+                client_ip.map(|ip| networks.iter().any(|net| net.contains(ip))).unwrap_or(false)
+            }
             Rule::And(a, b) => {
-                a.matches(host, path, query, method, headers) && b.matches(host, path, query, method, headers)
+                a.matches(host, path, query, method, headers, client_ip)
+                    && b.matches(host, path, query, method, headers, client_ip)
             }
             Rule::Or(a, b) => {
-                a.matches(host, path, query, method, headers) || b.matches(host, path, query, method, headers)
+                a.matches(host, path, query, method, headers, client_ip)
+                    || b.matches(host, path, query, method, headers, client_ip)
             }
-            Rule::Not(r) => !r.matches(host, path, query, method, headers),
+            Rule::Not(r) => !r.matches(host, path, query, method, headers, client_ip),
         }
     }
 
-    /// Check if a query string contains a parameter with the expected value
-    fn query_param_matches(query: &str, key: &str, expected_value: &str) -> bool {
+    /// Check if any value of the named header satisfies `pred`.
+    /// A missing header never matches; non-UTF-8 values are skipped.
+    fn header_matches(headers: &hyper::HeaderMap, name: &str, pred: impl Fn(&str) -> bool) -> bool {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(pred)
+    }
+
+    /// Check if any value of the named query parameter satisfies `pred`.
+    /// A bare `key` with no `=` is treated as present with an empty value.
+    fn query_param_matches(query: &str, key: &str, pred: impl Fn(&str) -> bool) -> bool {
         for pair in query.split('&') {
+            if pair.is_empty() {
+                continue;
+            }
             let mut parts = pair.splitn(2, '=');
-            if let (Some(k), Some(v)) = (parts.next(), parts.next()) {
-                // URL decode the key and value for comparison
-                let decoded_key = Self::url_decode(k);
-                let decoded_value = Self::url_decode(v);
+            let k = parts.next().unwrap_or("");
+            let v = parts.next().unwrap_or("");
 
-                if decoded_key == key && decoded_value == expected_value {
-                    return true;
-                }
+            // URL decode the key and value for comparison
+            if Self::url_decode(k) == key && pred(&Self::url_decode(v)) {
+                return true;
             }
         }
         false
     }
 
-    /// Simple URL decode (handles %XX encoding)
+    /// Simple URL decode (handles %XX encoding, including multi-byte UTF-8 sequences)
     fn url_decode(input: &str) -> String {
-        let mut result = String::with_capacity(input.len());
-        let mut chars = input.chars().peekable();
-
-        while let Some(c) = chars.next() {
-            if c == '%' {
-                // Try to read two hex digits
-                let mut hex = String::new();
-                for _ in 0..2 {
-                    if let Some(&next) = chars.peek()
-                        && next.is_ascii_hexdigit() {
-                            hex.push(chars.next().unwrap());
-                        }
-                }
-                if hex.len() == 2
-                    && let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                        result.push(byte as char);
+        let bytes = input.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' => {
+                    let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+                    if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                        result.push(byte);
+                        i += 3;
                         continue;
                     }
-                // If decode failed, keep original
-                result.push('%');
-                result.push_str(&hex);
-            } else if c == '+' {
+                    // If decode failed, keep original
+                    result.push(b'%');
+                }
                 // Plus is space in query strings
-                result.push(' ');
-            } else {
-                result.push(c);
+                b'+' => result.push(b' '),
+                b => result.push(b),
             }
+            i += 1;
         }
 
-        result
+        String::from_utf8_lossy(&result).into_owned()
     }
 }
 
@@ -165,6 +183,27 @@ impl Rule {
             _ => vec![],
         }
     }
+
+    /// Measure how specific the rule is as `(matcher count, literal length)`.
+    /// An `Or` is only as specific as its weakest branch, since either one alone can match.
+    pub fn specificity(&self) -> (usize, usize) {
+        match self {
+            Rule::Host(s) | Rule::Path(s) | Rule::PathPrefix(s) | Rule::Method(s) => (1, s.len()),
+            Rule::QueryPresent(key) => (1, key.len()),
+            Rule::HostRegex(re) | Rule::PathRegex(re) => (1, re.as_str().len()),
+            Rule::Header(name, value) | Rule::Query(name, value) => (1, name.len() + value.len()),
+            Rule::HeaderRegex(name, re) | Rule::QueryRegex(name, re) => {
+                (1, name.len() + re.as_str().len())
+            }
+            Rule::ClientIp(networks) => (1, networks.iter().map(|n| n.to_string().len()).sum()),
+            Rule::And(a, b) => {
+                let (a, b) = (a.specificity(), b.specificity());
+                (a.0 + b.0, a.1 + b.1)
+            }
+            Rule::Or(a, b) => a.specificity().min(b.specificity()),
+            Rule::Not(inner) => inner.specificity(),
+        }
+    }
 }
 
 /// Recursive-descent parser for Traefik-compatible routing rule expressions.
@@ -174,9 +213,38 @@ impl RuleParser {
     /// Parse a rule string into an AST, supporting `&&`, `||`, `!`, and function matchers.
     pub fn parse(input: &str) -> Result<Rule, RuleParseError> {
         let input = input.trim();
+        Self::check_balanced(input)?;
         Self::parse_or(input)
     }
 
+    /// Reject unbalanced parentheses and unterminated backticks up front, so the
+    /// recursive split below can rely on every `(` having a matching `)`.
+    fn check_balanced(input: &str) -> Result<(), RuleParseError> {
+        let mut depth = 0usize;
+        let mut in_backtick = false;
+
+        for b in input.bytes() {
+            match b {
+                b'`' => in_backtick = !in_backtick,
+                b'(' if !in_backtick => depth += 1,
+                b')' if !in_backtick => {
+                    depth = depth.checked_sub(1).ok_or_else(|| {
+                        RuleParseError::InvalidSyntax(format!("unbalanced parentheses in '{}'", input))
+                    })?;
+                }
+                _ => {}
+            }
+        }
+
+        if in_backtick {
+            return Err(RuleParseError::InvalidSyntax(format!("unterminated backtick in '{}'", input)));
+        }
+        if depth != 0 {
+            return Err(RuleParseError::InvalidSyntax(format!("unbalanced parentheses in '{}'", input)));
+        }
+        Ok(())
+    }
+
     fn parse_or(input: &str) -> Result<Rule, RuleParseError> {
         // Find || at the top level (not inside parentheses)
         if let Some(pos) = Self::find_operator(input, "||") {
@@ -211,8 +279,8 @@ impl RuleParser {
     fn parse_primary(input: &str) -> Result<Rule, RuleParseError> {
         let input = input.trim();
 
-        // Handle parentheses
-        if input.starts_with('(') && input.ends_with(')') {
+        // Handle parentheses, only when the opening one closes at the very end
+        if input.starts_with('(') && Self::closing_paren(input, 0) == Some(input.len() - 1) {
             return Self::parse_or(&input[1..input.len() - 1]);
         }
 
@@ -230,7 +298,9 @@ impl RuleParser {
 
         let func_name = &input[..paren_start];
 
-        if !input.ends_with(')') {
+        // The call's own closing parenthesis must end the expression; anything
+        // after it is a missing operator
+        if Self::closing_paren(input, paren_start) != Some(input.len() - 1) {
             return Err(RuleParseError::InvalidSyntax(input.to_string()));
         }
 
@@ -288,18 +358,50 @@ impl RuleParser {
                 Ok(Rule::HeaderRegex(args[0].clone(), re))
             }
             "Method" => {
-                let method = args
-                    .first()
-                    .ok_or_else(|| RuleParseError::InvalidSyntax("Method requires an argument".into()))?;
-                Ok(Rule::Method(method.clone()))
+                if args.is_empty() {
+                    return Err(RuleParseError::InvalidSyntax("Method requires an argument".into()));
+                }
+                // Method(`GET`, `HEAD`) is shorthand for Method(`GET`) || Method(`HEAD`)
+                let mut rules = args.iter().map(|m| {
+                    hyper::Method::from_bytes(m.as_bytes())
+                        .map(|_| Rule::Method(m.to_ascii_uppercase()))
+                        .map_err(|_| RuleParseError::InvalidSyntax(format!("Invalid HTTP method: {}", m)))
+                });
+                let first = rules.next().expect("args checked non-empty")?;
+                rules.try_fold(first, |acc, r| Ok(Rule::Or(Box::new(acc), Box::new(r?))))
+            }
+            "Query" => match args.len() {
+                1 => Ok(Rule::QueryPresent(args[0].clone())),
+                2 => Ok(Rule::Query(args[0].clone(), args[1].clone())),
+                _ => Err(RuleParseError::InvalidSyntax(
+                    "Query requires one or two arguments".into(),
+                )),
+            },
+            "ClientIP" => {
+                if args.is_empty() {
+                    return Err(RuleParseError::InvalidSyntax(
+                        "ClientIP requires at least one argument".into(),
+                    ));
+                }
+                // Pre-parse ranges at compile time so matching never re-parses
+                let networks = args
+                    .iter()
+                    .map(|range| {
+                        range
+                            .parse::<IpNetwork>()
+                            .map_err(|e| RuleParseError::InvalidClientIp(range.clone(), e))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Rule::ClientIp(networks))
             }
-            "Query" => {
+            "QueryRegexp" => {
                 if args.len() != 2 {
                     return Err(RuleParseError::InvalidSyntax(
-                        "Query requires two arguments".into(),
+                        "QueryRegexp requires two arguments".into(),
                     ));
                 }
-                Ok(Rule::Query(args[0].clone(), args[1].clone()))
+                let re = Regex::new(&args[1])?;
+                Ok(Rule::QueryRegex(args[0].clone(), re))
             }
             _ => Err(RuleParseError::UnknownFunction(func_name.to_string())),
         }
@@ -338,18 +440,39 @@ impl RuleParser {
         Ok(args)
     }
 
+    // Both scanners walk bytes: the syntax characters are ASCII, so byte offsets
+    // are always char boundaries even when literals contain UTF-8.
     fn find_operator(input: &str, op: &str) -> Option<usize> {
+        let bytes = input.as_bytes();
         let mut depth = 0;
         let mut in_backtick = false;
-        let chars: Vec<char> = input.chars().collect();
-
-        for i in 0..chars.len() {
-            match chars[i] {
-                '`' => in_backtick = !in_backtick,
-                '(' if !in_backtick => depth += 1,
-                ')' if !in_backtick => depth -= 1,
-                _ if !in_backtick && depth == 0 => {
-                    if input[i..].starts_with(op) {
+
+        for i in 0..bytes.len() {
+            match bytes[i] {
+                b'`' => in_backtick = !in_backtick,
+                b'(' if !in_backtick => depth += 1,
+                b')' if !in_backtick => depth -= 1,
+                _ if !in_backtick && depth == 0 && bytes[i..].starts_with(op.as_bytes()) => {
+                    return Some(i);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Byte offset of the `)` matching the `(` at `open`.
+    fn closing_paren(input: &str, open: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut in_backtick = false;
+
+        for (i, &b) in input.as_bytes().iter().enumerate().skip(open) {
+            match b {
+                b'`' => in_backtick = !in_backtick,
+                b'(' if !in_backtick => depth += 1,
+                b')' if !in_backtick => {
+                    depth -= 1;
+                    if depth == 0 {
                         return Some(i);
                     }
                 }
@@ -400,14 +523,14 @@ mod tests {
         let headers = hyper::HeaderMap::new();
 
         // Should match
-        assert!(rule.matches(None, "/", Some("env=prod"), None, &headers));
-        assert!(rule.matches(None, "/", Some("foo=bar&env=prod"), None, &headers));
-        assert!(rule.matches(None, "/", Some("env=prod&other=value"), None, &headers));
+        assert!(rule.matches(None, "/", Some("env=prod"), None, &headers, None));
+        assert!(rule.matches(None, "/", Some("foo=bar&env=prod"), None, &headers, None));
+        assert!(rule.matches(None, "/", Some("env=prod&other=value"), None, &headers, None));
 
         // Should not match
-        assert!(!rule.matches(None, "/", Some("env=dev"), None, &headers));
-        assert!(!rule.matches(None, "/", None, None, &headers));
-        assert!(!rule.matches(None, "/", Some("other=value"), None, &headers));
+        assert!(!rule.matches(None, "/", Some("env=dev"), None, &headers, None));
+        assert!(!rule.matches(None, "/", None, None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("other=value"), None, &headers, None));
     }
 
     #[test]
@@ -416,9 +539,9 @@ mod tests {
         let headers = hyper::HeaderMap::new();
 
         // URL encoded space as %20
-        assert!(rule.matches(None, "/", Some("name=hello%20world"), None, &headers));
+        assert!(rule.matches(None, "/", Some("name=hello%20world"), None, &headers, None));
         // URL encoded space as +
-        assert!(rule.matches(None, "/", Some("name=hello+world"), None, &headers));
+        assert!(rule.matches(None, "/", Some("name=hello+world"), None, &headers, None));
     }
 
     #[test]
@@ -426,9 +549,34 @@ mod tests {
         let rule = RuleParser::parse("Method(`POST`)").unwrap();
         let headers = hyper::HeaderMap::new();
 
-        assert!(rule.matches(None, "/", None, Some("POST"), &headers));
-        assert!(rule.matches(None, "/", None, Some("post"), &headers));
-        assert!(!rule.matches(None, "/", None, Some("GET"), &headers));
+        assert!(rule.matches(None, "/", None, Some("POST"), &headers, None));
+        assert!(rule.matches(None, "/", None, Some("post"), &headers, None));
+        assert!(!rule.matches(None, "/", None, Some("GET"), &headers, None));
+        assert!(!rule.matches(None, "/", None, None, &headers, None));
+    }
+
+    #[test]
+    fn test_method_and_path() {
+        let rule = RuleParser::parse("Method(`GET`) && Path(`/api`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+
+        assert!(rule.matches(None, "/api", None, Some("GET"), &headers, None));
+        assert!(rule.matches(None, "/api", None, Some("get"), &headers, None));
+        assert!(!rule.matches(None, "/api", None, Some("POST"), &headers, None));
+        assert!(!rule.matches(None, "/other", None, Some("GET"), &headers, None));
+    }
+
+    #[test]
+    fn test_method_multiple_and_invalid() {
+        let rule = RuleParser::parse("Method(`get`, `HEAD`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+
+        assert!(rule.matches(None, "/", None, Some("GET"), &headers, None));
+        assert!(rule.matches(None, "/", None, Some("HEAD"), &headers, None));
+        assert!(!rule.matches(None, "/", None, Some("DELETE"), &headers, None));
+
+        assert!(RuleParser::parse("Method(`GE T`)").is_err());
+        assert!(RuleParser::parse("Method()").is_err());
     }
 
     #[test]
@@ -436,8 +584,212 @@ mod tests {
         let rule = RuleParser::parse("PathPrefix(`/api`) && Query(`version`, `v2`)").unwrap();
         let headers = hyper::HeaderMap::new();
 
-        assert!(rule.matches(None, "/api/users", Some("version=v2"), None, &headers));
-        assert!(!rule.matches(None, "/api/users", Some("version=v1"), None, &headers));
-        assert!(!rule.matches(None, "/other", Some("version=v2"), None, &headers));
+        assert!(rule.matches(None, "/api/users", Some("version=v2"), None, &headers, None));
+        assert!(!rule.matches(None, "/api/users", Some("version=v1"), None, &headers, None));
+        assert!(!rule.matches(None, "/other", Some("version=v2"), None, &headers, None));
+    }
+
+    #[test]
+    fn test_query_missing_param() {
+        let rule = RuleParser::parse("Query(`version`, `v2`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+
+        assert!(!rule.matches(None, "/", Some(""), None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("versions=v2"), None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("version"), None, &headers, None));
+    }
+
+    #[test]
+    fn test_query_multiple_values() {
+        let rule = RuleParser::parse("Query(`tag`, `b`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+
+        assert!(rule.matches(None, "/", Some("tag=a&tag=b&tag=c"), None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("tag=a&tag=c"), None, &headers, None));
+    }
+
+    #[test]
+    fn test_query_presence() {
+        let rule = RuleParser::parse("Query(`debug`)").unwrap();
+        assert!(matches!(rule, Rule::QueryPresent(ref k) if k == "debug"));
+        let headers = hyper::HeaderMap::new();
+
+        assert!(rule.matches(None, "/", Some("debug"), None, &headers, None));
+        assert!(rule.matches(None, "/", Some("a=1&debug=&b=2"), None, &headers, None));
+        assert!(rule.matches(None, "/", Some("debug=true"), None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("nodebug=1"), None, &headers, None));
+        assert!(!rule.matches(None, "/", None, None, &headers, None));
+    }
+
+    #[test]
+    fn test_query_regexp() {
+        let rule = RuleParser::parse("QueryRegexp(`version`, `^v[0-9]+$`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+
+        assert!(rule.matches(None, "/", Some("version=v12"), None, &headers, None));
+        assert!(rule.matches(None, "/", Some("version=beta&version=v3"), None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("version=beta"), None, &headers, None));
+        assert!(!rule.matches(None, "/", Some("other=v1"), None, &headers, None));
+
+        assert!(RuleParser::parse("QueryRegexp(`version`, `(`)").is_err());
+    }
+
+    #[test]
+    fn test_query_url_encoded_key_and_utf8() {
+        let rule = RuleParser::parse("Query(`city name`, `Zürich`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+
+        assert!(rule.matches(None, "/", Some("city%20name=Z%C3%BCrich"), None, &headers, None));
+        assert!(rule.matches(None, "/", Some("city+name=Z%c3%bcrich"), None, &headers, None));
+        // Malformed escapes are kept literally rather than dropped
+        let literal = RuleParser::parse("Query(`q`, `100%`)").unwrap();
+        assert!(literal.matches(None, "/", Some("q=100%"), None, &headers, None));
+    }
+
+    #[test]
+    fn test_client_ip_matching() {
+        let rule = RuleParser::parse("ClientIP(`10.0.0.0/8`, `192.168.1.5`, `2001:db8::/32`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+        let check = |ip: &str| rule.matches(None, "/", None, None, &headers, Some(ip.parse().unwrap()));
+
+        assert!(check("10.1.2.3"));
+        assert!(check("192.168.1.5"));
+        assert!(check("2001:db8::1"));
+
+        assert!(!check("11.0.0.1"));
+        assert!(!check("192.168.1.6"));
+        assert!(!check("2001:db9::1"));
+        assert!(!rule.matches(None, "/", None, None, &headers, None));
+    }
+
+    #[test]
+    fn test_client_ip_combined_with_path() {
+        let rule = RuleParser::parse("ClientIP(`10.0.0.0/8`) && PathPrefix(`/admin`)").unwrap();
+        let headers = hyper::HeaderMap::new();
+        let internal = Some("10.0.0.7".parse().unwrap());
+        let external = Some("203.0.113.9".parse().unwrap());
+
+        assert!(rule.matches(None, "/admin/users", None, None, &headers, internal));
+        assert!(!rule.matches(None, "/admin/users", None, None, &headers, external));
+    }
+
+    #[test]
+    fn test_client_ip_malformed_cidr() {
+        let err = RuleParser::parse("ClientIP(`10.0.0.0/33`)").unwrap_err();
+        assert!(matches!(err, RuleParseError::InvalidClientIp(ref r, _) if r == "10.0.0.0/33"));
+        assert!(err.to_string().contains("10.0.0.0/33"));
+
+        assert!(RuleParser::parse("ClientIP(`not-an-ip`)").is_err());
+        assert!(RuleParser::parse("ClientIP()").is_err());
+    }
+
+    #[test]
+    fn test_header_regexp() {
+        let rule = RuleParser::parse("HeaderRegexp(`X-Version`, `^v[0-9]+$`)").unwrap();
+        assert!(matches!(rule, Rule::HeaderRegex(ref name, _) if name == "X-Version"));
+        let check = |values: &[&str]| {
+            let mut headers = hyper::HeaderMap::new();
+            for value in values {
+                headers.append("x-version", value.parse().unwrap());
+            }
+            rule.matches(None, "/", None, None, &headers, None)
+        };
+
+        assert!(check(&["v2"]));
+        assert!(!check(&["v2-beta"]));
+        assert!(!check(&[]));
+        // Any one of several values is enough
+        assert!(check(&["beta", "v10"]));
+        assert!(!check(&["beta", "rc1"]));
+    }
+
+    #[test]
+    fn test_header_multiple_values() {
+        let rule = RuleParser::parse("Header(`Accept-Language`, `fr`)").unwrap();
+        let mut headers = hyper::HeaderMap::new();
+        headers.append("accept-language", "en".parse().unwrap());
+        assert!(!rule.matches(None, "/", None, None, &headers, None));
+
+        headers.append("accept-language", "fr".parse().unwrap());
+        assert!(rule.matches(None, "/", None, None, &headers, None));
+    }
+
+    #[test]
+    fn test_header_regexp_invalid() {
+        let err = RuleParser::parse("HeaderRegexp(`X-Version`, `v[0-9`)").unwrap_err();
+        assert!(matches!(err, RuleParseError::InvalidRegex(_)));
+        assert!(RuleParser::parse("HeaderRegexp(`X-Version`)").is_err());
+    }
+
+    fn matches_request(rule: &Rule, host: &str, path: &str) -> bool {
+        rule.matches(Some(host), path, None, None, &hyper::HeaderMap::new(), None)
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        // Parsed as Host(a) || (Host(b) && PathPrefix(/api))
+        let rule = RuleParser::parse("Host(`a`) || Host(`b`) && PathPrefix(`/api`)").unwrap();
+        assert!(matches!(rule, Rule::Or(_, _)));
+
+        assert!(matches_request(&rule, "a", "/web"));
+        assert!(matches_request(&rule, "b", "/api/users"));
+        assert!(!matches_request(&rule, "b", "/web"));
+    }
+
+    #[test]
+    fn test_grouping_changes_result() {
+        let rule = RuleParser::parse("(Host(`a`) || Host(`b`)) && PathPrefix(`/api`)").unwrap();
+        assert!(matches!(rule, Rule::And(_, _)));
+
+        assert!(!matches_request(&rule, "a", "/web"));
+        assert!(matches_request(&rule, "a", "/api/users"));
+        assert!(matches_request(&rule, "b", "/api/users"));
+    }
+
+    #[test]
+    fn test_negation() {
+        let rule =
+            RuleParser::parse("(Host(`a`) || Host(`b`)) && !PathPrefix(`/internal`)").unwrap();
+        assert!(matches_request(&rule, "a", "/public"));
+        assert!(matches_request(&rule, "b", "/"));
+        assert!(!matches_request(&rule, "a", "/internal/metrics"));
+        assert!(!matches_request(&rule, "c", "/public"));
+
+        // `!` binds tighter than `&&`, and negates whole groups
+        let rule = RuleParser::parse("!Host(`a`) && PathPrefix(`/api`)").unwrap();
+        assert!(matches!(rule, Rule::And(ref left, _) if matches!(**left, Rule::Not(_))));
+        let rule = RuleParser::parse("!(Host(`a`) || Host(`b`))").unwrap();
+        assert!(!matches_request(&rule, "b", "/"));
+        assert!(matches_request(&rule, "c", "/"));
+        let rule = RuleParser::parse("!!Host(`a`)").unwrap();
+        assert!(matches_request(&rule, "a", "/"));
+    }
+
+    #[test]
+    fn test_operators_inside_literals_and_utf8() {
+        let rule = RuleParser::parse("Path(`/a&&b||(c`) && Query(`city`, `Zürich`)").unwrap();
+        assert!(matches!(rule, Rule::And(ref left, _) if matches!(**left, Rule::Path(ref p) if p == "/a&&b||(c")));
+        assert!(rule.matches(None, "/a&&b||(c", Some("city=Z%C3%BCrich"), None, &hyper::HeaderMap::new(), None));
+    }
+
+    #[test]
+    fn test_unbalanced_parentheses() {
+        for rule in [
+            "(Host(`a`) || Host(`b`)",
+            "Host(`a`) || Host(`b`))",
+            "Host(`a`))",
+            ")Host(`a`)(",
+            "Host(`a`",
+            "Host(`a)",
+        ] {
+            let err = RuleParser::parse(rule).unwrap_err();
+            assert!(matches!(err, RuleParseError::InvalidSyntax(_)), "{}: {}", rule, err);
+        }
+    }
+
+    #[test]
+    fn test_missing_operator_rejected() {
+        assert!(RuleParser::parse("Host(`a`) PathPrefix(`/api`)").is_err());
+        assert!(RuleParser::parse("(Host(`a`)) (Host(`b`))").is_err());
     }
 }
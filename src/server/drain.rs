@@ -0,0 +1,109 @@
+//! Drain response for an entrypoint (`transport.drainResponse`).
+//!
+//! While the node drains, new requests get a 503 pointing clients elsewhere
+//! instead of having their connections refused. Requests already being proxied
+//! are unaffected and complete normally.
+
+use crate::config::DrainResponse;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::header::{CONNECTION, CONTENT_TYPE, LOCATION, RETRY_AFTER};
+use hyper::http::HeaderValue;
+use hyper::{Request, Response, StatusCode, Version};
+
+const DRAIN_BODY: &str = "Service draining, retry on another node\n";
+
+/// 503 response sent to requests arriving on a draining node
+pub(crate) struct DrainResponder {
+    retry_after: Option<HeaderValue>,
+    location: Option<HeaderValue>,
+}
+
+impl DrainResponder {
+    /// Fails when the configured `location` isn't a valid header value.
+    pub(crate) fn from_config(config: &DrainResponse) -> Result<Self> {
+        let location = config
+            .location
+            .as_deref()
+            .map(HeaderValue::from_str)
+            .transpose()
+            .context("Invalid drainResponse location")?;
+
+        Ok(Self {
+            retry_after: config
+                .retry_after
+                .map(|d| HeaderValue::from(d.as_std().as_secs())),
+            location,
+        })
+    }
+
+    /// The drain response for `req`. HTTP/1 connections are asked to close so
+    /// the client reconnects, ideally to another node.
+    pub(crate) fn response<B>(
+        &self,
+        req: &Request<B>,
+    ) -> Response<BoxBody<Bytes, hyper::Error>> {
+        let mut resp = Response::new(
+            Full::new(Bytes::from_static(DRAIN_BODY.as_bytes()))
+                .map_err(|never| match never {})
+                .boxed(),
+        );
+        *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        let headers = resp.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+        if let Some(ref retry_after) = self.retry_after {
+            headers.insert(RETRY_AFTER, retry_after.clone());
+        }
+        if let Some(ref location) = self.location {
+            headers.insert(LOCATION, location.clone());
+        }
+        // Connection is a hop-by-hop header HTTP/2 forbids
+        if req.version() < Version::HTTP_2 {
+            headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Duration;
+
+    #[test]
+    fn test_drain_response_headers() {
+        let config = DrainResponse {
+            retry_after: Some(Duration::from_secs(30)),
+            location: Some("https://other.example.com/".to_string()),
+        };
+        let responder = DrainResponder::from_config(&config).unwrap();
+        let req = Request::new(());
+        let resp = responder.response(&req);
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()[RETRY_AFTER], "30");
+        assert_eq!(resp.headers()[LOCATION], "https://other.example.com/");
+        assert_eq!(resp.headers()[CONNECTION], "close");
+    }
+
+    #[test]
+    fn test_drain_response_over_http2_keeps_connection_header_out() {
+        let responder = DrainResponder::from_config(&DrainResponse::default()).unwrap();
+        let req = Request::builder().version(Version::HTTP_2).body(()).unwrap();
+        let resp = responder.response(&req);
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(resp.headers().get(CONNECTION).is_none());
+        assert!(resp.headers().get(RETRY_AFTER).is_none());
+        assert!(resp.headers().get(LOCATION).is_none());
+    }
+
+    #[test]
+    fn test_invalid_location_rejected() {
+        let config = DrainResponse {
+            retry_after: None,
+            location: Some("bad\nvalue".to_string()),
+        };
+        assert!(DrainResponder::from_config(&config).is_err());
+    }
+}
@@ -1,18 +1,25 @@
-use crate::config::EntryPoint;
-use crate::middleware::{AccessLogWriter, RequestContext};
-use crate::proxy::ProxyHandler;
-use crate::server::SharedState;
-use crate::tls::{try_handle_challenge, TlsAcceptor};
+use crate::config::{EntryPoint, RespondingTimeouts, TlsOptions};
+use crate::middleware::{AccessLogWriter, ForwardedHeadersPolicy, RequestContext};
+use crate::proxy::{is_websocket_upgrade, ProxyHandler};
+use crate::server::drain::DrainResponder;
+use crate::server::redirect::EntryPointRedirect;
+use crate::server::timeouts::{ConnectionActivity, TimeoutIo};
+use crate::server::{ConnectionSlot, SharedState};
+use crate::tcp::ProxyProtocolPolicy;
+use crate::tls::{try_handle_challenge, ClientCertInfo, StrictSniResolver, TlsAcceptor};
 use anyhow::{Context, Result};
 use http_body_util::BodyExt;
 use hyper::service::service_fn;
+use hyper::body::Body;
 use hyper::Request;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder as AutoBuilder;
 use rustls::server::ResolvesServerCert;
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
 use tokio_rustls::TlsAcceptor as TokioTlsAcceptor;
 use tracing::{debug, error, info};
 
@@ -23,75 +30,117 @@ pub struct Listener {
     state: Arc<SharedState>,
     proxy: Arc<ProxyHandler>,
     tls_acceptor: Option<TokioTlsAcceptor>,
+    proxy_protocol: Option<Arc<ProxyProtocolPolicy>>,
+    forwarded_headers: Arc<ForwardedHeadersPolicy>,
+    responding_timeouts: RespondingTimeouts,
+    redirect: Option<Arc<EntryPointRedirect>>,
+    /// Entrypoint's `transport.drainResponse`, served while the node drains
+    drain_response: Option<Arc<DrainResponder>>,
+    /// Entrypoint's `transport.maxConnections` cap
+    connection_limit: Option<Arc<Semaphore>>,
+    /// Connections currently open on this entrypoint
+    open_connections: Arc<AtomicUsize>,
 }
 
 impl Listener {
     /// Create a listener for the given entrypoint, optionally with TLS.
-    pub fn new(
+    /// Fails when the entrypoint's TLS options (e.g. client auth CA files) can't be applied,
+    /// so a misconfigured mTLS entrypoint never comes up without client verification.
+    pub(crate) fn new(
         name: String,
         entrypoint: EntryPoint,
+        tls_options: Option<&TlsOptions>,
+        redirect: Option<EntryPointRedirect>,
         state: Arc<SharedState>,
         proxy: Arc<ProxyHandler>,
-    ) -> Self {
+    ) -> Result<Self> {
         // Build TLS acceptor
-        let tls_acceptor = Self::build_tls_acceptor(&name, &entrypoint, &state);
+        let tls_acceptor = Self::build_tls_acceptor(&name, &entrypoint, tls_options, &state)?;
+        let proxy_protocol = entrypoint
+            .proxy_protocol
+            .as_ref()
+            .map(|config| Arc::new(ProxyProtocolPolicy::from_config(config)));
+        let forwarded_headers = Arc::new(ForwardedHeadersPolicy::from_config(
+            entrypoint.forwarded_headers.as_ref(),
+        ));
+        let responding_timeouts = entrypoint
+            .transport
+            .as_ref()
+            .and_then(|transport| transport.responding_timeouts.clone())
+            .unwrap_or_default();
+        let connection_limit = entrypoint
+            .transport
+            .as_ref()
+            .and_then(|transport| transport.max_connections)
+            .filter(|&max| max > 0)
+            .map(|max| Arc::new(Semaphore::new(max)));
+        let drain_response = entrypoint
+            .transport
+            .as_ref()
+            .and_then(|transport| transport.drain_response.as_ref())
+            .map(DrainResponder::from_config)
+            .transpose()
+            .with_context(|| format!("Invalid transport for entrypoint '{}'", name))?
+            .map(Arc::new);
 
-        Self {
+        Ok(Self {
             name: Arc::from(name),
             entrypoint,
             state,
             proxy,
             tls_acceptor,
-        }
+            proxy_protocol,
+            forwarded_headers,
+            responding_timeouts,
+            redirect: redirect.map(Arc::new),
+            drain_response,
+            connection_limit,
+            open_connections: Arc::new(AtomicUsize::new(0)),
+        })
     }
 
     fn build_tls_acceptor(
         name: &str,
         entrypoint: &EntryPoint,
+        tls_options: Option<&TlsOptions>,
         state: &SharedState,
-    ) -> Option<TokioTlsAcceptor> {
+    ) -> Result<Option<TokioTlsAcceptor>> {
         // TLS can be configured at entrypoint level (http.tls)
-        let tls_config = entrypoint.http.as_ref()?.tls.as_ref()?;
-
-        // Check if we should use ACME/SNI resolver
-        if tls_config.cert_resolver.is_some() {
-            // Use SNI-based certificate resolver from shared state
-            if let Some(ref resolver) = state.cert_resolver {
-                match TlsAcceptor::from_resolver(Arc::clone(resolver) as Arc<dyn ResolvesServerCert>) {
-                    Ok(acceptor) => {
-                        info!("TLS enabled for entrypoint '{}' (SNI resolver)", name);
-                        return Some(TokioTlsAcceptor::from(acceptor.get_config()));
-                    }
-                    Err(e) => {
-                        error!("Failed to configure SNI TLS for '{}': {}", name, e);
-                    }
-                }
-            } else {
-                error!(
-                    "Entrypoint '{}' requests cert_resolver but ACME is not configured",
-                    name
-                );
-            }
+        let Some(tls_config) = entrypoint.http.as_ref().and_then(|http| http.tls.as_ref()) else {
+            return Ok(None);
+        };
+
+        if tls_config.cert_resolver.is_some() && state.cert_resolver.is_none() {
+            error!(
+                "Entrypoint '{}' requests cert_resolver but ACME is not configured",
+                name
+            );
         }
 
-        // If TLS is enabled but no cert resolver, try to use certificates from global TLS config
-        // This will be resolved by the router/server when loading the full config
-        // For now, just enable TLS mode (the actual certs come from tls.certificates)
-        info!("TLS enabled for entrypoint '{}' (via http.tls)", name);
+        // Certificates come from the shared SNI resolver (static tls.certificates plus ACME)
+        let Some(ref resolver) = state.cert_resolver else {
+            return Ok(None);
+        };
 
-        // Use shared cert resolver if available
-        if let Some(ref resolver) = state.cert_resolver {
-            match TlsAcceptor::from_resolver(Arc::clone(resolver) as Arc<dyn ResolvesServerCert>) {
-                Ok(acceptor) => {
-                    return Some(TokioTlsAcceptor::from(acceptor.get_config()));
-                }
-                Err(e) => {
-                    error!("Failed to configure TLS for '{}': {}", name, e);
-                }
-            }
-        }
+        // sniStrict: refuse handshakes that would otherwise get the default certificate
+        let resolver: Arc<dyn ResolvesServerCert> = if tls_options.is_some_and(|o| o.sni_strict) {
+            Arc::new(StrictSniResolver::new(Arc::clone(resolver)))
+        } else {
+            Arc::clone(resolver) as Arc<dyn ResolvesServerCert>
+        };
+        let acceptor = TlsAcceptor::from_resolver_with_options(resolver, tls_options)
+        .with_context(|| format!("Failed to configure TLS for entrypoint '{}'", name))?;
 
-        None
+        let client_auth = tls_options
+            .and_then(|o| o.client_auth.as_ref())
+            .and_then(|c| c.client_auth_type.as_deref())
+            .unwrap_or("NoClientCert");
+        info!(
+            "TLS enabled for entrypoint '{}' (client auth: {})",
+            name, client_auth
+        );
+
+        Ok(Some(TokioTlsAcceptor::from(acceptor.get_config())))
     }
 
     /// Bind and accept connections in a loop until the server drains.
@@ -116,8 +165,34 @@ impl Listener {
             self.name, addr, protocol
         );
 
+        self.accept_connections(listener).await;
+        Ok(())
+    }
+
+    /// Wait for room under the entrypoint's and the server-wide connection caps.
+    /// The entrypoint's permit is taken first so waiting on the global cap never
+    /// holds a permit another entrypoint could use.
+    async fn acquire_slot(&self) -> ConnectionSlot {
+        let entrypoint_permit = match &self.connection_limit {
+            Some(limit) => Arc::clone(limit).acquire_owned().await.ok(),
+            None => None,
+        };
+        let global_permit = self.state.connections.acquire_permit().await;
+        ConnectionSlot::new(
+            Arc::clone(&self.name),
+            Arc::clone(&self.open_connections),
+            entrypoint_permit,
+            global_permit,
+        )
+    }
+
+    async fn accept_connections(&self, listener: TcpListener) {
         loop {
-            let (stream, remote_addr) = match listener.accept().await {
+            // Backpressure: stop accepting while at a connection cap, leaving
+            // further connections queued in the listen backlog until one closes
+            let slot = self.acquire_slot().await;
+
+            let (mut stream, mut remote_addr) = match listener.accept().await {
                 Ok(conn) => conn,
                 Err(e) => {
                     error!("Failed to accept connection: {}", e);
@@ -131,18 +206,49 @@ impl Listener {
             let tls_acceptor = self.tls_acceptor.clone();
             let connection_is_tls = tls_acceptor.is_some();
             let access_log = state.access_log.clone();
+            let proxy_protocol = self.proxy_protocol.clone();
+            let forwarded_headers = Arc::clone(&self.forwarded_headers);
+            let activity = ConnectionActivity::new(&self.responding_timeouts);
+            let redirect = self.redirect.clone();
+            let drain_response = self.drain_response.clone();
 
             tokio::spawn(async move {
-                // Check if draining - reject new connections
-                if !state.connections.connection_start() {
+                let slot = Arc::new(slot);
+
+                // Recover the real client address before anything uses remote_addr
+                if let Some(policy) = proxy_protocol {
+                    match policy.accept(&mut stream, remote_addr).await {
+                        Ok(addr) => remote_addr = addr,
+                        Err(e) => {
+                            debug!("Rejecting connection from {}: {}", remote_addr, e);
+                            return;
+                        }
+                    }
+                }
+
+                let trust_forwarded = forwarded_headers.is_trusted(remote_addr.ip());
+
+                // Check if draining - reject new connections, unless they're
+                // answered with the drain response (not counted as active)
+                let counted = state.connections.connection_start();
+                if !counted && drain_response.is_none() {
                     debug!("Rejecting connection from {} - server draining", remote_addr);
                     return;
                 }
 
+                // Read/write/idle timeouts apply from here on, TLS handshake included
+                let stream = TimeoutIo::new(stream, Arc::clone(&activity));
+
                 if let Some(acceptor) = tls_acceptor {
                     // TLS connection
                     match acceptor.accept(stream).await {
                         Ok(tls_stream) => {
+                            // Only present when the handshake's client verifier accepted it
+                            let client_cert = tls_stream
+                                .get_ref()
+                                .1
+                                .peer_certificates()
+                                .map(|chain| Arc::new(ClientCertInfo::from_chain(chain)));
                             let io = TokioIo::new(tls_stream);
                             Self::serve_connection(
                                 io,
@@ -151,7 +257,13 @@ impl Listener {
                                 Arc::clone(&state),
                                 proxy,
                                 connection_is_tls,
+                                trust_forwarded,
+                                client_cert,
                                 access_log,
+                                activity,
+                                redirect,
+                                drain_response,
+                                Some(slot),
                             )
                             .await;
                         }
@@ -169,17 +281,26 @@ impl Listener {
                         Arc::clone(&state),
                         proxy,
                         connection_is_tls,
+                        trust_forwarded,
+                        None,
                         access_log,
+                        activity,
+                        redirect,
+                        drain_response,
+                        Some(slot),
                     )
                     .await;
                 }
 
                 // Mark connection as done
-                state.connections.connection_end();
+                if counted {
+                    state.connections.connection_end();
+                }
             });
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn serve_connection<I>(
         io: I,
         remote_addr: SocketAddr,
@@ -187,7 +308,13 @@ impl Listener {
         state: Arc<SharedState>,
         proxy: Arc<ProxyHandler>,
         is_tls: bool,
+        trust_forwarded: bool,
+        client_cert: Option<Arc<ClientCertInfo>>,
         access_log: AccessLogWriter,
+        activity: Arc<ConnectionActivity>,
+        redirect: Option<Arc<EntryPointRedirect>>,
+        drain_response: Option<Arc<DrainResponder>>,
+        slot: Option<Arc<ConnectionSlot>>,
     ) where
         I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
     {
@@ -196,12 +323,18 @@ impl Listener {
             let proxy = Arc::clone(&proxy);
             let ep = Arc::clone(&entrypoint_name);
             let access_log = access_log.clone();
+            let client_cert = client_cert.clone();
+            let redirect = redirect.clone();
+            let drain_response = drain_response.clone();
+            let slot = slot.clone();
+            // Held by the response body so the write timeout covers streaming it
+            let request = activity.request_started();
 
             async move {
                 // Check for ACME HTTP-01 challenges first (on non-TLS connections)
                 if !is_tls
                     && let Some(response) =
-                        try_handle_challenge(&req, &state.acme_challenges).await
+                        try_handle_challenge(&req, &state.acme_challenges, &state.store).await
                     {
                         // Convert Full<Bytes> to BoxBody
                         let boxed = response.map(|body| {
@@ -210,15 +343,52 @@ impl Listener {
                             })
                             .boxed()
                         });
-                        return Ok(boxed);
+                        return Ok(request.hold_until_written(boxed));
                     }
 
+                // A draining node points new requests elsewhere
+                if let Some(drain_response) = &drain_response
+                    && state.connections.is_draining()
+                {
+                    return Ok(request.hold_until_written(drain_response.response(&req)));
+                }
+
+                // Entrypoint redirection (e.g. HTTP to HTTPS) happens before routing
+                if let Some(redirect) = &redirect
+                    && redirect.applies_to(&req, &ep, &state.router.load(), remote_addr)
+                {
+                    return Ok(request.hold_until_written(redirect.response(&req)));
+                }
+
+                // A body still to come stays under the read timeout until it's consumed
+                if !req.body().is_end_stream()
+                    && let Some(body_read) = request.body_read_deadline()
+                {
+                    req.extensions_mut().insert(body_read);
+                }
+
                 // Inject request context for middleware (remote_addr, is_tls)
                 req.extensions_mut().insert(RequestContext {
                     remote_addr,
                     is_tls,
+                    trust_forwarded,
                 });
 
+                // Expose the verified client certificate (mTLS) for passTLSClientCert
+                if let Some(client_cert) = client_cert {
+                    req.extensions_mut().insert(client_cert);
+                }
+
+                // WebSocket tunnels outlive this connection; they hold the tracker
+                // so graceful drain waits for (and closes) them
+                if is_websocket_upgrade(&req) {
+                    req.extensions_mut().insert(Arc::clone(&state.connections));
+                    if let Some(slot) = slot {
+                        req.extensions_mut().insert(slot);
+                    }
+                    request.connection_upgraded();
+                }
+
                 // Load current router, services, and middlewares (supports hot reload)
                 let router = state.router.load();
                 let services = state.services.load();
@@ -226,8 +396,9 @@ impl Listener {
                 let passive_health = Arc::clone(&state.passive_health);
 
                 proxy
-                    .handle(req, remote_addr, &ep, &router, &services, &middlewares, &passive_health, is_tls, &access_log)
+                    .handle(req, remote_addr, &ep, &router, &services, &middlewares, &passive_health, is_tls, &access_log, &state.tracer)
                     .await
+                    .map(|response| request.hold_until_written(response))
             }
         });
 
@@ -238,3 +409,472 @@ impl Listener {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Duration as ConfigDuration};
+    use crate::server::Server;
+    use crate::tls::PendingChallenge;
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// An entrypoint on an ephemeral port with every option left at its default
+    fn entrypoint() -> EntryPoint {
+        EntryPoint {
+            address: ":0".to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        }
+    }
+
+    /// Config with only the `web` entrypoint
+    fn web_config() -> Config {
+        Config {
+            entry_points: [("web".to_string(), entrypoint())].into(),
+            ..Default::default()
+        }
+    }
+
+    fn timeouts(read_timeout: u64, idle_timeout: u64) -> RespondingTimeouts {
+        RespondingTimeouts {
+            read_timeout: ConfigDuration::from_millis(read_timeout),
+            idle_timeout: ConfigDuration::from_millis(idle_timeout),
+            ..Default::default()
+        }
+    }
+
+    /// Serve plain HTTP connections with the given timeouts (no routes, so every request 404s)
+    async fn serve(timeouts: RespondingTimeouts) -> SocketAddr {
+        serve_with(timeouts, None, Arc::new(SharedState::new(&web_config()))).await
+    }
+
+    /// Serve plain HTTP connections as the `web` entrypoint
+    async fn serve_with(
+        timeouts: RespondingTimeouts,
+        redirect: Option<EntryPointRedirect>,
+        state: Arc<SharedState>,
+    ) -> SocketAddr {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let redirect = redirect.map(Arc::new);
+        let proxy = Arc::new(ProxyHandler::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote_addr) = listener.accept().await.unwrap();
+                let activity = ConnectionActivity::new(&timeouts);
+                let io = TokioIo::new(TimeoutIo::new(stream, Arc::clone(&activity)));
+                let access_log = state.access_log.clone();
+                tokio::spawn(Listener::serve_connection(
+                    io,
+                    remote_addr,
+                    Arc::from("web"),
+                    Arc::clone(&state),
+                    Arc::clone(&proxy),
+                    false,
+                    false,
+                    None,
+                    access_log,
+                    activity,
+                    redirect.clone(),
+                    None,
+                    None,
+                ));
+            }
+        });
+        addr
+    }
+
+    /// Run the real accept loop for a `web` entrypoint on an ephemeral port
+    async fn serve_entrypoint(entrypoint: EntryPoint, state: Arc<SharedState>) -> SocketAddr {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let listener =
+            Listener::new("web".to_string(), entrypoint, None, None, state, Arc::new(ProxyHandler::new()))
+                .unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        tokio::spawn(async move { listener.accept_connections(tcp).await });
+        addr
+    }
+
+    /// Open a connection and send one keep-alive request on it
+    async fn send_request(addr: SocketAddr) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        stream
+    }
+
+    /// Assert `waiting` gets no response while `holder` stays open, then is served once it closes
+    async fn assert_queued_until_closed(holder: TcpStream, waiting: &mut TcpStream) {
+        let queued = tokio::time::timeout(Duration::from_millis(300), read_response(waiting)).await;
+        assert!(queued.is_err(), "connection beyond the cap was served");
+
+        drop(holder);
+        let response = tokio::time::timeout(Duration::from_secs(5), read_response(waiting))
+            .await
+            .expect("queued connection not served after a slot freed");
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+    }
+
+    /// Read one response (headers plus Content-Length body)
+    async fn read_response(stream: &mut TcpStream) -> String {
+        let mut response = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed mid-response");
+            response.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&response).to_string();
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|line| {
+                        line.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|value| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if response.len() >= header_end + 4 + content_length {
+                    return text;
+                }
+            }
+        }
+    }
+
+    /// Wait for the server to close the connection, returning any bytes it sent first
+    async fn wait_for_close(stream: &mut TcpStream) -> usize {
+        let mut buf = [0u8; 1024];
+        let mut received = 0;
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return received,
+                Ok(n) => received += n,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_cut_off_at_read_timeout() {
+        let addr = serve(timeouts(300, 10_000)).await;
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        // Slowloris: keep the request headers trickling in, never finishing them
+        let started = Instant::now();
+        tokio::spawn(async move {
+            if writer.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n").await.is_err() {
+                return;
+            }
+            for _ in 0..50 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                if writer.write_all(b"X-Slow: 1\r\n").await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut buf = [0u8; 1024];
+        let read = tokio::time::timeout(Duration::from_secs(2), reader.read(&mut buf))
+            .await
+            .expect("slow request was never cut off");
+        if let Ok(n) = read {
+            assert_eq!(n, 0, "unexpected response: {}", String::from_utf8_lossy(&buf[..n]));
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(250), "closed too early: {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_idle_keep_alive_closed_at_idle_timeout() {
+        let addr = serve(timeouts(10_000, 300)).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // The connection stays usable for keep-alive requests within the idle timeout
+        for _ in 0..2 {
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let response = read_response(&mut stream).await;
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let idle_from = Instant::now();
+        let received = tokio::time::timeout(Duration::from_secs(2), wait_for_close(&mut stream))
+            .await
+            .expect("idle connection was never closed");
+        assert_eq!(received, 0);
+        let elapsed = idle_from.elapsed();
+        assert!(elapsed >= Duration::from_millis(150), "closed too early: {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_entrypoint_redirects_to_https_except_acme_challenges() {
+        let redirect = crate::config::RedirectEntryPoint {
+            to: "websecure".to_string(),
+            scheme: "https".to_string(),
+            permanent: true,
+            priority: None,
+        };
+        let web = EntryPoint {
+            http: Some(crate::config::EntryPointHttp {
+                redirections: Some(crate::config::EntryPointRedirections { entry_point: Some(redirect) }),
+                ..Default::default()
+            }),
+            ..entrypoint()
+        };
+        let websecure = EntryPoint { address: ":8443".to_string(), ..entrypoint() };
+        let config = Config {
+            entry_points: [("web".to_string(), web), ("websecure".to_string(), websecure)].into(),
+            ..Default::default()
+        };
+        let redirect = Server::redirect_for(&config, &config.entry_points["web"]).unwrap();
+        let state = Arc::new(SharedState::new(&config));
+        state.acme_challenges.write().await.insert(
+            "token123".to_string(),
+            PendingChallenge {
+                token: "token123".to_string(),
+                key_authorization: "token123.thumbprint".to_string(),
+            },
+        );
+        let addr = serve_with(RespondingTimeouts::default(), redirect, state).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        stream
+            .write_all(b"GET /app?page=2 HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_response(&mut stream).await;
+        assert!(response.starts_with("HTTP/1.1 301"), "{}", response);
+        assert!(
+            response.contains("location: https://example.com:8443/app?page=2\r\n"),
+            "{}",
+            response
+        );
+
+        stream
+            .write_all(b"GET /.well-known/acme-challenge/token123 HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        let response = String::from_utf8_lossy(&buf[..n]);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("token123.thumbprint"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_entrypoint_max_connections_queues_excess() {
+        let state = Arc::new(SharedState::new(&web_config()));
+        let transport = crate::config::EntryPointTransport { max_connections: Some(1), ..Default::default() };
+        let capped = EntryPoint { transport: Some(transport), ..entrypoint() };
+        let addr = serve_entrypoint(capped, Arc::clone(&state)).await;
+
+        let mut first = send_request(addr).await;
+        let response = read_response(&mut first).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        let mut second = send_request(addr).await;
+        assert_queued_until_closed(first, &mut second).await;
+
+        // The freed slot now belongs to the second connection
+        let mut third = send_request(addr).await;
+        assert_queued_until_closed(second, &mut third).await;
+    }
+
+    #[tokio::test]
+    async fn test_global_max_connections_spans_entrypoints() {
+        let config = Config { max_connections: Some(1), ..web_config() };
+        let state = Arc::new(SharedState::new(&config));
+        let web = serve_entrypoint(entrypoint(), Arc::clone(&state)).await;
+        let other = serve_entrypoint(entrypoint(), Arc::clone(&state)).await;
+
+        let mut first = send_request(web).await;
+        let response = read_response(&mut first).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        assert_eq!(state.connections.active_count(), 1);
+
+        let mut second = send_request(other).await;
+        assert_queued_until_closed(first, &mut second).await;
+    }
+
+    /// Backend answering every request with `200 ok` after `delay`
+    async fn slow_backend(delay: Duration) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let mut request = Vec::new();
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    tokio::time::sleep(delay).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                        .await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// Config routing every request on `web` to `backend`
+    fn routed_config(backend: SocketAddr) -> Config {
+        let server = crate::config::Server {
+            url: format!("http://{}", backend),
+            weight: 1,
+            preserve_path: false,
+            parsed_uri: None,
+            url_arc: None,
+        };
+        let app = crate::config::LoadBalancerService {
+            servers: vec![server],
+            pass_host_header: true,
+            sticky: None,
+            health_check: None,
+            servers_transport: None,
+            response_forwarding: None,
+            web_socket: None,
+        };
+        let router = crate::config::Router {
+            entry_points: vec![],
+            rule: "PathPrefix(`/`)".to_string(),
+            rule_syntax: None,
+            service: "app".to_string(),
+            middlewares: vec![],
+            priority: 0,
+            tls: None,
+            observability: None,
+        };
+        Config {
+            http: Some(crate::config::HttpConfig {
+                routers: [("app".to_string(), router)].into(),
+                services: [(
+                    "app".to_string(),
+                    crate::config::Service { load_balancer: Some(app), ..Default::default() },
+                )]
+                .into(),
+                ..Default::default()
+            }),
+            ..web_config()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_request_body_cut_off_at_read_timeout() {
+        // The backend never answers, so only the read timeout can end the request
+        let backend = slow_backend(Duration::from_secs(30)).await;
+        let state = Arc::new(SharedState::new(&routed_config(backend)));
+        let timeouts = RespondingTimeouts { read_timeout: ConfigDuration::from_millis(300), ..Default::default() };
+        let addr = serve_with(timeouts, None, state).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // Headers arrive promptly, then the body stalls partway
+        let started = Instant::now();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 100\r\n\r\npartial")
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(2), wait_for_close(&mut stream))
+            .await
+            .expect("stalled request body was never cut off");
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(250), "closed too early: {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_ends_once_request_body_is_read() {
+        // Responding takes longer than the read timeout, which only covers the request
+        let backend = slow_backend(Duration::from_millis(500)).await;
+        let state = Arc::new(SharedState::new(&routed_config(backend)));
+        let timeouts = RespondingTimeouts { read_timeout: ConfigDuration::from_millis(200), ..Default::default() };
+        let addr = serve_with(timeouts, None, state).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\nbody")
+            .await
+            .unwrap();
+        let response = read_response(&mut stream).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_draining_node_sends_drain_response_while_in_flight_completes() {
+        let backend = slow_backend(Duration::from_millis(300)).await;
+        let state = Arc::new(SharedState::new(&routed_config(backend)));
+        let drain_response = crate::config::DrainResponse {
+            retry_after: Some(ConfigDuration::from_secs(5)),
+            location: Some("http://other.example.com/".to_string()),
+        };
+        let transport = crate::config::EntryPointTransport {
+            drain_response: Some(drain_response),
+            ..Default::default()
+        };
+        let draining = EntryPoint { transport: Some(transport), ..entrypoint() };
+        let addr = serve_entrypoint(draining, Arc::clone(&state)).await;
+
+        // In flight at the backend when the drain starts
+        let mut in_flight = send_request(addr).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        state.connections.start_drain();
+
+        // A new connection is answered with the drain response, then closed
+        let mut new = send_request(addr).await;
+        let response = read_response(&mut new).await.to_ascii_lowercase();
+        assert!(response.starts_with("http/1.1 503"), "{}", response);
+        assert!(response.contains("connection: close"), "{}", response);
+        assert!(response.contains("retry-after: 5"), "{}", response);
+        assert!(response.contains("location: http://other.example.com/"), "{}", response);
+        assert_eq!(wait_for_close(&mut new).await, 0);
+
+        // The in-flight request still completes with the backend's response
+        let response = read_response(&mut in_flight).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("ok"), "{}", response);
+
+        // The next request on that keep-alive connection is a new one
+        in_flight
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_response(&mut in_flight).await;
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        assert_eq!(wait_for_close(&mut in_flight).await, 0);
+
+        // Drain-response connections were never counted, so the drain completes
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(state.connections.active_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_draining_node_refuses_connections_without_drain_response() {
+        let backend = slow_backend(Duration::from_millis(0)).await;
+        let state = Arc::new(SharedState::new(&routed_config(backend)));
+        let addr = serve_entrypoint(entrypoint(), Arc::clone(&state)).await;
+
+        state.connections.start_drain();
+        let mut refused = send_request(addr).await;
+        let received = tokio::time::timeout(Duration::from_secs(2), wait_for_close(&mut refused))
+            .await
+            .expect("draining node kept the connection open");
+        assert_eq!(received, 0);
+    }
+}
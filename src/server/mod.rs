@@ -1,6 +1,9 @@
 //! Server lifecycle management including TCP/TLS listeners, UDP listeners, and graceful shutdown.
 
+mod drain;
 mod listener;
+mod redirect;
+mod timeouts;
 mod udp_listener;
 
 /// TCP/TLS listener for HTTP and HTTPS entrypoints.
@@ -8,40 +11,79 @@ pub use listener::Listener;
 /// UDP listener for UDP-based entrypoints.
 pub use udp_listener::UdpListener;
 
-use crate::config::{watch_config_async, Config};
+pub(crate) use timeouts::BodyReadDeadline;
+
+use redirect::EntryPointRedirect;
+
+use crate::config::{watch_config_async, Config, EntryPoint, LifeCycle, TlsOptions};
 use crate::health::{PassiveHealthChecker, PassiveHealthConfig};
+use crate::metrics::Metrics;
 use crate::middleware::{AccessLogWriter, MiddlewareRegistry};
 use crate::proxy::ProxyHandler;
 use crate::router::Router;
 use crate::service::ServiceManager;
-use crate::tls::{AcmeManager, CertificateResolver, PendingChallenge};
+use crate::store::{LocalStore, Store};
+use crate::telemetry::Tracer;
+use crate::tls::{watch_certificates, AcmeManager, CertificateResolver, PendingChallenge};
 use crate::udp::{UdpRouter, UdpServiceManager};
 use anyhow::Result;
 use arc_swap::ArcSwap;
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, watch, OwnedSemaphorePermit, RwLock, Semaphore};
 use tracing::{error, info, warn};
 
 /// Tracks active connections for graceful shutdown
 pub struct ConnectionTracker {
     active: AtomicUsize,
     draining: AtomicBool,
+    drain_signal: watch::Sender<bool>,
+    /// Server-wide connection cap shared by every HTTP entrypoint
+    limit: Option<Arc<Semaphore>>,
 }
 
 impl ConnectionTracker {
     /// Create a new tracker with zero active connections.
     pub fn new() -> Self {
+        Self::with_limit(None)
+    }
+
+    /// Create a tracker that also caps concurrent connections server-wide
+    /// (`None` or 0 = unlimited).
+    pub fn with_limit(max_connections: Option<usize>) -> Self {
         Self {
             active: AtomicUsize::new(0),
             draining: AtomicBool::new(false),
+            drain_signal: watch::Sender::new(false),
+            limit: max_connections
+                .filter(|&max| max > 0)
+                .map(|max| Arc::new(Semaphore::new(max))),
         }
     }
 
+    /// Wait until the server-wide cap has room for another connection.
+    /// Returns `None` when there is no cap.
+    pub async fn acquire_permit(&self) -> Option<OwnedSemaphorePermit> {
+        let limit = Arc::clone(self.limit.as_ref()?);
+        limit.acquire_owned().await.ok()
+    }
+
+    /// Count a connection that outlives its HTTP connection (an upgraded
+    /// WebSocket tunnel) as active until the returned guard is dropped.
+    pub fn hold(self: &Arc<Self>) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(Arc::clone(self))
+    }
+
+    /// Subscribe to the drain signal; the value turns `true` once draining starts.
+    pub fn drain_signal(&self) -> watch::Receiver<bool> {
+        self.drain_signal.subscribe()
+    }
+
     /// Increment active connection count, returns false if draining
     #[inline]
     pub fn connection_start(&self) -> bool {
@@ -67,6 +109,13 @@ impl ConnectionTracker {
     /// Start draining - reject new connections
     pub fn start_drain(&self) {
         self.draining.store(true, Ordering::Release);
+        self.drain_signal.send_replace(true);
+    }
+
+    /// Stop draining - accept new connections again
+    pub fn stop_drain(&self) {
+        self.draining.store(false, Ordering::Release);
+        self.drain_signal.send_replace(false);
     }
 
     /// Check if draining
@@ -75,6 +124,29 @@ impl ConnectionTracker {
         self.draining.load(Ordering::Acquire)
     }
 
+    /// Shut down as configured by `life_cycle`: keep accepting new connections
+    /// for `requestAcceptGraceTimeout`, then start draining and give active
+    /// connections up to `graceTimeOut` to finish.
+    pub async fn drain_gracefully(&self, life_cycle: &LifeCycle) {
+        let accept_grace = life_cycle.request_accept_grace_timeout.as_std();
+        if !accept_grace.is_zero() {
+            info!("Still accepting connections for {:?} before draining", accept_grace);
+            tokio::time::sleep(accept_grace).await;
+        }
+
+        self.start_drain();
+
+        let grace = life_cycle.grace_time_out.as_std();
+        let active = self.active_count();
+        if active > 0 {
+            info!(
+                "Waiting for {} active connections to drain (timeout: {:?})",
+                active, grace
+            );
+            self.wait_for_drain(grace).await;
+        }
+    }
+
     /// Wait for all connections to finish, with timeout
     pub async fn wait_for_drain(&self, timeout: Duration) {
         let deadline = tokio::time::Instant::now() + timeout;
@@ -106,15 +178,66 @@ impl Default for ConnectionTracker {
     }
 }
 
-/// Build a cert resolver from static `tls.certificates` entries in the config.
-/// Returns None when no static certs are configured; returns Some even on
-/// partial success so at least the successfully-loaded certs work.
+/// Keeps a held connection counted by its [`ConnectionTracker`] until dropped.
+pub struct ConnectionGuard(Arc<ConnectionTracker>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.connection_end();
+    }
+}
+
+/// A connection's place under its entrypoint's and the server-wide connection
+/// caps. Dropping it frees both and updates the entrypoint's `active_connections`
+/// gauge; upgraded WebSocket tunnels keep it alive for as long as they run.
+pub struct ConnectionSlot {
+    entrypoint: Arc<str>,
+    open: Arc<AtomicUsize>,
+    _entrypoint_permit: Option<OwnedSemaphorePermit>,
+    _global_permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ConnectionSlot {
+    /// Count a newly accepted connection on `entrypoint`, holding its permits.
+    pub(crate) fn new(
+        entrypoint: Arc<str>,
+        open: Arc<AtomicUsize>,
+        entrypoint_permit: Option<OwnedSemaphorePermit>,
+        global_permit: Option<OwnedSemaphorePermit>,
+    ) -> Self {
+        let count = open.fetch_add(1, Ordering::Relaxed) + 1;
+        Metrics::global().record_active_connections(&entrypoint, count);
+        Self {
+            entrypoint,
+            open,
+            _entrypoint_permit: entrypoint_permit,
+            _global_permit: global_permit,
+        }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let count = self.open.fetch_sub(1, Ordering::Relaxed) - 1;
+        Metrics::global().record_active_connections(&self.entrypoint, count);
+    }
+}
+
+/// Build a cert resolver from static `tls.certificates` entries (and the
+/// default store's certificate) in the config. Returns None when neither is
+/// configured; returns Some even on partial success so at least the
+/// successfully-loaded certs work.
 fn build_static_resolver(config: &Config) -> Option<Arc<CertificateResolver>> {
-    let certs = config.tls.as_ref()?.certificates.as_slice();
-    if certs.is_empty() {
+    let tls = config.tls.as_ref()?;
+    let certs = tls.certificates.as_slice();
+    let has_default = tls
+        .stores
+        .get("default")
+        .is_some_and(|store| store.default_certificate.is_some());
+    if certs.is_empty() && !has_default {
         return None;
     }
-    match CertificateResolver::from_static_certs(certs) {
+    match CertificateResolver::from_tls_config(tls) {
         Ok(resolver) => {
             info!(
                 "Loaded {} static TLS certificate(s) for SNI-based resolution",
@@ -140,64 +263,194 @@ pub struct SharedState {
     /// Passive health checker shared across all request paths.
     pub passive_health: Arc<PassiveHealthChecker>,
     /// Tracks active connections for graceful drain.
-    pub connections: ConnectionTracker,
+    pub connections: Arc<ConnectionTracker>,
     /// Pending ACME challenges for HTTP-01 validation
     pub acme_challenges: Arc<RwLock<HashMap<String, PendingChallenge>>>,
     /// Certificate resolver for SNI-based cert selection
     pub cert_resolver: Option<Arc<CertificateResolver>>,
     /// File-backed access log writer (shared across all connections).
     pub access_log: AccessLogWriter,
+    /// OTLP span exporter (no-op when tracing is not configured).
+    pub tracer: Tracer,
+    /// Store holding sticky session pins and distributed rate limits; survives hot reloads.
+    pub store: Arc<dyn Store>,
 }
 
 impl SharedState {
     /// Build shared state from config without ACME support.
     pub fn new(config: &Config) -> Self {
+        Self::with_store(config, Arc::new(LocalStore::new()))
+    }
+
+    /// Build shared state whose sticky sessions, rate limits and digest nonce
+    /// counts live in `store`, e.g. the cluster store shared by every node.
+    pub fn with_store(config: &Config, store: Arc<dyn Store>) -> Self {
         let cert_resolver = build_static_resolver(config);
+        let passive_health = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig::default()));
         Self {
             router: ArcSwap::from_pointee(Router::from_config(config)),
-            services: ArcSwap::from_pointee(ServiceManager::new(config)),
-            middlewares: ArcSwap::from_pointee(MiddlewareRegistry::from_config(config.middlewares())),
-            passive_health: Arc::new(PassiveHealthChecker::new(PassiveHealthConfig::default())),
-            connections: ConnectionTracker::new(),
+            services: ArcSwap::from_pointee(ServiceManager::with_store(
+                config,
+                Arc::clone(&passive_health),
+                Arc::clone(&store),
+            )),
+            middlewares: ArcSwap::from_pointee(MiddlewareRegistry::with_store(
+                config.middlewares(),
+                Arc::clone(&store),
+            )),
+            passive_health,
+            connections: Arc::new(ConnectionTracker::with_limit(config.max_connections)),
             acme_challenges: Arc::new(RwLock::new(HashMap::new())),
             cert_resolver,
             access_log: AccessLogWriter::new(&config.access_log),
+            tracer: Tracer::new(&config.tracing),
+            store,
         }
     }
 
-    /// Create with ACME manager
+    /// Create with ACME manager. HTTP-01 challenges are also answered from the
+    /// store the manager publishes them to, which becomes the shared store.
     pub fn with_acme(config: &Config, acme_manager: &AcmeManager) -> Self {
+        let passive_health = Arc::new(PassiveHealthChecker::new(PassiveHealthConfig::default()));
+        let store: Arc<dyn Store> = acme_manager
+            .store()
+            .unwrap_or_else(|| Arc::new(LocalStore::new()));
         Self {
             router: ArcSwap::from_pointee(Router::from_config(config)),
-            services: ArcSwap::from_pointee(ServiceManager::new(config)),
-            middlewares: ArcSwap::from_pointee(MiddlewareRegistry::from_config(config.middlewares())),
-            passive_health: Arc::new(PassiveHealthChecker::new(PassiveHealthConfig::default())),
-            connections: ConnectionTracker::new(),
+            services: ArcSwap::from_pointee(ServiceManager::with_store(
+                config,
+                Arc::clone(&passive_health),
+                Arc::clone(&store),
+            )),
+            middlewares: ArcSwap::from_pointee(MiddlewareRegistry::with_store(
+                config.middlewares(),
+                Arc::clone(&store),
+            )),
+            passive_health,
+            connections: Arc::new(ConnectionTracker::with_limit(config.max_connections)),
             acme_challenges: acme_manager.get_pending_challenges(),
             cert_resolver: Some(acme_manager.get_resolver()),
             access_log: AccessLogWriter::new(&config.access_log),
+            tracer: Tracer::new(&config.tracing),
+            store,
         }
     }
 
     /// Hot-reload router, services, and middleware from updated config.
     pub fn reload(&self, config: &Config) {
         let new_router = Router::from_config(config);
-        let new_services = ServiceManager::new(config);
-        let new_middlewares = MiddlewareRegistry::from_config(config.middlewares());
+        let new_services =
+            ServiceManager::with_store(
+                config,
+                Arc::clone(&self.passive_health),
+                Arc::clone(&self.store),
+            );
+        // Unchanged middlewares keep their instance (and rate-limit state)
+        let new_middlewares = self.middlewares.load().reload(config.middlewares());
 
         self.router.store(Arc::new(new_router));
         self.services.store(Arc::new(new_services));
         self.middlewares.store(Arc::new(new_middlewares));
+        // Pick up a rotated access log file
+        self.access_log.reopen();
 
         info!("Router, services, and middlewares reloaded");
     }
 }
 
+/// Validates and hot-swaps the running config; shared by the file watcher,
+/// [`Server::reload_config`] and the admin API.
+pub struct ConfigReloader {
+    config_path: PathBuf,
+    config: Arc<ArcSwap<Config>>,
+    state: Arc<SharedState>,
+    version: AtomicU64,
+    /// Serializes reloads so config and state are always swapped together
+    apply_lock: parking_lot::Mutex<()>,
+    /// Configs applied locally, for publishing to the rest of a cluster
+    local_changes: broadcast::Sender<Arc<Config>>,
+}
+
+impl ConfigReloader {
+    /// Create a reloader for the running `config`, which becomes version 1.
+    pub fn new(config_path: PathBuf, config: Arc<ArcSwap<Config>>, state: Arc<SharedState>) -> Self {
+        Self {
+            config_path,
+            config,
+            state,
+            version: AtomicU64::new(1),
+            apply_lock: parking_lot::Mutex::new(()),
+            local_changes: broadcast::channel(16).0,
+        }
+    }
+
+    /// The running config.
+    pub fn current(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// Router, service and middleware state the reloader swaps.
+    pub fn state(&self) -> &Arc<SharedState> {
+        &self.state
+    }
+
+    /// Version of the running config, bumped by every applied reload.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Validate `config` and make it the running config, returning its version.
+    /// On error the running config and state are left untouched.
+    pub fn apply(&self, config: Config) -> Result<u64> {
+        let (version, config) = self.swap(config)?;
+        // No subscribers outside cluster mode
+        let _ = self.local_changes.send(config);
+        Ok(version)
+    }
+
+    /// Apply a config received from the cluster. Unlike [`apply`](Self::apply),
+    /// it is not sent to [`subscribe`](Self::subscribe) receivers, so it is not
+    /// published back to the cluster.
+    pub fn apply_remote(&self, config: Config) -> Result<u64> {
+        self.swap(config).map(|(version, _)| version)
+    }
+
+    /// Subscribe to configs applied locally (file reloads, admin API).
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Config>> {
+        self.local_changes.subscribe()
+    }
+
+    fn swap(&self, config: Config) -> Result<(u64, Arc<Config>)> {
+        config.validate()?;
+
+        let _guard = self.apply_lock.lock();
+        let config = Arc::new(config);
+        self.config.store(Arc::clone(&config));
+        self.state.reload(&config);
+        self.state.services.load().start_health_checks();
+
+        let version = self.version.fetch_add(1, Ordering::AcqRel) + 1;
+        info!("Configuration version {} applied", version);
+        Ok((version, config))
+    }
+
+    /// Parse and apply a YAML config document.
+    pub fn apply_yaml(&self, yaml: &str) -> Result<u64> {
+        self.apply(Config::from_yaml(yaml)?)
+    }
+
+    /// Re-read the config file from disk and apply it.
+    pub fn reload_from_disk(&self) -> Result<u64> {
+        self.apply(Config::load(&self.config_path)?)
+    }
+}
+
 /// Top-level server that binds entrypoints, manages config hot-reload, and handles graceful shutdown.
 pub struct Server {
     config_path: PathBuf,
     config: Arc<ArcSwap<Config>>,
     state: Arc<SharedState>,
+    reloader: Arc<ConfigReloader>,
     proxy: Arc<ProxyHandler>,
     #[allow(dead_code)] // Kept alive for renewal task
     acme_manager: Option<Arc<AcmeManager>>,
@@ -211,14 +464,22 @@ impl Server {
 
     /// Create a server with an explicit config file path for hot-reload watching.
     pub fn with_path(config: Config, config_path: PathBuf) -> Self {
-        let state = Arc::new(SharedState::new(&config));
+        Self::with_store(config, config_path, Arc::new(LocalStore::new()))
+    }
+
+    /// Create a server that keeps sticky sessions, rate limits and digest nonce
+    /// counts in `store` (the cluster store in multi-node deployments).
+    pub fn with_store(config: Config, config_path: PathBuf, store: Arc<dyn Store>) -> Self {
+        let state = Arc::new(SharedState::with_store(&config, store));
         let config = Arc::new(ArcSwap::from_pointee(config));
+        let reloader = Arc::new(ConfigReloader::new(config_path.clone(), Arc::clone(&config), Arc::clone(&state)));
         let proxy = Arc::new(ProxyHandler::new());
 
         Self {
             config_path,
             config,
             state,
+            reloader,
             proxy,
             acme_manager: None,
         }
@@ -232,17 +493,50 @@ impl Server {
     ) -> Self {
         let state = Arc::new(SharedState::with_acme(&config, &acme_manager));
         let config = Arc::new(ArcSwap::from_pointee(config));
+        let reloader = Arc::new(ConfigReloader::new(config_path.clone(), Arc::clone(&config), Arc::clone(&state)));
         let proxy = Arc::new(ProxyHandler::new());
 
         Self {
             config_path,
             config,
             state,
+            reloader,
             proxy,
             acme_manager: Some(acme_manager),
         }
     }
 
+    /// Resolve the TLS options an entrypoint refers to by name, falling back to
+    /// the `default` options when it names none
+    fn tls_options_for<'a>(
+        config: &'a Config,
+        entrypoint: &EntryPoint,
+    ) -> Result<Option<&'a TlsOptions>> {
+        let Some(entrypoint_tls) = entrypoint.http.as_ref().and_then(|http| http.tls.as_ref()) else {
+            return Ok(None);
+        };
+        let options = config.tls.as_ref().map(|tls| &tls.options);
+
+        match entrypoint_tls.options.as_deref() {
+            Some(name) => options
+                .and_then(|options| options.get(name))
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("Unknown TLS options '{}'", name)),
+            None => Ok(options.and_then(|options| options.get("default"))),
+        }
+    }
+
+    /// The entrypoint's redirection to another entrypoint, if it has one
+    fn redirect_for(config: &Config, entrypoint: &EntryPoint) -> Result<Option<EntryPointRedirect>> {
+        entrypoint
+            .http
+            .as_ref()
+            .and_then(|http| http.redirections.as_ref())
+            .and_then(|redirections| redirections.entry_point.as_ref())
+            .map(|target| EntryPointRedirect::new(target, &config.entry_points))
+            .transpose()
+    }
+
     /// Start all listeners, config watcher, and block until shutdown signal.
     pub async fn run(&self) -> Result<()> {
         // Start health checks for all services
@@ -261,12 +555,23 @@ impl Server {
 
         // Start HTTP/TCP listeners for entrypoints
         for (name, entrypoint) in entrypoints.clone() {
-            let listener = Listener::new(
-                name.clone(),
-                entrypoint,
-                Arc::clone(&self.state),
-                Arc::clone(&self.proxy),
-            );
+            let listener = match Self::tls_options_for(&config, &entrypoint).and_then(|tls_options| {
+                let redirect = Self::redirect_for(&config, &entrypoint)?;
+                Listener::new(
+                    name.clone(),
+                    entrypoint,
+                    tls_options,
+                    redirect,
+                    Arc::clone(&self.state),
+                    Arc::clone(&self.proxy),
+                )
+            }) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Entrypoint '{}' not started: {:#}", name, e);
+                    continue;
+                }
+            };
 
             let listener_name = name.clone();
             let handle = tokio::spawn(async move {
@@ -308,10 +613,33 @@ impl Server {
             }
         }
 
+        // Reload static certificates renewed on disk (ACME certificates renew themselves)
+        if self.acme_manager.is_none()
+            && let (Some(resolver), Some(tls)) = (&self.state.cert_resolver, &config.tls)
+        {
+            match watch_certificates(Arc::clone(resolver), tls.clone()) {
+                Ok(handle) => handles.push(handle),
+                Err(e) => error!("Failed to watch TLS certificate files: {}", e),
+            }
+        }
+
+        // Reopen the access log on SIGHUP, e.g. from logrotate's postrotate
+        #[cfg(unix)]
+        {
+            let access_log = self.state.access_log.clone();
+            match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+                Ok(mut hangup) => handles.push(tokio::spawn(async move {
+                    while hangup.recv().await.is_some() {
+                        access_log.reopen();
+                    }
+                })),
+                Err(e) => error!("Failed to install SIGHUP handler: {}", e),
+            }
+        }
+
         // Start config watcher
         let config_path_str = self.config_path.to_string_lossy().to_string();
-        let config_arc = Arc::clone(&self.config);
-        let state_arc = Arc::clone(&self.state);
+        let reloader = Arc::clone(&self.reloader);
 
         let watcher_handle = tokio::spawn(async move {
             let (mut rx, _handle) = watch_config_async(config_path_str).await;
@@ -319,14 +647,10 @@ impl Server {
             while let Ok(new_config) = rx.recv().await {
                 info!("Hot reloading configuration...");
 
-                // Update config
-                config_arc.store(Arc::new(new_config.clone()));
-
-                // Reload router and services
-                state_arc.reload(&new_config);
-
-                // Restart health checks with new services
-                state_arc.services.load().start_health_checks();
+                // Swap config, router, services and middlewares, then restart health checks
+                if let Err(e) = reloader.apply(new_config) {
+                    error!("Config reload rejected: {:#}", e);
+                }
             }
         });
 
@@ -340,30 +664,22 @@ impl Server {
         // Stop watcher
         watcher_handle.abort();
 
-        // Start draining - reject new connections
-        self.state.connections.start_drain();
+        // Keep accepting through the accept grace window, then reject new
+        // connections and wait for active ones to finish
+        let life_cycle = shutdown_life_cycle(&self.config.load());
+        self.state.connections.drain_gracefully(&life_cycle).await;
 
         // Signal UDP listeners to shutdown
         for tx in udp_shutdown_txs {
             let _ = tx.send(()).await;
         }
 
-        // Wait for existing connections to complete (30 second timeout)
-        let drain_timeout = Duration::from_secs(30);
-        let active = self.state.connections.active_count();
-        if active > 0 {
-            info!(
-                "Waiting for {} active connections to drain (timeout: {:?})",
-                active, drain_timeout
-            );
-            self.state.connections.wait_for_drain(drain_timeout).await;
-        }
-
         // Cancel all listeners
         for handle in handles {
             handle.abort();
         }
 
+        self.state.access_log.flush();
         info!("Server stopped");
 
         Ok(())
@@ -371,12 +687,36 @@ impl Server {
 
     /// Manually reload configuration (validates before applying).
     pub fn reload_config(&self, config: Config) -> Result<()> {
-        config.validate()?;
-        self.config.store(Arc::new(config.clone()));
-        self.state.reload(&config);
+        self.reloader.apply(config)?;
         info!("Configuration reloaded manually");
         Ok(())
     }
+
+    /// Reloader for the running config, e.g. to hand to [`AdminApi`](crate::admin::AdminApi).
+    pub fn config_reloader(&self) -> Arc<ConfigReloader> {
+        Arc::clone(&self.reloader)
+    }
+}
+
+/// Shutdown timing from the entrypoints' `lifeCycle`. Draining is server-wide,
+/// so the longest grace periods win; entrypoints without a `lifeCycle` count
+/// with its defaults.
+fn shutdown_life_cycle(config: &Config) -> LifeCycle {
+    config
+        .entry_points
+        .values()
+        .map(|entrypoint| {
+            entrypoint
+                .transport
+                .as_ref()
+                .and_then(|transport| transport.life_cycle.clone())
+                .unwrap_or_default()
+        })
+        .reduce(|a, b| LifeCycle {
+            grace_time_out: a.grace_time_out.max(b.grace_time_out),
+            request_accept_grace_timeout: a.request_accept_grace_timeout.max(b.request_accept_grace_timeout),
+        })
+        .unwrap_or_default()
 }
 
 async fn shutdown_signal() {
@@ -402,3 +742,227 @@ async fn shutdown_signal() {
         _ = terminate => {},
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+entryPoints:
+  web:
+    address: ":0"
+http:
+  routers:
+    api:
+      rule: "PathPrefix(`/api`)"
+      service: api
+    web:
+      rule: "PathPrefix(`/web`)"
+      service: api
+  services:
+    api:
+      loadBalancer:
+        servers:
+          - url: "http://127.0.0.1:9"
+"#;
+
+    fn matched_route(state: &SharedState, path: &str) -> Option<String> {
+        state
+            .router
+            .load()
+            .match_request("web", Some("localhost"), path, None, Some("GET"), &hyper::HeaderMap::new(), None)
+            .map(|route| route.name.clone())
+    }
+
+    #[test]
+    fn test_invalid_reload_keeps_serving_previous_routes() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = Arc::new(Config::from_yaml(CONFIG).unwrap());
+        let state = Arc::new(SharedState::new(&config));
+        let reloader = ConfigReloader::new(
+            PathBuf::from("unused.yaml"),
+            Arc::new(ArcSwap::new(config)),
+            Arc::clone(&state),
+        );
+
+        // A typo in one rule used to drop just that route on reload
+        let typo = CONFIG.replace("PathPrefix(`/web`)", "PathPrefx(`/web`)");
+        let err = reloader.apply_yaml(&typo).unwrap_err();
+        assert!(format!("{:#}", err).contains("Router 'web' has an invalid rule"), "{err:#}");
+        assert_eq!(reloader.version(), 1);
+        assert_eq!(reloader.current().routers()["web"].rule, "PathPrefix(`/web`)");
+        assert_eq!(matched_route(&state, "/web/index.html").as_deref(), Some("web"));
+        assert_eq!(matched_route(&state, "/api/users").as_deref(), Some("api"));
+
+        let fixed = CONFIG.replace("PathPrefix(`/web`)", "PathPrefix(`/site`)");
+        assert_eq!(reloader.apply_yaml(&fixed).unwrap(), 2);
+        assert_eq!(matched_route(&state, "/web/index.html"), None);
+        assert_eq!(matched_route(&state, "/site").as_deref(), Some("web"));
+    }
+
+    /// One load balancer with two servers and cookie stickiness
+    fn sticky_config() -> Config {
+        use crate::config::{HttpConfig, LoadBalancerService, Server, Service, Sticky, StickyCookie};
+
+        let server = |url: &str| Server {
+            url: url.to_string(),
+            weight: 1,
+            preserve_path: false,
+            parsed_uri: None,
+            url_arc: None,
+        };
+        let service = Service {
+            load_balancer: Some(LoadBalancerService {
+                servers: vec![server("http://127.0.0.1:9"), server("http://127.0.0.1:10")],
+                pass_host_header: true,
+                sticky: Some(Sticky {
+                    cookie: Some(StickyCookie {
+                        name: "SERVERID".to_string(),
+                        secure: false,
+                        http_only: true,
+                        same_site: None,
+                        max_age: Some(3600),
+                        path: None,
+                    }),
+                    header: None,
+                }),
+                health_check: None,
+                servers_transport: None,
+                response_forwarding: None,
+                web_socket: None,
+            }),
+            ..Default::default()
+        };
+        Config {
+            http: Some(HttpConfig {
+                services: HashMap::from([("api".to_string(), service)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sticky_pins_shared_between_nodes_on_one_store() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = sticky_config();
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        let node_a = SharedState::with_store(&config, Arc::clone(&store));
+        let node_b = SharedState::with_store(&config, store);
+
+        let sticky_a = node_a.services.load().get_service("api").unwrap().sticky.clone().unwrap();
+        let session_id = sticky_a.create_session_sync(1).await.unwrap();
+
+        // A client pinned on node A lands on node B with its cookie
+        let sticky_b = node_b.services.load().get_service("api").unwrap().sticky.clone().unwrap();
+        let request = hyper::Request::builder()
+            .header(hyper::header::COOKIE, format!("SERVERID={}", session_id))
+            .body(())
+            .unwrap();
+        assert_eq!(sticky_b.get_sticky_server_distributed(&request).await, Some(1));
+
+        // Nodes with their own stores don't see each other's pins
+        let node_c = SharedState::new(&config);
+        let sticky_c = node_c.services.load().get_service("api").unwrap().sticky.clone().unwrap();
+        assert_eq!(sticky_c.get_sticky_server_distributed(&request).await, None);
+    }
+
+    fn life_cycle(grace_ms: u64, accept_grace_ms: u64) -> LifeCycle {
+        LifeCycle {
+            grace_time_out: crate::config::Duration::from_millis(grace_ms),
+            request_accept_grace_timeout: crate::config::Duration::from_millis(accept_grace_ms),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_grace_timeout() {
+        // A connection that never finishes holds the drain for the full grace time
+        let tracker = ConnectionTracker::new();
+        assert!(tracker.connection_start());
+        let started = tokio::time::Instant::now();
+        tracker.drain_gracefully(&life_cycle(300, 0)).await;
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(700), "{elapsed:?}");
+        assert!(tracker.is_draining());
+
+        // One that finishes early ends the drain early
+        let tracker = Arc::new(ConnectionTracker::new());
+        assert!(tracker.connection_start());
+        let finishing = Arc::clone(&tracker);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            finishing.connection_end();
+        });
+        let started = tokio::time::Instant::now();
+        tracker.drain_gracefully(&life_cycle(5_000, 0)).await;
+        assert!(started.elapsed() < Duration::from_millis(1_000), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_connections_accepted_during_accept_grace() {
+        let tracker = Arc::new(ConnectionTracker::new());
+        let draining = Arc::clone(&tracker);
+        let drain = tokio::spawn(async move { draining.drain_gracefully(&life_cycle(2_000, 300)).await });
+
+        // Shutdown has begun, but the accept grace window is still open
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!tracker.is_draining());
+        assert!(tracker.connection_start());
+
+        // Once it closes new connections are refused, while the one opened
+        // inside the window is still given time to finish
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(tracker.is_draining());
+        assert!(!tracker.connection_start());
+        assert!(!drain.is_finished());
+
+        tracker.connection_end();
+        tokio::time::timeout(Duration::from_secs(1), drain).await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_life_cycle_uses_longest_grace() {
+        let config = Config::from_yaml(
+            r#"
+entryPoints:
+  web:
+    address: ":0"
+    transport:
+      lifeCycle:
+        graceTimeOut: 5s
+        requestAcceptGraceTimeout: 2s
+  admin:
+    address: ":0"
+"#,
+        )
+        .unwrap();
+        let life_cycle = shutdown_life_cycle(&config);
+        // admin falls back to the 10s default grace time
+        assert_eq!(life_cycle.grace_time_out.as_std(), Duration::from_secs(10));
+        assert_eq!(life_cycle.request_accept_grace_timeout.as_std(), Duration::from_secs(2));
+
+        let config = Config::from_yaml("entryPoints:\n  web:\n    address: \":0\"\n").unwrap();
+        assert_eq!(shutdown_life_cycle(&config).grace_time_out.as_std(), Duration::from_secs(10));
+        assert!(shutdown_life_cycle(&config).request_accept_grace_timeout.is_zero());
+    }
+
+    #[test]
+    fn test_connection_tracker_stop_drain_accepts_again() {
+        let tracker = ConnectionTracker::new();
+        let signal = tracker.drain_signal();
+        assert!(tracker.connection_start());
+
+        tracker.start_drain();
+        assert!(tracker.is_draining());
+        assert!(*signal.borrow());
+        assert!(!tracker.connection_start());
+        assert_eq!(tracker.active_count(), 1);
+
+        tracker.stop_drain();
+        assert!(!tracker.is_draining());
+        assert!(!*signal.borrow());
+        assert!(tracker.connection_start());
+        assert_eq!(tracker.active_count(), 2);
+    }
+}
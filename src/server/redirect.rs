@@ -0,0 +1,213 @@
+//! Entrypoint-level redirections, e.g. every request on `web` to HTTPS on
+//! `websecure`.
+//!
+//! The redirect keeps the request's host, path, and query and takes its port
+//! from the target entrypoint's address. ACME HTTP-01 challenges are never
+//! redirected, and when the redirection has a `priority`, routers on the
+//! entrypoint with a higher priority take precedence over it.
+
+use crate::config::{EntryPoint, RedirectEntryPoint, RedirectSchemeConfig};
+use crate::middleware::RedirectSchemeMiddleware;
+use crate::router::Router;
+use crate::tls::ChallengeHandler;
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::HOST;
+use hyper::{Method, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// Redirection applied to every request arriving on an entrypoint
+pub(crate) struct EntryPointRedirect {
+    location: RedirectSchemeMiddleware,
+    permanent: bool,
+    priority: Option<i32>,
+}
+
+impl EntryPointRedirect {
+    /// Redirect to the `to` entrypoint. Fails when that entrypoint isn't defined.
+    pub fn new(config: &RedirectEntryPoint, entry_points: &HashMap<String, EntryPoint>) -> Result<Self> {
+        let target = entry_points
+            .get(&config.to)
+            .ok_or_else(|| anyhow::anyhow!("Unknown redirect entrypoint '{}'", config.to))?;
+        let port = target
+            .address
+            .rsplit_once(':')
+            .map(|(_, port)| port.to_string());
+
+        Ok(Self {
+            location: RedirectSchemeMiddleware::new(RedirectSchemeConfig {
+                scheme: config.scheme.clone(),
+                permanent: config.permanent,
+                port,
+            }),
+            permanent: config.permanent,
+            priority: config.priority,
+        })
+    }
+
+    /// Whether the request gets redirected rather than routed
+    pub fn applies_to<B>(
+        &self,
+        req: &Request<B>,
+        entrypoint: &str,
+        router: &Router,
+        remote_addr: SocketAddr,
+    ) -> bool {
+        if ChallengeHandler::is_challenge_request(req) {
+            return false;
+        }
+        let Some(priority) = self.priority else {
+            return true;
+        };
+
+        let host = req
+            .headers()
+            .get(HOST)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.split(':').next().unwrap_or(h))
+            .or(req.uri().host());
+        router
+            .match_request(
+                entrypoint,
+                host,
+                req.uri().path(),
+                req.uri().query(),
+                Some(req.method().as_str()),
+                req.headers(),
+                Some(remote_addr.ip()),
+            )
+            .is_none_or(|route| route.priority <= priority)
+    }
+
+    /// The redirect response. Methods other than GET and HEAD get 307/308 so
+    /// clients repeat them (with their body) at the new location.
+    pub fn response<B>(&self, req: &Request<B>) -> Response<BoxBody<Bytes, hyper::Error>> {
+        let keeps_method = !matches!(*req.method(), Method::GET | Method::HEAD);
+        let status = match (self.permanent, keeps_method) {
+            (true, false) => StatusCode::MOVED_PERMANENTLY,
+            (true, true) => StatusCode::PERMANENT_REDIRECT,
+            (false, false) => StatusCode::FOUND,
+            (false, true) => StatusCode::TEMPORARY_REDIRECT,
+        };
+
+        let mut response = self
+            .location
+            .build_redirect(req)
+            .map(|()| Empty::new().map_err(|never| match never {}).boxed());
+        *response.status_mut() = status;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, HttpConfig};
+    use hyper::header::LOCATION;
+
+    fn entry_point(address: &str) -> EntryPoint {
+        EntryPoint {
+            address: address.to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        }
+    }
+
+    /// `web` on :80 and `websecure` on `secure_address`
+    fn entry_points(secure_address: &str) -> HashMap<String, EntryPoint> {
+        HashMap::from([
+            ("web".to_string(), entry_point(":80")),
+            ("websecure".to_string(), entry_point(secure_address)),
+        ])
+    }
+
+    fn to_websecure() -> RedirectEntryPoint {
+        RedirectEntryPoint {
+            to: "websecure".to_string(),
+            scheme: "https".to_string(),
+            permanent: true,
+            priority: None,
+        }
+    }
+
+    fn router_config(rule: &str, priority: i32) -> crate::config::Router {
+        crate::config::Router {
+            entry_points: vec![],
+            rule: rule.to_string(),
+            rule_syntax: None,
+            service: "app".to_string(),
+            middlewares: vec![],
+            priority,
+            tls: None,
+            observability: None,
+        }
+    }
+
+    fn request(method: Method, uri: &str) -> Request<()> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(HOST, "example.com")
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_redirects_to_target_entrypoint_port() {
+        let redirect = EntryPointRedirect::new(&to_websecure(), &entry_points(":8443")).unwrap();
+        let router = Router::from_config(&Config::default());
+        let req = request(Method::GET, "/app/page?x=1");
+        assert!(redirect.applies_to(&req, "web", &router, "127.0.0.1:1".parse().unwrap()));
+
+        let response = redirect.response(&req);
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers()[LOCATION],
+            "https://example.com:8443/app/page?x=1"
+        );
+        let post = redirect.response(&request(Method::POST, "/form"));
+        assert_eq!(post.status(), StatusCode::PERMANENT_REDIRECT);
+    }
+
+    #[test]
+    fn test_higher_priority_router_takes_precedence() {
+        let target = RedirectEntryPoint {
+            permanent: false,
+            priority: Some(10),
+            ..to_websecure()
+        };
+        let redirect = EntryPointRedirect::new(&target, &entry_points(":443")).unwrap();
+        let router = Router::from_config(&Config {
+            http: Some(HttpConfig {
+                routers: HashMap::from([
+                    ("plain".to_string(), router_config("PathPrefix(`/plain`)", 20)),
+                    ("low".to_string(), router_config("PathPrefix(`/low`)", 5)),
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let remote = "127.0.0.1:1".parse().unwrap();
+        assert!(!redirect.applies_to(&request(Method::GET, "/plain"), "web", &router, remote));
+        assert!(redirect.applies_to(&request(Method::GET, "/low"), "web", &router, remote));
+
+        let response = redirect.response(&request(Method::GET, "/low"));
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()[LOCATION], "https://example.com/low");
+    }
+
+    #[test]
+    fn test_unknown_target_entrypoint_rejected() {
+        let target = RedirectEntryPoint {
+            to: "missing".to_string(),
+            ..to_websecure()
+        };
+        assert!(EntryPointRedirect::new(&target, &entry_points(":8443")).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+//! Responding timeouts (read/write/idle) for inbound HTTP connections.
+//!
+//! Hyper has no notion of an idle keep-alive timeout separate from its header
+//! read timeout, so the listener wraps each connection's IO in [`TimeoutIo`].
+//! The request service reports when requests start and finish through the
+//! shared [`ConnectionActivity`], and the IO fails with `TimedOut` once the
+//! deadline for the connection's current phase passes:
+//!
+//! - idle: no request in progress, for `idleTimeout` since the last one ended
+//! - reading: from a request's first byte until hyper hands it to the router,
+//!   for `readTimeout`
+//! - responding: from the request reaching the router until its response body
+//!   is fully written, for `writeTimeout`. A request body still arriving keeps
+//!   the `readTimeout` deadline in force as well, until it has been read or
+//!   dropped (see [`BodyReadDeadline`]).
+//!
+//! A zero duration disables the corresponding timeout.
+
+use crate::config::RespondingTimeouts;
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
+use hyper::Response;
+use parking_lot::Mutex;
+use std::io;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// Per-connection request activity, shared between the IO and the request service
+pub(crate) struct ConnectionActivity {
+    timeouts: ConnectionTimeouts,
+    state: Mutex<ActivityState>,
+}
+
+#[derive(Clone, Copy)]
+struct ConnectionTimeouts {
+    read: Option<Duration>,
+    write: Option<Duration>,
+    idle: Option<Duration>,
+}
+
+struct ActivityState {
+    /// Requests handed to the service whose response hasn't finished
+    in_flight: usize,
+    /// When the first byte of the next request arrived
+    request_started: Option<Instant>,
+    /// When the connection last became idle
+    idle_since: Instant,
+    /// Deadline for the in-flight requests' responses
+    write_deadline: Option<Instant>,
+    /// Read deadlines of in-flight request bodies still arriving
+    body_deadlines: Vec<Instant>,
+    /// Upgraded (WebSocket) connections are tunnels and never time out
+    upgraded: bool,
+}
+
+/// Marks a request as in flight until dropped
+pub(crate) struct RequestGuard {
+    activity: Arc<ConnectionActivity>,
+    /// When the request's read phase ends, if `readTimeout` is set
+    read_deadline: Option<Instant>,
+}
+
+/// Keeps a request's read deadline in force while its body is still being
+/// read. Carried in the request's extensions until the body is consumed, then
+/// dropped to end the read phase.
+#[derive(Clone)]
+pub(crate) struct BodyReadDeadline {
+    _pending: Arc<PendingBody>,
+}
+
+struct PendingBody {
+    activity: Arc<ConnectionActivity>,
+    deadline: Instant,
+}
+
+impl ConnectionActivity {
+    pub fn new(timeouts: &RespondingTimeouts) -> Arc<Self> {
+        let enabled = |d: crate::config::Duration| (!d.is_zero()).then(|| d.as_std());
+        Arc::new(Self {
+            timeouts: ConnectionTimeouts {
+                read: enabled(timeouts.read_timeout),
+                write: enabled(timeouts.write_timeout),
+                idle: enabled(timeouts.idle_timeout),
+            },
+            state: Mutex::new(ActivityState {
+                in_flight: 0,
+                request_started: None,
+                idle_since: Instant::now(),
+                write_deadline: None,
+                body_deadlines: Vec::new(),
+                upgraded: false,
+            }),
+        })
+    }
+
+    /// A request reached the service. Keep the guard alive until its response
+    /// body has been written (or dropped).
+    pub fn request_started(self: &Arc<Self>) -> RequestGuard {
+        let mut state = self.state.lock();
+        let read_deadline = self
+            .timeouts
+            .read
+            .map(|d| state.request_started.unwrap_or_else(Instant::now) + d);
+        state.in_flight += 1;
+        state.request_started = None;
+        if state.in_flight == 1 {
+            state.write_deadline = self.timeouts.write.map(|d| Instant::now() + d);
+        }
+        RequestGuard {
+            activity: Arc::clone(self),
+            read_deadline,
+        }
+    }
+
+    fn on_read(&self, bytes: usize) {
+        let mut state = self.state.lock();
+        if bytes > 0 && state.in_flight == 0 && state.request_started.is_none() {
+            state.request_started = Some(Instant::now());
+        }
+    }
+
+    fn on_write(&self) {
+        // Writes with no request in flight are connection-level frames (HTTP/2
+        // SETTINGS and PING acks), not progress on a request
+        let mut state = self.state.lock();
+        if state.in_flight == 0 {
+            state.request_started = None;
+            state.idle_since = Instant::now();
+        }
+    }
+
+    /// Deadline for the connection's current phase, if that phase has a timeout
+    fn deadline(&self) -> Option<Instant> {
+        let state = self.state.lock();
+        if state.upgraded {
+            None
+        } else if state.in_flight > 0 {
+            let body = state.body_deadlines.iter().min().copied();
+            match (state.write_deadline, body) {
+                (Some(write), Some(body)) => Some(write.min(body)),
+                (write, body) => write.or(body),
+            }
+        } else if let Some(started) = state.request_started {
+            self.timeouts.read.map(|d| started + d)
+        } else {
+            self.timeouts.idle.map(|d| state.idle_since + d)
+        }
+    }
+}
+
+impl RequestGuard {
+    /// The request upgrades the connection; stop enforcing timeouts on it
+    pub fn connection_upgraded(&self) {
+        self.activity.state.lock().upgraded = true;
+    }
+
+    /// The request has a body to read: keep its read deadline in force until
+    /// the returned handle is dropped
+    pub fn body_read_deadline(&self) -> Option<BodyReadDeadline> {
+        let deadline = self.read_deadline?;
+        self.activity.state.lock().body_deadlines.push(deadline);
+        Some(BodyReadDeadline {
+            _pending: Arc::new(PendingBody {
+                activity: Arc::clone(&self.activity),
+                deadline,
+            }),
+        })
+    }
+
+    /// Keep the request in flight until `response`'s body is written or dropped
+    pub fn hold_until_written(
+        self,
+        response: Response<BoxBody<Bytes, hyper::Error>>,
+    ) -> Response<BoxBody<Bytes, hyper::Error>> {
+        response.map(|body| {
+            body.map_frame(move |frame| {
+                let _ = &self;
+                frame
+            })
+            .boxed()
+        })
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        let mut state = self.activity.state.lock();
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            state.write_deadline = None;
+            state.body_deadlines.clear();
+            state.idle_since = Instant::now();
+        }
+    }
+}
+
+impl BodyReadDeadline {
+    /// Forward `body`, ending the read phase once the body is dropped by
+    /// whoever consumes it
+    pub fn track(self, body: Incoming) -> BoxBody<Bytes, hyper::Error> {
+        body.map_frame(move |frame| {
+            let _ = &self;
+            frame
+        })
+        .boxed()
+    }
+}
+
+impl Drop for PendingBody {
+    fn drop(&mut self) {
+        let mut state = self.activity.state.lock();
+        if let Some(pos) = state.body_deadlines.iter().position(|d| *d == self.deadline) {
+            state.body_deadlines.swap_remove(pos);
+        }
+    }
+}
+
+/// Connection IO that fails once the current phase's deadline passes
+pub(crate) struct TimeoutIo<I> {
+    inner: I,
+    activity: Arc<ConnectionActivity>,
+    sleep: Pin<Box<Sleep>>,
+    armed: Option<Instant>,
+}
+
+impl<I> TimeoutIo<I> {
+    pub fn new(inner: I, activity: Arc<ConnectionActivity>) -> Self {
+        Self {
+            inner,
+            activity,
+            sleep: Box::pin(tokio::time::sleep(Duration::ZERO)),
+            armed: None,
+        }
+    }
+
+    /// The inner IO is pending: fail if the deadline has passed, otherwise
+    /// arrange a wakeup for it
+    fn pending<T>(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<T>> {
+        let Some(deadline) = self.activity.deadline() else {
+            self.armed = None;
+            return Poll::Pending;
+        };
+        if self.armed != Some(deadline) {
+            self.sleep.as_mut().reset(deadline);
+            self.armed = Some(deadline);
+        }
+        match self.sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "connection timed out",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for TimeoutIo<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                this.activity.on_read(buf.filled().len() - before);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => this.pending(cx),
+        }
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for TimeoutIo<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_write(cx, buf) {
+            Poll::Ready(Ok(n)) => {
+                this.activity.on_write();
+                Poll::Ready(Ok(n))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => this.pending(cx),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_write_vectored(cx, bufs) {
+            Poll::Ready(Ok(n)) => {
+                this.activity.on_write();
+                Poll::Ready(Ok(n))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => this.pending(cx),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_flush(cx) {
+            Poll::Pending => this.pending(cx),
+            ready => ready,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -2,6 +2,7 @@ use crate::config::FailoverService;
 use crate::health::HealthStatus;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tracing::{info, warn};
 
 /// Failover service router - routes to primary service unless it's unhealthy
 pub struct FailoverServiceRouter {
@@ -11,8 +12,9 @@ pub struct FailoverServiceRouter {
     fallback: String,
     /// Whether the primary service is currently healthy
     primary_healthy: AtomicBool,
-    /// Health status for tracking (if health check is configured)
-    health_status: Option<Arc<HealthStatus>>,
+    /// Health of the primary's servers as probed by the failover's own health
+    /// check; the primary is up while any of them is healthy
+    health_statuses: Vec<Arc<HealthStatus>>,
 }
 
 impl FailoverServiceRouter {
@@ -22,26 +24,36 @@ impl FailoverServiceRouter {
             primary: config.service.clone(),
             fallback: config.fallback.clone(),
             primary_healthy: AtomicBool::new(true),
-            health_status: None,
+            health_statuses: Vec::new(),
         }
     }
 
     /// Create with external health status monitoring
     pub fn with_health_status(config: &FailoverService, health_status: Arc<HealthStatus>) -> Self {
+        Self::with_health_statuses(config, vec![health_status])
+    }
+
+    /// Create with one health status per primary server, fed by the failover's health check
+    pub fn with_health_statuses(config: &FailoverService, health_statuses: Vec<Arc<HealthStatus>>) -> Self {
         Self {
             primary: config.service.clone(),
             fallback: config.fallback.clone(),
             primary_healthy: AtomicBool::new(true),
-            health_status: Some(health_status),
+            health_statuses,
         }
     }
 
+    /// Health statuses probed by the failover's own health check
+    pub fn health_statuses(&self) -> &[Arc<HealthStatus>] {
+        &self.health_statuses
+    }
+
     /// Get the current active service name
     /// Returns primary if healthy, fallback otherwise
     pub fn active_service(&self) -> &str {
-        // If we have an external health status, use that
-        if let Some(ref status) = self.health_status {
-            if status.is_healthy() {
+        // If we have external health statuses, use those
+        if !self.health_statuses.is_empty() {
+            if self.statuses_healthy() {
                 return &self.primary;
             } else {
                 return &self.fallback;
@@ -56,6 +68,31 @@ impl FailoverServiceRouter {
         }
     }
 
+    /// Pick the service for a request given whether the primary service has a
+    /// healthy server, combined with the failover's own health check. Fails over
+    /// and back as the primary's health changes, logging each transition.
+    pub fn route(&self, primary_available: bool) -> &str {
+        let healthy = primary_available && self.statuses_healthy();
+        let was_healthy = self.primary_healthy.swap(healthy, Ordering::AcqRel);
+        match (was_healthy, healthy) {
+            (true, false) => warn!(
+                "Primary service '{}' is down, failing over to '{}'",
+                self.primary, self.fallback
+            ),
+            (false, true) => info!(
+                "Primary service '{}' recovered, failing back from '{}'",
+                self.primary, self.fallback
+            ),
+            _ => {}
+        }
+
+        if healthy { &self.primary } else { &self.fallback }
+    }
+
+    fn statuses_healthy(&self) -> bool {
+        self.health_statuses.is_empty() || self.health_statuses.iter().any(|s| s.is_healthy())
+    }
+
     /// Mark the primary service as healthy
     pub fn mark_primary_healthy(&self) {
         self.primary_healthy.store(true, Ordering::Relaxed);
@@ -68,8 +105,8 @@ impl FailoverServiceRouter {
 
     /// Check if currently using the primary service
     pub fn is_using_primary(&self) -> bool {
-        if let Some(ref status) = self.health_status {
-            return status.is_healthy();
+        if !self.health_statuses.is_empty() {
+            return self.statuses_healthy();
         }
         self.primary_healthy.load(Ordering::Relaxed)
     }
@@ -172,4 +209,41 @@ mod tests {
         health_status.mark_healthy();
         assert_eq!(router.active_service(), "primary-service");
     }
+
+    #[test]
+    fn test_route_fails_over_and_back() {
+        let config = make_config();
+        let router = FailoverServiceRouter::new(&config);
+
+        assert_eq!(router.route(true), "primary-service");
+
+        // Primary reports down: traffic moves to the fallback and stays there
+        assert_eq!(router.route(false), "fallback-service");
+        assert_eq!(router.route(false), "fallback-service");
+        assert!(!router.is_using_primary());
+
+        // Primary recovers: traffic fails back
+        assert_eq!(router.route(true), "primary-service");
+        assert!(router.is_using_primary());
+    }
+
+    #[test]
+    fn test_route_consults_failover_health_check() {
+        let config = make_config();
+        let statuses = vec![Arc::new(HealthStatus::new()), Arc::new(HealthStatus::new())];
+        let router = FailoverServiceRouter::with_health_statuses(&config, statuses.clone());
+
+        // One probed server down is not enough to fail over
+        statuses[0].mark_unhealthy();
+        assert_eq!(router.route(true), "primary-service");
+
+        statuses[1].mark_unhealthy();
+        assert_eq!(router.route(true), "fallback-service");
+
+        statuses[1].mark_healthy();
+        assert_eq!(router.route(true), "primary-service");
+
+        // The primary service's own health still counts
+        assert_eq!(router.route(false), "fallback-service");
+    }
 }
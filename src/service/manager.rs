@@ -1,9 +1,11 @@
-use crate::balancer::LoadBalancer;
-use crate::config::{Config, LoadBalancerService, Service};
-use crate::health::{HealthChecker, HealthStatus};
+use crate::balancer::{LoadBalancer, StickySessionManager};
+use crate::config::{Config, FailoverService, ForwardingTimeouts, LoadBalancerService, ServersTransport, Service};
+use crate::health::{HealthChecker, HealthStatus, PassiveHealthChecker};
+use crate::service::{FailoverServiceRouter, MirroringServiceRouter};
+use crate::store::Store;
 use dashmap::DashMap;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 
 /// Manages all configured services, their load balancers, and health statuses.
 pub struct ServiceManager {
@@ -15,16 +17,108 @@ pub struct ServiceState {
     pub config: Service,
     pub balancer: Option<LoadBalancer>,
     pub health_statuses: Vec<Arc<HealthStatus>>,
+    /// Cookie-based session affinity, when the load balancer configures `sticky.cookie`.
+    pub sticky: Option<Arc<StickySessionManager>>,
+    /// Mirror selection and body buffering, when the service is a `mirroring` service.
+    pub mirroring: Option<Arc<MirroringServiceRouter>>,
+    /// Primary/fallback selection, when the service is a `failover` service.
+    pub failover: Option<Arc<FailoverServiceRouter>>,
+    /// The load balancer's `serversTransport`, when it references one.
+    pub servers_transport: Option<Arc<BackendTransport>>,
+}
+
+/// A `serversTransport` resolved from the name a load balancer references.
+#[derive(Debug)]
+pub struct BackendTransport {
+    pub name: String,
+    pub config: ServersTransport,
+    /// The serialized settings; services with identical transports share connection pools
+    pub key: String,
+}
+
+impl ServiceState {
+    /// Backend timeouts from the servers transport, or the defaults.
+    pub fn forwarding_timeouts(&self) -> ForwardingTimeouts {
+        self.servers_transport
+            .as_ref()
+            .and_then(|transport| transport.config.forwarding_timeouts.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the server at `index` is healthy to the balancer and the active health checker.
+    pub fn is_server_healthy(&self, index: usize) -> bool {
+        let balancer_healthy = self
+            .balancer
+            .as_ref()
+            .is_some_and(|balancer| balancer.is_healthy(index));
+        let checker_healthy = self
+            .health_statuses
+            .get(index)
+            .is_none_or(|status| status.is_healthy());
+        balancer_healthy && checker_healthy
+    }
+
+    /// Returns true if the service has at least one healthy server. Services without
+    /// servers of their own (weighted, mirroring, failover) count as healthy.
+    pub fn has_healthy_server(&self) -> bool {
+        match &self.balancer {
+            Some(_) => (0..self.health_statuses.len()).any(|idx| self.is_server_healthy(idx)),
+            None => true,
+        }
+    }
 }
 
 impl ServiceManager {
     /// Build the service registry from the full proxy configuration.
     pub fn new(config: &Config) -> Self {
+        Self::build(config, None, None)
+    }
+
+    /// Build the service registry with balancers that consult a shared passive health checker.
+    pub fn with_passive_health(config: &Config, passive_health: Arc<PassiveHealthChecker>) -> Self {
+        Self::build(config, Some(passive_health), None)
+    }
+
+    /// Build the service registry with passive health and sticky sessions persisted in `store`.
+    pub fn with_store(
+        config: &Config,
+        passive_health: Arc<PassiveHealthChecker>,
+        store: Arc<dyn Store>,
+    ) -> Self {
+        Self::build(config, Some(passive_health), Some(store))
+    }
+
+    fn build(
+        config: &Config,
+        passive_health: Option<Arc<PassiveHealthChecker>>,
+        store: Option<Arc<dyn Store>>,
+    ) -> Self {
         let services = DashMap::new();
 
         for (name, service_config) in config.services() {
+            let sticky = service_config
+                .load_balancer
+                .as_ref()
+                .and_then(|lb| Self::build_sticky(name, lb, store.as_ref()));
+            let mirroring = service_config
+                .mirroring
+                .as_ref()
+                .map(|m| Arc::new(MirroringServiceRouter::new(m)));
+            let failover = service_config
+                .failover
+                .as_ref()
+                .map(|f| Arc::new(Self::build_failover(f, config)));
+            let servers_transport = service_config
+                .load_balancer
+                .as_ref()
+                .and_then(|lb| Self::servers_transport(name, lb, config));
+
             let (balancer, health_statuses, server_count) = if let Some(lb) = &service_config.load_balancer {
-                let balancer = Some(LoadBalancer::from_load_balancer(lb));
+                let mut balancer = LoadBalancer::from_load_balancer(lb);
+                if let Some(passive) = &passive_health {
+                    balancer = balancer.with_passive_health(Arc::clone(passive));
+                }
+                let balancer = Some(balancer);
                 let statuses: Vec<Arc<HealthStatus>> = lb
                     .servers
                     .iter()
@@ -54,6 +148,10 @@ impl ServiceManager {
                     config: service_config.clone(),
                     balancer,
                     health_statuses,
+                    sticky,
+                    mirroring,
+                    failover,
+                    servers_transport,
                 },
             );
 
@@ -63,6 +161,68 @@ impl ServiceManager {
         Self { services }
     }
 
+    /// Resolve the servers transport a load balancer references
+    fn servers_transport(
+        name: &str,
+        lb: &LoadBalancerService,
+        config: &Config,
+    ) -> Option<Arc<BackendTransport>> {
+        let transport_name = lb.servers_transport.as_deref()?;
+        let transport = config
+            .http
+            .as_ref()
+            .and_then(|http| http.servers_transports.get(transport_name));
+        match transport {
+            Some(transport) => Some(Arc::new(BackendTransport {
+                name: transport_name.to_string(),
+                config: transport.clone(),
+                key: serde_json::to_string(transport).unwrap_or_default(),
+            })),
+            None => {
+                warn!(
+                    "Service '{}' references unknown serversTransport '{}', using the default transport",
+                    name, transport_name
+                );
+                None
+            }
+        }
+    }
+
+    fn build_sticky(
+        name: &str,
+        lb: &LoadBalancerService,
+        store: Option<&Arc<dyn Store>>,
+    ) -> Option<Arc<StickySessionManager>> {
+        let sticky = lb.sticky.as_ref()?;
+        let servers = Arc::new(lb.servers.clone());
+        let manager = match store {
+            Some(store) => StickySessionManager::with_store(sticky, servers, name, Arc::clone(store)),
+            None => StickySessionManager::new(sticky, servers, name),
+        }?;
+        info!(
+            "Sticky sessions enabled for service '{}' (cookie '{}')",
+            name,
+            manager.cookie_name()
+        );
+        Some(Arc::new(manager))
+    }
+
+    fn build_failover(failover: &FailoverService, config: &Config) -> FailoverServiceRouter {
+        // The failover's own health check probes each of the primary's servers
+        let primary_servers = config
+            .services()
+            .get(&failover.service)
+            .and_then(|s| s.load_balancer.as_ref())
+            .map_or(0, |lb| lb.servers.len());
+        match &failover.health_check {
+            Some(_) if primary_servers > 0 => FailoverServiceRouter::with_health_statuses(
+                failover,
+                (0..primary_servers).map(|_| Arc::new(HealthStatus::new())).collect(),
+            ),
+            _ => FailoverServiceRouter::new(failover),
+        }
+    }
+
     /// Look up a service by name.
     pub fn get_service(
         &self,
@@ -71,12 +231,31 @@ impl ServiceManager {
         self.services.get(name)
     }
 
+    /// Pick the service a failover routes to: its primary while the primary has a
+    /// healthy server (and passes the failover's own health check), else its fallback.
+    pub fn route_failover<'a>(&self, failover: &'a FailoverServiceRouter) -> &'a str {
+        let primary_available = self
+            .services
+            .get(failover.primary())
+            .is_some_and(|primary| primary.has_healthy_server());
+        failover.route(primary_available)
+    }
+
     /// Spawn background health check tasks for all services with health check configs.
     pub fn start_health_checks(&self) {
+        let mut failover_checks = Vec::new();
+
         for entry in self.services.iter() {
             let service_name = entry.key().clone();
             let service = entry.value();
 
+            if let (Some(f), Some(failover)) = (&service.config.failover, &service.failover)
+                && let Some(health_config) = &f.health_check
+                && !failover.health_statuses().is_empty()
+            {
+                failover_checks.push((service_name.clone(), health_config.clone(), Arc::clone(failover)));
+            }
+
             if let Some(lb) = &service.config.load_balancer
                 && let Some(health_config) = &lb.health_check {
                     for (idx, server) in lb.servers.iter().enumerate() {
@@ -97,6 +276,32 @@ impl ServiceManager {
                     }
                 }
         }
+
+        // Failover health checks probe the primary's servers; looked up after the
+        // iteration so no shard lock is held across the lookup
+        for (service_name, health_config, failover) in failover_checks {
+            let Some(primary) = self.get_load_balancer_config(failover.primary()) else {
+                continue;
+            };
+            for (server, status) in primary.servers.iter().zip(failover.health_statuses()) {
+                let checker = HealthChecker::new(
+                    health_config.clone(),
+                    server.url.clone(),
+                    Arc::clone(status),
+                );
+
+                info!(
+                    "Starting failover health checker for service '{}' primary '{}' server '{}'",
+                    service_name,
+                    failover.primary(),
+                    server.url
+                );
+
+                tokio::spawn(async move {
+                    checker.start().await;
+                });
+            }
+        }
     }
 
     /// Get the load balancer service config for a service (if it's a load balancer)
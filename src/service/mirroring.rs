@@ -1,4 +1,10 @@
 use crate::config::MirroringService;
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, StreamExt};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, BodyStream, StreamBody};
+use hyper::body::Frame;
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
 
 /// Fast xorshift random - no allocation, no syscall
 #[inline]
@@ -27,6 +33,17 @@ pub struct MirroringServiceRouter {
     mirrors: Vec<MirrorEntry>,
     /// Maximum body size to buffer for mirroring (None = no limit)
     max_body_size: Option<i64>,
+    /// Whether mirrors receive the request body
+    mirror_body: bool,
+}
+
+/// A request body read for mirroring
+pub enum MirrorBody {
+    /// The whole body: forwarded to the main service and copied to the mirrors
+    Buffered(Bytes),
+    /// Over `maxBodySize` (or carrying trailers): the frames already read are
+    /// replayed ahead of the rest, and the request is not mirrored
+    Oversized(BoxBody<Bytes, hyper::Error>),
 }
 
 struct MirrorEntry {
@@ -50,6 +67,7 @@ impl MirroringServiceRouter {
             main_service: config.service.clone(),
             mirrors,
             max_body_size: config.max_body_size,
+            mirror_body: config.mirror_body,
         }
     }
 
@@ -86,6 +104,46 @@ impl MirroringServiceRouter {
         }
     }
 
+    /// Whether mirrors receive the request body (otherwise they get headers only)
+    pub fn mirror_body(&self) -> bool {
+        self.mirror_body
+    }
+
+    /// Read a request body so it can go to both the main service and the mirrors.
+    /// A body declared or discovered to be over `maxBodySize` is handed back as
+    /// [`MirrorBody::Oversized`] without reading any further.
+    pub async fn buffer_body(
+        &self,
+        headers: &HeaderMap,
+        mut body: BoxBody<Bytes, hyper::Error>,
+    ) -> Result<MirrorBody, hyper::Error> {
+        let declared_len = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok());
+        if declared_len.is_some_and(|len| !self.body_within_limit(len)) {
+            return Ok(MirrorBody::Oversized(body));
+        }
+
+        let mut buffered = BytesMut::new();
+        while let Some(frame) = body.frame().await {
+            let frame = frame?;
+            let within_limit = frame
+                .data_ref()
+                .is_some_and(|data| self.body_within_limit((buffered.len() + data.len()) as i64));
+            if !within_limit {
+                let replay = stream::iter([Frame::data(buffered.freeze()), frame].map(Ok));
+                let rest = StreamBody::new(replay.chain(BodyStream::new(body)));
+                return Ok(MirrorBody::Oversized(BodyExt::boxed(rest)));
+            }
+            if let Ok(data) = frame.into_data() {
+                buffered.extend_from_slice(&data);
+            }
+        }
+
+        Ok(MirrorBody::Buffered(buffered.freeze()))
+    }
+
     /// Get max body size for mirroring
     pub fn max_body_size(&self) -> Option<i64> {
         self.max_body_size
@@ -106,6 +164,8 @@ impl MirroringServiceRouter {
 mod tests {
     use super::*;
     use crate::config::MirrorRef;
+    use http_body_util::Full;
+    use hyper::header::HeaderValue;
 
     fn make_mirroring_service(main: &str, mirrors: Vec<(&str, u32)>) -> MirroringService {
         MirroringService {
@@ -249,4 +309,52 @@ mod tests {
         // No limit means any size is OK
         assert!(router.body_within_limit(i64::MAX));
     }
+
+    fn chunked_body(chunks: &[&'static str]) -> BoxBody<Bytes, hyper::Error> {
+        let frames: Vec<Result<Frame<Bytes>, hyper::Error>> = chunks
+            .iter()
+            .map(|c| Ok(Frame::data(Bytes::from_static(c.as_bytes()))))
+            .collect();
+        BodyExt::boxed(StreamBody::new(stream::iter(frames)))
+    }
+
+    async fn collect(body: BoxBody<Bytes, hyper::Error>) -> Bytes {
+        body.collect().await.unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_mirroring_buffers_body_within_limit() {
+        let mut config = make_mirroring_service("main-api", vec![("shadow", 100)]);
+        config.max_body_size = Some(16);
+        let router = MirroringServiceRouter::new(&config);
+
+        let body = chunked_body(&["hello ", "mirror"]);
+        match router.buffer_body(&HeaderMap::new(), body).await.unwrap() {
+            MirrorBody::Buffered(bytes) => assert_eq!(bytes.as_ref(), b"hello mirror"),
+            MirrorBody::Oversized(_) => panic!("body within the limit was not buffered"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirroring_oversized_body_is_replayed_in_full() {
+        let mut config = make_mirroring_service("main-api", vec![("shadow", 100)]);
+        config.max_body_size = Some(8);
+        let router = MirroringServiceRouter::new(&config);
+
+        // Discovered while reading: the main service still gets every byte
+        let body = chunked_body(&["12345", "67890", "abc"]);
+        match router.buffer_body(&HeaderMap::new(), body).await.unwrap() {
+            MirrorBody::Oversized(rest) => assert_eq!(collect(rest).await.as_ref(), b"1234567890abc"),
+            MirrorBody::Buffered(_) => panic!("oversized body was buffered"),
+        }
+
+        // Declared up front: passed through unread
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(64u64));
+        let body = Full::new(Bytes::from(vec![b'x'; 64])).map_err(|never| match never {}).boxed();
+        match router.buffer_body(&headers, body).await.unwrap() {
+            MirrorBody::Oversized(rest) => assert_eq!(collect(rest).await.len(), 64),
+            MirrorBody::Buffered(_) => panic!("oversized body was buffered"),
+        }
+    }
 }
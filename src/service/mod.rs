@@ -9,7 +9,11 @@ mod weighted;
 pub use failover::FailoverServiceRouter;
 /// Central registry of all configured services and their backends.
 pub use manager::ServiceManager;
+/// A load balancer's resolved `serversTransport` (backend TLS, pooling, timeouts).
+pub use manager::BackendTransport;
 /// Traffic mirroring router that shadows requests to secondary services.
 pub use mirroring::MirroringServiceRouter;
+/// A request body read for mirroring, or handed back when too large to mirror.
+pub use mirroring::MirrorBody;
 /// Weighted traffic splitter using smooth round-robin distribution.
 pub use weighted::WeightedServiceRouter;
@@ -1,13 +1,45 @@
-use crate::config::WeightedService;
-use std::sync::atomic::{AtomicI64, Ordering};
+use crate::balancer::{format_set_cookie, hash_key, request_cookie};
+use crate::config::{StickyCookie, WeightedService};
+use hyper::header::HeaderName;
+use hyper::Request;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+use uuid::Uuid;
 
 /// Weighted service router for traffic splitting between services
-/// Uses smooth weighted round-robin for even distribution
+///
+/// Round-robin selection takes one atomic counter increment per request and
+/// walks the weight ranges in a fixed interleaved order, so every full cycle
+/// of `total / gcd(weights)` requests splits exactly by weight, however many
+/// threads select concurrently. Weights are fixed for the router's lifetime;
+/// a reload builds a new router.
 pub struct WeightedServiceRouter {
     services: Vec<WeightedServiceEntry>,
     total_weight: i64,
-    // Current weights for smooth weighted round-robin (signed for proper subtraction)
-    current_weights: Vec<AtomicI64>,
+    /// Upper bound of each service's range in `[0, total_weight)`
+    cumulative: Vec<i64>,
+    /// Requests per round-robin cycle: `total_weight / gcd(weights)`
+    cycle: u64,
+    /// Step through the cycle, coprime with it so every position is visited once
+    stride: u64,
+    /// Round-robin selections so far
+    counter: AtomicU64,
+    /// Where sticky bucket keys come from, when `sticky` is configured
+    sticky: Option<StickyKeySource>,
+}
+
+struct StickyKeySource {
+    cookie: Option<StickyCookie>,
+    header: Option<HeaderName>,
+}
+
+/// A sticky selection: the service plus the Set-Cookie value that pins the client to it
+#[derive(Debug, PartialEq, Eq)]
+pub struct StickySelection<'a> {
+    /// Service name to route to
+    pub service: &'a str,
+    /// Set-Cookie value to add to the response, when the client is not already pinned
+    pub set_cookie: Option<String>,
 }
 
 struct WeightedServiceEntry {
@@ -27,53 +59,131 @@ impl WeightedServiceRouter {
             })
             .collect();
 
-        let total_weight: i64 = services.iter().map(|s| s.weight as i64).sum();
-        let current_weights: Vec<AtomicI64> = services
+        let cumulative: Vec<i64> = services
             .iter()
-            .map(|_| AtomicI64::new(0))
+            .scan(0i64, |sum, s| {
+                *sum += s.weight as i64;
+                Some(*sum)
+            })
             .collect();
+        let total_weight = cumulative.last().copied().unwrap_or(0);
+        let divisor = services.iter().fold(0, |g, s| gcd(g, s.weight as u64)).max(1);
+        let cycle = (total_weight as u64 / divisor).max(1);
+
+        let sticky = config.sticky.as_ref().map(|sticky| StickyKeySource {
+            cookie: sticky.cookie.clone(),
+            header: sticky.header.as_deref().and_then(|name| {
+                HeaderName::try_from(name)
+                    .inspect_err(|_| warn!("Invalid weighted sticky header '{}'", name))
+                    .ok()
+            }),
+        });
 
         Self {
             services,
             total_weight,
-            current_weights,
+            cumulative,
+            cycle,
+            stride: interleave_stride(cycle),
+            counter: AtomicU64::new(0),
+            sticky,
         }
     }
 
-    /// Select the next service using smooth weighted round-robin
-    /// Returns the service name to route to
-    pub fn next_service(&self) -> Option<&str> {
-        if self.services.is_empty() {
-            return None;
-        }
+    /// Whether sticky (keyed) assignment is configured
+    pub fn is_sticky(&self) -> bool {
+        self.sticky.is_some()
+    }
 
-        if self.services.len() == 1 {
-            return Some(&self.services[0].name);
+    /// Deterministically map a key to a service. The hash lands in one of the
+    /// cumulative weight ranges, so a key always gets the same service while
+    /// distinct keys split according to the weights.
+    pub fn service_for_key(&self, key: &[u8]) -> Option<&str> {
+        if self.total_weight <= 0 {
+            return self.services.first().map(|s| s.name.as_str());
         }
 
-        // Smooth weighted round-robin algorithm
-        // 1. Add each service's weight to its current weight
-        // 2. Select the service with highest current weight
-        // 3. Subtract total weight from the selected service's current weight
+        self.service_at((hash_key(key) % self.total_weight as u64) as i64)
+    }
 
-        let mut max_weight = i64::MIN;
-        let mut selected_idx = 0;
+    /// Service whose weight range contains `target` (in `[0, total_weight)`)
+    fn service_at(&self, target: i64) -> Option<&str> {
+        let idx = self.cumulative.partition_point(|&upper| upper <= target);
+        self.services
+            .get(idx)
+            .or(self.services.last())
+            .map(|s| s.name.as_str())
+    }
 
-        for (i, service) in self.services.iter().enumerate() {
-            // Add the static weight
-            let current = self.current_weights[i].fetch_add(service.weight as i64, Ordering::Relaxed)
-                + service.weight as i64;
+    /// Select a service for a request in sticky mode. The bucket key is the pin
+    /// cookie, else the configured header; a client with neither gets a fresh
+    /// key (a random weighted draw) pinned by cookie, or a plain random draw when
+    /// no cookie is configured. Falls back to [`next_service`](Self::next_service)
+    /// when `sticky` is not configured.
+    pub fn sticky_service<B>(&self, req: &Request<B>) -> Option<StickySelection<'_>> {
+        let Some(sticky) = &self.sticky else {
+            return self.next_service().map(|service| StickySelection {
+                service,
+                set_cookie: None,
+            });
+        };
+
+        let cookie_key = sticky
+            .cookie
+            .as_ref()
+            .and_then(|cookie| request_cookie(req, &cookie.name))
+            .filter(|key| !key.is_empty());
+        if let Some(key) = cookie_key {
+            return self.service_for_key(key.as_bytes()).map(|service| StickySelection {
+                service,
+                set_cookie: None,
+            });
+        }
 
-            if current > max_weight {
-                max_weight = current;
-                selected_idx = i;
-            }
+        let header_key = sticky
+            .header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string);
+
+        let Some(cookie) = &sticky.cookie else {
+            let service = match header_key {
+                Some(key) => self.service_for_key(key.as_bytes()),
+                None => self.random_service(),
+            };
+            return service.map(|service| StickySelection {
+                service,
+                set_cookie: None,
+            });
+        };
+
+        let key = header_key.unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        self.service_for_key(key.as_bytes()).map(|service| StickySelection {
+            service,
+            // A header value that can't be a cookie value keeps working, just unpinned
+            set_cookie: is_cookie_value(&key).then(|| format_set_cookie(cookie, &key)),
+        })
+    }
+
+    /// Select the next service by weighted round-robin
+    /// Returns the service name to route to
+    pub fn next_service(&self) -> Option<&str> {
+        if self.services.is_empty() {
+            return None;
         }
 
-        // Subtract total weight from selected
-        self.current_weights[selected_idx].fetch_sub(self.total_weight, Ordering::Relaxed);
+        if self.services.len() == 1 || self.total_weight <= 0 {
+            return Some(&self.services[0].name);
+        }
 
-        Some(&self.services[selected_idx].name)
+        // Position n of the cycle maps to slot n * stride; the slots are the
+        // weight ranges scaled down by their gcd
+        let position = self.counter.fetch_add(1, Ordering::Relaxed) % self.cycle;
+        let slot = (position as u128 * self.stride as u128 % self.cycle as u128) as i64;
+        self.service_at(slot * (self.total_weight / self.cycle as i64))
     }
 
     /// Select a service using random weighted selection
@@ -87,18 +197,11 @@ impl WeightedServiceRouter {
             return Some(&self.services[0].name);
         }
 
-        let target = (fast_random() as i64).abs() % self.total_weight;
-
-        let mut cumulative = 0i64;
-        for service in &self.services {
-            cumulative += service.weight as i64;
-            if target < cumulative {
-                return Some(&service.name);
-            }
+        if self.total_weight <= 0 {
+            return Some(&self.services[0].name);
         }
 
-        // Fallback to last service
-        self.services.last().map(|s| s.name.as_str())
+        self.service_at((fast_random() as i64).abs() % self.total_weight)
     }
 
     /// Get all service names in the weighted group
@@ -117,6 +220,27 @@ impl WeightedServiceRouter {
     }
 }
 
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// A step near `cycle / phi` that is coprime with `cycle`. Stepping by it
+/// visits every slot once per cycle while spreading consecutive picks across
+/// the weight ranges instead of draining one service's range at a time.
+fn interleave_stride(cycle: u64) -> u64 {
+    let start = ((cycle as f64) * 0.618_033_988_75).round().max(1.0) as u64;
+    (start..start + cycle)
+        .map(|stride| (stride - 1) % cycle + 1)
+        .find(|&stride| gcd(stride, cycle) == 1)
+        .unwrap_or(1)
+}
+
+/// Whether a key can be stored as a cookie value unquoted (RFC 6265 cookie-octet)
+fn is_cookie_value(key: &str) -> bool {
+    key.bytes()
+        .all(|b| matches!(b, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e))
+}
+
 /// Fast xorshift random - no allocation, no syscall
 #[inline]
 fn fast_random() -> u32 {
@@ -137,7 +261,8 @@ fn fast_random() -> u32 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::WeightedServiceRef;
+    use crate::config::{Sticky, StickyCookie, WeightedServiceRef};
+    use hyper::header::COOKIE;
     use std::collections::HashMap;
 
     fn make_weighted_service(services: Vec<(&str, u32)>) -> WeightedService {
@@ -234,6 +359,70 @@ mod tests {
         assert_eq!(c, 20);
     }
 
+    #[test]
+    fn test_weighted_router_concurrent_split() {
+        let config = make_weighted_service(vec![
+            ("service-a", 50),
+            ("service-b", 30),
+            ("service-c", 20),
+        ]);
+        let router = std::sync::Arc::new(WeightedServiceRouter::new(&config));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let router = std::sync::Arc::clone(&router);
+                std::thread::spawn(move || {
+                    let mut counts: HashMap<String, usize> = HashMap::new();
+                    for _ in 0..10_000 {
+                        *counts.entry(router.next_service().unwrap().to_string()).or_insert(0) += 1;
+                    }
+                    counts
+                })
+            })
+            .collect();
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for handle in handles {
+            for (name, count) in handle.join().unwrap() {
+                *counts.entry(name).or_insert(0) += count;
+            }
+        }
+
+        // 80,000 selections are whole cycles of 10, so the split is exact
+        // however the threads interleaved
+        assert_eq!(counts["service-a"], 40_000);
+        assert_eq!(counts["service-b"], 24_000);
+        assert_eq!(counts["service-c"], 16_000);
+    }
+
+    #[test]
+    fn test_weighted_router_interleaves_large_weights() {
+        let config = make_weighted_service(vec![("stable", 700), ("canary", 300)]);
+        let router = WeightedServiceRouter::new(&config);
+
+        let picks: Vec<&str> = (0..1000).map(|_| router.next_service().unwrap()).collect();
+        assert_eq!(picks.iter().filter(|&&name| name == "canary").count(), 300);
+
+        // Picks alternate rather than sending 700 requests to one service in a row
+        let longest_run = picks
+            .chunk_by(|a, b| a == b)
+            .map(|run| run.len())
+            .max()
+            .unwrap();
+        assert!(longest_run <= 3, "longest run {}", longest_run);
+    }
+
+    #[test]
+    fn test_weighted_router_zero_weight_never_selected() {
+        let config = make_weighted_service(vec![("service-a", 0), ("service-b", 2), ("service-c", 0)]);
+        let router = WeightedServiceRouter::new(&config);
+
+        for _ in 0..20 {
+            assert_eq!(router.next_service(), Some("service-b"));
+            assert_eq!(router.random_service(), Some("service-b"));
+        }
+    }
+
     #[test]
     fn test_weighted_router_empty() {
         let config = make_weighted_service(vec![]);
@@ -266,4 +455,122 @@ mod tests {
         assert!(a > 850 && a < 950, "Expected ~900, got {}", a);
         assert!(b > 50 && b < 150, "Expected ~100, got {}", b);
     }
+
+    fn make_sticky_service(services: Vec<(&str, u32)>, sticky: Sticky) -> WeightedService {
+        WeightedService {
+            sticky: Some(sticky),
+            ..make_weighted_service(services)
+        }
+    }
+
+    /// Sticky on the `X-User-Id` header, pinned with `cookie` when set
+    fn by_user_id(cookie: Option<StickyCookie>) -> Sticky {
+        Sticky {
+            cookie,
+            header: Some("X-User-Id".to_string()),
+        }
+    }
+
+    fn canary_cookie() -> StickyCookie {
+        StickyCookie {
+            name: "CANARY".to_string(),
+            secure: false,
+            http_only: false,
+            same_site: None,
+            max_age: None,
+            path: None,
+        }
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Request<()> {
+        let mut builder = Request::builder();
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_same_key_same_bucket() {
+        let config = make_sticky_service(vec![("stable", 90), ("canary", 10)], by_user_id(Some(canary_cookie())));
+        let router = WeightedServiceRouter::new(&config);
+        assert!(router.is_sticky());
+
+        for i in 0..200 {
+            let key = format!("user-{}", i);
+            let expected = router.service_for_key(key.as_bytes()).unwrap();
+            for _ in 0..5 {
+                assert_eq!(router.service_for_key(key.as_bytes()), Some(expected));
+            }
+
+            // The header and the pin cookie agree on the bucket
+            let by_header = router.sticky_service(&request(&[("x-user-id", &key)])).unwrap();
+            assert_eq!(by_header.service, expected);
+            let cookie = format!("CANARY={}", key);
+            let by_cookie = router.sticky_service(&request(&[("cookie", &cookie)])).unwrap();
+            assert_eq!(by_cookie.service, expected);
+            assert_eq!(by_cookie.set_cookie, None);
+        }
+    }
+
+    #[test]
+    fn test_sticky_weights_approximate_split() {
+        let config = make_sticky_service(vec![("stable", 90), ("canary", 10)], by_user_id(None));
+        let router = WeightedServiceRouter::new(&config);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for i in 0..10_000 {
+            let key = format!("user-{}", i);
+            *counts.entry(router.service_for_key(key.as_bytes()).unwrap()).or_insert(0) += 1;
+        }
+
+        let canary = *counts.get("canary").unwrap_or(&0);
+        assert!(canary > 850 && canary < 1150, "Expected ~1000, got {}", canary);
+        assert_eq!(counts.values().sum::<usize>(), 10_000);
+    }
+
+    #[test]
+    fn test_sticky_pins_with_cookie() {
+        let cookie = StickyCookie {
+            http_only: true,
+            ..canary_cookie()
+        };
+        let config = make_sticky_service(vec![("stable", 1), ("canary", 1)], by_user_id(Some(cookie)));
+        let router = WeightedServiceRouter::new(&config);
+
+        // Keyed by header: the pin cookie carries the same key
+        let selection = router.sticky_service(&request(&[("x-user-id", "alice")])).unwrap();
+        assert_eq!(selection.service, router.service_for_key(b"alice").unwrap());
+        assert_eq!(selection.set_cookie.as_deref(), Some("CANARY=alice; Path=/; HttpOnly"));
+
+        // No key: a fresh one is minted and pinned
+        let fresh = router.sticky_service(&request(&[])).unwrap();
+        let set_cookie = fresh.set_cookie.unwrap();
+        let key = set_cookie.strip_prefix("CANARY=").unwrap().split(';').next().unwrap();
+        assert_eq!(router.service_for_key(key.as_bytes()), Some(fresh.service));
+        let pinned = format!("other=1; CANARY={}", key);
+        for _ in 0..10 {
+            let again = router.sticky_service(&request(&[(COOKIE.as_str(), &pinned)])).unwrap();
+            assert_eq!(again.service, fresh.service);
+        }
+
+        // A header value that isn't cookie-safe is still routed, just not pinned
+        let spaced = router.sticky_service(&request(&[("x-user-id", "a b;c")])).unwrap();
+        assert_eq!(spaced.set_cookie, None);
+    }
+
+    #[test]
+    fn test_sticky_header_only_falls_back_to_random() {
+        let config = make_sticky_service(vec![("stable", 9), ("canary", 1)], by_user_id(None));
+        let router = WeightedServiceRouter::new(&config);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for _ in 0..1000 {
+            let selection = router.sticky_service(&request(&[])).unwrap();
+            assert_eq!(selection.set_cookie, None);
+            *counts.entry(selection.service).or_insert(0) += 1;
+        }
+        let canary = *counts.get("canary").unwrap_or(&0);
+        assert!(canary > 50 && canary < 150, "Expected ~100, got {}", canary);
+    }
 }
@@ -0,0 +1,1345 @@
+use super::{
+    ConsulConfig, HealthStatus, NodeInfo, NodeStatus, Store, StoreError, StoreResult,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, info, warn};
+
+/// How long a blocking query waits for a change before Consul answers anyway
+const BLOCKING_WAIT: Duration = Duration::from_secs(30);
+
+/// Attempts for check-and-set updates before giving up under contention
+const MAX_CAS_ATTEMPTS: usize = 16;
+
+/// TTL for health status entries (health checks should refresh)
+const HEALTH_TTL: Duration = Duration::from_secs(300);
+
+/// TTL for node entries (heartbeat should refresh)
+const NODE_TTL: Duration = Duration::from_secs(60);
+
+/// A KV entry as returned by `GET /v1/kv/<key>`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct KvEntry {
+    key: String,
+    #[serde(default)]
+    value: Option<String>,
+    modify_index: u64,
+    #[serde(default)]
+    session: Option<String>,
+}
+
+impl KvEntry {
+    fn bytes(&self) -> StoreResult<Vec<u8>> {
+        match &self.value {
+            Some(value) => STANDARD
+                .decode(value)
+                .map_err(|e| StoreError::Serialization(e.to_string())),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn text(&self) -> StoreResult<String> {
+        String::from_utf8(self.bytes()?).map_err(|e| StoreError::Serialization(e.to_string()))
+    }
+
+    fn json<T: DeserializeOwned>(&self) -> StoreResult<T> {
+        serde_json::from_slice(&self.bytes()?).map_err(|e| StoreError::Serialization(e.to_string()))
+    }
+}
+
+/// Consul KV has no per-key TTL, so values carry their own expiry and are
+/// treated as absent once it has passed
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Expiring<T> {
+    value: T,
+    expires_at: u64,
+}
+
+/// Fixed-window counter used for rate limits and circuit breaker failures
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Counter {
+    count: u64,
+    reset_at: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SessionCreated {
+    #[serde(rename = "ID")]
+    id: String,
+}
+
+/// Thin client for the parts of the Consul HTTP API the store needs
+#[derive(Clone)]
+struct ConsulClient {
+    http: reqwest::Client,
+    endpoint: String,
+    token: Option<String>,
+    datacenter: Option<String>,
+    timeout: Duration,
+}
+
+impl ConsulClient {
+    fn url(&self, path: &[&str], params: &[(&str, &str)]) -> StoreResult<reqwest::Url> {
+        let mut url = reqwest::Url::parse(&self.endpoint)
+            .map_err(|e| StoreError::Connection(format!("Invalid Consul endpoint: {}", e)))?;
+        url.path_segments_mut()
+            .map_err(|_| StoreError::Connection("Invalid Consul endpoint".to_string()))?
+            .pop_if_empty()
+            .push("v1")
+            .extend(path);
+
+        if !params.is_empty() || self.datacenter.is_some() {
+            let mut query = url.query_pairs_mut();
+            for (name, value) in params {
+                query.append_pair(name, value);
+            }
+            if let Some(dc) = &self.datacenter {
+                query.append_pair("dc", dc);
+            }
+        }
+
+        Ok(url)
+    }
+
+    fn kv_url(&self, key: &str, params: &[(&str, &str)]) -> StoreResult<reqwest::Url> {
+        let path: Vec<&str> = std::iter::once("kv").chain(key.split('/')).collect();
+        self.url(&path, params)
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        timeout: Duration,
+    ) -> StoreResult<reqwest::Response> {
+        let request = match &self.token {
+            Some(token) => request.header("X-Consul-Token", token),
+            None => request,
+        };
+
+        request.timeout(timeout).send().await.map_err(|e| {
+            if e.is_timeout() {
+                StoreError::Timeout
+            } else {
+                StoreError::Connection(e.to_string())
+            }
+        })
+    }
+
+    fn check_status(response: &reqwest::Response) -> StoreResult<()> {
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(StoreError::Connection(format!(
+                "Consul returned {} for {}",
+                response.status(),
+                response.url().path()
+            )))
+        }
+    }
+
+    async fn get(&self, key: &str) -> StoreResult<Option<KvEntry>> {
+        let response = self
+            .send(self.http.get(self.kv_url(key, &[])?), self.timeout)
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Self::check_status(&response)?;
+
+        let entries: Vec<KvEntry> = response
+            .json()
+            .await
+            .map_err(|e| StoreError::Serialization(e.to_string()))?;
+        Ok(entries.into_iter().next())
+    }
+
+    /// List every entry under `prefix`. With `index`, this is a blocking query
+    /// that returns once the prefix changes past that index or the wait elapses.
+    /// Also returns the `X-Consul-Index` to pass to the next blocking call.
+    async fn list(&self, prefix: &str, index: Option<u64>) -> StoreResult<(Vec<KvEntry>, u64)> {
+        let index_param = index.map(|index| index.to_string());
+        let wait_param = format!("{}s", BLOCKING_WAIT.as_secs());
+        let mut params = vec![("recurse", "true")];
+        let mut timeout = self.timeout;
+        if let Some(index) = &index_param {
+            params.push(("index", index));
+            params.push(("wait", &wait_param));
+            // Consul adds up to wait/16 of jitter to blocking queries
+            timeout += BLOCKING_WAIT + BLOCKING_WAIT / 16;
+        }
+
+        let response = self
+            .send(self.http.get(self.kv_url(prefix, &params)?), timeout)
+            .await?;
+        let consul_index = response
+            .headers()
+            .get("X-Consul-Index")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok((Vec::new(), consul_index));
+        }
+        Self::check_status(&response)?;
+
+        let entries = response
+            .json()
+            .await
+            .map_err(|e| StoreError::Serialization(e.to_string()))?;
+        Ok((entries, consul_index))
+    }
+
+    /// PUT a value. Consul answers `true`/`false` for `cas`, `acquire` and `release`.
+    async fn put(&self, key: &str, value: Vec<u8>, params: &[(&str, &str)]) -> StoreResult<bool> {
+        let response = self
+            .send(self.http.put(self.kv_url(key, params)?).body(value), self.timeout)
+            .await?;
+        Self::check_status(&response)?;
+
+        let body = response
+            .text()
+            .await
+            .map_err(|e| StoreError::Connection(e.to_string()))?;
+        Ok(body.trim() == "true")
+    }
+
+    async fn delete(&self, key: &str) -> StoreResult<()> {
+        let response = self
+            .send(self.http.delete(self.kv_url(key, &[])?), self.timeout)
+            .await?;
+        Self::check_status(&response)
+    }
+
+    /// Create a session whose locks are deleted when it expires or is destroyed
+    async fn session_create(&self, name: &str, ttl: Duration) -> StoreResult<String> {
+        // Consul accepts session TTLs between 10s and 24h
+        let ttl = ttl.as_secs().clamp(10, 86400);
+        let body = serde_json::json!({
+            "Name": name,
+            "TTL": format!("{}s", ttl),
+            "Behavior": "delete",
+            // Let another node take over as soon as the session is gone,
+            // matching the expiry semantics of the other stores
+            "LockDelay": "0s",
+        });
+
+        let response = self
+            .send(
+                self.http.put(self.url(&["session", "create"], &[])?).json(&body),
+                self.timeout,
+            )
+            .await?;
+        Self::check_status(&response)?;
+
+        let created: SessionCreated = response
+            .json()
+            .await
+            .map_err(|e| StoreError::Serialization(e.to_string()))?;
+        Ok(created.id)
+    }
+
+    /// Renew a session, returning false if Consul no longer knows it
+    async fn session_renew(&self, id: &str) -> StoreResult<bool> {
+        let response = self
+            .send(self.http.put(self.url(&["session", "renew", id], &[])?), self.timeout)
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        Self::check_status(&response)?;
+        Ok(true)
+    }
+}
+
+/// Distributed store using Consul KV
+///
+/// Consul has no pub/sub, so change notifications are emulated with blocking
+/// queries on the config, health and node prefixes. Leader election uses
+/// Consul sessions and KV locks.
+pub struct ConsulStore {
+    client: ConsulClient,
+    key_prefix: String,
+
+    // Pub/sub channels
+    config_tx: broadcast::Sender<()>,
+    health_tx: broadcast::Sender<(String, String, HealthStatus)>,
+    drain_tx: broadcast::Sender<String>,
+
+    /// Session held by each node ID that has attempted leadership
+    sessions: Mutex<HashMap<String, String>>,
+
+    // Background task handles
+    watch_handles: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl ConsulStore {
+    /// Create a new Consul store
+    pub async fn new(config: &ConsulConfig) -> StoreResult<Self> {
+        let http = reqwest::Client::builder()
+            .connect_timeout(config.timeout.as_std())
+            .build()
+            .map_err(|e| StoreError::Connection(e.to_string()))?;
+
+        let (config_tx, _) = broadcast::channel(16);
+        let (health_tx, _) = broadcast::channel(256);
+        let (drain_tx, _) = broadcast::channel(16);
+
+        let mut store = Self {
+            client: ConsulClient {
+                http,
+                endpoint: config.endpoint.clone(),
+                token: config.token.clone(),
+                datacenter: config.datacenter.clone(),
+                timeout: config.timeout.as_std(),
+            },
+            key_prefix: config.key_prefix.trim_matches('/').to_string(),
+            config_tx,
+            health_tx,
+            drain_tx,
+            sessions: Mutex::new(HashMap::new()),
+            watch_handles: Vec::new(),
+        };
+
+        store.start_watchers().await?;
+
+        info!(
+            "Connected to Consul at {} with prefix '{}'",
+            config.endpoint, store.key_prefix
+        );
+
+        Ok(store)
+    }
+
+    /// Start blocking-query watchers that feed the broadcast channels
+    async fn start_watchers(&mut self) -> StoreResult<()> {
+        let config_tx = self.config_tx.clone();
+        let mut config_index = None;
+        let config_handle = self
+            .watch(self.key(&["config", "version"]), move |entries, initial| {
+                let index = entries.first().map(|entry| entry.modify_index);
+                if !initial && index != config_index {
+                    debug!("Config change notification received");
+                    let _ = config_tx.send(());
+                }
+                config_index = index;
+            })
+            .await?;
+
+        let health_tx = self.health_tx.clone();
+        let health_prefix = self.key(&["health", ""]);
+        let mut last_index = 0;
+        let health_handle = self
+            .watch(health_prefix.clone(), move |entries, initial| {
+                for entry in entries {
+                    if initial || entry.modify_index <= last_index {
+                        continue;
+                    }
+                    let Some((service, server_url)) = entry
+                        .key
+                        .strip_prefix(&health_prefix)
+                        .and_then(|rest| rest.split_once('/'))
+                    else {
+                        continue;
+                    };
+                    if let Ok(status) = entry.json::<Expiring<HealthStatus>>() {
+                        let _ = health_tx.send((
+                            unescape(service),
+                            unescape(server_url),
+                            status.value,
+                        ));
+                    }
+                }
+                last_index = entries
+                    .iter()
+                    .map(|entry| entry.modify_index)
+                    .max()
+                    .unwrap_or(0)
+                    .max(last_index);
+            })
+            .await?;
+
+        let drain_tx = self.drain_tx.clone();
+        let mut statuses: HashMap<String, NodeStatus> = HashMap::new();
+        let drain_handle = self
+            .watch(self.key(&["nodes", ""]), move |entries, initial| {
+                let mut current = HashMap::new();
+                for entry in entries {
+                    let Ok(node) = entry.json::<Expiring<NodeInfo>>() else {
+                        continue;
+                    };
+                    let info = node.value;
+                    if !initial
+                        && info.status == NodeStatus::Draining
+                        && statuses.get(&info.node_id) != Some(&NodeStatus::Draining)
+                    {
+                        debug!("Drain event received for node: {}", info.node_id);
+                        let _ = drain_tx.send(info.node_id.clone());
+                    }
+                    current.insert(info.node_id, info.status);
+                }
+                statuses = current;
+            })
+            .await?;
+
+        self.watch_handles = vec![config_handle, health_handle, drain_handle];
+        Ok(())
+    }
+
+    /// Read `prefix` once, then keep calling `on_change` whenever a blocking
+    /// query reports a new index. The first call is flagged as `initial` so
+    /// handlers can seed their state without emitting events.
+    async fn watch<F>(&self, prefix: String, mut on_change: F) -> StoreResult<tokio::task::JoinHandle<()>>
+    where
+        F: FnMut(&[KvEntry], bool) + Send + 'static,
+    {
+        let (entries, mut index) = self.client.list(&prefix, None).await?;
+        on_change(&entries, true);
+
+        let client = self.client.clone();
+        Ok(tokio::spawn(async move {
+            loop {
+                match client.list(&prefix, Some(index)).await {
+                    Ok((entries, new_index)) => {
+                        if new_index != index {
+                            on_change(&entries, false);
+                        }
+                        // Consul asks clients to reset when the index goes backwards
+                        index = if new_index < index { 0 } else { new_index };
+                    }
+                    Err(e) => {
+                        warn!("Consul watch on '{}' failed: {}, retrying in 1s", prefix, e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Build a key with the prefix. Parts are escaped so that a `/` inside
+    /// a part (e.g. a server URL) doesn't create extra key segments.
+    fn key(&self, parts: &[&str]) -> String {
+        let mut key = self.key_prefix.clone();
+        for part in parts {
+            key.push('/');
+            key.push_str(&escape(part));
+        }
+        key
+    }
+
+    /// Get current time in milliseconds
+    fn current_time_millis() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    async fn put_json<T: Serialize>(&self, key: &str, value: &T) -> StoreResult<()> {
+        let json = serde_json::to_vec(value).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.client.put(key, json, &[]).await?;
+        Ok(())
+    }
+
+    async fn put_expiring<T: Serialize>(&self, key: &str, value: T, ttl: Duration) -> StoreResult<()> {
+        let expiring = Expiring {
+            value,
+            expires_at: Self::current_time_millis() + ttl.as_millis() as u64,
+        };
+        self.put_json(key, &expiring).await
+    }
+
+    /// Read an expiring value, deleting it if it has expired
+    async fn get_expiring<T: DeserializeOwned>(&self, key: &str) -> StoreResult<Option<T>> {
+        let Some(entry) = self.client.get(key).await? else {
+            return Ok(None);
+        };
+
+        let expiring: Expiring<T> = entry.json()?;
+        if expiring.expires_at <= Self::current_time_millis() {
+            self.client.delete(key).await.ok();
+            return Ok(None);
+        }
+        Ok(Some(expiring.value))
+    }
+
+    /// List the live expiring values under a prefix, keyed by the rest of the key
+    async fn list_expiring<T: DeserializeOwned>(&self, prefix: &str) -> StoreResult<Vec<(String, T)>> {
+        let (entries, _) = self.client.list(prefix, None).await?;
+        let now = Self::current_time_millis();
+
+        let mut values = Vec::new();
+        for entry in entries {
+            let Some(rest) = entry.key.strip_prefix(prefix) else {
+                continue;
+            };
+            match entry.json::<Expiring<T>>() {
+                Ok(expiring) if expiring.expires_at > now => {
+                    values.push((unescape(rest), expiring.value));
+                }
+                Ok(_) => {
+                    self.client.delete(&entry.key).await.ok();
+                }
+                Err(_) => {}
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Read-modify-write a JSON value with check-and-set, retrying when another
+    /// node wins the race. `update` returns the value to write (or `None` to
+    /// leave it unchanged) along with the result to hand back.
+    async fn cas_update<T, R>(
+        &self,
+        key: &str,
+        mut update: impl FnMut(Option<T>) -> (Option<T>, R),
+    ) -> StoreResult<R>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        for _ in 0..MAX_CAS_ATTEMPTS {
+            let (current, index) = match self.client.get(key).await? {
+                Some(entry) => (Some(entry.json()?), entry.modify_index),
+                None => (None, 0),
+            };
+
+            let (next, result) = update(current);
+            let Some(next) = next else {
+                return Ok(result);
+            };
+
+            let json = serde_json::to_vec(&next).map_err(|e| StoreError::Serialization(e.to_string()))?;
+            if self
+                .client
+                .put(key, json, &[("cas", &index.to_string())])
+                .await?
+            {
+                return Ok(result);
+            }
+        }
+
+        warn!("Gave up updating '{}' after {} conflicting writes", key, MAX_CAS_ATTEMPTS);
+        Err(StoreError::Unavailable)
+    }
+
+    /// Session for `node_id`, renewed if it exists and recreated if Consul has
+    /// invalidated it
+    async fn session_for(&self, node_id: &str, ttl: Duration) -> StoreResult<String> {
+        let mut sessions = self.sessions.lock().await;
+
+        if let Some(id) = sessions.get(node_id)
+            && self.client.session_renew(id).await?
+        {
+            return Ok(id.clone());
+        }
+
+        let id = self
+            .client
+            .session_create(&format!("trafficcop-{}", node_id), ttl)
+            .await?;
+        debug!("Created Consul session {} for node {}", id, node_id);
+        sessions.insert(node_id.to_string(), id.clone());
+        Ok(id)
+    }
+}
+
+#[async_trait]
+impl Store for ConsulStore {
+    // =========================================================================
+    // Rate Limiting (Fixed Window with CAS)
+    // =========================================================================
+
+    async fn rate_limit_check(
+        &self,
+        key: &str,
+        limit: u64,
+        window_secs: u64,
+    ) -> StoreResult<(bool, u64, u64)> {
+        let full_key = self.key(&["ratelimit", key]);
+        let window_ms = window_secs * 1000;
+
+        self.cas_update(&full_key, |current: Option<Counter>| {
+            let now = Self::current_time_millis();
+            let mut counter = current
+                .filter(|counter| now < counter.reset_at)
+                .unwrap_or(Counter {
+                    count: 0,
+                    reset_at: now + window_ms,
+                });
+
+            if counter.count >= limit {
+                return (None, (false, 0, counter.reset_at));
+            }
+
+            counter.count += 1;
+            let result = (true, limit - counter.count, counter.reset_at);
+            (Some(counter), result)
+        })
+        .await
+    }
+
+    async fn rate_limit_remaining(&self, key: &str, limit: u64) -> StoreResult<u64> {
+        let full_key = self.key(&["ratelimit", key]);
+
+        let count = match self.client.get(&full_key).await? {
+            Some(entry) => {
+                let counter: Counter = entry.json()?;
+                if counter.reset_at > Self::current_time_millis() {
+                    counter.count
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        };
+
+        Ok(limit.saturating_sub(count))
+    }
+
+    // =========================================================================
+    // Sticky Sessions
+    // =========================================================================
+
+    async fn sticky_session_get(
+        &self,
+        service: &str,
+        session_id: &str,
+    ) -> StoreResult<Option<String>> {
+        let key = self.key(&["sticky", service, session_id]);
+        self.get_expiring(&key).await
+    }
+
+    async fn sticky_session_set(
+        &self,
+        service: &str,
+        session_id: &str,
+        server_url: &str,
+        ttl: Duration,
+    ) -> StoreResult<()> {
+        let key = self.key(&["sticky", service, session_id]);
+        self.put_expiring(&key, server_url, ttl).await
+    }
+
+    async fn sticky_session_delete(&self, service: &str, session_id: &str) -> StoreResult<()> {
+        let key = self.key(&["sticky", service, session_id]);
+        self.client.delete(&key).await
+    }
+
+    // =========================================================================
+    // Health Check State
+    // =========================================================================
+
+    async fn health_get(
+        &self,
+        service: &str,
+        server_url: &str,
+    ) -> StoreResult<Option<HealthStatus>> {
+        let key = self.key(&["health", service, server_url]);
+        self.get_expiring(&key).await
+    }
+
+    async fn health_set(
+        &self,
+        service: &str,
+        server_url: &str,
+        status: &HealthStatus,
+    ) -> StoreResult<()> {
+        // Watchers pick the change up through the health prefix
+        let key = self.key(&["health", service, server_url]);
+        self.put_expiring(&key, status, HEALTH_TTL).await
+    }
+
+    async fn health_get_all(&self, service: &str) -> StoreResult<HashMap<String, HealthStatus>> {
+        let prefix = self.key(&["health", service, ""]);
+        Ok(self.list_expiring(&prefix).await?.into_iter().collect())
+    }
+
+    // =========================================================================
+    // Circuit Breaker
+    // =========================================================================
+
+    async fn circuit_breaker_fail(&self, service: &str, window_secs: u64) -> StoreResult<u64> {
+        let key = self.key(&["circuit", service]);
+        let window_ms = window_secs * 1000;
+
+        self.cas_update(&key, |current: Option<Counter>| {
+            let now = Self::current_time_millis();
+            // The window starts at the first failure, as with an expiring counter
+            let mut counter = current
+                .filter(|counter| now < counter.reset_at)
+                .unwrap_or(Counter {
+                    count: 0,
+                    reset_at: now + window_ms,
+                });
+            counter.count += 1;
+            let count = counter.count;
+            (Some(counter), count)
+        })
+        .await
+    }
+
+    async fn circuit_breaker_success(&self, service: &str) -> StoreResult<()> {
+        let key = self.key(&["circuit", service]);
+        self.client.delete(&key).await
+    }
+
+    async fn circuit_breaker_failures(&self, service: &str) -> StoreResult<u64> {
+        let key = self.key(&["circuit", service]);
+
+        match self.client.get(&key).await? {
+            Some(entry) => {
+                let counter: Counter = entry.json()?;
+                if counter.reset_at > Self::current_time_millis() {
+                    Ok(counter.count)
+                } else {
+                    Ok(0)
+                }
+            }
+            None => Ok(0),
+        }
+    }
+
+    // =========================================================================
+    // Node Registry
+    // =========================================================================
+
+    async fn node_register(&self, info: &NodeInfo) -> StoreResult<()> {
+        let key = self.key(&["nodes", &info.node_id]);
+        self.put_expiring(&key, info, NODE_TTL).await
+    }
+
+    async fn node_heartbeat(&self, node_id: &str, connections: u64) -> StoreResult<()> {
+        if let Some(mut info) = self.node_get(node_id).await? {
+            info.last_heartbeat = Self::current_time_millis();
+            info.active_connections = connections;
+            self.node_register(&info).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn node_set_status(&self, node_id: &str, status: NodeStatus) -> StoreResult<()> {
+        // Drain events are emitted by the node watcher when it sees the transition
+        if let Some(mut info) = self.node_get(node_id).await? {
+            info.status = status;
+            info.last_heartbeat = Self::current_time_millis();
+            self.node_register(&info).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn node_get(&self, node_id: &str) -> StoreResult<Option<NodeInfo>> {
+        let key = self.key(&["nodes", node_id]);
+        self.get_expiring(&key).await
+    }
+
+    async fn node_list(&self) -> StoreResult<Vec<NodeInfo>> {
+        let prefix = self.key(&["nodes", ""]);
+        let nodes = self.list_expiring(&prefix).await?;
+        Ok(nodes.into_iter().map(|(_, info)| info).collect())
+    }
+
+    async fn node_deregister(&self, node_id: &str) -> StoreResult<()> {
+        let key = self.key(&["nodes", node_id]);
+        self.client.delete(&key).await
+    }
+
+    // =========================================================================
+    // Configuration
+    // =========================================================================
+
+    async fn config_version(&self) -> StoreResult<u64> {
+        let key = self.key(&["config", "version"]);
+
+        match self.client.get(&key).await? {
+            Some(entry) => entry.json(),
+            None => Ok(0),
+        }
+    }
+
+    async fn config_get(&self) -> StoreResult<Option<String>> {
+        let key = self.key(&["config", "current"]);
+
+        match self.client.get(&key).await? {
+            Some(entry) => Ok(Some(entry.text()?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn config_set(&self, content: &str) -> StoreResult<u64> {
+        let version_key = self.key(&["config", "version"]);
+        let content_key = self.key(&["config", "current"]);
+
+        // Write content first so watchers woken by the version bump see it
+        self.client
+            .put(&content_key, content.as_bytes().to_vec(), &[])
+            .await?;
+
+        self.cas_update(&version_key, |current: Option<u64>| {
+            let version = current.unwrap_or(0) + 1;
+            (Some(version), version)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // Pub/Sub (emulated with blocking queries)
+    // =========================================================================
+
+    async fn subscribe_config_changes(&self) -> StoreResult<broadcast::Receiver<()>> {
+        Ok(self.config_tx.subscribe())
+    }
+
+    async fn subscribe_health_changes(
+        &self,
+    ) -> StoreResult<broadcast::Receiver<(String, String, HealthStatus)>> {
+        Ok(self.health_tx.subscribe())
+    }
+
+    async fn subscribe_drain_events(&self) -> StoreResult<broadcast::Receiver<String>> {
+        Ok(self.drain_tx.subscribe())
+    }
+
+    // =========================================================================
+    // ACME Challenges
+    // =========================================================================
+
+    async fn acme_challenge_set(&self, token: &str, auth: &str, ttl: Duration) -> StoreResult<()> {
+        let key = self.key(&["acme", token]);
+        self.put_expiring(&key, auth, ttl).await
+    }
+
+    async fn acme_challenge_get(&self, token: &str) -> StoreResult<Option<String>> {
+        let key = self.key(&["acme", token]);
+        self.get_expiring(&key).await
+    }
+
+    async fn acme_challenge_delete(&self, token: &str) -> StoreResult<()> {
+        let key = self.key(&["acme", token]);
+        self.client.delete(&key).await
+    }
+
+    // =========================================================================
+    // Digest Auth Nonces
+    // =========================================================================
+
+    async fn digest_nonce_use(&self, nonce: &str, nc: u64, ttl: Duration) -> StoreResult<bool> {
+        let key = self.key(&["digest-nonce", nonce]);
+        let ttl_ms = ttl.as_millis() as u64;
+
+        self.cas_update(&key, |current: Option<Expiring<u64>>| {
+            let now = Self::current_time_millis();
+            let last = current
+                .filter(|entry| entry.expires_at > now)
+                .map_or(0, |entry| entry.value);
+            if nc <= last {
+                return (None, false);
+            }
+            (Some(Expiring { value: nc, expires_at: now + ttl_ms }), true)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // Leader Election (Sessions and KV locks)
+    // =========================================================================
+
+    async fn leader_acquire(&self, task: &str, node_id: &str, ttl: Duration) -> StoreResult<bool> {
+        let key = self.key(&["leader", task]);
+        let session = self.session_for(node_id, ttl).await?;
+
+        self.client
+            .put(&key, node_id.as_bytes().to_vec(), &[("acquire", &session)])
+            .await
+    }
+
+    async fn leader_release(&self, task: &str, node_id: &str) -> StoreResult<()> {
+        let key = self.key(&["leader", task]);
+        let session = self.sessions.lock().await.get(node_id).cloned();
+
+        // Consul refuses the release unless this node's session holds the lock
+        if let Some(session) = session {
+            self.client
+                .put(&key, node_id.as_bytes().to_vec(), &[("release", &session)])
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn leader_get(&self, task: &str) -> StoreResult<Option<String>> {
+        let key = self.key(&["leader", task]);
+
+        match self.client.get(&key).await? {
+            Some(entry) if entry.session.is_some() => Ok(Some(entry.text()?)),
+            _ => Ok(None),
+        }
+    }
+
+    // =========================================================================
+    // Utilities
+    // =========================================================================
+
+    async fn health_check(&self) -> StoreResult<()> {
+        let response = self
+            .client
+            .send(
+                self.client.http.get(self.client.url(&["status", "leader"], &[])?),
+                self.client.timeout,
+            )
+            .await?;
+        ConsulClient::check_status(&response)?;
+
+        // An empty leader address means the cluster has lost quorum
+        let leader = response
+            .text()
+            .await
+            .map_err(|e| StoreError::Connection(e.to_string()))?;
+        if leader.trim().trim_matches('"').is_empty() {
+            return Err(StoreError::Unavailable);
+        }
+
+        Ok(())
+    }
+
+    fn store_type(&self) -> &'static str {
+        "consul"
+    }
+}
+
+impl Drop for ConsulStore {
+    fn drop(&mut self) {
+        for handle in &self.watch_handles {
+            handle.abort();
+        }
+    }
+}
+
+/// Escape `%` and `/` so a key part stays a single Consul key segment
+fn escape(part: &str) -> String {
+    part.replace('%', "%25").replace('/', "%2F")
+}
+
+fn unescape(part: &str) -> String {
+    part.replace("%2F", "/").replace("%25", "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Incoming;
+    use hyper::service::service_fn;
+    use hyper::{Method, Request, Response, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use std::collections::{BTreeMap, HashSet};
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::Notify;
+
+    struct MockEntry {
+        value: Vec<u8>,
+        modify_index: u64,
+        session: Option<String>,
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        index: u64,
+        kv: BTreeMap<String, MockEntry>,
+        sessions: HashSet<String>,
+        next_session: u64,
+    }
+
+    /// Just enough of the Consul KV, session and status APIs to drive the store
+    #[derive(Default)]
+    struct MockConsul {
+        state: std::sync::Mutex<MockState>,
+        changed: Notify,
+    }
+
+    impl MockConsul {
+        fn bump(&self, state: &mut MockState) -> u64 {
+            state.index += 1;
+            self.changed.notify_waiters();
+            state.index
+        }
+
+        /// Invalidate a session as if its TTL lapsed, deleting the keys it held
+        fn expire_session(&self, id: &str) {
+            let mut state = self.state.lock().unwrap();
+            state.sessions.remove(id);
+            state.kv.retain(|_, entry| entry.session.as_deref() != Some(id));
+            self.bump(&mut state);
+        }
+
+        fn session_ids(&self) -> Vec<String> {
+            self.state.lock().unwrap().sessions.iter().cloned().collect()
+        }
+
+        async fn handle(self: Arc<Self>, req: Request<Incoming>) -> Response<Full<Bytes>> {
+            let method = req.method().clone();
+            let path = percent_decode(req.uri().path());
+            let params: HashMap<String, String> = url::form_urlencoded::parse(
+                req.uri().query().unwrap_or("").as_bytes(),
+            )
+            .into_owned()
+            .collect();
+            let body = req.into_body().collect().await.unwrap().to_bytes();
+
+            if let Some(key) = path.strip_prefix("/v1/kv/") {
+                return self.handle_kv(method, key, &params, body.to_vec()).await;
+            }
+
+            let mut state = self.state.lock().unwrap();
+            match (method, path.as_str()) {
+                (Method::PUT, "/v1/session/create") => {
+                    let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                    assert_eq!(request["Behavior"], "delete");
+                    state.next_session += 1;
+                    let id = format!("session-{}", state.next_session);
+                    state.sessions.insert(id.clone());
+                    reply(StatusCode::OK, serde_json::json!({ "ID": id }).to_string())
+                }
+                (Method::PUT, path) if path.starts_with("/v1/session/renew/") => {
+                    let id = path.trim_start_matches("/v1/session/renew/");
+                    if state.sessions.contains(id) {
+                        reply(StatusCode::OK, "[]".to_string())
+                    } else {
+                        reply(StatusCode::NOT_FOUND, String::new())
+                    }
+                }
+                (Method::GET, "/v1/status/leader") => {
+                    reply(StatusCode::OK, "\"127.0.0.1:8300\"".to_string())
+                }
+                _ => reply(StatusCode::NOT_FOUND, String::new()),
+            }
+        }
+
+        async fn handle_kv(
+            &self,
+            method: Method,
+            key: &str,
+            params: &HashMap<String, String>,
+            body: Vec<u8>,
+        ) -> Response<Full<Bytes>> {
+            if method == Method::GET {
+                if let Some(wanted) = params.get("index").map(|index| index.parse::<u64>().unwrap()) {
+                    loop {
+                        let notified = self.changed.notified();
+                        if self.state.lock().unwrap().index > wanted {
+                            break;
+                        }
+                        if tokio::time::timeout(Duration::from_secs(5), notified).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+
+                let state = self.state.lock().unwrap();
+                let recurse = params.contains_key("recurse");
+                let entries: Vec<_> = state
+                    .kv
+                    .iter()
+                    .filter(|(k, _)| if recurse { k.starts_with(key) } else { *k == key })
+                    .map(|(k, entry)| {
+                        serde_json::json!({
+                            "Key": k,
+                            "Value": STANDARD.encode(&entry.value),
+                            "ModifyIndex": entry.modify_index,
+                            "Session": entry.session,
+                        })
+                    })
+                    .collect();
+
+                let status = if entries.is_empty() { StatusCode::NOT_FOUND } else { StatusCode::OK };
+                let mut response = reply(status, serde_json::to_string(&entries).unwrap());
+                response
+                    .headers_mut()
+                    .insert("X-Consul-Index", state.index.to_string().parse().unwrap());
+                return response;
+            }
+
+            let mut state = self.state.lock().unwrap();
+            if method == Method::DELETE {
+                state.kv.remove(key);
+                self.bump(&mut state);
+                return reply(StatusCode::OK, "true".to_string());
+            }
+
+            let current = state.kv.get(key);
+            let current_index = current.map_or(0, |entry| entry.modify_index);
+            let current_session = current.and_then(|entry| entry.session.clone());
+
+            let session = if let Some(cas) = params.get("cas") {
+                if cas.parse::<u64>().unwrap() != current_index {
+                    return reply(StatusCode::OK, "false".to_string());
+                }
+                current_session
+            } else if let Some(session) = params.get("acquire") {
+                let held_by_other = current_session.as_ref().is_some_and(|s| s != session);
+                if !state.sessions.contains(session) || held_by_other {
+                    return reply(StatusCode::OK, "false".to_string());
+                }
+                Some(session.clone())
+            } else if let Some(session) = params.get("release") {
+                if current_session.as_ref() != Some(session) {
+                    return reply(StatusCode::OK, "false".to_string());
+                }
+                None
+            } else {
+                current_session
+            };
+
+            let modify_index = self.bump(&mut state);
+            state.kv.insert(
+                key.to_string(),
+                MockEntry {
+                    value: body,
+                    modify_index,
+                    session,
+                },
+            );
+            reply(StatusCode::OK, "true".to_string())
+        }
+    }
+
+    fn reply(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+        Response::builder()
+            .status(status)
+            .body(Full::new(Bytes::from(body)))
+            .unwrap()
+    }
+
+    /// Consul stores keys with the URL path decoded
+    fn percent_decode(path: &str) -> String {
+        let bytes = path.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%'
+                && let Some(hex) = path.get(i + 1..i + 3)
+                && let Ok(byte) = u8::from_str_radix(hex, 16)
+            {
+                out.push(byte);
+                i += 3;
+            } else {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    async fn spawn_mock() -> (Arc<MockConsul>, String) {
+        let mock = Arc::new(MockConsul::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = mock.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mock = server.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let mock = mock.clone();
+                        async move { Ok::<_, Infallible>(mock.handle(req).await) }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        (mock, endpoint)
+    }
+
+    async fn store(endpoint: &str) -> ConsulStore {
+        ConsulStore::new(&ConsulConfig {
+            endpoint: endpoint.to_string(),
+            token: None,
+            datacenter: None,
+            key_prefix: "trafficcop".to_string(),
+            timeout: crate::config::Duration::from_secs(5),
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn recv<T: Clone>(rx: &mut broadcast::Receiver<T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for event")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_leader_lock_contention() {
+        let (_mock, endpoint) = spawn_mock().await;
+        let node_a = store(&endpoint).await;
+        let node_b = store(&endpoint).await;
+        let ttl = Duration::from_secs(15);
+
+        assert!(node_a.leader_acquire("health_check", "node-a", ttl).await.unwrap());
+        assert!(!node_b.leader_acquire("health_check", "node-b", ttl).await.unwrap());
+        assert_eq!(
+            node_b.leader_get("health_check").await.unwrap().as_deref(),
+            Some("node-a")
+        );
+
+        // Re-acquiring renews the session and keeps the lock
+        assert!(node_a.leader_acquire("health_check", "node-a", ttl).await.unwrap());
+
+        // Releasing a lock held by someone else does nothing
+        node_b.leader_release("health_check", "node-b").await.unwrap();
+        assert_eq!(
+            node_a.leader_get("health_check").await.unwrap().as_deref(),
+            Some("node-a")
+        );
+
+        node_a.leader_release("health_check", "node-a").await.unwrap();
+        assert_eq!(node_a.leader_get("health_check").await.unwrap(), None);
+
+        assert!(node_b.leader_acquire("health_check", "node-b", ttl).await.unwrap());
+        assert!(!node_a.leader_acquire("health_check", "node-a", ttl).await.unwrap());
+        assert_eq!(
+            node_a.leader_get("health_check").await.unwrap().as_deref(),
+            Some("node-b")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_leader_failover_after_session_expiry() {
+        let (mock, endpoint) = spawn_mock().await;
+        let node_a = store(&endpoint).await;
+        let node_b = store(&endpoint).await;
+        let ttl = Duration::from_secs(15);
+
+        assert!(node_a.leader_acquire("health_check", "node-a", ttl).await.unwrap());
+        for id in mock.session_ids() {
+            mock.expire_session(&id);
+        }
+
+        assert_eq!(node_b.leader_get("health_check").await.unwrap(), None);
+        assert!(node_b.leader_acquire("health_check", "node-b", ttl).await.unwrap());
+
+        // node-a gets a fresh session but can't take the lock back
+        assert!(!node_a.leader_acquire("health_check", "node-a", ttl).await.unwrap());
+        assert_eq!(mock.session_ids().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_config_changes_are_broadcast() {
+        let (_mock, endpoint) = spawn_mock().await;
+        let writer = store(&endpoint).await;
+        let reader = store(&endpoint).await;
+        let mut rx = reader.subscribe_config_changes().await.unwrap();
+
+        assert_eq!(reader.config_version().await.unwrap(), 0);
+        assert_eq!(writer.config_set("http: {}").await.unwrap(), 1);
+        recv(&mut rx).await;
+
+        assert_eq!(reader.config_version().await.unwrap(), 1);
+        assert_eq!(reader.config_get().await.unwrap().as_deref(), Some("http: {}"));
+        assert_eq!(writer.config_set("tcp: {}").await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_health_and_drain_events() {
+        let (_mock, endpoint) = spawn_mock().await;
+        let writer = store(&endpoint).await;
+        let reader = store(&endpoint).await;
+        let mut health_rx = reader.subscribe_health_changes().await.unwrap();
+        let mut drain_rx = reader.subscribe_drain_events().await.unwrap();
+
+        let status = HealthStatus {
+            healthy: false,
+            consecutive_failures: 3,
+            ..Default::default()
+        };
+        writer
+            .health_set("api", "http://10.0.0.1:8080", &status)
+            .await
+            .unwrap();
+
+        let (service, server_url, received) = recv(&mut health_rx).await;
+        assert_eq!((service.as_str(), server_url.as_str()), ("api", "http://10.0.0.1:8080"));
+        assert!(!received.healthy);
+
+        let all = reader.health_get_all("api").await.unwrap();
+        assert_eq!(all["http://10.0.0.1:8080"].consecutive_failures, 3);
+
+        let info = NodeInfo {
+            node_id: "node-a".to_string(),
+            address: "10.0.0.1:8080".to_string(),
+            status: NodeStatus::Active,
+            active_connections: 0,
+            last_heartbeat: 0,
+            started_at: 0,
+            version: "test".to_string(),
+        };
+        writer.node_register(&info).await.unwrap();
+        writer.node_set_status("node-a", NodeStatus::Draining).await.unwrap();
+
+        assert_eq!(recv(&mut drain_rx).await, "node-a");
+        assert_eq!(reader.node_list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_counters_and_expiring_values() {
+        let (_mock, endpoint) = spawn_mock().await;
+        let store = store(&endpoint).await;
+
+        let (allowed, remaining, _) = store.rate_limit_check("1.2.3.4", 2, 60).await.unwrap();
+        assert!(allowed);
+        assert_eq!(remaining, 1);
+        assert!(store.rate_limit_check("1.2.3.4", 2, 60).await.unwrap().0);
+        assert!(!store.rate_limit_check("1.2.3.4", 2, 60).await.unwrap().0);
+        assert_eq!(store.rate_limit_remaining("1.2.3.4", 2).await.unwrap(), 0);
+
+        assert_eq!(store.circuit_breaker_fail("api", 60).await.unwrap(), 1);
+        assert_eq!(store.circuit_breaker_fail("api", 60).await.unwrap(), 2);
+        store.circuit_breaker_success("api").await.unwrap();
+        assert_eq!(store.circuit_breaker_failures("api").await.unwrap(), 0);
+
+        store
+            .sticky_session_set("api", "abc", "http://10.0.0.2", Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.sticky_session_get("api", "abc").await.unwrap().as_deref(),
+            Some("http://10.0.0.2")
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.sticky_session_get("api", "abc").await.unwrap(), None);
+
+        let ttl = Duration::from_secs(60);
+        assert!(store.digest_nonce_use("nonce/1", 1, ttl).await.unwrap());
+        assert!(store.digest_nonce_use("nonce/1", 2, ttl).await.unwrap());
+        assert!(!store.digest_nonce_use("nonce/1", 2, ttl).await.unwrap());
+
+        store.health_check().await.unwrap();
+        assert_eq!(store.store_type(), "consul");
+    }
+
+    #[test]
+    fn test_key_escaping() {
+        assert_eq!(escape("http://a/b%20"), "http:%2F%2Fa%2Fb%2520");
+        assert_eq!(unescape(&escape("http://a/b%20")), "http://a/b%20");
+    }
+}
@@ -34,6 +34,9 @@ pub struct LocalStore {
     // ACME challenges
     acme_challenges: DashMap<String, AcmeEntry>,
 
+    // Digest auth nonce counts
+    digest_nonces: DashMap<String, NonceEntry>,
+
     // Leader state (in local mode, this node is always leader)
     leaders: DashMap<String, LeaderEntry>,
 
@@ -63,6 +66,11 @@ struct AcmeEntry {
     expires_at: Instant,
 }
 
+struct NonceEntry {
+    nc: u64,
+    expires_at: Instant,
+}
+
 struct LeaderEntry {
     node_id: String,
     expires_at: Instant,
@@ -84,6 +92,7 @@ impl LocalStore {
             config_content: RwLock::new(None),
             config_version: AtomicU64::new(0),
             acme_challenges: DashMap::new(),
+            digest_nonces: DashMap::new(),
             leaders: DashMap::new(),
             config_tx,
             health_tx,
@@ -118,6 +127,10 @@ impl LocalStore {
         self.acme_challenges
             .retain(|_, entry| entry.expires_at > now);
 
+        // Clean digest auth nonces
+        self.digest_nonces
+            .retain(|_, entry| entry.expires_at > now);
+
         // Clean expired leaders
         self.leaders.retain(|_, entry| entry.expires_at > now);
     }
@@ -144,7 +157,9 @@ impl Store for LocalStore {
         let now = Instant::now();
         let window_duration = Duration::from_secs(window_secs);
 
-        let entry = self
+        // The entry guard holds the shard's write lock for the whole check, so
+        // concurrent callers can't both observe an expired window and double-reset it
+        let mut entry = self
             .rate_limits
             .entry(key.to_string())
             .or_insert_with(|| RateLimitEntry {
@@ -152,15 +167,12 @@ impl Store for LocalStore {
                 window_start: now,
             });
 
-        // Check if we're in a new window
-        let elapsed = now.duration_since(entry.window_start);
+        // Start a fresh window once the current one has elapsed
+        let mut elapsed = now.duration_since(entry.window_start);
         if elapsed >= window_duration {
-            // Reset for new window
-            entry.count.store(1, Ordering::Relaxed);
-            // Note: Can't update window_start through DashMap entry
-            // This is a limitation of local store, but works for single-threaded access
-            let reset_time = Self::current_time_millis() + (window_secs * 1000);
-            return Ok((true, limit.saturating_sub(1), reset_time));
+            entry.window_start = now;
+            entry.count.store(0, Ordering::Relaxed);
+            elapsed = Duration::ZERO;
         }
 
         let current = entry.count.fetch_add(1, Ordering::Relaxed);
@@ -417,6 +429,29 @@ impl Store for LocalStore {
         Ok(())
     }
 
+    // =========================================================================
+    // Digest Auth Nonces
+    // =========================================================================
+
+    async fn digest_nonce_use(&self, nonce: &str, nc: u64, ttl: Duration) -> StoreResult<bool> {
+        let now = Instant::now();
+        let mut entry = self
+            .digest_nonces
+            .entry(nonce.to_string())
+            .or_insert(NonceEntry { nc: 0, expires_at: now + ttl });
+
+        if entry.expires_at <= now {
+            entry.nc = 0;
+        }
+        if nc <= entry.nc {
+            return Ok(false);
+        }
+
+        entry.nc = nc;
+        entry.expires_at = now + ttl;
+        Ok(true)
+    }
+
     // =========================================================================
     // Leader Election
     // =========================================================================
@@ -496,6 +531,25 @@ mod tests {
         assert_eq!(remaining, 0);
     }
 
+    #[tokio::test]
+    async fn test_rate_limit_window_resets() {
+        let store = LocalStore::new();
+
+        for round in 0..2 {
+            if round > 0 {
+                // Wait past the window; this round must get a full fresh batch
+                tokio::time::sleep(Duration::from_millis(1100)).await;
+            }
+            for i in 0..3 {
+                let (allowed, remaining, _) = store.rate_limit_check("reset_ip", 3, 1).await.unwrap();
+                assert!(allowed, "round {round} request {i} should be allowed");
+                assert_eq!(remaining, 2 - i);
+            }
+            let (allowed, _, _) = store.rate_limit_check("reset_ip", 3, 1).await.unwrap();
+            assert!(!allowed, "round {round} should be limited after 3 requests");
+        }
+    }
+
     #[tokio::test]
     async fn test_sticky_session() {
         let store = LocalStore::new();
@@ -567,4 +621,22 @@ mod tests {
             .unwrap();
         assert!(acquired);
     }
+
+    #[tokio::test]
+    async fn test_digest_nonce_counts() {
+        let store = LocalStore::new();
+        let ttl = Duration::from_millis(50);
+
+        assert!(store.digest_nonce_use("n1", 1, ttl).await.unwrap());
+        assert!(store.digest_nonce_use("n1", 3, ttl).await.unwrap());
+        // Repeated or lower counts are replays
+        assert!(!store.digest_nonce_use("n1", 3, ttl).await.unwrap());
+        assert!(!store.digest_nonce_use("n1", 2, ttl).await.unwrap());
+        // Counts are tracked per nonce
+        assert!(store.digest_nonce_use("n2", 1, ttl).await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        store.cleanup();
+        assert!(store.digest_nonces.is_empty());
+    }
 }
@@ -1,8 +1,11 @@
 //! Distributed and local state storage for rate limiting, sessions, health, and cluster coordination.
 
+mod consul;
 mod local;
 mod valkey;
 
+/// Consul KV-backed store for distributed multi-node deployments.
+pub use consul::ConsulStore;
 /// In-memory store for single-node deployments.
 pub use local::LocalStore;
 /// Valkey/Redis-backed store for distributed multi-node deployments.
@@ -227,6 +230,15 @@ pub trait Store: Send + Sync {
     /// Delete an ACME challenge
     async fn acme_challenge_delete(&self, token: &str) -> StoreResult<()>;
 
+    // =========================================================================
+    // Digest Auth Nonces
+    // =========================================================================
+
+    /// Record nonce count `nc` for a digest auth nonce, remembered for `ttl`.
+    /// Returns false when `nc` isn't above every count already recorded for
+    /// the nonce, i.e. the request is a replay.
+    async fn digest_nonce_use(&self, nonce: &str, nc: u64, ttl: Duration) -> StoreResult<bool>;
+
     // =========================================================================
     // Leader Election (for health check coordination)
     // =========================================================================
@@ -260,6 +272,10 @@ pub async fn create_store(config: &StoreConfig) -> StoreResult<Arc<dyn Store>> {
             let store = ValkeyStore::new(valkey_config).await?;
             Ok(Arc::new(store))
         }
+        StoreConfig::Consul(consul_config) => {
+            let store = ConsulStore::new(consul_config).await?;
+            Ok(Arc::new(store))
+        }
     }
 }
 
@@ -273,6 +289,8 @@ pub enum StoreConfig {
     Local,
     /// Use a Valkey/Redis-backed distributed store.
     Valkey(Box<ValkeyConfig>),
+    /// Use a Consul KV-backed distributed store.
+    Consul(Box<ConsulConfig>),
 }
 
 
@@ -337,6 +355,39 @@ fn default_operation_timeout() -> crate::config::Duration {
     crate::config::Duration::from_secs(1)
 }
 
+/// Consul configuration for the KV-backed store
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsulConfig {
+    /// Consul HTTP API address, e.g. "http://127.0.0.1:8500"
+    #[serde(default = "default_consul_endpoint")]
+    pub endpoint: String,
+
+    /// ACL token sent with every request
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// Datacenter to use (defaults to the agent's own)
+    #[serde(default)]
+    pub datacenter: Option<String>,
+
+    /// Key prefix for all keys
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: String,
+
+    /// Timeout for individual requests
+    #[serde(default = "default_consul_timeout")]
+    pub timeout: crate::config::Duration,
+}
+
+fn default_consul_endpoint() -> String {
+    "http://127.0.0.1:8500".to_string()
+}
+
+fn default_consul_timeout() -> crate::config::Duration {
+    crate::config::Duration::from_secs(5)
+}
+
 /// TLS settings for connecting to Valkey/Redis.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -63,6 +63,21 @@ else
 end
 "#;
 
+/// Lua script recording a digest auth nonce count, only if it's above the last one
+const DIGEST_NONCE_SCRIPT: &str = r#"
+local key = KEYS[1]
+local nc = tonumber(ARGV[1])
+local ttl = tonumber(ARGV[2])
+
+local current = tonumber(redis.call('GET', key) or '0')
+if nc > current then
+    redis.call('SET', key, nc, 'EX', ttl)
+    return 1
+else
+    return 0
+end
+"#;
+
 /// Distributed store using Valkey/Redis
 pub struct ValkeyStore {
     conn: ConnectionManager,
@@ -79,6 +94,7 @@ pub struct ValkeyStore {
     rate_limit_script: Script,
     leader_acquire_script: Script,
     leader_release_script: Script,
+    digest_nonce_script: Script,
 
     // Background task handles
     subscriber_handle: RwLock<Option<tokio::task::JoinHandle<()>>>,
@@ -108,6 +124,7 @@ impl ValkeyStore {
             rate_limit_script: Script::new(RATE_LIMIT_SCRIPT),
             leader_acquire_script: Script::new(LEADER_ACQUIRE_SCRIPT),
             leader_release_script: Script::new(LEADER_RELEASE_SCRIPT),
+            digest_nonce_script: Script::new(DIGEST_NONCE_SCRIPT),
             subscriber_handle: RwLock::new(None),
         };
 
@@ -816,6 +833,25 @@ impl Store for ValkeyStore {
         Ok(())
     }
 
+    // =========================================================================
+    // Digest Auth Nonces
+    // =========================================================================
+
+    async fn digest_nonce_use(&self, nonce: &str, nc: u64, ttl: Duration) -> StoreResult<bool> {
+        let key = self.key(&["digest-nonce", nonce]);
+
+        let result: i64 = self
+            .digest_nonce_script
+            .key(&key)
+            .arg(nc)
+            .arg(ttl.as_secs().max(1))
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|e| StoreError::Connection(e.to_string()))?;
+
+        Ok(result == 1)
+    }
+
     // =========================================================================
     // Leader Election
     // =========================================================================
@@ -1,12 +1,17 @@
 //! TCP proxying: SNI-based routing, bidirectional stream copying, and backend load balancing.
 
 mod proxy;
+mod proxy_protocol;
 mod router;
 mod service;
 
 /// Handles incoming TCP connections, extracts SNI, and proxies to backends.
 pub use proxy::TcpProxy;
+/// PROXY protocol v1/v2 header parsing and trusted-peer policy.
+pub use proxy_protocol::{read_header, ProxyHeader, ProxyProtocolError, ProxyProtocolPolicy};
 /// Matches TCP connections to routes by SNI hostname or client IP.
 pub use router::TcpRouter;
-/// Manages TCP backend services and round-robin load balancing.
-pub use service::TcpServiceManager;
+/// TLS ClientHello SNI extraction for passthrough routing.
+pub use router::{read_client_hello_sni, ClientHelloError};
+/// Manages TCP backend services, round-robin load balancing and active health checks.
+pub use service::{TcpService, TcpServiceManager};
@@ -1,4 +1,4 @@
-use crate::tcp::{TcpRouter, TcpServiceManager};
+use crate::tcp::{read_client_hello_sni, ProxyHeader, ProxyProtocolPolicy, TcpRouter, TcpServiceManager};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,7 +10,7 @@ use tracing::{debug, error, warn};
 /// Timeout for initial connection to backend
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
-/// Timeout for reading SNI from TLS ClientHello (if needed)
+/// Timeout for reading the TLS ClientHello (if any) to extract SNI
 const SNI_READ_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// TCP proxy handler
@@ -25,19 +25,43 @@ impl TcpProxy {
         Self { router, services }
     }
 
-    /// Handle an incoming TCP connection
+    /// Handle an incoming TCP connection. With a PROXY protocol policy, a header
+    /// from a trusted peer replaces `client_addr` for routing and logging.
     pub async fn handle_connection(
         &self,
         mut client: TcpStream,
-        client_addr: SocketAddr,
+        mut client_addr: SocketAddr,
         entrypoint: &str,
+        proxy_protocol: Option<&ProxyProtocolPolicy>,
     ) {
-        // Try to extract SNI from TLS ClientHello if this looks like TLS
-        let (sni, initial_data) = match self.peek_tls_sni(&mut client).await {
-            Ok((sni, data)) => (sni, data),
-            Err(e) => {
-                debug!("TCP: Failed to peek TLS SNI: {}", e);
-                (None, Vec::new())
+        if let Some(policy) = proxy_protocol {
+            match policy.accept(&mut client, client_addr).await {
+                Ok(addr) => client_addr = addr,
+                Err(e) => {
+                    warn!("TCP: Rejecting connection from {}: {}", client_addr, e);
+                    return;
+                }
+            }
+        }
+
+        // Read the TLS ClientHello (if this is TLS) to route on SNI without
+        // terminating TLS. The bytes read are forwarded to the backend first.
+        let mut initial_data = Vec::new();
+        let sni = match timeout(
+            SNI_READ_TIMEOUT,
+            read_client_hello_sni(&mut client, &mut initial_data),
+        )
+        .await
+        {
+            Ok(Ok(sni)) => sni,
+            Ok(Err(e)) => {
+                warn!("TCP: Closing connection from {}: {}", client_addr, e);
+                return;
+            }
+            Err(_) => {
+                // Slow client or a protocol where the server speaks first
+                debug!("TCP: No ClientHello from {} within {}s", client_addr, SNI_READ_TIMEOUT.as_secs());
+                None
             }
         };
 
@@ -59,7 +83,7 @@ impl TcpProxy {
         };
 
         // Get the backend service
-        let service = match self.services.get_service(&route.service) {
+        let service = match self.services.select_service(&route.service) {
             Some(s) => s,
             None => {
                 error!("TCP: Service '{}' not found", route.service);
@@ -82,7 +106,7 @@ impl TcpProxy {
         );
 
         // Connect to backend
-        let backend_stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(&backend.address)).await {
+        let mut backend_stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(&backend.address)).await {
             Ok(Ok(stream)) => stream,
             Ok(Err(e)) => {
                 error!("TCP: Failed to connect to backend {}: {}", backend.address, e);
@@ -102,202 +126,79 @@ impl TcpProxy {
         let _ = client.set_nodelay(true);
         let _ = backend_stream.set_nodelay(true);
 
-        // If we have initial data (from SNI peeking), we need to write it first
-        // This is handled by the proxy function
-
-        // Start bidirectional proxy
-        if let Err(e) = self.proxy_bidirectional(client, backend_stream, initial_data).await {
-            debug!("TCP: Proxy ended for {}: {}", client_addr, e);
-        }
-
-        debug!("TCP: Connection closed for {}", client_addr);
-    }
-
-    /// Peek at the TLS ClientHello to extract SNI
-    /// Returns (Option<SNI>, initial_data)
-    async fn peek_tls_sni(&self, stream: &mut TcpStream) -> std::io::Result<(Option<String>, Vec<u8>)> {
-        // Read initial bytes to check for TLS
-        let mut buf = vec![0u8; 1024];
-
-        // Use timeout for reading
-        let n = match timeout(SNI_READ_TIMEOUT, stream.peek(&mut buf)).await {
-            Ok(Ok(n)) => n,
-            Ok(Err(e)) => return Err(e),
-            Err(_) => {
-                // Timeout - not TLS or slow client
-                return Ok((None, Vec::new()));
-            }
-        };
-
-        if n < 5 {
-            return Ok((None, Vec::new()));
-        }
-
-        // Check if this looks like TLS
-        if buf[0] != 0x16 {
-            // Not a TLS handshake
-            return Ok((None, Vec::new()));
-        }
-
-        // Parse TLS record header
-        let content_type = buf[0];
-        let _version_major = buf[1];
-        let _version_minor = buf[2];
-        let record_length = ((buf[3] as usize) << 8) | (buf[4] as usize);
-
-        if content_type != 0x16 {
-            // Not a handshake record
-            return Ok((None, Vec::new()));
-        }
-
-        // Read full ClientHello if needed
-        let total_needed = 5 + record_length;
-        if total_needed > buf.len() {
-            buf.resize(total_needed.min(16384), 0);
-        }
-
-        let n = match timeout(SNI_READ_TIMEOUT, stream.peek(&mut buf)).await {
-            Ok(Ok(n)) => n,
-            Ok(Err(e)) => return Err(e),
-            Err(_) => return Ok((None, Vec::new())),
-        };
-
-        if n < total_needed.min(buf.len()) {
-            return Ok((None, Vec::new()));
-        }
-
-        // Parse ClientHello for SNI
-        let sni = self.parse_sni(&buf[5..]);
-
-        Ok((sni, Vec::new())) // No initial data since we used peek()
-    }
-
-    /// Parse SNI from TLS ClientHello
-    fn parse_sni(&self, data: &[u8]) -> Option<String> {
-        if data.len() < 38 {
-            return None;
-        }
-
-        // Handshake type (should be ClientHello = 1)
-        if data[0] != 0x01 {
-            return None;
-        }
-
-        // Skip: handshake type (1), length (3), version (2), random (32)
-        let mut offset = 1 + 3 + 2 + 32;
-
-        if offset >= data.len() {
-            return None;
-        }
-
-        // Session ID length
-        let session_id_len = data[offset] as usize;
-        offset += 1 + session_id_len;
-
-        if offset + 2 > data.len() {
-            return None;
-        }
-
-        // Cipher suites length
-        let cipher_suites_len = ((data[offset] as usize) << 8) | (data[offset + 1] as usize);
-        offset += 2 + cipher_suites_len;
-
-        if offset + 1 > data.len() {
-            return None;
-        }
-
-        // Compression methods length
-        let compression_len = data[offset] as usize;
-        offset += 1 + compression_len;
-
-        if offset + 2 > data.len() {
-            return None;
-        }
-
-        // Extensions length
-        let extensions_len = ((data[offset] as usize) << 8) | (data[offset + 1] as usize);
-        offset += 2;
-
-        let extensions_end = offset + extensions_len;
-        if extensions_end > data.len() {
-            return None;
-        }
-
-        // Parse extensions looking for SNI (type 0x0000)
-        while offset + 4 <= extensions_end {
-            let ext_type = ((data[offset] as u16) << 8) | (data[offset + 1] as u16);
-            let ext_len = ((data[offset + 2] as usize) << 8) | (data[offset + 3] as usize);
-            offset += 4;
-
-            if ext_type == 0x0000 {
-                // SNI extension
-                return self.parse_sni_extension(&data[offset..offset + ext_len]);
+        // The PROXY header goes first on the raw connection, ahead of any
+        // relayed bytes (including a TLS ClientHello)
+        if let Some(version) = service.proxy_protocol() {
+            let header = ProxyHeader::Proxy {
+                source: client_addr,
+                destination: client.local_addr().unwrap_or(client_addr),
+            };
+            if let Err(e) = write_proxy_header(&mut backend_stream, version, &header).await {
+                error!("TCP: Failed to send PROXY header to {}: {}", backend.address, e);
+                return;
             }
-
-            offset += ext_len;
-        }
-
-        None
-    }
-
-    /// Parse SNI extension data
-    fn parse_sni_extension(&self, data: &[u8]) -> Option<String> {
-        if data.len() < 5 {
-            return None;
         }
 
-        // SNI list length
-        let _list_len = ((data[0] as usize) << 8) | (data[1] as usize);
-
-        // First entry
-        let name_type = data[2];
-        if name_type != 0 {
-            // Not a hostname
-            return None;
-        }
+        // Bytes read while looking for SNI are written to the backend first
+        // by the proxy function
 
-        let name_len = ((data[3] as usize) << 8) | (data[4] as usize);
-        if 5 + name_len > data.len() {
-            return None;
+        // Start bidirectional proxy
+        if let Err(e) = self
+            .proxy_bidirectional(client, backend_stream, initial_data, service.termination_delay())
+            .await
+        {
+            debug!("TCP: Proxy ended for {}: {}", client_addr, e);
         }
 
-        String::from_utf8(data[5..5 + name_len].to_vec()).ok()
+        debug!("TCP: Connection closed for {}", client_addr);
     }
 
-    /// Bidirectional proxy between client and backend
+    /// Bidirectional proxy between client and backend. When one direction
+    /// ends (and half-closes its write side), the other keeps relaying for up
+    /// to `termination_delay` before the connection is torn down, so protocols
+    /// that half-close still get their response through.
     async fn proxy_bidirectional(
         &self,
         client: TcpStream,
         backend: TcpStream,
         initial_data: Vec<u8>,
+        termination_delay: Duration,
     ) -> std::io::Result<()> {
         let (client_read, client_write) = client.into_split();
         let (backend_read, backend_write) = backend.into_split();
 
         // If we have initial data, prepend it
-        let client_to_backend = if initial_data.is_empty() {
+        let mut client_to_backend = if initial_data.is_empty() {
             tokio::spawn(copy_stream(client_read, backend_write))
         } else {
             tokio::spawn(copy_stream_with_initial(client_read, backend_write, initial_data))
         };
 
-        let backend_to_client = tokio::spawn(copy_stream(backend_read, client_write));
+        let mut backend_to_client = tokio::spawn(copy_stream(backend_read, client_write));
 
-        // Wait for either direction to finish
-        tokio::select! {
-            result = client_to_backend => {
-                match result {
-                    Ok(Ok(bytes)) => debug!("TCP: Client->Backend finished, {} bytes", bytes),
-                    Ok(Err(e)) => debug!("TCP: Client->Backend error: {}", e),
-                    Err(e) => debug!("TCP: Client->Backend task error: {}", e),
-                }
+        // Wait for either direction to finish, then give the other one the
+        // termination delay to drain
+        let (remaining, direction) = tokio::select! {
+            result = &mut client_to_backend => {
+                log_direction("Client->Backend", result);
+                (backend_to_client, "Backend->Client")
             }
-            result = backend_to_client => {
-                match result {
-                    Ok(Ok(bytes)) => debug!("TCP: Backend->Client finished, {} bytes", bytes),
-                    Ok(Err(e)) => debug!("TCP: Backend->Client error: {}", e),
-                    Err(e) => debug!("TCP: Backend->Client task error: {}", e),
-                }
+            result = &mut backend_to_client => {
+                log_direction("Backend->Client", result);
+                (client_to_backend, "Client->Backend")
+            }
+        };
+
+        let abort = remaining.abort_handle();
+        match timeout(termination_delay, remaining).await {
+            Ok(result) => log_direction(direction, result),
+            Err(_) => {
+                abort.abort();
+                debug!(
+                    "TCP: {} still open after termination delay of {}ms, closing",
+                    direction,
+                    termination_delay.as_millis()
+                );
             }
         }
 
@@ -305,6 +206,27 @@ impl TcpProxy {
     }
 }
 
+/// Log how one relay direction ended
+fn log_direction(direction: &str, result: Result<std::io::Result<u64>, tokio::task::JoinError>) {
+    match result {
+        Ok(Ok(bytes)) => debug!("TCP: {} finished, {} bytes", direction, bytes),
+        Ok(Err(e)) => debug!("TCP: {} error: {}", direction, e),
+        Err(e) => debug!("TCP: {} task error: {}", direction, e),
+    }
+}
+
+/// Write a v1 or v2 PROXY protocol header
+async fn write_proxy_header<W>(writer: &mut W, version: u8, header: &ProxyHeader) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let bytes = match version {
+        1 => header.encode_v1(),
+        _ => header.encode_v2(),
+    };
+    writer.write_all(&bytes).await
+}
+
 /// Copy data from reader to writer using tokio's optimized internal buffer
 async fn copy_stream<R, W>(mut reader: R, mut writer: W) -> std::io::Result<u64>
 where
@@ -339,25 +261,237 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{
+        Config, EntryPoint, TcpConfig, TcpLoadBalancer, TcpServer, TcpService, TcpWeightedService,
+        TcpWeightedServiceRef,
+    };
+
+    /// Single-server load balancer with no other options set
+    fn load_balancer(address: SocketAddr) -> TcpLoadBalancer {
+        TcpLoadBalancer {
+            servers: vec![TcpServer {
+                address: address.to_string(),
+                weight: 1,
+                tls: false,
+            }],
+            health_check: None,
+            servers_transport: None,
+            proxy_protocol: None,
+            termination_delay: None,
+        }
+    }
+
+    fn lb_service(load_balancer: TcpLoadBalancer) -> TcpService {
+        TcpService {
+            load_balancer: Some(load_balancer),
+            ..Default::default()
+        }
+    }
+
+    /// Config routing every connection on the `tcp` entrypoint to `service`
+    fn tcp_config(service: &str, services: Vec<(&str, TcpService)>) -> Config {
+        let entry_point = EntryPoint {
+            address: "127.0.0.1:0".to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        };
+        let router = crate::config::TcpRouter {
+            entry_points: vec!["tcp".to_string()],
+            rule: "HostSNI(`*`)".to_string(),
+            rule_syntax: None,
+            service: service.to_string(),
+            middlewares: vec![],
+            priority: 0,
+            tls: None,
+        };
+        Config {
+            entry_points: [("tcp".to_string(), entry_point)].into(),
+            tcp: Some(TcpConfig {
+                routers: [("all".to_string(), router)].into(),
+                services: services
+                    .into_iter()
+                    .map(|(name, service)| (name.to_string(), service))
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
 
-    #[test]
-    fn test_parse_sni_from_clienthello() {
-        // A minimal TLS ClientHello with SNI for "example.com"
-        // This is a simplified test - real parsing would need a complete ClientHello
+    /// Proxy one connection carrying `payload` to a mock backend and return
+    /// what the backend saw first (the decoded PROXY header) and the client's address
+    async fn proxy_to_mock_backend(
+        version: u8,
+        payload: &[u8],
+        policy: Option<ProxyProtocolPolicy>,
+    ) -> (Option<ProxyHeader>, SocketAddr, Vec<u8>) {
+        let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let lb = TcpLoadBalancer {
+            proxy_protocol: Some(version),
+            ..load_balancer(backend.local_addr().unwrap())
+        };
+        let config = tcp_config("backend", vec![("backend", lb_service(lb))]);
         let proxy = TcpProxy::new(
-            Arc::new(TcpRouter::from_config(&crate::config::Config::default())),
-            Arc::new(TcpServiceManager::new(&crate::config::Config::default())),
+            Arc::new(TcpRouter::from_config(&config)),
+            Arc::new(TcpServiceManager::new(&config)),
         );
 
-        // SNI extension data for "example.com"
-        let sni_ext = vec![
-            0x00, 0x0e, // list length = 14
-            0x00, // name type = hostname
-            0x00, 0x0b, // name length = 11
-            b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm',
-        ];
+        let front = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let front_addr = front.local_addr().unwrap();
+        let payload = payload.to_vec();
+        let mut client = TcpStream::connect(front_addr).await.unwrap();
+        client.write_all(&payload).await.unwrap();
+        let client_addr = client.local_addr().unwrap();
+
+        let (accepted, peer) = front.accept().await.unwrap();
+        tokio::spawn(async move {
+            proxy.handle_connection(accepted, peer, "tcp", policy.as_ref()).await;
+            drop(client);
+        });
+
+        let (mut conn, _) = backend.accept().await.unwrap();
+        let header = crate::tcp::read_header(&mut conn).await.unwrap();
+        let mut relayed = vec![0u8; 5];
+        tokio::io::AsyncReadExt::read_exact(&mut conn, &mut relayed).await.unwrap();
+        (header, client_addr, relayed)
+    }
 
-        let result = proxy.parse_sni_extension(&sni_ext);
-        assert_eq!(result, Some("example.com".to_string()));
+    #[tokio::test]
+    async fn test_weighted_service_splits_connections() {
+        let stable = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let canary = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let parked = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let weighted = TcpWeightedService {
+            services: [("stable", 3), ("canary", 1), ("parked", 0)]
+                .map(|(name, weight)| TcpWeightedServiceRef { name: name.to_string(), weight })
+                .to_vec(),
+        };
+        let config = tcp_config("split", vec![
+            ("split", TcpService { weighted: Some(weighted), ..Default::default() }),
+            ("stable", lb_service(load_balancer(stable.local_addr().unwrap()))),
+            ("canary", lb_service(load_balancer(canary.local_addr().unwrap()))),
+            ("parked", lb_service(load_balancer(parked.local_addr().unwrap()))),
+        ]);
+        let proxy = Arc::new(TcpProxy::new(
+            Arc::new(TcpRouter::from_config(&config)),
+            Arc::new(TcpServiceManager::new(&config)),
+        ));
+        let front = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let front_addr = front.local_addr().unwrap();
+
+        let mut counts = [0; 3];
+        for _ in 0..40 {
+            let mut client = TcpStream::connect(front_addr).await.unwrap();
+            client.write_all(b"hello").await.unwrap();
+            let (accepted, peer) = front.accept().await.unwrap();
+            let proxy = proxy.clone();
+            tokio::spawn(async move { proxy.handle_connection(accepted, peer, "tcp", None).await });
+
+            let backend = tokio::select! {
+                _ = stable.accept() => 0,
+                _ = canary.accept() => 1,
+                _ = parked.accept() => 2,
+            };
+            counts[backend] += 1;
+            drop(client);
+        }
+
+        assert_eq!(counts, [30, 10, 0]);
+    }
+
+    /// Proxy one connection to a backend that reads the whole request, then
+    /// replies after `reply_after`, with the service's `terminationDelay` set
+    /// to `delay`. Returns what the client received.
+    async fn half_closed_exchange(delay: Duration, reply_after: Duration) -> Vec<u8> {
+        let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let lb = TcpLoadBalancer {
+            termination_delay: Some(delay.into()),
+            ..load_balancer(backend.local_addr().unwrap())
+        };
+        let config = tcp_config("backend", vec![("backend", lb_service(lb))]);
+        let proxy = TcpProxy::new(
+            Arc::new(TcpRouter::from_config(&config)),
+            Arc::new(TcpServiceManager::new(&config)),
+        );
+
+        tokio::spawn(async move {
+            let (mut conn, _) = backend.accept().await.unwrap();
+            let mut request = Vec::new();
+            tokio::io::AsyncReadExt::read_to_end(&mut conn, &mut request).await.unwrap();
+            assert_eq!(request, b"request");
+            for part in [&b"response "[..], b"after ", b"half-close"] {
+                tokio::time::sleep(reply_after).await;
+                if conn.write_all(part).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let front = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(front.local_addr().unwrap()).await.unwrap();
+        client.write_all(b"request").await.unwrap();
+        client.shutdown().await.unwrap();
+        let (accepted, peer) = front.accept().await.unwrap();
+        tokio::spawn(async move { proxy.handle_connection(accepted, peer, "tcp", None).await });
+
+        let mut received = Vec::new();
+        let _ = timeout(
+            Duration::from_secs(5),
+            tokio::io::AsyncReadExt::read_to_end(&mut client, &mut received),
+        )
+        .await
+        .unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn test_half_closed_client_still_gets_response() {
+        // The client is done sending, but the backend keeps replying within the delay
+        let received = half_closed_exchange(Duration::from_secs(2), Duration::from_millis(100)).await;
+        assert_eq!(received, b"response after half-close");
+    }
+
+    #[tokio::test]
+    async fn test_termination_delay_bounds_half_closed_connection() {
+        // A reply that comes after the delay is cut off
+        let received = half_closed_exchange(Duration::from_millis(100), Duration::from_millis(500)).await;
+        assert!(received.is_empty(), "got {:?}", String::from_utf8_lossy(&received));
+    }
+
+    #[tokio::test]
+    async fn test_sends_proxy_header_to_backend() {
+        for version in [1, 2] {
+            let (header, client_addr, relayed) = proxy_to_mock_backend(version, b"hello", None).await;
+            let Some(ProxyHeader::Proxy { source, destination }) = header else {
+                panic!("v{version}: expected a PROXY header, got {header:?}");
+            };
+            assert_eq!(source, client_addr);
+            assert_eq!(destination.ip(), client_addr.ip());
+            assert_eq!(relayed, b"hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwards_recovered_ipv6_client() {
+        // The inbound header from a trusted load balancer names an IPv6 client
+        let policy = ProxyProtocolPolicy::from_config(&crate::config::ProxyProtocol {
+            trusted_ips: vec!["127.0.0.1".to_string()],
+            insecure: false,
+        });
+        let inbound = b"PROXY TCP6 2001:db8::1 2001:db8::2 4000 443\r\nhello";
+
+        for version in [1, 2] {
+            let (header, _, relayed) = proxy_to_mock_backend(version, inbound, Some(policy.clone())).await;
+            let Some(ProxyHeader::Proxy { source, destination }) = header else {
+                panic!("v{version}: expected a PROXY header, got {header:?}");
+            };
+            assert_eq!(source, "[2001:db8::1]:4000".parse::<SocketAddr>().unwrap());
+            // Our IPv4 listen address is sent IPv4-mapped alongside the IPv6 client
+            assert!(destination.is_ipv6());
+            assert_eq!(relayed, b"hello");
+        }
     }
 }
@@ -0,0 +1,518 @@
+//! PROXY protocol (v1 text and v2 binary) support for recovering the real
+//! client address behind load balancers that speak it.
+
+use crate::config::ProxyProtocol;
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tracing::warn;
+
+/// Signature that starts every v2 header
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Start of every v1 header
+const V1_PREFIX: &[u8; 6] = b"PROXY ";
+
+/// Longest possible v1 header, including the trailing CRLF
+const V1_MAX_LEN: usize = 107;
+
+/// How long a trusted peer has to send its header
+const HEADER_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay between peeks while a header is only partially received
+const PEEK_RETRY_DELAY: Duration = Duration::from_millis(5);
+
+/// Errors reading a PROXY protocol header
+#[derive(Debug, Error)]
+pub enum ProxyProtocolError {
+    /// Reading from the connection failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The header did not arrive in time
+    #[error("Timed out reading PROXY protocol header")]
+    Timeout,
+
+    /// The header is malformed
+    #[error("Invalid PROXY protocol header: {0}")]
+    Invalid(String),
+}
+
+/// A decoded PROXY protocol header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyHeader {
+    /// The connection was relayed on behalf of `source`
+    Proxy {
+        /// Original client address
+        source: SocketAddr,
+        /// Address the client connected to
+        destination: SocketAddr,
+    },
+    /// The connection was made by the proxy itself (v2 LOCAL, v1 UNKNOWN,
+    /// or an address family without IP addresses); the peer address applies
+    Local,
+}
+
+impl ProxyHeader {
+    /// Encode as a v1 text header
+    pub fn encode_v1(&self) -> Vec<u8> {
+        match self.addresses() {
+            Some((source, destination)) => {
+                let family = if source.is_ipv4() { "TCP4" } else { "TCP6" };
+                format!(
+                    "PROXY {} {} {} {} {}\r\n",
+                    family,
+                    source.ip(),
+                    destination.ip(),
+                    source.port(),
+                    destination.port()
+                )
+                .into_bytes()
+            }
+            None => b"PROXY UNKNOWN\r\n".to_vec(),
+        }
+    }
+
+    /// Encode as a v2 binary header without TLVs
+    pub fn encode_v2(&self) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+
+        let Some((source, destination)) = self.addresses() else {
+            // LOCAL command, AF_UNSPEC, no address block
+            header.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+            return header;
+        };
+
+        let mut addresses = Vec::with_capacity(36);
+        let family = match (source.ip(), destination.ip()) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                addresses.extend_from_slice(&src.octets());
+                addresses.extend_from_slice(&dst.octets());
+                0x11 // AF_INET, STREAM
+            }
+            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                addresses.extend_from_slice(&src.octets());
+                addresses.extend_from_slice(&dst.octets());
+                0x21 // AF_INET6, STREAM
+            }
+            _ => unreachable!("addresses() returns a single family"),
+        };
+        addresses.extend_from_slice(&source.port().to_be_bytes());
+        addresses.extend_from_slice(&destination.port().to_be_bytes());
+
+        header.push(0x21); // version 2, PROXY command
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend(addresses);
+        header
+    }
+
+    /// Source and destination in a single address family. A mixed pair is
+    /// expressed as IPv6, using IPv4-mapped addresses for the IPv4 side.
+    fn addresses(&self) -> Option<(SocketAddr, SocketAddr)> {
+        let ProxyHeader::Proxy { source, destination } = *self else {
+            return None;
+        };
+        if source.is_ipv4() == destination.is_ipv4() {
+            return Some((source, destination));
+        }
+
+        let to_v6 = |addr: SocketAddr| match addr.ip() {
+            IpAddr::V4(ip) => SocketAddr::new(ip.to_ipv6_mapped().into(), addr.port()),
+            IpAddr::V6(_) => addr,
+        };
+        Some((to_v6(source), to_v6(destination)))
+    }
+}
+
+/// Decides whose PROXY protocol headers to honor, per entrypoint
+#[derive(Debug, Clone)]
+pub struct ProxyProtocolPolicy {
+    trusted_ips: Vec<IpNetwork>,
+    insecure: bool,
+}
+
+impl ProxyProtocolPolicy {
+    /// Build a policy from entrypoint configuration. Invalid trusted IPs are skipped.
+    pub fn from_config(config: &ProxyProtocol) -> Self {
+        let trusted_ips = config
+            .trusted_ips
+            .iter()
+            .filter_map(|ip| match ip.parse() {
+                Ok(network) => Some(network),
+                Err(_) => {
+                    warn!("Ignoring invalid proxyProtocol trusted IP: {}", ip);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            trusted_ips,
+            insecure: config.insecure,
+        }
+    }
+
+    /// Whether headers from `ip` are honored
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.insecure || self.trusted_ips.iter().any(|network| network.contains(ip))
+    }
+
+    /// Consume the PROXY header from a trusted peer and return the client address.
+    ///
+    /// The header is optional for trusted peers; without one the peer address is
+    /// used. Connections from untrusted peers are not inspected, so a header they
+    /// send is left in the stream and rejected by the protocol that follows.
+    pub async fn accept(
+        &self,
+        stream: &mut TcpStream,
+        peer: SocketAddr,
+    ) -> Result<SocketAddr, ProxyProtocolError> {
+        if !self.is_trusted(peer.ip()) {
+            return Ok(peer);
+        }
+
+        match timeout(HEADER_READ_TIMEOUT, read_header(stream)).await {
+            Ok(Ok(Some(ProxyHeader::Proxy { source, .. }))) => Ok(source),
+            Ok(Ok(Some(ProxyHeader::Local) | None)) => Ok(peer),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(ProxyProtocolError::Timeout),
+        }
+    }
+}
+
+/// Read a PROXY header from the start of `stream`, consuming exactly its bytes.
+/// Returns `None` without consuming anything when the stream doesn't start with one.
+pub async fn read_header(stream: &mut TcpStream) -> Result<Option<ProxyHeader>, ProxyProtocolError> {
+    let mut buf = [0u8; V1_MAX_LEN];
+
+    loop {
+        let n = stream.peek(&mut buf).await?;
+        let peeked = &buf[..n];
+
+        if n == 0 {
+            // Connection closed before sending anything
+            return Ok(None);
+        }
+
+        if peeked.starts_with(V2_SIGNATURE) {
+            let mut header = [0u8; 16];
+            stream.read_exact(&mut header).await?;
+            let len = u16::from_be_bytes([header[14], header[15]]) as usize;
+            let mut body = vec![0u8; len];
+            stream.read_exact(&mut body).await?;
+            return parse_v2(&header, &body).map(Some);
+        }
+
+        if peeked.starts_with(V1_PREFIX) {
+            if let Some(end) = peeked.windows(2).position(|w| w == b"\r\n") {
+                let mut line = vec![0u8; end + 2];
+                stream.read_exact(&mut line).await?;
+                let line = std::str::from_utf8(&line[..end])
+                    .map_err(|_| ProxyProtocolError::Invalid("v1 header is not ASCII".to_string()))?;
+                return parse_v1(line).map(Some);
+            }
+            if n == V1_MAX_LEN {
+                return Err(ProxyProtocolError::Invalid("v1 header too long".to_string()));
+            }
+        } else if !V2_SIGNATURE.starts_with(peeked) && !V1_PREFIX.starts_with(peeked) {
+            // Can't be the start of either header
+            return Ok(None);
+        }
+
+        // Part of a header has arrived; wait for the rest
+        tokio::time::sleep(PEEK_RETRY_DELAY).await;
+    }
+}
+
+/// Parse a v1 header line without its trailing CRLF, e.g.
+/// `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443`
+fn parse_v1(line: &str) -> Result<ProxyHeader, ProxyProtocolError> {
+    let invalid = || ProxyProtocolError::Invalid(format!("malformed v1 header '{}'", line));
+    let mut parts = line.split(' ');
+
+    if parts.next() != Some("PROXY") {
+        return Err(invalid());
+    }
+
+    let family = parts.next().ok_or_else(invalid)?;
+    if family == "UNKNOWN" {
+        // The rest of the line is ignored for UNKNOWN
+        return Ok(ProxyHeader::Local);
+    }
+
+    let fields: Vec<&str> = parts.collect();
+    let [src_ip, dst_ip, src_port, dst_port] = fields[..] else {
+        return Err(invalid());
+    };
+
+    let (source_ip, destination_ip): (IpAddr, IpAddr) = match family {
+        "TCP4" => (
+            src_ip.parse::<Ipv4Addr>().map_err(|_| invalid())?.into(),
+            dst_ip.parse::<Ipv4Addr>().map_err(|_| invalid())?.into(),
+        ),
+        "TCP6" => (
+            src_ip.parse::<Ipv6Addr>().map_err(|_| invalid())?.into(),
+            dst_ip.parse::<Ipv6Addr>().map_err(|_| invalid())?.into(),
+        ),
+        _ => return Err(invalid()),
+    };
+
+    Ok(ProxyHeader::Proxy {
+        source: SocketAddr::new(source_ip, src_port.parse().map_err(|_| invalid())?),
+        destination: SocketAddr::new(destination_ip, dst_port.parse().map_err(|_| invalid())?),
+    })
+}
+
+/// Parse a v2 header: the fixed 16 bytes plus the address block that follows.
+/// TLVs after the addresses are ignored.
+fn parse_v2(header: &[u8; 16], body: &[u8]) -> Result<ProxyHeader, ProxyProtocolError> {
+    let version = header[12] >> 4;
+    let command = header[12] & 0x0f;
+    if version != 2 {
+        return Err(ProxyProtocolError::Invalid(format!("unsupported version {}", version)));
+    }
+
+    match command {
+        0x0 => return Ok(ProxyHeader::Local),
+        0x1 => {}
+        _ => {
+            return Err(ProxyProtocolError::Invalid(format!("unknown command {}", command)));
+        }
+    }
+
+    let truncated = || ProxyProtocolError::Invalid("address block truncated".to_string());
+    let port = |offset: usize| u16::from_be_bytes([body[offset], body[offset + 1]]);
+
+    match header[13] >> 4 {
+        // AF_INET
+        0x1 => {
+            if body.len() < 12 {
+                return Err(truncated());
+            }
+            let source = Ipv4Addr::from(<[u8; 4]>::try_from(&body[0..4]).unwrap());
+            let destination = Ipv4Addr::from(<[u8; 4]>::try_from(&body[4..8]).unwrap());
+            Ok(ProxyHeader::Proxy {
+                source: SocketAddr::new(source.into(), port(8)),
+                destination: SocketAddr::new(destination.into(), port(10)),
+            })
+        }
+        // AF_INET6
+        0x2 => {
+            if body.len() < 36 {
+                return Err(truncated());
+            }
+            let source = Ipv6Addr::from(<[u8; 16]>::try_from(&body[0..16]).unwrap());
+            let destination = Ipv6Addr::from(<[u8; 16]>::try_from(&body[16..32]).unwrap());
+            Ok(ProxyHeader::Proxy {
+                source: SocketAddr::new(source.into(), port(32)),
+                destination: SocketAddr::new(destination.into(), port(34)),
+            })
+        }
+        // AF_UNSPEC and AF_UNIX carry no usable client IP
+        _ => Ok(ProxyHeader::Local),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    fn v2_header(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    /// Send `data` over a real TCP connection and return the accepted server side
+    async fn connected(data: Vec<u8>) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(&data).await.unwrap();
+            // Keep the connection open until the test reads the payload
+            let mut rest = Vec::new();
+            let _ = client.read_to_end(&mut rest).await;
+        });
+        listener.accept().await.unwrap().0
+    }
+
+    async fn read_payload(stream: &mut TcpStream) -> Vec<u8> {
+        let mut payload = vec![0u8; 5];
+        stream.read_exact(&mut payload).await.unwrap();
+        payload
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        assert_eq!(
+            parse_v1("PROXY TCP4 192.0.2.1 198.51.100.1 56324 443").unwrap(),
+            ProxyHeader::Proxy {
+                source: "192.0.2.1:56324".parse().unwrap(),
+                destination: "198.51.100.1:443".parse().unwrap(),
+            }
+        );
+        assert_eq!(
+            parse_v1("PROXY TCP6 2001:db8::1 2001:db8::2 4000 80").unwrap(),
+            ProxyHeader::Proxy {
+                source: "[2001:db8::1]:4000".parse().unwrap(),
+                destination: "[2001:db8::2]:80".parse().unwrap(),
+            }
+        );
+        assert_eq!(parse_v1("PROXY UNKNOWN").unwrap(), ProxyHeader::Local);
+
+        assert!(parse_v1("PROXY TCP4 2001:db8::1 192.0.2.1 1 2").is_err());
+        assert!(parse_v1("PROXY TCP4 192.0.2.1 198.51.100.1 56324").is_err());
+        assert!(parse_v1("PROXY UDP4 192.0.2.1 198.51.100.1 1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut ipv4 = vec![192, 0, 2, 1, 198, 51, 100, 1];
+        ipv4.extend_from_slice(&56324u16.to_be_bytes());
+        ipv4.extend_from_slice(&443u16.to_be_bytes());
+        let header = v2_header(0x1, 0x11, &ipv4);
+        assert_eq!(
+            parse_v2(header[..16].try_into().unwrap(), &header[16..]).unwrap(),
+            ProxyHeader::Proxy {
+                source: "192.0.2.1:56324".parse().unwrap(),
+                destination: "198.51.100.1:443".parse().unwrap(),
+            }
+        );
+
+        // Truncated address block
+        let header = v2_header(0x1, 0x11, &ipv4[..6]);
+        assert!(parse_v2(header[..16].try_into().unwrap(), &header[16..]).is_err());
+
+        // Wrong version
+        let mut header = v2_header(0x1, 0x11, &ipv4);
+        header[12] = 0x11;
+        assert!(parse_v2(header[..16].try_into().unwrap(), &header[16..]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_v1_header_leaves_payload() {
+        let mut data = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n".to_vec();
+        data.extend_from_slice(b"hello");
+        let mut stream = connected(data).await;
+
+        let header = read_header(&mut stream).await.unwrap();
+        assert!(matches!(header, Some(ProxyHeader::Proxy { .. })));
+        assert_eq!(read_payload(&mut stream).await, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_read_v2_ipv6_header_with_tlvs() {
+        let source: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let destination: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let mut addresses = source.octets().to_vec();
+        addresses.extend_from_slice(&destination.octets());
+        addresses.extend_from_slice(&4000u16.to_be_bytes());
+        addresses.extend_from_slice(&443u16.to_be_bytes());
+        // PP2_TYPE_AUTHORITY TLV, which should be skipped
+        addresses.extend_from_slice(&[0x02, 0x00, 0x03, b'a', b'p', b'i']);
+
+        let mut data = v2_header(0x1, 0x21, &addresses);
+        data.extend_from_slice(b"hello");
+        let mut stream = connected(data).await;
+
+        assert_eq!(
+            read_header(&mut stream).await.unwrap(),
+            Some(ProxyHeader::Proxy {
+                source: "[2001:db8::1]:4000".parse().unwrap(),
+                destination: "[2001:db8::2]:443".parse().unwrap(),
+            })
+        );
+        assert_eq!(read_payload(&mut stream).await, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_v2_local_command_uses_peer_address() {
+        let mut data = v2_header(0x0, 0x00, &[]);
+        data.extend_from_slice(b"hello");
+        let mut stream = connected(data).await;
+        let peer = stream.peer_addr().unwrap();
+
+        let policy = ProxyProtocolPolicy::from_config(&ProxyProtocol {
+            trusted_ips: vec!["127.0.0.1".to_string()],
+            insecure: false,
+        });
+        assert_eq!(policy.accept(&mut stream, peer).await.unwrap(), peer);
+        assert_eq!(read_payload(&mut stream).await, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_trusted_peers_only() {
+        let header = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nhello".to_vec();
+
+        // Trusted peer: the header is honored and consumed
+        let mut stream = connected(header.clone()).await;
+        let peer = stream.peer_addr().unwrap();
+        let trusted = ProxyProtocolPolicy::from_config(&ProxyProtocol {
+            trusted_ips: vec!["127.0.0.0/8".to_string()],
+            insecure: false,
+        });
+        assert_eq!(
+            trusted.accept(&mut stream, peer).await.unwrap(),
+            "192.0.2.1:56324".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(read_payload(&mut stream).await, b"hello");
+
+        // Untrusted peer: the header is not interpreted
+        let mut stream = connected(header.clone()).await;
+        let untrusted = ProxyProtocolPolicy::from_config(&ProxyProtocol {
+            trusted_ips: vec!["10.0.0.0/8".to_string()],
+            insecure: false,
+        });
+        assert_eq!(untrusted.accept(&mut stream, peer).await.unwrap(), peer);
+        assert_eq!(read_payload(&mut stream).await, b"PROXY");
+
+        // Insecure trusts everyone
+        let insecure = ProxyProtocolPolicy::from_config(&ProxyProtocol {
+            trusted_ips: Vec::new(),
+            insecure: true,
+        });
+        assert!(insecure.is_trusted("203.0.113.9".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let v4 = ProxyHeader::Proxy {
+            source: "192.0.2.1:56324".parse().unwrap(),
+            destination: "198.51.100.1:443".parse().unwrap(),
+        };
+        assert_eq!(v4.encode_v1(), b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n");
+        let encoded = v4.encode_v2();
+        assert_eq!(parse_v2(encoded[..16].try_into().unwrap(), &encoded[16..]).unwrap(), v4);
+
+        let mixed = ProxyHeader::Proxy {
+            source: "192.0.2.1:56324".parse().unwrap(),
+            destination: "[2001:db8::2]:443".parse().unwrap(),
+        };
+        assert_eq!(
+            mixed.encode_v1(),
+            b"PROXY TCP6 ::ffff:192.0.2.1 2001:db8::2 56324 443\r\n"
+        );
+
+        assert_eq!(ProxyHeader::Local.encode_v1(), b"PROXY UNKNOWN\r\n");
+        let local = ProxyHeader::Local.encode_v2();
+        assert_eq!(parse_v2(local[..16].try_into().unwrap(), &local[16..]).unwrap(), ProxyHeader::Local);
+    }
+
+    #[tokio::test]
+    async fn test_no_header_from_trusted_peer() {
+        let mut stream = connected(b"GET / HTTP/1.1\r\n\r\n".to_vec()).await;
+        assert_eq!(read_header(&mut stream).await.unwrap(), None);
+        assert_eq!(read_payload(&mut stream).await, b"GET /");
+    }
+}
@@ -2,8 +2,34 @@ use crate::config::{Config, TcpRouterTls};
 use ipnetwork::IpNetwork;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tracing::debug;
 
+/// TLS record content type for handshake messages
+const TLS_HANDSHAKE: u8 = 0x16;
+
+/// Handshake message type of a ClientHello
+const CLIENT_HELLO: u8 = 0x01;
+
+/// Largest TLS record payload allowed by RFC 8446 (2^14 plus expansion)
+const MAX_RECORD_LEN: usize = 16384 + 2048;
+
+/// Upper bound on a ClientHello we are willing to buffer
+const MAX_CLIENT_HELLO_LEN: usize = 64 * 1024;
+
+/// Errors reading a TLS ClientHello for SNI routing
+#[derive(Debug, Error)]
+pub enum ClientHelloError {
+    /// Reading from the connection failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The connection looked like TLS but the ClientHello was invalid
+    #[error("Malformed ClientHello: {0}")]
+    Malformed(&'static str),
+}
+
 /// TCP router for matching connections to services
 pub struct TcpRouter {
     /// Routers by entrypoint
@@ -149,6 +175,169 @@ impl TcpRule {
     }
 }
 
+/// Read the start of a connection and extract the SNI hostname from its TLS
+/// ClientHello, reading only as many bytes as the ClientHello needs.
+///
+/// Everything read is appended to `buffered` so the caller can forward it to
+/// the backend ahead of the rest of the stream. Returns `None` when the
+/// connection isn't TLS or the ClientHello has no SNI.
+pub async fn read_client_hello_sni<R>(
+    stream: &mut R,
+    buffered: &mut Vec<u8>,
+) -> Result<Option<String>, ClientHelloError>
+where
+    R: AsyncRead + Unpin,
+{
+    if buffered.is_empty() && read_more(stream, buffered).await? == 0 {
+        return Ok(None);
+    }
+    if buffered[0] != TLS_HANDSHAKE {
+        return Ok(None);
+    }
+
+    // The ClientHello may span several handshake records
+    let mut handshake = Vec::new();
+    let mut offset = 0;
+    loop {
+        if handshake.len() >= 4 {
+            if handshake[0] != CLIENT_HELLO {
+                return Err(ClientHelloError::Malformed("first handshake message is not a ClientHello"));
+            }
+            let len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+            if len > MAX_CLIENT_HELLO_LEN {
+                return Err(ClientHelloError::Malformed("ClientHello too large"));
+            }
+            if handshake.len() >= 4 + len {
+                return parse_client_hello(&handshake[4..4 + len]);
+            }
+        }
+
+        // Need the next record
+        while buffered.len() < offset + 5 {
+            if read_more(stream, buffered).await? == 0 {
+                return Err(ClientHelloError::Malformed("connection closed mid-record"));
+            }
+        }
+        let header = &buffered[offset..offset + 5];
+        if header[0] != TLS_HANDSHAKE {
+            return Err(ClientHelloError::Malformed("unexpected record type before ClientHello"));
+        }
+        if header[1] != 0x03 {
+            return Err(ClientHelloError::Malformed("unsupported record version"));
+        }
+        let record_len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        if record_len == 0 || record_len > MAX_RECORD_LEN {
+            return Err(ClientHelloError::Malformed("invalid record length"));
+        }
+
+        while buffered.len() < offset + 5 + record_len {
+            if read_more(stream, buffered).await? == 0 {
+                return Err(ClientHelloError::Malformed("connection closed mid-record"));
+            }
+        }
+        handshake.extend_from_slice(&buffered[offset + 5..offset + 5 + record_len]);
+        offset += 5 + record_len;
+    }
+}
+
+async fn read_more<R>(stream: &mut R, buffered: &mut Vec<u8>) -> std::io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut chunk = [0u8; 4096];
+    let n = stream.read(&mut chunk).await?;
+    buffered.extend_from_slice(&chunk[..n]);
+    Ok(n)
+}
+
+/// Find the SNI hostname in a ClientHello body (after the 4-byte handshake header)
+fn parse_client_hello(body: &[u8]) -> Result<Option<String>, ClientHelloError> {
+    let truncated = || ClientHelloError::Malformed("truncated ClientHello");
+    let mut reader = Reader(body);
+
+    // legacy_version and random
+    reader.skip(2 + 32).ok_or_else(truncated)?;
+    // session ID, cipher suites and compression methods
+    let session_id_len = reader.u8().ok_or_else(truncated)? as usize;
+    reader.skip(session_id_len).ok_or_else(truncated)?;
+    let suites_len = reader.u16().ok_or_else(truncated)? as usize;
+    reader.skip(suites_len).ok_or_else(truncated)?;
+    let compression_len = reader.u8().ok_or_else(truncated)? as usize;
+    reader.skip(compression_len).ok_or_else(truncated)?;
+
+    // Extensions are optional
+    if reader.0.is_empty() {
+        return Ok(None);
+    }
+    let extensions_len = reader.u16().ok_or_else(truncated)? as usize;
+    let mut extensions = Reader(reader.take(extensions_len).ok_or_else(truncated)?);
+
+    while !extensions.0.is_empty() {
+        let (Some(ext_type), Some(ext_len)) = (extensions.u16(), extensions.u16()) else {
+            return Err(truncated());
+        };
+        let data = extensions.take(ext_len as usize).ok_or_else(truncated)?;
+
+        // server_name
+        if ext_type == 0x0000 {
+            return parse_sni_extension(data).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Parse the server_name extension and return the first host_name entry
+fn parse_sni_extension(data: &[u8]) -> Result<String, ClientHelloError> {
+    let malformed = || ClientHelloError::Malformed("invalid server_name extension");
+    let mut reader = Reader(data);
+    let list_len = reader.u16().ok_or_else(malformed)? as usize;
+    let mut list = Reader(reader.take(list_len).ok_or_else(malformed)?);
+
+    while !list.0.is_empty() {
+        let name_type = list.u8().ok_or_else(malformed)?;
+        let name_len = list.u16().ok_or_else(malformed)? as usize;
+        let name = list.take(name_len).ok_or_else(malformed)?;
+
+        // host_name
+        if name_type == 0 {
+            if name.is_empty() || !name.is_ascii() {
+                return Err(malformed());
+            }
+            let name = std::str::from_utf8(name).map_err(|_| malformed())?;
+            return Ok(name.to_ascii_lowercase());
+        }
+    }
+
+    Err(malformed())
+}
+
+/// Minimal big-endian cursor over ClientHello bytes
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
+
 impl TcpRouter {
     /// Create a new TCP router from configuration
     pub fn from_config(config: &Config) -> Self {
@@ -194,7 +383,7 @@ impl TcpRouter {
 
         // Sort routers by priority (higher first)
         for routes in routers.values_mut() {
-            routes.sort_by(|a, b| b.priority.cmp(&a.priority));
+            routes.sort_by_key(|r| std::cmp::Reverse(r.priority));
         }
 
         Self { routers, catch_all }
@@ -279,6 +468,149 @@ mod tests {
         assert!(!rule.matches(Some("other.com"), None));
     }
 
+    /// ClientHello as sent by rustls
+    fn recorded_client_hello(server_name: &str) -> Vec<u8> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth();
+        let name = rustls::pki_types::ServerName::try_from(server_name.to_string()).unwrap();
+        let mut conn = rustls::ClientConnection::new(std::sync::Arc::new(config), name).unwrap();
+        let mut bytes = Vec::new();
+        conn.write_tls(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Re-frame a single-record ClientHello as two handshake records
+    fn split_into_two_records(hello: &[u8]) -> Vec<u8> {
+        let fragment = &hello[5..];
+        let (first, second) = fragment.split_at(fragment.len() / 2);
+        let mut out = Vec::new();
+        for part in [first, second] {
+            out.extend_from_slice(&[0x16, 0x03, 0x01]);
+            out.extend_from_slice(&(part.len() as u16).to_be_bytes());
+            out.extend_from_slice(part);
+        }
+        out
+    }
+
+    async fn sni_of(bytes: &[u8]) -> (Result<Option<String>, ClientHelloError>, Vec<u8>) {
+        let mut stream = bytes;
+        let mut buffered = Vec::new();
+        let result = read_client_hello_sni(&mut stream, &mut buffered).await;
+        (result, buffered)
+    }
+
+    #[tokio::test]
+    async fn test_client_hello_with_sni() {
+        let mut hello = recorded_client_hello("App.Example.com");
+        let hello_len = hello.len();
+        // Bytes after the ClientHello are left for the proxy to copy
+        hello.extend_from_slice(&[0u8; 10_000]);
+
+        let (sni, buffered) = sni_of(&hello).await;
+        assert_eq!(sni.unwrap().as_deref(), Some("app.example.com"));
+        assert!(buffered.len() >= hello_len && buffered.len() < hello.len());
+        assert_eq!(&buffered[..], &hello[..buffered.len()]);
+
+        let split = split_into_two_records(&hello[..hello_len]);
+        let (sni, buffered) = sni_of(&split).await;
+        assert_eq!(sni.unwrap().as_deref(), Some("app.example.com"));
+        assert_eq!(buffered, split);
+    }
+
+    #[tokio::test]
+    async fn test_client_hello_without_sni() {
+        // rustls sends no SNI when connecting to an IP address
+        let hello = recorded_client_hello("192.0.2.1");
+        let (sni, buffered) = sni_of(&hello).await;
+        assert_eq!(sni.unwrap(), None);
+        assert_eq!(buffered, hello);
+
+        // Not TLS at all
+        let (sni, buffered) = sni_of(b"SSH-2.0-OpenSSH_9.6\r\n").await;
+        assert_eq!(sni.unwrap(), None);
+        assert!(!buffered.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_client_hello() {
+        let hello = recorded_client_hello("example.com");
+
+        // Truncated mid-record
+        assert!(sni_of(&hello[..hello.len() - 10]).await.0.is_err());
+
+        // Wrong handshake message type
+        let mut wrong_type = hello.clone();
+        wrong_type[5] = 0x02;
+        assert!(sni_of(&wrong_type).await.0.is_err());
+
+        // Record followed by application data before the ClientHello completes
+        let mut interleaved = split_into_two_records(&hello);
+        let second = 5 + (hello.len() - 5) / 2;
+        interleaved[second] = 0x17;
+        assert!(sni_of(&interleaved).await.0.is_err());
+    }
+
+    #[test]
+    fn test_parse_sni_extension() {
+        let sni_ext = vec![
+            0x00, 0x0e, // list length = 14
+            0x00, // name type = hostname
+            0x00, 0x0b, // name length = 11
+            b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm',
+        ];
+        assert_eq!(parse_sni_extension(&sni_ext).unwrap(), "example.com");
+        assert!(parse_sni_extension(&sni_ext[..8]).is_err());
+    }
+
+    #[test]
+    fn test_no_sni_routes_to_catch_all() {
+        let router = |rule: &str, service: &str, tls: Option<TcpRouterTls>| crate::config::TcpRouter {
+            entry_points: vec![],
+            rule: rule.to_string(),
+            rule_syntax: None,
+            service: service.to_string(),
+            middlewares: vec![],
+            priority: 0,
+            tls,
+        };
+        let passthrough = TcpRouterTls {
+            passthrough: true,
+            ..Default::default()
+        };
+        let entry_point = crate::config::EntryPoint {
+            address: ":443".to_string(),
+            as_default: false,
+            http: None,
+            forwarded_headers: None,
+            transport: None,
+            proxy_protocol: None,
+        };
+        let config = Config {
+            entry_points: [("tls".to_string(), entry_point)].into(),
+            tcp: Some(crate::config::TcpConfig {
+                routers: [
+                    ("app".to_string(), router("HostSNI(`app.example.com`)", "app", Some(passthrough))),
+                    ("fallback".to_string(), router("HostSNI(`*`)", "fallback", None)),
+                ]
+                .into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let router = TcpRouter::from_config(&config);
+
+        let route = router.match_connection("tls", Some("app.example.com"), None).unwrap();
+        assert_eq!(route.service, "app");
+        assert!(router.is_tls_passthrough(route));
+        assert_eq!(router.match_connection("tls", None, None).unwrap().service, "fallback");
+        assert_eq!(
+            router.match_connection("tls", Some("other.example.com"), None).unwrap().service,
+            "fallback"
+        );
+    }
+
     #[test]
     fn test_clientip_match() {
         let rule = TcpRule::parse("ClientIP(`192.168.1.0/24`)");
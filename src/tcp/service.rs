@@ -1,13 +1,24 @@
-use crate::config::Config;
-use parking_lot::RwLock;
+use crate::config::{Config, TcpHealthCheck, TcpWeightedService, WeightedService, WeightedServiceRef};
+use crate::health::HealthStatus;
+use crate::proxy::tls_client::insecure_connector;
+use crate::service::WeightedServiceRouter;
+use rustls::pki_types::ServerName;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use tracing::{debug, warn};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+/// How long a half-closed connection waits for the other direction when the
+/// service sets no `terminationDelay` (Traefik's default)
+const DEFAULT_TERMINATION_DELAY: Duration = Duration::from_millis(100);
 
 /// Manages TCP services and load balancing
 pub struct TcpServiceManager {
     services: HashMap<String, Arc<TcpService>>,
+    /// Weighted services, splitting connections across other services by weight
+    weighted: HashMap<String, WeightedServiceRouter>,
 }
 
 /// A TCP service with round-robin load balancing across backends.
@@ -16,8 +27,14 @@ pub struct TcpService {
     servers: Vec<TcpBackendServer>,
     /// Round-robin counter
     rr_counter: AtomicUsize,
-    /// Health status
-    healthy: RwLock<Vec<bool>>,
+    /// Health status per server, kept current by the active health check
+    health: Vec<Arc<HealthStatus>>,
+    /// PROXY protocol version (1 or 2) to send to backends
+    proxy_protocol: Option<u8>,
+    /// Active health check settings
+    health_check: Option<TcpHealthCheck>,
+    /// How long to keep relaying the other direction once one side half-closes
+    termination_delay: Duration,
 }
 
 /// A TCP backend server
@@ -35,6 +52,7 @@ impl TcpServiceManager {
     /// Create a new service manager from configuration
     pub fn new(config: &Config) -> Self {
         let mut services = HashMap::new();
+        let mut weighted = HashMap::new();
 
         for (name, service_config) in config.tcp_services() {
             if let Some(lb) = &service_config.load_balancer {
@@ -48,65 +66,162 @@ impl TcpServiceManager {
                     })
                     .collect();
 
-                let healthy = vec![true; servers.len()];
+                let health = servers.iter().map(|_| Arc::new(HealthStatus::new())).collect();
+
+                let proxy_protocol = match lb.proxy_protocol {
+                    Some(version @ (1 | 2)) => Some(version),
+                    Some(version) => {
+                        warn!(
+                            "TCP service '{}': Unsupported proxyProtocol version {}, not sending headers",
+                            name, version
+                        );
+                        None
+                    }
+                    None => None,
+                };
 
                 let service = TcpService {
                     name: name.clone(),
                     servers,
                     rr_counter: AtomicUsize::new(0),
-                    healthy: RwLock::new(healthy),
+                    health,
+                    proxy_protocol,
+                    health_check: lb.health_check.clone(),
+                    termination_delay: lb
+                        .termination_delay
+                        .map_or(DEFAULT_TERMINATION_DELAY, |delay| delay.as_std()),
                 };
 
                 services.insert(name.clone(), Arc::new(service));
+            } else if let Some(w) = &service_config.weighted {
+                weighted.insert(name.clone(), weighted_router(w));
             }
-            // TODO: Handle weighted services
         }
 
-        Self { services }
+        Self { services, weighted }
     }
 
-    /// Get a service by name
+    /// Get a load-balanced service by name
     pub fn get_service(&self, name: &str) -> Option<Arc<TcpService>> {
         self.services.get(name).cloned()
     }
-}
 
-impl TcpService {
-    /// Get the next healthy backend server (round-robin)
-    pub fn next_server(&self) -> Option<&TcpBackendServer> {
-        if self.servers.is_empty() {
-            return None;
+    /// Pick the service a new connection to `name` goes to. A weighted service
+    /// draws one of its services by weight (following nested weighted services),
+    /// so the split applies per connection.
+    pub fn select_service(&self, name: &str) -> Option<Arc<TcpService>> {
+        let mut current = name;
+        // Each hop goes through a distinct weighted service unless they form a cycle
+        for _ in 0..=self.weighted.len() {
+            if let Some(service) = self.services.get(current) {
+                return Some(service.clone());
+            }
+            current = self.weighted.get(current)?.next_service()?;
         }
+        warn!("TCP service '{}': Weighted services reference each other in a cycle", name);
+        None
+    }
 
-        let healthy = self.healthy.read();
-        let healthy_count = healthy.iter().filter(|&&h| h).count();
+    /// All services, keyed by name
+    pub fn services(&self) -> &HashMap<String, Arc<TcpService>> {
+        &self.services
+    }
 
-        if healthy_count == 0 {
-            warn!("TCP service '{}': No healthy backends available", self.name);
-            // Fall back to first server even if unhealthy
-            return self.servers.first();
+    /// Spawn an active health check for each server of every service with a
+    /// `healthCheck`. A check stops once its service is dropped.
+    pub fn start_health_checks(&self) {
+        for service in self.services.values() {
+            let Some(config) = &service.health_check else {
+                continue;
+            };
+            for (idx, server) in service.servers.iter().enumerate() {
+                info!(
+                    "Starting TCP health checker for service '{}' server '{}'",
+                    service.name, server.address
+                );
+                tokio::spawn(run_health_check(Arc::downgrade(service), idx, config.clone()));
+            }
         }
+    }
+}
 
-        // Round-robin through healthy servers
-        let mut attempts = 0;
-        loop {
-            let idx = self.rr_counter.fetch_add(1, Ordering::Relaxed) % self.servers.len();
-            if healthy[idx] {
-                return Some(&self.servers[idx]);
-            }
-            attempts += 1;
-            if attempts >= self.servers.len() {
-                // All servers checked, return first available
-                return self.servers.first();
-            }
+/// Weighted router over a TCP weighted service's references
+fn weighted_router(config: &TcpWeightedService) -> WeightedServiceRouter {
+    WeightedServiceRouter::new(&WeightedService {
+        services: config
+            .services
+            .iter()
+            .map(|s| WeightedServiceRef {
+                name: s.name.clone(),
+                weight: s.weight,
+            })
+            .collect(),
+        sticky: None,
+        health_check: None,
+    })
+}
+
+/// Probe one server of `service` every `interval` until the service is dropped.
+async fn run_health_check(service: Weak<TcpService>, index: usize, config: TcpHealthCheck) {
+    let mut ticker = tokio::time::interval(config.interval.as_std());
+    loop {
+        ticker.tick().await;
+        let Some(service) = service.upgrade() else {
+            return;
+        };
+        let result = probe(&service.servers[index], config.timeout.as_std()).await;
+        service.record_check(index, result);
+    }
+}
+
+/// A health probe succeeds when a TCP connection (plus a TLS handshake for
+/// `tls` backends) is established within `timeout`.
+async fn probe(server: &TcpBackendServer, timeout: Duration) -> Result<(), String> {
+    let check = async {
+        let stream = TcpStream::connect(&server.address)
+            .await
+            .map_err(|e| format!("Connect failed: {}", e))?;
+        if server.use_tls {
+            insecure_connector()
+                .connect(server_name(&server.address)?, stream)
+                .await
+                .map_err(|e| format!("TLS handshake failed: {}", e))?;
+        }
+        Ok(())
+    };
+    tokio::time::timeout(timeout, check)
+        .await
+        .map_err(|_| "Timeout".to_string())?
+}
+
+/// TLS server name for a `host:port` backend address
+fn server_name(address: &str) -> Result<ServerName<'static>, String> {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    ServerName::try_from(host.to_string())
+        .map_err(|e| format!("Invalid server name '{}': {}", host, e))
+}
+
+impl TcpService {
+    /// Get the next healthy backend server (round-robin), or `None` when every server is down
+    pub fn next_server(&self) -> Option<&TcpBackendServer> {
+        let len = self.servers.len();
+        let start = self.rr_counter.fetch_add(1, Ordering::Relaxed);
+
+        let server = (0..len)
+            .map(|i| (start + i) % len)
+            .find(|&idx| self.health[idx].is_healthy())
+            .map(|idx| &self.servers[idx]);
+        if server.is_none() && len > 0 {
+            warn!("TCP service '{}': No healthy backends available", self.name);
         }
+        server
     }
 
     /// Mark a server as unhealthy
     pub fn mark_unhealthy(&self, index: usize) {
-        if index < self.servers.len() {
-            let mut healthy = self.healthy.write();
-            healthy[index] = false;
+        if let Some(status) = self.health.get(index) {
+            status.mark_unhealthy();
             debug!(
                 "TCP service '{}': Marked server {} as unhealthy",
                 self.name, self.servers[index].address
@@ -116,9 +231,8 @@ impl TcpService {
 
     /// Mark a server as healthy
     pub fn mark_healthy(&self, index: usize) {
-        if index < self.servers.len() {
-            let mut healthy = self.healthy.write();
-            healthy[index] = true;
+        if let Some(status) = self.health.get(index) {
+            status.mark_healthy();
             debug!(
                 "TCP service '{}': Marked server {} as healthy",
                 self.name, self.servers[index].address
@@ -126,6 +240,49 @@ impl TcpService {
         }
     }
 
+    /// Apply a health probe result: a failure takes the server out of rotation
+    /// and the next success puts it back.
+    fn record_check(&self, index: usize, result: Result<(), String>) {
+        let status = &self.health[index];
+        let address = &self.servers[index].address;
+        match result {
+            Ok(()) => {
+                status.record_success();
+                if !status.is_healthy() {
+                    status.mark_healthy();
+                    info!("TCP service '{}': Server {} is healthy again", self.name, address);
+                }
+            }
+            Err(e) => {
+                status.record_failure(e.clone());
+                if status.is_healthy() {
+                    status.mark_unhealthy();
+                    warn!("TCP service '{}': Server {} is unhealthy: {}", self.name, address, e);
+                }
+            }
+        }
+    }
+
+    /// Service name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Active health status of the server at `index`
+    pub fn health_status(&self, index: usize) -> Option<&Arc<HealthStatus>> {
+        self.health.get(index)
+    }
+
+    /// PROXY protocol version to send to backends, if enabled
+    pub fn proxy_protocol(&self) -> Option<u8> {
+        self.proxy_protocol
+    }
+
+    /// How long the proxy keeps relaying one direction after the other closes
+    pub fn termination_delay(&self) -> Duration {
+        self.termination_delay
+    }
+
     /// Get all backend servers
     pub fn servers(&self) -> &[TcpBackendServer] {
         &self.servers
@@ -138,33 +295,38 @@ impl TcpService {
 
     /// Get healthy server count
     pub fn healthy_count(&self) -> usize {
-        self.healthy.read().iter().filter(|&&h| h).count()
+        self.health.iter().filter(|status| status.is_healthy()).count()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{TcpConfig, TcpLoadBalancer, TcpServer};
+    use tokio::net::TcpListener;
 
-    #[test]
-    fn test_round_robin() {
-        let service = TcpService {
+    fn make_service(addresses: &[&str]) -> TcpService {
+        TcpService {
             name: "test".to_string(),
-            servers: vec![
-                TcpBackendServer {
-                    address: "localhost:8001".to_string(),
+            servers: addresses
+                .iter()
+                .map(|address| TcpBackendServer {
+                    address: address.to_string(),
                     weight: 1,
                     use_tls: false,
-                },
-                TcpBackendServer {
-                    address: "localhost:8002".to_string(),
-                    weight: 1,
-                    use_tls: false,
-                },
-            ],
+                })
+                .collect(),
             rr_counter: AtomicUsize::new(0),
-            healthy: RwLock::new(vec![true, true]),
-        };
+            health: addresses.iter().map(|_| Arc::new(HealthStatus::new())).collect(),
+            proxy_protocol: None,
+            health_check: None,
+            termination_delay: DEFAULT_TERMINATION_DELAY,
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let service = make_service(&["localhost:8001", "localhost:8002"]);
 
         let s1 = service.next_server().unwrap();
         let s2 = service.next_server().unwrap();
@@ -177,28 +339,202 @@ mod tests {
 
     #[test]
     fn test_skip_unhealthy() {
-        let service = TcpService {
-            name: "test".to_string(),
-            servers: vec![
-                TcpBackendServer {
-                    address: "localhost:8001".to_string(),
-                    weight: 1,
-                    use_tls: false,
-                },
-                TcpBackendServer {
-                    address: "localhost:8002".to_string(),
-                    weight: 1,
-                    use_tls: false,
-                },
-            ],
-            rr_counter: AtomicUsize::new(0),
-            healthy: RwLock::new(vec![false, true]),
-        };
+        let service = make_service(&["localhost:8001", "localhost:8002"]);
+        service.mark_unhealthy(0);
 
         // Should always return the healthy server
         for _ in 0..5 {
             let s = service.next_server().unwrap();
             assert_eq!(s.address, "localhost:8002");
         }
+
+        service.mark_unhealthy(1);
+        assert!(service.next_server().is_none());
+        assert!(make_service(&[]).next_server().is_none());
+    }
+
+    fn load_balancer(address: &str) -> TcpLoadBalancer {
+        TcpLoadBalancer {
+            servers: vec![TcpServer {
+                address: address.to_string(),
+                weight: 1,
+                tls: false,
+            }],
+            health_check: None,
+            servers_transport: None,
+            proxy_protocol: None,
+            termination_delay: None,
+        }
+    }
+
+    fn lb_service(load_balancer: TcpLoadBalancer) -> crate::config::TcpService {
+        crate::config::TcpService {
+            load_balancer: Some(load_balancer),
+            ..Default::default()
+        }
+    }
+
+    fn weighted_service(services: &[(&str, u32)]) -> crate::config::TcpService {
+        let services = services
+            .iter()
+            .map(|(name, weight)| crate::config::TcpWeightedServiceRef {
+                name: name.to_string(),
+                weight: *weight,
+            })
+            .collect();
+        crate::config::TcpService {
+            weighted: Some(TcpWeightedService { services }),
+            ..Default::default()
+        }
+    }
+
+    /// Config holding just the given TCP services
+    fn services_config(services: Vec<(&str, crate::config::TcpService)>) -> Config {
+        Config {
+            tcp: Some(TcpConfig {
+                services: services
+                    .into_iter()
+                    .map(|(name, service)| (name.to_string(), service))
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Service manager for one `backend` service health-checked every 50ms
+    fn checked_manager(address: &str, tls: bool) -> TcpServiceManager {
+        let mut load_balancer = TcpLoadBalancer {
+            health_check: Some(TcpHealthCheck {
+                interval: crate::config::Duration::from_millis(50),
+                timeout: crate::config::Duration::from_millis(500),
+            }),
+            ..load_balancer(address)
+        };
+        load_balancer.servers[0].tls = tls;
+        TcpServiceManager::new(&services_config(vec![("backend", lb_service(load_balancer))]))
+    }
+
+    /// Wait up to 5s for the service's healthy server count to reach `expected`
+    async fn wait_for_healthy(service: &TcpService, expected: usize) {
+        for _ in 0..100 {
+            if service.healthy_count() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("healthy count stayed at {}, expected {}", service.healthy_count(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_removes_and_restores_server() {
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = backend.local_addr().unwrap();
+        let manager = checked_manager(&addr.to_string(), false);
+        manager.start_health_checks();
+        let service = manager.get_service("backend").unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(service.healthy_count(), 1);
+        assert!(service.next_server().is_some());
+
+        // The backend stops accepting connections
+        drop(backend);
+        wait_for_healthy(&service, 0).await;
+        assert!(service.next_server().is_none());
+        let status = service.health_status(0).unwrap();
+        assert!(status.last_error.read().as_deref().unwrap().starts_with("Connect failed"));
+
+        // ...and comes back on the same address
+        let _backend = TcpListener::bind(addr).await.unwrap();
+        wait_for_healthy(&service, 1).await;
+        assert_eq!(service.next_server().unwrap().address, addr.to_string());
+        assert!(status.last_error.read().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tls_health_check_requires_handshake() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        // Accepts TCP connections but never speaks TLS
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = backend.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = backend.accept().await.unwrap();
+                drop(stream);
+            }
+        });
+
+        let manager = checked_manager(&addr.to_string(), true);
+        manager.start_health_checks();
+        let service = manager.get_service("backend").unwrap();
+
+        wait_for_healthy(&service, 0).await;
+        let status = service.health_status(0).unwrap();
+        assert!(status.last_error.read().as_deref().unwrap().starts_with("TLS handshake failed"));
+    }
+
+    fn weighted_manager(weights: &[(&str, u32)]) -> TcpServiceManager {
+        let mut services = vec![("split", weighted_service(weights))];
+        for (i, (name, _)) in weights.iter().enumerate() {
+            services.push((*name, lb_service(load_balancer(&format!("127.0.0.1:{}", 9000 + i)))));
+        }
+        TcpServiceManager::new(&services_config(services))
+    }
+
+    fn selection_counts(manager: &TcpServiceManager, name: &str, n: usize) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for _ in 0..n {
+            let service = manager.select_service(name).unwrap();
+            *counts.entry(service.name().to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_weighted_split_follows_weights() {
+        let manager = weighted_manager(&[("primary", 3), ("canary", 1)]);
+        assert!(manager.get_service("split").is_none());
+
+        let counts = selection_counts(&manager, "split", 400);
+        assert_eq!(counts["primary"], 300);
+        assert_eq!(counts["canary"], 100);
+
+        // Load-balanced services resolve to themselves
+        assert_eq!(manager.select_service("canary").unwrap().name(), "canary");
+        assert!(manager.select_service("missing").is_none());
+    }
+
+    #[test]
+    fn test_weighted_zero_weight_gets_no_connections() {
+        let manager = weighted_manager(&[("live", 1), ("parked", 0)]);
+        let counts = selection_counts(&manager, "split", 100);
+        assert_eq!(counts["live"], 100);
+        assert!(!counts.contains_key("parked"));
+    }
+
+    #[test]
+    fn test_weighted_nesting_and_cycles() {
+        let config = services_config(vec![
+            ("outer", weighted_service(&[("inner", 1), ("a", 1)])),
+            ("inner", weighted_service(&[("b", 1)])),
+            ("a", lb_service(load_balancer("127.0.0.1:9000"))),
+            ("b", lb_service(load_balancer("127.0.0.1:9001"))),
+            ("loop-a", weighted_service(&[("loop-b", 1)])),
+            ("loop-b", weighted_service(&[("loop-a", 1)])),
+        ]);
+        let manager = TcpServiceManager::new(&config);
+
+        let counts = selection_counts(&manager, "outer", 10);
+        assert_eq!(counts["a"], 5);
+        assert_eq!(counts["b"], 5);
+        assert!(manager.select_service("loop-a").is_none());
+    }
+
+    #[test]
+    fn test_server_name() {
+        assert!(matches!(server_name("db.internal:5432"), Ok(ServerName::DnsName(_))));
+        assert!(matches!(server_name("10.0.0.5:5432"), Ok(ServerName::IpAddress(_))));
+        assert!(matches!(server_name("[::1]:5432"), Ok(ServerName::IpAddress(_))));
     }
 }
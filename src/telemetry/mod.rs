@@ -1,9 +1,15 @@
-//! Distributed tracing: W3C/B3/Jaeger context propagation and request span tracking.
+//! Distributed tracing: W3C/B3/Jaeger context propagation, request span tracking,
+//! and OTLP span export.
 
+mod otlp;
 mod propagation;
 mod span;
 
+/// OTLP/HTTP span exporter and server span factory.
+pub use otlp::Tracer;
 /// Extract trace context from incoming headers, inject into outgoing headers.
-pub use propagation::{extract_context, inject_context, TraceContext};
+pub use propagation::{
+    extract_context, inject_context, inject_context_as, try_extract_context, TraceContext,
+};
 /// Request span for structured tracing of HTTP requests through the proxy.
 pub use span::{RequestSpan, SpanKind};
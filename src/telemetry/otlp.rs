@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use hyper::HeaderMap;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use super::propagation::{inject_context_as, try_extract_context};
+use super::{RequestSpan, SpanKind, TraceContext};
+use crate::config::{TracePropagation, TracingConfig};
+
+/// Spans buffered between the proxy and the export task before new ones are dropped
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Maximum spans sent in one export request
+const MAX_BATCH_SIZE: usize = 512;
+
+/// Timeout for a single export request
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Starts request spans, ships finished ones to an OTLP/HTTP collector, and
+/// writes trace context onto backend requests.
+///
+/// Cheap to clone. Without an export endpoint, finishing spans is a no-op but
+/// context is still propagated, so callers don't need to special-case it.
+#[derive(Clone)]
+pub struct Tracer {
+    inner: Option<Arc<TracerInner>>,
+    propagation: Arc<[TracePropagation]>,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self {
+            inner: None,
+            propagation: Arc::new([TracePropagation::TraceContext]),
+        }
+    }
+}
+
+struct TracerInner {
+    sender: mpsc::Sender<Value>,
+    sampling_ratio: f64,
+}
+
+impl Tracer {
+    /// Start the exporter from config. Spans are not exported when no
+    /// endpoint is configured or there is no runtime to run the exporter on.
+    pub fn new(config: &Option<TracingConfig>) -> Self {
+        let Some(config) = config else {
+            return Self::default();
+        };
+        let disabled = Self {
+            inner: None,
+            propagation: config.propagation.clone().into(),
+        };
+
+        let Some(endpoint) = &config.endpoint else {
+            return disabled;
+        };
+        let endpoint = traces_url(endpoint);
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("Tracing configured but no async runtime available; spans will not be exported");
+            return disabled;
+        };
+
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let exporter = Exporter {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.clone(),
+            headers: config.headers.clone(),
+            resource: json!({
+                "attributes": [string_attribute("service.name", &config.service_name)],
+            }),
+        };
+        runtime.spawn(exporter.run(receiver, config.flush_interval.as_std()));
+
+        info!("Exporting spans to {} (sampling ratio {})", endpoint, config.sampling_ratio);
+        Self {
+            inner: Some(Arc::new(TracerInner {
+                sender,
+                sampling_ratio: config.sampling_ratio,
+            })),
+            propagation: disabled.propagation,
+        }
+    }
+
+    /// Whether spans are being exported
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Start the server span for an incoming request, continuing the caller's
+    /// trace when the headers carry one. New traces are sampled by ratio;
+    /// continued traces keep the caller's sampling decision.
+    pub fn start_server_span(&self, headers: &HeaderMap, name: impl Into<String>) -> RequestSpan {
+        match try_extract_context(headers) {
+            Some(remote) => {
+                let parent = remote.parent_id.clone();
+                RequestSpan::server(remote.child(), name).with_parent_span_id(Some(parent))
+            }
+            None => {
+                let mut context = TraceContext::new();
+                if !self.should_sample(&context.trace_id) {
+                    context.trace_flags &= !0x01;
+                }
+                RequestSpan::server(context, name)
+            }
+        }
+    }
+
+    /// Write `context` onto outgoing request headers in the configured
+    /// formats, replacing any trace headers already present
+    pub fn inject(&self, headers: &mut HeaderMap, context: &TraceContext) {
+        inject_context_as(headers, context, &self.propagation);
+    }
+
+    /// End `span` and queue it for export if its trace is sampled
+    pub fn finish(&self, span: RequestSpan) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        if !span.context.is_sampled() {
+            return;
+        }
+        if inner.sender.try_send(encode_span(&span)).is_err() {
+            debug!("Span queue full, dropping span '{}'", span.name);
+        }
+    }
+
+    fn should_sample(&self, trace_id: &str) -> bool {
+        let ratio = self.inner.as_ref().map(|i| i.sampling_ratio).unwrap_or(1.0);
+        if ratio >= 1.0 {
+            return true;
+        }
+        if ratio <= 0.0 {
+            return false;
+        }
+        // Same rule as OpenTelemetry's TraceIdRatioBased sampler: compare the
+        // low 64 bits of the trace ID against the ratio
+        let low = trace_id.get(trace_id.len().saturating_sub(16)..).unwrap_or("");
+        let value = u64::from_str_radix(low, 16).unwrap_or(0);
+        (value as f64) < ratio * u64::MAX as f64
+    }
+}
+
+/// Background task batching spans into OTLP/HTTP JSON export requests
+struct Exporter {
+    client: reqwest::Client,
+    endpoint: String,
+    headers: HashMap<String, String>,
+    resource: Value,
+}
+
+impl Exporter {
+    async fn run(self, mut receiver: mpsc::Receiver<Value>, flush_interval: Duration) {
+        let mut batch = Vec::new();
+        let mut ticker = tokio::time::interval(flush_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                span = receiver.recv() => match span {
+                    Some(span) => {
+                        batch.push(span);
+                        if batch.len() >= MAX_BATCH_SIZE {
+                            self.export(std::mem::take(&mut batch)).await;
+                        }
+                    }
+                    None => {
+                        // Every Tracer handle is gone; flush what's left and stop
+                        self.export(batch).await;
+                        return;
+                    }
+                },
+                _ = ticker.tick() => {
+                    if !batch.is_empty() {
+                        self.export(std::mem::take(&mut batch)).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn export(&self, spans: Vec<Value>) {
+        if spans.is_empty() {
+            return;
+        }
+        let count = spans.len();
+        let body = json!({
+            "resourceSpans": [{
+                "resource": self.resource,
+                "scopeSpans": [{
+                    "scope": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                    "spans": spans,
+                }],
+            }],
+        });
+
+        let mut request = self.client.post(&self.endpoint).timeout(EXPORT_TIMEOUT).json(&body);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!("Exported {} spans to {}", count, self.endpoint);
+            }
+            Ok(response) => {
+                warn!("OTLP collector rejected {} spans: HTTP {}", count, response.status());
+            }
+            Err(e) => {
+                warn!("Failed to export {} spans to {}: {}", count, self.endpoint, e);
+            }
+        }
+    }
+}
+
+/// Append the OTLP traces path unless the endpoint already points at it
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// Encode a finished span in the OTLP JSON representation
+fn encode_span(span: &RequestSpan) -> Value {
+    let duration = span.elapsed();
+    let start_nanos = span
+        .start_time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let end_nanos = start_nanos + duration.as_nanos();
+
+    let mut attributes = Vec::new();
+    let optional = [
+        ("http.method", &span.http_method),
+        ("http.url", &span.http_url),
+        ("trafficcop.router", &span.router_name),
+        ("trafficcop.service", &span.service_name),
+        ("trafficcop.backend", &span.backend),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            attributes.push(string_attribute(key, value));
+        }
+    }
+    if let Some(status) = span.http_status {
+        attributes.push(json!({
+            "key": "http.status_code",
+            "value": { "intValue": status.to_string() },
+        }));
+    }
+    if let Some(addr) = span.remote_addr {
+        attributes.push(string_attribute("net.peer.ip", &addr.ip().to_string()));
+    }
+    attributes.push(json!({
+        "key": "trafficcop.duration_ms",
+        "value": { "doubleValue": duration.as_secs_f64() * 1000.0 },
+    }));
+    for (key, value) in span.attributes() {
+        attributes.push(string_attribute(key, value));
+    }
+
+    let status = match &span.error {
+        Some(message) => json!({ "code": 2, "message": message }),
+        None if span.has_error() => json!({ "code": 2 }),
+        None => json!({ "code": 0 }),
+    };
+
+    let mut encoded = json!({
+        "traceId": pad_id(&span.context.trace_id, 32),
+        "spanId": pad_id(&span.span_id, 16),
+        "name": span.name,
+        "kind": match span.kind {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+        },
+        "startTimeUnixNano": start_nanos.to_string(),
+        "endTimeUnixNano": end_nanos.to_string(),
+        "attributes": attributes,
+        "status": status,
+    });
+    if let Some(parent) = &span.parent_span_id {
+        encoded["parentSpanId"] = Value::String(pad_id(parent, 16));
+    }
+    encoded
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// Left-pad short IDs (B3/Jaeger allow dropping leading zeros) to the OTLP width
+fn pad_id(id: &str, width: usize) -> String {
+    format!("{:0>width$}", id, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Incoming;
+    use hyper::header::HeaderValue;
+    use hyper::service::service_fn;
+    use hyper::{Request, Response};
+    use hyper_util::rt::TokioIo;
+    use std::convert::Infallible;
+    use tokio::net::TcpListener;
+
+    /// Accept OTLP/HTTP export requests, forwarding (auth header, body) pairs
+    async fn mock_collector() -> (String, mpsc::UnboundedReceiver<(Option<String>, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| {
+                        let tx = tx.clone();
+                        async move {
+                            assert_eq!(req.uri().path(), "/v1/traces");
+                            let auth = req
+                                .headers()
+                                .get("authorization")
+                                .map(|v| v.to_str().unwrap().to_string());
+                            let body = req.into_body().collect().await.unwrap().to_bytes();
+                            let _ = tx.send((auth, serde_json::from_slice(&body).unwrap()));
+                            Ok::<_, Infallible>(Response::new(Full::new(Bytes::from("{}"))))
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        (format!("http://{}", addr), rx)
+    }
+
+    fn config(endpoint: &str, sampling_ratio: f64) -> Option<TracingConfig> {
+        Some(TracingConfig {
+            endpoint: Some(endpoint.to_string()),
+            propagation: vec![TracePropagation::TraceContext],
+            headers: HashMap::from([("authorization".to_string(), "Bearer secret".to_string())]),
+            sampling_ratio,
+            service_name: "trafficcop".to_string(),
+            flush_interval: crate::config::Duration::from_millis(50),
+        })
+    }
+
+    fn attribute<'a>(span: &'a Value, key: &str) -> Option<&'a Value> {
+        span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["key"] == key)
+            .map(|a| &a["value"])
+    }
+
+    #[tokio::test]
+    async fn test_exports_request_and_backend_spans() {
+        let (endpoint, mut exports) = mock_collector().await;
+        let tracer = Tracer::new(&config(&endpoint, 1.0));
+        assert!(tracer.is_enabled());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+
+        let mut server = tracer
+            .start_server_span(&headers, "GET /api")
+            .with_method("GET")
+            .with_router("api")
+            .with_service("api-svc");
+        let mut backend = RequestSpan::client(&server.context, "backend")
+            .with_service("api-svc")
+            .with_backend("http://10.0.0.1:8080");
+        backend.record_status(502);
+        backend.record_error("connection refused");
+        server.record_status(502);
+
+        let server_id = server.span_id.clone();
+        tracer.finish(backend);
+        tracer.finish(server);
+
+        let (auth, body) = tokio::time::timeout(Duration::from_secs(5), exports.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth.as_deref(), Some("Bearer secret"));
+
+        let resource = &body["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "trafficcop"
+        );
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 2);
+        let backend = &spans[0];
+        let server = &spans[1];
+
+        // The server span continues the caller's trace; the backend span hangs off it
+        assert_eq!(server["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(server["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(server["spanId"], server_id.as_str());
+        assert_eq!(server["kind"], 2);
+        assert_eq!(backend["traceId"], server["traceId"]);
+        assert_eq!(backend["parentSpanId"], server["spanId"]);
+        assert_eq!(backend["kind"], 3);
+
+        assert_eq!(attribute(server, "trafficcop.router").unwrap()["stringValue"], "api");
+        assert_eq!(attribute(server, "http.status_code").unwrap()["intValue"], "502");
+        assert_eq!(
+            attribute(backend, "trafficcop.backend").unwrap()["stringValue"],
+            "http://10.0.0.1:8080"
+        );
+        assert!(attribute(backend, "trafficcop.duration_ms").is_some());
+        assert_eq!(backend["status"]["code"], 2);
+        assert_eq!(backend["status"]["message"], "connection refused");
+    }
+
+    #[tokio::test]
+    async fn test_unsampled_traces_are_not_exported() {
+        let (endpoint, mut exports) = mock_collector().await;
+        let tracer = Tracer::new(&config(&endpoint, 0.0));
+
+        // New traces fall under the 0% ratio
+        let span = tracer.start_server_span(&HeaderMap::new(), "dropped");
+        assert!(!span.context.is_sampled());
+        tracer.finish(span);
+
+        // A caller that sampled its trace overrides the local ratio
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        tracer.finish(tracer.start_server_span(&headers, "kept"));
+
+        let (_, body) = tokio::time::timeout(Duration::from_secs(5), exports.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let spans = body["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0]["name"], "kept");
+    }
+
+    #[test]
+    fn test_disabled_tracer() {
+        let tracer = Tracer::new(&None);
+        assert!(!tracer.is_enabled());
+        // Spans are still created (for propagation) but finishing is a no-op
+        let span = tracer.start_server_span(&HeaderMap::new(), "noop");
+        assert!(span.context.is_sampled());
+        tracer.finish(span);
+    }
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(traces_url("http://collector:4318"), "http://collector:4318/v1/traces");
+        assert_eq!(traces_url("http://collector:4318/"), "http://collector:4318/v1/traces");
+        assert_eq!(
+            traces_url("https://otlp.example.com/v1/traces"),
+            "https://otlp.example.com/v1/traces"
+        );
+    }
+}
@@ -1,6 +1,21 @@
 use hyper::header::HeaderValue;
 use hyper::HeaderMap;
 
+use crate::config::TracePropagation;
+
+/// Every trace header we understand; cleared before injecting a new context
+const TRACE_HEADERS: &[&str] = &[
+    "traceparent",
+    "tracestate",
+    "b3",
+    "x-b3-traceid",
+    "x-b3-spanid",
+    "x-b3-parentspanid",
+    "x-b3-sampled",
+    "x-b3-flags",
+    "uber-trace-id",
+];
+
 /// W3C Trace Context for distributed tracing
 /// See: <https://www.w3.org/TR/trace-context/>
 #[derive(Debug, Clone)]
@@ -127,6 +142,44 @@ impl TraceContext {
         })
     }
 
+    /// Parse a B3 single header value
+    /// Format: trace_id-span_id[-sampling_state[-parent_span_id]]
+    /// Example: 80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1
+    fn parse_b3_single(value: &str) -> Option<Self> {
+        let mut parts = value.split('-');
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let sampled = match parts.next() {
+            None | Some("1") | Some("d") => true,
+            Some("0") => false,
+            Some(_) => return None,
+        };
+
+        if !matches!(trace_id.len(), 16 | 32) || !trace_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        if span_id.len() != 16 || !span_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: format!("{:0>32}", trace_id.to_lowercase()),
+            parent_id: span_id.to_lowercase(),
+            trace_flags: if sampled { 0x01 } else { 0x00 },
+            trace_state: None,
+        })
+    }
+
+    /// Format as B3 single header value
+    pub fn to_b3_single(&self) -> String {
+        format!(
+            "{}-{}-{}",
+            self.trace_id,
+            self.parent_id,
+            if self.is_sampled() { "1" } else { "0" }
+        )
+    }
+
     /// Format as traceparent header value
     pub fn to_traceparent(&self) -> String {
         format!(
@@ -149,6 +202,12 @@ impl Default for TraceContext {
 
 /// Extract trace context from incoming request headers
 pub fn extract_context(headers: &HeaderMap) -> TraceContext {
+    // No trace context found, create new one
+    try_extract_context(headers).unwrap_or_default()
+}
+
+/// Extract trace context from incoming request headers, if the caller sent one
+pub fn try_extract_context(headers: &HeaderMap) -> Option<TraceContext> {
     // Try W3C traceparent header first
     if let Some(traceparent) = headers.get("traceparent")
         && let Ok(value) = traceparent.to_str()
@@ -158,9 +217,17 @@ pub fn extract_context(headers: &HeaderMap) -> TraceContext {
                     && let Ok(state) = tracestate.to_str() {
                         ctx.trace_state = Some(state.to_string());
                     }
-                return ctx;
+                return Some(ctx);
             }
 
+    // Try B3 single header
+    if let Some(b3) = headers.get("b3")
+        && let Ok(value) = b3.to_str()
+        && let Some(ctx) = TraceContext::parse_b3_single(value)
+    {
+        return Some(ctx);
+    }
+
     // Try B3 propagation format (used by Zipkin)
     if let (Some(trace_id), Some(span_id)) = (
         headers.get("x-b3-traceid"),
@@ -180,12 +247,12 @@ pub fn extract_context(headers: &HeaderMap) -> TraceContext {
                 tid.to_string()
             };
 
-            return TraceContext {
+            return Some(TraceContext {
                 trace_id: normalized_trace_id.to_lowercase(),
                 parent_id: sid.to_lowercase(),
                 trace_flags: if sampled { 0x01 } else { 0x00 },
                 trace_state: None,
-            };
+            });
         }
 
     // Try Jaeger format
@@ -205,17 +272,16 @@ pub fn extract_context(headers: &HeaderMap) -> TraceContext {
                     trace_id.to_string()
                 };
 
-                return TraceContext {
+                return Some(TraceContext {
                     trace_id: normalized_trace_id.to_lowercase(),
                     parent_id: span_id.to_lowercase(),
                     trace_flags: flags,
                     trace_state: None,
-                };
+                });
             }
         }
 
-    // No trace context found, create new one
-    TraceContext::new()
+    None
 }
 
 /// Inject trace context into outgoing request headers
@@ -246,6 +312,43 @@ pub fn inject_context(headers: &mut HeaderMap, ctx: &TraceContext) {
     headers.insert("x-b3-sampled", sampled);
 }
 
+/// Replace any trace headers with `ctx`, written in each of `formats`
+pub fn inject_context_as(headers: &mut HeaderMap, ctx: &TraceContext, formats: &[TracePropagation]) {
+    for name in TRACE_HEADERS {
+        headers.remove(*name);
+    }
+
+    for format in formats {
+        match format {
+            TracePropagation::TraceContext => {
+                if let Ok(value) = HeaderValue::from_str(&ctx.to_traceparent()) {
+                    headers.insert("traceparent", value);
+                }
+                if let Some(ref state) = ctx.trace_state
+                    && let Ok(value) = HeaderValue::from_str(state)
+                {
+                    headers.insert("tracestate", value);
+                }
+            }
+            TracePropagation::B3 => {
+                if let Ok(value) = HeaderValue::from_str(&ctx.to_b3_single()) {
+                    headers.insert("b3", value);
+                }
+            }
+            TracePropagation::B3Multi => {
+                if let Ok(value) = HeaderValue::from_str(&ctx.trace_id) {
+                    headers.insert("x-b3-traceid", value);
+                }
+                if let Ok(value) = HeaderValue::from_str(&ctx.parent_id) {
+                    headers.insert("x-b3-spanid", value);
+                }
+                let sampled = if ctx.is_sampled() { "1" } else { "0" };
+                headers.insert("x-b3-sampled", HeaderValue::from_static(sampled));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -322,6 +425,12 @@ mod tests {
         assert!(ctx.is_sampled());
     }
 
+    #[test]
+    fn test_try_extract_without_context() {
+        assert!(try_extract_context(&HeaderMap::new()).is_none());
+        assert_eq!(extract_context(&HeaderMap::new()).trace_id.len(), 32);
+    }
+
     #[test]
     fn test_inject_context() {
         let ctx = TraceContext {
@@ -341,6 +450,66 @@ mod tests {
         assert!(headers.contains_key("x-b3-sampled"));
     }
 
+    #[test]
+    fn test_extract_b3_single_context() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "b3",
+            HeaderValue::from_static("80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-0-05e3ac9a4f6e3b90"),
+        );
+
+        let ctx = extract_context(&headers);
+        assert_eq!(ctx.trace_id, "80f198ee56343ba864fe8b2a57d3eff7");
+        assert_eq!(ctx.parent_id, "e457b5a2e4d86bd1");
+        assert!(!ctx.is_sampled());
+
+        // 64-bit trace IDs are widened; a missing sampling state means sampled
+        headers.insert("b3", HeaderValue::from_static("a3ce929d0e0e4736-00f067aa0ba902b7"));
+        let ctx = extract_context(&headers);
+        assert_eq!(ctx.trace_id, "0000000000000000a3ce929d0e0e4736");
+        assert!(ctx.is_sampled());
+
+        // A bare sampling decision carries no context
+        headers.insert("b3", HeaderValue::from_static("0"));
+        assert!(try_extract_context(&headers).is_none());
+    }
+
+    #[test]
+    fn test_inject_context_as() {
+        let ctx = TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            parent_id: "00f067aa0ba902b7".to_string(),
+            trace_flags: 0x01,
+            trace_state: Some("vendor=value".to_string()),
+        };
+
+        // Stale inbound headers are replaced, not forwarded alongside
+        let mut headers = HeaderMap::new();
+        headers.insert("x-b3-traceid", HeaderValue::from_static("463ac35c9f6413ad48485a3953bb6124"));
+        headers.insert("uber-trace-id", HeaderValue::from_static("6f6f6d646e6f6873:1:0:1"));
+        inject_context_as(&mut headers, &ctx, &[TracePropagation::TraceContext]);
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(headers["tracestate"], "vendor=value");
+        assert!(!headers.contains_key("x-b3-traceid"));
+        assert!(!headers.contains_key("uber-trace-id"));
+
+        let mut headers = HeaderMap::new();
+        inject_context_as(&mut headers, &ctx, &[TracePropagation::B3]);
+        assert_eq!(headers["b3"], "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1");
+        assert!(!headers.contains_key("traceparent"));
+
+        let mut headers = HeaderMap::new();
+        inject_context_as(&mut headers, &ctx, &[TracePropagation::B3Multi, TracePropagation::TraceContext]);
+        assert_eq!(headers["x-b3-traceid"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(headers["x-b3-spanid"], "00f067aa0ba902b7");
+        assert_eq!(headers["x-b3-sampled"], "1");
+        assert!(headers.contains_key("traceparent"));
+        assert!(!headers.contains_key("b3"));
+    }
+
     #[test]
     fn test_child_context() {
         let parent = TraceContext::new();
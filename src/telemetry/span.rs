@@ -1,5 +1,5 @@
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 use super::TraceContext;
 
@@ -31,20 +31,28 @@ pub struct RequestSpan {
     pub context: TraceContext,
     /// Span ID for this specific span
     pub span_id: String,
+    /// Span ID of the parent span, if any
+    pub parent_span_id: Option<String>,
     /// Span kind
     pub kind: SpanKind,
     /// Operation name
     pub name: String,
     /// Start time
     pub start: Instant,
+    /// Wall-clock start time (for export)
+    pub start_time: SystemTime,
     /// HTTP method
     pub http_method: Option<String>,
     /// HTTP URL
     pub http_url: Option<String>,
     /// HTTP status code (set when span ends)
     pub http_status: Option<u16>,
+    /// Matched router name
+    pub router_name: Option<String>,
     /// Target service name
     pub service_name: Option<String>,
+    /// Backend server URL
+    pub backend: Option<String>,
     /// Remote address
     pub remote_addr: Option<SocketAddr>,
     /// Error message if any
@@ -58,14 +66,18 @@ impl RequestSpan {
     pub fn server(context: TraceContext, name: impl Into<String>) -> Self {
         Self {
             span_id: context.parent_id.clone(),
+            parent_span_id: None,
             context,
             kind: SpanKind::Server,
             name: name.into(),
             start: Instant::now(),
+            start_time: SystemTime::now(),
             http_method: None,
             http_url: None,
             http_status: None,
+            router_name: None,
             service_name: None,
+            backend: None,
             remote_addr: None,
             error: None,
             attributes: Vec::new(),
@@ -77,14 +89,18 @@ impl RequestSpan {
         let child_ctx = parent.child();
         Self {
             span_id: child_ctx.parent_id.clone(),
+            parent_span_id: Some(parent.parent_id.clone()),
             context: child_ctx,
             kind: SpanKind::Client,
             name: name.into(),
             start: Instant::now(),
+            start_time: SystemTime::now(),
             http_method: None,
             http_url: None,
             http_status: None,
+            router_name: None,
             service_name: None,
+            backend: None,
             remote_addr: None,
             error: None,
             attributes: Vec::new(),
@@ -96,14 +112,18 @@ impl RequestSpan {
         let child_ctx = parent.child();
         Self {
             span_id: child_ctx.parent_id.clone(),
+            parent_span_id: Some(parent.parent_id.clone()),
             context: child_ctx,
             kind: SpanKind::Internal,
             name: name.into(),
             start: Instant::now(),
+            start_time: SystemTime::now(),
             http_method: None,
             http_url: None,
             http_status: None,
+            router_name: None,
             service_name: None,
+            backend: None,
             remote_addr: None,
             error: None,
             attributes: Vec::new(),
@@ -122,12 +142,30 @@ impl RequestSpan {
         self
     }
 
+    /// Set the span ID of the remote parent (for server spans continuing a trace)
+    pub fn with_parent_span_id(mut self, parent_span_id: Option<String>) -> Self {
+        self.parent_span_id = parent_span_id;
+        self
+    }
+
+    /// Set router name
+    pub fn with_router(mut self, router: impl Into<String>) -> Self {
+        self.router_name = Some(router.into());
+        self
+    }
+
     /// Set service name
     pub fn with_service(mut self, service: impl Into<String>) -> Self {
         self.service_name = Some(service.into());
         self
     }
 
+    /// Set backend server URL
+    pub fn with_backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = Some(backend.into());
+        self
+    }
+
     /// Set remote address
     pub fn with_remote_addr(mut self, addr: SocketAddr) -> Self {
         self.remote_addr = Some(addr);
@@ -140,6 +178,11 @@ impl RequestSpan {
         self
     }
 
+    /// Custom attributes added with [`with_attribute`](Self::with_attribute)
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
     /// Record HTTP status code
     pub fn record_status(&mut self, status: u16) {
         self.http_status = Some(status);
@@ -225,6 +268,7 @@ mod tests {
         assert_eq!(span.kind, SpanKind::Client);
         assert_eq!(span.context.trace_id, parent.trace_id);
         assert_ne!(span.span_id, parent.parent_id);
+        assert_eq!(span.parent_span_id.as_deref(), Some(parent.parent_id.as_str()));
     }
 
     #[test]
@@ -1,22 +1,34 @@
 use super::client::PendingChallenge;
+use crate::store::Store;
 use bytes::Bytes;
 use http_body_util::Full;
 use hyper::{Request, Response, StatusCode};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::debug;
+use tracing::{debug, warn};
 
 /// HTTP-01 ACME challenge handler
 /// Responds to requests at /.well-known/acme-challenge/{token}
 pub struct ChallengeHandler {
     pending: Arc<RwLock<HashMap<String, PendingChallenge>>>,
+    store: Option<Arc<dyn Store>>,
 }
 
 impl ChallengeHandler {
     /// Create a handler backed by the given pending challenges map.
     pub fn new(pending: Arc<RwLock<HashMap<String, PendingChallenge>>>) -> Self {
-        Self { pending }
+        Self {
+            pending,
+            store: None,
+        }
+    }
+
+    /// Also answer tokens published to the (cluster-shared) store, so any node
+    /// can serve a challenge another node's ACME client is waiting on.
+    pub fn with_store(mut self, store: Arc<dyn Store>) -> Self {
+        self.store = Some(store);
+        self
     }
 
     /// Returns true if the request path matches `/.well-known/acme-challenge/`.
@@ -43,15 +55,31 @@ impl ChallengeHandler {
             return Some(not_found());
         }
 
-        let challenges = self.pending.read().await;
+        let local = self
+            .pending
+            .read()
+            .await
+            .get(token)
+            .map(|challenge| challenge.key_authorization.clone());
+        let key_authorization = match (local, &self.store) {
+            (Some(key_authorization), _) => Some(key_authorization),
+            (None, Some(store)) => match store.acme_challenge_get(token).await {
+                Ok(key_authorization) => key_authorization,
+                Err(e) => {
+                    warn!("Failed to look up ACME challenge {} in store: {}", token, e);
+                    None
+                }
+            },
+            (None, None) => None,
+        };
 
-        if let Some(challenge) = challenges.get(token) {
+        if let Some(key_authorization) = key_authorization {
             debug!("Responding to ACME challenge for token: {}", token);
 
             let response = Response::builder()
                 .status(StatusCode::OK)
                 .header("Content-Type", "text/plain")
-                .body(Full::new(Bytes::from(challenge.key_authorization.clone())))
+                .body(Full::new(Bytes::from(key_authorization)))
                 .unwrap();
 
             Some(response)
@@ -74,11 +102,72 @@ fn not_found() -> Response<Full<Bytes>> {
 pub async fn try_handle_challenge<B>(
     req: &Request<B>,
     pending: &Arc<RwLock<HashMap<String, PendingChallenge>>>,
+    store: &Arc<dyn Store>,
 ) -> Option<Response<Full<Bytes>>> {
     if !ChallengeHandler::is_challenge_request(req) {
         return None;
     }
 
-    let handler = ChallengeHandler::new(Arc::clone(pending));
+    let handler = ChallengeHandler::new(Arc::clone(pending)).with_store(Arc::clone(store));
     handler.handle(req).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::LocalStore;
+    use http_body_util::BodyExt;
+    use std::time::Duration;
+
+    fn challenge_request(token: &str) -> Request<()> {
+        Request::builder()
+            .uri(format!("/.well-known/acme-challenge/{}", token))
+            .body(())
+            .unwrap()
+    }
+
+    async fn body(response: Response<Full<Bytes>>) -> Bytes {
+        response.into_body().collect().await.unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_token_published_by_another_node_is_answered() {
+        let pending = Arc::new(RwLock::new(HashMap::new()));
+        // Stands in for the shared store another node's ACME client published to
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+        store
+            .acme_challenge_set("remote-token", "remote-token.thumbprint", Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let response = try_handle_challenge(&challenge_request("remote-token"), &pending, &store)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "remote-token.thumbprint");
+
+        let response = try_handle_challenge(&challenge_request("unknown"), &pending, &store)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_local_challenge_answered_without_store() {
+        let pending = Arc::new(RwLock::new(HashMap::new()));
+        pending.write().await.insert(
+            "local-token".to_string(),
+            PendingChallenge {
+                token: "local-token".to_string(),
+                key_authorization: "local-token.thumbprint".to_string(),
+            },
+        );
+        let store: Arc<dyn Store> = Arc::new(LocalStore::new());
+
+        let response = try_handle_challenge(&challenge_request("local-token"), &pending, &store)
+            .await
+            .unwrap();
+        assert_eq!(body(response).await, "local-token.thumbprint");
+        assert!(try_handle_challenge(&Request::new(()), &pending, &store).await.is_none());
+    }
+}
@@ -1,11 +1,12 @@
+use super::dns::Dns01Solver;
 use super::storage::{AcmeAccount, StorageManager, StoredCertificate};
+use crate::store::Store;
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use ring::rand::SystemRandom;
 use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
@@ -13,6 +14,9 @@ use tracing::{debug, info, warn};
 const LETS_ENCRYPT_STAGING: &str = "https://acme-staging-v02.api.letsencrypt.org/directory";
 const LETS_ENCRYPT_PRODUCTION: &str = "https://acme-v02.api.letsencrypt.org/directory";
 
+/// How long a published HTTP-01 challenge stays in the shared store (outlasts validation polling)
+const CHALLENGE_STORE_TTL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
 /// ACME directory endpoint URLs fetched from the CA server.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -70,6 +74,9 @@ pub struct AcmeAuthorization {
     pub status: String,
     /// Available challenges for this authorization.
     pub challenges: Vec<AcmeChallenge>,
+    /// Whether this authorization is for a wildcard name (DNS-01 only).
+    #[serde(default)]
+    pub wildcard: bool,
 }
 
 /// A single ACME challenge (HTTP-01, DNS-01, etc.).
@@ -105,6 +112,8 @@ pub struct AcmeClient {
     key_pair: Option<EcdsaKeyPair>,
     account_url: Option<String>,
     pending_challenges: Arc<RwLock<std::collections::HashMap<String, PendingChallenge>>>,
+    dns_solver: Option<Arc<Dns01Solver>>,
+    store: Option<Arc<dyn Store>>,
 }
 
 impl AcmeClient {
@@ -128,9 +137,22 @@ impl AcmeClient {
             key_pair: None,
             account_url: None,
             pending_challenges: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            dns_solver: None,
+            store: None,
         }
     }
 
+    /// Solve authorizations with DNS-01 instead of HTTP-01 (required for wildcards).
+    pub fn set_dns_solver(&mut self, solver: Arc<Dns01Solver>) {
+        self.dns_solver = Some(solver);
+    }
+
+    /// Publish HTTP-01 challenges to a store shared by the cluster, so whichever
+    /// node receives the validation request can answer it.
+    pub fn set_store(&mut self, store: Arc<dyn Store>) {
+        self.store = Some(store);
+    }
+
     /// Fetch the ACME directory and load or create an account.
     pub async fn init(&mut self) -> Result<()> {
         // Fetch directory
@@ -438,7 +460,7 @@ impl AcmeClient {
         let cert_pem = response.text().await?;
 
         // Parse certificate to get validity dates
-        let (not_before, not_after) = self.parse_certificate_dates(&cert_pem)?;
+        let (not_before, not_after) = Self::parse_certificate_dates(&cert_pem)?;
 
         let stored = StoredCertificate {
             domain: domains[0].clone(),
@@ -455,13 +477,13 @@ impl AcmeClient {
         info!(
             "Certificate obtained for {:?}, valid until {}",
             domains,
-            chrono_from_unix(not_after)
+            format_rfc3339(not_after)
         );
 
         Ok(stored)
     }
 
-    /// Process an authorization (complete HTTP-01 challenge)
+    /// Process an authorization (complete a DNS-01 or HTTP-01 challenge)
     async fn process_authorization(&mut self, authz_url: &str) -> Result<()> {
         let response = self.signed_request(authz_url, None, false).await?;
         let authz: AcmeAuthorization = response.json().await?;
@@ -471,6 +493,17 @@ impl AcmeClient {
             return Ok(());
         }
 
+        if let Some(solver) = self.dns_solver.clone() {
+            return self.process_dns_authorization(&authz, &solver).await;
+        }
+
+        if authz.wildcard {
+            return Err(anyhow::anyhow!(
+                "Wildcard certificate for {} requires a DNS-01 challenge (configure dnsChallenge)",
+                authz.identifier.value
+            ));
+        }
+
         // Find HTTP-01 challenge
         let challenge = authz
             .challenges
@@ -495,27 +528,65 @@ impl AcmeClient {
                 },
             );
         }
+        if let Some(store) = &self.store
+            && let Err(e) = store
+                .acme_challenge_set(&challenge.token, &key_auth, CHALLENGE_STORE_TTL)
+                .await
+        {
+            warn!(
+                "Failed to publish ACME challenge for {} to store, only this node can answer it: {}",
+                authz.identifier.value, e
+            );
+        }
 
         info!(
             "HTTP-01 challenge ready for {} at /.well-known/acme-challenge/{}",
             authz.identifier.value, challenge.token
         );
 
-        // Tell ACME server we're ready
+        // Tell ACME server we're ready, then poll for challenge completion
         let payload = serde_json::json!({});
-        self.signed_request(&challenge.url, Some(payload), false)
-            .await?;
-
-        // Poll for challenge completion
-        self.wait_for_challenge_valid(&challenge.url).await?;
+        let result = match self.signed_request(&challenge.url, Some(payload), false).await {
+            Ok(_) => self.wait_for_challenge_valid(&challenge.url).await,
+            Err(e) => Err(e),
+        };
 
         // Clean up pending challenge
         {
             let mut challenges = self.pending_challenges.write().await;
             challenges.remove(&challenge.token);
         }
+        if let Some(store) = &self.store
+            && let Err(e) = store.acme_challenge_delete(&challenge.token).await
+        {
+            debug!("Failed to remove ACME challenge from store: {}", e);
+        }
 
-        Ok(())
+        result
+    }
+
+    /// Complete a DNS-01 challenge by publishing the TXT record through the configured provider
+    async fn process_dns_authorization(&self, authz: &AcmeAuthorization, solver: &Dns01Solver) -> Result<()> {
+        let challenge = authz
+            .challenges
+            .iter()
+            .find(|c| c.challenge_type == "dns-01")
+            .ok_or_else(|| anyhow::anyhow!("No DNS-01 challenge available"))?;
+
+        if challenge.status == "valid" {
+            return Ok(());
+        }
+
+        let key_auth = self.get_key_authorization(&challenge.token)?;
+
+        solver
+            .solve(&authz.identifier.value, &key_auth, || async {
+                // Tell ACME server we're ready
+                self.signed_request(&challenge.url, Some(serde_json::json!({})), false)
+                    .await?;
+                self.wait_for_challenge_valid(&challenge.url).await
+            })
+            .await
     }
 
     /// Wait for a challenge to become valid
@@ -626,16 +697,20 @@ impl AcmeClient {
     }
 
     /// Parse certificate dates from PEM
-    fn parse_certificate_dates(&self, _pem: &str) -> Result<(u64, u64)> {
-        // Simple parsing - in production you'd use x509-parser
-        // For now, use 90 days validity (standard Let's Encrypt)
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        let not_before = now;
-        let not_after = now + 90 * 24 * 60 * 60; // 90 days
+    /// Extract `(notBefore, notAfter)` as unix seconds from the leaf certificate
+    /// (the first PEM block) of the issued chain.
+    fn parse_certificate_dates(pem: &str) -> Result<(u64, u64)> {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(pem.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Failed to parse certificate PEM: {}", e))?;
+        let cert = pem
+            .parse_x509()
+            .map_err(|e| anyhow::anyhow!("Failed to parse issued certificate: {}", e))?;
+
+        let validity = cert.validity();
+        let not_before = u64::try_from(validity.not_before.timestamp())
+            .context("Certificate notBefore is before the unix epoch")?;
+        let not_after = u64::try_from(validity.not_after.timestamp())
+            .context("Certificate notAfter is before the unix epoch")?;
 
         Ok((not_before, not_after))
     }
@@ -825,10 +900,76 @@ fn pem_encode(label: &str, data: &[u8]) -> String {
     pem
 }
 
-fn chrono_from_unix(timestamp: u64) -> String {
-    // Simple date formatting
-    let secs = timestamp as i64;
-    let days = secs / 86400;
-    let years = 1970 + days / 365;
-    format!("{}-xx-xx", years)
+/// Format unix seconds as an RFC 3339 UTC timestamp, e.g. `2026-10-17T02:06:51Z`
+pub(crate) fn format_rfc3339(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs_today = timestamp % 86400;
+
+    // Civil-from-days (Howard Hinnant), proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_today / 3600,
+        (secs_today % 3600) / 60,
+        secs_today % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed P-256 certificate for example.com, followed by a second block
+    /// to mimic the chain an ACME server returns
+    const FIXTURE_CHAIN: &str = "-----BEGIN CERTIFICATE-----
+MIIBgjCCASegAwIBAgIUUKMRtJm5z3pDNrEwovVnI0+j1f4wCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wHhcNMjYxMDE3MDIwNjUxWhcNMjcwMTE1
+MDIwNjUxWjAWMRQwEgYDVQQDDAtleGFtcGxlLmNvbTBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABPMEskgI+4rLIf9ZqgaYnjwsGHqCjW88UsTNWlj0hYwUMrWm4RLQ
+toBkA287oUYvyP9VuiG73eTZHB5bs+6ReWyjUzBRMB0GA1UdDgQWBBQ7N5jjBvYO
++Hko+LGLZHgnFfpNaDAfBgNVHSMEGDAWgBQ7N5jjBvYO+Hko+LGLZHgnFfpNaDAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQDXcTj93+U7EMegk/AO
+f0u4JbSNaNH/WlvR4IrsIFaPYQIhAJtgy5rlbgSKXJ4ukQZmtYTsrEfIJAxMUxaC
+RtqVPzWJ
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+AAAA
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_parse_certificate_dates() {
+        let (not_before, not_after) = AcmeClient::parse_certificate_dates(FIXTURE_CHAIN).unwrap();
+
+        // notBefore=Oct 17 02:06:51 2026 GMT, notAfter=Jan 15 02:06:51 2027 GMT
+        assert_eq!(not_before, 1_792_202_811);
+        assert_eq!(not_after, 1_799_978_811);
+        assert_eq!(format_rfc3339(not_before), "2026-10-17T02:06:51Z");
+        assert_eq!(format_rfc3339(not_after), "2027-01-15T02:06:51Z");
+    }
+
+    #[test]
+    fn test_parse_certificate_dates_rejects_garbage() {
+        assert!(AcmeClient::parse_certificate_dates("not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        // Leap day and the end of a leap year
+        assert_eq!(format_rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_rfc3339(1_735_689_599), "2024-12-31T23:59:59Z");
+    }
 }
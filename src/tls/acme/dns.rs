@@ -0,0 +1,551 @@
+//! DNS-01 challenge support: provider abstraction, TXT record digest, and
+//! propagation checks against recursive resolvers.
+
+use crate::config::DnsChallenge;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+/// Resolvers used for propagation checks when none are configured
+const DEFAULT_RESOLVERS: &[&str] = &["1.1.1.1:53", "8.8.8.8:53"];
+
+/// A DNS API capable of publishing the `_acme-challenge` TXT record.
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    /// Create a TXT record `fqdn` with the given value.
+    async fn create_txt_record(&self, fqdn: &str, value: &str) -> Result<()>;
+
+    /// Delete the TXT record `fqdn` with the given value.
+    async fn delete_txt_record(&self, fqdn: &str, value: &str) -> Result<()>;
+}
+
+/// Build the provider named in the DNS challenge config.
+pub fn provider_from_config(config: &DnsChallenge) -> Result<Arc<dyn DnsProvider>> {
+    match config.provider.to_ascii_lowercase().as_str() {
+        "cloudflare" => Ok(Arc::new(CloudflareProvider::from_env()?)),
+        other => Err(anyhow::anyhow!("Unsupported DNS challenge provider: {}", other)),
+    }
+}
+
+/// TXT record value for a DNS-01 challenge: base64url(SHA-256(key authorization)) (RFC 8555 §8.4)
+pub fn dns01_txt_value(key_authorization: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, key_authorization.as_bytes());
+    URL_SAFE_NO_PAD.encode(digest.as_ref())
+}
+
+/// Name of the TXT record to publish for `domain`; wildcards validate on the base domain
+pub fn challenge_record_name(domain: &str) -> String {
+    let domain = domain.strip_prefix("*.").unwrap_or(domain).trim_end_matches('.');
+    format!("_acme-challenge.{}", domain)
+}
+
+/// Drives a DNS-01 challenge: publish the record, wait for propagation,
+/// let the CA validate, and always remove the record afterwards.
+pub struct Dns01Solver {
+    provider: Arc<dyn DnsProvider>,
+    delay_before_check: Duration,
+    resolvers: Vec<SocketAddr>,
+    check_propagation: bool,
+    poll_interval: Duration,
+    propagation_timeout: Duration,
+}
+
+impl Dns01Solver {
+    /// Create a solver from the DNS challenge config and a provider.
+    pub fn new(config: &DnsChallenge, provider: Arc<dyn DnsProvider>) -> Result<Self> {
+        let resolvers = if config.resolvers.is_empty() {
+            DEFAULT_RESOLVERS.iter().map(|r| parse_resolver(r)).collect::<Result<_>>()?
+        } else {
+            config.resolvers.iter().map(|r| parse_resolver(r)).collect::<Result<_>>()?
+        };
+
+        Ok(Self {
+            provider,
+            delay_before_check: config.delay_before_check.map(|d| d.as_std()).unwrap_or_default(),
+            resolvers,
+            check_propagation: !config.disable_propagation_check,
+            poll_interval: Duration::from_secs(5),
+            propagation_timeout: Duration::from_secs(120),
+        })
+    }
+
+    /// Solve the challenge for `domain`. `validate` tells the CA the record is
+    /// ready and waits for the outcome; the record is cleaned up either way.
+    pub async fn solve<F, Fut>(&self, domain: &str, key_authorization: &str, validate: F) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let fqdn = challenge_record_name(domain);
+        let value = dns01_txt_value(key_authorization);
+
+        self.provider
+            .create_txt_record(&fqdn, &value)
+            .await
+            .with_context(|| format!("Failed to create TXT record {}", fqdn))?;
+        info!("DNS-01 challenge record created at {}", fqdn);
+
+        let result = async {
+            if !self.delay_before_check.is_zero() {
+                tokio::time::sleep(self.delay_before_check).await;
+            }
+            if self.check_propagation {
+                self.wait_for_propagation(&fqdn, &value).await?;
+            }
+            validate().await
+        }
+        .await;
+
+        if let Err(e) = self.provider.delete_txt_record(&fqdn, &value).await {
+            warn!("Failed to clean up TXT record {}: {}", fqdn, e);
+        }
+
+        result
+    }
+
+    /// Poll every resolver until all of them return the expected TXT value.
+    async fn wait_for_propagation(&self, fqdn: &str, value: &str) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + self.propagation_timeout;
+
+        loop {
+            let mut all_visible = true;
+            for resolver in &self.resolvers {
+                match lookup_txt(*resolver, fqdn).await {
+                    Ok(records) if records.iter().any(|r| r == value) => {}
+                    Ok(_) => {
+                        debug!("TXT record {} not yet visible at {}", fqdn, resolver);
+                        all_visible = false;
+                    }
+                    Err(e) => {
+                        debug!("TXT lookup for {} at {} failed: {}", fqdn, resolver, e);
+                        all_visible = false;
+                    }
+                }
+            }
+
+            if all_visible {
+                debug!("TXT record {} propagated to all resolvers", fqdn);
+                return Ok(());
+            }
+            if tokio::time::Instant::now() + self.poll_interval > deadline {
+                return Err(anyhow::anyhow!("Timed out waiting for TXT record {} to propagate", fqdn));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+/// Accept "1.1.1.1:53", "1.1.1.1", or "[2606:4700::1111]:53"
+fn parse_resolver(resolver: &str) -> Result<SocketAddr> {
+    resolver
+        .parse::<SocketAddr>()
+        .or_else(|_| resolver.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .with_context(|| format!("Invalid DNS resolver address: {}", resolver))
+}
+
+/// Query `resolver` for TXT records at `name` over UDP.
+async fn lookup_txt(resolver: SocketAddr, name: &str) -> Result<Vec<String>> {
+    let bind: SocketAddr = if resolver.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse()?;
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(resolver).await?;
+
+    let id: u16 = rand_id();
+    socket.send(&build_txt_query(id, name)?).await?;
+
+    let mut buf = [0u8; 4096];
+    let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+        .await
+        .context("DNS query timed out")??;
+
+    parse_txt_response(id, &buf[..len])
+}
+
+fn rand_id() -> u16 {
+    let mut id = [0u8; 2];
+    let _ = ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut id);
+    u16::from_be_bytes(id)
+}
+
+fn build_txt_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(18 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00]); // recursion desired
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // 1 question
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow::anyhow!("Invalid DNS name: {}", name));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&[0, 16, 0, 1]); // QTYPE=TXT, QCLASS=IN
+    Ok(packet)
+}
+
+fn parse_txt_response(id: u16, packet: &[u8]) -> Result<Vec<String>> {
+    let invalid = || anyhow::anyhow!("Malformed DNS response");
+
+    if packet.len() < 12 || u16::from_be_bytes([packet[0], packet[1]]) != id {
+        return Err(invalid());
+    }
+    let rcode = packet[3] & 0x0f;
+    // NXDOMAIN just means the record isn't there yet
+    if rcode == 3 {
+        return Ok(Vec::new());
+    }
+    if rcode != 0 {
+        return Err(anyhow::anyhow!("DNS server returned rcode {}", rcode));
+    }
+
+    let qdcount = u16::from_be_bytes([packet[4], packet[5]]);
+    let ancount = u16::from_be_bytes([packet[6], packet[7]]);
+    let mut pos = 12;
+
+    for _ in 0..qdcount {
+        pos = skip_name(packet, pos).ok_or_else(invalid)? + 4;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..ancount {
+        pos = skip_name(packet, pos).ok_or_else(invalid)?;
+        let header = packet.get(pos..pos + 10).ok_or_else(invalid)?;
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+        pos += 10;
+        let rdata = packet.get(pos..pos + rdlength).ok_or_else(invalid)?;
+        pos += rdlength;
+
+        if rtype != 16 {
+            continue;
+        }
+        // A TXT record is one or more <len><bytes> strings, concatenated
+        let mut value = Vec::new();
+        let mut i = 0;
+        while i < rdata.len() {
+            let len = rdata[i] as usize;
+            value.extend_from_slice(rdata.get(i + 1..i + 1 + len).ok_or_else(invalid)?);
+            i += 1 + len;
+        }
+        records.push(String::from_utf8_lossy(&value).into_owned());
+    }
+
+    Ok(records)
+}
+
+/// Return the offset just past a (possibly compressed) name starting at `pos`
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            l if l & 0xc0 == 0xc0 => return Some(pos + 2),
+            l => pos += 1 + l as usize,
+        }
+    }
+}
+
+/// Cloudflare DNS provider authenticated with a scoped API token
+/// (`CF_DNS_API_TOKEN` or `CLOUDFLARE_DNS_API_TOKEN`)
+pub struct CloudflareProvider {
+    client: reqwest::Client,
+    api_token: String,
+    api_base: String,
+}
+
+#[derive(Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct CloudflareId {
+    id: String,
+}
+
+impl CloudflareProvider {
+    /// Create a provider with an explicit API token.
+    pub fn new(api_token: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            api_token,
+            api_base: "https://api.cloudflare.com/client/v4".to_string(),
+        }
+    }
+
+    /// Create a provider from the `CF_DNS_API_TOKEN` / `CLOUDFLARE_DNS_API_TOKEN` env vars.
+    pub fn from_env() -> Result<Self> {
+        let token = std::env::var("CF_DNS_API_TOKEN")
+            .or_else(|_| std::env::var("CLOUDFLARE_DNS_API_TOKEN"))
+            .context("Cloudflare DNS challenge requires CF_DNS_API_TOKEN to be set")?;
+        Ok(Self::new(token))
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T> {
+        let url = url::Url::parse_with_params(&format!("{}{}", self.api_base, path), query)?;
+        let response: CloudflareResponse<T> = self
+            .client
+            .get(url)
+            .bearer_auth(&self.api_token)
+            .send()
+            .await?
+            .json()
+            .await?;
+        cloudflare_result(response)
+    }
+
+    /// Find the zone that owns `fqdn` by walking up its labels.
+    async fn zone_id(&self, fqdn: &str) -> Result<String> {
+        let mut candidate = fqdn.trim_end_matches('.');
+        while let Some((_, parent)) = candidate.split_once('.') {
+            candidate = parent;
+            let zones: Vec<CloudflareId> = self.get("/zones", &[("name", candidate)]).await?;
+            if let Some(zone) = zones.into_iter().next() {
+                return Ok(zone.id);
+            }
+        }
+        Err(anyhow::anyhow!("No Cloudflare zone found for {}", fqdn))
+    }
+}
+
+fn cloudflare_result<T>(response: CloudflareResponse<T>) -> Result<T> {
+    match response.result {
+        Some(result) if response.success => Ok(result),
+        _ => Err(anyhow::anyhow!("Cloudflare API error: {:?}", response.errors)),
+    }
+}
+
+#[async_trait]
+impl DnsProvider for CloudflareProvider {
+    async fn create_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+        let zone = self.zone_id(fqdn).await?;
+        let response: CloudflareResponse<CloudflareId> = self
+            .client
+            .post(format!("{}/zones/{}/dns_records", self.api_base, zone))
+            .bearer_auth(&self.api_token)
+            .json(&serde_json::json!({
+                "type": "TXT",
+                "name": fqdn,
+                "content": value,
+                "ttl": 120,
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        cloudflare_result(response)?;
+        Ok(())
+    }
+
+    async fn delete_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+        let zone = self.zone_id(fqdn).await?;
+        let records: Vec<CloudflareId> = self
+            .get(
+                &format!("/zones/{}/dns_records", zone),
+                &[("type", "TXT"), ("name", fqdn), ("content", value)],
+            )
+            .await?;
+
+        for record in records {
+            self.client
+                .delete(format!("{}/zones/{}/dns_records/{}", self.api_base, zone, record.id))
+                .bearer_auth(&self.api_token)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Records every call and publishes created records to a shared zone
+    #[derive(Default)]
+    struct MockProvider {
+        calls: Mutex<Vec<String>>,
+        zone: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    #[async_trait]
+    impl DnsProvider for MockProvider {
+        async fn create_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+            self.calls.lock().push(format!("create {}", fqdn));
+            self.zone.lock().push((fqdn.to_string(), value.to_string()));
+            Ok(())
+        }
+
+        async fn delete_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+            self.calls.lock().push(format!("delete {}", fqdn));
+            self.zone.lock().retain(|(n, v)| !(n == fqdn && v == value));
+            Ok(())
+        }
+    }
+
+    /// Authoritative-ish UDP DNS server answering TXT queries from `zone`,
+    /// but only after `hide_for` queries so the solver has to poll
+    async fn spawn_dns_server(
+        zone: Arc<Mutex<Vec<(String, String)>>>,
+        hide_for: usize,
+    ) -> (SocketAddr, Arc<AtomicUsize>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&queries);
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let query = &buf[..len];
+                let question_end = skip_name(query, 12).unwrap() + 4;
+
+                // Decode the queried name
+                let mut labels = Vec::new();
+                let mut pos = 12;
+                while query[pos] != 0 {
+                    let l = query[pos] as usize;
+                    labels.push(String::from_utf8_lossy(&query[pos + 1..pos + 1 + l]).into_owned());
+                    pos += 1 + l;
+                }
+                let name = labels.join(".");
+
+                let visible = counter.fetch_add(1, Ordering::SeqCst) >= hide_for;
+                let answers: Vec<String> = if visible {
+                    zone.lock().iter().filter(|(n, _)| *n == name).map(|(_, v)| v.clone()).collect()
+                } else {
+                    Vec::new()
+                };
+
+                let mut resp = query[..question_end].to_vec();
+                resp[2] = 0x81;
+                resp[3] = 0x80;
+                resp[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+                for value in answers {
+                    resp.extend_from_slice(&[0xc0, 0x0c, 0, 16, 0, 1, 0, 0, 0, 60]);
+                    resp.extend_from_slice(&((value.len() + 1) as u16).to_be_bytes());
+                    resp.push(value.len() as u8);
+                    resp.extend_from_slice(value.as_bytes());
+                }
+                let _ = socket.send_to(&resp, peer).await;
+            }
+        });
+
+        (addr, queries)
+    }
+
+    fn test_solver(provider: Arc<MockProvider>, resolver: SocketAddr) -> Dns01Solver {
+        let config = DnsChallenge {
+            provider: "mock".to_string(),
+            delay_before_check: Some(crate::config::Duration::from_millis(10)),
+            resolvers: vec![resolver.to_string()],
+            disable_propagation_check: false,
+        };
+        let mut solver = Dns01Solver::new(&config, provider).unwrap();
+        solver.poll_interval = Duration::from_millis(10);
+        solver.propagation_timeout = Duration::from_secs(5);
+        solver
+    }
+
+    #[test]
+    fn test_dns01_txt_value() {
+        // RFC 8555 §8.4: base64url(SHA-256(key authorization)), unpadded
+        assert_eq!(
+            dns01_txt_value("token.thumbprint"),
+            URL_SAFE_NO_PAD.encode(ring::digest::digest(&ring::digest::SHA256, b"token.thumbprint"))
+        );
+        assert_eq!(dns01_txt_value("").len(), 43);
+    }
+
+    #[test]
+    fn test_challenge_record_name() {
+        assert_eq!(challenge_record_name("example.com"), "_acme-challenge.example.com");
+        assert_eq!(challenge_record_name("*.example.com"), "_acme-challenge.example.com");
+        assert_eq!(challenge_record_name("www.example.com."), "_acme-challenge.www.example.com");
+    }
+
+    #[test]
+    fn test_parse_resolver() {
+        assert_eq!(parse_resolver("1.1.1.1").unwrap(), "1.1.1.1:53".parse().unwrap());
+        assert_eq!(parse_resolver("9.9.9.9:5353").unwrap(), "9.9.9.9:5353".parse().unwrap());
+        assert!(parse_resolver("dns.example").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_solve_polls_until_propagated_then_cleans_up() {
+        let provider = Arc::new(MockProvider::default());
+        let (resolver, queries) = spawn_dns_server(Arc::clone(&provider.zone), 3).await;
+        let solver = test_solver(Arc::clone(&provider), resolver);
+
+        let zone = Arc::clone(&provider.zone);
+        let result = solver
+            .solve("*.example.com", "token.thumbprint", || async move {
+                // The CA sees the record by the time we signal readiness
+                assert_eq!(zone.lock().len(), 1);
+                Ok(())
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert!(queries.load(Ordering::SeqCst) >= 4, "propagation should have been polled");
+        assert_eq!(
+            *provider.calls.lock(),
+            vec!["create _acme-challenge.example.com", "delete _acme-challenge.example.com"]
+        );
+        assert!(provider.zone.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_solve_cleans_up_on_validation_failure() {
+        let provider = Arc::new(MockProvider::default());
+        let (resolver, _queries) = spawn_dns_server(Arc::clone(&provider.zone), 0).await;
+        let solver = test_solver(Arc::clone(&provider), resolver);
+
+        let result = solver
+            .solve("example.com", "token.thumbprint", || async { Err(anyhow::anyhow!("Challenge failed")) })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(provider.calls.lock().len(), 2);
+        assert!(provider.zone.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_solve_cleans_up_on_propagation_timeout() {
+        let provider = Arc::new(MockProvider::default());
+        let (resolver, _queries) = spawn_dns_server(Arc::clone(&provider.zone), usize::MAX).await;
+        let mut solver = test_solver(Arc::clone(&provider), resolver);
+        solver.propagation_timeout = Duration::from_millis(100);
+
+        let validated = Arc::new(AtomicUsize::new(0));
+        let v = Arc::clone(&validated);
+        let result = solver
+            .solve("example.com", "token.thumbprint", || async move {
+                v.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+
+        assert!(result.unwrap_err().to_string().contains("propagate"));
+        assert_eq!(validated.load(Ordering::SeqCst), 0, "CA must not be asked to validate");
+        assert_eq!(*provider.calls.lock().last().unwrap(), "delete _acme-challenge.example.com");
+    }
+}
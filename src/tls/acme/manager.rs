@@ -1,5 +1,9 @@
 use super::client::AcmeClient;
-use super::storage::StorageManager;
+use super::dns::{provider_from_config, Dns01Solver};
+use super::renewal::RenewalScheduler;
+use super::storage::{StorageManager, DEFAULT_RENEW_BEFORE};
+use crate::config::DnsChallenge;
+use crate::store::Store;
 use crate::tls::CertificateResolver;
 use anyhow::Result;
 use std::collections::HashMap;
@@ -14,7 +18,8 @@ pub struct AcmeManager {
     client: Arc<RwLock<AcmeClient>>,
     resolver: Arc<CertificateResolver>,
     pending_challenges: Arc<RwLock<HashMap<String, super::client::PendingChallenge>>>,
-    renewal_interval: Duration,
+    renew_before: Duration,
+    store: Option<Arc<dyn Store>>,
 }
 
 impl AcmeManager {
@@ -41,7 +46,8 @@ impl AcmeManager {
             client: Arc::new(RwLock::new(client)),
             resolver: Arc::new(resolver),
             pending_challenges,
-            renewal_interval: Duration::from_secs(12 * 60 * 60), // Check every 12 hours
+            renew_before: DEFAULT_RENEW_BEFORE,
+            store: None,
         })
     }
 
@@ -57,6 +63,11 @@ impl AcmeManager {
         Arc::clone(&self.pending_challenges)
     }
 
+    /// The store HTTP-01 challenges are published to, if one was configured
+    pub fn store(&self) -> Option<Arc<dyn Store>> {
+        self.store.clone()
+    }
+
     /// Order and store a new certificate for the given domains.
     pub async fn obtain_certificate(&self, domains: &[String]) -> Result<()> {
         info!("Requesting certificate for domains: {:?}", domains);
@@ -81,7 +92,7 @@ impl AcmeManager {
 
             // Check if we already have a valid certificate
             if let Some(cert) = self.storage.get_certificate(primary) {
-                if !cert.needs_renewal() {
+                if !cert.needs_renewal_within(self.renew_before) {
                     info!("Certificate for {} is valid, skipping", primary);
                     continue;
                 }
@@ -97,33 +108,18 @@ impl AcmeManager {
         Ok(())
     }
 
-    /// Spawn a background task that periodically checks and renews expiring certificates.
+    /// Spawn a background task that renews certificates as they approach
+    /// expiry (see [`RenewalScheduler`]).
     pub fn start_renewal_task(self: Arc<Self>) {
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(self.renewal_interval).await;
-
-                info!("Checking certificates for renewal...");
-
-                let certs_needing_renewal = self.storage.get_certificates_needing_renewal();
-
-                for cert in certs_needing_renewal {
-                    info!(
-                        "Renewing certificate for {} (expires at {})",
-                        cert.domain, cert.not_after
-                    );
-
-                    match self.obtain_certificate(&cert.domains).await {
-                        Ok(_) => {
-                            info!("Successfully renewed certificate for {}", cert.domain);
-                        }
-                        Err(e) => {
-                            error!("Failed to renew certificate for {}: {}", cert.domain, e);
-                        }
-                    }
-                }
-            }
-        });
+        let scheduler = RenewalScheduler::new(
+            Arc::clone(&self.storage),
+            Arc::clone(&self.resolver),
+            self.renew_before,
+        );
+        tokio::spawn(scheduler.run(move |domains| {
+            let manager = Arc::clone(&self);
+            async move { manager.obtain_certificate(&domains).await }
+        }));
     }
 }
 
@@ -133,6 +129,9 @@ pub struct AcmeManagerBuilder {
     email: String,
     ca_server: Option<String>,
     domains: Vec<Vec<String>>,
+    dns_challenge: Option<DnsChallenge>,
+    store: Option<Arc<dyn Store>>,
+    renew_before: Duration,
 }
 
 impl AcmeManagerBuilder {
@@ -143,6 +142,9 @@ impl AcmeManagerBuilder {
             email: email.to_string(),
             ca_server: None,
             domains: Vec::new(),
+            dns_challenge: None,
+            store: None,
+            renew_before: DEFAULT_RENEW_BEFORE,
         }
     }
 
@@ -172,15 +174,46 @@ impl AcmeManagerBuilder {
         self
     }
 
+    /// Solve challenges via DNS-01 using the configured provider.
+    pub fn dns_challenge(mut self, config: DnsChallenge) -> Self {
+        self.dns_challenge = Some(config);
+        self
+    }
+
+    /// Share HTTP-01 challenges through a cluster store so any node can answer them.
+    pub fn store(mut self, store: Arc<dyn Store>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Renew certificates this long before they expire (default 30 days).
+    pub fn renew_before(mut self, renew_before: Duration) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
     /// Build, initialize, and start the ACME manager with certificate renewal.
     pub async fn build(self) -> Result<Arc<AcmeManager>> {
-        let manager = AcmeManager::new(
+        let mut manager = AcmeManager::new(
             &self.storage_path,
             &self.email,
             self.ca_server.as_deref(),
         )
         .await?;
 
+        if let Some(dns_config) = &self.dns_challenge {
+            let provider = provider_from_config(dns_config)?;
+            let solver = Dns01Solver::new(dns_config, provider)?;
+            manager.client.write().await.set_dns_solver(Arc::new(solver));
+            info!("ACME DNS-01 challenge enabled (provider: {})", dns_config.provider);
+        }
+
+        manager.renew_before = self.renew_before;
+        if let Some(store) = self.store {
+            manager.client.write().await.set_store(Arc::clone(&store));
+            manager.store = Some(store);
+        }
+
         let manager = Arc::new(manager);
 
         // Ensure certificates for all domains
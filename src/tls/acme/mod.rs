@@ -2,13 +2,18 @@
 
 mod challenge;
 mod client;
+mod dns;
 mod manager;
+mod renewal;
 mod storage;
 
 /// HTTP-01 challenge handler and standalone request matcher.
 pub use challenge::{try_handle_challenge, ChallengeHandler};
 /// ACME protocol client for account management and certificate ordering.
 pub use client::{AcmeClient, PendingChallenge};
+pub(crate) use client::format_rfc3339;
+/// DNS-01 challenge solver and DNS provider integrations.
+pub use dns::{CloudflareProvider, Dns01Solver, DnsProvider};
 /// Certificate lifecycle manager with automatic renewal.
 pub use manager::{AcmeManager, AcmeManagerBuilder};
 /// Persistent storage for ACME accounts and certificates.
@@ -7,7 +7,7 @@ use std::fs::{self, File};
 use std::io::{BufReader, BufWriter};
 use std::path::Path;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info};
 
 /// Storage for ACME account and certificates
@@ -58,17 +58,22 @@ pub struct StoredCertificate {
     pub not_before: u64,
 }
 
+/// Default window before expiry in which certificates are renewed
+pub const DEFAULT_RENEW_BEFORE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
 impl StoredCertificate {
     /// Returns true if the certificate expires within 30 days.
     pub fn needs_renewal(&self) -> bool {
+        self.needs_renewal_within(DEFAULT_RENEW_BEFORE)
+    }
+
+    /// Returns true if the certificate expires within `renew_before`.
+    pub fn needs_renewal_within(&self, renew_before: Duration) -> bool {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
-
-        // Renew if less than 30 days until expiry
-        let thirty_days = 30 * 24 * 60 * 60;
-        self.not_after.saturating_sub(thirty_days) < now
+        self.not_after.saturating_sub(renew_before.as_secs()) < now
     }
 
     /// Returns true if the certificate's not-after date has passed.
@@ -205,12 +210,12 @@ impl StorageManager {
         self.save()
     }
 
-    /// Get all certificates that need renewal
-    pub fn get_certificates_needing_renewal(&self) -> Vec<StoredCertificate> {
+    /// Get all certificates expiring within `renew_before`
+    pub fn get_certificates_needing_renewal(&self, renew_before: Duration) -> Vec<StoredCertificate> {
         let data = self.data.read();
         data.certificates
             .values()
-            .filter(|c| c.needs_renewal())
+            .filter(|c| c.needs_renewal_within(renew_before))
             .cloned()
             .collect()
     }
@@ -4,7 +4,9 @@
 pub mod acme;
 /// Mutual TLS client certificate authentication.
 pub mod mtls;
+mod options;
 mod resolver;
+mod watcher;
 
 /// Re-exports from the ACME submodule for certificate automation.
 pub use acme::{
@@ -14,9 +16,11 @@ pub use acme::{
 /// Re-exports for mutual TLS client authentication.
 pub use mtls::{ClientAuthMode, ClientCertInfo, MtlsConfigBuilder};
 /// SNI-based certificate resolver with static, ACME, and wildcard support.
-pub use resolver::CertificateResolver;
+pub use resolver::{CertificateResolver, StrictSniResolver};
+/// Reloads static certificates when their files change on disk.
+pub use watcher::watch_certificates;
 
-use crate::config::TlsConfig;
+use crate::config::{TlsConfig, TlsOptions};
 use anyhow::{Context, Result};
 use rustls::pki_types::CertificateDer;
 use rustls::server::ResolvesServerCert;
@@ -39,7 +43,11 @@ impl TlsAcceptor {
         }
 
         let cert_config = &tls_config.certificates[0];
-        let config = Self::build_server_config(&cert_config.cert_file, &cert_config.key_file)?;
+        let config = Self::build_server_config(
+            &cert_config.cert_file,
+            &cert_config.key_file,
+            tls_config.options.get("default"),
+        )?;
 
         Ok(Some(Self {
             config: Arc::new(config),
@@ -49,7 +57,7 @@ impl TlsAcceptor {
 
     /// Build a TLS acceptor from certificate and key PEM files.
     pub fn from_files(cert_path: &str, key_path: &str) -> Result<Self> {
-        let config = Self::build_server_config(cert_path, key_path)?;
+        let config = Self::build_server_config(cert_path, key_path, None)?;
 
         Ok(Self {
             config: Arc::new(config),
@@ -58,19 +66,30 @@ impl TlsAcceptor {
 
     /// Create a TLS acceptor with SNI-based certificate resolution
     pub fn from_resolver(resolver: Arc<dyn ResolvesServerCert>) -> Result<Self> {
-        let mut config = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_cert_resolver(resolver);
+        Self::from_resolver_with_options(resolver, None)
+    }
+
+    /// Create a TLS acceptor with SNI-based certificate resolution, applying
+    /// client authentication from the given TLS options
+    pub fn from_resolver_with_options(
+        resolver: Arc<dyn ResolvesServerCert>,
+        options: Option<&TlsOptions>,
+    ) -> Result<Self> {
+        let mut config = Self::mtls_builder(options)?.build_with_resolver(resolver)?;
 
-        // Enable ALPN for HTTP/2 and HTTP/1.1
-        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        // ALPN from the TLS options, defaulting to HTTP/2 and HTTP/1.1
+        config.alpn_protocols = options::alpn_protocols(options);
 
         Ok(Self {
             config: Arc::new(config),
         })
     }
 
-    fn build_server_config(cert_path: &str, key_path: &str) -> Result<ServerConfig> {
+    fn build_server_config(
+        cert_path: &str,
+        key_path: &str,
+        options: Option<&TlsOptions>,
+    ) -> Result<ServerConfig> {
         let cert_file = File::open(cert_path)
             .with_context(|| format!("Failed to open cert file: {}", cert_path))?;
         let mut cert_reader = BufReader::new(cert_file);
@@ -85,17 +104,21 @@ impl TlsAcceptor {
             .context("Failed to parse private key")?
             .ok_or_else(|| anyhow::anyhow!("No private key found in file"))?;
 
-        let mut config = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, key)
-            .context("Failed to build TLS config")?;
+        let mut config = Self::mtls_builder(options)?.build_with_cert(certs, key)?;
 
-        // Enable ALPN for HTTP/2 and HTTP/1.1
-        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        // ALPN from the TLS options, defaulting to HTTP/2 and HTTP/1.1
+        config.alpn_protocols = options::alpn_protocols(options);
 
         Ok(config)
     }
 
+    fn mtls_builder(options: Option<&TlsOptions>) -> Result<MtlsConfigBuilder> {
+        match options {
+            Some(options) => MtlsConfigBuilder::from_tls_options(options),
+            None => Ok(MtlsConfigBuilder::new()),
+        }
+    }
+
     /// Get a clone of the underlying server config.
     pub fn get_config(&self) -> Arc<ServerConfig> {
         Arc::clone(&self.config)
@@ -0,0 +1,255 @@
+//! Translation of Traefik-style `TlsOptions` (Go names) into rustls settings.
+
+use crate::config::TlsOptions;
+use anyhow::{bail, Result};
+use rustls::crypto::CryptoProvider;
+use rustls::{CipherSuite, NamedGroup, SupportedProtocolVersion};
+use std::sync::Arc;
+
+/// Protocol versions allowed by `min_version`/`max_version`.
+/// rustls only implements TLS 1.2 and 1.3, so a minimum below 1.2 is treated as 1.2.
+pub(crate) fn protocol_versions(options: &TlsOptions) -> Result<Vec<&'static SupportedProtocolVersion>> {
+    let min = match options.min_version.as_deref() {
+        Some(name) => version_number(name)?,
+        None => 12,
+    };
+    let max = match options.max_version.as_deref() {
+        Some(name) => version_number(name)?,
+        None => 13,
+    };
+
+    let versions: Vec<_> = [(12, &rustls::version::TLS12), (13, &rustls::version::TLS13)]
+        .into_iter()
+        .filter(|(number, _)| (min..=max).contains(number))
+        .map(|(_, version)| version)
+        .collect();
+
+    if versions.is_empty() {
+        bail!(
+            "TLS version range {}..{} allows no supported version (TLS 1.2 or 1.3)",
+            options.min_version.as_deref().unwrap_or("VersionTLS12"),
+            options.max_version.as_deref().unwrap_or("VersionTLS13")
+        );
+    }
+
+    Ok(versions)
+}
+
+fn version_number(name: &str) -> Result<u8> {
+    Ok(match name {
+        "VersionTLS10" => 10,
+        "VersionTLS11" => 11,
+        "VersionTLS12" => 12,
+        "VersionTLS13" => 13,
+        _ => bail!("Unknown TLS version '{}'", name),
+    })
+}
+
+/// Crypto provider restricted to the configured cipher suites and curves, in the
+/// configured order. As in Go, TLS 1.3 suites stay enabled unless some are listed.
+pub(crate) fn crypto_provider(options: &TlsOptions) -> Result<Arc<CryptoProvider>> {
+    let mut provider = CryptoProvider::get_default()
+        .map(|provider| provider.as_ref().clone())
+        .unwrap_or_else(rustls::crypto::ring::default_provider);
+
+    if !options.cipher_suites.is_empty() {
+        let wanted = options
+            .cipher_suites
+            .iter()
+            .map(|name| cipher_suite(name))
+            .collect::<Result<Vec<_>>>()?;
+        let lists_tls13 = wanted.iter().any(|suite| is_tls13_suite(*suite));
+
+        let mut suites = Vec::new();
+        for suite in &wanted {
+            let Some(supported) = provider.cipher_suites.iter().find(|s| s.suite() == *suite) else {
+                bail!("Cipher suite '{:?}' is not supported by the crypto provider", suite);
+            };
+            suites.push(*supported);
+        }
+        if !lists_tls13 {
+            suites.extend(
+                provider
+                    .cipher_suites
+                    .iter()
+                    .filter(|s| is_tls13_suite(s.suite())),
+            );
+        }
+        provider.cipher_suites = suites;
+    }
+
+    if !options.curve_preferences.is_empty() {
+        let mut groups = Vec::new();
+        for name in &options.curve_preferences {
+            let group = named_group(name)?;
+            let Some(supported) = provider.kx_groups.iter().find(|g| g.name() == group) else {
+                bail!("Curve '{}' is not supported by the crypto provider", name);
+            };
+            groups.push(*supported);
+        }
+        provider.kx_groups = groups;
+    }
+
+    Ok(Arc::new(provider))
+}
+
+/// Map a Go cipher suite name (as used by Traefik) to its IANA identifier.
+/// Only AEAD suites are accepted; rustls does not implement CBC or RSA key exchange.
+fn cipher_suite(name: &str) -> Result<CipherSuite> {
+    Ok(match name {
+        "TLS_AES_128_GCM_SHA256" => CipherSuite::TLS13_AES_128_GCM_SHA256,
+        "TLS_AES_256_GCM_SHA384" => CipherSuite::TLS13_AES_256_GCM_SHA384,
+        "TLS_CHACHA20_POLY1305_SHA256" => CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+        "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256" => CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+        "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384" => CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+        "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256" => CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+        "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384" => CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+        "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305" | "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256" => {
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256
+        }
+        "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305" | "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256" => {
+            CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256
+        }
+        _ => bail!("Unknown or unsupported cipher suite '{}'", name),
+    })
+}
+
+fn is_tls13_suite(suite: CipherSuite) -> bool {
+    matches!(
+        suite,
+        CipherSuite::TLS13_AES_128_GCM_SHA256
+            | CipherSuite::TLS13_AES_256_GCM_SHA384
+            | CipherSuite::TLS13_CHACHA20_POLY1305_SHA256
+    )
+}
+
+/// Map a Go curve name to its TLS named group.
+fn named_group(name: &str) -> Result<NamedGroup> {
+    Ok(match name {
+        "X25519" => NamedGroup::X25519,
+        "CurveP256" | "secp256r1" => NamedGroup::secp256r1,
+        "CurveP384" | "secp384r1" => NamedGroup::secp384r1,
+        "CurveP521" | "secp521r1" => NamedGroup::secp521r1,
+        _ => bail!("Unknown curve '{}'", name),
+    })
+}
+
+/// ALPN protocols to advertise, defaulting to HTTP/2 with HTTP/1.1 fallback.
+pub(crate) fn alpn_protocols(options: Option<&TlsOptions>) -> Vec<Vec<u8>> {
+    match options {
+        Some(options) if !options.alpn_protocols.is_empty() => options
+            .alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect(),
+        _ => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> TlsOptions {
+        TlsOptions::default()
+    }
+
+    #[test]
+    fn test_protocol_versions() {
+        assert_eq!(protocol_versions(&options()).unwrap().len(), 2);
+
+        let tls13_only = TlsOptions {
+            min_version: Some("VersionTLS13".to_string()),
+            ..options()
+        };
+        let versions = protocol_versions(&tls13_only).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].version, rustls::ProtocolVersion::TLSv1_3);
+
+        // Legacy minimums are clamped to what rustls supports
+        let legacy_min = TlsOptions {
+            min_version: Some("VersionTLS10".to_string()),
+            max_version: Some("VersionTLS12".to_string()),
+            ..options()
+        };
+        let versions = protocol_versions(&legacy_min).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].version, rustls::ProtocolVersion::TLSv1_2);
+
+        let legacy_max = TlsOptions {
+            max_version: Some("VersionTLS11".to_string()),
+            ..options()
+        };
+        assert!(protocol_versions(&legacy_max).is_err());
+
+        let unknown = TlsOptions {
+            min_version: Some("TLS1.2".to_string()),
+            ..options()
+        };
+        assert!(protocol_versions(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_cipher_suites_filter_and_order() {
+        let opts = TlsOptions {
+            cipher_suites: vec![
+                "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384".to_string(),
+                "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256".to_string(),
+            ],
+            ..options()
+        };
+        let provider = crypto_provider(&opts).unwrap();
+        let suites: Vec<_> = provider.cipher_suites.iter().map(|s| s.suite()).collect();
+
+        assert_eq!(suites[0], CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384);
+        assert_eq!(suites[1], CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256);
+        // TLS 1.3 suites are kept because none were listed
+        assert!(suites[2..].iter().all(|s| is_tls13_suite(*s)));
+        assert_eq!(suites.len(), 5);
+
+        let tls13 = TlsOptions {
+            cipher_suites: vec!["TLS_AES_256_GCM_SHA384".to_string()],
+            ..options()
+        };
+        let provider = crypto_provider(&tls13).unwrap();
+        assert_eq!(provider.cipher_suites.len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_cipher_suite_errors() {
+        let opts = TlsOptions {
+            cipher_suites: vec!["TLS_RSA_WITH_AES_128_CBC_SHA".to_string()],
+            ..options()
+        };
+        let err = crypto_provider(&opts).unwrap_err().to_string();
+        assert!(err.contains("TLS_RSA_WITH_AES_128_CBC_SHA"));
+    }
+
+    #[test]
+    fn test_curve_preferences() {
+        let opts = TlsOptions {
+            curve_preferences: vec!["CurveP384".to_string(), "X25519".to_string()],
+            ..options()
+        };
+        let provider = crypto_provider(&opts).unwrap();
+        let groups: Vec<_> = provider.kx_groups.iter().map(|g| g.name()).collect();
+        assert_eq!(groups, vec![NamedGroup::secp384r1, NamedGroup::X25519]);
+
+        let bad = TlsOptions {
+            curve_preferences: vec!["Curve25519".to_string()],
+            ..options()
+        };
+        assert!(crypto_provider(&bad).is_err());
+    }
+
+    #[test]
+    fn test_alpn_protocols() {
+        assert_eq!(alpn_protocols(None), vec![b"h2".to_vec(), b"http/1.1".to_vec()]);
+
+        let opts = TlsOptions {
+            alpn_protocols: vec!["http/1.1".to_string()],
+            ..options()
+        };
+        assert_eq!(alpn_protocols(Some(&opts)), vec![b"http/1.1".to_vec()]);
+    }
+}
@@ -1,7 +1,5 @@
 use crate::udp::{UdpRouter, UdpServiceManager};
 use dashmap::DashMap;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -13,9 +11,6 @@ use tracing::{debug, error, info, warn};
 /// Maximum datagram size (64KB - typical max UDP payload)
 const MAX_DATAGRAM_SIZE: usize = 65535;
 
-/// Default session timeout (how long to keep session mappings)
-const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
-
 /// How often to clean up expired sessions
 const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
 
@@ -25,8 +20,6 @@ pub struct UdpProxy {
     services: Arc<UdpServiceManager>,
     /// Session tracking: maps client addr -> backend info
     sessions: Arc<DashMap<SocketAddr, UdpSession>>,
-    /// Session timeout
-    session_timeout: Duration,
     /// Metrics
     packets_received: AtomicU64,
     packets_sent: AtomicU64,
@@ -38,11 +31,12 @@ pub struct UdpProxy {
 struct UdpSession {
     /// Backend server address
     backend_addr: SocketAddr,
-    /// Service name for this session (for metrics/logging)
-    #[allow(dead_code)]
+    /// Service name for this session
     service_name: String,
     /// Last activity time
     last_activity: Instant,
+    /// Idle time after which the session is dropped (from the service config)
+    timeout: Duration,
     /// Socket bound to ephemeral port for this session (for receiving responses)
     backend_socket: Arc<UdpSocket>,
 }
@@ -54,7 +48,6 @@ impl UdpProxy {
             router,
             services,
             sessions: Arc::new(DashMap::new()),
-            session_timeout: DEFAULT_SESSION_TIMEOUT,
             packets_received: AtomicU64::new(0),
             packets_sent: AtomicU64::new(0),
             bytes_received: AtomicU64::new(0),
@@ -62,12 +55,6 @@ impl UdpProxy {
         }
     }
 
-    /// Create with custom session timeout
-    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
-        self.session_timeout = timeout;
-        self
-    }
-
     /// Run the UDP proxy for an entrypoint
     pub async fn run(
         self: Arc<Self>,
@@ -85,11 +72,12 @@ impl UdpProxy {
 
         // Start session cleanup task
         let sessions_cleanup = Arc::clone(&self.sessions);
-        let session_timeout = self.session_timeout;
+        let services = Arc::clone(&self.services);
         let cleanup_handle = tokio::spawn(async move {
             loop {
                 tokio::time::sleep(SESSION_CLEANUP_INTERVAL).await;
-                Self::cleanup_expired_sessions(&sessions_cleanup, session_timeout);
+                Self::cleanup_expired_sessions(&sessions_cleanup);
+                services.sweep_expired_sessions();
             }
         });
 
@@ -151,8 +139,13 @@ impl UdpProxy {
             // Forward to existing backend
             let backend_socket = Arc::clone(&session.backend_socket);
             let backend_addr = session.backend_addr;
+            let service_name = session.service_name.clone();
             drop(session); // Release lock
 
+            if let Some(service) = self.services.get_service(&service_name) {
+                service.touch_client(client_addr);
+            }
+
             self.forward_to_backend(
                 client_addr,
                 &backend_socket,
@@ -185,9 +178,8 @@ impl UdpProxy {
             }
         };
 
-        // Use consistent hashing based on client IP for session affinity
-        let hash = Self::hash_addr(&client_addr);
-        let backend = match service.get_server_by_hash(hash) {
+        // Keep the client on the backend it was pinned to, if any
+        let backend = match service.server_for_client(client_addr) {
             Some(b) => b,
             None => {
                 error!("UDP: No healthy backends for service '{}'", route.service);
@@ -217,6 +209,7 @@ impl UdpProxy {
             backend_addr,
             service_name: route.service.clone(),
             last_activity: Instant::now(),
+            timeout: service.session_timeout(),
             backend_socket: Arc::clone(&backend_socket),
         };
         self.sessions.insert(client_addr, session);
@@ -233,7 +226,7 @@ impl UdpProxy {
         // Spawn task to listen for backend responses
         let proxy_metrics = self.clone_metrics();
         let sessions = Arc::clone(&self.sessions);
-        let session_timeout = self.session_timeout;
+        let session_timeout = service.session_timeout();
 
         tokio::spawn(async move {
             Self::listen_for_responses(
@@ -349,15 +342,12 @@ impl UdpProxy {
     }
 
     /// Clean up expired sessions
-    fn cleanup_expired_sessions(
-        sessions: &DashMap<SocketAddr, UdpSession>,
-        timeout: Duration,
-    ) {
+    fn cleanup_expired_sessions(sessions: &DashMap<SocketAddr, UdpSession>) {
         let now = Instant::now();
         let mut expired = Vec::new();
 
         for entry in sessions.iter() {
-            if now.duration_since(entry.last_activity) > timeout {
+            if now.duration_since(entry.last_activity) > entry.timeout {
                 expired.push(*entry.key());
             }
         }
@@ -368,13 +358,6 @@ impl UdpProxy {
         }
     }
 
-    /// Hash a socket address for consistent routing
-    fn hash_addr(addr: &SocketAddr) -> usize {
-        let mut hasher = DefaultHasher::new();
-        addr.ip().hash(&mut hasher);
-        hasher.finish() as usize
-    }
-
     /// Clone just the metrics counters for the response listener
     fn clone_metrics(&self) -> UdpProxyMetrics {
         UdpProxyMetrics {
@@ -415,20 +398,3 @@ pub struct UdpProxyStats {
     pub active_sessions: usize,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_hash_addr_consistency() {
-        let addr1: SocketAddr = "192.168.1.1:12345".parse().unwrap();
-        let addr2: SocketAddr = "192.168.1.1:54321".parse().unwrap();
-        let addr3: SocketAddr = "192.168.1.2:12345".parse().unwrap();
-
-        // Same IP should hash the same (we hash by IP only for session affinity)
-        assert_eq!(UdpProxy::hash_addr(&addr1), UdpProxy::hash_addr(&addr2));
-
-        // Different IPs should (probably) hash differently
-        assert_ne!(UdpProxy::hash_addr(&addr1), UdpProxy::hash_addr(&addr3));
-    }
-}
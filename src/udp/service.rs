@@ -1,16 +1,23 @@
 use crate::config::Config;
+use crate::metrics::Metrics;
+use dashmap::DashMap;
 use parking_lot::RwLock;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
+/// Default idle time before a client's backend affinity expires
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Manages UDP services and load balancing
 pub struct UdpServiceManager {
     services: HashMap<String, Arc<UdpService>>,
 }
 
-/// A UDP service with round-robin load balancing across backends.
+/// A UDP service with weighted round-robin load balancing across backends.
 pub struct UdpService {
     name: String,
     servers: Vec<UdpBackendServer>,
@@ -18,6 +25,16 @@ pub struct UdpService {
     rr_counter: AtomicUsize,
     /// Health status
     healthy: RwLock<Vec<bool>>,
+    /// Client address -> pinned backend
+    sessions: DashMap<SocketAddr, ClientAffinity>,
+    /// Idle time after which a client's affinity is dropped
+    session_timeout: Duration,
+}
+
+/// Backend a client is pinned to, and when the client was last seen
+struct ClientAffinity {
+    server: usize,
+    last_seen: Instant,
 }
 
 /// A UDP backend server
@@ -52,6 +69,11 @@ impl UdpServiceManager {
                     servers,
                     rr_counter: AtomicUsize::new(0),
                     healthy: RwLock::new(healthy),
+                    sessions: DashMap::new(),
+                    session_timeout: lb
+                        .session_timeout
+                        .map(|timeout| timeout.as_std())
+                        .unwrap_or(DEFAULT_SESSION_TIMEOUT),
                 };
 
                 services.insert(name.clone(), Arc::new(service));
@@ -71,55 +93,138 @@ impl UdpServiceManager {
     pub fn service_names(&self) -> impl Iterator<Item = &String> {
         self.services.keys()
     }
+
+    /// Drop idle client affinities across all services and update the
+    /// active session metric. Returns the number of sessions removed.
+    pub fn sweep_expired_sessions(&self) -> usize {
+        self.services
+            .values()
+            .map(|service| {
+                let removed = service.sweep_expired_sessions();
+                Metrics::global().record_udp_sessions(&service.name, service.active_sessions());
+                removed
+            })
+            .sum()
+    }
 }
 
 impl UdpService {
-    /// Get the next healthy backend server (round-robin)
+    /// Get the next healthy backend server (weighted round-robin)
     pub fn next_server(&self) -> Option<&UdpBackendServer> {
+        self.next_server_index().map(|idx| &self.servers[idx])
+    }
+
+    fn next_server_index(&self) -> Option<usize> {
         if self.servers.is_empty() {
             return None;
         }
 
         let healthy = self.healthy.read();
-        let healthy_count = healthy.iter().filter(|&&h| h).count();
+        let healthy_servers = || self.servers.iter().enumerate().filter(|&(idx, _)| healthy[idx]);
+
+        // When every healthy server has zero weight, treat them as equal
+        let total_weight: u64 = healthy_servers().map(|(_, s)| s.weight as u64).sum();
+        let weighted = total_weight > 0;
+        let total = if weighted {
+            total_weight
+        } else {
+            healthy_servers().count() as u64
+        };
 
-        if healthy_count == 0 {
+        if total == 0 {
             warn!("UDP service '{}': No healthy backends available", self.name);
             // Fall back to first server even if unhealthy
-            return self.servers.first();
+            return Some(0);
         }
 
-        // Round-robin through healthy servers
-        let mut attempts = 0;
-        loop {
-            let idx = self.rr_counter.fetch_add(1, Ordering::Relaxed) % self.servers.len();
-            if healthy[idx] {
-                return Some(&self.servers[idx]);
-            }
-            attempts += 1;
-            if attempts >= self.servers.len() {
-                // All servers checked, return first available
-                return self.servers.first();
+        // Each selection takes the next slot of a cycle in which every
+        // healthy server owns `weight` consecutive slots
+        let mut slot = self.rr_counter.fetch_add(1, Ordering::Relaxed) as u64 % total;
+        for (idx, server) in healthy_servers() {
+            let weight = if weighted { server.weight as u64 } else { 1 };
+            if slot < weight {
+                return Some(idx);
             }
+            slot -= weight;
         }
+        Some(0)
     }
 
     /// Get server by index (for consistent hashing based on source IP)
     pub fn get_server_by_hash(&self, hash: usize) -> Option<&UdpBackendServer> {
+        self.server_index_by_hash(hash).map(|idx| &self.servers[idx])
+    }
+
+    fn server_index_by_hash(&self, hash: usize) -> Option<usize> {
         if self.servers.is_empty() {
             return None;
         }
 
-        let healthy = self.healthy.read();
         let idx = hash % self.servers.len();
 
         // Try the hashed server first
-        if healthy[idx] {
-            return Some(&self.servers[idx]);
+        if self.healthy.read()[idx] {
+            return Some(idx);
         }
 
         // Fall back to round-robin if hashed server is unhealthy
-        self.next_server()
+        self.next_server_index()
+    }
+
+    /// Backend for a datagram from `client`. A client keeps the same backend
+    /// until it has been idle for the session timeout or that backend becomes
+    /// unhealthy; new clients are spread across backends by weighted round-robin.
+    pub fn server_for_client(&self, client: SocketAddr) -> Option<&UdpBackendServer> {
+        let now = Instant::now();
+
+        if let Some(mut affinity) = self.sessions.get_mut(&client)
+            && now.duration_since(affinity.last_seen) <= self.session_timeout
+            && self.healthy.read()[affinity.server]
+        {
+            affinity.last_seen = now;
+            return Some(&self.servers[affinity.server]);
+        }
+
+        let idx = self.next_server_index()?;
+        self.sessions.insert(
+            client,
+            ClientAffinity {
+                server: idx,
+                last_seen: now,
+            },
+        );
+        Some(&self.servers[idx])
+    }
+
+    /// Record activity from `client`, keeping its affinity alive
+    pub fn touch_client(&self, client: SocketAddr) {
+        if let Some(mut affinity) = self.sessions.get_mut(&client) {
+            affinity.last_seen = Instant::now();
+        }
+    }
+
+    /// Remove client affinities idle for longer than the session timeout.
+    /// Returns the number removed.
+    pub fn sweep_expired_sessions(&self) -> usize {
+        let now = Instant::now();
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|_, affinity| now.duration_since(affinity.last_seen) <= self.session_timeout);
+        let removed = before.saturating_sub(self.sessions.len());
+        if removed > 0 {
+            debug!("UDP service '{}': Expired {} idle sessions", self.name, removed);
+        }
+        removed
+    }
+
+    /// Number of clients currently pinned to a backend
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Idle time after which a client's affinity is dropped
+    pub fn session_timeout(&self) -> Duration {
+        self.session_timeout
     }
 
     /// Mark a server as unhealthy
@@ -186,6 +291,8 @@ mod tests {
                 },
             ],
             rr_counter: AtomicUsize::new(0),
+            sessions: DashMap::new(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
             healthy: RwLock::new(vec![true, true]),
         };
 
@@ -213,6 +320,8 @@ mod tests {
                 },
             ],
             rr_counter: AtomicUsize::new(0),
+            sessions: DashMap::new(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
             healthy: RwLock::new(vec![false, true]),
         };
 
@@ -242,6 +351,8 @@ mod tests {
                 },
             ],
             rr_counter: AtomicUsize::new(0),
+            sessions: DashMap::new(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
             healthy: RwLock::new(vec![true, true, true]),
         };
 
@@ -250,4 +361,115 @@ mod tests {
         let s2 = service.get_server_by_hash(42).unwrap();
         assert_eq!(s1.address, s2.address);
     }
+
+    fn service_with_timeout(session_timeout: Duration) -> UdpService {
+        UdpService {
+            name: "test".to_string(),
+            servers: (1..=3)
+                .map(|i| UdpBackendServer {
+                    address: format!("localhost:500{}", i),
+                    weight: 1,
+                })
+                .collect(),
+            rr_counter: AtomicUsize::new(0),
+            sessions: DashMap::new(),
+            session_timeout,
+            healthy: RwLock::new(vec![true, true, true]),
+        }
+    }
+
+    fn service_with_weights(weights: &[u32]) -> UdpService {
+        UdpService {
+            name: "test".to_string(),
+            servers: weights
+                .iter()
+                .enumerate()
+                .map(|(i, &weight)| UdpBackendServer {
+                    address: format!("localhost:500{}", i + 1),
+                    weight,
+                })
+                .collect(),
+            rr_counter: AtomicUsize::new(0),
+            sessions: DashMap::new(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            healthy: RwLock::new(vec![true; weights.len()]),
+        }
+    }
+
+    /// Clients per backend address, sending `datagrams` from each of `clients`
+    fn placements(service: &UdpService, clients: usize, datagrams: usize) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for port in 0..clients {
+            // Same IP, distinct source ports: each is its own flow
+            let client = SocketAddr::from(([10, 0, 0, 1], 40000 + port as u16));
+            let backend = service.server_for_client(client).unwrap().address.clone();
+            for _ in 1..datagrams {
+                assert_eq!(service.server_for_client(client).unwrap().address, backend);
+            }
+            *counts.entry(backend).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_clients_spread_across_backends() {
+        let service = service_with_weights(&[1, 1, 1]);
+        let counts = placements(&service, 9, 5);
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|&n| n == 3), "{:?}", counts);
+        assert_eq!(service.active_sessions(), 9);
+    }
+
+    #[test]
+    fn test_clients_placed_by_weight() {
+        let service = service_with_weights(&[3, 1, 0]);
+        let counts = placements(&service, 8, 3);
+        assert_eq!(counts["localhost:5001"], 6);
+        assert_eq!(counts["localhost:5002"], 2);
+        assert!(!counts.contains_key("localhost:5003"));
+
+        // Zero weights everywhere means equal shares
+        let service = service_with_weights(&[0, 0]);
+        let counts = placements(&service, 4, 1);
+        assert_eq!(counts["localhost:5001"], 2);
+        assert_eq!(counts["localhost:5002"], 2);
+    }
+
+    #[test]
+    fn test_client_affinity_across_datagrams() {
+        let service = service_with_timeout(Duration::from_secs(60));
+        let client: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+
+        let first = service.server_for_client(client).unwrap().address.clone();
+        for _ in 0..10 {
+            // Other clients arriving in between don't move this one
+            service.next_server();
+            assert_eq!(service.server_for_client(client).unwrap().address, first);
+        }
+        assert_eq!(service.active_sessions(), 1);
+
+        // An unhealthy backend releases its clients
+        let idx = service.servers.iter().position(|s| s.address == first).unwrap();
+        service.mark_unhealthy(idx);
+        assert_ne!(service.server_for_client(client).unwrap().address, first);
+    }
+
+    #[test]
+    fn test_sessions_expire_after_idle_timeout() {
+        let service = service_with_timeout(Duration::from_millis(200));
+        let idle: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+        let busy: SocketAddr = "10.0.0.2:40000".parse().unwrap();
+
+        service.server_for_client(idle);
+        service.server_for_client(busy);
+        assert_eq!(service.active_sessions(), 2);
+
+        std::thread::sleep(Duration::from_millis(150));
+        service.server_for_client(busy);
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert_eq!(service.sweep_expired_sessions(), 1);
+        assert_eq!(service.active_sessions(), 1);
+        assert!(service.sessions.contains_key(&busy));
+    }
 }